@@ -26,38 +26,153 @@
 extern crate yaml_rust;
 
 mod configparser;
+mod schema;
 
+use crate::shell::alias::{self, AliasError};
+use crate::shell::expansion::is_name;
+use crate::shell::prompt::{is_builtin_key, palette_references, recursive_macros, Palette, PromptColor, MACRO_MAX_DEPTH};
+use crate::translator::LocalizedError;
+use crate::utils::logger::LogLevel;
 use configparser::ConfigParser;
+use regex::Regex;
 use std::collections::HashMap;
 use std::fmt;
+use std::os::unix::io::RawFd;
 use yaml_rust::{Yaml, YamlLoader};
 
 use std::path::PathBuf;
 
+//Output tokens which are never translated by default: URLs, emails, absolute paths and hex digests
+pub const DEFAULT_NO_TRANSLATE_PATTERNS: [&str; 4] = [
+    r"^[a-zA-Z][a-zA-Z0-9+.-]*://",
+    r"^[\w.+-]+@[\w-]+(\.[\w-]+)+",
+    r#"^["'(\[<]?~?/[^\s/]"#,
+    r"^[0-9a-fA-F]{7,}$",
+];
+
 //Types
 #[derive(Clone)]
 pub struct Config {
-    pub language: String,
+    pub language: Option<String>, //None if not configured: the language is detected from the locale
+    pub language_standard: String, //Transliteration standard used for russian and ukrainian
+    pub language_strict: bool,     //Characters with no transliteration make the command fail, instead of passing through
+    pub banner: bool,              //Print the banner when an interactive session starts
+    pub assume_width: Option<usize>, //Terminal width used when neither the terminal nor the environment report it
+    pub core_config: CoreConfig,
     pub shell_config: ShellConfig,
+    pub terminal_config: TerminalConfig,
     pub alias: HashMap<String, String>,
+    pub alias_options: HashMap<String, AliasOptions>, //Working directory and environment of the aliases defined as stanzas
     pub output_config: OutputConfig,
+    pub input_config: InputConfig,
     pub prompt_config: PromptConfig,
+    pub unknown_keys: Vec<ConfigError>, //Keys of the configuration file which are not known (reported as warnings)
+    pub overrides: ConfigOverrides,     //Options set on the command line
+}
+
+/// ### ConfigOverrides
+///
+/// ConfigOverrides contains the options set on the command line, which take precedence over the configuration file for the whole session
+#[derive(Clone, Default)]
+pub struct ConfigOverrides {
+    pub translate_output: Option<bool>,
+    pub input_translation: Option<InputTranslation>,
+}
+
+#[derive(Clone, PartialEq, fmt::Debug)]
+pub struct AliasOptions {
+    pub cwd: Option<String>,
+    pub env: Vec<(String, String)>,
+}
+
+/// Aliases commands and the options of the aliases defined as stanzas
+type AliasTables = (HashMap<String, String>, HashMap<String, AliasOptions>);
+
+#[derive(Clone)]
+pub struct CoreConfig {
+    pub log_level: LogLevel,
+    pub log_file: Option<PathBuf>, //Log entries are written to this file; logging is disabled if not set (unless `--debug`)
+    pub rc_file: Option<PathBuf>, //Script run at interactive startup; disabled if None
+    pub sigint_escalation: SigintEscalation,
+    pub command_timeout: usize, //Seconds after which a command run with `-c` is terminated; 0 disables the timeout
+    pub use_pty: bool, //Run the shell and the jobs of interactive sessions under a pseudo-terminal
+}
+
+/// ### SigintEscalation
+///
+/// When Ctrl+C is pressed `presses` times within `window_ms` and the command is still running, it's terminated (SIGTERM);
+/// if it's still running after `kill_after_ms`, it's killed (SIGKILL). `presses` set to 0 disables the escalation
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub struct SigintEscalation {
+    pub presses: usize,
+    pub window_ms: usize,
+    pub kill_after_ms: usize,
+}
+
+#[derive(Clone, Default)]
+pub struct TerminalConfig {
+    pub clear_scrollback: bool, //Clearing the screen (Ctrl+L, `clear`) erases the scrollback too
 }
 
 #[derive(Clone)]
 pub struct ShellConfig {
-    pub exec: String,
-    pub args: Vec<String>
+    pub exec: Option<String>, //None: the shell is detected at startup
+    pub args: Vec<String>,
+    pub norc: bool //Start the shell without reading its rc files
 }
 
 #[derive(Clone)]
 pub struct OutputConfig {
-    pub translate_output: bool,
+    pub translate_stdout: bool,
+    pub translate_stderr: bool,
+    pub force_translate_pipe: bool, //Translate stdout even if it's not a terminal
+    pub translate_only: Vec<String>,
+    pub never_translate: Vec<String>,
+    pub localize_units: bool,
+    pub localize_dictionary: HashMap<String, String>,
+    pub error_hints: bool,
+    pub hints_dictionary: HashMap<String, String>,
+    pub no_translate_patterns: Vec<String>, //Output tokens matching one of these patterns are never translated
+    pub echo_command: bool,                 //Print the command line sent to the shell before running it
+    pub error_color: Option<String>,        //Color of the errors; the `error` color of the palette if None
+    pub error_prefix: String,               //Text prepended to each line printed to stderr
+}
+
+/// ### OutputTranslation
+///
+/// OutputTranslation tells whether the stdout and the stderr of the commands have to be translated
+#[derive(Copy, Clone, PartialEq, fmt::Debug)]
+pub struct OutputTranslation {
+    pub stdout: bool,
+    pub stderr: bool,
+}
+
+#[derive(Clone)]
+pub struct InputConfig {
+    pub preserve_redirect_targets: bool,
+    pub preserve_output_args: Vec<String>,
+    pub strict_boundaries: bool,
+    pub expand_variables: bool,
+    pub error_on_unset: bool,
+    pub nullglob: bool,
+    pub literal_delimiters: Option<(String, String)>, //Text between these delimiters is never translated; None if disabled
+    pub translation: InputTranslation, //Which typed input is transliterated to latin
+}
+
+/// ### InputTranslation
+///
+/// InputTranslation tells which input typed by the user is transliterated to latin
+#[derive(Copy, Clone, PartialEq, fmt::Debug)]
+pub enum InputTranslation {
+    Always,          //Both the command line and the input for the running commands
+    Never,           //Input is never transliterated
+    CommandLineOnly, //Only the command line typed at pyc's prompt; running commands get input as it has been typed
 }
 
 #[derive(Clone)]
 pub struct PromptConfig {
     pub prompt_line: String,
+    pub prompt_line_right: String, //Segment aligned to the right edge of the terminal (empty if not set)
     pub history_size: usize,
     pub translate: bool,
     pub break_enabled: bool,
@@ -65,10 +180,33 @@ pub struct PromptConfig {
     pub min_duration: usize,
     pub rc_ok: String,
     pub rc_err: String,
+    pub rc_code_always: bool,
+    pub prompt_char_user: String, //`PROMPT_CHAR` when the effective user is not root
+    pub prompt_char_root: String, //`PROMPT_CHAR` when the effective user is root
     pub git_branch: String,
     pub git_commit_ref: usize,
     pub git_commit_prepend: Option<String>,
-    pub git_commit_append: Option<String>
+    pub git_commit_append: Option<String>,
+    pub git_dirty: String,
+    pub git_staged: String,
+    pub git_cache_ttl: usize, //Seconds the repository of the working directory is kept open while the working directory doesn't change
+    pub time_format: String,
+    pub date_format: String,
+    pub duration_format: Option<String>,
+    pub duration_prefix: Option<String>, //Written before the duration (the phrase of the language is used if not set)
+    pub venv_prefix: String,
+    pub battery_charging: String,
+    pub battery_discharging: String,
+    pub battery_low_threshold: u8, //Charge (percentage) below which the battery is red
+    pub jobs_running: String, //`JOBS` symbol when all the jobs are running
+    pub jobs_stopped: String, //`JOBS` symbol when at least one job is suspended
+    pub wrkdir_max_depth: usize,    //Amount of components of the working directory shown (0 shows all of them)
+    pub wrkdir_truncate_repo: bool, //Show the working directory relative to the root of the repository
+    pub keep_trailing_space: bool,  //Don't trim the whitespace at the end of the prompt line
+    pub continuation: String,       //Prompt shown while a command continues on the next line
+    pub module_timeout: usize, //Milliseconds to wait for async modules (e.g. git) before rendering their cached value
+    pub prompt_macros: HashMap<String, String>, //User-defined keys (without `${}`) and the text they expand to
+    pub palette: HashMap<String, String>, //Styles of the semantic color names (`palette` section)
 }
 
 #[derive(Copy, Clone, PartialEq, fmt::Debug)]
@@ -76,8 +214,10 @@ pub enum ConfigErrorCode {
     NoSuchFileOrDirectory,
     CouldNotReadFile,
     YamlSyntaxError,
+    UnknownKey,
 }
 
+#[derive(Clone, fmt::Debug)]
 pub struct ConfigError {
     pub code: ConfigErrorCode,
     pub message: String,
@@ -89,6 +229,7 @@ impl fmt::Display for ConfigErrorCode {
             ConfigErrorCode::NoSuchFileOrDirectory => "NoSuchFileOrDirectory",
             ConfigErrorCode::CouldNotReadFile => "CouldNotReadFile",
             ConfigErrorCode::YamlSyntaxError => "YamlSyntaxError",
+            ConfigErrorCode::UnknownKey => "UnknownKey",
         };
         write!(f, "{}", code_str)
     }
@@ -100,6 +241,10 @@ impl fmt::Display for ConfigError {
     }
 }
 
+impl std::error::Error for ConfigError {}
+
+impl LocalizedError for ConfigError {}
+
 impl Config {
     /// ### default
     ///
@@ -107,11 +252,21 @@ impl Config {
     pub fn default() -> Config {
         let alias_config: HashMap<String, String> = HashMap::new();
         Config {
-            language: String::from("ru"),
+            language: None,
+            language_standard: String::from("gost"),
+            language_strict: false,
+            banner: true,
+            assume_width: None,
+            core_config: CoreConfig::default(),
             shell_config: ShellConfig::default(),
+            terminal_config: TerminalConfig::default(),
             alias: alias_config,
+            alias_options: HashMap::new(),
             output_config: OutputConfig::default(),
+            input_config: InputConfig::default(),
             prompt_config: PromptConfig::default(),
+            unknown_keys: Vec::new(),
+            overrides: ConfigOverrides::default(),
         }
     }
 
@@ -147,10 +302,10 @@ impl Config {
         //Parse YAML file
         let yaml_docs: Vec<Yaml> = match YamlLoader::load_from_str(config.as_str()) {
             Ok(doc) => doc,
-            Err(_) => {
+            Err(err) => {
                 return Err(ConfigError {
                     code: ConfigErrorCode::YamlSyntaxError,
-                    message: String::from("Configuration is not a valid YAML"),
+                    message: format!("Configuration is not a valid YAML: {}", err),
                 });
             }
         };
@@ -162,22 +317,67 @@ impl Config {
             });
         };
         let yaml_doc: &Yaml = &yaml_docs[0];
+        //Validate the structure: type mismatches are errors, unknown keys are warnings
+        let (unknown_keys, type_errors): (Vec<ConfigError>, Vec<ConfigError>) = schema::validate(config.as_str(), yaml_doc, &schema::CONFIG_SCHEMA)
+            .into_iter()
+            .partition(|issue| issue.code == ConfigErrorCode::UnknownKey);
+        if !type_errors.is_empty() {
+            return Err(ConfigError {
+                code: ConfigErrorCode::YamlSyntaxError,
+                message: type_errors.into_iter().map(|err| err.message).collect::<Vec<String>>().join("\n"),
+            });
+        }
+        match Config::parse_yaml(yaml_doc) {
+            Ok(mut config) => {
+                config.unknown_keys = unknown_keys;
+                Ok(config)
+            }
+            Err(mut err) => {
+                //A missing key may have been misspelled
+                for unknown_key in unknown_keys.into_iter() {
+                    err.message.push('\n');
+                    err.message.push_str(unknown_key.message.as_str());
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// ### parse_yaml
+    ///
+    /// Parse the configuration YAML document
+    fn parse_yaml(yaml_doc: &Yaml) -> Result<Config, ConfigError> {
         //Look for keys and get configuration parts
         //Get language
-        let language: String = match ConfigParser::get_child(&yaml_doc, String::from("language")) {
+        let (language, language_standard, language_strict): (Option<String>, String, bool) = match ConfigParser::get_child(yaml_doc, String::from("language")) {
             Ok(node) => match Config::parse_language(&node) {
                 Ok(l) => l,
                 Err(err) => return Err(err),
             },
-            Err(_) => String::from("ru"),
+            Err(_) => (None, String::from("gost"), false),
+        };
+        //Get banner
+        let banner: bool = match ConfigParser::get_child(yaml_doc, String::from("banner")) {
+            Ok(_) => ConfigParser::get_bool(yaml_doc, String::from("banner"))?,
+            Err(_) => true,
+        };
+        //Get assumed terminal width
+        let assume_width: Option<usize> = match ConfigParser::get_child(yaml_doc, String::from("assume_width")) {
+            Ok(_) => Some(ConfigParser::get_usize(yaml_doc, String::from("assume_width"))?),
+            Err(_) => None,
         };
         //Get alias
-        let alias_config: HashMap<String, String> = match ConfigParser::get_child(&yaml_doc, String::from("alias")) {
+        let (alias_config, alias_options): AliasTables =
+            match ConfigParser::get_child(yaml_doc, String::from("alias")) {
                 Ok(node) => match Config::parse_alias(&node) {
                     Ok(cfg) => cfg,
                     Err(err) => return Err(err),
                 },
-                Err(_) => HashMap::new(),
+                Err(_) => (HashMap::new(), HashMap::new()),
+            };
+        let core_config: CoreConfig = match ConfigParser::get_child(yaml_doc, String::from("core")) {
+            Ok(node) => CoreConfig::parse_config(node)?,
+            Err(_) => CoreConfig::default(),
         };
         let shell_config: ShellConfig = match ConfigParser::get_child(&yaml_doc, String::from("shell")) {
             Ok(node) => match ShellConfig::parse_config(&node) {
@@ -186,6 +386,10 @@ impl Config {
             },
             Err(_) => ShellConfig::default()
         };
+        let terminal_config: TerminalConfig = match ConfigParser::get_child(yaml_doc, String::from("terminal")) {
+            Ok(node) => TerminalConfig::parse_config(node)?,
+            Err(_) => TerminalConfig::default(),
+        };
         //Get output config
         let output_config: OutputConfig =
             match ConfigParser::get_child(&yaml_doc, String::from("output")) {
@@ -195,8 +399,14 @@ impl Config {
                 },
                 Err(_) => OutputConfig::default(),
             };
+        //Get input config
+        let input_config: InputConfig =
+            match ConfigParser::get_child(yaml_doc, String::from("input")) {
+                Ok(node) => InputConfig::parse_config(node)?,
+                Err(_) => InputConfig::default(),
+            };
         //Get prompt config
-        let prompt_config: PromptConfig =
+        let mut prompt_config: PromptConfig =
             match ConfigParser::get_child(&yaml_doc, String::from("prompt")) {
                 Ok(node) => match PromptConfig::parse_config(&node) {
                     Ok(config) => config,
@@ -204,15 +414,57 @@ impl Config {
                 },
                 Err(_) => PromptConfig::default(),
             };
+        //Get palette
+        if let Ok(node) = ConfigParser::get_child(yaml_doc, String::from("palette")) {
+            match Config::parse_palette(node) {
+                Ok(palette) => prompt_config.palette.extend(palette),
+                Err(err) => return Err(err),
+            }
+        }
         Ok(Config {
             language: language,
+            language_standard,
+            language_strict,
+            banner,
+            assume_width,
+            core_config,
             shell_config: shell_config,
+            terminal_config,
             alias: alias_config,
+            alias_options,
             output_config: output_config,
+            input_config,
             prompt_config: prompt_config,
+            unknown_keys: Vec::new(),
+            overrides: ConfigOverrides::default(),
         })
     }
 
+    /// ### apply_overrides
+    ///
+    /// Apply the options set on the command line to the configuration.
+    /// They're kept in the configuration, so that they can be applied again when it's reloaded
+    pub fn apply_overrides(&mut self, overrides: ConfigOverrides) {
+        if let Some(translate_output) = overrides.translate_output {
+            self.output_config.translate_stdout = translate_output;
+            self.output_config.translate_stderr = translate_output;
+        }
+        if let Some(translation) = overrides.input_translation {
+            self.input_config.translation = translation;
+        }
+        self.overrides = overrides;
+    }
+
+    /// ### apply_pipe
+    ///
+    /// Disable the translation of stdout if it's not a terminal (see `OutputConfig::apply_pipe`);
+    /// the translation set on the command line is always kept
+    pub fn apply_pipe(&mut self, isatty: fn(RawFd) -> bool) {
+        if self.overrides.translate_output.is_none() {
+            self.output_config.apply_pipe(isatty);
+        }
+    }
+
     /// ### get_alias
     ///
     ///  Get alias from configuration
@@ -223,10 +475,115 @@ impl Config {
         }
     }
 
+    /// ### get_aliases
+    ///
+    /// Get all the configured aliases, as (name, command) pairs sorted by name
+    pub fn get_aliases(&self) -> Vec<(String, String)> {
+        let mut aliases: Vec<(String, String)> = self.alias.iter().map(|(name, cmd)| (name.clone(), cmd.clone())).collect();
+        aliases.sort();
+        aliases
+    }
+
+    /// ### find_aliases
+    ///
+    /// Get the names of the configured aliases starting with prefix, sorted
+    pub fn find_aliases(&self, prefix: &str) -> Vec<String> {
+        let mut names: Vec<String> = self.alias.keys().filter(|name| name.starts_with(prefix)).cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// ### resolve_alias_chain
+    ///
+    /// Resolve an alias whose command starts with other aliases (e.g. `лл: "лс -л"`, `лс: ls`) to the command it runs (`ls -л`).
+    /// Returns None if name is not an alias, or an error if the aliases form a cycle
+    pub fn resolve_alias_chain(&self, name: &str) -> Result<Option<String>, AliasError> {
+        if !self.alias.contains_key(name) {
+            return Ok(None);
+        }
+        let mut argv: Vec<String> = vec![String::from(name)];
+        alias::expand(&mut argv, &|name: &str| self.alias.get(name).cloned())?;
+        Ok(Some(argv.join(" ")))
+    }
+
+    /// ### warnings
+    ///
+    /// Returns the problems of the configuration which don't prevent pyc from running (e.g. unknown keys or palette names)
+    pub fn warnings(&self) -> Vec<String> {
+        let prompt_config: &PromptConfig = &self.prompt_config;
+        let mut warnings: Vec<String> = self.unknown_keys.iter().map(|err| err.message.clone()).collect();
+        for (key, value) in [
+            ("prompt.prompt_line", &prompt_config.prompt_line),
+            ("prompt.rc.ok", &prompt_config.rc_ok),
+            ("prompt.rc.error", &prompt_config.rc_err),
+            ("prompt.prompt_char_user", &prompt_config.prompt_char_user),
+            ("prompt.prompt_char_root", &prompt_config.prompt_char_root),
+            ("prompt.jobs.running", &prompt_config.jobs_running),
+            ("prompt.jobs.stopped", &prompt_config.jobs_stopped),
+        ]
+        .iter()
+        {
+            for name in palette_references(value) {
+                if !prompt_config.palette.contains_key(&name) {
+                    warnings.push(format!("{}: unknown palette color '{}'", key, name));
+                }
+            }
+        }
+        for (name, _) in self.get_aliases() {
+            if let Err(err) = self.resolve_alias_chain(name.as_str()) {
+                warnings.push(format!("alias.{}: {}", name, err));
+            }
+        }
+        for name in recursive_macros(&prompt_config.prompt_macros) {
+            warnings.push(format!(
+                "prompt.macros.{}: macro is recursive or nested more than {} times; it renders empty",
+                name, MACRO_MAX_DEPTH
+            ));
+        }
+        warnings
+    }
+
+    /// ### parse_palette
+    ///
+    /// Parse the palette section, which maps color names to styles (e.g. `primary: bold #ff8700`)
+    fn parse_palette(palette_yaml: &Yaml) -> Result<HashMap<String, String>, ConfigError> {
+        let palette_yaml = match palette_yaml.as_hash() {
+            Some(palette) => palette,
+            None => return Err(ConfigError {
+                code: ConfigErrorCode::YamlSyntaxError,
+                message: String::from("'palette' key is not a map"),
+            }),
+        };
+        let mut palette: HashMap<String, String> = HashMap::new();
+        for (name, style) in palette_yaml.iter() {
+            //256 colors indexes are integers
+            let style: Option<String> = match style {
+                Yaml::Integer(index) => Some(index.to_string()),
+                style => style.as_str().map(String::from),
+            };
+            let (name, style): (&str, String) = match (name.as_str(), style) {
+                (Some(name), Some(style)) => (name, style),
+                _ => return Err(ConfigError {
+                    code: ConfigErrorCode::YamlSyntaxError,
+                    message: String::from("'palette' item is not a string pair"),
+                }),
+            };
+            if Palette::parse_style(style.as_str()).is_none() {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::YamlSyntaxError,
+                    message: format!("Invalid style '{}' for palette color '{}'", style, name),
+                });
+            }
+            palette.insert(String::from(name), style);
+        }
+        Ok(palette)
+    }
+
     /// ### parse_alias
     ///
-    /// Parse alias in Pyc configuration file
-    fn parse_alias(alias_yaml: &Yaml) -> Result<HashMap<String, String>, ConfigError> {
+    /// Parse alias in Pyc configuration file. An alias is either a command or a stanza with the command (`cmd`),
+    /// its working directory (`cwd`) and its environment (`env`); the options of stanzas are returned in the second table
+    fn parse_alias(alias_yaml: &Yaml) -> Result<AliasTables, ConfigError> {
         if !alias_yaml.is_array() {
             return Err(ConfigError {
                 code: ConfigErrorCode::YamlSyntaxError,
@@ -234,43 +591,111 @@ impl Config {
             });
         }
         let mut alias_table: HashMap<String, String> = HashMap::new();
+        let mut options_table: HashMap<String, AliasOptions> = HashMap::new();
         //Iterate over alias
         for pair in alias_yaml.as_vec().unwrap() {
             for p in pair.as_hash().unwrap().iter() {
                 let key: String = String::from(p.0.as_str().unwrap());
-                let value: String = String::from(p.1.as_str().unwrap());
-                alias_table.insert(key, value);
+                if key.is_empty() || key.contains(|c: char| c.is_whitespace() || c == '\'' || c == '"' || c == '`') {
+                    return Err(ConfigError {
+                        code: ConfigErrorCode::YamlSyntaxError,
+                        message: format!("alias '{}': the name can't contain whitespace or quotes", key),
+                    });
+                }
+                if let Some(value) = p.1.as_str() {
+                    alias_table.insert(key, String::from(value));
+                    continue;
+                }
+                let value: String = match ConfigParser::get_string(p.1, String::from("cmd")) {
+                    Ok(cmd) => cmd,
+                    Err(_) => {
+                        return Err(ConfigError {
+                            code: ConfigErrorCode::YamlSyntaxError,
+                            message: format!("alias '{}': missing 'cmd'", key),
+                        })
+                    }
+                };
+                let options: AliasOptions = Config::parse_alias_options(key.as_str(), p.1)?;
+                alias_table.insert(key.clone(), value);
+                options_table.insert(key, options);
             }
         }
-        Ok(alias_table)
+        Ok((alias_table, options_table))
+    }
+
+    /// ### parse_alias_options
+    ///
+    /// Parse the working directory and the environment of an alias stanza
+    fn parse_alias_options(alias: &str, stanza_yaml: &Yaml) -> Result<AliasOptions, ConfigError> {
+        let cwd: Option<String> = ConfigParser::get_string(stanza_yaml, String::from("cwd")).ok();
+        let mut env: Vec<(String, String)> = Vec::new();
+        if let Some(vars) = stanza_yaml["env"].as_hash() {
+            for (name, value) in vars.iter() {
+                let name: String = match name.as_str() {
+                    Some(name) if is_name(name) => String::from(name),
+                    _ => {
+                        return Err(ConfigError {
+                            code: ConfigErrorCode::YamlSyntaxError,
+                            message: format!("alias '{}': invalid environment variable name", alias),
+                        })
+                    }
+                };
+                let value: String = match value {
+                    Yaml::Integer(i) => i.to_string(),
+                    value => String::from(value.as_str().unwrap_or("")),
+                };
+                env.push((name, value));
+            }
+        }
+        Ok(AliasOptions { cwd, env })
     }
 
     /// ### parse_language
     ///
-    /// Parse language YAML object
-    fn parse_language(language_yaml: &Yaml) -> Result<String, ConfigError> {
-        match language_yaml.as_str() {
-            Some(s) => Ok(String::from(s)),
-            None => Err(ConfigError {
+    /// Parse language YAML object: either the language name or a stanza with its name, the transliteration standard
+    /// and whether the transliteration is strict. Returns the language (None if the name is not set), the standard and the strict flag
+    fn parse_language(language_yaml: &Yaml) -> Result<(Option<String>, String, bool), ConfigError> {
+        if let Some(s) = language_yaml.as_str() {
+            return Ok((Some(String::from(s)), String::from("gost"), false));
+        }
+        if language_yaml.as_hash().is_none() {
+            return Err(ConfigError {
                 code: ConfigErrorCode::YamlSyntaxError,
                 message: String::from("'language' is not a string"),
-            }),
+            });
         }
+        let name: Option<String> = match ConfigParser::get_child(language_yaml, String::from("name")) {
+            Ok(_) => Some(ConfigParser::get_string(language_yaml, String::from("name"))?),
+            Err(_) => None,
+        };
+        let standard: String = match ConfigParser::get_child(language_yaml, String::from("standard")) {
+            Ok(_) => ConfigParser::get_string(language_yaml, String::from("standard"))?,
+            Err(_) => String::from("gost"),
+        };
+        let strict: bool = match ConfigParser::get_child(language_yaml, String::from("strict")) {
+            Ok(_) => ConfigParser::get_bool(language_yaml, String::from("strict"))?,
+            Err(_) => false,
+        };
+        Ok((name, standard, strict))
     }
 }
 
 impl ShellConfig {
     pub fn default() -> ShellConfig {
         ShellConfig {
-            exec: String::from("bash"),
-            args: vec![]
+            exec: None,
+            args: vec![],
+            norc: false
         }
     }
 
     pub fn parse_config(shell_yaml: &Yaml) -> Result<ShellConfig, ConfigError> {
-        let exec: String = match ConfigParser::get_string(&shell_yaml, String::from("exec")) {
-            Ok(s) => s,
-            Err(err) => return Err(err)
+        let exec: Option<String> = match ConfigParser::get_child(shell_yaml, String::from("exec")) {
+            Ok(_) => match ConfigParser::get_string(shell_yaml, String::from("exec")) {
+                Ok(s) => Some(s),
+                Err(err) => return Err(err)
+            },
+            Err(_) => None
         };
 
         let args: Vec<String> = match ConfigParser::get_child(&shell_yaml, String::from("args")) {
@@ -287,9 +712,127 @@ impl ShellConfig {
             },
             Err(_) => Vec::new()
         };
+        let norc: bool = match ConfigParser::get_child(shell_yaml, String::from("norc")) {
+            Ok(_) => ConfigParser::get_bool(shell_yaml, String::from("norc"))?,
+            Err(_) => false
+        };
         Ok(ShellConfig {
             exec: exec,
-            args: args
+            args,
+            norc
+        })
+    }
+}
+
+impl TerminalConfig {
+    pub fn parse_config(terminal_yaml: &Yaml) -> Result<TerminalConfig, ConfigError> {
+        let clear_scrollback: bool = match ConfigParser::get_child(terminal_yaml, String::from("clear_scrollback")) {
+            Ok(_) => ConfigParser::get_bool(terminal_yaml, String::from("clear_scrollback"))?,
+            Err(_) => TerminalConfig::default().clear_scrollback,
+        };
+        Ok(TerminalConfig {
+            clear_scrollback,
+        })
+    }
+}
+
+impl Default for CoreConfig {
+    fn default() -> CoreConfig {
+        CoreConfig {
+            log_level: LogLevel::Warn,
+            log_file: None,
+            rc_file: dirs::home_dir().map(|home| home.join(".config/pyc/pycrc")),
+            sigint_escalation: SigintEscalation::default(),
+            command_timeout: 0,
+            use_pty: true,
+        }
+    }
+}
+
+impl CoreConfig {
+    pub fn parse_config(core_yaml: &Yaml) -> Result<CoreConfig, ConfigError> {
+        let default: CoreConfig = CoreConfig::default();
+        let log_level: LogLevel = match ConfigParser::get_child(core_yaml, String::from("log_level")) {
+            Ok(_) => {
+                let name: String = ConfigParser::get_string(core_yaml, String::from("log_level"))?;
+                match LogLevel::from_name(name.as_str()) {
+                    Some(level) => level,
+                    None => return Err(ConfigError {
+                        code: ConfigErrorCode::YamlSyntaxError,
+                        message: format!("'log_level' must be one of 'error', 'warn', 'info', 'debug' (found '{}')", name),
+                    }),
+                }
+            }
+            Err(_) => default.log_level,
+        };
+        let log_file: Option<PathBuf> = match ConfigParser::get_child(core_yaml, String::from("log_file")) {
+            Ok(_) => Some(PathBuf::from(ConfigParser::get_string(core_yaml, String::from("log_file"))?)),
+            Err(_) => default.log_file,
+        };
+        //An empty path disables the rc file
+        let rc_file: Option<PathBuf> = match ConfigParser::get_child(core_yaml, String::from("rc_file")) {
+            Ok(_) => {
+                let rc_file: String = ConfigParser::get_string(core_yaml, String::from("rc_file"))?;
+                match (rc_file.as_str(), rc_file.strip_prefix("~/")) {
+                    ("", _) => None,
+                    (_, Some(path)) => dirs::home_dir().map(|home| home.join(path)),
+                    (path, None) => Some(PathBuf::from(path)),
+                }
+            }
+            Err(_) => default.rc_file,
+        };
+        let sigint_escalation: SigintEscalation = match ConfigParser::get_child(core_yaml, String::from("sigint_escalation")) {
+            Ok(node) => SigintEscalation::parse_config(node)?,
+            Err(_) => default.sigint_escalation,
+        };
+        let command_timeout: usize = match ConfigParser::get_child(core_yaml, String::from("command_timeout")) {
+            Ok(_) => ConfigParser::get_usize(core_yaml, String::from("command_timeout"))?,
+            Err(_) => default.command_timeout,
+        };
+        let use_pty: bool = match ConfigParser::get_child(core_yaml, String::from("use_pty")) {
+            Ok(_) => ConfigParser::get_bool(core_yaml, String::from("use_pty"))?,
+            Err(_) => default.use_pty,
+        };
+        Ok(CoreConfig {
+            log_level,
+            log_file,
+            rc_file,
+            sigint_escalation,
+            command_timeout,
+            use_pty,
+        })
+    }
+}
+
+impl Default for SigintEscalation {
+    fn default() -> SigintEscalation {
+        SigintEscalation {
+            presses: 3,
+            window_ms: 2000,
+            kill_after_ms: 3000,
+        }
+    }
+}
+
+impl SigintEscalation {
+    pub fn parse_config(escalation_yaml: &Yaml) -> Result<SigintEscalation, ConfigError> {
+        let default: SigintEscalation = SigintEscalation::default();
+        let presses: usize = match ConfigParser::get_child(escalation_yaml, String::from("presses")) {
+            Ok(_) => ConfigParser::get_usize(escalation_yaml, String::from("presses"))?,
+            Err(_) => default.presses,
+        };
+        let window_ms: usize = match ConfigParser::get_child(escalation_yaml, String::from("window_ms")) {
+            Ok(_) => ConfigParser::get_usize(escalation_yaml, String::from("window_ms"))?,
+            Err(_) => default.window_ms,
+        };
+        let kill_after_ms: usize = match ConfigParser::get_child(escalation_yaml, String::from("kill_after_ms")) {
+            Ok(_) => ConfigParser::get_usize(escalation_yaml, String::from("kill_after_ms"))?,
+            Err(_) => default.kill_after_ms,
+        };
+        Ok(SigintEscalation {
+            presses,
+            window_ms,
+            kill_after_ms,
         })
     }
 }
@@ -297,22 +840,352 @@ impl ShellConfig {
 impl OutputConfig {
     pub fn default() -> OutputConfig {
         OutputConfig {
-            translate_output: true,
+            translate_stdout: true,
+            translate_stderr: true,
+            force_translate_pipe: false,
+            translate_only: Vec::new(),
+            never_translate: Vec::new(),
+            localize_units: false,
+            localize_dictionary: HashMap::new(),
+            error_hints: false,
+            hints_dictionary: HashMap::new(),
+            no_translate_patterns: DEFAULT_NO_TRANSLATE_PATTERNS.iter().map(|p| String::from(*p)).collect(),
+            echo_command: false,
+            error_color: None,
+            error_prefix: String::new(),
         }
     }
 
     pub fn parse_config(output_yaml: &Yaml) -> Result<OutputConfig, ConfigError> {
-        let translate_output: bool =
-            match ConfigParser::get_bool(&output_yaml, String::from("translate")) {
-                Ok(t) => t,
-                Err(err) => return Err(err),
-            };
+        //`translate` (deprecated) sets both the streams; `translate_stdout` and `translate_stderr` take precedence over it
+        let translate: Option<bool> = match ConfigParser::get_child(output_yaml, String::from("translate")) {
+            Ok(_) => Some(ConfigParser::get_bool(output_yaml, String::from("translate"))?),
+            Err(_) => None,
+        };
+        let translate_stdout: Option<bool> = match ConfigParser::get_child(output_yaml, String::from("translate_stdout")) {
+            Ok(_) => Some(ConfigParser::get_bool(output_yaml, String::from("translate_stdout"))?),
+            Err(_) => None,
+        };
+        let translate_stderr: Option<bool> = match ConfigParser::get_child(output_yaml, String::from("translate_stderr")) {
+            Ok(_) => Some(ConfigParser::get_bool(output_yaml, String::from("translate_stderr"))?),
+            Err(_) => None,
+        };
+        let (translate_stdout, translate_stderr): (bool, bool) = match (translate_stdout.or(translate), translate_stderr.or(translate)) {
+            (Some(stdout), Some(stderr)) => (stdout, stderr),
+            _ => {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::YamlSyntaxError,
+                    message: String::from("Missing 'translate_stdout' and 'translate_stderr' (or 'translate')"),
+                })
+            }
+        };
+        let force_translate_pipe: bool = match ConfigParser::get_child(output_yaml, String::from("force_translate_pipe")) {
+            Ok(_) => ConfigParser::get_bool(output_yaml, String::from("force_translate_pipe"))?,
+            Err(_) => false,
+        };
+        let translate_only: Vec<String> =
+            OutputConfig::parse_command_list(output_yaml, String::from("translate_only"))?;
+        let never_translate: Vec<String> =
+            OutputConfig::parse_command_list(output_yaml, String::from("never_translate"))?;
+        let localize_units: bool = match ConfigParser::get_child(output_yaml, String::from("localize_units")) {
+            Ok(_) => ConfigParser::get_bool(output_yaml, String::from("localize_units"))?,
+            Err(_) => false,
+        };
+        let localize_dictionary: HashMap<String, String> =
+            OutputConfig::parse_dictionary(output_yaml, String::from("localize_dictionary"))?;
+        let error_hints: bool = match ConfigParser::get_child(output_yaml, String::from("error_hints")) {
+            Ok(_) => ConfigParser::get_bool(output_yaml, String::from("error_hints"))?,
+            Err(_) => false,
+        };
+        let hints_dictionary: HashMap<String, String> =
+            OutputConfig::parse_dictionary(output_yaml, String::from("hints_dictionary"))?;
+        let no_translate_patterns: Vec<String> = match ConfigParser::get_child(output_yaml, String::from("no_translate_patterns")) {
+            Ok(_) => OutputConfig::parse_command_list(output_yaml, String::from("no_translate_patterns"))?,
+            Err(_) => OutputConfig::default().no_translate_patterns,
+        };
+        let echo_command: bool = match ConfigParser::get_child(output_yaml, String::from("echo_command")) {
+            Ok(_) => ConfigParser::get_bool(output_yaml, String::from("echo_command"))?,
+            Err(_) => false,
+        };
+        //256 colors indexes are integers
+        let error_color: Option<String> = match ConfigParser::get_child(output_yaml, String::from("error_color")) {
+            Ok(Yaml::Integer(index)) => Some(index.to_string()),
+            Ok(_) => Some(ConfigParser::get_string(output_yaml, String::from("error_color"))?),
+            Err(_) => None,
+        };
+        if let Some(color) = error_color.as_ref() {
+            if PromptColor::from_name(color.as_str()).is_none() {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::YamlSyntaxError,
+                    message: format!("Invalid color '{}' for 'error_color'", color),
+                });
+            }
+        }
+        let error_prefix: String = match ConfigParser::get_child(output_yaml, String::from("error_prefix")) {
+            Ok(_) => ConfigParser::get_string(output_yaml, String::from("error_prefix"))?,
+            Err(_) => String::new(),
+        };
+        for pattern in no_translate_patterns.iter() {
+            if let Err(err) = Regex::new(pattern.as_str()) {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::YamlSyntaxError,
+                    message: format!("'no_translate_patterns' item '{}' is not a valid regex: {}", pattern, err),
+                });
+            }
+        }
         Ok(OutputConfig {
-            translate_output: translate_output,
+            translate_stdout,
+            translate_stderr,
+            force_translate_pipe,
+            translate_only,
+            never_translate,
+            localize_units,
+            localize_dictionary,
+            error_hints,
+            hints_dictionary,
+            no_translate_patterns,
+            echo_command,
+            error_color,
+            error_prefix,
+        })
+    }
+
+    /// ### translation
+    ///
+    /// Returns which output streams have to be translated
+    pub fn translation(&self) -> OutputTranslation {
+        OutputTranslation {
+            stdout: self.translate_stdout,
+            stderr: self.translate_stderr,
+        }
+    }
+
+    /// ### translate_command
+    ///
+    /// Returns which output streams of the provided command have to be translated.
+    /// `never_translate` wins over everything; if `translate_only` is not empty, only the commands listed there are translated
+    pub fn translate_command(&self, command: &str) -> OutputTranslation {
+        let listed: bool = !self.never_translate.iter().any(|c| c == command)
+            && (self.translate_only.is_empty() || self.translate_only.iter().any(|c| c == command));
+        OutputTranslation {
+            stdout: listed && self.translate_stdout,
+            stderr: listed && self.translate_stderr,
+        }
+    }
+
+    /// ### apply_pipe
+    ///
+    /// Disable the translation of stdout if it's not a terminal (e.g. it's piped to another program or redirected to a file),
+    /// unless `force_translate_pipe` is set. `isatty` tells whether the provided file descriptor is a terminal
+    pub fn apply_pipe(&mut self, isatty: fn(RawFd) -> bool) {
+        if !self.force_translate_pipe && !isatty(1) {
+            self.translate_stdout = false;
+        }
+    }
+
+    /// ### parse_command_list
+    ///
+    /// Parse an optional list of command names; if the key is missing, an empty list is returned
+    fn parse_command_list(output_yaml: &Yaml, key: String) -> Result<Vec<String>, ConfigError> {
+        let list_yaml: &Yaml = match ConfigParser::get_child(output_yaml, key.clone()) {
+            Ok(node) => node,
+            Err(_) => return Ok(Vec::new()),
+        };
+        let list_yaml: &Vec<Yaml> = match list_yaml.as_vec() {
+            Some(list) => list,
+            None => return Err(ConfigError {
+                code: ConfigErrorCode::YamlSyntaxError,
+                message: format!("'{}' key is not an array", key),
+            }),
+        };
+        let mut commands: Vec<String> = Vec::with_capacity(list_yaml.len());
+        for command in list_yaml.iter() {
+            commands.push(match command.as_str() {
+                Some(s) => String::from(s),
+                None => return Err(ConfigError {
+                    code: ConfigErrorCode::YamlSyntaxError,
+                    message: format!("'{}' item is not a string", key),
+                }),
+            });
+        }
+        Ok(commands)
+    }
+
+    /// ### parse_dictionary
+    ///
+    /// Parse an optional list of `word: replacement` pairs; if the key is missing, an empty dictionary is returned
+    fn parse_dictionary(output_yaml: &Yaml, key: String) -> Result<HashMap<String, String>, ConfigError> {
+        let dictionary_yaml: &Yaml = match ConfigParser::get_child(output_yaml, key.clone()) {
+            Ok(node) => node,
+            Err(_) => return Ok(HashMap::new()),
+        };
+        let dictionary_yaml: &Vec<Yaml> = match dictionary_yaml.as_vec() {
+            Some(list) => list,
+            None => return Err(ConfigError {
+                code: ConfigErrorCode::YamlSyntaxError,
+                message: format!("'{}' key is not an array", key),
+            }),
+        };
+        let mut dictionary: HashMap<String, String> = HashMap::new();
+        for pair in dictionary_yaml.iter() {
+            let pair = match pair.as_hash() {
+                Some(pair) => pair,
+                None => return Err(ConfigError {
+                    code: ConfigErrorCode::YamlSyntaxError,
+                    message: format!("'{}' item is not a map", key),
+                }),
+            };
+            for (word, replacement) in pair.iter() {
+                match (word.as_str(), replacement.as_str()) {
+                    (Some(word), Some(replacement)) => {
+                        dictionary.insert(String::from(word), String::from(replacement));
+                    }
+                    _ => return Err(ConfigError {
+                        code: ConfigErrorCode::YamlSyntaxError,
+                        message: format!("'{}' item is not a string pair", key),
+                    }),
+                }
+            }
+        }
+        Ok(dictionary)
+    }
+}
+
+impl Default for InputConfig {
+    /// ### default
+    ///
+    /// Instantiate a default InputConfig struct
+    fn default() -> InputConfig {
+        InputConfig {
+            preserve_redirect_targets: true,
+            preserve_output_args: vec![
+                String::from("curl"),
+                String::from("wget"),
+                String::from("gcc"),
+                String::from("g++"),
+                String::from("clang"),
+                String::from("pandoc"),
+            ],
+            strict_boundaries: true,
+            expand_variables: true,
+            error_on_unset: false,
+            nullglob: false,
+            literal_delimiters: Some((String::from("«"), String::from("»"))),
+            translation: InputTranslation::Always,
+        }
+    }
+}
+
+impl InputConfig {
+    /// ### parse_config
+    ///
+    /// Parse an InputConfig from YAML configuration file; missing keys take the default value
+    pub fn parse_config(input_yaml: &Yaml) -> Result<InputConfig, ConfigError> {
+        let default: InputConfig = InputConfig::default();
+        let preserve_redirect_targets: bool = match ConfigParser::get_child(input_yaml, String::from("preserve_redirect_targets")) {
+            Ok(_) => ConfigParser::get_bool(input_yaml, String::from("preserve_redirect_targets"))?,
+            Err(_) => default.preserve_redirect_targets,
+        };
+        let preserve_output_args: Vec<String> = match ConfigParser::get_child(input_yaml, String::from("preserve_output_args")) {
+            Ok(_) => OutputConfig::parse_command_list(input_yaml, String::from("preserve_output_args"))?,
+            Err(_) => default.preserve_output_args,
+        };
+        let strict_boundaries: bool = match ConfigParser::get_child(input_yaml, String::from("strict_boundaries")) {
+            Ok(_) => ConfigParser::get_bool(input_yaml, String::from("strict_boundaries"))?,
+            Err(_) => default.strict_boundaries,
+        };
+        let expand_variables: bool = match ConfigParser::get_child(input_yaml, String::from("expand_variables")) {
+            Ok(_) => ConfigParser::get_bool(input_yaml, String::from("expand_variables"))?,
+            Err(_) => default.expand_variables,
+        };
+        let error_on_unset: bool = match ConfigParser::get_child(input_yaml, String::from("error_on_unset")) {
+            Ok(_) => ConfigParser::get_bool(input_yaml, String::from("error_on_unset"))?,
+            Err(_) => default.error_on_unset,
+        };
+        let nullglob: bool = match ConfigParser::get_child(input_yaml, String::from("nullglob")) {
+            Ok(_) => ConfigParser::get_bool(input_yaml, String::from("nullglob"))?,
+            Err(_) => default.nullglob,
+        };
+        //Literal delimiters are a pair of strings (opening and closing); an empty list disables them
+        let literal_delimiters: Option<(String, String)> = match ConfigParser::get_child(input_yaml, String::from("literal_delimiters")) {
+            Ok(_) => {
+                let delimiters: Vec<String> = OutputConfig::parse_command_list(input_yaml, String::from("literal_delimiters"))?;
+                match delimiters.as_slice() {
+                    [] => None,
+                    [open, close] if !open.is_empty() && !close.is_empty() => Some((open.clone(), close.clone())),
+                    _ => return Err(ConfigError {
+                        code: ConfigErrorCode::YamlSyntaxError,
+                        message: String::from("'literal_delimiters' must contain the opening and the closing delimiter"),
+                    }),
+                }
+            }
+            Err(_) => default.literal_delimiters,
+        };
+        let translation: InputTranslation = match ConfigParser::get_child(input_yaml, String::from("translation")) {
+            Ok(_) => {
+                let name: String = ConfigParser::get_string(input_yaml, String::from("translation"))?;
+                match InputTranslation::from_name(name.as_str()) {
+                    Some(translation) => translation,
+                    None => return Err(ConfigError {
+                        code: ConfigErrorCode::YamlSyntaxError,
+                        message: format!("'translation' must be one of 'always', 'never', 'command-line-only' (found '{}')", name),
+                    }),
+                }
+            }
+            Err(_) => default.translation,
+        };
+        Ok(InputConfig {
+            preserve_redirect_targets,
+            preserve_output_args,
+            strict_boundaries,
+            expand_variables,
+            error_on_unset,
+            nullglob,
+            literal_delimiters,
+            translation,
         })
     }
 }
 
+impl InputTranslation {
+    /// ### from_name
+    ///
+    /// Get the input translation mode from its name in the configuration
+    pub fn from_name(name: &str) -> Option<InputTranslation> {
+        match name {
+            "always" => Some(InputTranslation::Always),
+            "never" => Some(InputTranslation::Never),
+            "command-line-only" => Some(InputTranslation::CommandLineOnly),
+            _ => None,
+        }
+    }
+
+    /// ### command_line
+    ///
+    /// Returns whether the command line typed at pyc's prompt is transliterated
+    pub fn command_line(&self) -> bool {
+        *self != InputTranslation::Never
+    }
+
+    /// ### subprocess
+    ///
+    /// Returns whether the input typed while a command is running is transliterated before being sent to it
+    pub fn subprocess(&self) -> bool {
+        *self == InputTranslation::Always
+    }
+}
+
+impl fmt::Display for InputTranslation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mode_str: &str = match self {
+            InputTranslation::Always => "always",
+            InputTranslation::Never => "never",
+            InputTranslation::CommandLineOnly => "command-line-only",
+        };
+        write!(f, "{}", mode_str)
+    }
+}
+
 impl PromptConfig {
     /// ### default
     ///
@@ -320,6 +1193,7 @@ impl PromptConfig {
     pub fn default() -> PromptConfig {
         PromptConfig {
             prompt_line: String::from("${USER}@${HOSTNAME}:${WRKDIR}$"),
+            prompt_line_right: String::new(),
             history_size: 256,
             translate: false,
             break_enabled: false,
@@ -327,11 +1201,55 @@ impl PromptConfig {
             min_duration: 2000,
             rc_ok: String::from("✔"),
             rc_err: String::from("✖"),
+            prompt_char_user: String::from("$"),
+            prompt_char_root: String::from("#"),
+            rc_code_always: false,
             git_branch: String::from("on "),
             git_commit_ref: 8,
             git_commit_append: None,
-            git_commit_prepend: None
+            git_commit_prepend: None,
+            git_dirty: String::from("✚"),
+            git_staged: String::from("●"),
+            git_cache_ttl: 30,
+            time_format: String::from("%H:%M:%S"),
+            date_format: String::from("%Y-%m-%d"),
+            duration_format: None,
+            duration_prefix: None,
+            venv_prefix: String::from("🐍"),
+            battery_charging: String::from("⚡"),
+            battery_discharging: String::from("🔋"),
+            battery_low_threshold: 20,
+            jobs_running: String::from("⚙"),
+            jobs_stopped: String::from("⏸"),
+            wrkdir_max_depth: 0,
+            wrkdir_truncate_repo: false,
+            keep_trailing_space: false,
+            continuation: String::from("> "),
+            module_timeout: 100,
+            prompt_macros: HashMap::new(),
+            palette: PromptConfig::default_palette(),
+        }
+    }
+
+    /// ### default_palette
+    ///
+    /// Returns the builtin palette; the palette configured by the user is merged over it
+    pub fn default_palette() -> HashMap<String, String> {
+        let mut palette: HashMap<String, String> = HashMap::new();
+        for (name, style) in [
+            ("primary", "yellow"),
+            ("secondary", "green"),
+            ("accent", "cyan"),
+            ("highlight", "magenta"),
+            ("success", "green"),
+            ("error", "red"),
+            ("muted", "gray"),
+        ]
+        .iter()
+        {
+            palette.insert(String::from(*name), String::from(*style));
         }
+        palette
     }
 
     /// ### parse_config
@@ -344,6 +1262,8 @@ impl PromptConfig {
                 Ok(ret) => ret,
                 Err(err) => return Err(err),
             };
+        //Right segment
+        let prompt_line_right: String = ConfigParser::get_string(prompt_config_yaml, String::from("prompt_line_right")).unwrap_or_default();
         //History size
         let history_size: usize =
             match ConfigParser::get_usize(&prompt_config_yaml, String::from("history_size")) {
@@ -398,6 +1318,8 @@ impl PromptConfig {
             Ok(ret) => ret,
             Err(err) => return Err(err),
         };
+        //Show exit code even if 0
+        let rc_code_always: bool = ConfigParser::get_bool(rc, String::from("code_always")).unwrap_or_default();
         //Git
         let git: &Yaml = match ConfigParser::get_child(&prompt_config_yaml, String::from("git")) {
             Ok(ret) => ret,
@@ -426,8 +1348,125 @@ impl PromptConfig {
                 Ok(ret) => Some(ret),
                 Err(_) => None,
             };
+        //Git dirty symbol
+        let git_dirty: String = match ConfigParser::get_string(git, String::from("dirty")) {
+            Ok(ret) => ret,
+            Err(_) => String::from("✚"),
+        };
+        //Git staged symbol
+        let git_staged: String = match ConfigParser::get_string(git, String::from("staged")) {
+            Ok(ret) => ret,
+            Err(_) => String::from("●"),
+        };
+        //Git repository cache
+        let git_cache_ttl: usize = match ConfigParser::get_child(git, String::from("cache_ttl")) {
+            Ok(_) => ConfigParser::get_usize(git, String::from("cache_ttl"))?,
+            Err(_) => 30,
+        };
+        //Time format
+        let time_format: String = match ConfigParser::get_string(prompt_config_yaml, String::from("time_format")) {
+            Ok(ret) => ret,
+            Err(_) => String::from("%H:%M:%S"),
+        };
+        //Date format
+        let date_format: String = match ConfigParser::get_string(prompt_config_yaml, String::from("date_format")) {
+            Ok(ret) => ret,
+            Err(_) => String::from("%Y-%m-%d"),
+        };
+        //Duration format (the phrase of the language is used if not set)
+        let duration_format: Option<String> = ConfigParser::get_string(prompt_config_yaml, String::from("duration_format")).ok();
+        //Duration prefix
+        let duration_prefix: Option<String> = ConfigParser::get_string(prompt_config_yaml, String::from("duration_prefix")).ok();
+        //Virtual environment prefix
+        let venv_prefix: String = match ConfigParser::get_child(prompt_config_yaml, String::from("venv")) {
+            Ok(venv) => match ConfigParser::get_string(venv, String::from("prefix")) {
+                Ok(ret) => ret,
+                Err(_) => String::from("🐍"),
+            },
+            Err(_) => String::from("🐍"),
+        };
+        //Battery
+        let (battery_charging, battery_discharging, battery_low_threshold): (String, String, u8) =
+            match ConfigParser::get_child(prompt_config_yaml, String::from("battery")) {
+                Ok(battery) => {
+                    let charging: String = match ConfigParser::get_string(battery, String::from("charging")) {
+                        Ok(ret) => ret,
+                        Err(_) => String::from("⚡"),
+                    };
+                    let discharging: String = match ConfigParser::get_string(battery, String::from("discharging")) {
+                        Ok(ret) => ret,
+                        Err(_) => String::from("🔋"),
+                    };
+                    let low_threshold: u8 = match ConfigParser::get_child(battery, String::from("low_threshold")) {
+                        Ok(_) => match ConfigParser::get_usize(battery, String::from("low_threshold")) {
+                            Ok(ret) if ret <= 100 => ret as u8,
+                            Ok(ret) => {
+                                return Err(ConfigError {
+                                    code: ConfigErrorCode::YamlSyntaxError,
+                                    message: format!("'low_threshold' must be a percentage between 0 and 100 (found {})", ret),
+                                })
+                            }
+                            Err(err) => return Err(err),
+                        },
+                        Err(_) => 20,
+                    };
+                    (charging, discharging, low_threshold)
+                }
+                Err(_) => (String::from("⚡"), String::from("🔋"), 20),
+            };
+        //Jobs
+        let (jobs_running, jobs_stopped): (String, String) = match ConfigParser::get_child(prompt_config_yaml, String::from("jobs")) {
+            Ok(jobs) => {
+                let running: String = match ConfigParser::get_string(jobs, String::from("running")) {
+                    Ok(ret) => ret,
+                    Err(_) => String::from("⚙"),
+                };
+                let stopped: String = match ConfigParser::get_string(jobs, String::from("stopped")) {
+                    Ok(ret) => ret,
+                    Err(_) => String::from("⏸"),
+                };
+                (running, stopped)
+            }
+            Err(_) => (String::from("⚙"), String::from("⏸")),
+        };
+        //Working directory
+        let wrkdir_max_depth: usize = match ConfigParser::get_child(prompt_config_yaml, String::from("wrkdir_max_depth")) {
+            Ok(_) => ConfigParser::get_usize(prompt_config_yaml, String::from("wrkdir_max_depth"))?,
+            Err(_) => 0,
+        };
+        let wrkdir_truncate_repo: bool = match ConfigParser::get_child(prompt_config_yaml, String::from("wrkdir_truncate_repo")) {
+            Ok(_) => ConfigParser::get_bool(prompt_config_yaml, String::from("wrkdir_truncate_repo"))?,
+            Err(_) => false,
+        };
+        let keep_trailing_space: bool = match ConfigParser::get_child(prompt_config_yaml, String::from("keep_trailing_space")) {
+            Ok(_) => ConfigParser::get_bool(prompt_config_yaml, String::from("keep_trailing_space"))?,
+            Err(_) => false,
+        };
+        let prompt_char_user: String = match ConfigParser::get_child(prompt_config_yaml, String::from("prompt_char_user")) {
+            Ok(_) => ConfigParser::get_string(prompt_config_yaml, String::from("prompt_char_user"))?,
+            Err(_) => String::from("$"),
+        };
+        let prompt_char_root: String = match ConfigParser::get_child(prompt_config_yaml, String::from("prompt_char_root")) {
+            Ok(_) => ConfigParser::get_string(prompt_config_yaml, String::from("prompt_char_root"))?,
+            Err(_) => String::from("#"),
+        };
+        let continuation: String = match ConfigParser::get_child(prompt_config_yaml, String::from("continuation")) {
+            Ok(_) => ConfigParser::get_string(prompt_config_yaml, String::from("continuation"))?,
+            Err(_) => String::from("> "),
+        };
+        //Async modules timeout
+        let module_timeout: usize = match ConfigParser::get_child(prompt_config_yaml, String::from("module_timeout_ms")) {
+            Ok(_) => ConfigParser::get_usize(prompt_config_yaml, String::from("module_timeout_ms"))?,
+            Err(_) => 100,
+        };
+        //Macros
+        let prompt_macros: HashMap<String, String> = match ConfigParser::get_child(prompt_config_yaml, String::from("macros")) {
+            Ok(node) => PromptConfig::parse_macros(node)?,
+            Err(_) => HashMap::new(),
+        };
         Ok(PromptConfig {
             prompt_line: prompt_line,
+            prompt_line_right,
             history_size: history_size,
             translate: translate,
             break_enabled: break_enabled,
@@ -435,25 +1474,83 @@ impl PromptConfig {
             min_duration: min_duration,
             rc_ok: rc_ok,
             rc_err: rc_err,
+            prompt_char_user,
+            prompt_char_root,
+            rc_code_always,
             git_branch: git_branch,
             git_commit_ref: git_commit_ref,
             git_commit_append: git_commit_append,
-            git_commit_prepend: git_commit_prepend
+            git_commit_prepend,
+            git_dirty,
+            git_staged,
+            git_cache_ttl,
+            time_format,
+            date_format,
+            duration_format,
+            duration_prefix,
+            venv_prefix,
+            battery_charging,
+            battery_discharging,
+            battery_low_threshold,
+            jobs_running,
+            jobs_stopped,
+            wrkdir_max_depth,
+            wrkdir_truncate_repo,
+            keep_trailing_space,
+            continuation,
+            module_timeout,
+            prompt_macros,
+            palette: PromptConfig::default_palette(),
         })
     }
+
+    /// ### parse_macros
+    ///
+    /// Parse the prompt macros, which map a key name (e.g. `GITLINE`) to the text it expands to.
+    /// Macros can't redefine the keys resolved by Pyc
+    fn parse_macros(macros_yaml: &Yaml) -> Result<HashMap<String, String>, ConfigError> {
+        let macros_yaml = match macros_yaml.as_hash() {
+            Some(macros) => macros,
+            None => return Err(ConfigError {
+                code: ConfigErrorCode::YamlSyntaxError,
+                message: String::from("'macros' key is not a map"),
+            }),
+        };
+        let mut macros: HashMap<String, String> = HashMap::new();
+        for (name, definition) in macros_yaml.iter() {
+            let (name, definition): (&str, &str) = match (name.as_str(), definition.as_str()) {
+                (Some(name), Some(definition)) => (name, definition),
+                _ => return Err(ConfigError {
+                    code: ConfigErrorCode::YamlSyntaxError,
+                    message: String::from("'macros' item is not a string pair"),
+                }),
+            };
+            if is_builtin_key(name) {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::YamlSyntaxError,
+                    message: format!("Macro '{}' collides with the builtin prompt key '${{{}}}'", name, name),
+                });
+            }
+            macros.insert(String::from(name), String::from(definition));
+        }
+        Ok(macros)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::translator::lang::Language;
+    use crate::translator::{new_translator, Translator};
     use std::io::Write;
 
     #[test]
     fn test_config_default() {
         let config: Config = Config::default();
         assert!(config.get_alias(&String::from("чд")).is_none());
-        assert_eq!(config.output_config.translate_output, true);
-        assert_eq!(config.language, String::from("ru"));
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: true, stderr: true });
+        assert!(!config.output_config.force_translate_pipe);
+        assert!(config.language.is_none());
         let prompt_config: PromptConfig = config.prompt_config;
         assert_eq!(prompt_config.prompt_line, String::from("${USER}@${HOSTNAME}:${WRKDIR}$"));
         assert_eq!(prompt_config.break_enabled, false);
@@ -462,12 +1559,34 @@ mod tests {
         assert_eq!(prompt_config.git_commit_ref, 8);
         assert_eq!(prompt_config.git_commit_prepend, None);
         assert_eq!(prompt_config.git_commit_append, None);
+        assert_eq!(prompt_config.git_dirty, String::from("✚"));
+        assert_eq!(prompt_config.git_staged, String::from("●"));
+        assert_eq!(prompt_config.git_cache_ttl, 30);
+        assert_eq!(prompt_config.jobs_running, String::from("⚙"));
+        assert_eq!(prompt_config.jobs_stopped, String::from("⏸"));
+        assert_eq!(prompt_config.time_format, String::from("%H:%M:%S"));
+        assert_eq!(prompt_config.date_format, String::from("%Y-%m-%d"));
+        assert!(prompt_config.duration_format.is_none());
+        assert!(prompt_config.duration_prefix.is_none());
+        assert_eq!(prompt_config.venv_prefix, String::from("🐍"));
+        assert_eq!(prompt_config.battery_charging, String::from("⚡"));
+        assert_eq!(prompt_config.battery_discharging, String::from("🔋"));
+        assert_eq!(prompt_config.battery_low_threshold, 20);
+        assert_eq!(prompt_config.module_timeout, 100);
+        assert_eq!(prompt_config.wrkdir_max_depth, 0);
+        assert!(!prompt_config.wrkdir_truncate_repo);
+        assert!(!prompt_config.keep_trailing_space);
+        assert_eq!(prompt_config.continuation, String::from("> "));
+        assert_eq!(prompt_config.prompt_char_user, String::from("$"));
+        assert_eq!(prompt_config.prompt_char_root, String::from("#"));
+        assert_eq!(prompt_config.prompt_line_right, String::new());
         assert_eq!(prompt_config.history_size, 256);
         assert_eq!(prompt_config.min_duration, 2000);
         assert_eq!(prompt_config.rc_err, String::from("✖"));
+        assert!(!prompt_config.rc_code_always);
         assert_eq!(prompt_config.rc_ok, String::from("✔"));
         assert_eq!(prompt_config.translate, false);
-        assert_eq!(config.shell_config.exec, String::from("bash"));
+        assert!(config.shell_config.exec.is_none());
         assert_eq!(config.shell_config.args.len(), 0);
     }
 
@@ -482,8 +1601,9 @@ mod tests {
         let config: Config = config.ok().unwrap();
         // Verify parameters
         assert!(config.get_alias(&String::from("чд")).is_some());
-        assert_eq!(config.output_config.translate_output, true);
-        assert_eq!(config.language, String::from("ru"));
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: true, stderr: true });
+        assert!(!config.output_config.force_translate_pipe);
+        assert!(config.language.is_none());
         let prompt_config: PromptConfig = config.prompt_config;
         assert_eq!(prompt_config.prompt_line, String::from("${USER}@${HOSTNAME}:${WRKDIR}$"));
         assert_eq!(prompt_config.break_enabled, false);
@@ -497,7 +1617,7 @@ mod tests {
         assert_eq!(prompt_config.rc_err, String::from("✖"));
         assert_eq!(prompt_config.rc_ok, String::from("✔"));
         assert_eq!(prompt_config.translate, false);
-        assert_eq!(config.shell_config.exec, String::from("bash"));
+        assert!(config.shell_config.exec.is_none());
         assert_eq!(config.shell_config.args.len(), 0);
         
     }
@@ -556,6 +1676,60 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_config_alias_accessors() {
+        let config: Config = Config::parse_config_str(String::from(
+            "alias:\n  - лс: ls\n  - лл: \"лс -л\"\n  - ла: \"лл -а\"\n  - ls: \"ls --color\"\n  - гс: \"git status\"\n  - гд: \"git diff\"\n  - гдс: \"гд --staged\"\n  - деплой:\n      cmd: \"ла /srv\"\n      cwd: /tmp\n  - пинг: понг\n  - понг: пинг\n",
+        ))
+        .ok()
+        .unwrap();
+        //Listing
+        let aliases: Vec<(String, String)> = config.get_aliases();
+        assert_eq!(aliases.len(), 10);
+        assert_eq!(aliases[0], (String::from("ls"), String::from("ls --color")));
+        assert_eq!(aliases[1], (String::from("гд"), String::from("git diff")));
+        assert_eq!(aliases[9], (String::from("понг"), String::from("пинг")));
+        assert!(aliases.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(Config::default().get_aliases().is_empty());
+        //Prefix search
+        assert_eq!(config.find_aliases("г"), vec![String::from("гд"), String::from("гдс"), String::from("гс")]);
+        assert_eq!(config.find_aliases("гд"), vec![String::from("гд"), String::from("гдс")]);
+        assert_eq!(config.find_aliases("л"), vec![String::from("ла"), String::from("лл"), String::from("лс")]);
+        assert!(config.find_aliases("x").is_empty());
+        assert_eq!(config.find_aliases("").len(), 10);
+        //Chains
+        assert_eq!(config.resolve_alias_chain("лс").unwrap(), Some(String::from("ls --color")));
+        assert_eq!(config.resolve_alias_chain("ла").unwrap(), Some(String::from("ls --color -л -а")));
+        assert_eq!(config.resolve_alias_chain("гдс").unwrap(), Some(String::from("git diff --staged")));
+        assert_eq!(config.resolve_alias_chain("деплой").unwrap(), Some(String::from("ls --color -л -а /srv")));
+        assert_eq!(config.resolve_alias_chain("ls").unwrap(), Some(String::from("ls --color")));
+        assert_eq!(config.resolve_alias_chain("cd").unwrap(), None);
+        //Cycle
+        assert_eq!(
+            config.resolve_alias_chain("пинг").err().unwrap(),
+            AliasError::Cycle(vec![String::from("пинг"), String::from("понг"), String::from("пинг")])
+        );
+        assert_eq!(
+            config.warnings(),
+            vec![
+                String::from("alias.пинг: alias cycle: пинг -> понг -> пинг"),
+                String::from("alias.понг: alias cycle: понг -> пинг -> понг"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config_alias_invalid_name() {
+        for name in ["\"л с\"", "\"it's\"", "'\"q\"'", "\"`x`\"", "\"\""].iter() {
+            let err: ConfigError = Config::parse_config_str(format!("alias:\n  - {}: ls\n", name)).err().unwrap();
+            assert_eq!(err.code, ConfigErrorCode::YamlSyntaxError);
+        }
+        let err: ConfigError = Config::parse_config_str(String::from("alias:\n  - лл: \"ls -l\"\n  - \"г с\": \"git status\"\n")).err().unwrap();
+        assert_eq!(err.message, String::from("alias 'г с': the name can't contain whitespace or quotes"));
+        let err: ConfigError = Config::parse_config_str(String::from("alias:\n  - \"a\\tb\":\n      cmd: ls\n")).err().unwrap();
+        assert_eq!(err.message, String::from("alias 'a\tb': the name can't contain whitespace or quotes"));
+    }
+
     #[test]
     fn test_config_no_alias() {
         //Try to parse a configuration file
@@ -573,20 +1747,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_config_alias_stanza() {
+        let config: String = String::from("alias:\n  - ll: \"ls -l\"\n  - deploy:\n      cmd: \"ansible-playbook site.yml\"\n      cwd: \"~/infra\"\n      env:\n        ANSIBLE_FORCE_COLOR: 1\n        MSG: \"привет\"\n  - top:\n      cmd: htop\n");
+        let config: Config = Config::parse_config_str(config).ok().unwrap();
+        //Plain string
+        assert_eq!(config.get_alias(&String::from("ll")).unwrap(), String::from("ls -l"));
+        assert!(!config.alias_options.contains_key("ll"));
+        //Stanza
+        assert_eq!(config.get_alias(&String::from("deploy")).unwrap(), String::from("ansible-playbook site.yml"));
+        assert_eq!(
+            config.alias_options.get("deploy").unwrap(),
+            &AliasOptions {
+                cwd: Some(String::from("~/infra")),
+                env: vec![(String::from("ANSIBLE_FORCE_COLOR"), String::from("1")), (String::from("MSG"), String::from("привет"))],
+            }
+        );
+        assert_eq!(config.get_alias(&String::from("top")).unwrap(), String::from("htop"));
+        assert_eq!(config.alias_options.get("top").unwrap(), &AliasOptions { cwd: None, env: Vec::new() });
+        //Unknown keys are errors
+        let err: ConfigError = Config::parse_config_str(String::from("alias:\n  - deploy:\n      cmd: make\n      cwdd: /tmp\n")).err().unwrap();
+        assert_eq!(err.code, ConfigErrorCode::YamlSyntaxError);
+        assert_eq!(err.message, String::from("alias[0].deploy.cwdd: unknown field, did you mean cwd? (line 4)"));
+        //Missing command
+        let err: ConfigError = Config::parse_config_str(String::from("alias:\n  - deploy:\n      cwd: /tmp\n")).err().unwrap();
+        assert_eq!(err.message, String::from("alias 'deploy': missing 'cmd'"));
+        //Invalid variable name
+        assert_eq!(
+            Config::parse_config_str(String::from("alias:\n  - deploy:\n      cmd: make\n      env:\n        1A: b\n")).err().unwrap().code,
+            ConfigErrorCode::YamlSyntaxError
+        );
+    }
+
     #[test]
     fn test_config_shell_config() {
         let config: String = String::from("shell:\n  exec: \"sh\"\n  args:\n    - \"-l\"\n    - \"-h\"\n");
         let config: Config = Config::parse_config_str(config).ok().unwrap();
-        assert_eq!(config.shell_config.exec, String::from("sh"));
+        assert_eq!(config.shell_config.exec, Some(String::from("sh")));
         assert_eq!(config.shell_config.args, vec![String::from("-l"), String::from("-h")]);
+        assert!(!config.shell_config.norc);
+        let config: String = String::from("shell:\n  exec: \"bash\"\n  norc: true\n");
+        let config: Config = Config::parse_config_str(config).ok().unwrap();
+        assert!(config.shell_config.norc);
+        //Arguments are passed to the detected shell when exec is not set
+        let config: String = String::from("shell:\n  args:\n    - \"--login\"\n");
+        let config: Config = Config::parse_config_str(config).ok().unwrap();
+        assert!(config.shell_config.exec.is_none());
+        assert_eq!(config.shell_config.args, vec![String::from("--login")]);
     }
 
     #[test]
     fn test_config_shell_config_bad() {
-        let config: String = String::from("shell:\n  args:\n    - \"-l\"\n    - \"-h\"\n");
-        assert!(Config::parse_config_str(config).is_err());
         let config: String = String::from("shell:\n  args: 5\n");
         assert!(Config::parse_config_str(config).is_err());
+        let config: String = String::from("shell:\n  exec: \"bash\"\n  norc: 5\n");
+        assert!(Config::parse_config_str(config).is_err());
     }
 
     #[test]
@@ -594,11 +1809,217 @@ mod tests {
         let config: String =
             String::from("alias:\n  - чд: \"cd\"\n  - пвд: \"pwd\"\n  - уич: \"which\"");
         let config: Config = Config::parse_config_str(config).ok().unwrap();
-        assert!(config.output_config.translate_output);
+        assert!(config.output_config.translate_stdout);
+        assert!(config.output_config.translate_stderr);
         //Try to parse a configuration file
         let config: String = String::from("output:\n  translate: false\n");
         let config: Config = Config::parse_config_str(config).ok().unwrap();
-        assert!(!config.output_config.translate_output);
+        assert!(!config.output_config.translate_stdout);
+        assert!(!config.output_config.translate_stderr);
+        assert!(!config.output_config.force_translate_pipe);
+        assert_eq!(config.output_config.translate_only.len(), 0);
+        assert_eq!(config.output_config.never_translate.len(), 0);
+        //Command lists
+        let config: String = String::from("output:\n  translate: true\n  translate_only:\n    - \"ls\"\n    - \"cat\"\n  never_translate:\n    - \"man\"\n");
+        let config: Config = Config::parse_config_str(config).ok().unwrap();
+        assert_eq!(config.output_config.translate_only, vec![String::from("ls"), String::from("cat")]);
+        assert_eq!(config.output_config.never_translate, vec![String::from("man")]);
+        assert!(!config.output_config.localize_units);
+        assert_eq!(config.output_config.no_translate_patterns.len(), DEFAULT_NO_TRANSLATE_PATTERNS.len());
+        assert_eq!(config.output_config.localize_dictionary.len(), 0);
+        assert!(!config.output_config.echo_command);
+        //Echo command
+        let config: Config = Config::parse_config_str(String::from("output:\n  translate: true\n  echo_command: true\n")).ok().unwrap();
+        assert!(config.output_config.echo_command);
+        assert_eq!(
+            Config::parse_config_str(String::from("output:\n  translate: true\n  echo_command: 1\n")).err().unwrap().code,
+            ConfigErrorCode::YamlSyntaxError
+        );
+        //Error color and prefix
+        assert!(config.output_config.error_color.is_none());
+        assert!(config.output_config.error_prefix.is_empty());
+        let config: Config = Config::parse_config_str(String::from("output:\n  translate: true\n  error_color: Yellow\n  error_prefix: \"⚠ \"\n")).ok().unwrap();
+        assert_eq!(config.output_config.error_color.as_deref(), Some("Yellow"));
+        assert_eq!(config.output_config.error_prefix.as_str(), "⚠ ");
+        let config: Config = Config::parse_config_str(String::from("output:\n  translate: true\n  error_color: 214\n")).ok().unwrap();
+        assert_eq!(config.output_config.error_color.as_deref(), Some("214"));
+        let config: Config = Config::parse_config_str(String::from("output:\n  translate: true\n  error_color: \"#ff8700\"\n")).ok().unwrap();
+        assert_eq!(config.output_config.error_color.as_deref(), Some("#ff8700"));
+        for bad in ["error_color: purple", "error_color: 300", "error_color: [red]", "error_prefix: [x]"].iter() {
+            assert_eq!(
+                Config::parse_config_str(format!("output:\n  translate: true\n  {}\n", bad)).err().unwrap().code,
+                ConfigErrorCode::YamlSyntaxError
+            );
+        }
+        //No translate patterns replace the default ones
+        let config: String = String::from("output:\n  translate: true\n  no_translate_patterns:\n    - \"^v[0-9]\"\n");
+        let config: Config = Config::parse_config_str(config).ok().unwrap();
+        assert_eq!(config.output_config.no_translate_patterns, vec![String::from("^v[0-9]")]);
+        let config: String = String::from("output:\n  translate: true\n  no_translate_patterns: []\n");
+        let config: Config = Config::parse_config_str(config).ok().unwrap();
+        assert!(config.output_config.no_translate_patterns.is_empty());
+        let config: String = String::from("output:\n  translate: true\n  no_translate_patterns:\n    - \"[a-\"\n");
+        assert_eq!(
+            Config::parse_config_str(config).err().unwrap().code,
+            ConfigErrorCode::YamlSyntaxError
+        );
+        //Localize units
+        let config: String = String::from("output:\n  translate: true\n  localize_units: true\n  localize_dictionary:\n    - Tue: \"втр\"\n    - Gi: \"Ги\"\n");
+        let config: Config = Config::parse_config_str(config).ok().unwrap();
+        assert!(config.output_config.localize_units);
+        assert_eq!(config.output_config.localize_dictionary.get("Tue").unwrap(), "втр");
+        assert_eq!(config.output_config.localize_dictionary.get("Gi").unwrap(), "Ги");
+        assert!(!config.output_config.error_hints);
+        assert_eq!(config.output_config.hints_dictionary.len(), 0);
+        //Error hints
+        let config: String = String::from("output:\n  translate: true\n  error_hints: true\n  hints_dictionary:\n    - \"disk quota exceeded\": \"превышена дисковая квота\"\n");
+        let config: Config = Config::parse_config_str(config).ok().unwrap();
+        assert!(config.output_config.error_hints);
+        assert_eq!(config.output_config.hints_dictionary.get("disk quota exceeded").unwrap(), "превышена дисковая квота");
+    }
+
+    #[test]
+    fn test_config_input_config() {
+        //Default
+        let config: Config = Config::parse_config_str(String::from("language: ru\n")).ok().unwrap();
+        assert!(config.input_config.preserve_redirect_targets);
+        assert!(config.input_config.preserve_output_args.contains(&String::from("curl")));
+        assert!(config.input_config.strict_boundaries);
+        assert!(config.input_config.expand_variables);
+        assert!(!config.input_config.error_on_unset);
+        assert!(!config.input_config.nullglob);
+        //Custom
+        let config: String = String::from("input:\n  preserve_redirect_targets: false\n  strict_boundaries: false\n  expand_variables: false\n  error_on_unset: true\n  nullglob: true\n  preserve_output_args:\n    - \"ffmpeg\"\n");
+        let config: Config = Config::parse_config_str(config).ok().unwrap();
+        assert!(!config.input_config.preserve_redirect_targets);
+        assert!(!config.input_config.strict_boundaries);
+        assert!(!config.input_config.expand_variables);
+        assert!(config.input_config.error_on_unset);
+        assert!(config.input_config.nullglob);
+        assert_eq!(config.input_config.preserve_output_args, vec![String::from("ffmpeg")]);
+        //Only one key
+        let config: Config = Config::parse_config_str(String::from("input:\n  preserve_redirect_targets: false\n")).ok().unwrap();
+        assert!(config.input_config.preserve_output_args.contains(&String::from("wget")));
+        assert_eq!(config.input_config.literal_delimiters, Some((String::from("«"), String::from("»"))));
+        //Literal delimiters
+        let config: Config = Config::parse_config_str(String::from("input:\n  literal_delimiters: [\"{{\", \"}}\"]\n")).ok().unwrap();
+        assert_eq!(config.input_config.literal_delimiters, Some((String::from("{{"), String::from("}}"))));
+        let config: Config = Config::parse_config_str(String::from("input:\n  literal_delimiters: []\n")).ok().unwrap();
+        assert!(config.input_config.literal_delimiters.is_none());
+        assert!(Config::parse_config_str(String::from("input:\n  literal_delimiters: [\"«\"]\n")).is_err());
+        assert!(Config::parse_config_str(String::from("input:\n  literal_delimiters: [\"\", \"»\"]\n")).is_err());
+        //Input translation
+        assert_eq!(config.input_config.translation, InputTranslation::Always);
+        let config: Config = Config::parse_config_str(String::from("input:\n  translation: command-line-only\n")).ok().unwrap();
+        assert_eq!(config.input_config.translation, InputTranslation::CommandLineOnly);
+        assert!(config.input_config.translation.command_line());
+        assert!(!config.input_config.translation.subprocess());
+        let config: Config = Config::parse_config_str(String::from("input:\n  translation: never\n")).ok().unwrap();
+        assert_eq!(config.input_config.translation, InputTranslation::Never);
+        assert!(!config.input_config.translation.command_line());
+        assert_eq!(config.input_config.translation.to_string(), String::from("never"));
+        assert_eq!(
+            Config::parse_config_str(String::from("input:\n  translation: sometimes\n")).err().unwrap().message,
+            String::from("'translation' must be one of 'always', 'never', 'command-line-only' (found 'sometimes')")
+        );
+        //Bad values
+        assert!(Config::parse_config_str(String::from("input:\n  preserve_redirect_targets: 5\n")).is_err());
+        assert!(Config::parse_config_str(String::from("input:\n  preserve_output_args: curl\n")).is_err());
+        assert!(Config::parse_config_str(String::from("input:\n  strict_boundaries: 1\n")).is_err());
+        assert!(Config::parse_config_str(String::from("input:\n  error_on_unset: \"no\"\n")).is_err());
+        assert!(Config::parse_config_str(String::from("input:\n  nullglob: []\n")).is_err());
+    }
+
+    #[test]
+    fn test_config_output_translate_command() {
+        let mut config: OutputConfig = OutputConfig::default();
+        let both = OutputTranslation { stdout: true, stderr: true };
+        let none = OutputTranslation { stdout: false, stderr: false };
+        //Everything is translated by default
+        assert_eq!(config.translate_command("ls"), both);
+        assert_eq!(config.translate_command("man"), both);
+        //Never translate
+        config.never_translate = vec![String::from("man")];
+        assert_eq!(config.translate_command("ls"), both);
+        assert_eq!(config.translate_command("man"), none);
+        //Translate only
+        config.translate_only = vec![String::from("ls"), String::from("man")];
+        assert_eq!(config.translate_command("ls"), both);
+        assert_eq!(config.translate_command("cat"), none);
+        //Never translate wins over translate only
+        assert_eq!(config.translate_command("man"), none);
+        //Stderr translation disabled
+        config.translate_stderr = false;
+        assert_eq!(config.translate_command("ls"), OutputTranslation { stdout: true, stderr: false });
+        assert_eq!(config.translate_command("man"), none);
+        //Translation disabled
+        config.translate_stdout = false;
+        assert_eq!(config.translate_command("ls"), none);
+    }
+
+    #[test]
+    fn test_config_output_translate_streams() {
+        //Deprecated 'translate' sets both streams
+        let config: Config = Config::parse_config_str(String::from("output:\n  translate: false\n")).ok().unwrap();
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: false, stderr: false });
+        //Streams set explicitly win over 'translate'
+        let config: Config = Config::parse_config_str(String::from("output:\n  translate: true\n  translate_stdout: false\n")).ok().unwrap();
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: false, stderr: true });
+        let config: Config = Config::parse_config_str(String::from("output:\n  translate: false\n  translate_stderr: true\n")).ok().unwrap();
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: false, stderr: true });
+        //Streams without 'translate'
+        let config: Config = Config::parse_config_str(String::from("output:\n  translate_stdout: true\n  translate_stderr: false\n  force_translate_pipe: true\n")).ok().unwrap();
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: true, stderr: false });
+        assert!(config.output_config.force_translate_pipe);
+        //A stream is missing
+        let config: String = String::from("output:\n  translate_stdout: true\n");
+        assert_eq!(Config::parse_config_str(config).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+        //Bad values
+        let config: String = String::from("output:\n  translate_stdout: 1\n  translate_stderr: true\n");
+        assert_eq!(Config::parse_config_str(config).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+        let config: String = String::from("output:\n  translate: true\n  force_translate_pipe: \"yes\"\n");
+        assert_eq!(Config::parse_config_str(config).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+    }
+
+    #[test]
+    fn test_config_output_apply_pipe() {
+        //Stdout is a terminal
+        let mut config: OutputConfig = OutputConfig::default();
+        config.apply_pipe(|_| true);
+        assert_eq!(config.translation(), OutputTranslation { stdout: true, stderr: true });
+        //Stdout is piped: only stderr is translated
+        config.apply_pipe(|fd| fd != 1);
+        assert_eq!(config.translation(), OutputTranslation { stdout: false, stderr: true });
+        //Forced translation
+        let mut config: OutputConfig = OutputConfig::default();
+        config.force_translate_pipe = true;
+        config.apply_pipe(|_| false);
+        assert_eq!(config.translation(), OutputTranslation { stdout: true, stderr: true });
+    }
+
+    #[test]
+    fn test_config_apply_pipe_overrides() {
+        //Without overrides stdout translation is disabled when piped
+        let mut config: Config = Config::default();
+        config.apply_overrides(ConfigOverrides::default());
+        config.apply_pipe(|_| false);
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: false, stderr: true });
+        //--translate-output wins over the pipe detection
+        let mut config: Config = Config::parse_config_str(String::from("output:\n  translate: false\n")).ok().unwrap();
+        config.apply_overrides(ConfigOverrides {
+            translate_output: Some(true),
+            input_translation: None,
+        });
+        config.apply_pipe(|_| false);
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: true, stderr: true });
+        //--no-translate-output
+        let mut config: Config = Config::default();
+        config.apply_overrides(ConfigOverrides {
+            translate_output: Some(false),
+            input_translation: None,
+        });
+        config.apply_pipe(|_| true);
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: false, stderr: false });
     }
 
     #[test]
@@ -618,29 +2039,168 @@ mod tests {
             Config::parse_config_str(config).err().unwrap().code,
             ConfigErrorCode::YamlSyntaxError
         );
+        let config: String = String::from("output:\n  translate: true\n  translate_only: ls\n");
+        assert_eq!(
+            Config::parse_config_str(config).err().unwrap().code,
+            ConfigErrorCode::YamlSyntaxError
+        );
+        let config: String = String::from("output:\n  translate: true\n  never_translate:\n    - 5\n");
+        assert_eq!(
+            Config::parse_config_str(config).err().unwrap().code,
+            ConfigErrorCode::YamlSyntaxError
+        );
+        let config: String = String::from("output:\n  translate: true\n  localize_units: 5\n");
+        assert_eq!(
+            Config::parse_config_str(config).err().unwrap().code,
+            ConfigErrorCode::YamlSyntaxError
+        );
+        let config: String = String::from("output:\n  translate: true\n  localize_dictionary:\n    - Tue\n");
+        assert_eq!(
+            Config::parse_config_str(config).err().unwrap().code,
+            ConfigErrorCode::YamlSyntaxError
+        );
+        let config: String = String::from("output:\n  translate: true\n  error_hints: \"yes\"\n");
+        assert_eq!(
+            Config::parse_config_str(config).err().unwrap().code,
+            ConfigErrorCode::YamlSyntaxError
+        );
     }
 
     #[test]
     fn test_config_language() {
         let config: String = String::from("language: bg\n");
         let config: Config = Config::parse_config_str(config).ok().unwrap();
-        assert_eq!(config.language, String::from("bg"));
+        assert_eq!(config.language.as_deref(), Some("bg"));
     }
 
     #[test]
     fn test_config_language_missing() {
         let config: String = String::from("output:\n  translate: false\n");
         let config: Config = Config::parse_config_str(config).ok().unwrap();
-        assert_eq!(config.language, String::from("ru"));
+        assert!(config.language.is_none());
+    }
+
+    #[test]
+    fn test_config_language_standard() {
+        let config: Config = Config::parse_config_str(String::from("language:\n  name: ua\n  standard: bgn-pcgn\n")).ok().unwrap();
+        assert_eq!(config.language.as_deref(), Some("ua"));
+        assert_eq!(config.language_standard, String::from("bgn-pcgn"));
+        //Defaults
+        let config: Config = Config::parse_config_str(String::from("language:\n  standard: scientific\n")).ok().unwrap();
+        assert!(config.language.is_none());
+        assert_eq!(config.language_standard, String::from("scientific"));
+        let config: Config = Config::parse_config_str(String::from("language: bg\n")).ok().unwrap();
+        assert_eq!(config.language_standard, String::from("gost"));
+        assert_eq!(Config::default().language_standard, String::from("gost"));
+    }
+
+    #[test]
+    fn test_config_language_strict() {
+        assert!(!Config::default().language_strict);
+        let config: Config = Config::parse_config_str(String::from("language:\n  name: ua\n  strict: true\n")).ok().unwrap();
+        assert_eq!(config.language.as_deref(), Some("ua"));
+        assert!(config.language_strict);
+        let config: Config = Config::parse_config_str(String::from("language: ru\n")).ok().unwrap();
+        assert!(!config.language_strict);
+        assert_eq!(Config::parse_config_str(String::from("language:\n  strict: maybe\n")).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
     }
 
     #[test]
     #[should_panic]
     fn test_config_language_badvalue() {
-        let config: String = String::from("language:\n  name: ru\n");
+        let config: String = String::from("language:\n  - ru\n");
         assert!(Config::parse_config_str(config).is_ok());
     }
 
+    #[test]
+    fn test_config_banner() {
+        assert!(Config::default().banner);
+        assert!(Config::parse_config_str(String::from("language: ru\n")).ok().unwrap().banner);
+        assert!(!Config::parse_config_str(String::from("banner: false\n")).ok().unwrap().banner);
+        assert_eq!(Config::parse_config_str(String::from("banner: no way\n")).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+    }
+
+    #[test]
+    fn test_config_assume_width() {
+        assert!(Config::default().assume_width.is_none());
+        assert_eq!(Config::parse_config_str(String::from("assume_width: 120\n")).ok().unwrap().assume_width, Some(120));
+        assert_eq!(Config::parse_config_str(String::from("assume_width: wide\n")).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+    }
+
+    #[test]
+    fn test_config_core_config() {
+        let config: Config = Config::default();
+        assert_eq!(config.core_config.log_level, LogLevel::Warn);
+        assert!(config.core_config.log_file.is_none());
+        let config: Config = Config::parse_config_str(String::from("core:\n  log_level: debug\n  log_file: /tmp/pyc.log\n")).ok().unwrap();
+        assert_eq!(config.core_config.log_level, LogLevel::Debug);
+        assert_eq!(config.core_config.log_file, Some(PathBuf::from("/tmp/pyc.log")));
+        let config: Config = Config::parse_config_str(String::from("core:\n  log_level: info\n")).ok().unwrap();
+        assert_eq!(config.core_config.log_level, LogLevel::Info);
+        assert!(config.core_config.log_file.is_none());
+        //Bad values
+        assert_eq!(Config::parse_config_str(String::from("core:\n  log_level: verbose\n")).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+        assert_eq!(Config::parse_config_str(String::from("core:\n  log_file: 5\n")).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+    }
+
+    #[test]
+    fn test_config_core_sigint_escalation() {
+        assert_eq!(Config::default().core_config.sigint_escalation, SigintEscalation { presses: 3, window_ms: 2000, kill_after_ms: 3000 });
+        let config: Config = Config::parse_config_str(String::from("core:\n  sigint_escalation:\n    presses: 5\n    window_ms: 1000\n    kill_after_ms: 500\n")).ok().unwrap();
+        assert_eq!(config.core_config.sigint_escalation, SigintEscalation { presses: 5, window_ms: 1000, kill_after_ms: 500 });
+        //Missing keys keep their default
+        let config: Config = Config::parse_config_str(String::from("core:\n  sigint_escalation:\n    presses: 0\n")).ok().unwrap();
+        assert_eq!(config.core_config.sigint_escalation, SigintEscalation { presses: 0, window_ms: 2000, kill_after_ms: 3000 });
+        //Bad values
+        assert_eq!(Config::parse_config_str(String::from("core:\n  sigint_escalation:\n    presses: many\n")).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+    }
+
+    #[test]
+    fn test_config_terminal() {
+        assert!(!Config::default().terminal_config.clear_scrollback);
+        let config: Config = Config::parse_config_str(String::from("terminal:\n  clear_scrollback: true\n")).ok().unwrap();
+        assert!(config.terminal_config.clear_scrollback);
+        let config: Config = Config::parse_config_str(String::from("terminal: {}\n")).ok().unwrap();
+        assert!(!config.terminal_config.clear_scrollback);
+        assert_eq!(Config::parse_config_str(String::from("terminal:\n  clear_scrollback: 1\n")).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+    }
+
+    #[test]
+    fn test_config_core_command_timeout() {
+        assert_eq!(Config::default().core_config.command_timeout, 0);
+        let config: Config = Config::parse_config_str(String::from("core:\n  command_timeout: 30\n")).ok().unwrap();
+        assert_eq!(config.core_config.command_timeout, 30);
+        assert_eq!(Config::parse_config_str(String::from("core:\n  command_timeout: soon\n")).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+    }
+
+    #[test]
+    fn test_config_core_use_pty() {
+        assert!(Config::default().core_config.use_pty);
+        let config: Config = Config::parse_config_str(String::from("core:\n  use_pty: false\n")).ok().unwrap();
+        assert!(!config.core_config.use_pty);
+        assert_eq!(Config::parse_config_str(String::from("core:\n  use_pty: \"yes\"\n")).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+    }
+
+    #[test]
+    fn test_config_core_rc_file() {
+        let home: Option<PathBuf> = dirs::home_dir();
+        assert_eq!(Config::default().core_config.rc_file, home.as_ref().map(|home| home.join(".config/pyc/pycrc")));
+        let config: Config = Config::parse_config_str(String::from("core:\n  rc_file: /tmp/pycrc\n")).ok().unwrap();
+        assert_eq!(config.core_config.rc_file, Some(PathBuf::from("/tmp/pycrc")));
+        let config: Config = Config::parse_config_str(String::from("core:\n  rc_file: ~/scripts/pycrc\n")).ok().unwrap();
+        assert_eq!(config.core_config.rc_file, home.as_ref().map(|home| home.join("scripts/pycrc")));
+        //Empty path disables the rc file
+        let config: Config = Config::parse_config_str(String::from("core:\n  rc_file: \"\"\n")).ok().unwrap();
+        assert!(config.core_config.rc_file.is_none());
+        assert_eq!(Config::parse_config_str(String::from("core:\n  rc_file: 5\n")).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+    }
+
+    #[test]
+    fn test_config_language_standard_badvalue() {
+        let config: String = String::from("language:\n  name: ru\n  standard: 1\n");
+        assert_eq!(Config::parse_config_str(config).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+    }
+
     #[test]
     fn test_config_prompt_default() {
         let config: String = String::from("language:\n  ru\n");
@@ -658,10 +2218,43 @@ mod tests {
         assert_eq!(prompt_config.translate, false);
     }
 
+    #[test]
+    fn test_config_overrides() {
+        //Default
+        let mut config: Config = Config::default();
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: true, stderr: true });
+        assert_eq!(config.input_config.translation, InputTranslation::Always);
+        //Empty overrides keep the configuration
+        config.apply_overrides(ConfigOverrides::default());
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: true, stderr: true });
+        assert_eq!(config.input_config.translation, InputTranslation::Always);
+        //File over default
+        let mut config: Config = Config::parse_config_str(String::from("output:\n  translate: false\ninput:\n  translation: command-line-only\n")).ok().unwrap();
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: false, stderr: false });
+        assert_eq!(config.input_config.translation, InputTranslation::CommandLineOnly);
+        //Command line over file
+        config.apply_overrides(ConfigOverrides {
+            translate_output: Some(true),
+            input_translation: Some(InputTranslation::Never),
+        });
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: true, stderr: true });
+        assert_eq!(config.input_config.translation, InputTranslation::Never);
+        assert_eq!(config.overrides.translate_output, Some(true));
+        //Only the options which have been set are overridden
+        let mut config: Config = Config::parse_config_str(String::from("output:\n  translate: false\ninput:\n  translation: command-line-only\n")).ok().unwrap();
+        config.apply_overrides(ConfigOverrides {
+            translate_output: None,
+            input_translation: Some(InputTranslation::Always),
+        });
+        assert_eq!(config.output_config.translation(), OutputTranslation { stdout: false, stderr: false });
+        assert_eq!(config.input_config.translation, InputTranslation::Always);
+    }
+
     #[test]
     fn test_config_prompt() {
-        let config: String = String::from("prompt:\n  prompt_line: \"${USER} on ${HOSTNAME} in ${WRKDIR} ${GIT_BRANCH} (${GIT_COMMIT}) ${CMD_TIME}\"\n  history_size: 1024\n  translate: true\n  break:\n    enabled: false\n    with: \">\"\n  duration:\n    min_elapsed_time: 5000\n  rc:\n    ok: \"^_^\"\n    error: \"x_x\"\n  git:\n    branch: \"on \"\n    commit_ref_len: 4\n    commit_prepend: \"(\"\n    commit_append: \")\"\n");
+        let config: String = String::from("prompt:\n  prompt_line: \"${USER} on ${HOSTNAME} in ${WRKDIR} ${GIT_BRANCH} (${GIT_COMMIT}) ${CMD_TIME}\"\n  history_size: 1024\n  translate: true\n  time_format: \"%H:%M\"\n  date_format: \"%d.%m.%Y\"\n  duration_format: \"{secs} sec\"\n  duration_prefix: \"⏱ \"\n  module_timeout_ms: 250\n  wrkdir_max_depth: 3\n  wrkdir_truncate_repo: true\n  keep_trailing_space: true\n  continuation: \"… \"\n  prompt_char_user: \"❯\"\n  prompt_char_root: \"${KRED}#${KRST}\"\n  prompt_line_right: \"${TIME} ${RC}\"\n  break:\n    enabled: false\n    with: \">\"\n  duration:\n    min_elapsed_time: 5000\n  rc:\n    ok: \"^_^\"\n    error: \"x_x\"\n    code_always: true\n  git:\n    branch: \"on \"\n    commit_ref_len: 4\n    commit_prepend: \"(\"\n    commit_append: \")\"\n    dirty: \"*\"\n    staged: \"+\"\n    cache_ttl: 5\n  venv:\n    prefix: \"py:\"\n  battery:\n    charging: \"+\"\n    discharging: \"-\"\n    low_threshold: 10\n  jobs:\n    running: \"&\"\n    stopped: \"${C:warning}!${KRST}\"\n");
         let config: Config = Config::parse_config_str(config).ok().unwrap();
+        assert_eq!(config.warnings(), vec![String::from("prompt.jobs.stopped: unknown palette color 'warning'")]);
         //Verify config parameters
         let prompt_config: PromptConfig = config.prompt_config;
         assert_eq!(prompt_config.prompt_line, String::from("${USER} on ${HOSTNAME} in ${WRKDIR} ${GIT_BRANCH} (${GIT_COMMIT}) ${CMD_TIME}"));
@@ -671,9 +2264,31 @@ mod tests {
         assert_eq!(prompt_config.git_commit_ref, 4);
         assert_eq!(prompt_config.git_commit_prepend, Some(String::from("(")));
         assert_eq!(prompt_config.git_commit_append, Some(String::from(")")));
+        assert_eq!(prompt_config.git_dirty, String::from("*"));
+        assert_eq!(prompt_config.git_staged, String::from("+"));
+        assert_eq!(prompt_config.git_cache_ttl, 5);
+        assert_eq!(prompt_config.time_format, String::from("%H:%M"));
+        assert_eq!(prompt_config.date_format, String::from("%d.%m.%Y"));
+        assert_eq!(prompt_config.duration_format.as_ref().unwrap(), "{secs} sec");
+        assert_eq!(prompt_config.duration_prefix.as_ref().unwrap(), "⏱ ");
+        assert_eq!(prompt_config.venv_prefix, String::from("py:"));
+        assert_eq!(prompt_config.battery_charging, String::from("+"));
+        assert_eq!(prompt_config.battery_discharging, String::from("-"));
+        assert_eq!(prompt_config.battery_low_threshold, 10);
+        assert_eq!(prompt_config.jobs_running, String::from("&"));
+        assert_eq!(prompt_config.jobs_stopped, String::from("${C:warning}!${KRST}"));
+        assert_eq!(prompt_config.module_timeout, 250);
+        assert_eq!(prompt_config.wrkdir_max_depth, 3);
+        assert!(prompt_config.wrkdir_truncate_repo);
+        assert!(prompt_config.keep_trailing_space);
+        assert_eq!(prompt_config.continuation, String::from("… "));
+        assert_eq!(prompt_config.prompt_char_user, String::from("❯"));
+        assert_eq!(prompt_config.prompt_char_root, String::from("${KRED}#${KRST}"));
+        assert_eq!(prompt_config.prompt_line_right, String::from("${TIME} ${RC}"));
         assert_eq!(prompt_config.history_size, 1024);
         assert_eq!(prompt_config.min_duration, 5000);
         assert_eq!(prompt_config.rc_err, String::from("x_x"));
+        assert!(prompt_config.rc_code_always);
         assert_eq!(prompt_config.rc_ok, String::from("^_^"));
         assert_eq!(prompt_config.translate, true);
     }
@@ -710,6 +2325,104 @@ mod tests {
         assert!(Config::parse_config_str(config).is_err());
     }
 
+    #[test]
+    fn test_config_palette() {
+        //Default palette
+        let config: Config = Config::parse_config_str(String::from("language: ru\n")).ok().unwrap();
+        assert_eq!(config.prompt_config.palette.get("primary").unwrap(), "yellow");
+        assert_eq!(config.prompt_config.palette.get("error").unwrap(), "red");
+        assert!(config.warnings().is_empty());
+        //Custom palette is merged over the default one
+        let config: String = String::from("palette:\n  primary: \"bold #ff8700\"\n  warning: 214\nprompt:\n  prompt_line: \"${C:primary}${USER} ${C:warning}${C:error}${C:nope}\"\n  history_size: 256\n  translate: false\n  break:\n    enabled: false\n    with: \">\"\n  duration:\n    min_elapsed_time: 2000\n  rc:\n    ok: \"${C:success}^_^\"\n    error: \"${C:fail}x_x\"\n  git:\n    branch: \"on \"\n    commit_ref_len: 4\n");
+        let config: Config = Config::parse_config_str(config).ok().unwrap();
+        assert_eq!(config.prompt_config.palette.get("primary").unwrap(), "bold #ff8700");
+        assert_eq!(config.prompt_config.palette.get("warning").unwrap(), "214");
+        assert_eq!(config.prompt_config.palette.get("error").unwrap(), "red");
+        //Unknown names are reported as warnings
+        assert_eq!(
+            config.warnings(),
+            vec![
+                String::from("prompt.prompt_line: unknown palette color 'nope'"),
+                String::from("prompt.rc.error: unknown palette color 'fail'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config_palette_bad() {
+        let config: String = String::from("palette:\n  primary: purple\n");
+        assert_eq!(Config::parse_config_str(config).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+        let config: String = String::from("palette:\n  - primary: red\n");
+        assert_eq!(Config::parse_config_str(config).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+        let config: String = String::from("palette:\n  primary: [red]\n");
+        assert_eq!(Config::parse_config_str(config).err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+    }
+
+    #[test]
+    fn test_config_prompt_battery() {
+        //Symbols keep their default when not set
+        let config: String = String::from("prompt:\n  prompt_line: \"${BATTERY}\"\n  history_size: 1024\n  translate: false\n  break:\n    enabled: false\n    with: \">\"\n  duration:\n    min_elapsed_time: 5000\n  rc:\n    ok: \"^_^\"\n    error: \"x_x\"\n  git:\n    branch: \"on \"\n    commit_ref_len: 4\n  battery:\n    low_threshold: 15\n");
+        let prompt_config: PromptConfig = Config::parse_config_str(config).ok().unwrap().prompt_config;
+        assert_eq!(prompt_config.battery_charging, String::from("⚡"));
+        assert_eq!(prompt_config.battery_discharging, String::from("🔋"));
+        assert_eq!(prompt_config.battery_low_threshold, 15);
+        //Threshold is a percentage
+        let config: String = String::from("prompt:\n  prompt_line: \"${BATTERY}\"\n  history_size: 1024\n  translate: false\n  break:\n    enabled: false\n    with: \">\"\n  duration:\n    min_elapsed_time: 5000\n  rc:\n    ok: \"^_^\"\n    error: \"x_x\"\n  git:\n    branch: \"on \"\n    commit_ref_len: 4\n  battery:\n    low_threshold: 150\n");
+        let err: ConfigError = Config::parse_config_str(config).err().unwrap();
+        assert_eq!(err.code, ConfigErrorCode::YamlSyntaxError);
+        assert_eq!(err.message, String::from("'low_threshold' must be a percentage between 0 and 100 (found 150)"));
+    }
+
+    #[test]
+    fn test_config_prompt_macros() {
+        let prompt: &str = "prompt:\n  prompt_line: \"${GITLINE}\"\n  history_size: 256\n  translate: false\n  break:\n    enabled: false\n    with: \">\"\n  duration:\n    min_elapsed_time: 2000\n  rc:\n    ok: \"^_^\"\n    error: \"x_x\"\n  git:\n    branch: \"on \"\n    commit_ref_len: 4\n";
+        let config: Config = Config::parse_config_str(format!("{}  macros:\n    GITLINE: \"${{KMAG}}${{GIT_BRANCH}}${{KRST}}@${{GIT_COMMIT}}\"\n", prompt)).ok().unwrap();
+        assert_eq!(config.prompt_config.prompt_macros.get("GITLINE").unwrap(), "${KMAG}${GIT_BRANCH}${KRST}@${GIT_COMMIT}");
+        assert!(config.warnings().is_empty());
+        assert!(Config::default().prompt_config.prompt_macros.is_empty());
+        //Builtin keys can't be redefined
+        let err: ConfigError = Config::parse_config_str(format!("{}  macros:\n    USER: root\n", prompt)).err().unwrap();
+        assert_eq!(err.code, ConfigErrorCode::YamlSyntaxError);
+        assert_eq!(err.message, String::from("Macro 'USER' collides with the builtin prompt key '${USER}'"));
+        assert!(Config::parse_config_str(format!("{}  macros:\n    - GITLINE: x\n", prompt)).is_err());
+        //Cycles are warnings
+        let config: Config = Config::parse_config_str(format!("{}  macros:\n    GITLINE: \"${{LOOP}}\"\n    LOOP: \"${{GITLINE}}\"\n", prompt)).ok().unwrap();
+        assert_eq!(
+            config.warnings(),
+            vec![
+                String::from("prompt.macros.GITLINE: macro is recursive or nested more than 8 times; it renders empty"),
+                String::from("prompt.macros.LOOP: macro is recursive or nested more than 8 times; it renders empty"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config_validation() {
+        //Unknown keys are warnings
+        let config_file: tempfile::NamedTempFile = write_config_file("language: ru\noutput:\n  translate: true\n  translte_only:\n    - ls\n");
+        let config: Config = Config::parse_config(config_file.path().to_path_buf()).ok().unwrap();
+        assert_eq!(config.unknown_keys.len(), 1);
+        assert_eq!(config.unknown_keys[0].code, ConfigErrorCode::UnknownKey);
+        assert_eq!(config.warnings(), vec![String::from("output.translte_only: unknown field, did you mean translate_only? (line 4)")]);
+        //Type mismatches are errors, all of them are reported
+        let config_file: tempfile::NamedTempFile = write_config_file("language: ru\ninput:\n  nullglob: \"yes\"\n  error_on_unset: 1\n  expan_variables: true\n");
+        let err: ConfigError = Config::parse_config(config_file.path().to_path_buf()).err().unwrap();
+        assert_eq!(err.code, ConfigErrorCode::YamlSyntaxError);
+        assert_eq!(
+            err.message,
+            String::from("input.nullglob: expected a bool, found a string (line 3)\ninput.error_on_unset: expected a bool, found an integer (line 4)")
+        );
+        //A misspelled optional key is a warning: the default is used
+        let config_file: tempfile::NamedTempFile = write_config_file("shell:\n  exce: bash\n");
+        let config: Config = Config::parse_config(config_file.path().to_path_buf()).ok().unwrap();
+        assert!(config.shell_config.exec.is_none());
+        assert_eq!(config.warnings(), vec![String::from("shell.exce: unknown field, did you mean exec? (line 2)")]);
+        //Syntax errors have a position
+        let config_file: tempfile::NamedTempFile = write_config_file("language: ru\nalias:\n  - ll: \"ls -l\n");
+        let err: ConfigError = Config::parse_config(config_file.path().to_path_buf()).err().unwrap();
+        assert!(err.message.contains("line 3"));
+    }
+
     #[test]
     fn test_config_bad_syntax() {
         let config: String = String::from("foobar: 5:\n");
@@ -730,19 +2443,50 @@ mod tests {
 
     #[test]
     fn test_config_error_display() {
-        println!(
-            "{};{};{}",
-            ConfigErrorCode::CouldNotReadFile,
-            ConfigErrorCode::NoSuchFileOrDirectory,
-            ConfigErrorCode::YamlSyntaxError
+        assert_eq!(ConfigErrorCode::CouldNotReadFile.to_string(), String::from("CouldNotReadFile"));
+        assert_eq!(ConfigErrorCode::NoSuchFileOrDirectory.to_string(), String::from("NoSuchFileOrDirectory"));
+        assert_eq!(ConfigErrorCode::YamlSyntaxError.to_string(), String::from("YamlSyntaxError"));
+        assert_eq!(ConfigErrorCode::UnknownKey.to_string(), String::from("UnknownKey"));
+        let err: ConfigError = ConfigError {
+            code: ConfigErrorCode::NoSuchFileOrDirectory,
+            message: String::from("No such file or directory ~/.config/pyc/pyc.yml"),
+        };
+        assert_eq!(err.to_string(), String::from("No such file or directory ~/.config/pyc/pyc.yml (NoSuchFileOrDirectory)"));
+        //Can be boxed
+        let err: Box<dyn std::error::Error> = Box::new(err);
+        assert_eq!(err.to_string(), String::from("No such file or directory ~/.config/pyc/pyc.yml (NoSuchFileOrDirectory)"));
+    }
+
+    #[test]
+    fn test_config_error_localized() {
+        let translator: Box<dyn Translator> = new_translator(Language::Russian);
+        let localized = |code: ConfigErrorCode, message: &str| ConfigError { code, message: String::from(message) }.localized(translator.as_ref());
+        assert_eq!(
+            localized(ConfigErrorCode::CouldNotReadFile, "Could not read 'pyc.yml'"),
+            String::from("Коулд нот реад 'pyc.yml' (КоулдНотРеадФиле)")
         );
-        println!(
-            "{}",
-            ConfigError {
-                code: ConfigErrorCode::NoSuchFileOrDirectory,
-                message: String::from("No such file or directory ~/.config/pyc/pyc.yml")
-            }
+        assert_eq!(
+            localized(ConfigErrorCode::NoSuchFileOrDirectory, "No such file"),
+            String::from("Но суч филе (НоСучФилеОрДиректоры)")
         );
+        //Names between quotes are kept as they are
+        assert_eq!(
+            localized(ConfigErrorCode::YamlSyntaxError, "'language' is not a string"),
+            String::from("'language' ис нот а стринг (ЫамлСынтаксЕррор)")
+        );
+        assert_eq!(
+            localized(ConfigErrorCode::UnknownKey, "alias 'г с': unknown field"),
+            String::from("аляс 'г с': ункноун фиелд (УнкноунКеы)")
+        );
+    }
+
+    /// ### write_config_file
+    ///
+    /// Write the provided configuration to a temporary file
+    fn write_config_file(config: &str) -> tempfile::NamedTempFile {
+        let mut tmpfile: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        write!(tmpfile, "{}", config).unwrap();
+        tmpfile
     }
 
     /// ### write_config_file_en
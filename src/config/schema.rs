@@ -0,0 +1,488 @@
+//! ## Schema
+//!
+//! `schema` describes the structure of the configuration file and validates the parsed YAML against it,
+//! reporting unknown keys and type mismatches with their path and line
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate yaml_rust;
+
+use super::{ConfigError, ConfigErrorCode};
+use crate::shell::suggestion::edit_distance;
+use std::collections::HashMap;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+use yaml_rust::Yaml;
+
+/// ### Schema
+///
+/// Schema describes the expected type of a configuration node
+pub(crate) enum Schema {
+    Bool,
+    Integer,
+    String,
+    Scalar,                                     // String or integer
+    List(&'static Schema),                      // Items follow the schema
+    Map(&'static Schema),                       // Any key; values follow the schema
+    Section(&'static [(&'static str, Schema)]), // Known keys only
+    Stanza(&'static [(&'static str, Schema)]),  // Known keys only; unknown keys are errors
+    StringOr(&'static Schema),                  // String or a node following the schema
+}
+
+const STRING_LIST: Schema = Schema::List(&Schema::String);
+const DICTIONARY: Schema = Schema::List(&Schema::Map(&Schema::String));
+/// Aliases are either a command or a stanza with the command, its working directory and its environment
+const ALIASES: Schema = Schema::List(&Schema::Map(&Schema::StringOr(&Schema::Stanza(&[
+    ("cmd", Schema::String),
+    ("cwd", Schema::String),
+    ("env", Schema::Map(&Schema::Scalar)),
+]))));
+
+/// Schema of the configuration file
+pub(crate) const CONFIG_SCHEMA: Schema = Schema::Section(&[
+    ("language", Schema::StringOr(&Schema::Stanza(&[("name", Schema::String), ("standard", Schema::String), ("strict", Schema::Bool)]))),
+    ("banner", Schema::Bool),
+    ("assume_width", Schema::Integer),
+    ("alias", ALIASES),
+    (
+        "core",
+        Schema::Section(&[
+            ("log_level", Schema::String),
+            ("log_file", Schema::String),
+            ("rc_file", Schema::String),
+            (
+                "sigint_escalation",
+                Schema::Section(&[("presses", Schema::Integer), ("window_ms", Schema::Integer), ("kill_after_ms", Schema::Integer)]),
+            ),
+            ("command_timeout", Schema::Integer),
+            ("use_pty", Schema::Bool),
+        ]),
+    ),
+    ("shell", Schema::Section(&[("exec", Schema::String), ("args", STRING_LIST), ("norc", Schema::Bool)])),
+    ("terminal", Schema::Section(&[("clear_scrollback", Schema::Bool)])),
+    (
+        "output",
+        Schema::Section(&[
+            ("translate", Schema::Bool),
+            ("translate_stdout", Schema::Bool),
+            ("translate_stderr", Schema::Bool),
+            ("force_translate_pipe", Schema::Bool),
+            ("translate_only", STRING_LIST),
+            ("never_translate", STRING_LIST),
+            ("localize_units", Schema::Bool),
+            ("localize_dictionary", DICTIONARY),
+            ("error_hints", Schema::Bool),
+            ("hints_dictionary", DICTIONARY),
+            ("no_translate_patterns", STRING_LIST),
+            ("echo_command", Schema::Bool),
+            ("error_color", Schema::Scalar),
+            ("error_prefix", Schema::String),
+        ]),
+    ),
+    (
+        "input",
+        Schema::Section(&[
+            ("preserve_redirect_targets", Schema::Bool),
+            ("preserve_output_args", STRING_LIST),
+            ("strict_boundaries", Schema::Bool),
+            ("expand_variables", Schema::Bool),
+            ("error_on_unset", Schema::Bool),
+            ("nullglob", Schema::Bool),
+            ("literal_delimiters", STRING_LIST),
+            ("translation", Schema::String),
+        ]),
+    ),
+    (
+        "prompt",
+        Schema::Section(&[
+            ("prompt_line", Schema::String),
+            ("prompt_line_right", Schema::String),
+            ("history_size", Schema::Integer),
+            ("translate", Schema::Bool),
+            ("module_timeout_ms", Schema::Integer),
+            ("wrkdir_max_depth", Schema::Integer),
+            ("wrkdir_truncate_repo", Schema::Bool),
+            ("keep_trailing_space", Schema::Bool),
+            ("continuation", Schema::String),
+            ("prompt_char_user", Schema::String),
+            ("prompt_char_root", Schema::String),
+            ("break", Schema::Section(&[("enabled", Schema::Bool), ("with", Schema::String)])),
+            ("duration", Schema::Section(&[("min_elapsed_time", Schema::Integer)])),
+            (
+                "rc",
+                Schema::Section(&[("ok", Schema::String), ("error", Schema::String), ("code_always", Schema::Bool)]),
+            ),
+            (
+                "git",
+                Schema::Section(&[
+                    ("branch", Schema::String),
+                    ("commit_ref_len", Schema::Integer),
+                    ("commit_prepend", Schema::String),
+                    ("commit_append", Schema::String),
+                    ("dirty", Schema::String),
+                    ("staged", Schema::String),
+                    ("cache_ttl", Schema::Integer),
+                ]),
+            ),
+            ("time_format", Schema::String),
+            ("date_format", Schema::String),
+            ("duration_format", Schema::String),
+            ("duration_prefix", Schema::String),
+            ("venv", Schema::Section(&[("prefix", Schema::String)])),
+            (
+                "battery",
+                Schema::Section(&[
+                    ("charging", Schema::String),
+                    ("discharging", Schema::String),
+                    ("low_threshold", Schema::Integer),
+                ]),
+            ),
+            ("jobs", Schema::Section(&[("running", Schema::String), ("stopped", Schema::String)])),
+            ("macros", Schema::Map(&Schema::String)),
+        ]),
+    ),
+    ("palette", Schema::Map(&Schema::Scalar)),
+]);
+
+/// ### validate
+///
+/// Validate the configuration against the schema. Unknown keys are reported with the `UnknownKey` code,
+/// type mismatches with the `YamlSyntaxError` code. Empty values are left to the parser
+pub(crate) fn validate(config: &str, yaml_doc: &Yaml, schema: &Schema) -> Vec<ConfigError> {
+    let mut validator: Validator = Validator {
+        positions: key_positions(config),
+        issues: Vec::new(),
+    };
+    validator.walk(yaml_doc, schema, "");
+    validator.issues
+}
+
+/// ### Validator
+///
+/// Validator walks a YAML document and collects the issues found
+struct Validator {
+    positions: HashMap<String, usize>,
+    issues: Vec<ConfigError>,
+}
+
+impl Validator {
+    /// ### walk
+    ///
+    /// Validate node, found at path, against schema
+    fn walk(&mut self, node: &Yaml, schema: &Schema, path: &str) {
+        match (schema, node) {
+            (_, Yaml::Null) => {}
+            (Schema::Bool, Yaml::Boolean(_))
+            | (Schema::Integer, Yaml::Integer(_))
+            | (Schema::String, Yaml::String(_))
+            | (Schema::Scalar, Yaml::String(_))
+            | (Schema::Scalar, Yaml::Integer(_)) => {}
+            (Schema::List(item), Yaml::Array(items)) => {
+                for (i, node) in items.iter().enumerate() {
+                    self.walk(node, item, format!("{}[{}]", path, i).as_str());
+                }
+            }
+            (Schema::Map(value), Yaml::Hash(map)) => {
+                for (key, node) in map.iter() {
+                    let key: String = key_to_string(key);
+                    self.walk(node, value, join_path(path, key.as_str()).as_str());
+                }
+            }
+            (Schema::StringOr(_), Yaml::String(_)) => {}
+            (Schema::StringOr(stanza), Yaml::Hash(_)) => self.walk(node, stanza, path),
+            (Schema::Section(fields), Yaml::Hash(map)) | (Schema::Stanza(fields), Yaml::Hash(map)) => {
+                let strict: bool = matches!(schema, Schema::Stanza(_));
+                for (key, node) in map.iter() {
+                    let key: String = key_to_string(key);
+                    let key_path: String = join_path(path, key.as_str());
+                    match fields.iter().find(|(name, _)| *name == key) {
+                        Some((_, field)) => self.walk(node, field, key_path.as_str()),
+                        None => {
+                            let suggestion: String = match suggest(key.as_str(), fields) {
+                                Some(name) => format!(", did you mean {}?", name),
+                                None => String::new(),
+                            };
+                            let message: String = format!("{}: unknown field{}{}", key_path, suggestion, self.position(key_path.as_str()));
+                            self.issues.push(ConfigError {
+                                code: match strict {
+                                    true => ConfigErrorCode::YamlSyntaxError,
+                                    false => ConfigErrorCode::UnknownKey,
+                                },
+                                message,
+                            });
+                        }
+                    }
+                }
+            }
+            (schema, node) => {
+                let message: String = format!(
+                    "{}: expected {}, found {}{}",
+                    match path.is_empty() {
+                        true => "configuration",
+                        false => path,
+                    },
+                    schema_type_name(schema),
+                    yaml_type_name(node),
+                    self.position(path)
+                );
+                self.issues.push(ConfigError {
+                    code: ConfigErrorCode::YamlSyntaxError,
+                    message,
+                });
+            }
+        }
+    }
+
+    /// ### position
+    ///
+    /// Returns the position of path to be appended to a message (e.g. ` (line 4)`), if known
+    fn position(&self, path: &str) -> String {
+        match self.positions.get(path) {
+            Some(line) => format!(" (line {})", line),
+            None => String::new(),
+        }
+    }
+}
+
+/// ### suggest
+///
+/// Find the field of the section, or of its subsections, which is the most similar to key.
+/// Subsection fields are matched with their names joined by '_' (e.g. `break_enabled` => `break.enabled`)
+fn suggest(key: &str, fields: &[(&str, Schema)]) -> Option<String> {
+    let mut candidates: Vec<(String, String)> = Vec::new();
+    for (name, schema) in fields.iter() {
+        candidates.push((String::from(*name), String::from(*name)));
+        if let Schema::Section(subfields) = schema {
+            for (subname, _) in subfields.iter() {
+                candidates.push((format!("{}_{}", name, subname), format!("{}.{}", name, subname)));
+            }
+        }
+    }
+    let max_distance: usize = std::cmp::max(2, key.chars().count() / 3);
+    candidates
+        .into_iter()
+        .map(|(candidate, suggestion)| (edit_distance(key, candidate.as_str()), suggestion))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, suggestion)| suggestion)
+}
+
+/// ### join_path
+///
+/// Join a key to the path of its parent
+fn join_path(path: &str, key: &str) -> String {
+    match path.is_empty() {
+        true => String::from(key),
+        false => format!("{}.{}", path, key),
+    }
+}
+
+/// ### key_to_string
+///
+/// Converts a map key to string
+fn key_to_string(key: &Yaml) -> String {
+    match key {
+        Yaml::String(s) => s.clone(),
+        Yaml::Integer(i) => i.to_string(),
+        Yaml::Boolean(b) => b.to_string(),
+        Yaml::Real(r) => r.clone(),
+        _ => String::from("?"),
+    }
+}
+
+/// ### schema_type_name
+///
+/// Name of the type expected by schema
+fn schema_type_name(schema: &Schema) -> &'static str {
+    match schema {
+        Schema::Bool => "a bool",
+        Schema::Integer => "an integer",
+        Schema::String => "a string",
+        Schema::Scalar => "a string or an integer",
+        Schema::List(_) => "a list",
+        Schema::Map(_) | Schema::Section(_) | Schema::Stanza(_) => "a map",
+        Schema::StringOr(_) => "a string or a map",
+    }
+}
+
+/// ### yaml_type_name
+///
+/// Name of the type of a YAML node
+fn yaml_type_name(node: &Yaml) -> &'static str {
+    match node {
+        Yaml::Boolean(_) => "a bool",
+        Yaml::Integer(_) => "an integer",
+        Yaml::Real(_) => "a real number",
+        Yaml::String(_) => "a string",
+        Yaml::Array(_) => "a list",
+        Yaml::Hash(_) => "a map",
+        Yaml::Null => "nothing",
+        Yaml::Alias(_) | Yaml::BadValue => "an invalid value",
+    }
+}
+
+/// ### key_positions
+///
+/// Returns the line of each key (and list item) of the first document, by its path
+fn key_positions(config: &str) -> HashMap<String, usize> {
+    let mut receiver: PositionReceiver = PositionReceiver {
+        positions: HashMap::new(),
+        stack: Vec::new(),
+    };
+    let mut parser = Parser::new(config.chars());
+    let _ = parser.load(&mut receiver, false);
+    receiver.positions
+}
+
+/// ### Frame
+///
+/// Frame is a collection which is being read by the PositionReceiver
+enum Frame {
+    Map { path: String, key: Option<String> },
+    Seq { path: String, index: usize },
+}
+
+/// ### PositionReceiver
+///
+/// PositionReceiver tracks the path of the YAML events, to record the line of each key
+struct PositionReceiver {
+    positions: HashMap<String, usize>,
+    stack: Vec<Frame>,
+}
+
+impl PositionReceiver {
+    /// ### value_path
+    ///
+    /// Returns the path of the value which starts at mark, recording its position if it's a list item
+    fn value_path(&mut self, mark: &Marker) -> String {
+        match self.stack.last() {
+            Some(Frame::Map { path, key: Some(key) }) => join_path(path, key),
+            Some(Frame::Seq { path, index }) => {
+                let path: String = format!("{}[{}]", path, index);
+                self.positions.insert(path.clone(), mark.line());
+                path
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// ### value_done
+    ///
+    /// A value of the current collection has been read
+    fn value_done(&mut self) {
+        match self.stack.last_mut() {
+            Some(Frame::Map { key, .. }) => *key = None,
+            Some(Frame::Seq { index, .. }) => *index += 1,
+            None => {}
+        }
+    }
+}
+
+impl MarkedEventReceiver for PositionReceiver {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, ..) => {
+                if let Some(Frame::Map { path, key: key @ None }) = self.stack.last_mut() {
+                    //Scalar is a key
+                    self.positions.insert(join_path(path, value.as_str()), mark.line());
+                    *key = Some(value);
+                } else {
+                    self.value_path(&mark);
+                    self.value_done();
+                }
+            }
+            Event::Alias(_) => {
+                self.value_path(&mark);
+                self.value_done();
+            }
+            Event::MappingStart(_) => {
+                let path: String = self.value_path(&mark);
+                self.stack.push(Frame::Map { path, key: None });
+            }
+            Event::SequenceStart(_) => {
+                let path: String = self.value_path(&mark);
+                self.stack.push(Frame::Seq { path, index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+                self.value_done();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    fn check(config: &str) -> Vec<String> {
+        let yaml_doc: Yaml = YamlLoader::load_from_str(config).unwrap().remove(0);
+        validate(config, &yaml_doc, &CONFIG_SCHEMA).into_iter().map(|err| format!("{}: {}", err.code, err.message)).collect()
+    }
+
+    #[test]
+    fn test_config_schema_valid() {
+        let config: String = std::fs::read_to_string("pyc.yml").unwrap();
+        assert!(check(config.as_str()).is_empty());
+        assert!(check("language: ru\nalias:\n  - чд: cd\npalette:\n  primary: 214\nprompt:\n  git:\n    commit_prepend:\n").is_empty());
+    }
+
+    #[test]
+    fn test_config_schema_unknown_keys() {
+        assert_eq!(
+            check("language: ru\nprompt:\n  prompt_line: \"${USER}\"\n  break_enabld: true\n  break:\n    enabld: true\n  histroy_size: 12\nfoo: bar\n"),
+            vec![
+                String::from("UnknownKey: prompt.break_enabld: unknown field, did you mean break.enabled? (line 4)"),
+                String::from("UnknownKey: prompt.break.enabld: unknown field, did you mean enabled? (line 6)"),
+                String::from("UnknownKey: prompt.histroy_size: unknown field, did you mean history_size? (line 7)"),
+                String::from("UnknownKey: foo: unknown field (line 8)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config_schema_type_mismatches() {
+        assert_eq!(
+            check("output:\n  translate: \"yes\"\n  never_translate: git\ninput:\n  preserve_output_args:\n    - curl\n    - 4\nprompt:\n  history_size: 12.5\n  break: true\nalias:\n  - ll: [ls]\n"),
+            vec![
+                String::from("YamlSyntaxError: output.translate: expected a bool, found a string (line 2)"),
+                String::from("YamlSyntaxError: output.never_translate: expected a list, found a string (line 3)"),
+                String::from("YamlSyntaxError: input.preserve_output_args[1]: expected a string, found an integer (line 7)"),
+                String::from("YamlSyntaxError: prompt.history_size: expected an integer, found a real number (line 9)"),
+                String::from("YamlSyntaxError: prompt.break: expected a map, found a bool (line 10)"),
+                String::from("YamlSyntaxError: alias[0].ll: expected a string or a map, found a list (line 12)"),
+            ]
+        );
+        assert_eq!(check("- language\n"), vec![String::from("YamlSyntaxError: configuration: expected a map, found a list")]);
+    }
+
+    #[test]
+    fn test_config_schema_edit_distance() {
+        assert_eq!(edit_distance("break_enabld", "break_enabled"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("язык", "язык"), 0);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
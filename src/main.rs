@@ -33,7 +33,8 @@ use ansi_term::{Colour, Style};
 use dirs::home_dir;
 use getopts::Options;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 //Internal modules
 mod config;
@@ -42,44 +43,246 @@ mod shell;
 mod translator;
 mod utils;
 
+use runtime::capabilities::{Capabilities, Feature};
+use runtime::output;
 use translator::lang::Language;
+use translator::stream::{ConvertResult, Direction, StreamTranslator};
+use translator::trace::TraceOutput;
+use translator::TransliterationStandard;
+use utils::console;
+use utils::logger::{self, LogLevel, Logger};
+use utils::recorder::{Cast, Recorder};
+use utils::terminal;
 
 /// ### print_usage
 ///
 /// Print usage
 
 fn print_usage(program: &String, opts: Options) {
-    let brief = format!("Usage: {} [Options]... [File]", program);
+    let brief = format!(
+        "Usage: {} [Options]... [File]\n       {} replay [--speed <multiplier>] <file>\n       {} send <session> <command>\n       {} convert --to-latin|--to-cyrillic [--in-place] <file>...\n       {} doctor\n       {} prompt [--rc <code>] [--duration-ms <n>] [--wrkdir <path>] [--readline-escapes]\n       {} status [--format json|plain] [--template <template>] [--rc <code>] [--wrkdir <path>]",
+        program, program, program, program, program, program, program
+    );
     print!("{}", opts.usage(&brief));
 }
 
 /// ### str_to_language
 ///
-/// Convert CLI option language string to Language enum
-
+/// Convert CLI option language string to Language enum; locale names (e.g. `uk_UA.UTF-8`) are accepted too
 fn str_to_language(lang: String) -> Language {
     match lang.as_str() {
         "ru" | "рус" => Language::Russian,
         "by" | "бел" => Language::Belarusian,
         "bg" | "бг" | "блг" => Language::Bulgarian,
+        "kk" | "каз" => Language::Kazakh,
         "rs" | "срб" => Language::Serbian,
         "ua" | "укр" => Language::Ukrainian,
         "nil" => Language::Nil,
-        _ => {
-            eprintln!(
-                "{}",
-                Colour::Red.paint(format!(
-                    "Unknown language: '{}'; Setting language to default: ru",
-                    lang
-                ))
-            );
+        _ => match translator::language_from_locale(lang.as_str()) {
+            Some(language) => language,
+            None => {
+                output::print_error(&format!("Unknown language: '{}'; Setting language to default: ru", lang));
+                Language::Russian
+            }
+        },
+    }
+}
+
+/// ### locale_language
+///
+/// Get the language from the locale of the environment (`LC_ALL`, then `LC_MESSAGES`, then `LANG`).
+/// Falls back to russian if the locale is not set, or with a warning if its language is not supported
+fn locale_language() -> Language {
+    let locale: String = match ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+    {
+        Some(locale) => locale,
+        None => {
+            logger::info("main", "locale is not set; using russian");
+            return Language::Russian;
+        }
+    };
+    match translator::language_from_locale(locale.as_str()) {
+        Some(language) => {
+            logger::debug("main", format!("language detected from locale '{}'", locale).as_str());
+            language
+        }
+        None => {
+            logger::warn("main", format!("unsupported locale '{}'; using russian", locale).as_str());
+            output::print_warning(&format!("Locale '{}' is not supported; Setting language to default: ru (use -l or `language` in the configuration to choose it)", locale));
             Language::Russian
         }
     }
 }
 
+/// ### str_to_input_translation
+///
+/// Convert the value of `--translate-input` to InputTranslation; besides the names used in the configuration, `on` and `off` are accepted
+fn str_to_input_translation(mode: &str) -> Option<config::InputTranslation> {
+    match mode {
+        "on" => Some(config::InputTranslation::Always),
+        "off" => Some(config::InputTranslation::Never),
+        mode => config::InputTranslation::from_name(mode),
+    }
+}
+
+/// ### str_to_standard
+///
+/// Convert the transliteration standard string to TransliterationStandard enum
+fn str_to_standard(standard: String) -> TransliterationStandard {
+    match TransliterationStandard::from_name(standard.as_str()) {
+        Some(standard) => standard,
+        None => {
+            output::print_error(&format!("Unknown transliteration standard: '{}'; Setting standard to default: gost", standard));
+            TransliterationStandard::default()
+        }
+    }
+}
+
+/// ### show_config
+///
+/// Print the effective configuration and the features which have been disabled
+fn show_config(config_file: &Path, config: &config::Config, language: Language, shell: &Option<String>, history_file: Option<&PathBuf>, trace_output: Option<&TraceOutput>) {
+    let capabilities: Capabilities = Capabilities::probe_outputs(history_file, trace_output);
+    let feature_status = |feature: Feature| -> String {
+        match capabilities.path(feature) {
+            Some(path) => match capabilities.is_enabled(feature) {
+                true => format!("{}", path.display()),
+                false => format!("{} (disabled: not writable)", path.display()),
+            },
+            None => String::from("off"),
+        }
+    };
+    let shell: String = {
+        let (exec, args): (String, Vec<String>) = runtime::resolve_shell(config, shell.clone());
+        let mut argv: Vec<String> = vec![exec];
+        argv.extend(args);
+        argv.join(" ")
+    };
+    println!("config: {}", config_file.display());
+    println!("language: {}", language.to_string());
+    println!("language.standard: {}", config.language_standard);
+    println!("language.strict: {}", config.language_strict);
+    println!("banner: {}", config.banner);
+    println!("core.log_level: {}", config.core_config.log_level);
+    println!(
+        "core.log_file: {}",
+        config.core_config.log_file.as_ref().map(|file| file.display().to_string()).unwrap_or_else(|| String::from("off"))
+    );
+    println!(
+        "core.rc_file: {}",
+        config.core_config.rc_file.as_ref().map(|file| file.display().to_string()).unwrap_or_else(|| String::from("off"))
+    );
+    println!("core.command_timeout: {}", config.core_config.command_timeout);
+    println!("core.use_pty: {}", config.core_config.use_pty);
+    let size: terminal::TerminalSize = terminal::size();
+    println!("terminal: {}x{} ({:?})", size.columns, size.rows, size.source);
+    println!("shell: {}", shell);
+    println!("shell.norc: {}", config.shell_config.norc);
+    println!("terminal.clear_scrollback: {}", config.terminal_config.clear_scrollback);
+    println!("output.translate_stdout: {}", config.output_config.translate_stdout);
+    println!("output.translate_stderr: {}", config.output_config.translate_stderr);
+    println!("output.force_translate_pipe: {}", config.output_config.force_translate_pipe);
+    println!("output.translate_only: {}", config.output_config.translate_only.join(", "));
+    println!("output.never_translate: {}", config.output_config.never_translate.join(", "));
+    println!("output.error_hints: {}", config.output_config.error_hints);
+    println!("output.no_translate_patterns: {}", config.output_config.no_translate_patterns.join(", "));
+    println!("output.echo_command: {}", config.output_config.echo_command);
+    println!("output.error_color: {}", config.output_config.error_color.as_deref().unwrap_or("palette"));
+    println!("output.error_prefix: {}", config.output_config.error_prefix);
+    println!("input.preserve_redirect_targets: {}", config.input_config.preserve_redirect_targets);
+    println!("input.preserve_output_args: {}", config.input_config.preserve_output_args.join(", "));
+    println!("input.strict_boundaries: {}", config.input_config.strict_boundaries);
+    println!("input.expand_variables: {}", config.input_config.expand_variables);
+    println!("input.error_on_unset: {}", config.input_config.error_on_unset);
+    println!("input.nullglob: {}", config.input_config.nullglob);
+    println!("input.translation: {}", config.input_config.translation);
+    println!("prompt.history_size: {}", config.prompt_config.history_size);
+    println!("prompt.module_timeout_ms: {}", config.prompt_config.module_timeout);
+    println!("prompt.git.cache_ttl: {}", config.prompt_config.git_cache_ttl);
+    let mut palette: Vec<String> = config.prompt_config.palette.iter().map(|(name, style)| format!("{}={}", name, style)).collect();
+    palette.sort();
+    println!("palette: {}", palette.join(", "));
+    println!("history: {}", feature_status(Feature::History));
+    println!("trace: {}", match trace_output {
+        Some(TraceOutput::Stderr) => String::from("stderr"),
+        _ => feature_status(Feature::TranslationTrace),
+    });
+    println!("degraded: {}", match capabilities.is_degraded() {
+        true => capabilities.disabled_features().join(", "),
+        false => String::from("no"),
+    });
+}
+
+/// ### replay
+///
+/// Play back a recorded session on stdout
+fn replay(file: &str, speed: f64) -> u8 {
+    let cast: Cast = match Cast::read(Path::new(file)) {
+        Ok(cast) => cast,
+        Err(err) => {
+            output::print_error(&format!("Could not read '{}': {}", file, err));
+            return 255;
+        }
+    };
+    match cast.replay(&mut std::io::stdout(), speed) {
+        Ok(_) => 0,
+        Err(err) => {
+            output::print_error(&format!("Could not replay '{}': {}", file, err));
+            255
+        }
+    }
+}
+
+/// ### send
+///
+/// Run a command in a named session and print its output
+fn send(session_name: &str, command: &str) -> u8 {
+    let socket: PathBuf = runtime::session::socket_path(session_name);
+    match runtime::session::send(socket.as_path(), command, &mut std::io::stdout()) {
+        Ok(rc) => rc,
+        Err(err) => {
+            output::print_error(&format!("Could not send command to session '{}': {}", session_name, err));
+            255
+        }
+    }
+}
+
+/// ### convert
+///
+/// Transliterate files, writing the result to stdout or replacing the files
+fn convert(files: &[String], stream: &StreamTranslator, in_place: bool) -> u8 {
+    let mut rc: u8 = 0;
+    let stdout = std::io::stdout();
+    for file in files.iter() {
+        let result: std::io::Result<ConvertResult> = match in_place {
+            true => stream.convert_file(Path::new(file.as_str()), None),
+            false => stream.convert_file(Path::new(file.as_str()), Some(&mut stdout.lock())),
+        };
+        match result {
+            Ok(ConvertResult::Converted) => {}
+            Ok(ConvertResult::SkippedBinary) => {
+                output::print_warning(&format!("{}: binary file, skipped", file));
+            }
+            Err(err) => {
+                output::print_error(&format!("Could not convert '{}': {}", file, err));
+                rc = 1;
+            }
+        }
+    }
+    rc
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let started: Instant = Instant::now();
+    let mut args: Vec<String> = env::args().collect();
+    //Hidden option: print the time spent in each startup phase
+    if let Some(pos) = args.iter().position(|arg| arg == "--profile-startup") {
+        args.remove(pos);
+        utils::profile::start(started);
+    }
     let program: String = args[0].clone();
     //Program CLI options
     let config_file: PathBuf;
@@ -97,9 +300,34 @@ fn main() {
     //Process options
     let mut opts = Options::new();
     opts.optopt("c", "command", "Specify command to run. Shell returns after running the command", "<command>");
+    opts.optopt("", "timeout", "Terminate the command given with -c if it runs longer than the given seconds and exit with 124 (overrides `core.command_timeout`)", "<seconds>");
+    opts.optmulti("e", "expression", "Run the expression (transliterated) and exit with its status, like `sh -c`; repeat it to run more expressions in order", "<expression>");
     opts.optopt("C", "config", "Specify YAML configuration file", "<config>");
     opts.optopt("l", "lang", "Specify shell language", "<ru|рус>");
-    opts.optopt("s", "shell", "Force the shell binary path", "</bin/bash>");
+    opts.optopt("", "standard", "Specify the transliteration standard of russian and ukrainian", "<gost|bgn-pcgn|scientific>");
+    opts.optopt("s", "shell", "Specify the shell to wrap (overrides `shell.exec`)", "</bin/bash>");
+    opts.optflagopt("", "trace-translation", "Write a JSON trace of each translated command to stderr or to file", "<file>");
+    opts.optflag("", "translate-output", "Transliterate the output of the commands, whatever the configuration says");
+    opts.optflag("", "no-translate-output", "Don't transliterate the output of the commands, whatever the configuration says");
+    opts.optopt("", "translate-input", "Transliterate the typed input: on, off or command-line-only (overrides the configuration)", "<on|off|command-line-only>");
+    opts.optflag("", "keep-going", "When commands are read from stdin or given with -e, don't stop at the first command which fails");
+    opts.optflag("", "dry-run", "Print the command (or the commands read from stdin) transliterated, without running it");
+    opts.optflag("q", "quiet", "Don't print the banner when the session starts");
+    opts.optflag("", "norc", "Don't run the rc file when the interactive session starts");
+    opts.optflag("", "debug", "Write debug log entries to the log file, or to stderr if no log file is configured");
+    opts.optflag("", "show-config", "Print the effective configuration and the disabled features, then exit");
+    opts.optopt("", "record", "Record the session to an asciicast file", "<file>");
+    opts.optopt("", "speed", "Playback speed multiplier for replay (default: 1)", "<multiplier>");
+    opts.optopt("", "session", "Start a named session, which can receive commands with `pyc send`", "<name>");
+    opts.optflag("", "to-latin", "Transliterate files to latin with convert");
+    opts.optflag("", "to-cyrillic", "Transliterate files to cyrillic with convert");
+    opts.optflag("", "in-place", "Replace the files transliterated with convert, instead of printing them");
+    opts.optopt("", "rc", "Exit code of the last command rendered by prompt and status (default: 0)", "<code>");
+    opts.optopt("", "duration-ms", "Duration of the last command rendered by prompt (default: 0)", "<n>");
+    opts.optopt("", "wrkdir", "Working directory rendered by prompt and status (default: current directory)", "<path>");
+    opts.optopt("", "format", "Output format of status (default: plain)", "<json|plain>");
+    opts.optopt("", "template", "Line printed by status in plain format, with the prompt keys (e.g. ${GIT_BRANCH})", "<template>");
+    opts.optflag("", "readline-escapes", "Wrap the escape sequences printed by prompt in \\[ \\], to set it as bash's PS1");
     opts.optflag("v", "version", "");
     opts.optflag("h", "help", "Print this menu");
     let matches = match opts.parse(&args[1..]) {
@@ -109,6 +337,7 @@ fn main() {
             std::process::exit(255);
         }
     };
+    utils::profile::mark("args");
     if matches.opt_present("h") {
         print_usage(&program, opts);
         std::process::exit(255);
@@ -123,6 +352,35 @@ fn main() {
         );
         std::process::exit(255);
     }
+    //Replay a recorded session
+    if matches.free.first().map(|s| s.as_str()) == Some("replay") {
+        let file: &String = match matches.free.get(1) {
+            Some(file) => file,
+            None => {
+                print_usage(&program, opts);
+                std::process::exit(255);
+            }
+        };
+        let speed: f64 = match matches.opt_str("speed").map(|s| s.parse::<f64>()) {
+            None => 1.0,
+            Some(Ok(speed)) if speed > 0.0 => speed,
+            Some(_) => {
+                output::print_error("Speed must be a positive number");
+                std::process::exit(255);
+            }
+        };
+        std::process::exit(replay(file.as_str(), speed) as i32);
+    }
+    //Send command to a session
+    if matches.free.first().map(|s| s.as_str()) == Some("send") {
+        match (matches.free.get(1), matches.free.get(2)) {
+            (Some(session_name), Some(command)) => std::process::exit(send(session_name, command) as i32),
+            _ => {
+                print_usage(&program, opts);
+                std::process::exit(255);
+            }
+        }
+    }
     //Get shell
     if let Some(sh) = matches.opt_str("s") {
         shell = Some(sh);
@@ -132,11 +390,44 @@ fn main() {
         Some(lang) => Some(str_to_language(lang)),
         None => None,
     };
+    //Get translation trace output
+    let trace_output: Option<TraceOutput> = match matches.opt_present("trace-translation") {
+        true => match matches.opt_str("trace-translation") {
+            Some(file) => Some(TraceOutput::File(PathBuf::from(file))),
+            None => Some(TraceOutput::Stderr)
+        },
+        false => None
+    };
     //Get command
     let command = match matches.opt_str("c") {
         Some(cmd) => Some(cmd.clone()),
         None => None
     };
+    let expressions: Vec<String> = matches.opt_strs("e");
+    if command.is_some() && !expressions.is_empty() {
+        output::print_error("-c and -e can't be used together");
+        std::process::exit(255);
+    }
+    //Options which override the configuration
+    let translate_output: Option<bool> = match (matches.opt_present("translate-output"), matches.opt_present("no-translate-output")) {
+        (true, true) => {
+            output::print_error("--translate-output and --no-translate-output can't be used together");
+            std::process::exit(255);
+        }
+        (true, false) => Some(true),
+        (false, true) => Some(false),
+        (false, false) => None,
+    };
+    let input_translation: Option<config::InputTranslation> = match matches.opt_str("translate-input") {
+        Some(mode) => match str_to_input_translation(mode.as_str()) {
+            Some(translation) => Some(translation),
+            None => {
+                output::print_error(&format!("Unknown input translation: '{}'; expected on, off or command-line-only", mode));
+                std::process::exit(255);
+            }
+        },
+        None => None,
+    };
     //Set config file to '-C' file or to default file
     config_file = match matches.opt_str("C") {
         Some(cfg_override) => PathBuf::from(cfg_override.as_str()),
@@ -147,12 +438,7 @@ fn main() {
                 pyc_config_file.push("pyc.yml");
                 pyc_config_file
             } else {
-                eprintln!(
-                    "{}",
-                    Colour::Red.paint(format!(
-                        "Could not find home directory for this user"
-                    ))
-                );
+                output::print_error("Could not find home directory for this user");
                 std::process::exit(255);
             }
         }
@@ -163,20 +449,24 @@ fn main() {
         0 => None,
         _ => Some(extra_args.get(0).unwrap().clone())
     };
+    //Check the environment
+    if matches.free.first().map(|s| s.as_str()) == Some("doctor") {
+        let settings: runtime::doctor::DoctorSettings = runtime::doctor::DoctorSettings {
+            config_file: config_file.clone(),
+            shell: shell.clone(),
+            history_file: pyc_config_dir.as_ref().map(|dir| dir.join("pyc_history")),
+            trace_output: trace_output.clone(),
+            shell_timeout: Duration::from_secs(5),
+        };
+        let results: Vec<runtime::doctor::DoctorResult> = runtime::doctor::run(&settings);
+        std::process::exit(runtime::doctor::report(&results) as i32);
+    }
     //Parse configuration
-    let config: config::Config = match config::Config::parse_config(config_file.clone()) {
+    let mut config: config::Config = match config::Config::parse_config(config_file.clone()) {
         Ok(cfg) => cfg,
         Err(err) => match err.code {
             config::ConfigErrorCode::NoSuchFileOrDirectory => {
-                eprintln!(
-                    "{}",
-                    Colour::Red.paint(format!(
-                        "{}: {}; {}",
-                        String::from("No such file or directory"),
-                        config_file.display(),
-                        String::from("Using default configuration")
-                    ))
-                );
+                output::print_error(&format!("No such file or directory: {}; Using default configuration", config_file.display()));
                 config::Config::default()
             }
             _ => panic!(
@@ -189,29 +479,187 @@ fn main() {
             ),
         },
     };
+    config.apply_overrides(config::ConfigOverrides {
+        translate_output,
+        input_translation,
+    });
+    //Translated stdout is meant to be read on a terminal; when it's piped it's left as it is
+    config.apply_pipe(|fd| nix::unistd::isatty(fd).unwrap_or(false));
+    for warning in config.warnings() {
+        output::print_warning(&format!("Configuration warning: {}", warning));
+    }
+    //Log entries are written to the log file; `--debug` writes all of them, to stderr if no log file is configured
+    if matches.opt_present("debug") {
+        config.core_config.log_level = LogLevel::Debug;
+    }
+    if matches.opt_present("debug") || config.core_config.log_file.is_some() {
+        match Logger::open(config.core_config.log_level, config.core_config.log_file.as_deref()) {
+            Ok(logger) => logger::init(logger),
+            Err(err) => output::print_warning(&format!("Could not open log file: {}", err)),
+        }
+    }
+    logger::info("main", format!("pyc {} started; configuration: {}", PYC_VERSION, config_file.display()).as_str());
+    //The assumed width is used when the terminal size can't be determined
+    terminal::set_assume_width(config.assume_width);
+    //Errors are printed with the error color and prefix
+    output::setup_errors(&config);
     //Set language
     let language: Language = match language {
         Some(l) => l,
-        None => str_to_language(config.language.clone())
+        None => match config.language.clone() {
+            Some(lang) => str_to_language(lang),
+            None => locale_language(),
+        },
+    };
+    //Set transliteration standard; the command line option overrides the configuration
+    let standard: TransliterationStandard = str_to_standard(matches.opt_str("standard").unwrap_or_else(|| config.language_standard.clone()));
+    config.language_standard = standard.to_string();
+    if matches.opt_present("q") {
+        config.banner = false;
+    }
+    if matches.opt_present("norc") {
+        config.core_config.rc_file = None;
+    }
+    if let Some(timeout) = matches.opt_str("timeout") {
+        match timeout.parse::<usize>() {
+            Ok(seconds) if command.is_some() => config.core_config.command_timeout = seconds,
+            Ok(_) => {
+                output::print_error("--timeout requires a command (-c)");
+                std::process::exit(255);
+            }
+            Err(_) => {
+                output::print_error(&format!("Invalid timeout: '{}'; expected a number of seconds", timeout));
+                std::process::exit(255);
+            }
+        }
+    }
+    utils::profile::mark("config");
+    //Get history file
+    let history_file: Option<PathBuf> = match pyc_config_dir {
+        None => None,
+        Some(dir) => {
+            let mut pyc_history_file: PathBuf = dir;
+            pyc_history_file.push("pyc_history");
+            Some(pyc_history_file)
+        }
     };
+    //Print prompt for another shell
+    if matches.free.first().map(|s| s.as_str()) == Some("prompt") {
+        let rc: u8 = match matches.opt_str("rc").map(|s| s.parse::<u8>()) {
+            None => 0,
+            Some(Ok(rc)) => rc,
+            Some(Err(_)) => {
+                output::print_error("Exit code must be a number between 0 and 255");
+                std::process::exit(255);
+            }
+        };
+        let duration: Duration = match matches.opt_str("duration-ms").map(|s| s.parse::<u64>()) {
+            None => Duration::from_millis(0),
+            Some(Ok(duration)) => Duration::from_millis(duration),
+            Some(Err(_)) => {
+                output::print_error("Duration must be a positive number of milliseconds");
+                std::process::exit(255);
+            }
+        };
+        let wrkdir: PathBuf = match matches.opt_str("wrkdir") {
+            Some(wrkdir) => PathBuf::from(wrkdir),
+            None => std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
+        };
+        std::process::exit(runtime::run_prompt(language, config, rc, duration, wrkdir, matches.opt_present("readline-escapes")) as i32);
+    }
+    //Print the status values for external status bars
+    if matches.free.first().map(|s| s.as_str()) == Some("status") {
+        let json: bool = match matches.opt_str("format").as_deref() {
+            None | Some("plain") => false,
+            Some("json") => true,
+            Some(format) => {
+                output::print_error(&format!("Unknown status format '{}': expected json or plain", format));
+                std::process::exit(255);
+            }
+        };
+        let rc: u8 = match matches.opt_str("rc").map(|s| s.parse::<u8>()) {
+            None => 0,
+            Some(Ok(rc)) => rc,
+            Some(Err(_)) => {
+                output::print_error("Exit code must be a number between 0 and 255");
+                std::process::exit(255);
+            }
+        };
+        let wrkdir: PathBuf = match matches.opt_str("wrkdir") {
+            Some(wrkdir) => PathBuf::from(wrkdir),
+            None => std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
+        };
+        std::process::exit(runtime::run_status(language, config, rc, wrkdir, json, matches.opt_str("template")) as i32);
+    }
+    //Show config
+    if matches.opt_present("show-config") {
+        show_config(&config_file, &config, language, &shell, history_file.as_ref(), trace_output.as_ref());
+        std::process::exit(0);
+    }
+    //Transliterate files
+    if matches.free.first().map(|s| s.as_str()) == Some("convert") {
+        let direction: Direction = match (matches.opt_present("to-latin"), matches.opt_present("to-cyrillic")) {
+            (true, false) => Direction::ToLatin,
+            (false, true) => Direction::ToCyrillic,
+            _ => {
+                output::print_error("Exactly one of --to-latin and --to-cyrillic must be provided");
+                std::process::exit(255);
+            }
+        };
+        if matches.free.len() < 2 {
+            print_usage(&program, opts);
+            std::process::exit(255);
+        }
+        let options: translator::TranslatorOptions = translator::TranslatorOptions {
+            strict_boundaries: config.input_config.strict_boundaries,
+            standard,
+        };
+        let stream: StreamTranslator = StreamTranslator::new(translator::new_translator_with_options(language, options), direction);
+        std::process::exit(convert(&matches.free[1..], &stream, matches.opt_present("in-place")) as i32);
+    }
+    //Print the commands as they would be sent to the shell, without running them
+    if matches.opt_present("dry-run") {
+        let lines: Vec<String> = match &command {
+            Some(command) => command.lines().map(String::from).collect(),
+            None if !expressions.is_empty() => expressions,
+            None if file.is_none() && !nix::unistd::isatty(0).unwrap_or(false) => {
+                let mut input: String = String::new();
+                if let Err(err) = std::io::Read::read_to_string(&mut std::io::stdin(), &mut input) {
+                    output::print_error(&format!("Could not read stdin: {}", err));
+                    std::process::exit(255);
+                }
+                input.lines().map(String::from).collect()
+            }
+            None => {
+                output::print_error("--dry-run requires a command (-c or -e) or commands on stdin");
+                std::process::exit(255);
+            }
+        };
+        std::process::exit(runtime::dry_run(&lines, language, config) as i32);
+    }
+    //Start recording
+    if let Some(record_file) = matches.opt_str("record") {
+        let size: terminal::TerminalSize = terminal::size();
+        let (width, height): (usize, usize) = (size.columns, size.rows);
+        match Recorder::create(Path::new(record_file.as_str()), width, height) {
+            Ok(recorder) => console::start_recording(recorder),
+            Err(err) => {
+                output::print_error(&format!("Could not record session to '{}': {}", record_file, err));
+                std::process::exit(255);
+            }
+        }
+    }
     //Start runtime
     let rc: u8 = match command {
-        Some(command) => runtime::run_command(command, language, config, shell),
+        Some(command) => runtime::run_command(command, language, config, shell, trace_output),
+        None if !expressions.is_empty() => runtime::run_expressions(&expressions, language, config, shell, trace_output, matches.opt_present("keep-going")),
         None => match file {
-            None => {
-                //Get history file
-                let history_file: Option<PathBuf> = match pyc_config_dir {
-                    None => None,
-                    Some(dir) => {
-                        let mut pyc_history_file: PathBuf = dir;
-                        pyc_history_file.push("pyc_history");
-                        Some(pyc_history_file)
-                    }
-                };
-                runtime::run_interactive(language, config, shell, history_file)
-            },
-            Some(file) => runtime::run_file(file, language, config, shell)
+            //Commands are piped to pyc (e.g. `echo "лс" | pyc`)
+            None if !nix::unistd::isatty(0).unwrap_or(false) && !matches.opt_present("session") => runtime::run_stdin(language, config, shell, trace_output, matches.opt_present("keep-going")),
+            None => runtime::run_interactive(language, config, config_file, shell, history_file, trace_output, matches.opt_str("session")),
+            Some(file) => runtime::run_file(file, language, config, shell, trace_output)
         }
     };
+    console::stop_recording();
     std::process::exit(rc as i32);
 }
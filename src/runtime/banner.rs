@@ -0,0 +1,169 @@
+//! ## Banner
+//!
+//! `banner` renders the short banner printed when an interactive session starts: the version, the language,
+//! the configuration file and a usage tip, which changes every session
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate ansi_term;
+
+use ansi_term::Style;
+use std::env;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::translator::lang::Language;
+use crate::translator::messages;
+use crate::utils::console;
+
+const PYC_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// ### should_print
+///
+/// Returns whether the banner has to be printed: it must be enabled in the configuration
+/// and the session must be attached to a terminal (never when commands are piped to pyc)
+pub(super) fn should_print(config: &Config, terminal: bool) -> bool {
+    config.banner && terminal
+}
+
+/// ### tip_seed
+///
+/// Returns the index of the tip shown in this session; it changes every minute
+pub(super) fn tip_seed() -> usize {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => (elapsed.as_secs() / 60) as usize,
+        Err(_) => 0,
+    }
+}
+
+/// ### render
+///
+/// Render the banner lines. Each line is truncated to the terminal width and styled only if colors are enabled
+pub(super) fn render(language: Language, standard: &str, config_file: &Path, tip_index: usize, width: usize, colors: bool) -> Vec<String> {
+    let tips: [&str; 5] = messages::usage_tips(language);
+    let lines: [(String, Style); 3] = [
+        (format!("pyc {} · {} ({})", PYC_VERSION, language.to_string(), standard), Style::new().bold()),
+        (config_file.display().to_string(), Style::new().dimmed()),
+        (format!("💡 {}", tips[tip_index % tips.len()]), Style::new().italic()),
+    ];
+    lines
+        .iter()
+        .map(|(line, style)| {
+            let line: String = truncate(line, width);
+            match colors {
+                true => style.paint(line).to_string(),
+                false => line,
+            }
+        })
+        .collect()
+}
+
+/// ### colors_enabled
+///
+/// Returns whether the banner can be colored: not if `NO_COLOR` is set or the terminal is dumb
+pub(super) fn colors_enabled() -> bool {
+    env::var_os("NO_COLOR").is_none() && env::var("TERM").map(|term| term != "dumb").unwrap_or(false)
+}
+
+/// ### truncate
+///
+/// Truncate the line to the provided amount of columns, ending it with '…' if it has been truncated
+fn truncate(line: &str, width: usize) -> String {
+    if console::str_width(line) <= width {
+        return String::from(line);
+    }
+    let mut truncated: String = String::new();
+    let mut columns: usize = 0;
+    for ch in line.chars() {
+        columns += console::char_width(ch);
+        if columns + 1 > width {
+            break;
+        }
+        truncated.push(ch);
+    }
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::utils::terminal;
+
+    #[test]
+    fn test_runtime_banner_should_print() {
+        let mut config: Config = Config::default();
+        assert!(should_print(&config, true));
+        //Never without a terminal
+        assert!(!should_print(&config, false));
+        //Disabled in configuration (or with `--quiet`)
+        config.banner = false;
+        assert!(!should_print(&config, true));
+    }
+
+    #[test]
+    fn test_runtime_banner_render() {
+        let lines: Vec<String> = render(Language::Russian, "gost", Path::new("/home/user/.config/pyc/pyc.yml"), 0, 80, false);
+        assert_eq!(
+            lines,
+            vec![
+                format!("pyc {} · рус (gost)", PYC_VERSION),
+                String::from("/home/user/.config/pyc/pyc.yml"),
+                String::from("💡 Текст в кавычках не транслитерируется"),
+            ]
+        );
+        //Colors
+        let lines: Vec<String> = render(Language::Nil, "gost", Path::new("pyc.yml"), 0, 80, true);
+        assert!(lines[0].starts_with("\x1b[1m"));
+        assert!(lines.iter().all(|line| line.ends_with("\x1b[0m")));
+    }
+
+    #[test]
+    fn test_runtime_banner_tips_rotate() {
+        let tip = |index: usize| render(Language::Nil, "gost", Path::new("pyc.yml"), index, 80, false).remove(2);
+        assert_eq!(tip(0), String::from("💡 Text between quotes is not transliterated"));
+        assert_eq!(tip(1), String::from("💡 Ctrl+R searches the history"));
+        assert_eq!(tip(4), String::from("💡 `pyc convert --to-latin <file>` transliterates whole files"));
+        //Tips start over
+        assert_eq!(tip(5), tip(0));
+        assert_eq!(tip(42), tip(2));
+        //Every language has a tip for each index
+        for language in [Language::Belarusian, Language::Bulgarian, Language::Kazakh, Language::Russian, Language::Serbian, Language::Ukrainian].iter() {
+            assert!(messages::usage_tips(*language).iter().all(|tip| !tip.is_empty()));
+        }
+    }
+
+    #[test]
+    fn test_runtime_banner_truncate() {
+        let lines: Vec<String> = render(Language::Ukrainian, "bgn-pcgn", Path::new("pyc.yml"), 4, 20, false);
+        assert_eq!(lines[2], String::from("💡 `pyc convert --t…"));
+        assert!(lines.iter().all(|line| console::str_width(line) <= 20));
+        //Default terminal size (the size couldn't be determined)
+        let lines: Vec<String> = render(Language::Serbian, "gost", Path::new("/home/user/.config/pyc/pyc.yml"), 4, terminal::DEFAULT_COLUMNS, false);
+        assert_eq!(lines[2], String::from("💡 `pyc convert --to-latin <датотека>` пресловљава целе датотеке"));
+        assert!(lines.iter().all(|line| console::str_width(line) <= terminal::DEFAULT_COLUMNS));
+        assert_eq!(truncate("рус", 3), String::from("рус"));
+        assert_eq!(truncate("русский", 3), String::from("ру…"));
+    }
+}
@@ -0,0 +1,250 @@
+//! ## Capabilities
+//!
+//! `capabilities` checks whether the files written by pyc can actually be written and keeps track of the features
+//! which have been disabled because of that
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use nix::unistd::{access, AccessFlags};
+use std::path::{Path, PathBuf};
+
+use crate::translator::trace::TraceOutput;
+
+/// ### Feature
+///
+/// Feature describes a pyc feature which needs to write to a file
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum Feature {
+    History,
+    TranslationTrace,
+}
+
+/// ### Probe
+///
+/// Probe describes the writability of the path used by a feature
+#[derive(Clone, std::fmt::Debug)]
+struct Probe {
+    feature: Feature,
+    path: PathBuf,
+    writable: bool,
+}
+
+/// ### Capabilities
+///
+/// Capabilities contains the result of the writability check for each configured output path
+#[derive(Clone, std::fmt::Debug)]
+pub struct Capabilities {
+    probes: Vec<Probe>,
+}
+
+impl Feature {
+    /// ### name
+    ///
+    /// Returns the name of the feature
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::History => "history",
+            Feature::TranslationTrace => "trace",
+        }
+    }
+}
+
+impl Capabilities {
+    /// ### probe
+    ///
+    /// Check once whether each path can be written; features whose path is not writable are disabled
+    pub fn probe(paths: Vec<(Feature, PathBuf)>) -> Capabilities {
+        let mut capabilities: Capabilities = Capabilities {
+            probes: paths
+                .into_iter()
+                .map(|(feature, path)| Probe {
+                    feature,
+                    path,
+                    writable: false,
+                })
+                .collect(),
+        };
+        capabilities.reprobe();
+        capabilities
+    }
+
+    /// ### probe_outputs
+    ///
+    /// Probe the output paths used by the runtime (history file and translation trace file)
+    pub fn probe_outputs(history_file: Option<&PathBuf>, trace_output: Option<&TraceOutput>) -> Capabilities {
+        let mut paths: Vec<(Feature, PathBuf)> = Vec::new();
+        if let Some(history_file) = history_file {
+            paths.push((Feature::History, history_file.clone()));
+        }
+        if let Some(TraceOutput::File(trace_file)) = trace_output {
+            paths.push((Feature::TranslationTrace, trace_file.clone()));
+        }
+        Capabilities::probe(paths)
+    }
+
+    /// ### reprobe
+    ///
+    /// Check again the writability of each path (e.g. after the configuration has been reloaded)
+    pub fn reprobe(&mut self) {
+        for probe in self.probes.iter_mut() {
+            probe.writable = is_writable(probe.path.as_path());
+        }
+    }
+
+    /// ### is_enabled
+    ///
+    /// Returns whether a feature can be used. Features which don't write to any file are always enabled
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.probes.iter().filter(|p| p.feature == feature).all(|p| p.writable)
+    }
+
+    /// ### is_degraded
+    ///
+    /// Returns whether at least one feature has been disabled
+    pub fn is_degraded(&self) -> bool {
+        self.probes.iter().any(|p| !p.writable)
+    }
+
+    /// ### disabled_features
+    ///
+    /// Returns the names of the disabled features
+    pub fn disabled_features(&self) -> Vec<String> {
+        self.probes.iter().filter(|p| !p.writable).map(|p| String::from(p.feature.name())).collect()
+    }
+
+    /// ### path
+    ///
+    /// Returns the path used by a feature
+    pub fn path(&self, feature: Feature) -> Option<&Path> {
+        self.probes.iter().find(|p| p.feature == feature).map(|p| p.path.as_path())
+    }
+
+    /// ### warning
+    ///
+    /// Returns a single message describing all the disabled features, if any
+    pub fn warning(&self) -> Option<String> {
+        if !self.is_degraded() {
+            return None;
+        }
+        let paths: Vec<String> = self
+            .probes
+            .iter()
+            .filter(|p| !p.writable)
+            .map(|p| format!("'{}'", p.path.display()))
+            .collect();
+        Some(format!(
+            "Could not write to {}; disabled features: {}",
+            paths.join(", "),
+            self.disabled_features().join(", ")
+        ))
+    }
+}
+
+/// ### is_writable
+///
+/// Check whether a file can be written, without creating anything: an existing file must be writable,
+/// otherwise the nearest existing ancestor must be a writable directory, so that the file can be created on its first write
+fn is_writable(path: &Path) -> bool {
+    if path.exists() {
+        return !path.is_dir() && access(path, AccessFlags::W_OK).is_ok();
+    }
+    for dir in path.ancestors().skip(1) {
+        let dir: &Path = match dir.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => dir,
+        };
+        if dir.exists() {
+            return dir.is_dir() && access(dir, AccessFlags::W_OK | AccessFlags::X_OK).is_ok();
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::io::Write;
+
+    #[test]
+    fn test_capabilities_writable() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let history_file: PathBuf = tmpdir.path().join("pyc/pyc_history");
+        let capabilities: Capabilities = Capabilities::probe_outputs(Some(&history_file), Some(&TraceOutput::Stderr));
+        assert!(capabilities.is_enabled(Feature::History));
+        assert!(capabilities.is_enabled(Feature::TranslationTrace));
+        assert!(!capabilities.is_degraded());
+        assert!(capabilities.warning().is_none());
+        assert_eq!(capabilities.disabled_features().len(), 0);
+        assert_eq!(capabilities.path(Feature::History).unwrap(), history_file.as_path());
+        assert!(capabilities.path(Feature::TranslationTrace).is_none());
+        //Nothing has been created
+        assert!(!history_file.parent().unwrap().exists());
+    }
+
+    #[test]
+    fn test_capabilities_existing_file_is_not_changed() {
+        let mut history_file: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        writeln!(history_file, "ls -l").unwrap();
+        let history_path: PathBuf = history_file.path().to_path_buf();
+        let capabilities: Capabilities = Capabilities::probe_outputs(Some(&history_path), None);
+        assert!(capabilities.is_enabled(Feature::History));
+        assert_eq!(fs::read_to_string(history_path).unwrap(), String::from("ls -l\n"));
+    }
+
+    #[test]
+    fn test_capabilities_not_writable() {
+        //Use a regular file as config directory, so that the check fails even if running as root
+        let config_dir: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let history_file: PathBuf = config_dir.path().join("pyc_history");
+        let trace_file: PathBuf = config_dir.path().join("trace.json");
+        let mut capabilities: Capabilities = Capabilities::probe_outputs(Some(&history_file), Some(&TraceOutput::File(trace_file.clone())));
+        assert!(!capabilities.is_enabled(Feature::History));
+        assert!(!capabilities.is_enabled(Feature::TranslationTrace));
+        assert!(capabilities.is_degraded());
+        assert_eq!(capabilities.disabled_features(), vec![String::from("history"), String::from("trace")]);
+        assert_eq!(
+            capabilities.warning().unwrap(),
+            format!("Could not write to '{}', '{}'; disabled features: history, trace", history_file.display(), trace_file.display())
+        );
+        //Reprobe doesn't change anything
+        capabilities.reprobe();
+        assert!(capabilities.is_degraded());
+    }
+
+    #[test]
+    fn test_capabilities_reprobe() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let config_dir: PathBuf = tmpdir.path().join("pyc");
+        //Config dir is a file
+        fs::write(config_dir.as_path(), "").unwrap();
+        let history_file: PathBuf = config_dir.join("pyc_history");
+        let mut capabilities: Capabilities = Capabilities::probe_outputs(Some(&history_file), None);
+        assert!(!capabilities.is_enabled(Feature::History));
+        //Fix config dir
+        fs::remove_file(config_dir.as_path()).unwrap();
+        capabilities.reprobe();
+        assert!(capabilities.is_enabled(Feature::History));
+        assert!(!capabilities.is_degraded());
+    }
+}
@@ -0,0 +1,387 @@
+//! ## Doctor
+//!
+//! `doctor` provides the self-test which checks that the environment pyc runs in works end-to-end
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate ansi_term;
+#[cfg(not(feature = "git-lite"))]
+extern crate git2;
+extern crate nix;
+extern crate tempfile;
+
+use ansi_term::Colour;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::capabilities::{Capabilities, Feature};
+use crate::config::{Config, ConfigErrorCode};
+#[cfg(feature = "git-lite")]
+use crate::shell::proc::process::capture_output;
+use crate::shell::{render_prompt, Shell};
+use crate::translator::ioprocessor::IOProcessor;
+use crate::translator::lang::Language;
+use crate::translator::new_translator;
+use crate::translator::trace::TraceOutput;
+
+//Sample used for the round-trip translation check; it must survive the round trip in every language
+const ROUND_TRIP_SAMPLE: &str = "ls -la /tmp";
+
+/// ### DoctorStatus
+///
+/// DoctorStatus describes the outcome of a check. Only failed checks make the doctor exit with an error
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum DoctorStatus {
+    Ok,
+    Warning,
+    Failed,
+}
+
+/// ### DoctorResult
+///
+/// DoctorResult is the result of a single check
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct DoctorResult {
+    pub name: String,
+    pub status: DoctorStatus,
+    pub message: String,
+}
+
+/// ### DoctorSettings
+///
+/// DoctorSettings contains the settings the doctor checks
+#[derive(Clone, std::fmt::Debug)]
+pub struct DoctorSettings {
+    pub config_file: PathBuf,
+    pub shell: Option<String>,
+    pub history_file: Option<PathBuf>,
+    pub trace_output: Option<TraceOutput>,
+    pub shell_timeout: Duration,
+}
+
+impl DoctorResult {
+    /// ### ok
+    ///
+    /// Instantiates a new successful DoctorResult
+    pub fn ok(name: &str, message: String) -> DoctorResult {
+        DoctorResult::new(name, DoctorStatus::Ok, message)
+    }
+
+    /// ### warning
+    ///
+    /// Instantiates a new DoctorResult for a soft check which didn't pass
+    pub fn warning(name: &str, message: String) -> DoctorResult {
+        DoctorResult::new(name, DoctorStatus::Warning, message)
+    }
+
+    /// ### failed
+    ///
+    /// Instantiates a new DoctorResult for a hard check which didn't pass
+    pub fn failed(name: &str, message: String) -> DoctorResult {
+        DoctorResult::new(name, DoctorStatus::Failed, message)
+    }
+
+    fn new(name: &str, status: DoctorStatus, message: String) -> DoctorResult {
+        DoctorResult {
+            name: String::from(name),
+            status,
+            message,
+        }
+    }
+}
+
+/// ### run
+///
+/// Run all the checks and return their results.
+/// The configuration is loaded once; if it can't be parsed, the default configuration is checked instead
+pub fn run(settings: &DoctorSettings) -> Vec<DoctorResult> {
+    let config: Config = Config::parse_config(settings.config_file.clone()).unwrap_or_else(|_| Config::default());
+    let (exec, args): (String, Vec<String>) = super::resolve_shell(&config, settings.shell.clone());
+    //NOTE: checks are listed here
+    let mut results: Vec<DoctorResult> = vec![
+        check_config(settings.config_file.as_path()),
+        check_shell(exec, args, &config, settings.shell_timeout),
+        check_pty(),
+    ];
+    results.append(&mut check_output_paths(settings.history_file.as_ref(), settings.trace_output.as_ref()));
+    results.push(check_git());
+    results.push(check_prompt(&config));
+    results.push(check_colors());
+    results.append(&mut check_translators());
+    results
+}
+
+/// ### report
+///
+/// Print the results as a table and return the exit code: 1 if any hard check failed, 0 otherwise
+pub fn report(results: &[DoctorResult]) -> u8 {
+    let width: usize = results.iter().map(|r| r.name.chars().count()).max().unwrap_or(0);
+    for result in results.iter() {
+        let mark: String = match result.status {
+            DoctorStatus::Ok => Colour::Green.paint("✓").to_string(),
+            DoctorStatus::Warning => Colour::Yellow.paint("!").to_string(),
+            DoctorStatus::Failed => Colour::Red.paint("✗").to_string(),
+        };
+        println!("{} {:width$}  {}", mark, result.name, result.message, width = width);
+    }
+    match results.iter().any(|r| r.status == DoctorStatus::Failed) {
+        true => 1,
+        false => 0,
+    }
+}
+
+//@! Checks
+
+/// ### check_config
+///
+/// Check that the configuration file can be parsed. A missing file is a warning, since the default configuration is used
+fn check_config(config_file: &Path) -> DoctorResult {
+    match Config::parse_config(config_file.to_path_buf()) {
+        Ok(config) => match config.warnings() {
+            warnings if warnings.is_empty() => DoctorResult::ok("config", format!("{} parsed", config_file.display())),
+            warnings => DoctorResult::warning("config", format!("{} parsed; {}", config_file.display(), warnings.join("; "))),
+        },
+        Err(err) => match err.code {
+            ConfigErrorCode::NoSuchFileOrDirectory => DoctorResult::warning(
+                "config",
+                format!("{} doesn't exist; using default configuration", config_file.display()),
+            ),
+            _ => DoctorResult::failed("config", format!("{}: {}", config_file.display(), err)),
+        },
+    }
+}
+
+/// ### check_shell
+///
+/// Check that the shell starts and answers the handshake within the timeout
+fn check_shell(exec: String, args: Vec<String>, config: &Config, timeout: Duration) -> DoctorResult {
+    let mut shell: Shell = match Shell::start(exec.clone(), args, &config.prompt_config) {
+        Ok(shell) => shell,
+        Err(err) => return DoctorResult::failed("shell", format!("could not start {}: {}", exec, err)),
+    };
+    let started: Instant = Instant::now();
+    let result: DoctorResult = match shell.handshake(timeout) {
+        Ok(_) => DoctorResult::ok("shell", format!("{} answered in {}ms", exec, started.elapsed().as_millis())),
+        Err(err) => DoctorResult::failed("shell", format!("{}: {}; {}", exec, err, err.hints().join("; "))),
+    };
+    let _ = shell.stop();
+    result
+}
+
+/// ### check_pty
+///
+/// Check that a pseudo terminal can be allocated
+fn check_pty() -> DoctorResult {
+    match nix::pty::openpty(None, None) {
+        Ok(pty) => {
+            let _ = nix::unistd::close(pty.master);
+            let _ = nix::unistd::close(pty.slave);
+            DoctorResult::ok("pty", String::from("pseudo terminal allocated"))
+        }
+        Err(err) => DoctorResult::warning("pty", format!("could not allocate a pseudo terminal: {}", err)),
+    }
+}
+
+/// ### check_output_paths
+///
+/// Check that the files written by pyc (history and translation trace) are writable
+fn check_output_paths(history_file: Option<&PathBuf>, trace_output: Option<&TraceOutput>) -> Vec<DoctorResult> {
+    let capabilities: Capabilities = Capabilities::probe_outputs(history_file, trace_output);
+    let mut results: Vec<DoctorResult> = Vec::new();
+    for feature in [Feature::History, Feature::TranslationTrace].iter() {
+        let path: &Path = match capabilities.path(*feature) {
+            Some(path) => path,
+            None => continue,
+        };
+        results.push(match capabilities.is_enabled(*feature) {
+            true => DoctorResult::ok(feature.name(), format!("{} is writable", path.display())),
+            false => DoctorResult::failed(feature.name(), format!("{} is not writable", path.display())),
+        });
+    }
+    results
+}
+
+/// ### check_git
+///
+/// Check that git2 can create and open a repository
+#[cfg(not(feature = "git-lite"))]
+fn check_git() -> DoctorResult {
+    let tmpdir: tempfile::TempDir = match tempfile::TempDir::new() {
+        Ok(dir) => dir,
+        Err(err) => return DoctorResult::failed("git", format!("could not create temporary directory: {}", err)),
+    };
+    if let Err(err) = git2::Repository::init(tmpdir.path()) {
+        return DoctorResult::failed("git", format!("could not init test repository: {}", err));
+    }
+    match git2::Repository::open(tmpdir.path()) {
+        Ok(_) => DoctorResult::ok("git", String::from("test repository opened")),
+        Err(err) => DoctorResult::failed("git", format!("could not open test repository: {}", err)),
+    }
+}
+
+/// ### check_git
+///
+/// Check that the git executable, used to get the status of the repositories, can be run
+#[cfg(feature = "git-lite")]
+fn check_git() -> DoctorResult {
+    match capture_output(&["git", "--version"], env::temp_dir().as_path(), Duration::from_secs(5)) {
+        Ok(version) => DoctorResult::ok("git", format!("{} (git-lite)", version.trim())),
+        Err(err) => DoctorResult::warning("git", format!("could not run git ({}): repository status is not available", err)),
+    }
+}
+
+/// ### check_prompt
+///
+/// Render the prompt in the current directory and report how long it took.
+/// If the modules take longer than `prompt.module_timeout_ms`, the interactive prompt is rendered with their previous value
+fn check_prompt(config: &Config) -> DoctorResult {
+    let wrkdir: PathBuf = env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+    let processor: IOProcessor = IOProcessor::new(Language::Nil, new_translator(Language::Nil));
+    let started: Instant = Instant::now();
+    let _ = render_prompt(&config.prompt_config, 0, Duration::from_millis(0), wrkdir, &processor);
+    let elapsed: u128 = started.elapsed().as_millis();
+    match elapsed >= config.prompt_config.module_timeout as u128 {
+        false => DoctorResult::ok("prompt", format!("rendered in {}ms", elapsed)),
+        true => DoctorResult::warning(
+            "prompt",
+            format!(
+                "rendered in {}ms, more than prompt.module_timeout_ms ({}ms): the previous values are displayed",
+                elapsed, config.prompt_config.module_timeout
+            ),
+        ),
+    }
+}
+
+/// ### check_colors
+///
+/// Check that the terminal supports the colors used by pyc
+fn check_colors() -> DoctorResult {
+    if env::var_os("NO_COLOR").is_some() {
+        return DoctorResult::warning("colors", String::from("NO_COLOR is set, but pyc prints colors"));
+    }
+    match env::var("TERM") {
+        Err(_) => DoctorResult::warning("colors", String::from("TERM is not set")),
+        Ok(term) if term == "dumb" => DoctorResult::warning("colors", String::from("TERM is 'dumb' and doesn't support colors")),
+        Ok(term) => match nix::unistd::isatty(1) {
+            Ok(true) => DoctorResult::ok("colors", format!("{} supports ANSI colors", term)),
+            _ => DoctorResult::warning("colors", format!("{}, but stdout is not a terminal", term)),
+        },
+    }
+}
+
+/// ### check_translators
+///
+/// Check for each language that a command survives the round trip through the translator
+fn check_translators() -> Vec<DoctorResult> {
+    let languages: [Language; 6] = [
+        Language::Belarusian,
+        Language::Bulgarian,
+        Language::Kazakh,
+        Language::Russian,
+        Language::Serbian,
+        Language::Ukrainian,
+    ];
+    languages
+        .iter()
+        .map(|language| check_translator(*language))
+        .collect()
+}
+
+/// ### check_translator
+///
+/// Translate the sample to cyrillic and back to latin
+fn check_translator(language: Language) -> DoctorResult {
+    let name: String = format!("translator {}", language.to_string());
+    let translator = new_translator(language);
+    let cyrillic: String = translator.to_cyrillic(&String::from(ROUND_TRIP_SAMPLE));
+    let latin: String = translator.to_latin(&cyrillic);
+    match latin == ROUND_TRIP_SAMPLE {
+        true => DoctorResult::ok(name.as_str(), format!("'{}' -> '{}' -> '{}'", ROUND_TRIP_SAMPLE, cyrillic, latin)),
+        false => DoctorResult::failed(name.as_str(), format!("'{}' became '{}'", ROUND_TRIP_SAMPLE, latin)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    fn settings(config_file: PathBuf, shell: Option<String>, history_file: Option<PathBuf>) -> DoctorSettings {
+        DoctorSettings {
+            config_file,
+            shell,
+            history_file,
+            trace_output: None,
+            shell_timeout: Duration::from_secs(5),
+        }
+    }
+
+    fn find<'a>(results: &'a [DoctorResult], name: &str) -> &'a DoctorResult {
+        results.iter().find(|r| r.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_doctor_healthy() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let mut config_file: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        writeln!(config_file, "language: ru").unwrap();
+        let results: Vec<DoctorResult> = run(&settings(
+            config_file.path().to_path_buf(),
+            Some(String::from("sh")),
+            Some(tmpdir.path().join("pyc_history")),
+        ));
+        assert_eq!(find(&results, "config").status, DoctorStatus::Ok);
+        assert_eq!(find(&results, "shell").status, DoctorStatus::Ok);
+        assert_eq!(find(&results, "history").status, DoctorStatus::Ok);
+        assert_eq!(find(&results, "git").status, DoctorStatus::Ok);
+        assert!(find(&results, "prompt").message.starts_with("rendered in "));
+        assert_eq!(find(&results, "translator рус").status, DoctorStatus::Ok);
+        assert!(results.iter().all(|r| r.status != DoctorStatus::Failed));
+        assert_eq!(report(&results), 0);
+    }
+
+    #[test]
+    fn test_doctor_broken_settings() {
+        //History file's parent is a file, so it can't be created
+        let history_parent: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let results: Vec<DoctorResult> = run(&settings(
+            PathBuf::from("/nonexistent/pyc.yml"),
+            Some(String::from("/bin/this-shell-doesnt-exist")),
+            Some(history_parent.path().join("pyc_history")),
+        ));
+        assert_eq!(find(&results, "config").status, DoctorStatus::Warning);
+        let shell: &DoctorResult = find(&results, "shell");
+        assert_eq!(shell.status, DoctorStatus::Failed);
+        assert!(shell.message.contains("/bin/this-shell-doesnt-exist"));
+        assert_eq!(find(&results, "history").status, DoctorStatus::Failed);
+        assert_eq!(report(&results), 1);
+    }
+
+    #[test]
+    fn test_doctor_bad_config() {
+        let mut config_file: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        writeln!(config_file, "language: [").unwrap();
+        assert_eq!(check_config(config_file.path()).status, DoctorStatus::Failed);
+    }
+}
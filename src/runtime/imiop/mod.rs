@@ -29,6 +29,8 @@
 use crate::shell::Shell;
 use crate::utils::console::InputEvent;
 
+use std::path::Path;
+
 // List of Imiop
 pub(crate) mod shiop;
 pub(crate) mod subprociop;
@@ -41,6 +43,43 @@ pub(crate) trait Imiop {
     ///
     /// Handle input event received from stdin
     fn handle_input_event(&mut self, ev: InputEvent, shell: &mut Shell);
+
+    /// ### take_dispatched_command
+    ///
+    /// Returns the last command written to the shell by the Imiop, if any, and resets it
+    fn take_dispatched_command(&mut self) -> Option<String> {
+        None
+    }
+
+    /// ### take_reload_request
+    ///
+    /// Returns whether the user has requested the configuration to be reloaded, and resets the request
+    fn take_reload_request(&mut self) -> bool {
+        false
+    }
+
+    /// ### dispatch_input
+    ///
+    /// Process an input line which has not been typed by the user (e.g. received from the session socket),
+    /// as if it had been typed. Imiops which can't process it ignore it
+    fn dispatch_input(&mut self, _input: String, _shell: &mut Shell) {}
+
+    /// ### source_input
+    ///
+    /// Process a line of the rc file as if it had been typed, but silently (no echo, no history, no prompt).
+    /// Imiops which can't process it ignore it
+    fn source_input(&mut self, _input: String, _shell: &mut Shell) {}
+
+    /// ### redraw_prompt
+    ///
+    /// Render the prompt again, keeping the line being edited. Called when the values of async prompt modules have changed.
+    /// Imiops which don't show the prompt ignore it
+    fn redraw_prompt(&mut self, _shell: &mut Shell) {}
+
+    /// ### set_config_file
+    ///
+    /// Set the configuration file in use, reported by `help`
+    fn set_config_file(&mut self, _config_file: &Path) {}
 }
 
 // TODO: add factory for imiop
@@ -26,35 +26,70 @@
 
 use super::Imiop;
 use crate::config::Config;
-use crate::runtime::{console_fmt, print_err, print_out, resolve_command};
+use crate::runtime::output::{console_fmt, print_out};
+use crate::runtime::{echo_line, print_err, print_expression_err, resolve_command, write_trace};
+use crate::shell::alias::{self, AliasError};
+use crate::shell::builtins::{self, KillTarget, HELP_ALIAS};
+use crate::shell::completion::definitions::CompletionDefinitions;
+use crate::shell::completion::index::CommandIndex;
+use crate::shell::completion::{Completer, Completion};
+use crate::config::AliasOptions;
+use crate::shell::dirstack::{resolve_dir, DirError};
+use crate::shell::environment::{self, split_words, EnvError};
+use crate::shell::expansion::{expand_globs, expand_variables, ExpansionContext, ExpansionError};
+use crate::shell::jobs::Job;
+use crate::shell::lineeditor::{LineEditor, MultilineInput, ReverseSearch, SearchAction, PASTE_NEWLINE};
+use crate::shell::proc::ExecOptions;
 use crate::shell::Shell;
-use crate::translator::ioprocessor::IOProcessor;
+use crate::translator::ioprocessor::{ExpressionParserError, IOProcessor};
+use crate::translator::trace::{DispatchMode, TokenDecision, TokenTrace, TraceOutput, TranslationTrace};
 use crate::utils::buffer;
 use crate::utils::console::{self, InputEvent};
+use crate::utils::logger;
+
+use std::cell::Cell;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 pub(crate) struct ShIop {
-    input_buffer: Vec<char>,
-    input_buffer_cursor: usize,
-    rev_search: Option<String>, // Reverse search match
-    rev_search_idx: usize,      // Reverse search last match index
-    history_index: usize,
+    editor: LineEditor,
+    multiline: MultilineInput, // Lines of a command which continues on the next line
+    rev_search: Option<ReverseSearch>, // Reverse search started with Ctrl+R
     config: Config,
     processor: IOProcessor,
+    trace_output: Option<TraceOutput>,
+    dispatched_command: Option<String>,
+    last_tab: bool, // Whether the previous input event was a Tab
+    completion_definitions: Option<CompletionDefinitions>, // Loaded at the first completion
+    command_index: Option<CommandIndex>, // Built at the first completion
+    reload_requested: bool, // Whether `reload` has been run
+    config_file: Option<PathBuf>, // Configuration file reported by `help`
+    sourcing: bool, // Whether a line of the rc file is being run (no prompt, no history)
+    builtin_started: Cell<Option<Instant>>, // When the line being handled by pyc has been entered
 }
 
 impl ShIop {
     /// ### new
     ///
     /// Instantiate a new `ShIop`
-    pub fn new(config: Config, processor: IOProcessor) -> ShIop {
+    pub fn new(config: Config, processor: IOProcessor, trace_output: Option<TraceOutput>) -> ShIop {
         ShIop {
-            input_buffer: Vec::with_capacity(2048),
-            input_buffer_cursor: 0,
+            editor: LineEditor::new(),
+            multiline: MultilineInput::new(),
             rev_search: None,
-            rev_search_idx: 0,
-            history_index: 0,
             config: config,
             processor: processor,
+            trace_output,
+            dispatched_command: None,
+            last_tab: false,
+            completion_definitions: None,
+            command_index: None,
+            reload_requested: false,
+            config_file: None,
+            sourcing: false,
+            builtin_started: Cell::new(None),
         }
     }
 
@@ -62,8 +97,7 @@ impl ShIop {
     ///
     /// Clear buffer and reset cursor to 0
     fn clear_buffer(&mut self) {
-        self.input_buffer.clear();
-        self.input_buffer_cursor = 0;
+        self.editor.clear();
     }
 
     /// ### reset_history_index
@@ -71,102 +105,157 @@ impl ShIop {
     /// Reset history index to 0
     fn reset_history_index(&mut self) {
         //Reset history index too
-        self.history_index = 0;
+        self.editor.reset_history();
     }
 
     /// ### backspace
     ///
     /// Perform backspace on current console and buffers
     fn backspace(&mut self) {
-        //Remove from buffer and backspace (if possible)
-        if self.input_buffer_cursor > 0 {
-            self.input_buffer_cursor -= 1;
-            if self.input_buffer.len() > self.input_buffer_cursor {
-                self.input_buffer.remove(self.input_buffer_cursor);
-            }
-            console::backspace();
-        }
+        self.edit(|editor| editor.backspace());
     }
 
     /// ### move_left
     ///
     /// Move cursor to left
     fn move_left(&mut self) {
-        //If possible, move the cursor right
-        if self.input_buffer_cursor != 0 {
-            self.input_buffer_cursor -= 1;
-            console::move_cursor_left();
-        }
+        self.edit(|editor| editor.move_left());
     }
 
     /// ### move_right
     ///
     /// Move cursor to right
     fn move_right(&mut self) {
-        //If possible, move the cursor left
-        if self.input_buffer_cursor + 1 <= self.input_buffer.len() {
-            self.input_buffer_cursor += 1;
-            console::move_cursor_right();
-        }
-    }
-
-    /// ### perform_history_backward
-    ///
-    /// Get previous element in history and put it into the buffer
-    fn perform_history_backward(&mut self, shell: &mut Shell) {
-        //Match history size
-        if self.history_index > 1 {
-            //Decrement history index
-            self.history_index -= 1;
-            //Check if history has index
-            if let Some(cmd) = shell.history.at(self.history_index - 1) {
-                let prev_len: usize = self.input_buffer.len();
-                //Clear buffer
-                self.clear_buffer();
-                //Push command to buffer
-                for ch in cmd.chars() {
-                    //Push character
-                    self.input_buffer.push(ch);
-                    //Increment buffer pointer
-                    self.input_buffer_cursor += 1;
-                }
-                //Rewrite line
-                console::rewrite(cmd, prev_len);
-            }
-        } else if self.history_index == 1 {
-            let prev_len: usize = self.input_buffer.len();
-            //Put history index to 0
-            self.history_index = 0;
-            //Clear buffer
-            self.clear_buffer();
-            console::rewrite(String::from(""), prev_len);
-        }
-    }
-
-    /// ### perform_history_forward
-    ///
-    /// Get next element in history and put it into the buffer
-    fn perform_history_forward(&mut self, shell: &mut Shell) {
-        //Match history size
-        if self.history_index + 1 <= shell.history.len() {
-            //Increment history index
-            self.history_index += 1;
-            //Check if history has index
-            if let Some(cmd) = shell.history.at(self.history_index - 1) {
-                let prev_len: usize = self.input_buffer.len();
-                //Clear buffer
-                self.clear_buffer();
-                //Push command to buffer
-                for ch in cmd.chars() {
-                    //Push character
-                    self.input_buffer.push(ch);
-                    //Increment buffer pointer
-                    self.input_buffer_cursor += 1;
-                }
-                //Rewrite line
-                console::rewrite(cmd, prev_len);
+        self.edit(|editor| editor.move_right());
+    }
+
+    /// ### perform_edit
+    ///
+    /// Apply an editing or history navigation event to the line editor and redraw the line
+    fn perform_edit(&mut self, ev: InputEvent, shell: &mut Shell) {
+        let history = &shell.history;
+        self.edit(|editor| editor.handle_input_event(&ev, history));
+    }
+
+    /// ### edit
+    ///
+    /// Apply `edit_fn` to the line editor; if the line has changed, it is redrawn, otherwise only the cursor is moved.
+    /// Columns are computed from the characters width, not from their length in bytes
+    fn edit<F>(&mut self, edit_fn: F)
+    where
+        F: FnOnce(&mut LineEditor) -> bool,
+    {
+        let prev_line: Vec<char> = self.editor.buffer.clone();
+        let prev_column: usize = self.editor.cursor_column();
+        if !edit_fn(&mut self.editor) {
+            return;
+        }
+        let appended: bool = prev_column == console::str_width(buffer::chars_to_string(&prev_line).as_str())
+            && self.editor.cursor == self.editor.buffer.len()
+            && self.editor.buffer.starts_with(&prev_line);
+        if prev_line == self.editor.buffer {
+            console::move_cursor(prev_column, self.editor.cursor_column());
+        } else if appended {
+            //Text has been typed at the end of the line; just print it
+            console::print(buffer::printable(buffer::chars_to_string(&self.editor.buffer[prev_line.len()..].to_vec()).as_str()));
+        } else {
+            console::redraw(buffer::printable(self.editor.get_line().as_str()), prev_column, self.editor.cursor_column());
+        }
+    }
+
+    /// ### complete
+    ///
+    /// Complete the token before the cursor. If there is only one candidate, the token is replaced with it;
+    /// otherwise the common prefix of the candidates is inserted and, if Tab has been pressed twice,
+    /// the candidates are listed on a new line and the prompt is rendered again
+    fn complete(&mut self, shell: &mut Shell) {
+        if self.completion_definitions.is_none() {
+            let (definitions, err) = CompletionDefinitions::load(CompletionDefinitions::user_file().as_deref());
+            if let Some(err) = err {
+                console::println(String::new());
+                print_err(
+                    format!("Could not load completion definitions: {}", err),
+                    self.config.output_config.translate_stderr,
+                    &self.processor,
+                );
+                self.print_line(shell);
             }
+            self.completion_definitions = Some(definitions);
+        }
+        //Build the index of the executables again if PATH has changed
+        let path: Option<OsString> = env::var_os("PATH");
+        if self.command_index.as_ref().map(|index| index.is_stale(&path)).unwrap_or(true) {
+            self.command_index = Some(CommandIndex::build(path));
+        }
+        let aliases: Vec<String> = shell.aliases.keys().cloned().collect();
+        let config: &Config = &self.config;
+        let find_aliases = |prefix: &str| config.find_aliases(prefix);
+        let completion: Completion = match (self.completion_definitions.as_ref(), self.command_index.as_ref()) {
+            (Some(definitions), Some(index)) => Completer::new(&self.processor, aliases, shell.get_wrkdir())
+                .with_alias_search(&find_aliases)
+                .with_definitions(definitions)
+                .with_index(index)
+                .complete(&self.editor.buffer, self.editor.cursor),
+            _ => return,
+        };
+        let replacement: String = match completion.replacement() {
+            Some(replacement) => replacement,
+            None => return,
+        };
+        let token: String = self.editor.buffer[completion.start..completion.end].iter().collect();
+        if replacement != token && (completion.candidates.len() == 1 || self.processor.text_to_latin(&replacement).chars().count() > self.processor.text_to_latin(&token).chars().count()) {
+            self.edit(|editor| {
+                editor.replace_range(completion.start, completion.end, replacement.as_str());
+                true
+            });
+        } else if completion.candidates.len() > 1 && self.last_tab {
+            //List candidates and render the prompt again
+            console::println(String::new());
+            console::println(completion.display_names().join("  "));
+            self.print_line(shell);
+        }
+    }
+
+    /// ### print_line
+    ///
+    /// Print the prompt and the line being edited, then move the cursor to its position
+    fn print_line(&self, shell: &mut Shell) {
+        match self.multiline.is_pending() {
+            true => console::print(format!("{}{}", self.config.prompt_config.continuation, buffer::printable(self.editor.get_line().as_str()))),
+            false => console::print(format!("{} {}", shell.get_promptline(&self.processor), buffer::printable(self.editor.get_line().as_str()))),
         }
+        console::move_cursor(console::str_width(self.editor.get_line().as_str()), self.editor.cursor_column());
+    }
+
+    /// ### clear_screen
+    ///
+    /// Clear the screen (and the scrollback if `terminal.clear_scrollback` is set), then print the prompt and the line being edited again.
+    /// The prompt is rendered with the values of the prompt modules already resolved, which are not run again
+    fn clear_screen(&self, shell: &mut Shell) {
+        console::clear(self.config.terminal_config.clear_scrollback);
+        let prompt: String = match self.multiline.is_pending() {
+            true => self.config.prompt_config.continuation.clone(),
+            false => format!("{} ", shell.redraw_promptline(&self.processor)),
+        };
+        console::print(format!("{}{}", prompt, buffer::printable(self.editor.get_line().as_str())));
+        console::move_cursor(console::str_width(self.editor.get_line().as_str()), self.editor.cursor_column());
+    }
+
+    /// ### redraw
+    ///
+    /// Replace the prompt and the line being edited with the prompt returned by `redraw_promptline`
+    fn redraw(&self, shell: &mut Shell) {
+        let prompt_line: String = shell.redraw_promptline(&self.processor);
+        console::clear_lines(prompt_line.matches('\n').count());
+        console::print(format!("{} {}", prompt_line, buffer::printable(self.editor.get_line().as_str())));
+        console::move_cursor(console::str_width(self.editor.get_line().as_str()), self.editor.cursor_column());
+    }
+
+    /// ### kill_args
+    ///
+    /// Returns the arguments of a `kill` line; signal names typed in cyrillic are converted to latin
+    fn kill_args(&self, input: &str) -> Vec<String> {
+        input.split_whitespace().skip(1).map(|arg| self.processor.text_to_latin(&String::from(arg))).collect()
     }
 
     /// ### indent_history_index
@@ -184,28 +273,74 @@ impl ShIop {
         }
     }
 
-    /// ### search_reverse
+    /// ### print_search
+    ///
+    /// Replace the input line with the reverse search line: "(reverse-i-search)`query': candidate"
+    fn print_search(&self) {
+        if let Some(search) = &self.rev_search {
+            console::print(format!(
+                "\r\x1b[K{}`{}': {}",
+                console_fmt(String::from(search.label()), self.config.output_config.translate_stdout, &self.processor),
+                search.query(),
+                buffer::printable(search.candidate().unwrap_or(""))
+            ));
+        }
+    }
+
+    /// ### handle_search_event
     ///
-    /// Perform reverse search
-    /// Returns matched command in history
-    fn search_reverse(&mut self, shell: &Shell) -> Option<String> {
-        let current_match: String = match &self.rev_search {
-            Some(s) => s.clone(),
-            None => return None,
+    /// Handle an input event while the reverse search is active; when the search is over, the prompt and the line are redrawn.
+    /// Returns whether the event has been consumed by the search
+    fn handle_search_event(&mut self, ev: &InputEvent, shell: &mut Shell) -> bool {
+        let action: SearchAction = match self.rev_search.as_mut() {
+            Some(search) => search.handle_input_event(ev, &mut self.editor, &shell.history),
+            None => return false,
         };
-        // Iterate over history
-        for i in self.rev_search_idx..shell.history.len() {
-            // Check if element at index matches (and is different than previous match)
-            if let Some(check_match) = shell.history.at(i) {
-                if check_match.contains(current_match.as_str()) {
-                    // Update index
-                    self.rev_search_idx = i + 1; // i + 1, in order to avoid same result at next cycle
-                                                 // Return match
-                    return Some(check_match.clone());
+        if action == SearchAction::Update {
+            self.print_search();
+            return true;
+        }
+        self.rev_search = None;
+        self.redraw(shell);
+        match action {
+            SearchAction::Execute => {
+                self.perform_interactive_enter(shell);
+                true
+            }
+            SearchAction::Leave => false,
+            _ => true,
+        }
+    }
+
+    /// ### expand_history_event
+    ///
+    /// If input is an history event (`!N`), returns the command at the N-th position of the history;
+    /// otherwise input is returned as it is.
+    /// None is returned if the event doesn't exist
+    fn expand_history_event(&self, shell: &mut Shell, input: String) -> Option<String> {
+        if !input.starts_with("!") {
+            return Some(input);
+        }
+        //Get index
+        let history_index: &str = input[1..].trim();
+        //Convert index to number
+        if let Ok(history_index) = history_index.parse::<usize>() {
+            //Check if index is bigger than history lenght
+            if history_index < shell.history.len() {
+                //Reverse index
+                if let Some(cmd) = shell.history.at(shell.history.len() - history_index - 1) {
+                    //Event exists, replace input with command
+                    return Some(cmd);
                 }
             }
         }
-        // Return None if not found
+        //Event doesn't exist
+        print_err(
+            format!("{}: event not found", input.trim()),
+            self.config.output_config.translate_stderr,
+            &self.processor,
+        );
+        self.print_prompt(shell);
         None
     }
 
@@ -215,131 +350,503 @@ impl ShIop {
     fn perform_interactive_enter(&mut self, shell: &mut Shell) {
         //Reset history index
         self.reset_history_index();
-        // Exit reverse search
-        self.rev_search = None;
         //Newline first
         console::println(String::new());
         //Convert input buffer to string
-        let stdin_input: String = buffer::chars_to_string(&self.input_buffer);
+        let stdin_input: String = buffer::chars_to_string(&self.editor.buffer);
+        //Clear input buffer
+        self.clear_buffer();
         //If input is empty, print prompt (if state is IDLE)
-        if stdin_input.trim().len() == 0 {
-            console::print(format!("{} ", shell.get_promptline(&self.processor)));
-            self.clear_buffer();
-        } else {
-            //Treat input
-            //If state is Idle, convert expression, otherwise convert text
-            let input: String = {
-                //Resolve alias
-                let mut argv: Vec<String> =
-                    Vec::with_capacity(stdin_input.matches(" ").count() + 1);
-                for arg in stdin_input.split_whitespace() {
-                    argv.push(String::from(arg));
+        if stdin_input.trim().is_empty() && !self.multiline.is_pending() {
+            self.print_prompt(shell);
+            return;
+        }
+        //Keep reading lines while the command continues on the next line
+        match self.multiline.push(stdin_input.as_str()) {
+            Some(input) if input.trim().is_empty() => self.print_prompt(shell),
+            Some(input) => self.process_input_line(shell, input),
+            None => console::print(self.config.prompt_config.continuation.clone()),
+        }
+    }
+
+    /// ### process_input_line
+    ///
+    /// Expand, translate and process a (not empty) input line
+    fn process_input_line(&mut self, shell: &mut Shell, stdin_input: String) {
+        //Time the line until it's written to the shell (builtins)
+        self.builtin_started.set(Some(Instant::now()));
+        //Replace history event with the command it refers to
+        let stdin_input: String = match self.expand_history_event(shell, stdin_input) {
+            Some(input) => input,
+            None => return,
+        };
+        //Push input to history as it has been typed by the user
+        if !self.sourcing {
+            shell.history.push(stdin_input.clone());
+        }
+        //Pasted lines are executed as a script
+        let stdin_input: String = match stdin_input.contains(PASTE_NEWLINE) {
+            true => stdin_input.replace(PASTE_NEWLINE, "\n").trim().to_string(),
+            false => stdin_input,
+        };
+        if stdin_input.is_empty() {
+            self.print_prompt(shell);
+            return;
+        }
+        let multiline: bool = stdin_input.contains('\n');
+        //Reload builtin: the configuration is reloaded by the runtime
+        if !multiline && self.reload_builtin(stdin_input.as_str()) {
+            return;
+        }
+        //Help builtin
+        if !multiline && self.help_builtin(shell, stdin_input.as_str()) {
+            return;
+        }
+        //Alias builtin: aliases are defined as typed
+        if !multiline && self.alias_builtin(shell, stdin_input.as_str()) {
+            return;
+        }
+        //Environment builtins: variable names are translated, values are not
+        if !multiline && self.environment_builtin(shell, stdin_input.as_str()) {
+            return;
+        }
+        //Directory builtins: directories are looked up as typed first
+        if !multiline && self.directory_builtin(shell, stdin_input.as_str()) {
+            return;
+        }
+        //Treat input
+        //If state is Idle, convert expression, otherwise convert text
+        let input: String = {
+            //Resolve alias (of the first line only)
+            let (first_line, next_lines): (&str, &str) = match stdin_input.find('\n') {
+                Some(pos) => stdin_input.split_at(pos),
+                None => (stdin_input.as_str(), ""),
+            };
+            let mut argv: Vec<String> =
+                Vec::with_capacity(first_line.matches(" ").count() + 1);
+            for arg in first_line.split_whitespace() {
+                argv.push(String::from(arg));
+            }
+            //Process arg 0
+            let command: String = argv[0].clone();
+            let alias_words: usize = match resolve_command(&mut argv, &self.config, &shell.aliases) {
+                Ok(words) => words,
+                Err(err) => {
+                    print_err(format!("{}: {}", command, err), self.config.output_config.translate_stderr, &self.processor);
+                    self.print_prompt(shell);
+                    return;
                 }
-                //Process arg 0
-                resolve_command(&mut argv, &self.config);
-                //Rejoin arguments
-                let input: String = argv.join(" ") + "\n";
-                match &self.processor.expression_to_latin(&input) {
-                    Ok(ex) => ex.clone(),
-                    Err(err) => {
-                        print_err(
-                            String::from(format!("Input error: {:?}", err)),
-                            self.config.output_config.translate_output,
-                            &self.processor,
-                        );
-                        //Clear input buffer
-                        self.clear_buffer();
-                        return;
-                    }
+            };
+            //Working directory and environment of the alias
+            let exec_options: ExecOptions = match self.exec_options(shell, command.as_str()) {
+                Ok(options) => options,
+                Err(err) => {
+                    print_err(format!("{}: {}", command, err), self.config.output_config.translate_stderr, &self.processor);
+                    self.print_prompt(shell);
+                    return;
                 }
             };
-            //Clear input buffer
-            self.clear_buffer();
-            //Process input
-            self.process_input_interactive(shell, input);
+            //Rejoin arguments
+            let input: String = argv.join(" ") + next_lines + "\n";
+            let translated: Result<(String, Vec<TokenTrace>), ExpressionParserError> = match self.config.input_config.translation.command_line() {
+                true => self.processor.expression_to_latin_traced(&input),
+                false => Ok((input.clone(), Vec::new())),
+            };
+            match translated {
+                Ok((ex, tokens)) => {
+                    //Expand environment variables
+                    let ex: String = match self.expand_variables(shell, ex) {
+                        Ok(ex) => ex,
+                        Err(err) => {
+                            print_err(err.to_string(), self.config.output_config.translate_stderr, &self.processor);
+                            self.print_prompt(shell);
+                            return;
+                        }
+                    };
+                    //Expand glob patterns
+                    let ex: String = expand_globs(ex.as_str(), shell.get_wrkdir().as_path(), self.config.input_config.nullglob);
+                    let ex: String = apply_exec_options(&exec_options, ex);
+                    if let Some(trace_output) = &self.trace_output {
+                        let mut trace: TranslationTrace = TranslationTrace::new(stdin_input.clone(), DispatchMode::Interactive);
+                        if alias_words > 0 {
+                            trace.tokens.push(TokenTrace::new(command, argv[..alias_words].join(" "), TokenDecision::AliasExpanded));
+                        }
+                        trace.tokens.extend(tokens);
+                        trace.set_output(&ex);
+                        write_trace(&trace, trace_output);
+                    }
+                    ex
+                }
+                Err(err) => {
+                    print_expression_err(input.as_str(), err, self.config.output_config.translate_stderr, &self.processor);
+                    return;
+                }
+            }
+        };
+        //Process input
+        self.process_input_interactive(shell, input);
+    }
+
+    /// ### exec_options
+    ///
+    /// Returns the working directory and the environment the command has to be run with, if it is a configured alias
+    /// with a `cwd` or an `env` (aliases defined at runtime with the same name override it)
+    fn exec_options(&self, shell: &Shell, command: &str) -> Result<ExecOptions, DirError> {
+        let options: &AliasOptions = match self.config.alias_options.get(command) {
+            Some(options) if !shell.aliases.contains_key(command) => options,
+            _ => return Ok(ExecOptions::default()),
+        };
+        let wrkdir: Option<PathBuf> = match options.cwd.as_ref() {
+            Some(cwd) => Some(resolve_dir(cwd.as_str(), shell.get_wrkdir().as_path(), &|dir: &str| String::from(dir))?),
+            None => None,
+        };
+        Ok(ExecOptions {
+            wrkdir,
+            env: options.env.clone(),
+            clear_env: false,
+            pty: false,
+        })
+    }
+
+    /// ### reload_builtin
+    ///
+    /// If input is `reload`, request the configuration file to be parsed again; returns whether input has been handled.
+    /// The prompt is printed by the runtime once the configuration has been reloaded
+    fn reload_builtin(&mut self, input: &str) -> bool {
+        if self.processor.text_to_latin(&String::from(input.trim())) != "reload" {
+            return false;
         }
+        self.reload_requested = true;
+        true
     }
 
-    /// ### process_input_interactive
+    /// ### help_builtin
     ///
-    /// Process input after enter in interactive mode
-    fn process_input_interactive(&mut self, shell: &mut Shell, mut input: String) {
-        //@! Handle events before anything else
-        if input.starts_with("!") {
-            //Execute command from history
-            //Get index
-            let history_index: &str = &input.as_str()[1..input.len() - 1];
-            //Convert index to number
-            if let Ok(history_index) = history_index.parse::<usize>() {
-                //Check if index is bigger than history lenght
-                if history_index >= shell.history.len() {
-                    print_err(
-                        format!("!{}: event not found", history_index),
-                        self.config.output_config.translate_output,
-                        &self.processor,
-                    );
-                    console::print(format!("{} ", shell.get_promptline(&self.processor)));
-                    return;
+    /// If input is `help` (or `помощь`), print the builtins, the prompt keys, the language and the configuration file;
+    /// with `help NAME` only the usage of the builtin `NAME` is printed. Returns whether input has been handled
+    fn help_builtin(&mut self, shell: &mut Shell, input: &str) -> bool {
+        let mut words = input.split_whitespace();
+        let command: &str = words.next().unwrap_or("");
+        if command != HELP_ALIAS && self.processor.text_to_latin(&String::from(command)) != "help" {
+            return false;
+        }
+        match words.next().map(|name| self.processor.text_to_latin(&String::from(name))) {
+            None => self.print_lines(builtins::help(self.processor.language, self.config_file.as_deref())),
+            Some(name) => match builtins::find(name.as_str()) {
+                Some(builtin) => self.print_lines(vec![String::from(builtin.usage), format!("    {}", builtin.description)]),
+                None => print_err(format!("help: no help topics match `{}'", name), self.config.output_config.translate_stderr, &self.processor),
+            },
+        }
+        self.print_prompt(shell);
+        true
+    }
+
+    /// ### alias_builtin
+    ///
+    /// Run `alias` if input is it; returns whether input has been handled.
+    /// Aliases defined with `alias name=value` are kept for the whole session and override the configured ones
+    fn alias_builtin(&mut self, shell: &mut Shell, input: &str) -> bool {
+        let input: &str = input.trim();
+        let (command, args): (&str, &str) = match input.find(char::is_whitespace) {
+            Some(pos) => (&input[..pos], &input[pos..]),
+            None => (input, ""),
+        };
+        if self.processor.text_to_latin(&String::from(command)) != "alias" {
+            return false;
+        }
+        let args: Vec<String> = match split_words(args) {
+            Some(args) => args,
+            None => return false,
+        };
+        let result: Result<(), AliasError> = match args.iter().all(|arg| !arg.contains('=')) {
+            true => {
+                let aliases: Vec<String> = alias::list(&self.config.alias, &shell.aliases);
+                let aliases: Vec<String> = match args.is_empty() {
+                    true => aliases,
+                    false => aliases.into_iter().filter(|line| args.iter().any(|arg| line.starts_with(format!("alias {}=", arg).as_str()))).collect(),
+                };
+                let missing: Option<&String> = args.iter().find(|arg| !aliases.iter().any(|line| line.starts_with(format!("alias {}=", arg).as_str())));
+                self.print_lines(aliases);
+                match missing {
+                    Some(name) => Err(AliasError::NotFound(name.clone())),
+                    None => Ok(()),
                 }
-                //Reverse index
-                let history_index: usize = shell.history.len() - history_index - 1;
-                match shell.history.at(history_index) {
-                    Some(cmd) => {
-                        //Event exists, replace input with command
-                        //Reverse index
-                        input = format!("{}\n", cmd);
-                    }
-                    None => {
-                        //Event doesn't exist
-                        print_err(
-                            format!("!{}: event not found", history_index),
-                            self.config.output_config.translate_output,
-                            &self.processor,
-                        );
-                        console::print(format!("{} ", shell.get_promptline(&self.processor)));
-                        return;
-                    }
+            }
+            false => alias::define(&mut shell.aliases, &args),
+        };
+        if let Err(err) = result {
+            print_err(format!("alias: {}", err), self.config.output_config.translate_stderr, &self.processor);
+        }
+        self.print_prompt(shell);
+        true
+    }
+
+    /// ### environment_builtin
+    ///
+    /// Run `export`, `unset` or `env` if input is one of them; returns whether input has been handled.
+    /// Variables are set both in pyc's environment and in the shell
+    fn environment_builtin(&mut self, shell: &mut Shell, input: &str) -> bool {
+        let input: &str = input.trim();
+        let (command, args): (&str, &str) = match input.find(char::is_whitespace) {
+            Some(pos) => (&input[..pos], &input[pos..]),
+            None => (input, ""),
+        };
+        let command: String = self.processor.text_to_latin(&String::from(command));
+        let args: Vec<String> = match split_words(args) {
+            Some(args) => args,
+            None => return false,
+        };
+        let processor: &IOProcessor = &self.processor;
+        let to_latin = |name: &str| processor.text_to_latin(&String::from(name));
+        let result: Result<Option<String>, EnvError> = match (command.as_str(), args.is_empty()) {
+            ("export", true) => {
+                self.print_lines(environment::list_exports());
+                Ok(None)
+            }
+            ("export", false) => environment::export(&args, &to_latin).map(Some),
+            ("unset", false) => environment::unset(&args, &to_latin).map(Some),
+            ("env", true) => {
+                self.print_lines(environment::list_env(shell.get_wrkdir().as_path()));
+                Ok(None)
+            }
+            _ => return false,
+        };
+        match result {
+            Ok(Some(command)) => self.process_input_interactive(shell, command),
+            Ok(None) => self.print_prompt(shell),
+            Err(err) => {
+                print_err(format!("{}: {}", command, err), self.config.output_config.translate_stderr, &self.processor);
+                self.print_prompt(shell);
+            }
+        }
+        true
+    }
+
+    /// ### directory_builtin
+    ///
+    /// Run `cd`, `pushd`, `popd` or `dirs` if input is one of them; returns whether input has been handled.
+    /// The directory is resolved by pyc, which keeps the directory stack, then the shell changes to it
+    fn directory_builtin(&mut self, shell: &mut Shell, input: &str) -> bool {
+        let input: &str = input.trim();
+        let (command, args): (&str, &str) = match input.find(char::is_whitespace) {
+            Some(pos) => (&input[..pos], &input[pos..]),
+            None => (input, ""),
+        };
+        let mut argv: Vec<String> = match split_words(args) {
+            Some(args) => std::iter::once(String::from(command)).chain(args).collect(),
+            None => return false,
+        };
+        let command: String = match resolve_command(&mut argv, &self.config, &shell.aliases) {
+            Ok(0) => self.processor.text_to_latin(&argv[0]),
+            Ok(_) => argv[0].clone(),
+            Err(_) => return false,
+        };
+        let args: Vec<String> = argv.split_off(1);
+        //Options are left to the shell
+        if args.len() > 1 || args.iter().any(|arg| arg.starts_with('-') && (arg != "-" || command != "cd")) {
+            return false;
+        }
+        let wrkdir: PathBuf = shell.get_wrkdir();
+        let arg: Option<&str> = args.first().map(|arg| arg.as_str());
+        let processor: &IOProcessor = &self.processor;
+        let to_latin = |dir: &str| processor.text_to_latin(&String::from(dir));
+        let result: Result<Option<PathBuf>, DirError> = match (command.as_str(), arg) {
+            ("cd", _) => shell.dirstack.cd(arg, wrkdir.as_path(), &to_latin).map(Some),
+            ("pushd", _) => shell.dirstack.pushd(arg, wrkdir.as_path(), &to_latin).map(Some),
+            ("popd", None) => shell.dirstack.popd(wrkdir.as_path()).map(Some),
+            ("dirs", None) => {
+                self.print_lines(vec![shell.dirstack.dirs(wrkdir.as_path())]);
+                Ok(None)
+            }
+            _ => return false,
+        };
+        match result {
+            Ok(Some(target)) => {
+                match command.as_str() {
+                    "cd" if arg == Some("-") => self.print_lines(vec![target.display().to_string()]),
+                    "pushd" | "popd" => self.print_lines(vec![shell.dirstack.dirs(target.as_path())]),
+                    _ => {}
                 }
-            } else {
-                //Event is Not a number
-                print_err(
-                    format!("!{}: event not found", history_index),
-                    self.config.output_config.translate_output,
-                    &self.processor,
-                );
-                console::print(format!("{} ", shell.get_promptline(&self.processor)));
-                return;
+                self.process_input_interactive(shell, format!("cd {}", environment::quote(buffer::os_str_to_string(target.as_os_str()).as_str())));
+            }
+            Ok(None) => self.print_prompt(shell),
+            Err(err) => {
+                print_err(format!("{}: {}", command, err), self.config.output_config.translate_stderr, &self.processor);
+                self.print_prompt(shell);
             }
         }
-        //Push input to history
-        shell.history.push(input.clone());
+        true
+    }
+
+    /// ### print_prompt
+    ///
+    /// Print the prompt, unless the rc file is being run.
+    /// If the prompt follows a line handled by pyc, its execution time becomes the one of the last command
+    fn print_prompt(&self, shell: &mut Shell) {
+        if let Some(started) = self.builtin_started.take() {
+            shell.set_exec_time(started.elapsed());
+        }
+        if !self.sourcing {
+            console::print(format!("{} ", shell.get_promptline(&self.processor)));
+        }
+    }
+
+    /// ### print_lines
+    ///
+    /// Print the output of a builtin command
+    fn print_lines(&self, lines: Vec<String>) {
+        for line in lines.into_iter() {
+            if print_out(line, self.config.output_config.translate_stdout, &self.processor).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// ### expand_variables
+    ///
+    /// Expand the environment variables and the special parameters in the translated input, if enabled in configuration
+    fn expand_variables(&self, shell: &Shell, input: String) -> Result<String, ExpansionError> {
+        if !self.config.input_config.expand_variables {
+            return Ok(input);
+        }
+        let ctx: ExpansionContext = ExpansionContext {
+            pid: std::process::id(),
+            exit_status: shell.get_exit_status(),
+            error_on_unset: self.config.input_config.error_on_unset,
+        };
+        expand_variables(input.as_str(), &ctx, &|name: &str| env::var(name).ok())
+    }
+
+    /// ### process_input_interactive
+    ///
+    /// Process input after enter in interactive mode
+    fn process_input_interactive(&mut self, shell: &mut Shell, input: String) {
         // @! Built-in commands
         // Check if clear command
-        if input.starts_with("clear") {
+        if input.split_whitespace().next() == Some("clear") {
             //Clear screen, then write prompt
-            console::clear();
-            console::print(format!("{} ", shell.get_promptline(&self.processor)));
+            if let Some(started) = self.builtin_started.take() {
+                shell.set_exec_time(started.elapsed());
+            }
+            match self.sourcing {
+                true => console::clear(self.config.terminal_config.clear_scrollback),
+                false => self.clear_screen(shell),
+            }
         } else if input.starts_with("history") {
             //Print history
             let history_lines: Vec<String> = shell.history.dump();
             for (idx, line) in history_lines.iter().enumerate() {
-                print_out(
+                if print_out(
                     format!("{} {}", self.indent_history_index(idx), line),
-                    self.config.output_config.translate_output,
+                    self.config.output_config.translate_stdout,
                     &self.processor,
-                );
+                ).is_err() {
+                    break;
+                }
             }
-            console::print(format!("{} ", shell.get_promptline(&self.processor)));
+            self.print_prompt(shell);
+        } else if let Some(command) = background_command(input.as_str()) {
+            //Start job in background
+            if self.config.output_config.echo_command {
+                console::println(echo_line(command));
+            }
+            match shell.spawn_job(command) {
+                Ok((id, pid)) => console::println(format!("[{}] {}", id, pid)),
+                Err(err) => print_err(err.to_string(), self.config.output_config.translate_stderr, &self.processor),
+            }
+            self.print_prompt(shell);
+        } else if input.split_whitespace().next() == Some("time") {
+            //Run command in foreground; its timing is reported once it terminates
+            let command: &str = input.trim().trim_start_matches("time").trim_start();
+            let result: Result<(), String> = match command.is_empty() {
+                true => Err(String::from("missing command")),
+                false => shell.time_job(command).map_err(|err| err.to_string()),
+            };
+            match result {
+                Ok(_) => {
+                    self.builtin_started.set(None);
+                    self.dispatched_command = Some(String::from(command));
+                }
+                Err(err) => {
+                    print_err(format!("time: {}", err), self.config.output_config.translate_stderr, &self.processor);
+                    self.print_prompt(shell);
+                }
+            }
+        } else if input.split_whitespace().next() == Some("jobs") {
+            let jobs: Vec<String> = {
+                let jobs: &[Job] = shell.jobs();
+                let current: Option<usize> = jobs.last().map(|job| job.id);
+                jobs.iter().map(|job| job.describe(Some(job.id) == current)).collect()
+            };
+            for line in jobs.into_iter() {
+                if print_out(line, self.config.output_config.translate_stdout, &self.processor).is_err() {
+                    break;
+                }
+            }
+            self.print_prompt(shell);
+        } else if input.split_whitespace().next() == Some("fg") {
+            //Bring job to foreground
+            let id: Option<usize> = input.split_whitespace().nth(1).map(|arg| arg.trim_start_matches('%').parse::<usize>().unwrap_or(0));
+            match shell.foreground_job(id) {
+                Ok(command) => {
+                    console::println(command.clone());
+                    self.builtin_started.set(None);
+                    self.dispatched_command = Some(command);
+                }
+                Err(err) => {
+                    print_err(format!("fg: {}", err), self.config.output_config.translate_stderr, &self.processor);
+                    self.print_prompt(shell);
+                }
+            }
+        } else if input.split_whitespace().next() == Some("bg") {
+            //Resume suspended job in background
+            let id: Option<usize> = input.split_whitespace().nth(1).map(|arg| arg.trim_start_matches('%').parse::<usize>().unwrap_or(0));
+            match shell.background_job(id) {
+                Ok((id, command)) => console::println(format!("[{}]+ {} &", id, command)),
+                Err(err) => print_err(format!("bg: {}", err), self.config.output_config.translate_stderr, &self.processor),
+            }
+            self.print_prompt(shell);
+        } else if input.split_whitespace().next() == Some("kill") && builtins::handles_kill(input.as_str(), &self.kill_args(input.as_str())) {
+            //Send a signal to jobs and processes; signal names can be typed in cyrillic too
+            let args: Vec<String> = self.kill_args(input.as_str());
+            let errors: Vec<String> = match builtins::parse_kill(&args) {
+                Ok((signal, targets)) => targets
+                    .into_iter()
+                    .filter_map(|target| match target {
+                        KillTarget::Job(id) => shell.signal_job(id, signal).err().map(|err| format!("%{}: {}", id, err)),
+                        KillTarget::Process(pid) => shell.signal_process(pid, signal).err().map(|err| format!("({}): {}", pid, err)),
+                    })
+                    .collect(),
+                Err(err) => vec![err],
+            };
+            for err in errors.into_iter() {
+                print_err(format!("kill: {}", err), self.config.output_config.translate_stderr, &self.processor);
+            }
+            self.print_prompt(shell);
+        } else if input.split_whitespace().next() == Some("wait") {
+            //Wait for all the jobs; prompt is printed once they've terminated
+            self.builtin_started.set(None);
+            shell.wait_jobs();
         } else if input.starts_with("lev") {
             // TODO: start lev
         } else {
             //@! Write input as usual
-            if let Err(err) = shell.write(input) {
-                print_err(
-                    String::from(err.to_string()),
-                    self.config.output_config.translate_output,
-                    &self.processor,
-                );
+            logger::debug("runtime", format!("writing to shell: {}", input.trim_end()).as_str());
+            self.builtin_started.set(None);
+            if self.config.output_config.echo_command {
+                console::println(echo_line(input.as_str()));
+            }
+            match shell.write(input.clone()) {
+                Ok(_) => self.dispatched_command = Some(input),
+                Err(err) => {
+                    //The session goes on: the runtime terminates once the shell has exited
+                    print_err(
+                        err.to_string(),
+                        self.config.output_config.translate_stderr,
+                        &self.processor,
+                    );
+                    self.print_prompt(shell);
+                }
             }
         }
     }
@@ -350,27 +857,28 @@ impl Imiop for ShIop {
     ///
     /// Handle input event received from stdin
     fn handle_input_event(&mut self, ev: InputEvent, shell: &mut Shell) {
+        let tab: bool = ev == InputEvent::Ctrl(9);
+        if self.handle_search_event(&ev, shell) {
+            self.last_tab = false;
+            return;
+        }
         match ev {
-            InputEvent::ArrowDown => {
-                //Get previous element in history
-                self.perform_history_backward(shell);
-            }
-            InputEvent::ArrowUp => {
-                //Get next element in history
-                self.perform_history_forward(shell);
-            }
-            InputEvent::ArrowLeft => {
-                self.move_left();
+            InputEvent::ArrowDown | InputEvent::ArrowUp => {
+                //Get next/previous element in history
+                self.perform_edit(ev, shell);
             }
-            InputEvent::ArrowRight => {
-                self.move_right();
-            }
-            InputEvent::Backspace => {
-                self.backspace();
+            InputEvent::ArrowLeft
+            | InputEvent::ArrowRight
+            | InputEvent::Backspace
+            | InputEvent::Delete
+            | InputEvent::Home
+            | InputEvent::End => {
+                self.perform_edit(ev, shell);
             }
             InputEvent::CarriageReturn => {
                 console::carriage_return();
             }
+            InputEvent::Escape => {} //Only used to cancel the reverse search
             InputEvent::Ctrl(sig) => {
                 //Check running state
                 //if running state is Idle, it will be handled by the console,
@@ -378,11 +886,7 @@ impl Imiop for ShIop {
                     1 => {
                         //CTRL + A
                         //We must return at the beginning of the string
-                        for _ in 0..self.input_buffer_cursor {
-                            //Move left
-                            console::move_cursor_left();
-                        }
-                        self.input_buffer_cursor = 0; //Reset cursor
+                        self.edit(|editor| editor.move_home());
                     }
                     2 => {
                         //CTRL + B
@@ -392,12 +896,11 @@ impl Imiop for ShIop {
                         //CTRL + C
                         //Abort input and go to newline
                         self.clear_buffer();
+                        self.multiline.clear();
                         //Reset history index
                         self.reset_history_index();
-                        // Unset reverse search
-                        self.rev_search = None;
                         console::println(String::new());
-                        console::print(format!("{} ", shell.get_promptline(&self.processor)));
+                        self.print_prompt(shell);
                     }
                     4 => {
                         //CTRL + D
@@ -405,10 +908,7 @@ impl Imiop for ShIop {
                     }
                     5 => {
                         //CTRL + E
-                        for _ in self.input_buffer_cursor..self.input_buffer.len() {
-                            console::move_cursor_right();
-                        }
-                        self.input_buffer_cursor = self.input_buffer.len();
+                        self.edit(|editor| editor.move_end());
                     }
                     6 => {
                         //CTRL + F
@@ -416,88 +916,52 @@ impl Imiop for ShIop {
                     }
                     7 => {
                         //CTRL + G
-                        // exit rev search (and clear buffer)
-                        self.rev_search = None;
-                        self.rev_search_idx = 0;
                         //Abort input and go to newline
                         self.clear_buffer();
+                        self.multiline.clear();
                         console::println(String::new());
-                        console::print(format!("{} ", shell.get_promptline(&self.processor)));
+                        self.print_prompt(shell);
                     }
                     8 => {
                         //CTRL + H
                         self.backspace();
                     }
+                    9 => {
+                        // TAB
+                        self.complete(shell);
+                    }
                     11 => {
                         // CTRL + K
                         //Delete all characters after cursor
-                        while self.input_buffer_cursor < self.input_buffer.len() {
-                            let _ = self.input_buffer.pop();
-                        }
+                        self.edit(|editor| editor.kill_to_end());
                     }
                     12 => {
                         // CTRL + L
                         //Clear, but doesn't reset input
-                        console::clear();
-                        console::print(format!(
-                            "{} {}",
-                            shell.get_promptline(&self.processor),
-                            buffer::chars_to_string(&self.input_buffer)
-                        ));
+                        self.clear_screen(shell);
                     }
                     18 => {
                         // CTRL + R
-                        // If reverse search is empty, set reverse search match
-                        if self.rev_search.is_none() {
-                            // Set reverse search to current input buffer
-                            let curr_stdin: String = buffer::chars_to_string(&self.input_buffer);
-                            self.rev_search = Some(curr_stdin.clone());
-                            // Set index to first element (0)
-                            self.rev_search_idx = 0;
-                            // Write reverse-i-search prompt
-                            console::rewrite(
-                                format!(
-                                    "{}`{}':  ",
-                                    console_fmt(
-                                        String::from("(reverse-i-search)"),
-                                        self.config.output_config.translate_output,
-                                        &self.processor
-                                    ),
-                                    curr_stdin
-                                ),
-                                curr_stdin.len(),
-                            );
-                        }
-                        // Find current input in history starting from bottom
-                        if let Some(matched) = self.search_reverse(shell) {
-                            // Set matched as current input
-                            let prev_length: usize = self.input_buffer.len();
-                            self.input_buffer.clear();
-                            self.input_buffer = matched.chars().collect();
-                            // Set cursor to new length
-                            self.input_buffer_cursor = self.input_buffer.len();
-                            // Print prompt
-                            console::rewrite(matched, prev_length);
-                        }
+                        //Start the reverse search; the line being typed is the query
+                        self.rev_search = Some(ReverseSearch::new(&self.editor, &shell.history));
+                        self.print_search();
                     }
                     _ => {} //Unhandled
                 }
             }
             InputEvent::Key(k) => {
-                //Push key
-                //Push k to input buffer
-                for ch in k.chars() {
-                    self.input_buffer.insert(self.input_buffer_cursor, ch);
-                    self.input_buffer_cursor += 1;
-                }
-                // If rev search, put new input buffer to reverse search
-                if self.rev_search.is_some() {
-                    // Set reverse search to current input buffer
-                    let curr_stdin: String = buffer::chars_to_string(&self.input_buffer);
-                    self.rev_search = Some(curr_stdin.clone());
-                }
-                //Print key
-                console::print(k);
+                //Insert key at cursor and redraw line
+                self.edit(|editor| {
+                    editor.insert(k.as_str());
+                    true
+                });
+            }
+            InputEvent::Paste(text) => {
+                //Pasted text is inserted literally; nothing is executed before enter is pressed
+                self.edit(|editor| {
+                    editor.paste(text.as_str());
+                    true
+                });
             }
             InputEvent::Enter => {
                 //@! Send input
@@ -505,13 +969,103 @@ impl Imiop for ShIop {
                 self.perform_interactive_enter(shell);
             }
         }
+        self.last_tab = tab;
+    }
+
+    /// ### take_dispatched_command
+    ///
+    /// Returns the last command written to the shell and resets it
+    fn take_dispatched_command(&mut self) -> Option<String> {
+        self.dispatched_command.take()
+    }
+
+    /// ### take_reload_request
+    ///
+    /// Returns whether `reload` has been run and resets it
+    fn take_reload_request(&mut self) -> bool {
+        std::mem::replace(&mut self.reload_requested, false)
+    }
+
+    /// ### dispatch_input
+    ///
+    /// Print the input after the prompt, then process it as if it had been typed by the user
+    fn dispatch_input(&mut self, input: String, shell: &mut Shell) {
+        if input.trim().is_empty() {
+            return;
+        }
+        console::println(input.clone());
+        self.process_input_line(shell, input);
+    }
+
+    /// ### source_input
+    ///
+    /// Process a line of the rc file as if it had been typed, without echoing it, pushing it to the history or printing the prompt
+    fn source_input(&mut self, input: String, shell: &mut Shell) {
+        if input.trim().is_empty() {
+            return;
+        }
+        self.sourcing = true;
+        self.process_input_line(shell, input);
+        self.sourcing = false;
     }
+
+    /// ### set_config_file
+    ///
+    /// Set the configuration file reported by `help`
+    fn set_config_file(&mut self, config_file: &Path) {
+        self.config_file = Some(config_file.to_path_buf());
+    }
+
+    /// ### redraw_prompt
+    ///
+    /// Render the prompt again, unless the reverse search prompt or the continuation prompt is shown
+    fn redraw_prompt(&mut self, shell: &mut Shell) {
+        if self.rev_search.is_none() && !self.multiline.is_pending() {
+            self.redraw(shell);
+        }
+    }
+}
+
+/// ### background_command
+///
+/// If the command has to be run in background (i.e. it ends with a single `&`), returns the command without it
+fn background_command(input: &str) -> Option<&str> {
+    let input: &str = input.trim_end();
+    let command: &str = input.strip_suffix('&')?;
+    if command.ends_with('&') || command.ends_with('\\') || command.ends_with('>') || command.ends_with('|') {
+        return None;
+    }
+    match command.trim_end() {
+        "" => None,
+        command => Some(command),
+    }
+}
+
+/// ### apply_exec_options
+///
+/// Run the first line of the input with the options; a command run in background stays in background
+fn apply_exec_options(options: &ExecOptions, input: String) -> String {
+    if options.is_empty() {
+        return input;
+    }
+    let (line, next_lines): (&str, &str) = match input.find('\n') {
+        Some(pos) => input.split_at(pos + 1),
+        None => (input.as_str(), ""),
+    };
+    let line: String = match background_command(line) {
+        Some(command) => format!("{} &\n", options.wrap(command)),
+        None => format!("{}\n", options.wrap(line.trim_end())),
+    };
+    line + next_lines
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::shell::completion::index::INDEXES_BUILT;
+    use crate::shell::jobs::JobState;
+    use crate::shell::ShellState;
     use crate::config::Config;
     use crate::translator::ioprocessor::IOProcessor;
     use crate::translator::lang::Language;
@@ -525,72 +1079,72 @@ mod tests {
         let shiop = new_shiop();
         assert!(shiop.config.get_alias(&String::from("ll")).is_none());
         assert_eq!(shiop.processor.language, Language::Russian);
-        assert_eq!(shiop.input_buffer.capacity(), 2048);
-        assert_eq!(shiop.input_buffer_cursor, 0);
-        assert_eq!(shiop.rev_search, None);
-        assert_eq!(shiop.rev_search_idx, 0);
-        assert_eq!(shiop.history_index, 0);
+        assert_eq!(shiop.editor.buffer.capacity(), 2048);
+        assert_eq!(shiop.editor.cursor, 0);
+        assert!(shiop.rev_search.is_none());
+        assert_eq!(shiop.editor.history_index, 0);
+        assert!(shiop.dispatched_command.is_none());
     }
 
     #[test]
     fn test_runtimeprops_clear_buffer() {
         let mut shiop = new_shiop();
-        shiop.input_buffer = vec!['a', 'b', 'c'];
-        shiop.input_buffer_cursor = 3;
+        shiop.editor.buffer = vec!['a', 'b', 'c'];
+        shiop.editor.cursor = 3;
         shiop.clear_buffer();
-        assert_eq!(shiop.input_buffer.len(), 0);
-        assert_eq!(shiop.input_buffer_cursor, 0);
+        assert_eq!(shiop.editor.buffer.len(), 0);
+        assert_eq!(shiop.editor.cursor, 0);
         //History index
-        shiop.history_index = 128;
+        shiop.editor.history_index = 128;
         shiop.reset_history_index();
-        assert_eq!(shiop.history_index, 0);
+        assert_eq!(shiop.editor.history_index, 0);
     }
 
     #[test]
     fn test_runtimeprops_backspace() {
         let mut shiop = new_shiop();
-        shiop.input_buffer = vec!['a', 'b', 'c'];
+        shiop.editor.buffer = vec!['a', 'b', 'c'];
         //If cursor is 0, cursor and input buffer won't change
         shiop.backspace();
-        assert_eq!(shiop.input_buffer_cursor, 0);
-        assert_eq!(shiop.input_buffer.len(), 3);
-        shiop.input_buffer_cursor = 3;
+        assert_eq!(shiop.editor.cursor, 0);
+        assert_eq!(shiop.editor.buffer.len(), 3);
+        shiop.editor.cursor = 3;
         //Backspace from end of buffer
         shiop.backspace();
-        assert_eq!(shiop.input_buffer_cursor, 2);
-        assert_eq!(shiop.input_buffer, vec!['a', 'b']);
+        assert_eq!(shiop.editor.cursor, 2);
+        assert_eq!(shiop.editor.buffer, vec!['a', 'b']);
         //Set cursor to 1 and backspace from the middle
-        shiop.input_buffer_cursor = 1;
+        shiop.editor.cursor = 1;
         shiop.backspace();
-        assert_eq!(shiop.input_buffer_cursor, 0);
-        assert_eq!(shiop.input_buffer, vec!['b']);
+        assert_eq!(shiop.editor.cursor, 0);
+        assert_eq!(shiop.editor.buffer, vec!['b']);
         //Try to delete with cursor out of range
-        shiop.input_buffer = vec!['a', 'b', 'c'];
-        shiop.input_buffer_cursor = 4;
+        shiop.editor.buffer = vec!['a', 'b', 'c'];
+        shiop.editor.cursor = 4;
         shiop.backspace();
-        assert_eq!(shiop.input_buffer_cursor, 3);
-        assert_eq!(shiop.input_buffer.len(), 3);
+        assert_eq!(shiop.editor.cursor, 3);
+        assert_eq!(shiop.editor.buffer.len(), 3);
     }
 
     #[test]
     fn test_runtimeprops_move_cursor() {
         let mut shiop = new_shiop();
-        shiop.input_buffer = vec!['a', 'b', 'c', 'd', 'e'];
+        shiop.editor.buffer = vec!['a', 'b', 'c', 'd', 'e'];
         //Move left
-        shiop.input_buffer_cursor = 5;
+        shiop.editor.cursor = 5;
         shiop.move_left();
-        assert_eq!(shiop.input_buffer_cursor, 4);
+        assert_eq!(shiop.editor.cursor, 4);
         //Try to move left when is at 0
-        shiop.input_buffer_cursor = 0;
+        shiop.editor.cursor = 0;
         shiop.move_left();
-        assert_eq!(shiop.input_buffer_cursor, 0);
+        assert_eq!(shiop.editor.cursor, 0);
         //Move right
         shiop.move_right();
-        assert_eq!(shiop.input_buffer_cursor, 1);
+        assert_eq!(shiop.editor.cursor, 1);
         //Move out of bounds
-        shiop.input_buffer = vec!['a'];
+        shiop.editor.buffer = vec!['a'];
         shiop.move_right();
-        assert_eq!(shiop.input_buffer_cursor, 1);
+        assert_eq!(shiop.editor.cursor, 1);
     }
 
     #[test]
@@ -602,182 +1156,611 @@ mod tests {
                                            //Prepare history
         shell.history.push(String::from("pwd"));
         shell.history.push(String::from("ls -l"));
-        assert_eq!(shiop.history_index, 0);
+        assert_eq!(shiop.editor.history_index, 0);
         //Arrow up
         shiop.handle_input_event(InputEvent::ArrowUp, &mut shell);
-        assert_eq!(shiop.history_index, 1); //History index increased
-        assert_eq!(shiop.input_buffer, vec!['l', 's', ' ', '-', 'l']); //ls -l
-        assert_eq!(shiop.input_buffer_cursor, 5);
+        assert_eq!(shiop.editor.history_index, 1); //History index increased
+        assert_eq!(shiop.editor.buffer, vec!['l', 's', ' ', '-', 'l']); //ls -l
+        assert_eq!(shiop.editor.cursor, 5);
         //index 2
         shiop.handle_input_event(InputEvent::ArrowUp, &mut shell);
-        assert_eq!(shiop.history_index, 2); //History index increased
-        assert_eq!(shiop.input_buffer, vec!['p', 'w', 'd']); //pwd
-        assert_eq!(shiop.input_buffer_cursor, 3);
+        assert_eq!(shiop.editor.history_index, 2); //History index increased
+        assert_eq!(shiop.editor.buffer, vec!['p', 'w', 'd']); //pwd
+        assert_eq!(shiop.editor.cursor, 3);
         //Nothing bad should happen, input buffer won't change, history index won't be increased
         shiop.handle_input_event(InputEvent::ArrowUp, &mut shell);
-        assert_eq!(shiop.history_index, 2); //History index didn't change
-        assert_eq!(shiop.input_buffer, vec!['p', 'w', 'd']); //pwd
-        assert_eq!(shiop.input_buffer_cursor, 3);
+        assert_eq!(shiop.editor.history_index, 2); //History index didn't change
+        assert_eq!(shiop.editor.buffer, vec!['p', 'w', 'd']); //pwd
+        assert_eq!(shiop.editor.cursor, 3);
         //Arrow down
         shiop.handle_input_event(InputEvent::ArrowDown, &mut shell);
-        assert_eq!(shiop.history_index, 1); //History index decreased
-        assert_eq!(shiop.input_buffer, vec!['l', 's', ' ', '-', 'l']); //ls -l
-        assert_eq!(shiop.input_buffer_cursor, 5);
+        assert_eq!(shiop.editor.history_index, 1); //History index decreased
+        assert_eq!(shiop.editor.buffer, vec!['l', 's', ' ', '-', 'l']); //ls -l
+        assert_eq!(shiop.editor.cursor, 5);
         shiop.handle_input_event(InputEvent::ArrowDown, &mut shell);
-        assert_eq!(shiop.history_index, 0); //History index decreased
-        assert_eq!(shiop.input_buffer.len(), 0); //Empty
+        assert_eq!(shiop.editor.history_index, 0); //History index decreased
+        assert_eq!(shiop.editor.buffer.len(), 0); //Empty
                                                  //Buffer should now be empty
-        assert_eq!(shiop.input_buffer.len(), 0);
-        assert_eq!(shiop.input_buffer_cursor, 0);
+        assert_eq!(shiop.editor.buffer.len(), 0);
+        assert_eq!(shiop.editor.cursor, 0);
         //Another arrow down should change nothing
-        shiop.input_buffer = vec!['l', 's'];
-        shiop.input_buffer_cursor = 2;
+        shiop.editor.buffer = vec!['l', 's'];
+        shiop.editor.cursor = 2;
         shiop.handle_input_event(InputEvent::ArrowDown, &mut shell);
-        assert_eq!(shiop.history_index, 0); //History index decreased
-        assert_eq!(shiop.input_buffer.len(), 2); //Empty
-        assert_eq!(shiop.input_buffer_cursor, 2);
+        assert_eq!(shiop.editor.history_index, 0); //History index decreased
+        assert_eq!(shiop.editor.buffer.len(), 2); //Empty
+        assert_eq!(shiop.editor.cursor, 2);
         //Arrow left
         //Move cursor to left by 1 position
-        shiop.input_buffer = vec!['l', 's', ' ', '-', 'l'];
-        shiop.input_buffer_cursor = 5;
+        shiop.editor.buffer = vec!['l', 's', ' ', '-', 'l'];
+        shiop.editor.cursor = 5;
         shiop.handle_input_event(InputEvent::ArrowLeft, &mut shell);
-        assert_eq!(shiop.input_buffer_cursor, 4);
+        assert_eq!(shiop.editor.cursor, 4);
         //Move cursor to right by 1 position
         shiop.handle_input_event(InputEvent::ArrowRight, &mut shell);
-        assert_eq!(shiop.input_buffer_cursor, 5);
+        assert_eq!(shiop.editor.cursor, 5);
         //Backspace
         shiop.handle_input_event(InputEvent::Backspace, &mut shell);
-        assert_eq!(shiop.input_buffer, vec!['l', 's', ' ', '-']);
-        assert_eq!(shiop.input_buffer_cursor, 4);
+        assert_eq!(shiop.editor.buffer, vec!['l', 's', ' ', '-']);
+        assert_eq!(shiop.editor.cursor, 4);
         //Carriage return
         shiop.handle_input_event(InputEvent::CarriageReturn, &mut shell);
         //CTRL A
         shiop.handle_input_event(InputEvent::Ctrl(1), &mut shell);
-        assert_eq!(shiop.input_buffer_cursor, 0);
+        assert_eq!(shiop.editor.cursor, 0);
         //CTRL B
-        shiop.input_buffer_cursor = 2;
+        shiop.editor.cursor = 2;
         shiop.handle_input_event(InputEvent::Ctrl(2), &mut shell);
-        assert_eq!(shiop.input_buffer_cursor, 1);
+        assert_eq!(shiop.editor.cursor, 1);
         //CTRL C
-        shiop.history_index = 255;
+        shiop.editor.history_index = 255;
         shiop.handle_input_event(InputEvent::Ctrl(3), &mut shell);
-        assert_eq!(shiop.input_buffer.len(), 0);
-        assert_eq!(shiop.input_buffer_cursor, 0);
-        assert_eq!(shiop.history_index, 0); //Reset history index
-                                            //CTRL R ( reverse search; set input buffer to ifc)
-        shiop.input_buffer = vec!['i', 'f', 'c'];
-        shiop.input_buffer_cursor = 3;
+        assert_eq!(shiop.editor.buffer.len(), 0);
+        assert_eq!(shiop.editor.cursor, 0);
+        assert_eq!(shiop.editor.history_index, 0); //Reset history index
+        //CTRL R ( reverse search; set input buffer to ifc)
+        shiop.editor.buffer = vec!['i', 'f', 'c'];
+        shiop.editor.cursor = 3;
         shell.history.push(String::from("ifconfig eth0"));
         shiop.handle_input_event(InputEvent::Ctrl(18), &mut shell);
-        // Input buffer should now be 'ifconfig eth'
-        assert_eq!(
-            shiop.input_buffer,
-            vec!['i', 'f', 'c', 'o', 'n', 'f', 'i', 'g', ' ', 'e', 't', 'h', '0']
-        );
-        assert_eq!(shiop.rev_search, Some(String::from("ifc")));
-        assert_eq!(shiop.rev_search_idx, 1); // 0 + 1
-                                             //CTRL G ( exit rev-search )
+        // Candidate should now be 'ifconfig eth0'; the line is untouched until the candidate is accepted
+        assert_eq!(shiop.rev_search.as_ref().unwrap().query(), "ifc");
+        assert_eq!(shiop.rev_search.as_ref().unwrap().candidate(), Some("ifconfig eth0"));
+        assert_eq!(shiop.editor.buffer, vec!['i', 'f', 'c']);
+        //CTRL G ( exit rev-search and restore the line )
+        shiop.handle_input_event(InputEvent::Ctrl(7), &mut shell);
+        assert!(shiop.rev_search.is_none());
+        assert_eq!(shiop.editor.buffer, vec!['i', 'f', 'c']);
+        assert_eq!(shiop.editor.cursor, 3);
+        //CTRL G ( abort input )
         shiop.handle_input_event(InputEvent::Ctrl(7), &mut shell);
-        assert_eq!(shiop.input_buffer.len(), 0);
-        assert_eq!(shiop.input_buffer_cursor, 0);
-        assert_eq!(shiop.rev_search, None);
-        assert_eq!(shiop.rev_search_idx, 0); // 0
-                                             //CTRL D
-        shiop.input_buffer = vec!['l', 's', ' ', '-', 'l'];
-        shiop.input_buffer_cursor = 5;
+        assert_eq!(shiop.editor.buffer.len(), 0);
+        assert_eq!(shiop.editor.cursor, 0);
+        //CTRL D
+        shiop.editor.buffer = vec!['l', 's', ' ', '-', 'l'];
+        shiop.editor.cursor = 5;
         shiop.handle_input_event(InputEvent::Ctrl(4), &mut shell);
-        assert_eq!(shiop.input_buffer, vec!['l', 's', ' ', '-']);
-        assert_eq!(shiop.input_buffer_cursor, 4);
+        assert_eq!(shiop.editor.buffer, vec!['l', 's', ' ', '-']);
+        assert_eq!(shiop.editor.cursor, 4);
         //CTRL E
-        shiop.input_buffer_cursor = 1;
+        shiop.editor.cursor = 1;
         shiop.handle_input_event(InputEvent::Ctrl(5), &mut shell);
-        assert_eq!(shiop.input_buffer_cursor, 4);
+        assert_eq!(shiop.editor.cursor, 4);
         //CTRL F
-        shiop.input_buffer_cursor = 1;
+        shiop.editor.cursor = 1;
         shiop.handle_input_event(InputEvent::Ctrl(6), &mut shell);
-        assert_eq!(shiop.input_buffer_cursor, 2);
+        assert_eq!(shiop.editor.cursor, 2);
         //CTRL H
         shiop.handle_input_event(InputEvent::Ctrl(8), &mut shell);
-        assert_eq!(shiop.input_buffer, vec!['l', ' ', '-']);
-        assert_eq!(shiop.input_buffer_cursor, 1);
+        assert_eq!(shiop.editor.buffer, vec!['l', ' ', '-']);
+        assert_eq!(shiop.editor.cursor, 1);
         //CTRL K
         shiop.handle_input_event(InputEvent::Ctrl(11), &mut shell);
-        assert_eq!(shiop.input_buffer, vec!['l']);
-        assert_eq!(shiop.input_buffer_cursor, 1);
-        //CTRL L
+        assert_eq!(shiop.editor.buffer, vec!['l']);
+        assert_eq!(shiop.editor.cursor, 1);
+        //CTRL L (the line buffer survives the redraw)
+        shiop.handle_input_event(InputEvent::Ctrl(12), &mut shell);
+        assert_eq!(shiop.editor.buffer, vec!['l']);
+        assert_eq!(shiop.editor.cursor, 1);
+        shiop.config.terminal_config.clear_scrollback = true;
         shiop.handle_input_event(InputEvent::Ctrl(12), &mut shell);
-        assert_eq!(shiop.input_buffer, vec!['l']);
-        assert_eq!(shiop.input_buffer_cursor, 1);
+        assert_eq!(shiop.editor.buffer, vec!['l']);
+        assert_eq!(shiop.editor.cursor, 1);
         //Unhandled ctrl key
         shiop.handle_input_event(InputEvent::Ctrl(255), &mut shell);
-        assert_eq!(shiop.input_buffer, vec!['l']);
-        assert_eq!(shiop.input_buffer_cursor, 1);
+        assert_eq!(shiop.editor.buffer, vec!['l']);
+        assert_eq!(shiop.editor.cursor, 1);
         //Key
         shiop.clear_buffer();
         shiop.handle_input_event(InputEvent::Key(String::from("l")), &mut shell);
-        assert_eq!(shiop.input_buffer, vec!['l']);
-        assert_eq!(shiop.input_buffer_cursor, 1);
+        assert_eq!(shiop.editor.buffer, vec!['l']);
+        assert_eq!(shiop.editor.cursor, 1);
         //Try UTF8 character
         shiop.handle_input_event(InputEvent::Key(String::from("л")), &mut shell);
-        assert_eq!(shiop.input_buffer, vec!['l', 'л']);
-        assert_eq!(shiop.input_buffer_cursor, 2);
+        assert_eq!(shiop.editor.buffer, vec!['l', 'л']);
+        assert_eq!(shiop.editor.cursor, 2);
         //Add character one position behind
         shiop.move_left();
         shiop.handle_input_event(InputEvent::Key(String::from("s")), &mut shell);
-        assert_eq!(shiop.input_buffer, vec!['l', 's', 'л']);
-        assert_eq!(shiop.input_buffer_cursor, 2);
-        shiop.input_buffer = Vec::new();
-        shiop.input_buffer_cursor = 0;
+        assert_eq!(shiop.editor.buffer, vec!['l', 's', 'л']);
+        assert_eq!(shiop.editor.cursor, 2);
+        shiop.editor.buffer = Vec::new();
+        shiop.editor.cursor = 0;
         shiop.handle_input_event(InputEvent::Enter, &mut shell);
-        assert_eq!(shiop.input_buffer.len(), 0);
-        assert_eq!(shiop.input_buffer_cursor, 0);
-        assert_eq!(shiop.history_index, 0);
+        assert_eq!(shiop.editor.buffer.len(), 0);
+        assert_eq!(shiop.editor.cursor, 0);
+        assert_eq!(shiop.editor.history_index, 0);
         //Enter (command)
-        shiop.history_index = 255;
-        shiop.input_buffer = vec!['l', 's'];
-        shiop.input_buffer_cursor = 2;
+        shiop.editor.history_index = 255;
+        shiop.editor.buffer = vec!['l', 's'];
+        shiop.editor.cursor = 2;
         shiop.handle_input_event(InputEvent::Enter, &mut shell);
-        assert_eq!(shiop.input_buffer.len(), 0);
-        assert_eq!(shiop.input_buffer_cursor, 0);
-        assert_eq!(shiop.history_index, 0); //Reset history index
+        assert_eq!(shiop.editor.buffer.len(), 0);
+        assert_eq!(shiop.editor.cursor, 0);
+        assert_eq!(shiop.editor.history_index, 0); //Reset history index
                                             //@! Check if ls is now in history
         assert_eq!(shell.history.at(0).unwrap(), String::from("ls"));
         //Enter (clear)
-        shiop.input_buffer = vec!['c', 'l', 'e', 'a', 'r'];
-        shiop.input_buffer_cursor = 5;
+        shiop.editor.buffer = vec!['c', 'l', 'e', 'a', 'r'];
+        shiop.editor.cursor = 5;
         shiop.handle_input_event(InputEvent::Enter, &mut shell);
-        assert_eq!(shiop.input_buffer.len(), 0);
-        assert_eq!(shiop.input_buffer_cursor, 0);
+        assert_eq!(shiop.editor.buffer.len(), 0);
+        assert_eq!(shiop.editor.cursor, 0);
         //Enter (history)
-        shiop.input_buffer = vec!['h', 'i', 's', 't', 'o', 'r', 'y'];
-        shiop.input_buffer_cursor = 7;
+        shiop.editor.buffer = vec!['h', 'i', 's', 't', 'o', 'r', 'y'];
+        shiop.editor.cursor = 7;
         shiop.handle_input_event(InputEvent::Enter, &mut shell);
-        assert_eq!(shiop.input_buffer.len(), 0);
-        assert_eq!(shiop.input_buffer_cursor, 0);
+        assert_eq!(shiop.editor.buffer.len(), 0);
+        assert_eq!(shiop.editor.cursor, 0);
         //Enter (! => Out of range)
-        shiop.input_buffer = vec!['!', '4', '0'];
-        shiop.input_buffer_cursor = 3;
+        shiop.editor.buffer = vec!['!', '4', '0'];
+        shiop.editor.cursor = 3;
         shiop.handle_input_event(InputEvent::Enter, &mut shell);
-        assert_eq!(shiop.input_buffer.len(), 0);
-        assert_eq!(shiop.input_buffer_cursor, 0);
+        assert_eq!(shiop.editor.buffer.len(), 0);
+        assert_eq!(shiop.editor.cursor, 0);
         //Enter (! => Valid)
-        shiop.input_buffer = vec!['!', '1'];
-        shiop.input_buffer_cursor = 2;
+        shiop.editor.buffer = vec!['!', '1'];
+        shiop.editor.cursor = 2;
         shiop.handle_input_event(InputEvent::Enter, &mut shell);
-        assert_eq!(shiop.input_buffer.len(), 0);
-        assert_eq!(shiop.input_buffer_cursor, 0);
+        assert_eq!(shiop.editor.buffer.len(), 0);
+        assert_eq!(shiop.editor.cursor, 0);
         //Enter (! => String)
-        shiop.input_buffer = vec!['!', 'f', 'o', 'o'];
-        shiop.input_buffer_cursor = 4;
+        shiop.editor.buffer = vec!['!', 'f', 'o', 'o'];
+        shiop.editor.cursor = 4;
         shiop.handle_input_event(InputEvent::Enter, &mut shell);
-        assert_eq!(shiop.input_buffer.len(), 0);
-        assert_eq!(shiop.input_buffer_cursor, 0);
+        assert_eq!(shiop.editor.buffer.len(), 0);
+        assert_eq!(shiop.editor.cursor, 0);
         //Terminate shell
         sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
         let _ = shell.stop();
         sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
     }
 
+    #[test]
+    fn test_runtimeprops_trace_translation() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let trace_file: std::path::PathBuf = tmpdir.path().join("trace.json");
+        let mut config: Config = Config::default();
+        config.alias.insert(String::from("ллл"), String::from("лс -л"));
+        let mut shiop: ShIop = ShIop::new(
+            config,
+            IOProcessor::new(Language::Russian, new_translator(Language::Russian)),
+            Some(TraceOutput::File(trace_file.clone())),
+        );
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Alias + translated + escaped + unchanged
+        shiop.editor.buffer = "ллл \"привет\" /tmp".chars().collect();
+        shiop.editor.cursor = shiop.editor.buffer.len();
+        shiop.perform_interactive_enter(&mut shell);
+        let lines: Vec<String> = crate::utils::file::read_lines(trace_file).unwrap();
+        assert_eq!(lines.len(), 1);
+        let mut trace: TranslationTrace = TranslationTrace::new(String::from("ллл \"привет\" /tmp"), DispatchMode::Interactive);
+        trace.tokens = vec![
+            TokenTrace::new(String::from("ллл"), String::from("лс -л"), TokenDecision::AliasExpanded),
+            TokenTrace::new(String::from("лс -л "), String::from("ls -l "), TokenDecision::Translated),
+            TokenTrace::new(String::from("\"привет\""), String::from("\"привет\""), TokenDecision::Escaped),
+            TokenTrace::new(String::from(" /tmp\n"), String::from(" /tmp\n"), TokenDecision::Unchanged),
+        ];
+        trace.set_output("ls -l \"привет\" /tmp");
+        assert_eq!(*lines.first().unwrap(), trace.to_json());
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_expand_variables() {
+        let mut shiop: ShIop = new_shiop();
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        env::set_var("PYC_TEST_EXPANSION", "привет мир");
+        //Variables are expanded by pyc, also inside double quotes
+        assert_eq!(shiop.expand_variables(&shell, String::from("echo \"$PYC_TEST_EXPANSION\" '$PYC_TEST_EXPANSION'")).unwrap(), String::from("echo \"привет мир\" '$PYC_TEST_EXPANSION'"));
+        //`$?` is the exit status of the last command
+        shiop.process_input_line(&mut shell, String::from("false"));
+        sleep(Duration::from_millis(500));
+        let _ = shell.read();
+        assert_eq!(shell.get_exit_status(), 1);
+        assert_eq!(shiop.expand_variables(&shell, String::from("echo $?")).unwrap(), String::from("echo 1"));
+        shiop.process_input_line(&mut shell, String::from("экхо $?"));
+        sleep(Duration::from_millis(500));
+        assert_eq!(shell.read().ok().unwrap().0, Some(String::from("1\n")));
+        //Unset variables
+        assert_eq!(shiop.expand_variables(&shell, String::from("echo $PYC_TEST_UNSET")).unwrap(), String::from("echo "));
+        shiop.config.input_config.error_on_unset = true;
+        assert!(shiop.expand_variables(&shell, String::from("echo $PYC_TEST_UNSET")).is_err());
+        //Disabled
+        shiop.config.input_config.expand_variables = false;
+        assert_eq!(shiop.expand_variables(&shell, String::from("echo $PYC_TEST_UNSET")).unwrap(), String::from("echo $PYC_TEST_UNSET"));
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_alias_exec_options() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let mut shiop: ShIop = new_shiop();
+        shiop.config.alias.insert(String::from("деплой"), String::from("pwd; env"));
+        shiop.config.alias_options.insert(
+            String::from("деплой"),
+            AliasOptions {
+                cwd: Some(tmpdir.path().to_string_lossy().to_string()),
+                env: vec![(String::from("PYC_DEPLOY_COLOR"), String::from("1"))],
+            },
+        );
+        shiop.config.alias.insert(String::from("лл"), String::from("ls -l"));
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let wrkdir: PathBuf = shell.get_wrkdir();
+        //Working directory and environment are applied to the command only
+        shiop.process_input_line(&mut shell, String::from("деплой"));
+        assert_eq!(
+            shiop.take_dispatched_command(),
+            Some(format!("(cd '{}' && export PYC_DEPLOY_COLOR='1' && pwd; env)\n", tmpdir.path().display()))
+        );
+        sleep(Duration::from_millis(500));
+        let stdout: String = shell.read().ok().unwrap().0.unwrap();
+        let mut lines = stdout.lines();
+        assert_eq!(lines.next(), Some(tmpdir.path().to_string_lossy().as_ref()));
+        assert!(lines.any(|line| line == "PYC_DEPLOY_COLOR=1"));
+        //Session state is restored
+        assert_eq!(shell.get_wrkdir(), wrkdir);
+        shiop.process_input_line(&mut shell, String::from("printenv PYC_DEPLOY_COLOR"));
+        sleep(Duration::from_millis(500));
+        assert_eq!(shell.read().ok().unwrap().0, None);
+        assert_eq!(shell.get_exit_status(), 1);
+        //Plain aliases are not wrapped
+        shiop.process_input_line(&mut shell, String::from("лл /tmp"));
+        assert_eq!(shiop.take_dispatched_command(), Some(String::from("ls -l /tmp\n")));
+        //Background
+        assert_eq!(
+            apply_exec_options(&ExecOptions { wrkdir: None, env: vec![(String::from("A"), String::from("1"))], clear_env: false, pty: false }, String::from("sleep 1 &\n")),
+            String::from("(export A='1' && sleep 1) &\n")
+        );
+        //Missing working directory: command is not run
+        shiop.config.alias_options.get_mut("деплой").unwrap().cwd = Some(String::from("/nonexistent/infra"));
+        shiop.process_input_line(&mut shell, String::from("деплой"));
+        assert_eq!(shiop.take_dispatched_command(), None);
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_environment_builtins() {
+        let mut shiop: ShIop = new_shiop();
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Name is translated, value is not
+        shiop.process_input_line(&mut shell, String::from("экспорт ПЫЗ_ФОО=\"валуе с пробелом\""));
+        assert_eq!(shiop.take_dispatched_command(), Some(String::from("export PYZ_FOO='валуе с пробелом'")));
+        sleep(Duration::from_millis(500));
+        let _ = shell.read();
+        assert_eq!(env::var("PYZ_FOO").unwrap(), String::from("валуе с пробелом"));
+        //The child sees the value
+        shiop.process_input_line(&mut shell, String::from("принтенв ПЫЗ_ФОО"));
+        sleep(Duration::from_millis(500));
+        assert_eq!(shell.read().ok().unwrap().0, Some(String::from("валуе с пробелом\n")));
+        //Unset
+        shiop.process_input_line(&mut shell, String::from("унсет ПЫЗ_ФОО"));
+        assert_eq!(shiop.take_dispatched_command(), Some(String::from("unset PYZ_FOO")));
+        sleep(Duration::from_millis(500));
+        let _ = shell.read();
+        assert!(env::var("PYZ_FOO").is_err());
+        shiop.process_input_line(&mut shell, String::from("printenv PYZ_FOO"));
+        sleep(Duration::from_millis(500));
+        assert_eq!(shell.read().ok().unwrap().0, None);
+        assert_eq!(shell.get_exit_status(), 1);
+        assert_eq!(shiop.take_dispatched_command(), Some(String::from("printenv PYZ_FOO\n")));
+        //Left to the shell
+        assert!(!shiop.environment_builtin(&mut shell, "export PYZ_FOO=1 && ls"));
+        assert!(!shiop.environment_builtin(&mut shell, "env -i ls"));
+        //Errors are handled by pyc
+        assert!(shiop.environment_builtin(&mut shell, "export 1A=b"));
+        assert_eq!(shiop.take_dispatched_command(), None);
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_directory_builtins() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let root: PathBuf = tmpdir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("документы")).unwrap();
+        std::fs::create_dir(root.join("beta")).unwrap();
+        let mut shiop: ShIop = new_shiop();
+        shiop.config.alias.insert(String::from("чд"), String::from("cd"));
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let run = |shiop: &mut ShIop, shell: &mut Shell, input: &str| {
+            shiop.process_input_line(shell, String::from(input));
+            sleep(Duration::from_millis(300));
+            let _ = shell.read();
+            shell.get_wrkdir()
+        };
+        let start: PathBuf = shell.get_wrkdir();
+        //cd (through alias)
+        assert_eq!(run(&mut shiop, &mut shell, format!("чд {}", root.display()).as_str()), root);
+        //Cyrillic directory which exists is not transliterated
+        assert_eq!(run(&mut shiop, &mut shell, "чд документы"), root.join("документы"));
+        //cd -
+        assert_eq!(run(&mut shiop, &mut shell, "чд -"), root);
+        assert_eq!(run(&mut shiop, &mut shell, "чд -"), root.join("документы"));
+        //pushd (directory is transliterated) and popd
+        assert_eq!(run(&mut shiop, &mut shell, "пушд ../бета"), root.join("beta"));
+        assert_eq!(shell.dirstack.dirs(shell.get_wrkdir().as_path()), format!("{} {}", root.join("beta").display(), root.join("документы").display()));
+        assert_eq!(run(&mut shiop, &mut shell, "пушд"), root.join("документы"));
+        assert_eq!(run(&mut shiop, &mut shell, "попд"), root.join("beta"));
+        //Errors don't change the working directory
+        assert_eq!(run(&mut shiop, &mut shell, "попд"), root.join("beta"));
+        assert_eq!(shiop.take_dispatched_command(), Some(format!("cd '{}'", root.join("beta").display())));
+        assert_eq!(run(&mut shiop, &mut shell, "чд нет"), root.join("beta"));
+        assert_eq!(shiop.take_dispatched_command(), None);
+        //Left to the shell
+        assert!(!shiop.directory_builtin(&mut shell, "cd .. && ls"));
+        assert!(!shiop.directory_builtin(&mut shell, "cd -P .."));
+        let _ = run(&mut shiop, &mut shell, format!("cd {}", start.display()).as_str());
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_bracketed_paste() {
+        let mut shiop: ShIop = new_shiop();
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Paste containing newlines and control characters: nothing is dispatched
+        for ev in console::decode_input("\x1b[200~echo a\r\nэкхо\x03 b\n\x1b[201~".as_bytes()) {
+            shiop.handle_input_event(ev, &mut shell);
+        }
+        assert_eq!(shiop.take_dispatched_command(), None);
+        assert_eq!(shiop.editor.get_line(), String::from("echo a⏎экхо b⏎"));
+        assert_eq!(shell.get_state(), ShellState::Shell);
+        //Lines are executed on enter
+        shiop.handle_input_event(InputEvent::Enter, &mut shell);
+        assert_eq!(shiop.take_dispatched_command(), Some(String::from("echo a\necho b\n")));
+        assert_eq!(shell.history.at(0), Some(String::from("echo a⏎экхо b⏎")));
+        sleep(Duration::from_millis(300));
+        let _ = shell.read();
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_multiline_input() {
+        let mut shiop: ShIop = new_shiop();
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Unclosed quote: lines are accumulated until it's closed
+        for line in ["экхо 'привет", "", "мир'"].iter() {
+            assert_eq!(shiop.take_dispatched_command(), None);
+            shiop.handle_input_event(InputEvent::Key(String::from(*line)), &mut shell);
+            shiop.handle_input_event(InputEvent::Enter, &mut shell);
+        }
+        assert!(!shiop.multiline.is_pending());
+        assert_eq!(shiop.take_dispatched_command(), Some(String::from("echo 'привет\n\nмир'\n")));
+        assert_eq!(shell.history.at(0), Some(String::from("экхо 'привет⏎⏎мир'")));
+        sleep(Duration::from_millis(300));
+        let _ = shell.read();
+        //Trailing backslash
+        shiop.handle_input_event(InputEvent::Key(String::from("экхо а \\")), &mut shell);
+        shiop.handle_input_event(InputEvent::Enter, &mut shell);
+        assert!(shiop.multiline.is_pending());
+        assert_eq!(shiop.take_dispatched_command(), None);
+        shiop.handle_input_event(InputEvent::Key(String::from("б")), &mut shell);
+        shiop.handle_input_event(InputEvent::Enter, &mut shell);
+        assert_eq!(shiop.take_dispatched_command(), Some(String::from("echo a b\n")));
+        sleep(Duration::from_millis(300));
+        let _ = shell.read();
+        //CTRL+C discards the lines
+        shiop.handle_input_event(InputEvent::Key(String::from("экхо \"а")), &mut shell);
+        shiop.handle_input_event(InputEvent::Enter, &mut shell);
+        assert!(shiop.multiline.is_pending());
+        shiop.handle_input_event(InputEvent::Ctrl(3), &mut shell);
+        assert!(!shiop.multiline.is_pending());
+        assert_eq!(shiop.take_dispatched_command(), None);
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_alias_builtin() {
+        let mut shiop: ShIop = new_shiop();
+        shiop.config.alias.insert(String::from("ll"), String::from("ls -l"));
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Define aliases at runtime
+        shiop.process_input_line(&mut shell, String::from("алиас ехо='echo -n' лл='ll -a'"));
+        assert_eq!(shell.aliases.get("ехо"), Some(&String::from("echo -n")));
+        assert_eq!(shell.aliases.get("лл"), Some(&String::from("ll -a")));
+        assert_eq!(shiop.take_dispatched_command(), None);
+        //Multi-word and recursive expansion with arguments
+        shiop.process_input_line(&mut shell, String::from("лл /tmp"));
+        assert_eq!(shiop.take_dispatched_command(), Some(String::from("ls -l -a /tmp\n")));
+        //Cycle: command is not dispatched
+        shiop.process_input_line(&mut shell, String::from("alias ls=лл"));
+        shiop.process_input_line(&mut shell, String::from("лл /tmp"));
+        assert_eq!(shiop.take_dispatched_command(), None);
+        //Listing
+        assert!(shiop.alias_builtin(&mut shell, "alias"));
+        assert!(shiop.alias_builtin(&mut shell, "alias nope"));
+        assert!(!shiop.alias_builtin(&mut shell, "aliases"));
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_help_builtin() {
+        let mut shiop: ShIop = new_shiop();
+        shiop.set_config_file(Path::new("/tmp/pyc.yml"));
+        assert_eq!(shiop.config_file, Some(PathBuf::from("/tmp/pyc.yml")));
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(shiop.help_builtin(&mut shell, "help"));
+        assert!(shiop.help_builtin(&mut shell, "хелп цд"));
+        assert!(shiop.help_builtin(&mut shell, "помощь"));
+        assert!(shiop.help_builtin(&mut shell, "help nope"));
+        assert!(!shiop.help_builtin(&mut shell, "helper"));
+        shiop.process_input_line(&mut shell, String::from("помощь"));
+        assert_eq!(shiop.take_dispatched_command(), None);
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_clear_builtin() {
+        let mut shiop: ShIop = new_shiop();
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        shiop.process_input_line(&mut shell, String::from("клеар"));
+        assert_eq!(shiop.take_dispatched_command(), None);
+        assert!(shiop.editor.buffer.is_empty());
+        //Only the clear command is a builtin
+        shiop.process_input_line(&mut shell, String::from("clearx"));
+        assert_eq!(shiop.take_dispatched_command(), Some(String::from("clearx\n")));
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_reload_builtin() {
+        let mut shiop: ShIop = new_shiop();
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(!shiop.take_reload_request());
+        shiop.process_input_line(&mut shell, String::from("релоад"));
+        assert_eq!(shiop.take_dispatched_command(), None);
+        assert!(shiop.take_reload_request());
+        assert!(!shiop.take_reload_request());
+        //Only the bare command is a builtin
+        assert!(!shiop.reload_builtin("reloader"));
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_jobs() {
+        let mut shiop = new_shiop();
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Start job in background; prompt is not blocked
+        shiop.editor.set_line("слееп 0.5 &");
+        shiop.perform_interactive_enter(&mut shell);
+        assert_eq!(shell.get_state(), ShellState::Shell);
+        assert_eq!(shell.jobs().len(), 1);
+        assert_eq!(shell.jobs()[0].command, String::from("sleep 0.5"));
+        shiop.editor.set_line("жобс");
+        shiop.perform_interactive_enter(&mut shell);
+        //Bring it to foreground
+        shiop.editor.set_line("фг %1");
+        shiop.perform_interactive_enter(&mut shell);
+        assert_eq!(shiop.dispatched_command, Some(String::from("sleep 0.5")));
+        assert_eq!(shell.get_state(), ShellState::SubprocessRunning);
+        sleep(Duration::from_millis(700));
+        assert_eq!(shell.get_state(), ShellState::Shell);
+        assert!(shell.jobs().is_empty());
+        //No job
+        shiop.editor.set_line("fg");
+        shiop.perform_interactive_enter(&mut shell);
+        assert_eq!(shell.get_state(), ShellState::Shell);
+        //Wait
+        shiop.editor.set_line("sleep 0.3 &");
+        shiop.perform_interactive_enter(&mut shell);
+        shiop.editor.set_line("wait");
+        shiop.perform_interactive_enter(&mut shell);
+        assert_eq!(shell.get_state(), ShellState::SubprocessRunning);
+        sleep(Duration::from_millis(500));
+        assert_eq!(shell.get_state(), ShellState::Shell);
+        //Kill a job
+        shiop.editor.set_line("sleep 5 &");
+        shiop.perform_interactive_enter(&mut shell);
+        assert_eq!(shell.jobs().len(), 1);
+        let id: usize = shell.jobs()[0].id;
+        shiop.dispatched_command = None;
+        shiop.editor.set_line(format!("kill -KILL %{}", id).as_str());
+        shiop.perform_interactive_enter(&mut shell);
+        assert!(shiop.dispatched_command.is_none());
+        sleep(Duration::from_millis(500));
+        assert!(matches!(shell.jobs()[0].state, JobState::Done(_)));
+        //Kill lines the builtin doesn't understand are run by the shell
+        shiop.editor.set_line("kill -l");
+        shiop.perform_interactive_enter(&mut shell);
+        assert_eq!(shiop.dispatched_command, Some(String::from("kill -l\n")));
+        sleep(Duration::from_millis(500));
+        shiop.dispatched_command = None;
+        shiop.editor.set_line("kill 99999999 || echo ok");
+        shiop.perform_interactive_enter(&mut shell);
+        assert_eq!(shiop.dispatched_command, Some(String::from("kill 99999999 || echo ok\n")));
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_background_command() {
+        assert_eq!(background_command("sleep 10 &\n"), Some("sleep 10"));
+        assert_eq!(background_command("sleep 10&"), Some("sleep 10"));
+        assert_eq!(background_command("sleep 10"), None);
+        assert_eq!(background_command("true && false"), None);
+        assert_eq!(background_command("make 2>&"), None);
+        assert_eq!(background_command("echo \\&"), None);
+        assert_eq!(background_command("&"), None);
+    }
+
     #[test]
     fn test_runtimeprops_indent_history_index() {
         let shiop = new_shiop();
@@ -795,33 +1778,91 @@ mod tests {
         sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
                                            //Prepare history
         shell.history.push(String::from("pwd"));
-        shell.history.push(String::from("ifconfig"));
+        shell.history.push(String::from("экхо Привет"));
         shell.history.push(String::from("ls -l"));
         shell.history.push(String::from("ls"));
         shell.history.push(String::from("ls -la"));
         shell.history.push(String::from("lsd")); // Newer ls match
-        shell.history.push(String::from("if")); // Newer if match
-                                                // Perform reverse search
-        shiop.rev_search = Some(String::from("ls"));
-        shiop.rev_search_idx = 0;
-        assert_eq!(shiop.search_reverse(&mut shell), Some(String::from("lsd")));
-        assert_eq!(
-            shiop.search_reverse(&mut shell),
-            Some(String::from("ls -la"))
-        );
-        assert_eq!(shiop.search_reverse(&mut shell), Some(String::from("ls")));
-        assert_eq!(
-            shiop.search_reverse(&mut shell),
-            Some(String::from("ls -l"))
-        );
-        assert_eq!(shiop.search_reverse(&mut shell), None);
-        assert_eq!(shiop.search_reverse(&mut shell), None); // No panic?
+        shell.history.push(String::from("if"));
+        //Search cyrillic, case insensitive; Esc restores the line
+        for ev in console::decode_input("экхо\x12ПРИ".as_bytes()) {
+            shiop.handle_input_event(ev, &mut shell);
+        }
+        assert_eq!(shiop.rev_search.as_ref().unwrap().query(), "экхоПРИ");
+        assert_eq!(shiop.rev_search.as_ref().unwrap().candidate(), Some("экхо Привет"));
+        assert_eq!(shiop.rev_search.as_ref().unwrap().label(), "(failed reverse-i-search)");
+        for ev in console::decode_input("\x7f\x7f\x7f\x7f\x7f\x7f\x7fПРИ".as_bytes()) {
+            shiop.handle_input_event(ev, &mut shell);
+        }
+        assert_eq!(shiop.rev_search.as_ref().unwrap().candidate(), Some("экхо Привет"));
+        shiop.handle_input_event(InputEvent::Escape, &mut shell);
+        assert!(shiop.rev_search.is_none());
+        assert_eq!(shiop.editor.get_line(), String::from("экхо"));
+        assert_eq!(shiop.take_dispatched_command(), None);
+        //Ctrl+R again moves to older matches; enter executes the candidate
+        shiop.clear_buffer();
+        for ev in console::decode_input(b"\x12LS\x12\x12") {
+            shiop.handle_input_event(ev, &mut shell);
+        }
+        assert_eq!(shiop.rev_search.as_ref().unwrap().candidate(), Some("ls"));
+        shiop.handle_input_event(InputEvent::Enter, &mut shell);
+        assert!(shiop.rev_search.is_none());
+        assert_eq!(shiop.editor.get_line(), String::new());
+        assert_eq!(shiop.take_dispatched_command(), Some(String::from("ls\n")));
+        assert_eq!(shell.history.at(0), Some(String::from("ls")));
+        sleep(Duration::from_millis(300));
+        let _ = shell.read();
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_complete() {
+        let mut shiop = new_shiop();
+        assert!(shiop.command_index.is_none());
+        let mut shell: Shell =
+            Shell::start(String::from("sh"), Vec::new(), &shiop.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Complete file in working directory (tests run in the crate directory)
+        shiop.editor.set_line("кат Cargo.t");
+        shiop.handle_input_event(InputEvent::Ctrl(9), &mut shell);
+        assert_eq!(shiop.editor.get_line(), String::from("кат Cargo.toml "));
+        assert!(shiop.last_tab);
+        //Multiple candidates: common prefix is inserted
+        shiop.editor.set_line("кат Карг");
+        shiop.handle_input_event(InputEvent::Key(String::from(" ")), &mut shell);
+        shiop.handle_input_event(InputEvent::Backspace, &mut shell);
+        assert!(!shiop.last_tab);
+        shiop.handle_input_event(InputEvent::Ctrl(9), &mut shell);
+        assert_eq!(shiop.editor.get_line(), String::from("кат Cargo."));
+        //Second tab lists candidates; line is unchanged
+        shiop.handle_input_event(InputEvent::Ctrl(9), &mut shell);
+        assert_eq!(shiop.editor.get_line(), String::from("кат Cargo."));
+        assert_eq!(shiop.editor.cursor, 10);
+        //Subcommands of well-known commands
+        shiop.editor.set_line("гит comm");
+        shiop.handle_input_event(InputEvent::Ctrl(9), &mut shell);
+        assert_eq!(shiop.editor.get_line(), String::from("гит commit "));
+        assert!(shiop.completion_definitions.is_some());
+        assert!(shiop.command_index.is_some());
+        //Index is built once, at the first completion
+        assert_eq!(INDEXES_BUILT.with(|count| count.get()), 1);
+        //No candidates
+        shiop.editor.set_line("кат nosuchfile");
+        shiop.handle_input_event(InputEvent::Ctrl(9), &mut shell);
+        assert_eq!(shiop.editor.get_line(), String::from("кат nosuchfile"));
+        //Terminate shell
+        assert!(shell.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
     }
 
     fn new_shiop() -> ShIop {
         ShIop::new(
             Config::default(),
             IOProcessor::new(Language::Russian, new_translator(Language::Russian)),
+            None,
         )
     }
 }
@@ -26,9 +26,12 @@
 
 use super::Imiop;
 use crate::config::Config;
-use crate::runtime::print_err;
+use crate::runtime::{print_err, shellsignal_to_signal, suspend_job, write_trace};
+use crate::shell::escalation::InterruptAction;
+use crate::shell::proc::ShellError;
 use crate::shell::Shell;
 use crate::translator::ioprocessor::IOProcessor;
+use crate::translator::trace::{DispatchMode, TokenTrace, TraceOutput, TranslationTrace};
 use crate::utils::buffer;
 use crate::utils::console::{self, InputEvent};
 
@@ -37,18 +40,20 @@ pub(crate) struct SubProcIop {
     input_buffer_cursor: usize,
     config: Config,
     processor: IOProcessor,
+    trace_output: Option<TraceOutput>,
 }
 
 impl SubProcIop {
     /// ### new
     ///
     /// Instantiate a new `SubProcIop`
-    pub fn new(config: Config, processor: IOProcessor) -> SubProcIop {
+    pub fn new(config: Config, processor: IOProcessor, trace_output: Option<TraceOutput>) -> SubProcIop {
         SubProcIop {
             input_buffer: Vec::with_capacity(2048),
             input_buffer_cursor: 0,
             config: config,
             processor: processor,
+            trace_output,
         }
     }
 
@@ -70,7 +75,9 @@ impl SubProcIop {
             if self.input_buffer.len() > self.input_buffer_cursor {
                 self.input_buffer.remove(self.input_buffer_cursor);
             }
-            console::backspace();
+            if console::input_echo() {
+                console::backspace();
+            }
         }
     }
 
@@ -83,17 +90,35 @@ impl SubProcIop {
         //If input is empty, ignore it
         if stdin_input.trim().len() > 0 {
             //Treat input
-            //Convert text
-            let input: String = self.processor.text_to_latin(&stdin_input);
-            if let Err(err) = shell.write(input) {
-                print_err(
+            //Convert text (unless the command must get input as it has been typed)
+            let input: String = match self.config.input_config.translation.subprocess() {
+                true => self.processor.text_to_latin(&stdin_input),
+                false => stdin_input.clone(),
+            };
+            if let Some(trace_output) = &self.trace_output {
+                let mut trace: TranslationTrace = TranslationTrace::new(stdin_input.clone(), DispatchMode::Subprocess);
+                trace.tokens.push(TokenTrace::translated(stdin_input.clone(), input.clone()));
+                trace.set_output(&input);
+                write_trace(&trace, trace_output);
+            }
+            match shell.write(input) {
+                Ok(_) => {}
+                //The command has exited before reading its input: the input is discarded
+                Err(ShellError::ProcessTerminated) => print_err(
+                    String::from("Command has terminated: input discarded"),
+                    self.config.output_config.translate_stderr,
+                    &self.processor,
+                ),
+                Err(err) => print_err(
                     String::from(err.to_string()),
-                    self.config.output_config.translate_output,
+                    self.config.output_config.translate_stderr,
                     &self.processor,
-                );
+                ),
             }
         }
         self.clear_buffer();
+        //Input has been sent; enable echo again
+        console::set_input_echo(true);
     }
 }
 
@@ -119,25 +144,45 @@ impl Imiop for SubProcIop {
                 //Pass key
                 let _ = shell.write(console::input_event_to_string(ev));
             }
+            InputEvent::Home | InputEvent::End | InputEvent::Delete | InputEvent::Escape => {
+                //Pass key
+                let _ = shell.write(console::input_event_to_string(ev));
+            }
             InputEvent::Backspace => {
                 self.backspace();
             }
             InputEvent::CarriageReturn => {
                 let _ = shell.write(console::input_event_to_string(ev));
             }
+            InputEvent::Ctrl(3) => {
+                //Interrupt the running command (every stage of a pipeline); terminate it if it keeps ignoring the interrupts
+                match shell.interrupt() {
+                    Ok(InterruptAction::Interrupt) => {}
+                    Ok(InterruptAction::Terminate) => print_err(
+                        String::from("The command is still running: sending SIGTERM"),
+                        self.config.output_config.translate_stderr,
+                        &self.processor,
+                    ),
+                    Err(_) => print_err(String::from("Could not send signal to shell"), self.config.output_config.translate_stderr, &self.processor),
+                }
+            }
+            InputEvent::Ctrl(28) => {
+                //Quit the running command
+                if let Some(sig) = shellsignal_to_signal(28) {
+                    if shell.raise(sig).is_err() {
+                        print_err(String::from("Could not send signal to shell"), self.config.output_config.translate_stderr, &self.processor);
+                    }
+                }
+            }
+            InputEvent::Ctrl(26) => {
+                //Suspend the job in foreground; commands run by the shell can't be suspended, so the key is passed to them
+                if !suspend_job(shell) {
+                    let _ = shell.write(console::input_event_to_string(ev));
+                }
+            }
             InputEvent::Ctrl(_) => {
                 //Pass to child
-                //FIXME: doesn't work
                 let _ = shell.write(console::input_event_to_string(ev));
-                //let mut output = String::with_capacity(1);
-                //output.push(sig as char);
-                //let _ = shell.write(output);
-                /*
-                if let Some(sig) = super::shellsignal_to_signal(sig) {
-                    if let Err(_) = shell.raise(sig) {
-                        print_err(String::from("Could not send signal to shell"), self.config.output_config.translate_output, &self.processor);
-                    }
-                }*/
             }
             InputEvent::Key(k) => {
                 //Push key
@@ -146,8 +191,16 @@ impl Imiop for SubProcIop {
                     self.input_buffer.insert(self.input_buffer_cursor, ch);
                     self.input_buffer_cursor += 1;
                 }
-                //Print key
-                console::print(k);
+                //Print key (unless echo is disabled)
+                console::echo(k);
+            }
+            InputEvent::Paste(text) => {
+                //Pasted text is sent with the input buffer when enter is pressed
+                for ch in text.chars() {
+                    self.input_buffer.insert(self.input_buffer_cursor, ch);
+                    self.input_buffer_cursor += 1;
+                }
+                console::echo(text);
             }
             InputEvent::Enter => {
                 //@! Send input
@@ -161,7 +214,7 @@ impl Imiop for SubProcIop {
 mod tests {
     use super::*;
 
-    use crate::config::Config;
+    use crate::config::{Config, InputTranslation};
     use crate::translator::ioprocessor::IOProcessor;
     use crate::translator::lang::Language;
     use crate::translator::new_translator;
@@ -231,6 +284,9 @@ mod tests {
         processor.handle_input_event(InputEvent::ArrowUp, &mut shell);
         //Signal
         processor.handle_input_event(InputEvent::Ctrl(3), &mut shell);
+        //No job to suspend: Ctrl+Z is passed to the shell
+        processor.handle_input_event(InputEvent::Ctrl(26), &mut shell);
+        processor.handle_input_event(InputEvent::Ctrl(28), &mut shell);
         //Stop shell
         sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
         let _ = shell.stop();
@@ -246,10 +302,36 @@ mod tests {
         sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
     }
 
+    #[test]
+    fn test_runtimeprops_input_translation() {
+        //Input typed for a running command is transliterated only with `always`
+        for (translation, expected) in [
+            (InputTranslation::Always, "privet, mir\n"),
+            (InputTranslation::CommandLineOnly, "привет, мир\n"),
+            (InputTranslation::Never, "привет, мир\n"),
+        ].iter() {
+            let mut processor = new_subprociop();
+            processor.config.input_config.translation = *translation;
+            let mut shell: Shell = Shell::start(String::from("sh"), Vec::new(), &processor.config.prompt_config).unwrap();
+            //Run cat in foreground: it writes back the bytes it gets
+            shell.spawn_job("cat").unwrap();
+            shell.foreground_job(None).unwrap();
+            processor.handle_input_event(InputEvent::Key(String::from("привет, мир")), &mut shell);
+            processor.handle_input_event(InputEvent::Enter, &mut shell);
+            let _ = shell.write(String::from("\n"));
+            sleep(Duration::from_millis(300));
+            let (stdout, _) = shell.read().unwrap();
+            assert_eq!(stdout.unwrap(), String::from(*expected));
+            let _ = shell.raise(shellsignal_to_signal(3).unwrap());
+            let _ = shell.stop();
+        }
+    }
+
     fn new_subprociop() -> SubProcIop {
         SubProcIop::new(
             Config::default(),
             IOProcessor::new(Language::Russian, new_translator(Language::Russian)),
+            None,
         )
     }
 }
@@ -28,28 +28,74 @@ extern crate ansi_term;
 extern crate nix;
 
 // Runtime modules
+mod banner;
+pub mod capabilities;
+pub mod doctor;
 mod props;
 mod imiop;
+pub mod output;
+pub mod session;
 
-use ansi_term::Colour;
+use ansi_term::{Colour, Style};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{DirBuilder, OpenOptions};
+use std::os::unix::fs::DirBuilderExt;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::thread::sleep;
-use std::time::{Duration};
+use std::time::{Duration, Instant};
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet};
 
 //Config
 use crate::config;
+//Capabilities
+use capabilities::{Capabilities, Feature};
 //Props
+use output::print_out;
 use props::RuntimeProps;
+use session::SessionServer;
 //Shell
-use crate::shell::{Shell, ShellState};
+use crate::shell::alias::{self, AliasError};
+use crate::shell::handshake;
+use crate::shell::lookup;
+use crate::shell::terminal::Terminal;
+use crate::shell::{self, prompt, Shell, ShellState};
+use crate::shell::escalation::{Timeout, TimeoutAction};
+use crate::shell::prompt::{PromptCache, StatusReport};
 use crate::shell::unixsignal::UnixSignal;
 // Translator
-use crate::translator::ioprocessor::IOProcessor;
+use crate::translator::filter::hints::ErrorHints;
+use crate::translator::filter::units::UnitsFilter;
+use crate::translator::ioprocessor::{ExpressionParserError, IOProcessor, OutputStream};
 use crate::translator::lang::Language;
-use crate::translator::new_translator;
+use crate::translator::{new_translator, new_translator_with_options, LocalizedError, TranslationMode, TranslatorOptions, TransliterationStandard};
+use crate::translator::trace::{DispatchMode, TokenDecision, TokenTrace, TraceOutput, TranslationTrace};
 //Utils
 use crate::utils::console;
-use crate::utils::file;
+use crate::utils::file::{self, LoadedLines};
+use crate::utils::logger;
+use crate::utils::profile;
+use crate::utils::terminal;
+
+/// Time the shell has to answer at startup
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Exit code of the command which has run out of time (`core.command_timeout`), the same of `timeout`
+const COMMAND_TIMEOUT_EXIT_CODE: u8 = 124;
+
+/// Set by the SIGHUP handler
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Set by the SIGTSTP handler
+static SUSPEND_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Set by the SIGQUIT handler
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// File and line being run from the rc file; errors are prefixed with it
+    static ref ERROR_LOCATION: Mutex<Option<String>> = Mutex::new(None);
+}
 
 //@! Runners
 
@@ -57,35 +103,92 @@ use crate::utils::file;
 ///
 /// Run pyc in interactive mode
 
-pub fn run_interactive(language: Language, config: config::Config, shell: Option<String>, history_file: Option<PathBuf>) -> u8 {
+pub fn run_interactive(language: Language, config: config::Config, config_file: PathBuf, shell: Option<String>, mut history_file: Option<PathBuf>, mut trace_output: Option<TraceOutput>, session_name: Option<String>) -> u8 {
+    let mut processor: IOProcessor = new_processor(language, &config);
+    profile::mark("translator");
+    //Disable features which can't write their files
+    let capabilities: Capabilities = probe_capabilities(&mut history_file, &mut trace_output, config.output_config.translate_stderr, &processor);
+    profile::mark("capabilities");
+    //Render the banner before the configuration file is handed to the props
+    let banner: Vec<String> = match banner::should_print(&config, nix::unistd::isatty(0).unwrap_or(false)) {
+        true => {
+            banner::render(language, config.language_standard.as_str(), config_file.as_path(), banner::tip_seed(), terminal::size().columns, banner::colors_enabled())
+        }
+        false => Vec::new(),
+    };
     //Instantiate Runtime Props
-    let mut props: RuntimeProps = RuntimeProps::new(true, config, language);
-    let processor: IOProcessor = IOProcessor::new(language, new_translator(language));
+    let mut props: RuntimeProps = RuntimeProps::new(true, config, language, trace_output);
+    props.set_config_file(config_file);
+    //Create the session control socket
+    let mut session: Option<SessionServer> = match session_name {
+        None => None,
+        Some(name) => match SessionServer::bind(name.as_str()) {
+            Ok(server) => Some(server),
+            Err(err) => {
+                print_err(
+                    format!("Could not create session '{}': {}", name, err),
+                    props.config.output_config.translate_stderr,
+                    &processor,
+                );
+                return 255;
+            }
+        },
+    };
     //Determine the shell to use
     let (shell, args): (String, Vec<String>) = resolve_shell(&props.config, shell);
-    //Intantiate and start a new shell
-    let mut shell: Shell = match Shell::start(shell, args, &props.config.prompt_config) {
+    if let Err(err) = lookup::check(shell.as_str(), env::var_os("PATH")) {
+        print_err(err.to_string(), props.config.output_config.translate_stderr, &processor);
+        return 255;
+    }
+    //Intantiate and start a new shell; in interactive mode commands run under a pseudo-terminal, unless disabled
+    let mut shell: Shell = match Shell::start_with(shell, args, &props.config.prompt_config, props.config.core_config.use_pty) {
         Ok(sh) => sh,
         Err(err) => {
-            print_err(
-                String::from(format!("Could not start shell: {}", err)),
-                props.config.output_config.translate_output,
-                &processor,
-            );
+            print_localized_err("Could not start shell", &err, props.config.output_config.translate_stderr, &processor);
             return 255;
         }
     };
+    if !shell_handshake(&mut shell, props.config.output_config.translation(), &processor) {
+        return 255;
+    }
+    shell.set_degraded(capabilities.disabled_features());
+    props.set_capabilities(capabilities);
+    shell.set_sigint_escalation(&props.config.core_config.sigint_escalation);
+    profile::mark("shell");
     //If history file is set, load history
-    if let Some(history_file) = history_file.clone() {
-        match file::read_lines(history_file.clone()) {
-            Ok(lines) => shell.history.load(lines),
+    if let Some(history_file) = history_file.as_ref() {
+        match load_history(&mut shell, history_file) {
+            Ok(Some(warning)) => print_err(warning, props.config.output_config.translate_stderr, &processor),
+            Ok(None) => {}
             Err(err) => print_err(
                 String::from(format!("Could not load history from '{}': {}", history_file.display(), err)),
-                props.config.output_config.translate_output,
+                props.config.output_config.translate_stderr,
                 &processor,
-            )
+            ),
         }
     };
+    profile::mark("history");
+    //Print the banner before the first prompt
+    for line in banner.into_iter() {
+        console::println(line);
+    }
+    //Run the rc file before the first prompt
+    if let Some(rc_file) = props.config.core_config.rc_file.clone() {
+        source_rc_file(rc_file.as_path(), &mut props, &mut shell, &mut processor);
+        profile::mark("rc_file");
+    }
+    //Keys are read as soon as they're typed; the original termios is restored when the terminal is dropped
+    let terminal: Option<Terminal> = Terminal::enter_raw();
+    //Pasted text is read as a whole, so that its newlines don't execute it
+    console::set_bracketed_paste(true);
+    //SIGHUP reloads the configuration
+    handle_sighup();
+    //Writing to a command which has exited mustn't terminate pyc
+    ignore_sigpipe();
+    //SIGTSTP and SIGQUIT are forwarded to the job in foreground
+    handle_job_signals();
+    //SIGWINCH makes the terminal size to be resolved again
+    terminal::watch_resize();
     //@! Main loop
     while props.get_last_state() != ShellState::Terminated {
         //@! Print prompt if state is Idle and state has changed
@@ -94,10 +197,23 @@ pub fn run_interactive(language: Language, config: config::Config, shell: Option
             props.update_state(current_state);
         }
         if props.get_state_changed() && current_state == ShellState::Shell {
+            //Print the timing of the command run with `time`
+            if let Some(timing) = shell.take_timing() {
+                let _ = console::write(&mut io::stderr(), timing.report().as_str());
+            }
+            //Print the hint for the failed command
+            if let Some(hint) = props.take_error_hint(shell.get_exit_status()) {
+                print_err(hint, false, &processor);
+            }
+            if let Some(suggestion) = props.take_command_suggestion(shell.get_exit_status(), &processor) {
+                print_err(suggestion, props.config.output_config.translate_stderr, &processor);
+            }
             //Force shellenv to refresh info
             shell.refresh_env();
             //Print prompt
-            console::print(format!("{} ", shell.get_promptline(&processor)));
+            let prompt_line: String = shell.get_promptline(&processor);
+            profile::finish("prompt");
+            console::print(format!("{} ", prompt_line));
             props.report_state_changed_notified(); //Force state changed to false
         } else if props.get_state_changed() {
             props.report_state_changed_notified(); //Check has been done, nothing to do
@@ -106,23 +222,56 @@ pub fn run_interactive(language: Language, config: config::Config, shell: Option
         if let Some(ev) = console::read() {
             props.handle_input_event(ev, &mut shell);
         };
+        //@! Send the new terminal size to the running command
+        if terminal::take_resize() {
+            shell.resize();
+        }
+        //@! Forward SIGTSTP and SIGQUIT received by pyc
+        if SUSPEND_REQUESTED.swap(false, Ordering::SeqCst) {
+            suspend_job(&mut shell);
+        }
+        if QUIT_REQUESTED.swap(false, Ordering::SeqCst) {
+            let _ = shell.raise(UnixSignal::Sigquit);
+        }
+        //@! Kill the command which doesn't terminate after SIGTERM
+        if shell.poll_escalation() {
+            print_err(String::from("The command is still running: sending SIGKILL"), props.config.output_config.translate_stderr, &processor);
+        }
+        //@! Reload configuration if `reload` has been run or SIGHUP has been received
+        if props.take_reload_request() || RELOAD_REQUESTED.swap(false, Ordering::SeqCst) {
+            reload_config(&mut props, &mut shell, &mut processor);
+        }
         //Update state after write
         let new_state = shell.get_state(); //Force last state to be changed
         if new_state != props.get_last_state() {
             props.update_state(new_state);
         }
         //@! Read Shell stdout
-        read_from_shell(&mut shell, &props.config, &processor);
+        if let Err(err) = read_from_shell(&mut shell, props.get_translate_output(), &mut processor, session.as_mut(), props.get_error_hints()) {
+            if err.kind() == io::ErrorKind::BrokenPipe {
+                handle_broken_pipe(&mut shell);
+                break;
+            }
+        }
+        //@! Run commands received from the session socket
+        if let Some(session) = session.as_mut() {
+            serve_session(session, &mut props, &mut shell);
+        }
+        //@! Redraw prompt once async prompt modules have been resolved (all the values received are drawn at once)
+        if shell.poll_prompt() && props.get_last_state() == ShellState::Shell {
+            props.redraw_prompt(&mut shell);
+        }
         //Check if shell has terminated
         sleep(Duration::from_nanos(100)); //Sleep for 100ns
     } //@! End of loop
-    //Write history back to file
-    if let Some(history_file) = history_file {
-        let lines: Vec<String> = shell.history.dump();
-        if let Err(err) = file::write_lines(history_file.clone(), lines) {
+    console::set_bracketed_paste(false);
+    drop(terminal);
+    //Write history back to file; history may have been enabled again by a reload
+    if let Some(history_file) = props.get_history_file() {
+        if let Err(err) = save_history(&mut shell, history_file.as_path()) {
             print_err(
                 String::from(format!("Could not write history to '{}': {}", history_file.display(), err)),
-                props.config.output_config.translate_output,
+                props.config.output_config.translate_stderr,
                 &processor,
             );
         }
@@ -131,7 +280,7 @@ pub fn run_interactive(language: Language, config: config::Config, shell: Option
     match shell.stop() {
         Ok(rc) => rc,
         Err(err) => {
-            print_err(format!("Could not stop shell: {}", err), props.config.output_config.translate_output, &processor);
+            print_localized_err("Could not stop shell", &err, props.config.output_config.translate_stderr, &processor);
             255
         }
     }
@@ -140,24 +289,34 @@ pub fn run_interactive(language: Language, config: config::Config, shell: Option
 /// ### run_command
 /// 
 /// Run command in shell and return
-pub fn run_command(mut command: String, language: Language, config: config::Config, shell: Option<String>) -> u8 {
+pub fn run_command(mut command: String, language: Language, config: config::Config, shell: Option<String>, mut trace_output: Option<TraceOutput>) -> u8 {
+    let mut processor: IOProcessor = new_processor(language, &config);
+    profile::mark("translator");
+    //Disable features which can't write their files
+    let _ = probe_capabilities(&mut None, &mut trace_output, config.output_config.translate_stderr, &processor);
+    profile::mark("capabilities");
     //Instantiate Runtime Props
-    let mut props: RuntimeProps = RuntimeProps::new(false, config, language);
-    let processor: IOProcessor = IOProcessor::new(language, new_translator(language));
+    let mut props: RuntimeProps = RuntimeProps::new(false, config, language, trace_output.clone());
     //Determine the shell to use
     let (shell, args): (String, Vec<String>) = resolve_shell(&props.config, shell);
+    if let Err(err) = lookup::check(shell.as_str(), env::var_os("PATH")) {
+        print_err(err.to_string(), props.config.output_config.translate_stderr, &processor);
+        return 255;
+    }
+    //Writing to the shell once it has exited mustn't terminate pyc
+    ignore_sigpipe();
     //Intantiate and start a new shell
     let mut shell: Shell = match Shell::start(shell, args, &props.config.prompt_config) {
         Ok(sh) => sh,
         Err(err) => {
-            print_err(
-                String::from(format!("Could not start shell: {}", err)),
-                props.config.output_config.translate_output,
-                &processor,
-            );
+            print_localized_err("Could not start shell", &err, props.config.output_config.translate_stderr, &processor);
             return 255;
         }
     };
+    if !shell_handshake(&mut shell, props.config.output_config.translation(), &processor) {
+        return 255;
+    }
+    profile::mark("shell");
     //Prepare command
     while command.ends_with('\n') {
         command.pop();
@@ -165,37 +324,79 @@ pub fn run_command(mut command: String, language: Language, config: config::Conf
     while command.ends_with(';') {
         command.pop();
     }
+    //Command is not translated
+    logger::debug("runtime", format!("running command: {}", command).as_str());
+    props.update_translate_output(command.as_str());
+    props.start_error_hints(command.as_str());
+    if let Some(trace_output) = &trace_output {
+        let mut trace: TranslationTrace = TranslationTrace::new(command.clone(), DispatchMode::Oneshot);
+        trace.tokens.push(TokenTrace::new(command.clone(), command.clone(), TokenDecision::Unchanged));
+        trace.set_output(&command);
+        write_trace(&trace, trace_output);
+    }
+    if props.config.output_config.echo_command {
+        console::println(echo_line(command.as_str()));
+    }
     //FIXME: handle fish $status
     command.push_str("; exit $?\n");
     //Write command
     if let Err(err) = shell.write(command) {
-        print_err(
-            String::from(format!("Could not start shell: {}", err)),
-            props.config.output_config.translate_output,
-            &processor,
-        );
+        print_localized_err("Could not start shell", &err, props.config.output_config.translate_stderr, &processor);
         return 255;
     }
     let _ = shell.write(String::from("\n"));
+    profile::finish("dispatch");
+    //The command is terminated once `core.command_timeout` has elapsed, and killed if it's still running after the grace period
+    let mut timeout: Option<Timeout> = match props.config.core_config.command_timeout {
+        0 => None,
+        seconds => Some(Timeout::new(
+            Instant::now(),
+            Duration::from_secs(seconds as u64),
+            Duration::from_millis(props.config.core_config.sigint_escalation.kill_after_ms as u64),
+        )),
+    };
     //@! Main loop
     loop { //Check state after reading/writing, since program could have already terminate
         //@! Read user input
         if let Some(ev) = console::read() {
             props.handle_input_event(ev, &mut shell);
         };
+        //@! Terminate the command which has run out of time
+        if let Some(action) = timeout.as_mut().and_then(|timeout| timeout.poll(Instant::now())) {
+            let signal: UnixSignal = match action {
+                TimeoutAction::Terminate => UnixSignal::Sigterm,
+                TimeoutAction::Kill => UnixSignal::Sigkill,
+            };
+            logger::warn("runtime", format!("command timed out: sending {:?}", signal).as_str());
+            let _ = shell.abort(signal);
+        }
         //@! Read Shell stdout
-        read_from_shell(&mut shell, &props.config, &processor);
+        if let Err(err) = read_from_shell(&mut shell, props.get_translate_output(), &mut processor, None, props.get_error_hints()) {
+            if err.kind() == io::ErrorKind::BrokenPipe {
+                handle_broken_pipe(&mut shell);
+                break;
+            }
+        }
         //Check if shell has terminated
         if shell.get_state() == ShellState::Terminated {
             break;
         }
         sleep(Duration::from_nanos(100)); //Sleep for 100ns
     } //@! End of main loop
-    //Return shell exitcode
+    //Return shell exitcode; the command which has timed out exits with 124, like `timeout`
     match shell.stop() {
-        Ok(rc) => rc,
+        Ok(_) if timeout.as_ref().map(|timeout| timeout.expired()).unwrap_or(false) => COMMAND_TIMEOUT_EXIT_CODE,
+        Ok(rc) => {
+            if let Some(hint) = props.take_error_hint(rc) {
+                print_err(hint, false, &processor);
+            }
+            if let Some(suggestion) = props.take_command_suggestion(rc, &processor) {
+                print_err(suggestion, props.config.output_config.translate_stderr, &processor);
+            }
+            rc
+        }
         Err(err) => {
-            print_err(format!("Could not stop shell: {}", err), props.config.output_config.translate_output, &processor);
+            print_localized_err("Could not stop shell", &err, props.config.output_config.translate_stderr, &processor);
             255
         }
     }
@@ -204,47 +405,422 @@ pub fn run_command(mut command: String, language: Language, config: config::Conf
 /// ### run_file
 /// 
 /// Run shell reading commands from file
-pub fn run_file(file: String, language: Language, config: config::Config, shell: Option<String>) -> u8 {
+pub fn run_file(file: String, language: Language, config: config::Config, shell: Option<String>, trace_output: Option<TraceOutput>) -> u8 {
     let file_path: &Path = Path::new(file.as_str());
     let processor: IOProcessor = IOProcessor::new(language, new_translator(language));
     let lines: Vec<String> = match file::read_lines(file_path) {
         Ok(lines) => lines,
         Err(_) => {
-            print_err(format!("{}: No such file or directory", file), config.output_config.translate_output, &processor);
+            print_err(format!("{}: No such file or directory", file), config.output_config.translate_stderr, &processor);
             return 255
         }
     };
     //Join lines in a single command
     let command: String = script_lines_to_string(&lines);
     //Execute command
-    run_command(command, language, config, shell)
+    run_command(command, language, config, shell, trace_output)
+}
+
+/// ### run_stdin
+///
+/// Run shell reading commands from stdin (e.g. `echo "лс" | pyc`); each line is transliterated and executed
+/// sequentially. Execution stops at the first command which fails, unless `keep_going` is set.
+/// Returns the exit code of the failed command, or the one of the last command if all of them succeeded
+pub fn run_stdin(language: Language, config: config::Config, shell: Option<String>, trace_output: Option<TraceOutput>, keep_going: bool) -> u8 {
+    let mut input: Vec<u8> = Vec::new();
+    if let Err(err) = io::Read::read_to_end(&mut io::stdin(), &mut input) {
+        print_err(format!("Could not read stdin: {}", err), config.output_config.translate_stderr, &new_processor(language, &config));
+        return 255;
+    }
+    let lines: Vec<String> = String::from_utf8_lossy(&input).lines().map(String::from).collect();
+    run_expressions(&lines, language, config, shell, trace_output, keep_going)
+}
+
+/// ### run_expressions
+///
+/// Run shell executing the provided expressions (e.g. `pyc -e "экхо 'а | б' | кат"`), as `sh -c` does: each expression is
+/// transliterated and executed sequentially. Execution stops at the first expression which fails, unless `keep_going` is set.
+/// Returns the exit code of the failed expression, or the one of the last expression if all of them succeeded
+pub fn run_expressions(expressions: &[String], language: Language, config: config::Config, shell: Option<String>, trace_output: Option<TraceOutput>, keep_going: bool) -> u8 {
+    let mut processor: IOProcessor = new_processor(language, &config);
+    //Determine the shell to use
+    let translation: config::OutputTranslation = config.output_config.translation();
+    let (shell, args): (String, Vec<String>) = resolve_shell(&config, shell);
+    if let Err(err) = lookup::check(shell.as_str(), env::var_os("PATH")) {
+        print_err(err.to_string(), translation.stderr, &processor);
+        return 255;
+    }
+    //Writing to the shell once it has exited mustn't terminate pyc
+    ignore_sigpipe();
+    let mut shell: Shell = match Shell::start(shell, args, &config.prompt_config) {
+        Ok(sh) => sh,
+        Err(err) => {
+            print_localized_err("Could not start shell", &err, translation.stderr, &processor);
+            return 255;
+        }
+    };
+    if !shell_handshake(&mut shell, translation, &processor) {
+        return 255;
+    }
+    let rc: u8 = run_lines(&mut shell, expressions, &mut processor, &config, trace_output.as_ref(), keep_going);
+    //Wait for the shell to exit
+    if shell.write(String::from("exit\n")).is_ok() {
+        while shell.get_state() != ShellState::Terminated {
+            if read_from_shell(&mut shell, translation, &mut processor, None, None).is_err() {
+                break;
+            }
+        }
+    }
+    match shell.stop() {
+        Ok(exit_rc) if rc == 0 => exit_rc,
+        Ok(_) => rc,
+        Err(err) => {
+            print_localized_err("Could not stop shell", &err, translation.stderr, &processor);
+            255
+        }
+    }
+}
+
+/// ### run_prompt
+///
+/// Print the configured prompt for another shell (e.g. `PS1` of bash), with the provided exit status, duration and working directory.
+/// The prompt is printed on a single line without trailing newline; with `readline_escapes` it's ready to be set as `PS1`
+pub fn run_prompt(language: Language, config: config::Config, exit_status: u8, elapsed_time: Duration, wrkdir: PathBuf, readline_escapes: bool) -> u8 {
+    let processor: IOProcessor = new_processor(language, &config);
+    let prompt_line: String = prompt_line(&config, exit_status, elapsed_time, wrkdir, readline_escapes, &processor);
+    match console::write(&mut io::stdout(), prompt_line.as_str()) {
+        Ok(_) => 0,
+        Err(_) => 255,
+    }
+}
+
+/// ### run_status
+///
+/// Print the status values of the working directory for external status bars, either as a JSON object or on a line rendered from the template.
+/// The repository discovery is cached in the session directory, so that frequent invocations are cheap
+pub fn run_status(language: Language, config: config::Config, exit_status: u8, wrkdir: PathBuf, json: bool, template: Option<String>) -> u8 {
+    let cache_file: PathBuf = session::session_dir().join("prompt-cache");
+    let mut cache: PromptCache = PromptCache::load(cache_file.as_path());
+    let report: StatusReport = shell::status(&config.prompt_config, exit_status, wrkdir, language, &mut cache);
+    profile::finish(match report.cached_repository {
+        true => "status (cached repository)",
+        false => "status",
+    });
+    //The cache is an optimization; the status is printed even if it can't be written
+    if let Some(dir) = cache_file.parent() {
+        if !dir.exists() {
+            let _ = DirBuilder::new().recursive(true).mode(0o700).create(dir);
+        }
+    }
+    let _ = cache.save(cache_file.as_path());
+    let output: String = match json {
+        true => report.to_json(),
+        false => report.render(template.as_deref().unwrap_or(prompt::DEFAULT_TEMPLATE)),
+    };
+    match console::write(&mut io::stdout(), format!("{}\n", output).as_str()) {
+        Ok(_) => 0,
+        Err(_) => 255,
+    }
+}
+
+/// ### prompt_line
+///
+/// Render the prompt for another shell on a single line
+fn prompt_line(config: &config::Config, exit_status: u8, elapsed_time: Duration, wrkdir: PathBuf, readline_escapes: bool, processor: &IOProcessor) -> String {
+    let prompt_line: String = shell::render_prompt(&config.prompt_config, exit_status, elapsed_time, wrkdir, processor);
+    match readline_escapes {
+        true => prompt::readline_escapes(prompt_line.as_str()),
+        false => prompt_line.replace('\n', " "),
+    }
 }
 
 //@! Shell functions
 
 /// ### read_from_shell
 /// 
-/// Read from shell stderr and stdout
-fn read_from_shell(shell: &mut Shell, config: &config::Config, processor: &IOProcessor) {
+/// Read from shell stderr and stdout; the output is forwarded to the session client which requested the running command, if any.
+/// Stderr is collected by the error hints, if enabled, before being translated.
+/// Translated output is streamed line by line through the processor; the pending partial lines are printed once the stream is idle.
+/// Returns error if pyc's stdout is no longer writable (e.g. the reader of a pipe has gone away)
+fn read_from_shell(shell: &mut Shell, translation: config::OutputTranslation, processor: &mut IOProcessor, mut session: Option<&mut SessionServer>, hints: Option<&mut ErrorHints>) -> io::Result<()> {
     if let Ok((out, err)) = shell.read() {
-        if out.is_some() {
-            //Convert out to cyrillic
-            print_out(out.unwrap(), config.output_config.translate_output, &processor);
+        //If a subprocess is asking for a password, don't echo what the user types
+        let asks_password = |o: &Option<String>| o.as_ref().map(|o| is_password_prompt(o)).unwrap_or(false);
+        if shell.get_state() == ShellState::SubprocessRunning && (asks_password(&out) || asks_password(&err)) {
+            console::set_input_echo(false);
         }
-        if err.is_some() {
-            //Convert err to cyrillic
-            print_err(err.unwrap().to_string(), config.output_config.translate_output, &processor);
+        if let Some(err) = err.as_ref() {
+            if let Some(hints) = hints {
+                hints.feed(err.as_str());
+            }
         }
+        //Once the command has terminated, its whole output must be printed
+        let idle: bool = shell.get_state() == ShellState::Shell;
+        //Convert out to cyrillic
+        let out: String = stream_output(out, OutputStream::Stdout, translation.stdout, idle, processor);
+        if !out.is_empty() {
+            if let Some(session) = session.as_mut() {
+                session.forward_output(out.as_str());
+            }
+            print_out(out, false, processor)?;
+        }
+        //Convert err to cyrillic
+        let err: String = stream_output(err, OutputStream::Stderr, translation.stderr, idle, processor);
+        if !err.is_empty() {
+            if let Some(session) = session.as_mut() {
+                session.forward_output(err.as_str());
+            }
+            output::print_err(err, false, processor);
+        }
+    }
+    Ok(())
+}
+
+/// ### stream_output
+///
+/// Pass a chunk read from a shell stream to the processor; if nothing has been read or the shell is idle,
+/// the pending output of the stream is flushed. If output is not translated, the chunk is returned as is
+fn stream_output(chunk: Option<String>, stream: OutputStream, translate_output: bool, idle: bool, processor: &mut IOProcessor) -> String {
+    match (chunk, translate_output) {
+        (Some(chunk), true) => {
+            let mut output: String = processor.push_output(stream, chunk.as_str());
+            if idle {
+                output.push_str(processor.flush_output(stream).as_str());
+            }
+            output
+        }
+        (None, true) => processor.flush_output(stream),
+        (Some(chunk), false) => chunk,
+        (None, false) => String::new(),
+    }
+}
+
+/// ### serve_session
+///
+/// Handle the requests received on the session socket: when the shell is idle, the next queued command is run
+/// and, once it has terminated, its exit code is reported to the client
+fn serve_session(session: &mut SessionServer, props: &mut RuntimeProps, shell: &mut Shell) {
+    session.poll(shell.get_wrkdir().as_path());
+    if shell.get_state() != ShellState::Shell {
+        return;
+    }
+    //The command received from the socket has terminated
+    if session.is_running() {
+        session.command_done(shell.get_exit_status());
+    }
+    if let Some(command) = session.next_command() {
+        if props.dispatch_input(command, shell).is_none() {
+            //Nothing has been written to the shell (e.g. builtin command or input error)
+            session.command_done(0);
+        }
+    }
+}
+
+/// ### is_password_prompt
+///
+/// Returns whether the last line of the output is asking for a password (e.g. `Password: `)
+fn is_password_prompt(out: &str) -> bool {
+    let last_line: String = match out.lines().last() {
+        Some(line) => line.trim().to_lowercase(),
+        None => return false,
+    };
+    last_line.ends_with(':') && ["password", "passphrase", "пароль"].iter().any(|p| last_line.contains(p))
+}
+
+/// ### probe_capabilities
+///
+/// Check whether the output files can be written; the features which can't write their file are disabled
+/// and a single warning, listing all of them, is printed
+fn probe_capabilities(history_file: &mut Option<PathBuf>, trace_output: &mut Option<TraceOutput>, to_cyrillic: bool, processor: &IOProcessor) -> Capabilities {
+    let capabilities: Capabilities = Capabilities::probe_outputs(history_file.as_ref(), trace_output.as_ref());
+    if let Some(warning) = capabilities.warning() {
+        print_err(warning, to_cyrillic, processor);
+    }
+    if !capabilities.is_enabled(Feature::History) {
+        *history_file = None;
+    }
+    if !capabilities.is_enabled(Feature::TranslationTrace) {
+        *trace_output = None;
+    }
+    capabilities
+}
+
+/// ### load_history
+///
+/// Load shell history from history file; if the file contains more entries than the history size, the oldest are discarded.
+/// If the history file is corrupt, it is moved aside and the warning to report is returned
+fn load_history(shell: &mut Shell, history_file: &Path) -> io::Result<Option<String>> {
+    match file::load_lines(history_file)? {
+        LoadedLines::Valid(lines) => {
+            shell.history.load(lines);
+            Ok(None)
+        }
+        LoadedLines::Quarantined(quarantine, reason) => Ok(Some(format!(
+            "History file '{}' {}: it has been moved to '{}'",
+            history_file.display(),
+            reason,
+            quarantine.display()
+        ))),
+    }
+}
+
+/// ### save_history
+///
+/// Write shell history to history file, oldest entries first; the directory of the file is created if it doesn't exist.
+/// The entries written to the file by other sessions in the meantime are kept, before the ones of this session
+fn save_history(shell: &mut Shell, history_file: &Path) -> io::Result<()> {
+    if let Some(dir) = history_file.parent() {
+        if !dir.as_os_str().is_empty() && !dir.exists() {
+            DirBuilder::new().recursive(true).create(dir)?;
+        }
+    }
+    let lines: Vec<String> = shell.history.dump();
+    let size: usize = shell.history.size();
+    file::update_lines(history_file, |current| merge_history(current, lines, size))
+}
+
+/// ### merge_history
+///
+/// Merge the entries of the history file with the history of this session, keeping the newest `size` entries
+fn merge_history(current: Vec<String>, lines: Vec<String>, size: usize) -> Vec<String> {
+    let mut merged: Vec<String> = current.into_iter().filter(|entry| !lines.contains(entry)).collect();
+    merged.extend(lines);
+    let excess: usize = merged.len().saturating_sub(size);
+    merged.split_off(excess)
+}
+
+/// ### handle_broken_pipe
+/// 
+/// Stdout has been closed by its reader (e.g. `pyc -c "лс" | head -1`): stop printing and send SIGPIPE to the shell,
+/// so that it terminates as it would have done if it had been writing to the pipe itself.
+/// Processes started by the shell will get SIGPIPE on their own once the shell pipes are closed
+fn handle_broken_pipe(shell: &mut Shell) {
+    logger::warn("runtime", "stdout has been closed by its reader");
+    let _ = shell.raise(UnixSignal::Sigpipe);
+}
+
+/// ### handle_sighup
+///
+/// Install the SIGHUP handler, which requests the configuration to be reloaded
+fn handle_sighup() {
+    let action: SigAction = SigAction::new(SigHandler::Handler(request_reload), SaFlags::SA_RESTART, SigSet::empty());
+    let _ = unsafe { signal::sigaction(signal::Signal::SIGHUP, &action) };
+}
+
+/// ### ignore_sigpipe
+///
+/// Ignore SIGPIPE: writing to a process which has exited fails with EPIPE instead of terminating pyc.
+/// The processes started by pyc restore the default disposition
+fn ignore_sigpipe() {
+    let _ = unsafe { signal::signal(signal::Signal::SIGPIPE, SigHandler::SigIgn) };
+}
+
+/// ### request_reload
+///
+/// SIGHUP handler
+extern "C" fn request_reload(_: i32) {
+    RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// ### handle_job_signals
+///
+/// Install the SIGTSTP and SIGQUIT handlers; pyc is neither stopped nor terminated, the signals are forwarded instead
+fn handle_job_signals() {
+    let action: SigAction = SigAction::new(SigHandler::Handler(request_suspend), SaFlags::SA_RESTART, SigSet::empty());
+    let _ = unsafe { signal::sigaction(signal::Signal::SIGTSTP, &action) };
+    let action: SigAction = SigAction::new(SigHandler::Handler(request_quit), SaFlags::SA_RESTART, SigSet::empty());
+    let _ = unsafe { signal::sigaction(signal::Signal::SIGQUIT, &action) };
+}
+
+/// ### request_suspend
+///
+/// SIGTSTP handler
+extern "C" fn request_suspend(_: i32) {
+    SUSPEND_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// ### request_quit
+///
+/// SIGQUIT handler
+extern "C" fn request_quit(_: i32) {
+    QUIT_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// ### suspend_job
+///
+/// Suspend the job in foreground and report it; returns false if no job is in foreground
+fn suspend_job(shell: &mut Shell) -> bool {
+    match shell.suspend() {
+        Some((id, command)) => {
+            console::println(String::new());
+            console::println(format!("[{}]+ {:<24}{}", id, "Stopped", command));
+            true
+        }
+        None => false,
+    }
+}
+
+/// ### reload_config
+///
+/// Reload the configuration from its file and rebuild the translator; if the new configuration can't be parsed
+/// the current one is kept and the error is printed
+fn reload_config(props: &mut RuntimeProps, shell: &mut Shell, processor: &mut IOProcessor) {
+    //Move to a new line if the prompt is being shown
+    if props.get_last_state() == ShellState::Shell {
+        console::println(String::new());
+    }
+    match props.reload_config(shell) {
+        Ok(warnings) => {
+            logger::info("runtime", "configuration reloaded");
+            *processor = new_processor(props.get_language(), &props.config);
+            terminal::set_assume_width(props.config.assume_width);
+            for warning in warnings {
+                console::println(Colour::Yellow.paint(format!("Configuration warning: {}", warning)).to_string());
+            }
+        }
+        Err(err) => print_localized_err("Could not reload configuration", &err, props.config.output_config.translate_stderr, processor),
     }
 }
 
 /// ### resolve_shell
 /// 
-/// Resolve shell to use from configuration and arguments
-fn resolve_shell(config: &config::Config, shellopt: Option<String>) -> (String, Vec<String>) {
-    match shellopt {
-        Some(sh) => (sh, vec![]),
-        None => (config.shell_config.exec.clone(), config.shell_config.args.clone()) //Get shell from config
+/// Resolve shell to use from arguments, configuration, the shell pyc has been started from and `SHELL` (see `lookup::select`).
+/// The arguments in configuration are passed to any shell but the one given on the command line.
+/// If `shell.norc` is set, the arguments which disable the rc files of the shell come first
+pub fn resolve_shell(config: &config::Config, shellopt: Option<String>) -> (String, Vec<String>) {
+    let args: Vec<String> = match shellopt {
+        Some(_) => vec![],
+        None => config.shell_config.args.clone()
+    };
+    let exec: String = lookup::select(shellopt, config.shell_config.exec.clone(), lookup::parent_shell(), env::var("SHELL").ok(), env::current_exe().ok());
+    match config.shell_config.norc {
+        true => {
+            let mut norc_args: Vec<String> = handshake::norc_args(exec.as_str());
+            norc_args.extend(args);
+            (exec, norc_args)
+        }
+        false => (exec, args)
+    }
+}
+
+/// ### shell_handshake
+///
+/// Wait for the shell to answer; what the shell printed at startup is printed.
+/// If the shell doesn't answer, the diagnostic is printed and the shell is stopped; returns whether the shell is ready
+fn shell_handshake(shell: &mut Shell, translation: config::OutputTranslation, processor: &IOProcessor) -> bool {
+    match shell.handshake(HANDSHAKE_TIMEOUT) {
+        Ok(output) => {
+            let output: &str = output.strip_suffix('\n').unwrap_or(output.as_str());
+            if !output.is_empty() {
+                let _ = print_out(String::from(output), translation.stdout, processor);
+            }
+            true
+        }
+        Err(err) => {
+            print_err(err.diagnostic(), translation.stderr, processor);
+            let _ = shell.stop();
+            false
+        }
     }
 }
 
@@ -269,63 +845,270 @@ fn script_lines_to_string(lines: &Vec<String>) -> String {
     command
 }
 
-/// ### resolve_command
+/// ### run_lines
 ///
-/// resolve command according to configured alias
+/// Transliterate and execute the script lines one at a time, waiting for each one to terminate.
+/// Comments and empty lines are skipped; returns the exit code of the first failed command (or of the last failed one
+/// if `keep_going` is set), 0 if all of them succeeded
+fn run_lines(shell: &mut Shell, lines: &[String], processor: &mut IOProcessor, config: &config::Config, trace_output: Option<&TraceOutput>, keep_going: bool) -> u8 {
+    let translation: config::OutputTranslation = config.output_config.translation();
+    let mut rc: u8 = 0;
+    for line in lines.iter().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let translated: Result<(String, Vec<TokenTrace>), ExpressionParserError> = match config.input_config.translation.command_line() {
+            true => processor.expression_to_latin_traced(&String::from(line)),
+            false => Ok((String::from(line), Vec::new())),
+        };
+        let (command, tokens): (String, Vec<TokenTrace>) = match translated {
+            Ok(translated) => translated,
+            Err(err) => {
+                print_expression_err(line, err, translation.stderr, processor);
+                rc = 255;
+                match keep_going {
+                    true => continue,
+                    false => break,
+                }
+            }
+        };
+        logger::debug("runtime", format!("running command: {}", command).as_str());
+        if let Some(trace_output) = trace_output {
+            let mut trace: TranslationTrace = TranslationTrace::new(String::from(line), DispatchMode::Oneshot);
+            trace.tokens.extend(tokens);
+            trace.set_output(&command);
+            write_trace(&trace, trace_output);
+        }
+        if config.output_config.echo_command {
+            console::println(echo_line(command.as_str()));
+        }
+        if shell.write(format!("{}\n", command)).is_err() {
+            break;
+        }
+        //Wait for the command to terminate
+        loop {
+            if let Err(err) = read_from_shell(shell, translation, processor, None, None) {
+                if err.kind() == io::ErrorKind::BrokenPipe {
+                    handle_broken_pipe(shell);
+                    return rc;
+                }
+            }
+            match shell.get_state() {
+                ShellState::Shell => break,
+                ShellState::Terminated => return rc,
+                _ => sleep(Duration::from_nanos(100)),
+            }
+        }
+        let exit_status: u8 = shell.get_exit_status();
+        if exit_status != 0 {
+            rc = exit_status;
+            if !keep_going {
+                break;
+            }
+        }
+    }
+    rc
+}
+
+/// ### dry_run
+///
+/// Print the commands read from the command line or from stdin as they would be sent to the shell, transliterated
+/// according to the input translation, without running them. Returns 255 if a command can't be translated, 0 otherwise
+pub fn dry_run(lines: &[String], language: Language, config: config::Config) -> u8 {
+    let processor: IOProcessor = new_processor(language, &config);
+    let mut rc: u8 = 0;
+    for (line, translated) in dry_run_lines(lines, &processor, &config) {
+        match translated {
+            Ok(command) => console::println(command),
+            Err(err) => {
+                print_expression_err(line.as_str(), err, config.output_config.translate_stderr, &processor);
+                rc = 255;
+            }
+        }
+    }
+    rc
+}
 
-fn resolve_command(argv: &mut Vec<String>, config: &config::Config) {
-    //Process arg 0
-    match config.get_alias(&argv[0]) {
-        Some(resolved) => argv[0] = resolved,
-        None => {}
+/// ### dry_run_lines
+///
+/// Translate the lines which would be run by `run_lines`; each line is returned along with its translation
+fn dry_run_lines(lines: &[String], processor: &IOProcessor, config: &config::Config) -> Vec<(String, Result<String, ExpressionParserError>)> {
+    lines
+        .iter()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|line| {
+            let translated: Result<String, ExpressionParserError> = match config.input_config.translation.command_line() {
+                true => processor.expression_to_latin(&String::from(line)),
+                false => Ok(String::from(line)),
+            };
+            (String::from(line), translated)
+        })
+        .collect()
+}
+
+/// ### echo_line
+///
+/// Returns the line which shows the command sent to the shell when `output.echo_command` is enabled (e.g. `➜ ls -l`), dimmed
+fn echo_line(command: &str) -> String {
+    Style::new().dimmed().paint(format!("➜ {}", command.trim_end().replace('\n', "\n  "))).to_string()
+}
+
+/// ### source_rc_file
+///
+/// Run the lines of the rc file, one at a time, through the same pipeline as the typed commands (builtins included).
+/// Errors are prefixed with the file and the line which caused them, but don't stop the following lines from being run.
+/// A missing rc file is not an error
+fn source_rc_file(rc_file: &Path, props: &mut RuntimeProps, shell: &mut Shell, processor: &mut IOProcessor) {
+    let lines: Vec<String> = match file::read_lines(rc_file) {
+        Ok(lines) => lines,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+        Err(err) => {
+            print_err(format!("Could not read '{}': {}", rc_file.display(), err), props.config.output_config.translate_stderr, processor);
+            return;
+        }
     };
+    logger::debug("runtime", format!("running rc file {}", rc_file.display()).as_str());
+    for (index, line) in lines.iter().enumerate() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        *ERROR_LOCATION.lock().unwrap() = Some(format!("{}: line {}", rc_file.display(), index + 1));
+        if props.source_input(String::from(line), shell).is_some() {
+            //Wait for the command to terminate
+            loop {
+                if let Err(err) = read_from_shell(shell, props.get_translate_output(), processor, None, None) {
+                    if err.kind() == io::ErrorKind::BrokenPipe {
+                        break;
+                    }
+                }
+                match shell.get_state() {
+                    ShellState::Shell | ShellState::Terminated => break,
+                    _ => sleep(Duration::from_nanos(100)),
+                }
+            }
+            let exit_status: u8 = shell.get_exit_status();
+            if exit_status != 0 {
+                print_err(format!("command exited with status {}", exit_status), props.config.output_config.translate_stderr, processor);
+            }
+        }
+        *ERROR_LOCATION.lock().unwrap() = None;
+        if shell.get_state() == ShellState::Terminated {
+            break;
+        }
+    }
 }
 
-/*
-/// ### get_shell_from_env
+/// ### resolve_command
 ///
-/// Try to get the shell path from SHELL environment variable
-fn get_shell_from_env() -> Result<String, ()> {
-    if let Ok(val) = env::var("SHELL") {
-        Ok(val)
-    } else {
-        Err(())
+/// resolve the commands of argv (every command of a list, e.g. `лл && гс`) according to the aliases defined at runtime and to the configured ones.
+/// Returns the amount of leading arguments which come from the alias of the first command
+fn resolve_command(argv: &mut Vec<String>, config: &config::Config, aliases: &HashMap<String, String>) -> Result<usize, AliasError> {
+    alias::expand_list(argv, &|name: &str| match aliases.get(name) {
+        Some(cmd) => Some(cmd.clone()),
+        None => config.get_alias(&String::from(name)),
+    })
+}
+
+/// ### get_command_name
+///
+/// Get the name of the program executed by a command line (e.g. `/usr/bin/man ls` => `man`).
+/// Leading environment variable assignments are skipped
+fn get_command_name(command: &str) -> Option<String> {
+    let program: &str = command.split_whitespace().find(|arg| !arg.contains('='))?;
+    match Path::new(program).file_name() {
+        Some(name) => Some(String::from(name.to_string_lossy())),
+        None => Some(String::from(program)),
     }
 }
-*/
+
+/// ### new_processor
+///
+/// Instantiates the IOProcessor for the provided language, with the output filters and the input preserve rules set in configuration
+fn new_processor(language: Language, config: &config::Config) -> IOProcessor {
+    let options: TranslatorOptions = TranslatorOptions {
+        strict_boundaries: config.input_config.strict_boundaries,
+        standard: TransliterationStandard::from_name(&config.language_standard).unwrap_or_default(),
+    };
+    let mut processor: IOProcessor = IOProcessor::new(language, new_translator_with_options(language, options));
+    processor.set_preserve_rules(config.input_config.preserve_redirect_targets, config.input_config.preserve_output_args.clone());
+    if config.language_strict {
+        processor.set_translation_mode(TranslationMode::Strict);
+    }
+    if let Some((open, close)) = &config.input_config.literal_delimiters {
+        processor.set_literal_delimiters(open, close);
+    }
+    processor.set_no_translate_patterns(&config.output_config.no_translate_patterns);
+    if config.output_config.localize_units {
+        processor.add_output_filter(Box::new(UnitsFilter::new(language, &config.output_config.localize_dictionary)));
+    }
+    processor
+}
 
 //@! Prompt functions
 
 /// ### print_err
 /// 
-/// print error message; the message is may converted to cyrillic if translate config is true
-
+/// print error message; the message is may converted to cyrillic if translate config is true.
+/// The error is logged as it is, before being converted
 fn print_err(err: String, to_cyrillic: bool, processor: &IOProcessor) {
-    match to_cyrillic {
-        true => eprintln!("{}", Colour::Red.paint(processor.text_to_cyrillic(&err))),
-        false => eprintln!("{}", Colour::Red.paint(err)),
+    let err: String = match ERROR_LOCATION.lock().unwrap().as_ref() {
+        Some(location) => format!("{}: {}", location, err),
+        None => err,
     };
+    logger::error("runtime", err.as_str());
+    output::print_err(err, to_cyrillic, processor);
 }
 
-/// ### print_out
+/// ### print_localized_err
 ///
-/// print normal message; the message is may converted to cyrillic if translate config is true
-
-fn print_out(out: String, to_cyrillic: bool, processor: &IOProcessor) {
-    match to_cyrillic {
-        true => console::println(format!("{}", processor.text_to_cyrillic(&out))),
-        false => console::println(format!("{}", out)),
+/// print an error preceded by what failed (e.g. `Could not start shell`); if translate config is true the error is rendered in cyrillic,
+/// keeping the values it reports as they are
+fn print_localized_err(context: &str, err: &dyn LocalizedError, to_cyrillic: bool, processor: &IOProcessor) {
+    let err: String = match to_cyrillic {
+        true => format!("{}: {}", processor.message_to_cyrillic(context), processor.localize_error(err)),
+        false => format!("{}: {}", context, err),
     };
+    print_err(err, false, processor);
+}
+
+/// ### print_expression_err
+/// 
+/// print an error occurred while parsing an expression, followed by the line of the expression which contains the error
+/// and by a caret under the character which caused it. The expression is printed as it has been typed
+fn print_expression_err(expression: &str, err: ExpressionParserError, to_cyrillic: bool, processor: &IOProcessor) {
+    print_localized_err("Input error", &err, to_cyrillic, processor);
+    let _ = console::write(&mut io::stderr(), format!("{}\n", expression_caret(expression, err.position())).as_str());
 }
 
-/// ### console_fmt
+/// ### expression_caret
 /// 
-/// Format console message
+/// Returns the line of the expression which contains the character at `position` and, below it, a caret under that character
+fn expression_caret(expression: &str, position: usize) -> String {
+    let before: Vec<char> = expression.chars().take(position).collect();
+    let line_start: usize = before.iter().rposition(|c| *c == '\n').map(|i| i + 1).unwrap_or(0);
+    let line: String = expression.chars().skip(line_start).take_while(|c| *c != '\n').collect();
+    //Tabs are kept, so that the caret is aligned however they're rendered
+    let padding: String = before[line_start..].iter().map(|c| if *c == '\t' { '\t' } else { ' ' }).collect();
+    format!("{}\n{}^", line, padding)
+}
 
-fn console_fmt(out: String, to_cyrillic: bool, processor: &IOProcessor) -> String {
-    match to_cyrillic {
-        true => format!("{}", processor.text_to_cyrillic(&out)),
-        false => format!("{}", out)
+/// ### write_trace
+///
+/// Write translation trace as a JSON line to the trace output; the trace file and its directory are created on the first write
+fn write_trace(trace: &TranslationTrace, output: &TraceOutput) {
+    match output {
+        TraceOutput::Stderr => {
+            let _ = console::write(&mut io::stderr(), format!("{}\n", trace.to_json()).as_str());
+        }
+        TraceOutput::File(path) => {
+            if let Some(dir) = path.parent() {
+                if !dir.as_os_str().is_empty() && !dir.exists() {
+                    let _ = DirBuilder::new().recursive(true).create(dir);
+                }
+            }
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = writeln!(file, "{}", trace.to_json());
+            }
+        }
     }
 }
 
@@ -336,7 +1119,8 @@ fn console_fmt(out: String, to_cyrillic: bool, processor: &IOProcessor) -> Strin
 fn shellsignal_to_signal(sig: u8) -> Option<UnixSignal> {
     match sig {
         3 => Some(UnixSignal::Sigint),
-        26 => Some(UnixSignal::Sigstop),
+        26 => Some(UnixSignal::Sigtstp),
+        28 => Some(UnixSignal::Sigquit),
         _ => None
     }
 }
@@ -345,7 +1129,7 @@ fn shellsignal_to_signal(sig: u8) -> Option<UnixSignal> {
 mod tests {
     use super::*;
 
-    use crate::config::Config;
+    use crate::config::{Config, InputTranslation, OutputTranslation};
 
     use crate::translator::ioprocessor::IOProcessor;
     use crate::translator::new_translator;
@@ -358,30 +1142,31 @@ mod tests {
     #[test]
     fn test_runtime_read_from_shell() {
         let mut cfg: Config = Config::default();
-        cfg.output_config.translate_output = true;
-        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        cfg.output_config.translate_stdout = true;
+        cfg.output_config.translate_stderr = true;
+        let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
         let mut shell: Shell = Shell::start(String::from("sh"), vec![], &cfg.prompt_config).unwrap();
         sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
         //Write
         let _ = shell.write(String::from("echo 4\n"));
         sleep(Duration::from_millis(100));
         //Read
-        read_from_shell(&mut shell, &cfg, &iop);
-        //Don't translate
-        cfg.output_config.translate_output = false;
+        assert!(read_from_shell(&mut shell, cfg.output_config.translation(), &mut iop, None, None).is_ok());
+        //Don't translate stdout
+        cfg.output_config.translate_stdout = false;
         let _ = shell.write(String::from("echo 5\n"));
         sleep(Duration::from_millis(100));
-        read_from_shell(&mut shell, &cfg, &iop);
+        assert!(read_from_shell(&mut shell, cfg.output_config.translation(), &mut iop, None, None).is_ok());
         //Try stderr
-        cfg.output_config.translate_output = true;
+        cfg.output_config.translate_stderr = true;
         let _ = shell.write(String::from("poropero\n"));
         sleep(Duration::from_millis(100));
-        read_from_shell(&mut shell, &cfg, &iop);
+        assert!(read_from_shell(&mut shell, cfg.output_config.translation(), &mut iop, None, None).is_ok());
         //Try stderr not translated
-        cfg.output_config.translate_output = false;
+        cfg.output_config.translate_stderr = false;
         let _ = shell.write(String::from("poropero\n"));
         sleep(Duration::from_millis(100));
-        read_from_shell(&mut shell, &cfg, &iop);
+        assert!(read_from_shell(&mut shell, cfg.output_config.translation(), &mut iop, None, None).is_ok());
         //Terminate shell
         sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
         assert!(shell.stop().is_ok());
@@ -391,11 +1176,89 @@ mod tests {
     #[test]
     fn test_runtime_resolve_shell() {
         let mut cfg: Config = Config::default();
+        cfg.shell_config.exec = Some(String::from("bash"));
         cfg.shell_config.args = vec![String::from("-i")];
         //Resolve shell without cli option
         assert_eq!(resolve_shell(&cfg, None), (String::from("bash"), vec![String::from("-i")]));
         //Resolve shell with cli option
         assert_eq!(resolve_shell(&cfg, Some(String::from("fish"))), (String::from("fish"), vec![]));
+        //Norc arguments come first
+        cfg.shell_config.norc = true;
+        assert_eq!(resolve_shell(&cfg, None), (String::from("bash"), vec![String::from("--norc"), String::from("--noprofile"), String::from("-i")]));
+        assert_eq!(resolve_shell(&cfg, Some(String::from("fish"))), (String::from("fish"), vec![String::from("--no-config")]));
+    }
+
+    #[test]
+    fn test_runtime_shell_handshake_norc() {
+        use std::os::unix::fs::PermissionsExt;
+        //Fake bash whose rc file clears the screen and exits, unless started with --norc
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let exec: PathBuf = tmpdir.path().join("bash");
+        std::fs::write(&exec, "#!/bin/sh\nif [ \"$1\" = \"--norc\" ]; then exec sh; fi\necho 'Welcome!'\nprintf '\\033[H\\033[2J'\nexit 1\n").unwrap();
+        std::fs::set_permissions(&exec, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let mut cfg: Config = Config::default();
+        cfg.shell_config.exec = Some(String::from(exec.to_str().unwrap()));
+        //Without norc the handshake fails
+        let (shell, args): (String, Vec<String>) = resolve_shell(&cfg, None);
+        let mut shell: Shell = Shell::start(shell, args, &cfg.prompt_config).unwrap();
+        assert!(!shell_handshake(&mut shell, OutputTranslation { stdout: false, stderr: false }, &iop));
+        assert_eq!(shell.get_state(), ShellState::Terminated);
+        //With norc the shell answers
+        cfg.shell_config.norc = true;
+        let (shell, args): (String, Vec<String>) = resolve_shell(&cfg, None);
+        let mut shell: Shell = Shell::start(shell, args, &cfg.prompt_config).unwrap();
+        assert!(shell_handshake(&mut shell, OutputTranslation { stdout: false, stderr: false }, &iop));
+        assert_eq!(shell.get_state(), ShellState::Shell);
+        assert!(shell.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtime_prompt_line() {
+        let mut cfg: Config = Config::default();
+        cfg.prompt_config.prompt_line = String::from("${KRED}${RC_CODE}${KRST} ${WRKDIR}\\");
+        cfg.prompt_config.break_enabled = true;
+        cfg.prompt_config.break_str = String::from(">");
+        let iop: IOProcessor = new_processor(Language::Russian, &cfg);
+        assert_eq!(prompt_line(&cfg, 1, Duration::from_millis(0), PathBuf::from("/tmp"), false, &iop), String::from("\x1b[31m1\x1b[0m /tmp\\ >"));
+        assert_eq!(
+            prompt_line(&cfg, 1, Duration::from_millis(0), PathBuf::from("/tmp"), true, &iop),
+            String::from("\\[\x1b[31m\\]1\\[\x1b[0m\\] /tmp\\\\\\n>")
+        );
+    }
+
+    #[test]
+    fn test_runtime_merge_history() {
+        let entries = |e: &[&str]| e.iter().map(|e| String::from(*e)).collect::<Vec<String>>();
+        //Entries written by another session are kept before the ones of this session
+        assert_eq!(merge_history(entries(&["a", "b", "x"]), entries(&["a", "b", "y"]), 8), entries(&["x", "a", "b", "y"]));
+        //Oldest entries are discarded
+        assert_eq!(merge_history(entries(&["a", "b", "c"]), entries(&["b", "c", "d"]), 3), entries(&["b", "c", "d"]));
+        assert_eq!(merge_history(vec![], entries(&["a"]), 3), entries(&["a"]));
+    }
+
+    #[test]
+    fn test_runtime_history_quarantine() {
+        let cfg: Config = Config::default();
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let history_file: PathBuf = tmpdir.path().join("pyc_history");
+        std::fs::write(history_file.as_path(), "экхо 1\nэкх").unwrap();
+        let mut shell: Shell = Shell::start(String::from("sh"), vec![], &cfg.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Truncated file is moved aside and history starts empty
+        let warning: String = load_history(&mut shell, history_file.as_path()).unwrap().unwrap();
+        assert!(warning.contains("is truncated"));
+        assert!(warning.contains("pyc_history.corrupt"));
+        assert_eq!(shell.history.len(), 0);
+        assert_eq!(std::fs::read_to_string(tmpdir.path().join("pyc_history.corrupt")).unwrap(), String::from("экхо 1\nэкх"));
+        //History is saved to a new file
+        shell.history.push(String::from("экхо 2"));
+        assert!(save_history(&mut shell, history_file.as_path()).is_ok());
+        assert!(load_history(&mut shell, history_file.as_path()).unwrap().is_none());
+        assert_eq!(shell.history.dump(), vec![String::from("экхо 2")]);
+        assert!(shell.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
     }
 
     #[test]
@@ -404,51 +1267,448 @@ mod tests {
         assert_eq!(script_lines_to_string(&lines), String::from("echo 4;cat /tmp/output;"));
     }
 
+    #[test]
+    fn test_runtime_run_lines() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let dir: &str = tmpdir.path().to_str().unwrap();
+        let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let script: Vec<String> = vec![
+            String::from("#!/usr/bin/pyc"),
+            format!("тоуч {}/а", dir),
+            String::from(""),
+            String::from("фалсе"),
+            format!("тоуч {}/б", dir),
+        ];
+        let mut cfg: Config = Config::default();
+        cfg.output_config.translate_stdout = false;
+        cfg.output_config.translate_stderr = false;
+        //Stop at the first failure
+        let mut shell: Shell = Shell::start(String::from("sh"), vec![], &cfg.prompt_config).unwrap();
+        assert!(shell_handshake(&mut shell, OutputTranslation { stdout: false, stderr: false }, &iop));
+        assert_eq!(run_lines(&mut shell, &script, &mut iop, &cfg, None, false), 1);
+        assert!(tmpdir.path().join("a").exists());
+        assert!(!tmpdir.path().join("b").exists());
+        //Commands after the failure are executed with keep going
+        assert_eq!(run_lines(&mut shell, &script, &mut iop, &cfg, None, true), 1);
+        assert!(tmpdir.path().join("b").exists());
+        //All commands succeeded
+        assert_eq!(run_lines(&mut shell, &script[..3], &mut iop, &cfg, None, false), 0);
+        //Command lists short-circuit on the exit status of each command, cd included
+        let lists: Vec<String> = vec![
+            format!("кд {}/нонэксистент && тоуч {}/ц", dir, dir),
+            format!("фалсе || тоуч {}/д", dir),
+            format!("труе && фалсе && тоуч {}/е || тоуч {}/ф", dir, dir),
+            format!("мкъдир {}/суб && кд {}/суб ; тоуч {}/суб/г ;", dir, dir, dir),
+            format!("экхо ' && тоуч {}/х'", dir),
+        ];
+        assert_eq!(run_lines(&mut shell, &lists, &mut iop, &cfg, None, true), 2);
+        assert!(!tmpdir.path().join("z").exists());
+        assert!(tmpdir.path().join("d").exists());
+        assert!(!tmpdir.path().join("e").exists());
+        assert!(tmpdir.path().join("f").exists());
+        assert!(tmpdir.path().join("sub/g").exists());
+        assert!(!tmpdir.path().join("h").exists());
+        //The last command executed gives the exit status
+        assert_eq!(run_lines(&mut shell, &lists[1..], &mut iop, &cfg, None, false), 0);
+        //Input translation disabled: lines are sent as they are
+        let latin: Vec<String> = vec![format!("touch {}/ж", dir)];
+        cfg.input_config.translation = InputTranslation::Never;
+        assert_eq!(run_lines(&mut shell, &latin, &mut iop, &cfg, None, false), 0);
+        assert!(tmpdir.path().join("ж").exists());
+        assert!(!tmpdir.path().join("zh").exists());
+        //Commands are echoed
+        cfg.input_config.translation = InputTranslation::Always;
+        cfg.output_config.echo_command = true;
+        assert_eq!(run_lines(&mut shell, &[format!("тоуч {}/з", dir)], &mut iop, &cfg, None, false), 0);
+        assert!(tmpdir.path().join("z").exists());
+        assert!(shell.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtime_run_expressions() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let dir: &str = tmpdir.path().to_str().unwrap();
+        let mut cfg: Config = Config::default();
+        cfg.output_config.translate_stdout = false;
+        cfg.output_config.translate_stderr = false;
+        let expressions: Vec<String> = vec![
+            format!("экхо \"а | б\" | кат > {}/out", dir),
+            format!("экхо 'x; y' \\'z\\' \"$((1 + 2)) \\\"ц\\\"\" >> {}/out", dir),
+            format!("тест -ф {}/out || экхо нот фоунд > {}/b", dir, dir),
+        ];
+        assert_eq!(run_expressions(&expressions, Language::Russian, cfg.clone(), Some(String::from("sh")), None, false), 0);
+        assert_eq!(std::fs::read_to_string(tmpdir.path().join("out")).unwrap(), String::from("а | б\nx; y 'z' 3 \"ц\"\n"));
+        assert!(!tmpdir.path().join("b").exists());
+        //Exit status of the failed expression
+        let expressions: Vec<String> = vec![String::from("(exit 3)"), format!("тоуч {}/ц", dir)];
+        assert_eq!(run_expressions(&expressions, Language::Russian, cfg.clone(), Some(String::from("sh")), None, false), 3);
+        assert!(!tmpdir.path().join("z").exists());
+        //Unterminated quote
+        let expressions: Vec<String> = vec![String::from("экхо \"а")];
+        assert_eq!(run_expressions(&expressions, Language::Russian, cfg, Some(String::from("sh")), None, false), 255);
+    }
+
+    #[test]
+    fn test_runtime_run_command_timeout() {
+        let mut cfg: Config = Config::default();
+        cfg.core_config.command_timeout = 1;
+        cfg.core_config.sigint_escalation.kill_after_ms = 500;
+        //The command which runs out of time is terminated and exits with 124
+        let started: Instant = Instant::now();
+        assert_eq!(run_command(String::from("sleep 5"), Language::Russian, cfg.clone(), Some(String::from("sh")), None), 124);
+        assert!(started.elapsed() < Duration::from_secs(4));
+        //A fast command is unaffected
+        assert_eq!(run_command(String::from("true"), Language::Russian, cfg.clone(), Some(String::from("sh")), None), 0);
+        assert_eq!(run_command(String::from("exit 3"), Language::Russian, cfg, Some(String::from("sh")), None), 3);
+    }
+
+    #[test]
+    fn test_runtime_run_command_trace() {
+        let trace_file: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let trace_output: TraceOutput = TraceOutput::File(trace_file.path().to_path_buf());
+        assert_eq!(run_command(String::from("true;\n"), Language::Russian, Config::default(), Some(String::from("sh")), Some(trace_output)), 0);
+        //The command is not translated
+        let trace: String = std::fs::read_to_string(trace_file.path()).unwrap();
+        assert!(trace.contains("\"decision\":\"unchanged\""));
+        assert!(!trace.contains("\"decision\":\"translated\""));
+    }
+
+    #[test]
+    fn test_runtime_echo_line() {
+        assert_eq!(echo_line("ls -l\n"), String::from("\x1b[2m➜ ls -l\x1b[0m"));
+        //Continuation lines are indented
+        assert_eq!(echo_line("echo a \\\nb"), String::from("\x1b[2m➜ echo a \\\n  b\x1b[0m"));
+        //Echoed commands are the translated ones
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let echo = |input: &str| echo_line(iop.expression_to_latin(&String::from(input)).unwrap().as_str());
+        assert_eq!(echo("лс -ла"), String::from("\x1b[2m➜ ls -la\x1b[0m"));
+        assert_eq!(echo("кд /тмп && гит статус"), String::from("\x1b[2m➜ cd /tmp && git status\x1b[0m"));
+        assert_eq!(echo("экхо \"привет\""), String::from("\x1b[2m➜ echo \"привет\"\x1b[0m"));
+    }
+
+    #[test]
+    fn test_runtime_dry_run() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let dir: &str = tmpdir.path().to_str().unwrap();
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let mut cfg: Config = Config::default();
+        let lines: Vec<String> = vec![format!("тоуч {}/а", dir), String::from(""), String::from("# comment"), String::from("экхо )")];
+        let translated: Vec<(String, Result<String, ExpressionParserError>)> = dry_run_lines(&lines, &iop, &cfg);
+        assert_eq!(translated.len(), 2);
+        assert_eq!(translated[0], (format!("тоуч {}/а", dir), Ok(format!("touch {}/a", dir))));
+        assert_eq!(translated[1], (String::from("экхо )"), Err(ExpressionParserError::UnexpectedToken(5, ')'))));
+        //Input translation disabled
+        cfg.input_config.translation = InputTranslation::Never;
+        assert_eq!(dry_run_lines(&lines[..1], &iop, &cfg)[0].1, Ok(format!("тоуч {}/а", dir)));
+        //Nothing is executed
+        cfg.input_config.translation = InputTranslation::Always;
+        assert_eq!(dry_run(&lines[..1], Language::Russian, cfg.clone()), 0);
+        assert_eq!(dry_run(&lines, Language::Russian, cfg), 255);
+        assert!(!tmpdir.path().join("a").exists());
+        assert!(!tmpdir.path().join("а").exists());
+    }
+
+    #[test]
+    fn test_runtime_source_rc_file() {
+        let rc_file: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            rc_file.path(),
+            "# aliases\nalias лл='ls -l'\n\nexport PYC_RC_TEST=привет\necho 'unterminated\nfalse\nalias гс='git status'\n",
+        ).unwrap();
+        let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let mut props: RuntimeProps = RuntimeProps::new(true, Config::default(), Language::Russian, None);
+        let mut shell: Shell = Shell::start(String::from("sh"), vec![], &props.config.prompt_config).unwrap();
+        assert!(shell_handshake(&mut shell, OutputTranslation { stdout: false, stderr: false }, &iop));
+        source_rc_file(rc_file.path(), &mut props, &mut shell, &mut iop);
+        assert_eq!(shell.aliases.get("лл"), Some(&String::from("ls -l")));
+        assert_eq!(std::env::var("PYC_RC_TEST").ok(), Some(String::from("привет")));
+        //Errors don't stop the following lines
+        assert_eq!(shell.aliases.get("гс"), Some(&String::from("git status")));
+        assert!(ERROR_LOCATION.lock().unwrap().is_none());
+        //Lines are not pushed to history
+        assert_eq!(shell.history.len(), 0);
+        //Missing rc file is ignored
+        source_rc_file(Path::new("/tmp/pyc-no-such-rc-file"), &mut props, &mut shell, &mut iop);
+        assert!(shell.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
     #[test]
     fn test_runtime_resolve_command() {
         let mut alias_cfg: HashMap<String, String> = HashMap::new();
         alias_cfg.insert(String::from("ll"), String::from("ls -l"));
         let cfg: Config = Config {
-            language: String::from(""),
+            language: None,
+            language_standard: String::from("gost"),
+            language_strict: false,
+            banner: true,
+            assume_width: None,
+            core_config: config::CoreConfig::default(),
             shell_config: config::ShellConfig::default(),
+            terminal_config: config::TerminalConfig::default(),
             alias: alias_cfg,
+            alias_options: HashMap::new(),
             output_config: config::OutputConfig::default(),
-            prompt_config: config::PromptConfig::default()
+            input_config: config::InputConfig::default(),
+            prompt_config: config::PromptConfig::default(),
+            unknown_keys: Vec::new(),
+            overrides: config::ConfigOverrides::default()
         };
         //Resolve command
         let mut argv: Vec<String> = vec![String::from("ll"), String::from("/tmp/")];
-        resolve_command(&mut argv, &cfg);
-        assert_eq!(*argv.get(0).unwrap(), String::from("ls -l"));
+        assert_eq!(resolve_command(&mut argv, &cfg, &HashMap::new()).unwrap(), 2);
+        assert_eq!(argv, vec![String::from("ls"), String::from("-l"), String::from("/tmp/")]);
+        //Runtime aliases are resolved first and recursively
+        let mut runtime_alias: HashMap<String, String> = HashMap::new();
+        runtime_alias.insert(String::from("ll"), String::from("ls -la"));
+        runtime_alias.insert(String::from("лл"), String::from("ll -h"));
+        let mut argv: Vec<String> = vec![String::from("лл"), String::from("/tmp/")];
+        assert_eq!(resolve_command(&mut argv, &cfg, &runtime_alias).unwrap(), 3);
+        assert_eq!(argv, vec![String::from("ls"), String::from("-la"), String::from("-h"), String::from("/tmp/")]);
+        //Cycle
+        runtime_alias.insert(String::from("ls"), String::from("лл"));
+        let mut argv: Vec<String> = vec![String::from("лл")];
+        assert!(resolve_command(&mut argv, &cfg, &runtime_alias).is_err());
 
         //Unresolved command
         let mut argv: Vec<String> = vec![String::from("du"), String::from("-hs")];
-        resolve_command(&mut argv, &cfg);
+        assert_eq!(resolve_command(&mut argv, &cfg, &HashMap::new()).unwrap(), 0);
         assert_eq!(*argv.get(0).unwrap(), String::from("du"));
+        //Every command of a list is resolved
+        let mut argv: Vec<String> = vec![String::from("du"), String::from("&&"), String::from("ll")];
+        assert_eq!(resolve_command(&mut argv, &cfg, &HashMap::new()).unwrap(), 0);
+        assert_eq!(argv, vec![String::from("du"), String::from("&&"), String::from("ls"), String::from("-l")]);
+    }
+
+    #[test]
+    fn test_runtime_history_persistence() {
+        let mut cfg: Config = Config::default();
+        cfg.prompt_config.history_size = 3;
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let history_file: PathBuf = tmpdir.path().join("pyc/pyc_history");
+        //Load from a file which doesn't exist; its directory is created when history is saved
+        let mut shell: Shell = Shell::start(String::from("sh"), vec![], &cfg.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(load_history(&mut shell, history_file.as_path()).is_err());
+        //Type commands
+        let mut props: RuntimeProps = RuntimeProps::new(true, cfg.clone(), Language::Russian, None);
+        for command in ["экхо 1", "экхо 2", "экхо 3", "экхо 4"].iter() {
+            props.handle_input_event(console::InputEvent::Key(String::from(*command)), &mut shell);
+            props.handle_input_event(console::InputEvent::Enter, &mut shell);
+            sleep(Duration::from_millis(100));
+        }
+        //History contains the original input, truncated to history size
+        assert_eq!(shell.history.len(), 3);
+        assert_eq!(shell.history.at(0).unwrap(), String::from("экхо 4"));
+        assert!(save_history(&mut shell, history_file.as_path()).is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(shell.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Restart
+        let mut shell: Shell = Shell::start(String::from("sh"), vec![], &cfg.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(load_history(&mut shell, history_file.as_path()).is_ok());
+        assert_eq!(shell.history.dump(), vec![String::from("экхо 2"), String::from("экхо 3"), String::from("экхо 4")]);
+        //Re-execute first entry
+        let mut props: RuntimeProps = RuntimeProps::new(true, cfg.clone(), Language::Russian, None);
+        props.handle_input_event(console::InputEvent::Key(String::from("!0")), &mut shell);
+        props.handle_input_event(console::InputEvent::Enter, &mut shell);
+        assert_eq!(shell.history.dump(), vec![String::from("экхо 3"), String::from("экхо 4"), String::from("экхо 2")]);
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(shell.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Restart with a smaller history: oldest entries are discarded
+        cfg.prompt_config.history_size = 2;
+        let mut shell: Shell = Shell::start(String::from("sh"), vec![], &cfg.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(load_history(&mut shell, history_file.as_path()).is_ok());
+        assert_eq!(shell.history.dump(), vec![String::from("экхо 3"), String::from("экхо 4")]);
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(shell.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtime_session() {
+        let cfg: Config = Config::default();
+        let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let socket: PathBuf = tmpdir.path().join("work.sock");
+        let mut session: SessionServer = SessionServer::bind_at(socket.as_path()).unwrap();
+        let mut shell: Shell = Shell::start(String::from("sh"), vec![], &cfg.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let mut props: RuntimeProps = RuntimeProps::new(true, cfg.clone(), Language::Russian, None);
+        //Send commands from another "terminal"
+        let client_socket: PathBuf = socket.clone();
+        let client = std::thread::spawn(move || {
+            let mut cd_out: Vec<u8> = Vec::new();
+            let cd_rc: u8 = session::send(client_socket.as_path(), "кд /tmp", &mut cd_out).unwrap();
+            let mut echo_out: Vec<u8> = Vec::new();
+            let echo_rc: u8 = session::send(client_socket.as_path(), "экхо привет", &mut echo_out).unwrap();
+            let mut false_out: Vec<u8> = Vec::new();
+            let false_rc: u8 = session::send(client_socket.as_path(), "фалсе", &mut false_out).unwrap();
+            (cd_rc, echo_rc, String::from_utf8(echo_out).unwrap(), false_rc)
+        });
+        //Serve the session until the client has done
+        let t_start: std::time::Instant = std::time::Instant::now();
+        while !client.is_finished() && t_start.elapsed() < Duration::from_secs(10) {
+            assert!(read_from_shell(&mut shell, OutputTranslation { stdout: true, stderr: true }, &mut iop, Some(&mut session), None).is_ok());
+            serve_session(&mut session, &mut props, &mut shell);
+            sleep(Duration::from_millis(10));
+        }
+        let (cd_rc, echo_rc, echo_out, false_rc): (u8, u8, String, u8) = client.join().unwrap();
+        assert_eq!(cd_rc, 0);
+        assert_eq!(echo_rc, 0);
+        assert_eq!(echo_out, String::from("привет\n"));
+        assert_eq!(false_rc, 1);
+        //Session state has been updated
+        assert_eq!(shell.get_wrkdir(), PathBuf::from("/tmp"));
+        assert_eq!(shell.history.dump(), vec![String::from("кд /tmp"), String::from("экхо привет"), String::from("фалсе")]);
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(shell.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtime_probe_capabilities() {
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        //Writable
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let mut history_file: Option<PathBuf> = Some(tmpdir.path().join("pyc_history"));
+        let mut trace_output: Option<TraceOutput> = Some(TraceOutput::Stderr);
+        let capabilities: Capabilities = probe_capabilities(&mut history_file, &mut trace_output, true, &iop);
+        assert!(!capabilities.is_degraded());
+        assert!(history_file.is_some());
+        assert!(trace_output.is_some());
+        //Config directory is a file: not writable, even by root
+        let config_dir: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let mut history_file: Option<PathBuf> = Some(config_dir.path().join("pyc_history"));
+        let mut trace_output: Option<TraceOutput> = Some(TraceOutput::File(config_dir.path().join("trace.json")));
+        let capabilities: Capabilities = probe_capabilities(&mut history_file, &mut trace_output, true, &iop);
+        assert!(capabilities.is_degraded());
+        assert_eq!(capabilities.disabled_features(), vec![String::from("history"), String::from("trace")]);
+        assert!(history_file.is_none());
+        assert!(trace_output.is_none());
+        //Commands are still executed normally
+        let cfg: Config = Config::default();
+        let mut shell: Shell = Shell::start(String::from("sh"), vec![], &cfg.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        shell.set_degraded(capabilities.disabled_features());
+        let mut props: RuntimeProps = RuntimeProps::new(true, cfg.clone(), Language::Russian, trace_output);
+        props.handle_input_event(console::InputEvent::Key(String::from("экхо привет")), &mut shell);
+        props.handle_input_event(console::InputEvent::Enter, &mut shell);
+        sleep(Duration::from_millis(500));
+        assert_eq!(shell.read().ok().unwrap().0, Some(String::from("privet\n")));
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(shell.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtime_get_command_name() {
+        assert_eq!(get_command_name("man ls"), Some(String::from("man")));
+        assert_eq!(get_command_name("  /usr/bin/man ls\n"), Some(String::from("man")));
+        assert_eq!(get_command_name("LANG=C man ls"), Some(String::from("man")));
+        assert_eq!(get_command_name("ls --color=auto"), Some(String::from("ls")));
+        assert_eq!(get_command_name("  \n"), None);
     }
 
     #[test]
     fn test_runtime_print() {
         let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
         //Out
-        print_out(String::from("Hello"), true, &iop);
-        print_out(String::from("Hello"), false, &iop);
+        assert!(print_out(String::from("Hello"), true, &iop).is_ok());
+        assert!(print_out(String::from("Hello"), false, &iop).is_ok());
         //Err
         print_err(String::from("Hello"), true, &iop);
         print_err(String::from("Hello"), false, &iop);
     }
 
     #[test]
-    fn test_runtime_console_fmt() {
+    fn test_runtime_handle_broken_pipe() {
+        let cfg: Config = Config::default();
+        let mut shell: Shell = Shell::start(String::from("sh"), vec![], &cfg.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Start a process with a long output, which prints its pid first
+        let _ = shell.write(String::from("sh -c 'echo $$; exec yes'\n"));
+        sleep(Duration::from_millis(100));
+        let (out, _) = shell.read().unwrap();
+        let out: String = out.unwrap();
+        let pid: String = String::from(out.lines().next().unwrap());
+        //Stdout is gone
+        handle_broken_pipe(&mut shell);
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Shell must have been terminated by SIGPIPE
+        assert_eq!(shell.get_state(), ShellState::Terminated);
+        assert_eq!(shell.stop().unwrap(), 141);
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Child must have terminated too (or it's a zombie waiting to be reaped)
+        if let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            assert!(stat.contains(") Z "));
+        }
+    }
+
+    #[test]
+    fn test_runtime_write_trace() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let trace_file: PathBuf = tmpdir.path().join("pyc/trace.json");
+        let mut trace: TranslationTrace = TranslationTrace::new(String::from("лс"), DispatchMode::Interactive);
+        trace.tokens.push(TokenTrace::translated(String::from("лс"), String::from("ls")));
+        trace.set_output("ls");
+        write_trace(&trace, &TraceOutput::Stderr);
+        //Traces are appended to file, which is created with its directory
+        write_trace(&trace, &TraceOutput::File(trace_file.clone()));
+        write_trace(&trace, &TraceOutput::File(trace_file.clone()));
+        let lines: Vec<String> = file::read_lines(trace_file).unwrap();
+        assert_eq!(lines, vec![trace.to_json(), trace.to_json()]);
+    }
+
+    #[test]
+    fn test_runtime_expression_caret() {
+        assert_eq!(expression_caret("экхо \"привет", 5), String::from("экхо \"привет\n     ^"));
+        assert_eq!(expression_caret("ls\n\tцат $(хостнамэ\n", 9), String::from("\tцат $(хостнамэ\n\t     ^"));
+        assert_eq!(expression_caret("", 0), String::from("\n^"));
+        //Print error
         let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
-        //Out
-        assert_eq!(console_fmt(String::from("Hello"), true, &iop), String::from("Хелло"));
-        assert_eq!(console_fmt(String::from("Hello"), false, &iop), String::from("Hello"));
+        print_expression_err("экхо )", iop.expression_to_latin(&String::from("экхо )")).err().unwrap(), true, &iop);
+    }
+
+    #[test]
+    fn test_runtime_is_password_prompt() {
+        assert!(is_password_prompt("Password:"));
+        assert!(is_password_prompt("[sudo] password for user: "));
+        assert!(is_password_prompt("Connecting...\nEnter passphrase for key '/home/user/.ssh/id_rsa':"));
+        assert!(is_password_prompt("Пароль: "));
+        assert!(!is_password_prompt("Password: changed\n"));
+        assert!(!is_password_prompt("password"));
+        assert!(!is_password_prompt("Name: "));
+        assert!(!is_password_prompt(""));
+    }
+
+    #[test]
+    fn test_runtime_stream_output() {
+        let mut cfg: Config = Config::default();
+        //Units are not localized by default
+        let mut iop: IOProcessor = new_processor(Language::Russian, &cfg);
+        assert_eq!(stream_output(Some(String::from("4G Jan\n")), OutputStream::Stdout, true, false, &mut iop), iop.text_to_cyrillic(&String::from("4G Jan\n")));
+        cfg.output_config.localize_units = true;
+        let mut iop: IOProcessor = new_processor(Language::Russian, &cfg);
+        //Partial lines are printed once the stream is idle
+        assert_eq!(stream_output(Some(String::from("4G Jan")), OutputStream::Stdout, true, false, &mut iop), String::new());
+        assert_eq!(stream_output(None, OutputStream::Stdout, true, false, &mut iop), String::from("4Г янв"));
+        //Or once the shell is idle
+        assert_eq!(stream_output(Some(String::from("4G Jan")), OutputStream::Stderr, true, true, &mut iop), String::from("4Г янв"));
+        //Not applied if output is not translated
+        assert_eq!(stream_output(Some(String::from("4G Jan")), OutputStream::Stdout, false, false, &mut iop), String::from("4G Jan"));
+        assert_eq!(stream_output(None, OutputStream::Stdout, false, false, &mut iop), String::new());
     }
 
     #[test]
     fn test_runtime_shellsignal() {
         assert_eq!(shellsignal_to_signal(3).unwrap(), UnixSignal::Sigint);
-        assert_eq!(shellsignal_to_signal(26).unwrap(), UnixSignal::Sigstop);
+        assert_eq!(shellsignal_to_signal(26).unwrap(), UnixSignal::Sigtstp);
+        assert_eq!(shellsignal_to_signal(28).unwrap(), UnixSignal::Sigquit);
         assert!(shellsignal_to_signal(255).is_none());
     }
 
@@ -0,0 +1,213 @@
+//! ## Output
+//!
+//! `output` contains the helpers which print the output of the commands and the messages of Pyc,
+//! transliterated to cyrillic if required
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate ansi_term;
+
+use ansi_term::Colour;
+use std::io::{self, Write};
+
+use crate::config::Config;
+use crate::shell::prompt::{Palette, PromptColor};
+use crate::translator::ioprocessor::IOProcessor;
+use crate::utils::console;
+
+/// ### setup_errors
+///
+/// Set the style and the prefix of the errors from the configuration.
+/// `output.error_color` takes precedence over the `error` color of the palette
+pub fn setup_errors(config: &Config) {
+    let style: String = match config.output_config.error_color.as_deref().and_then(PromptColor::from_name) {
+        Some(color) => color.to_string(),
+        None => Palette::new(&config.prompt_config.palette).resolve("error"),
+    };
+    console::set_error_style(style);
+    console::set_error_prefix(config.output_config.error_prefix.clone());
+}
+
+/// ### print_out
+///
+/// Print a line to stdout; the line is converted to cyrillic if `to_cyrillic` is true
+pub fn print_out(out: String, to_cyrillic: bool, processor: &IOProcessor) -> io::Result<()> {
+    write_out(&mut io::stdout(), out, to_cyrillic, processor)
+}
+
+/// ### print_err
+///
+/// Print a line to stderr with the error style; the line is converted to cyrillic if `to_cyrillic` is true
+pub fn print_err(err: String, to_cyrillic: bool, processor: &IOProcessor) {
+    let _ = write_err(&mut io::stderr(), err, to_cyrillic, processor);
+}
+
+/// ### print_error
+///
+/// Print an error of Pyc to stderr with the error style, as it is
+pub fn print_error(err: &str) {
+    let _ = console::write(&mut io::stderr(), format!("{}\n", error_fmt(String::from(err))).as_str());
+}
+
+/// ### print_warning
+///
+/// Print a warning of Pyc to stderr in yellow, as it is
+pub fn print_warning(warning: &str) {
+    let _ = console::write(&mut io::stderr(), format!("{}\n", Colour::Yellow.paint(warning)).as_str());
+}
+
+/// ### write_out
+///
+/// Write a line to writer; the line is converted to cyrillic if `to_cyrillic` is true
+pub fn write_out(writer: &mut dyn Write, out: String, to_cyrillic: bool, processor: &IOProcessor) -> io::Result<()> {
+    console::write(writer, format!("{}\n", console_fmt(out, to_cyrillic, processor)).as_str())
+}
+
+/// ### write_err
+///
+/// Write a line to writer with the error style; the line is converted to cyrillic if `to_cyrillic` is true.
+/// The escape sequences of the line are kept as they are
+pub fn write_err(writer: &mut dyn Write, err: String, to_cyrillic: bool, processor: &IOProcessor) -> io::Result<()> {
+    let err: String = match to_cyrillic {
+        true => processor.message_to_cyrillic(err.as_str()),
+        false => err,
+    };
+    console::write(writer, format!("{}\n", error_fmt(err)).as_str())
+}
+
+/// ### console_fmt
+///
+/// Format console message
+pub fn console_fmt(out: String, to_cyrillic: bool, processor: &IOProcessor) -> String {
+    match to_cyrillic {
+        true => processor.text_to_cyrillic(&out),
+        false => out,
+    }
+}
+
+/// ### error_fmt
+///
+/// Format error message with the error style (red if not set) and prefix
+pub fn error_fmt(err: String) -> String {
+    style_error(err, console::error_style(), console::error_prefix().as_str())
+}
+
+/// ### style_error
+///
+/// Prepend prefix to each non empty line of err and paint it with style (red if None)
+fn style_error(err: String, style: Option<String>, prefix: &str) -> String {
+    let err: String = match prefix.is_empty() {
+        true => err,
+        false => err
+            .split('\n')
+            .map(|line| match line.is_empty() {
+                true => String::from(line),
+                false => format!("{}{}", prefix, line),
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+    };
+    match style {
+        Some(style) => format!("{}{}\x1b[0m", style, err),
+        None => Colour::Red.paint(err).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::config::PromptConfig;
+    use crate::shell::prompt::Palette;
+    use crate::translator::lang::Language;
+    use crate::translator::new_translator;
+
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_output_write() {
+        let processor: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let written = |stream: &str, to_cyrillic: bool| -> String {
+            let mut output: Vec<u8> = Vec::new();
+            let message: String = String::from("privet 'mir'");
+            match stream {
+                "stdout" => assert!(write_out(&mut output, message, to_cyrillic, &processor).is_ok()),
+                _ => assert!(write_err(&mut output, message, to_cyrillic, &processor).is_ok()),
+            }
+            String::from_utf8(output).unwrap()
+        };
+        //Each line is converted once, or not at all
+        assert_eq!(written("stdout", false), String::from("privet 'mir'\n"));
+        assert_eq!(written("stdout", true), String::from("привет 'мир'\n"));
+        //Errors are styled (the style depends on the palette) and keep the values they report between quotes
+        let stderr: String = written("stderr", false);
+        assert!(stderr.starts_with("\x1b[") && stderr.ends_with("mprivet 'mir'\x1b[0m\n"));
+        let stderr: String = written("stderr", true);
+        assert!(stderr.starts_with("\x1b[") && stderr.ends_with("mпривет 'mir'\x1b[0m\n"));
+    }
+
+    #[test]
+    fn test_output_write_out_broken_pipe() {
+        use std::os::unix::io::FromRawFd;
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        //Open a pipe and close its read end
+        let (read_fd, write_fd) = nix::unistd::pipe().unwrap();
+        assert!(nix::unistd::close(read_fd).is_ok());
+        let mut writer: std::fs::File = unsafe { std::fs::File::from_raw_fd(write_fd) };
+        //Write must fail with broken pipe, without panicking
+        assert_eq!(write_out(&mut writer, String::from("Hello"), true, &iop).err().unwrap().kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_output_console_fmt() {
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        //Out
+        assert_eq!(console_fmt(String::from("Hello"), true, &iop), String::from("Хелло"));
+        assert_eq!(console_fmt(String::from("Hello"), false, &iop), String::from("Hello"));
+    }
+
+    #[test]
+    fn test_output_error_fmt() {
+        let mut palette: HashMap<String, String> = PromptConfig::default_palette();
+        palette.insert(String::from("error"), String::from("bold #ff0000"));
+        console::set_error_style(Palette::new(&palette).resolve("error"));
+        assert_eq!(error_fmt(String::from("Oops")), String::from("\x1b[1m\x1b[38;2;255;0;0mOops\x1b[0m"));
+    }
+
+    #[test]
+    fn test_output_style_error() {
+        //Default style
+        assert_eq!(style_error(String::from("Oops"), None, ""), String::from("\x1b[31mOops\x1b[0m"));
+        //Error color
+        let style: Option<String> = PromptColor::from_name("magenta").map(|color| color.to_string());
+        assert_eq!(style_error(String::from("Oops"), style, ""), String::from("\x1b[35mOops\x1b[0m"));
+        let style: Option<String> = PromptColor::from_name("214").map(|color| color.to_string());
+        assert_eq!(style_error(String::from("Oops"), style, ""), String::from("\x1b[38;5;214mOops\x1b[0m"));
+        //Prefix is prepended to each line
+        let style: Option<String> = PromptColor::from_name("yellow").map(|color| color.to_string());
+        assert_eq!(
+            style_error(String::from("Oops\nOuch\n"), style, "⚠ "),
+            String::from("\x1b[33m⚠ Oops\n⚠ Ouch\n\x1b[0m")
+        );
+    }
+}
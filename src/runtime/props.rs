@@ -23,23 +23,37 @@
 *
 */
 
+use super::capabilities::{Capabilities, Feature};
+use super::{get_command_name, new_processor, output};
 use super::imiop::{self, Imiop};
 
-use crate::config::Config;
+use crate::config::{Config, ConfigError, ConfigErrorCode, OutputTranslation};
+use crate::shell::suggestion::CommandSuggester;
 use crate::shell::{Shell, ShellState};
+use crate::translator::filter::hints::ErrorHints;
 use crate::translator::ioprocessor::IOProcessor;
 use crate::translator::lang::Language;
-use crate::translator::new_translator;
-use crate::utils::console::InputEvent;
+use crate::translator::trace::TraceOutput;
+use crate::utils::console::{self, InputEvent};
+
+use std::env;
+use std::path::{Path, PathBuf};
 
 /// ## RuntimeProps
 ///
 /// Runtime Props is a wrapper for all the properties used by the Runtime module
 pub(super) struct RuntimeProps {
     pub config: Config,
+    config_file: Option<PathBuf>, // Configuration file parsed again by `reload`
     language: Language,
     last_state: ShellState,
     state_changed: bool,
+    trace_output: Option<TraceOutput>,
+    capabilities: Capabilities, // Writability of the output files; probed again by `reload`
+    translate_output: OutputTranslation,
+    error_hints: Option<ErrorHints>,
+    suggester: CommandSuggester,
+    last_program: Option<String>, // Program run by the last command, as it has been written
     imiop: Box<dyn Imiop>,
 }
 
@@ -47,14 +61,106 @@ impl RuntimeProps {
     /// ### new
     ///
     /// Instantiates a new RuntimeProps
-    pub(super) fn new(interactive: bool, config: Config, language: Language) -> RuntimeProps {
+    pub(super) fn new(interactive: bool, config: Config, language: Language, trace_output: Option<TraceOutput>) -> RuntimeProps {
         RuntimeProps {
             config: config.clone(),
+            config_file: None,
             language: language,
             last_state: ShellState::Unknown,
             state_changed: true,
-            imiop: RuntimeProps::init_imiop(interactive, &config, language),
+            trace_output: trace_output.clone(),
+            capabilities: Capabilities::probe(Vec::new()),
+            translate_output: config.output_config.translation(),
+            error_hints: match config.output_config.error_hints {
+                true => Some(ErrorHints::new(language, &config.output_config.hints_dictionary)),
+                false => None,
+            },
+            suggester: CommandSuggester::new(),
+            last_program: None,
+            imiop: RuntimeProps::init_imiop(interactive, &config, language, trace_output),
+        }
+    }
+
+    /// ### set_config_file
+    ///
+    /// Set the configuration file which is parsed again when the configuration is reloaded
+    pub(super) fn set_config_file(&mut self, config_file: PathBuf) {
+        self.imiop.set_config_file(config_file.as_path());
+        self.config_file = Some(config_file);
+    }
+
+    /// ### set_capabilities
+    ///
+    /// Set the capabilities probed at startup; the features they disabled are enabled again by `reload` once their files can be written
+    pub(super) fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    /// ### get_history_file
+    ///
+    /// Get the history file, if history is enabled
+    pub(super) fn get_history_file(&self) -> Option<PathBuf> {
+        match self.capabilities.is_enabled(Feature::History) {
+            true => self.capabilities.path(Feature::History).map(Path::to_path_buf),
+            false => None,
+        }
+    }
+
+    /// ### reload_config
+    ///
+    /// Parse the configuration file again and apply it: the prompt and the aliases are rebuilt, while the shell keeps running.
+    /// If the configuration can't be parsed, the current one is kept. Returns the warnings of the new configuration
+    pub(super) fn reload_config(&mut self, shell: &mut Shell) -> Result<Vec<String>, ConfigError> {
+        //The imiop is instantiated again and the prompt is printed again
+        self.state_changed = true;
+        let config_file: PathBuf = match self.config_file.as_ref() {
+            Some(config_file) => config_file.clone(),
+            None => {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::NoSuchFileOrDirectory,
+                    message: String::from("No configuration file"),
+                })
+            }
+        };
+        let mut config: Config = Config::parse_config(config_file)?;
+        //Options set on the command line still take precedence
+        config.apply_overrides(self.config.overrides.clone());
+        config.apply_pipe(|fd| nix::unistd::isatty(fd).unwrap_or(false));
+        shell.reload_prompt(&config.prompt_config);
+        shell.set_sigint_escalation(&config.core_config.sigint_escalation);
+        output::setup_errors(&config);
+        self.translate_output = config.output_config.translation();
+        self.error_hints = match config.output_config.error_hints {
+            true => Some(ErrorHints::new(self.language, &config.output_config.hints_dictionary)),
+            false => None,
+        };
+        self.suggester.clear();
+        //Check again the output files: the features they disabled are enabled again if they can now be written
+        self.capabilities.reprobe();
+        shell.set_degraded(self.capabilities.disabled_features());
+        if let Some(trace_file) = self.capabilities.path(Feature::TranslationTrace) {
+            self.trace_output = match self.capabilities.is_enabled(Feature::TranslationTrace) {
+                true => Some(TraceOutput::File(trace_file.to_path_buf())),
+                false => None,
+            };
         }
+        let warnings: Vec<String> = config.warnings();
+        self.config = config;
+        Ok(warnings)
+    }
+
+    /// ### take_reload_request
+    ///
+    /// Returns whether the current IMIOP has requested the configuration to be reloaded
+    pub(super) fn take_reload_request(&mut self) -> bool {
+        self.imiop.take_reload_request()
+    }
+
+    /// ### get_language
+    ///
+    /// Get the language used by the translators
+    pub(super) fn get_language(&self) -> Language {
+        self.language
     }
 
     /// ### get_state
@@ -87,6 +193,65 @@ impl RuntimeProps {
         self.state_changed = false;
     }
 
+    /// ### get_translate_output
+    ///
+    /// Get whether stdout and stderr of the last dispatched command have to be translated
+    pub(super) fn get_translate_output(&self) -> OutputTranslation {
+        self.translate_output
+    }
+
+    /// ### update_translate_output
+    ///
+    /// Determine whether stdout and stderr of the command which is going to run have to be translated
+    pub(super) fn update_translate_output(&mut self, command: &str) {
+        self.translate_output = match get_command_name(command) {
+            Some(name) => self.config.output_config.translate_command(name.as_str()),
+            None => self.config.output_config.translation(),
+        };
+    }
+
+    /// ### get_error_hints
+    ///
+    /// Get the error hints collector, if error hints are enabled
+    pub(super) fn get_error_hints(&mut self) -> Option<&mut ErrorHints> {
+        self.error_hints.as_mut()
+    }
+
+    /// ### start_error_hints
+    ///
+    /// Start collecting the stderr of the command which is going to run and remember the program it runs
+    pub(super) fn start_error_hints(&mut self, command: &str) {
+        if let Some(hints) = self.error_hints.as_mut() {
+            hints.start(get_command_name(command));
+        }
+        self.last_program = command.split_whitespace().find(|arg| !arg.contains('=')).map(String::from);
+    }
+
+    /// ### take_error_hint
+    ///
+    /// Get the summary to print for the command which has terminated with the provided exit status, if any
+    pub(super) fn take_error_hint(&mut self, rc: u8) -> Option<String> {
+        self.error_hints.as_mut().and_then(|hints| hints.finish(rc))
+    }
+
+    /// ### take_command_suggestion
+    ///
+    /// Get the commands to suggest when the last command has not been found (exit status 127), if any
+    pub(super) fn take_command_suggestion(&mut self, rc: u8, processor: &IOProcessor) -> Option<String> {
+        let program: String = self.last_program.take()?;
+        if rc != 127 {
+            return None;
+        }
+        let suggestions: Vec<String> = self.suggester.suggest(program.as_str(), env::var_os("PATH"), processor);
+        match suggestions.is_empty() {
+            true => None,
+            false => Some(format!(
+                "did you mean: {}?",
+                suggestions.iter().map(|s| format!("'{}'", s)).collect::<Vec<String>>().join(", ")
+            )),
+        }
+    }
+
     /// ### handle_input_event
     ///
     /// Handle input event received from stdin
@@ -95,21 +260,66 @@ impl RuntimeProps {
         self.switch_imiop();
         // Call handle input event for current IMIOP
         self.imiop.handle_input_event(ev, shell);
+        // If a command has been dispatched, update output translation
+        if let Some(command) = self.imiop.take_dispatched_command() {
+            self.update_translate_output(command.as_str());
+            self.start_error_hints(command.as_str());
+        }
+    }
+
+    /// ### dispatch_input
+    ///
+    /// Process an input line which has not been typed by the user, as if it had been typed.
+    /// Returns the command written to the shell, if any
+    pub(super) fn dispatch_input(&mut self, input: String, shell: &mut Shell) -> Option<String> {
+        // Check if IMIOP has to be changed
+        self.switch_imiop();
+        self.imiop.dispatch_input(input, shell);
+        let command: Option<String> = self.imiop.take_dispatched_command();
+        if let Some(command) = command.as_ref() {
+            self.update_translate_output(command.as_str());
+            self.start_error_hints(command.as_str());
+        }
+        command
+    }
+
+    /// ### source_input
+    ///
+    /// Process a line of the rc file through the current IMIOP, as if it had been typed.
+    /// Returns the command written to the shell, if any
+    pub(super) fn source_input(&mut self, input: String, shell: &mut Shell) -> Option<String> {
+        // Check if IMIOP has to be changed
+        self.switch_imiop();
+        self.imiop.source_input(input, shell);
+        let command: Option<String> = self.imiop.take_dispatched_command();
+        if let Some(command) = command.as_ref() {
+            self.update_translate_output(command.as_str());
+        }
+        command
+    }
+
+    /// ### redraw_prompt
+    ///
+    /// Render the prompt again through the current IMIOP
+    pub(super) fn redraw_prompt(&mut self, shell: &mut Shell) {
+        self.imiop.redraw_prompt(shell);
     }
 
     /// ### init_imiop
     ///
     /// Instantiate the first IMIOP at first launch of props
 
-    fn init_imiop(interactive: bool, config: &Config, language: Language) -> Box<dyn Imiop> {
+    fn init_imiop(interactive: bool, config: &Config, language: Language, trace_output: Option<TraceOutput>) -> Box<dyn Imiop> {
         match interactive {
             true => Box::new(imiop::shiop::ShIop::new(
                 config.clone(),
-                IOProcessor::new(language, new_translator(language)),
+                new_processor(language, config),
+                trace_output,
             )),
             false => Box::new(imiop::subprociop::SubProcIop::new(
                 config.clone(),
-                IOProcessor::new(language, new_translator(language)),
+                new_processor(language, config),
+                trace_output,
             )),
         }
     }
@@ -125,17 +335,25 @@ impl RuntimeProps {
             self.imiop = match self.get_last_state() {
                 ShellState::Shell => Box::new(imiop::shiop::ShIop::new(
                     self.config.clone(),
-                    IOProcessor::new(self.language, new_translator(self.language)),
+                    new_processor(self.language, &self.config),
+                    self.trace_output.clone(),
                 )),
                 ShellState::SubprocessRunning => Box::new(imiop::subprociop::SubProcIop::new(
                     self.config.clone(),
-                    IOProcessor::new(self.language, new_translator(self.language)),
+                    new_processor(self.language, &self.config),
+                    self.trace_output.clone(),
                 )),
                 _ => Box::new(imiop::shiop::ShIop::new(
                     self.config.clone(),
-                    IOProcessor::new(self.language, new_translator(self.language)),
+                    new_processor(self.language, &self.config),
+                    self.trace_output.clone(),
                 )),
             };
+            if let Some(config_file) = self.config_file.as_ref() {
+                self.imiop.set_config_file(config_file.as_path());
+            }
+            // Input echo could have been disabled by the previous subprocess
+            console::set_input_echo(true);
             // Reset state changed
             self.report_state_changed_notified();
         }
@@ -146,8 +364,11 @@ impl RuntimeProps {
 mod tests {
     use super::*;
 
-    use crate::config::Config;
+    use crate::config::{Config, ConfigOverrides};
+    use crate::runtime::output::write_out;
     use crate::translator::lang::Language;
+    use crate::translator::ioprocessor::IOProcessor;
+    use crate::translator::new_translator;
 
     use std::thread::sleep;
     use std::time::Duration;
@@ -159,6 +380,7 @@ mod tests {
         assert_eq!(props.language, Language::Russian);
         assert_eq!(props.last_state, ShellState::Unknown);
         assert_eq!(props.state_changed, true);
+        assert!(props.trace_output.is_none());
     }
 
     #[test]
@@ -234,7 +456,227 @@ mod tests {
         sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
     }
 
+    #[test]
+    fn test_runtimeprops_translate_output_by_command() {
+        let mut config: Config = Config::default();
+        config.output_config.translate_stdout = true;
+        config.output_config.translate_stderr = true;
+        config.output_config.never_translate = vec![String::from("printf")];
+        config.alias.insert(String::from("принт"), String::from("printf"));
+        let mut props: RuntimeProps = RuntimeProps::new(true, config.clone(), Language::Russian, None);
+        assert_eq!(props.get_translate_output(), OutputTranslation { stdout: true, stderr: true });
+        let processor: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let mut shell: Shell = Shell::start(
+            String::from("sh"),
+            Vec::new(),
+            &config.prompt_config,
+        )
+        .unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Unlisted command: output is translated
+        assert_eq!(run_and_read(&mut props, &mut shell, &processor, "echo privet"), String::from("привет\n\n"));
+        assert_eq!(props.get_translate_output(), OutputTranslation { stdout: true, stderr: true });
+        //Listed command (through alias): output is not translated
+        assert_eq!(run_and_read(&mut props, &mut shell, &processor, "принт 'privet\\n'"), String::from("privet\n\n"));
+        assert_eq!(props.get_translate_output(), OutputTranslation { stdout: false, stderr: false });
+        //Absolute path
+        assert_eq!(run_and_read(&mut props, &mut shell, &processor, "/usr/bin/printf 'privet\\n'"), String::from("privet\n\n"));
+        //Back to unlisted
+        assert_eq!(run_and_read(&mut props, &mut shell, &processor, "echo privet"), String::from("привет\n\n"));
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_update_translate_output() {
+        let mut config: Config = Config::default();
+        config.output_config.translate_only = vec![String::from("ls")];
+        let mut props: RuntimeProps = RuntimeProps::new(false, config, Language::Russian, None);
+        props.update_translate_output("ls -l");
+        assert_eq!(props.get_translate_output(), OutputTranslation { stdout: true, stderr: true });
+        props.update_translate_output("LANG=C cat /tmp/foo");
+        assert_eq!(props.get_translate_output(), OutputTranslation { stdout: false, stderr: false });
+        //Empty command: fallback to global setting
+        props.update_translate_output("");
+        assert_eq!(props.get_translate_output(), OutputTranslation { stdout: true, stderr: true });
+    }
+
+    #[test]
+    fn test_runtimeprops_command_suggestion() {
+        let mut props: RuntimeProps = new_runtime_props(true);
+        let processor: IOProcessor = new_processor(Language::Russian, &props.config);
+        props.start_error_hints("LANG=C bashh -c true");
+        let suggestion: String = props.take_command_suggestion(127, &processor).unwrap();
+        assert!(suggestion.starts_with("did you mean: "));
+        assert!(suggestion.contains("'bash'"));
+        //Taken
+        assert!(props.take_command_suggestion(127, &processor).is_none());
+        //Found
+        props.start_error_hints("bashh -c true");
+        assert!(props.take_command_suggestion(0, &processor).is_none());
+        //Paths are not looked up
+        props.start_error_hints("/tmp/bashh");
+        assert!(props.take_command_suggestion(127, &processor).is_none());
+    }
+
+    #[test]
+    fn test_runtimeprops_error_hints() {
+        let mut config: Config = Config::default();
+        config.output_config.translate_stdout = false;
+        config.output_config.translate_stderr = false;
+        config.output_config.error_hints = true;
+        let mut props: RuntimeProps = RuntimeProps::new(true, config.clone(), Language::Russian, None);
+        let mut shell: Shell = Shell::start(
+            String::from("sh"),
+            Vec::new(),
+            &config.prompt_config,
+        )
+        .unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Failing command
+        props.handle_input_event(InputEvent::Key(String::from("grep foo /tmp/pyc_missing_file.txt")), &mut shell);
+        props.handle_input_event(InputEvent::Enter, &mut shell);
+        sleep(Duration::from_millis(500));
+        let stderr: String = read_stderr(&mut props, &mut shell);
+        //Stderr is untouched
+        assert_eq!(stderr, String::from("grep: /tmp/pyc_missing_file.txt: No such file or directory\n"));
+        assert_eq!(
+            props.take_error_hint(shell.get_exit_status()),
+            Some(String::from("[2] неверное использование: нет такого файла или каталога"))
+        );
+        assert_eq!(props.take_error_hint(shell.get_exit_status()), None);
+        //Successful command
+        props.handle_input_event(InputEvent::Key(String::from("echo foo >&2")), &mut shell);
+        props.handle_input_event(InputEvent::Enter, &mut shell);
+        sleep(Duration::from_millis(500));
+        assert_eq!(read_stderr(&mut props, &mut shell), String::from("foo\n"));
+        assert_eq!(props.take_error_hint(shell.get_exit_status()), None);
+        //Disabled
+        let mut props: RuntimeProps = new_runtime_props(true);
+        props.start_error_hints("grep foo");
+        assert!(props.get_error_hints().is_none());
+        assert_eq!(props.take_error_hint(2), None);
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    /// ### read_stderr
+    ///
+    /// Read the shell stderr, collecting it into the error hints as the runtime does
+    #[test]
+    fn test_runtimeprops_reload_reprobe() {
+        let processor: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        //Config dir is a file: history and trace are disabled
+        let config_dir: PathBuf = tmpdir.path().join("pyc");
+        std::fs::write(config_dir.as_path(), "").unwrap();
+        let history_file: PathBuf = config_dir.join("pyc_history");
+        let trace_file: PathBuf = config_dir.join("trace.json");
+        let config_file: PathBuf = tmpdir.path().join("pyc.yml");
+        std::fs::write(config_file.as_path(), reload_config_yaml("${DEGRADED}>")).unwrap();
+        let capabilities: Capabilities = Capabilities::probe_outputs(Some(&history_file), Some(&TraceOutput::File(trace_file.clone())));
+        let mut props: RuntimeProps = new_runtime_props(true);
+        props.set_config_file(config_file);
+        let mut shell: Shell = Shell::start(String::from("sh"), Vec::new(), &props.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        shell.set_degraded(capabilities.disabled_features());
+        props.set_capabilities(capabilities);
+        assert!(props.get_history_file().is_none());
+        assert!(props.trace_output.is_none());
+        //Still not writable
+        assert!(props.reload_config(&mut shell).is_ok());
+        assert!(props.get_history_file().is_none());
+        assert_eq!(shell.get_promptline(&processor), String::from("history,trace>"));
+        //Make the config dir writable
+        std::fs::remove_file(config_dir.as_path()).unwrap();
+        assert!(props.reload_config(&mut shell).is_ok());
+        assert_eq!(props.get_history_file(), Some(history_file));
+        assert_eq!(props.trace_output, Some(TraceOutput::File(trace_file)));
+        assert_eq!(shell.get_promptline(&processor), String::from(">"));
+        //Stop shell
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_runtimeprops_reload_config() {
+        let processor: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let config_file: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let mut props: RuntimeProps = new_runtime_props(true);
+        let mut shell: Shell = Shell::start(String::from("sh"), Vec::new(), &props.config.prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Configuration file is not set
+        assert!(props.reload_config(&mut shell).is_err());
+        props.set_config_file(config_file.path().to_path_buf());
+        let prompt_line: String = shell.get_promptline(&processor);
+        //Reload a valid configuration
+        std::fs::write(config_file.path(), reload_config_yaml("${KRED}reloaded${KRST}>")).unwrap();
+        props.report_state_changed_notified();
+        assert_eq!(props.reload_config(&mut shell).ok().unwrap(), Vec::<String>::new());
+        assert!(props.get_state_changed());
+        assert_eq!(props.config.get_alias(&String::from("лл")).unwrap(), String::from("ls -l"));
+        assert_ne!(shell.get_promptline(&processor), prompt_line);
+        assert_eq!(shell.get_promptline(&processor), String::from("\x1b[31mreloaded\x1b[0m>"));
+        //Options set on the command line are applied again
+        props.config.apply_overrides(ConfigOverrides {
+            translate_output: Some(false),
+            input_translation: None,
+        });
+        assert!(props.reload_config(&mut shell).is_ok());
+        assert_eq!(props.config.output_config.translation(), OutputTranslation { stdout: false, stderr: false });
+        assert_eq!(props.translate_output, OutputTranslation { stdout: false, stderr: false });
+        //An invalid configuration is not applied
+        std::fs::write(config_file.path(), "prompt:\n  prompt_line: [1, 2]\n").unwrap();
+        assert!(props.reload_config(&mut shell).is_err());
+        assert_eq!(shell.get_promptline(&processor), String::from("\x1b[31mreloaded\x1b[0m>"));
+        assert!(props.config.get_alias(&String::from("лл")).is_some());
+        //Stop shell
+        let _ = shell.stop();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    fn read_stderr(props: &mut RuntimeProps, shell: &mut Shell) -> String {
+        match shell.read() {
+            Ok((_, Some(err))) => {
+                if let Some(hints) = props.get_error_hints() {
+                    hints.feed(err.as_str());
+                }
+                err
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// ### run_and_read
+    ///
+    /// Type command into props, then read the shell output as it would be printed by the runtime
+    fn run_and_read(props: &mut RuntimeProps, shell: &mut Shell, processor: &IOProcessor, command: &str) -> String {
+        props.handle_input_event(InputEvent::Key(String::from(command)), shell);
+        props.handle_input_event(InputEvent::Enter, shell);
+        sleep(Duration::from_millis(500));
+        let mut output: Vec<u8> = Vec::new();
+        if let Ok((Some(out), _)) = shell.read() {
+            assert!(write_out(&mut output, out, props.get_translate_output().stdout, processor).is_ok());
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    /// ### reload_config_yaml
+    ///
+    /// Returns a configuration with the provided prompt line and an alias
+    fn reload_config_yaml(prompt_line: &str) -> String {
+        format!(
+            "alias:\n  - лл: \"ls -l\"\nprompt:\n  prompt_line: \"{}\"\n  history_size: 256\n  translate: false\n  break:\n    enabled: false\n    with: \">\"\n  duration:\n    min_elapsed_time: 2000\n  rc:\n    ok: \"ok\"\n    error: \"err\"\n  git:\n    branch: \"on \"\n    commit_ref_len: 8\n",
+            prompt_line
+        )
+    }
+
     fn new_runtime_props(interactive: bool) -> RuntimeProps {
-        RuntimeProps::new(interactive, Config::default(), Language::Russian)
+        RuntimeProps::new(interactive, Config::default(), Language::Russian, None)
     }
 }
@@ -0,0 +1,403 @@
+//! ## Session
+//!
+//! `session` implements the control channel of a named session: a Unix socket which other pyc instances
+//! can use to run commands in the session.
+//!
+//! The protocol is line oriented; each request is a line and is answered with one or more lines:
+//!
+//! - `PING` => `PONG`
+//! - `CWD?` => `CWD <path>`
+//! - `RUN <command>` => `OUT <line>` for each line of output, then `DONE <exit code>`
+//!
+//! Errors are reported with `ERR <message>`. Commands are executed one at a time, in the order they have been received.
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate nix;
+
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+/// ### SessionServer
+///
+/// SessionServer listens on the session socket, answers to the requests and queues the commands to run
+pub struct SessionServer {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<SessionClient>,
+    next_client_id: usize,
+    queue: VecDeque<(usize, String)>,
+    running: Option<usize>,
+    output: String,
+}
+
+/// ### SessionClient
+///
+/// SessionClient is a connection to the session socket
+struct SessionClient {
+    id: usize,
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    closed: bool,
+}
+
+impl SessionServer {
+    /// ### bind
+    ///
+    /// Create the control socket for the session with the provided name
+    pub fn bind(name: &str) -> io::Result<SessionServer> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(io::Error::new(ErrorKind::InvalidInput, format!("invalid session name '{}'", name)));
+        }
+        let dir: PathBuf = session_dir();
+        if !dir.exists() {
+            DirBuilder::new().recursive(true).mode(0o700).create(dir.as_path())?;
+        }
+        SessionServer::bind_at(socket_path(name).as_path())
+    }
+
+    /// ### bind_at
+    ///
+    /// Create the control socket at the provided path; only the owner can connect to it
+    pub fn bind_at(path: &Path) -> io::Result<SessionServer> {
+        if path.exists() {
+            //Check whether the session is still alive
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(ErrorKind::AddrInUse, "session already exists"));
+            }
+            fs::remove_file(path)?;
+        }
+        let listener: UnixListener = UnixListener::bind(path)?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        listener.set_nonblocking(true)?;
+        Ok(SessionServer {
+            path: path.to_path_buf(),
+            listener,
+            clients: Vec::new(),
+            next_client_id: 0,
+            queue: VecDeque::new(),
+            running: None,
+            output: String::new(),
+        })
+    }
+
+    /// ### poll
+    ///
+    /// Accept new connections and handle the requests received; `RUN` requests are queued.
+    /// `wrkdir` is the current working directory of the session
+    pub fn poll(&mut self, wrkdir: &Path) {
+        //Accept new clients
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.clients.push(SessionClient {
+                    id: self.next_client_id,
+                    stream,
+                    buffer: Vec::new(),
+                    closed: false,
+                });
+                self.next_client_id += 1;
+            }
+        }
+        //Read requests
+        let mut requests: Vec<(usize, String)> = Vec::new();
+        for client in self.clients.iter_mut() {
+            requests.extend(client.read_requests().into_iter().map(|r| (client.id, r)));
+        }
+        for (client, request) in requests.into_iter() {
+            let request: &str = request.trim_end_matches('\r');
+            if request == "PING" {
+                self.reply(client, "PONG");
+            } else if request == "CWD?" {
+                self.reply(client, format!("CWD {}", wrkdir.display()).as_str());
+            } else if let Some(command) = request.strip_prefix("RUN ") {
+                match command.trim().is_empty() {
+                    true => self.reply(client, "ERR empty command"),
+                    false => self.queue.push_back((client, String::from(command))),
+                }
+            } else {
+                self.reply(client, format!("ERR unknown request '{}'", request).as_str());
+            }
+        }
+        //Remove closed clients (their queued commands are run anyway)
+        let running: Option<usize> = self.running;
+        self.clients.retain(|c| !c.closed || Some(c.id) == running);
+    }
+
+    /// ### next_command
+    ///
+    /// If no command is running, returns the next queued command; the command is then considered running until `command_done`
+    pub fn next_command(&mut self) -> Option<String> {
+        if self.running.is_some() {
+            return None;
+        }
+        let (client, command): (usize, String) = self.queue.pop_front()?;
+        self.running = Some(client);
+        self.output.clear();
+        Some(command)
+    }
+
+    /// ### is_running
+    ///
+    /// Returns whether a command received from the socket is running
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// ### forward_output
+    ///
+    /// Send the output of the running command to the client which requested it
+    pub fn forward_output(&mut self, output: &str) {
+        let client: usize = match self.running {
+            Some(client) => client,
+            None => return,
+        };
+        self.output.push_str(output);
+        //Send complete lines only
+        while let Some(pos) = self.output.find('\n') {
+            let line: String = self.output.drain(..=pos).collect();
+            self.reply(client, format!("OUT {}", line.trim_end_matches('\n')).as_str());
+        }
+    }
+
+    /// ### command_done
+    ///
+    /// Report to the client that the running command has terminated with the provided exit code
+    pub fn command_done(&mut self, rc: u8) {
+        let client: usize = match self.running.take() {
+            Some(client) => client,
+            None => return,
+        };
+        if !self.output.is_empty() {
+            let line: String = std::mem::take(&mut self.output);
+            self.reply(client, format!("OUT {}", line).as_str());
+        }
+        self.reply(client, format!("DONE {}", rc).as_str());
+        self.clients.retain(|c| !c.closed);
+    }
+
+    /// ### reply
+    ///
+    /// Send a line to a client
+    fn reply(&mut self, client: usize, line: &str) {
+        if let Some(client) = self.clients.iter_mut().find(|c| c.id == client) {
+            //Socket is non blocking; write the reply in blocking mode, since it's short
+            let _ = client.stream.set_nonblocking(false);
+            if writeln!(client.stream, "{}", line).is_err() {
+                client.closed = true;
+            }
+            let _ = client.stream.set_nonblocking(true);
+        }
+    }
+}
+
+impl Drop for SessionServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.path.as_path());
+    }
+}
+
+impl SessionClient {
+    /// ### read_requests
+    ///
+    /// Read the available data and returns the complete lines received
+    fn read_requests(&mut self) -> Vec<String> {
+        let mut buf: [u8; 1024] = [0; 1024];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(n) => self.buffer.extend_from_slice(&buf[..n]),
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(ref err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        let mut requests: Vec<String> = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            requests.push(String::from_utf8_lossy(&line[..line.len() - 1]).to_string());
+        }
+        requests
+    }
+}
+
+/// ### session_dir
+///
+/// Returns the directory where the session sockets are created
+pub fn session_dir() -> PathBuf {
+    env::temp_dir().join(format!("pyc-{}", nix::unistd::getuid()))
+}
+
+/// ### socket_path
+///
+/// Returns the path of the socket of the session with the provided name
+pub fn socket_path(name: &str) -> PathBuf {
+    session_dir().join(format!("{}.sock", name))
+}
+
+/// ### send
+///
+/// Run a command in the session listening on `path`; the output of the command is written to `out`.
+/// Returns the exit code of the command
+pub fn send(path: &Path, command: &str, out: &mut dyn Write) -> io::Result<u8> {
+    if command.contains('\n') {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "command must be a single line"));
+    }
+    let mut stream: UnixStream = UnixStream::connect(path)?;
+    writeln!(stream, "RUN {}", command)?;
+    let reader: BufReader<UnixStream> = BufReader::new(stream);
+    for line in reader.lines() {
+        let line: String = line?;
+        if let Some(output) = line.strip_prefix("OUT ") {
+            writeln!(out, "{}", output)?;
+        } else if line == "OUT" {
+            writeln!(out)?;
+        } else if let Some(rc) = line.strip_prefix("DONE ") {
+            return Ok(rc.trim().parse::<u8>().unwrap_or(255));
+        } else if let Some(err) = line.strip_prefix("ERR ") {
+            return Err(io::Error::other(String::from(err)));
+        }
+    }
+    Err(io::Error::new(ErrorKind::UnexpectedEof, "session closed the connection"))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::thread::sleep;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_session_protocol() {
+        let tmpdir: TempDir = TempDir::new().unwrap();
+        let path: PathBuf = tmpdir.path().join("test.sock");
+        let mut server: SessionServer = SessionServer::bind_at(path.as_path()).unwrap();
+        //Permissions
+        assert_eq!(fs::metadata(path.as_path()).unwrap().permissions().mode() & 0o777, 0o600);
+        //Session already exists
+        assert_eq!(SessionServer::bind_at(path.as_path()).err().unwrap().kind(), ErrorKind::AddrInUse);
+        let mut client: UnixStream = UnixStream::connect(path.as_path()).unwrap();
+        write!(client, "PING\nCWD?\nFOO\nRUN \n").unwrap();
+        sleep(Duration::from_millis(100));
+        server.poll(Path::new("/tmp"));
+        let mut reader: BufReader<UnixStream> = BufReader::new(client.try_clone().unwrap());
+        let mut line: String = String::new();
+        for expected in ["PONG\n", "CWD /tmp\n", "ERR unknown request 'FOO'\n", "ERR empty command\n"].iter() {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            assert_eq!(line.as_str(), *expected);
+        }
+        assert!(server.next_command().is_none());
+    }
+
+    #[test]
+    fn test_session_queue() {
+        let tmpdir: TempDir = TempDir::new().unwrap();
+        let path: PathBuf = tmpdir.path().join("test.sock");
+        let mut server: SessionServer = SessionServer::bind_at(path.as_path()).unwrap();
+        let mut first: UnixStream = UnixStream::connect(path.as_path()).unwrap();
+        let mut second: UnixStream = UnixStream::connect(path.as_path()).unwrap();
+        writeln!(first, "RUN ls").unwrap();
+        writeln!(second, "RUN pwd").unwrap();
+        sleep(Duration::from_millis(100));
+        server.poll(Path::new("/tmp"));
+        //Commands are run one at a time
+        assert_eq!(server.next_command().unwrap(), String::from("ls"));
+        assert!(server.is_running());
+        assert!(server.next_command().is_none());
+        server.forward_output("a\nb");
+        server.forward_output("c\n");
+        server.command_done(0);
+        assert!(!server.is_running());
+        assert_eq!(server.next_command().unwrap(), String::from("pwd"));
+        server.forward_output("/tmp");
+        server.command_done(1);
+        //Check replies
+        let mut reply: String = String::new();
+        first.shutdown(std::net::Shutdown::Write).unwrap();
+        BufReader::new(first).read_line(&mut reply).unwrap();
+        assert_eq!(reply, String::from("OUT a\n"));
+        drop(server);
+        let mut replies: String = String::new();
+        BufReader::new(second).read_to_string(&mut replies).unwrap();
+        assert_eq!(replies, String::from("OUT /tmp\nDONE 1\n"));
+        //Socket is removed
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_session_bind_stale_socket() {
+        let tmpdir: TempDir = TempDir::new().unwrap();
+        let path: PathBuf = tmpdir.path().join("test.sock");
+        //Socket of a dead session
+        let listener: UnixListener = UnixListener::bind(path.as_path()).unwrap();
+        drop(listener);
+        assert!(path.exists());
+        assert!(SessionServer::bind_at(path.as_path()).is_ok());
+        //Bad names
+        assert!(SessionServer::bind("").is_err());
+        assert!(SessionServer::bind("../work").is_err());
+    }
+
+    #[test]
+    fn test_session_send() {
+        let tmpdir: TempDir = TempDir::new().unwrap();
+        let path: PathBuf = tmpdir.path().join("test.sock");
+        let mut server: SessionServer = SessionServer::bind_at(path.as_path()).unwrap();
+        let client_path: PathBuf = path.clone();
+        let client = std::thread::spawn(move || {
+            let mut out: Vec<u8> = Vec::new();
+            let rc: u8 = send(client_path.as_path(), "экхо привет", &mut out).unwrap();
+            (rc, String::from_utf8(out).unwrap())
+        });
+        //Serve the command
+        let command: String = loop {
+            server.poll(Path::new("/"));
+            if let Some(command) = server.next_command() {
+                break command;
+            }
+            sleep(Duration::from_millis(10));
+        };
+        assert_eq!(command, String::from("экхо привет"));
+        server.forward_output("привет\n");
+        server.command_done(3);
+        assert_eq!(client.join().unwrap(), (3, String::from("привет\n")));
+        //Multi line commands are refused
+        assert!(send(path.as_path(), "ls\npwd", &mut Vec::new()).is_err());
+        //Session doesn't exist
+        drop(server);
+        assert!(send(path.as_path(), "ls", &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! ## Alias
+//!
+//! `alias` provides the expansion of the aliases configured by the user or defined at runtime
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+
+//Maximum amount of aliases expanded for a single command
+pub const MAX_ALIAS_EXPANSIONS: usize = 16;
+
+/// ### AliasError
+///
+/// AliasError describes why an alias couldn't be expanded or defined
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub enum AliasError {
+    Cycle(Vec<String>),
+    TooManyExpansions,
+    InvalidName(String),
+    NotFound(String),
+}
+
+impl fmt::Display for AliasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AliasError::Cycle(chain) => write!(f, "alias cycle: {}", chain.join(" -> ")),
+            AliasError::TooManyExpansions => write!(f, "more than {} aliases expanded", MAX_ALIAS_EXPANSIONS),
+            AliasError::InvalidName(name) => write!(f, "`{}': invalid alias name", name),
+            AliasError::NotFound(name) => write!(f, "{}: not found", name),
+        }
+    }
+}
+
+/// ### expand
+///
+/// Replace the first word of argv with the words of its alias, as long as the first word is an alias.
+/// An alias which starts with its own name (e.g. `ls` => `ls --color`) is expanded once.
+/// Returns the amount of leading words of argv which come from aliases (0 if the command is not an alias)
+pub fn expand(argv: &mut Vec<String>, lookup: &dyn Fn(&str) -> Option<String>) -> Result<usize, AliasError> {
+    let mut expanded: Vec<String> = Vec::new();
+    let mut alias_words: usize = 0;
+    while let Some(name) = argv.first().cloned() {
+        let replacement: String = match lookup(name.as_str()) {
+            Some(replacement) => replacement,
+            None => break,
+        };
+        if expanded.last() == Some(&name) {
+            break;
+        }
+        if expanded.contains(&name) {
+            expanded.push(name);
+            return Err(AliasError::Cycle(expanded));
+        }
+        if expanded.len() == MAX_ALIAS_EXPANSIONS {
+            return Err(AliasError::TooManyExpansions);
+        }
+        let words: Vec<String> = replacement.split_whitespace().map(String::from).collect();
+        if words.is_empty() {
+            break;
+        }
+        alias_words = alias_words.max(1) - 1 + words.len();
+        argv.splice(0..1, words);
+        expanded.push(name);
+    }
+    Ok(alias_words)
+}
+
+/// ### expand_list
+///
+/// Expand the aliases of every command of a command list (e.g. `лл && гс`): the first word and each word which follows
+/// an unquoted separator (`;`, `&&`, `||`, `|` or `&`) are expanded as `expand` does; separators coming from an alias are not.
+/// Returns the amount of leading words of argv which come from the alias of the first command
+pub fn expand_list(argv: &mut Vec<String>, lookup: &dyn Fn(&str) -> Option<String>) -> Result<usize, AliasError> {
+    //Split words into commands
+    let mut commands: Vec<Vec<String>> = vec![Vec::new()];
+    let mut quote: Option<char> = None;
+    for word in std::mem::take(argv).into_iter() {
+        let ends_command: bool = ends_command(word.as_str(), &mut quote);
+        if let Some(command) = commands.last_mut() {
+            command.push(word);
+        }
+        if ends_command {
+            commands.push(Vec::new());
+        }
+    }
+    let mut alias_words: usize = 0;
+    for (index, mut command) in commands.into_iter().enumerate() {
+        let words: usize = expand(&mut command, lookup)?;
+        if index == 0 {
+            alias_words = words;
+        }
+        argv.append(&mut command);
+    }
+    Ok(alias_words)
+}
+
+/// ### ends_command
+///
+/// Returns whether word ends with a command separator which is neither quoted nor escaped.
+/// `quote` keeps track of the quote opened by the previous words
+fn ends_command(word: &str, quote: &mut Option<char>) -> bool {
+    let mut backslash: bool = false;
+    let mut separator: bool = false;
+    for c in word.chars() {
+        separator = false;
+        if backslash {
+            backslash = false;
+            continue;
+        }
+        match (*quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => *quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => backslash = true,
+            (Some(_), _) => {}
+            (None, '\'') | (None, '"') => *quote = Some(c),
+            (None, ';') | (None, '&') | (None, '|') => separator = true,
+            (None, _) => {}
+        }
+    }
+    separator
+}
+
+/// ### define
+///
+/// Define the aliases described by `name=value` arguments
+pub fn define(aliases: &mut HashMap<String, String>, args: &[String]) -> Result<(), AliasError> {
+    for arg in args.iter() {
+        let (name, value): (&str, &str) = match arg.find('=') {
+            Some(pos) => (&arg[..pos], &arg[pos + 1..]),
+            None => return Err(AliasError::NotFound(arg.clone())),
+        };
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '/' || c == '\'' || c == '"') {
+            return Err(AliasError::InvalidName(String::from(name)));
+        }
+        aliases.insert(String::from(name), String::from(value));
+    }
+    Ok(())
+}
+
+/// ### list
+///
+/// Returns the aliases as `alias name='value'` lines, sorted by name.
+/// Runtime aliases override the configured ones with the same name
+pub fn list(configured: &HashMap<String, String>, runtime: &HashMap<String, String>) -> Vec<String> {
+    let mut aliases: Vec<(&String, &String)> = configured.iter().filter(|(name, _)| !runtime.contains_key(*name)).chain(runtime.iter()).collect();
+    aliases.sort();
+    aliases.into_iter().map(|(name, value)| format!("alias {}='{}'", name, value.replace('\'', "'\\''"))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn expand_with(aliases: &HashMap<String, String>, input: &str) -> Result<(Vec<String>, usize), AliasError> {
+        let mut argv: Vec<String> = input.split_whitespace().map(String::from).collect();
+        let words: usize = expand(&mut argv, &|name: &str| aliases.get(name).cloned())?;
+        Ok((argv, words))
+    }
+
+    fn words(input: &str) -> Vec<String> {
+        input.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_alias_expand() {
+        let mut aliases: HashMap<String, String> = HashMap::new();
+        aliases.insert(String::from("гс"), String::from("git status --short"));
+        aliases.insert(String::from("ls"), String::from("ls --color"));
+        aliases.insert(String::from("лл"), String::from("ls -l"));
+        aliases.insert(String::from("ллл"), String::from("лл -a"));
+        aliases.insert(String::from("пусто"), String::new());
+        //Multi-word expansion with arguments
+        assert_eq!(expand_with(&aliases, "гс src").unwrap(), (words("git status --short src"), 3));
+        //Not an alias
+        assert_eq!(expand_with(&aliases, "du -hs").unwrap(), (words("du -hs"), 0));
+        //Self reference is expanded once
+        assert_eq!(expand_with(&aliases, "ls /tmp").unwrap(), (words("ls --color /tmp"), 2));
+        //Recursion
+        assert_eq!(expand_with(&aliases, "ллл /tmp").unwrap(), (words("ls --color -l -a /tmp"), 4));
+        //Empty alias is ignored
+        assert_eq!(expand_with(&aliases, "пусто x").unwrap(), (words("пусто x"), 0));
+    }
+
+    #[test]
+    fn test_alias_expand_list() {
+        let mut aliases: HashMap<String, String> = HashMap::new();
+        aliases.insert(String::from("гс"), String::from("git status"));
+        aliases.insert(String::from("лл"), String::from("ls -l"));
+        aliases.insert(String::from("оба"), String::from("лл; гс"));
+        let expand_list_with = |input: &str| -> (Vec<String>, usize) {
+            let mut argv: Vec<String> = words(input);
+            let words: usize = expand_list(&mut argv, &|name: &str| aliases.get(name).cloned()).unwrap();
+            (argv, words)
+        };
+        //Every command of the list is expanded
+        assert_eq!(expand_list_with("лл /tmp && гс"), (words("ls -l /tmp && git status"), 2));
+        assert_eq!(expand_list_with("cd /tmp || лл ; гс | лл"), (words("cd /tmp || ls -l ; git status | ls -l"), 0));
+        assert_eq!(expand_list_with("cd /tmp; лл"), (words("cd /tmp; ls -l"), 0));
+        assert_eq!(expand_list_with("sleep 1 & лл"), (words("sleep 1 & ls -l"), 0));
+        //Quoted or escaped separators don't end the command
+        assert_eq!(expand_list_with("echo ' && лл' \"; гс\" \\; лл"), (words("echo ' && лл' \"; гс\" \\; лл"), 0));
+        //Redirections are not separators
+        assert_eq!(expand_list_with("ls 2>&1 лл"), (words("ls 2>&1 лл"), 0));
+        //Separators coming from an alias don't start a new command
+        assert_eq!(expand_list_with("оба"), (words("лл; гс"), 2));
+    }
+
+    #[test]
+    fn test_alias_cycle() {
+        let mut aliases: HashMap<String, String> = HashMap::new();
+        aliases.insert(String::from("a"), String::from("b -x"));
+        aliases.insert(String::from("b"), String::from("c"));
+        aliases.insert(String::from("c"), String::from("a -y"));
+        assert_eq!(
+            expand_with(&aliases, "a").err().unwrap(),
+            AliasError::Cycle(vec![String::from("a"), String::from("b"), String::from("c"), String::from("a")])
+        );
+        assert_eq!(AliasError::Cycle(vec![String::from("a"), String::from("b"), String::from("a")]).to_string(), String::from("alias cycle: a -> b -> a"));
+        //Too many expansions
+        let mut aliases: HashMap<String, String> = HashMap::new();
+        for i in 0..MAX_ALIAS_EXPANSIONS + 1 {
+            aliases.insert(format!("a{}", i), format!("a{}", i + 1));
+        }
+        assert_eq!(expand_with(&aliases, "a0").err().unwrap(), AliasError::TooManyExpansions);
+        aliases.remove(&format!("a{}", MAX_ALIAS_EXPANSIONS));
+        assert_eq!(expand_with(&aliases, "a0").unwrap(), (vec![format!("a{}", MAX_ALIAS_EXPANSIONS)], 1));
+    }
+
+    #[test]
+    fn test_alias_define_list() {
+        let mut configured: HashMap<String, String> = HashMap::new();
+        configured.insert(String::from("чд"), String::from("cd"));
+        configured.insert(String::from("лл"), String::from("ls -l"));
+        let mut runtime: HashMap<String, String> = HashMap::new();
+        assert!(define(&mut runtime, &[String::from("лл=ls -la"), String::from("гс=git status --short")]).is_ok());
+        assert_eq!(define(&mut runtime, &[String::from("a b=c")]).err().unwrap(), AliasError::InvalidName(String::from("a b")));
+        assert_eq!(define(&mut runtime, &[String::from("=c")]).err().unwrap(), AliasError::InvalidName(String::new()));
+        assert_eq!(define(&mut runtime, &[String::from("гс")]).err().unwrap(), AliasError::NotFound(String::from("гс")));
+        assert_eq!(
+            list(&configured, &runtime),
+            vec![
+                String::from("alias гс='git status --short'"),
+                String::from("alias лл='ls -la'"),
+                String::from("alias чд='cd'"),
+            ]
+        );
+    }
+}
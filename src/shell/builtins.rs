@@ -0,0 +1,215 @@
+//! ## Builtins
+//!
+//! `builtins` provides the table of the commands run by Pyc itself instead of the shell and the output of `help`
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::prompt::PROMPT_KEYS;
+use super::unixsignal::UnixSignal;
+use crate::translator::lang::Language;
+
+use std::path::Path;
+
+/// ### Builtin
+///
+/// Builtin describes a command run by Pyc itself
+pub struct Builtin {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub description: &'static str,
+}
+
+/// Name of `help` which is not transliterated
+pub const HELP_ALIAS: &str = "помощь";
+
+/// Commands run by Pyc itself, as listed by `help`
+pub const BUILTINS: [Builtin; 18] = [
+    Builtin { name: "alias", usage: "alias [NAME[=VALUE]...]", description: "Define or list aliases" },
+    Builtin { name: "bg", usage: "bg [%N]", description: "Resume a stopped job in background" },
+    Builtin { name: "cd", usage: "cd [DIR|-]", description: "Change the working directory" },
+    Builtin { name: "clear", usage: "clear", description: "Clear the screen" },
+    Builtin { name: "dirs", usage: "dirs", description: "Print the directory stack" },
+    Builtin { name: "env", usage: "env", description: "Print the environment" },
+    Builtin { name: "export", usage: "export [NAME=VALUE...]", description: "Set or list environment variables" },
+    Builtin { name: "fg", usage: "fg [%N]", description: "Bring a job to foreground" },
+    Builtin { name: "help", usage: "help [BUILTIN]", description: "Print this help (also `помощь`)" },
+    Builtin { name: "history", usage: "history", description: "Print the command history" },
+    Builtin { name: "jobs", usage: "jobs", description: "List the jobs started in background" },
+    Builtin { name: "kill", usage: "kill [-SIGNAL] %N|PID...", description: "Send a signal (TERM by default) to jobs or processes" },
+    Builtin { name: "popd", usage: "popd", description: "Leave the directory on top of the stack" },
+    Builtin { name: "pushd", usage: "pushd [DIR]", description: "Enter a directory, pushing it on the stack" },
+    Builtin { name: "reload", usage: "reload", description: "Parse the configuration file again" },
+    Builtin { name: "time", usage: "time COMMAND", description: "Run a command and print the time it took" },
+    Builtin { name: "unset", usage: "unset NAME...", description: "Remove environment variables" },
+    Builtin { name: "wait", usage: "wait", description: "Wait for all the jobs to terminate" },
+];
+
+/// ### find
+///
+/// Returns the builtin with the provided name
+pub fn find(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+/// ### KillTarget
+///
+/// KillTarget is a job or a process `kill` sends the signal to
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum KillTarget {
+    Job(usize),
+    Process(i32),
+}
+
+/// ### parse_kill
+///
+/// Parse the arguments of `kill`: an optional signal (`-KILL`, `-SIGKILL`, `-9` or `-s KILL`) followed by jobs (`%N`) and pids.
+/// The signal is SIGTERM if not provided
+pub fn parse_kill(args: &[String]) -> Result<(UnixSignal, Vec<KillTarget>), String> {
+    let mut args = args.iter().map(|arg| arg.as_str()).peekable();
+    let signal: UnixSignal = match args.peek().copied() {
+        Some("-s") => {
+            args.next();
+            let name: &str = args.next().ok_or_else(|| String::from("-s: option requires an argument"))?;
+            UnixSignal::from_name(name).ok_or_else(|| format!("{}: invalid signal specification", name))?
+        }
+        Some(arg) if arg.starts_with('-') => {
+            args.next();
+            UnixSignal::from_name(&arg[1..]).ok_or_else(|| format!("{}: invalid signal specification", &arg[1..]))?
+        }
+        _ => UnixSignal::Sigterm,
+    };
+    let targets: Vec<KillTarget> = args
+        .map(|arg| match arg.strip_prefix('%') {
+            Some(id) => id.parse::<usize>().map(KillTarget::Job).map_err(|_| format!("{}: no such job", arg)),
+            None => match arg.parse::<i32>() {
+                Ok(pid) if pid > 0 => Ok(KillTarget::Process(pid)),
+                _ => Err(format!("{}: arguments must be process or job IDs", arg)),
+            },
+        })
+        .collect::<Result<Vec<KillTarget>, String>>()?;
+    match targets.is_empty() {
+        true => Err(String::from("usage: kill [-SIGNAL] %N|PID...")),
+        false => Ok((signal, targets)),
+    }
+}
+
+/// ### handles_kill
+///
+/// Returns whether the `kill` line is run by the builtin. Lines with separators, redirections or substitutions are left to the shell;
+/// the others are run by the builtin if they contain a job (`%N`), which the shell doesn't know, or if all the arguments are understood.
+/// Anything else (e.g. `kill -l`, `kill -- -PGID` or `kill $(pgrep x)`) is left to the shell
+pub fn handles_kill(line: &str, args: &[String]) -> bool {
+    if line.contains([';', '&', '|', '<', '>', '$', '`', '(', ')']) {
+        return false;
+    }
+    args.iter().any(|arg| arg.starts_with('%')) || parse_kill(args).is_ok()
+}
+
+/// ### help
+///
+/// Returns the lines printed by `help`: the builtins, the prompt keys, the language and the configuration file
+pub fn help(language: Language, config_file: Option<&Path>) -> Vec<String> {
+    let mut lines: Vec<String> = vec![String::from("Builtins:")];
+    lines.extend(BUILTINS.iter().map(|builtin| format!("  {:<26}{}", builtin.usage, builtin.description)));
+    lines.push(String::new());
+    lines.push(String::from("Prompt keys:"));
+    lines.extend(PROMPT_KEYS.iter().map(|(key, description)| format!("  {:<26}{}", key, description)));
+    lines.push(String::new());
+    lines.push(format!("Language: {}", language.to_string()));
+    lines.push(format!(
+        "Configuration: {}",
+        config_file.map(|file| file.display().to_string()).unwrap_or_else(|| String::from("default"))
+    ));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_builtins_help() {
+        let lines: Vec<String> = help(Language::Russian, Some(PathBuf::from("/home/user/.config/pyc/pyc.yml").as_path()));
+        //Every builtin is listed
+        for builtin in BUILTINS.iter() {
+            assert!(lines.iter().any(|line| line.trim_start().starts_with(format!("{} ", builtin.name).as_str())), "{} is missing", builtin.name);
+        }
+        //Every prompt key is listed
+        for (key, _) in PROMPT_KEYS.iter() {
+            assert!(lines.iter().any(|line| line.contains(key)), "{} is missing", key);
+        }
+        assert_eq!(lines.last().unwrap(), "Configuration: /home/user/.config/pyc/pyc.yml");
+        assert_eq!(lines[lines.len() - 2], format!("Language: {}", Language::Russian.to_string()));
+        assert_eq!(help(Language::Russian, None).last().unwrap(), "Configuration: default");
+    }
+
+    #[test]
+    fn test_builtins_parse_kill() {
+        let parse = |args: &[&str]| parse_kill(&args.iter().map(|arg| String::from(*arg)).collect::<Vec<String>>());
+        assert_eq!(parse(&["%1"]), Ok((UnixSignal::Sigterm, vec![KillTarget::Job(1)])));
+        assert_eq!(parse(&["-KILL", "%2", "1234"]), Ok((UnixSignal::Sigkill, vec![KillTarget::Job(2), KillTarget::Process(1234)])));
+        assert_eq!(parse(&["-SIGINT", "1234"]), Ok((UnixSignal::Sigint, vec![KillTarget::Process(1234)])));
+        assert_eq!(parse(&["-9", "1234"]), Ok((UnixSignal::Sigkill, vec![KillTarget::Process(1234)])));
+        assert_eq!(parse(&["-s", "hup", "%1"]), Ok((UnixSignal::Sighup, vec![KillTarget::Job(1)])));
+        //Bad arguments
+        assert_eq!(parse(&[]), Err(String::from("usage: kill [-SIGNAL] %N|PID...")));
+        assert_eq!(parse(&["-TERM"]), Err(String::from("usage: kill [-SIGNAL] %N|PID...")));
+        assert_eq!(parse(&["-DIE", "1234"]), Err(String::from("DIE: invalid signal specification")));
+        assert_eq!(parse(&["-s"]), Err(String::from("-s: option requires an argument")));
+        assert_eq!(parse(&["%x"]), Err(String::from("%x: no such job")));
+        assert_eq!(parse(&["firefox"]), Err(String::from("firefox: arguments must be process or job IDs")));
+        assert_eq!(parse(&["0"]), Err(String::from("0: arguments must be process or job IDs")));
+    }
+
+    #[test]
+    fn test_builtins_handles_kill() {
+        let handles = |line: &str| {
+            let args: Vec<String> = line.split_whitespace().skip(1).map(String::from).collect();
+            handles_kill(line, &args)
+        };
+        assert!(handles("kill %1"));
+        assert!(handles("kill -KILL %2 1234"));
+        assert!(handles("kill -DIE %1"));
+        assert!(handles("kill 1234"));
+        assert!(handles("kill -9 1234"));
+        //Left to the shell
+        assert!(!handles("kill -l"));
+        assert!(!handles("kill -- -1234"));
+        assert!(!handles("kill $(pgrep x)"));
+        assert!(!handles("kill `pgrep x`"));
+        assert!(!handles("kill 1234 && echo ok"));
+        assert!(!handles("kill 1234; echo ok"));
+        assert!(!handles("kill %1 && echo ok"));
+        assert!(!handles("kill %1 | cat"));
+        assert!(!handles("kill firefox"));
+        assert!(!handles("kill"));
+    }
+
+    #[test]
+    fn test_builtins_find() {
+        assert_eq!(find("bg").unwrap().usage, "bg [%N]");
+        assert!(find("ls").is_none());
+    }
+}
@@ -0,0 +1,275 @@
+//! ## Definitions
+//!
+//! `definitions` contains the subcommands and the flags of well-known commands, used to complete their arguments
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use crate::config::{ConfigError, ConfigErrorCode};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use yaml_rust::{Yaml, YamlLoader};
+
+/// Definitions shipped with Pyc
+const DEFAULT_DEFINITIONS: &str = include_str!("definitions.yml");
+
+/// ### CommandDefinition
+///
+/// CommandDefinition contains the subcommands and the flags of a command
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct CommandDefinition {
+    pub subcommands: Vec<String>,
+    pub flags: Vec<String>,
+}
+
+/// ### CompletionDefinitions
+///
+/// CompletionDefinitions associates a command name to its definition
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct CompletionDefinitions {
+    commands: HashMap<String, CommandDefinition>,
+}
+
+impl CompletionDefinitions {
+    /// ### load
+    ///
+    /// Load the shipped definitions and merge them with the user definitions in `user_file`, if it exists.
+    /// A user file which can't be parsed is reported as error; the shipped definitions are still returned
+    pub fn load(user_file: Option<&Path>) -> (CompletionDefinitions, Option<ConfigError>) {
+        let mut definitions: CompletionDefinitions = match CompletionDefinitions::parse_str(DEFAULT_DEFINITIONS) {
+            Ok(definitions) => definitions,
+            Err(_) => CompletionDefinitions::empty(),
+        };
+        let user_file: &Path = match user_file {
+            Some(path) if path.exists() => path,
+            _ => return (definitions, None),
+        };
+        let user_definitions: Result<CompletionDefinitions, ConfigError> = match std::fs::read_to_string(user_file) {
+            Ok(content) => CompletionDefinitions::parse_str(content.as_str()),
+            Err(_) => Err(ConfigError {
+                code: ConfigErrorCode::CouldNotReadFile,
+                message: format!("Could not read file {}", user_file.display()),
+            }),
+        };
+        match user_definitions {
+            Ok(user_definitions) => {
+                definitions.merge(user_definitions);
+                (definitions, None)
+            }
+            Err(err) => (definitions, Some(err)),
+        }
+    }
+
+    /// ### user_file
+    ///
+    /// Returns the path of the user definitions file (`~/.config/pyc/completions.yml`)
+    pub fn user_file() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".config/pyc/completions.yml"))
+    }
+
+    /// ### empty
+    ///
+    /// Instantiates definitions without any command
+    pub fn empty() -> CompletionDefinitions {
+        CompletionDefinitions {
+            commands: HashMap::new(),
+        }
+    }
+
+    /// ### parse_str
+    ///
+    /// Parse definitions from a YAML document. The document is a map of command names,
+    /// each one with the optional `subcommands` and `flags` lists
+    pub fn parse_str(definitions: &str) -> Result<CompletionDefinitions, ConfigError> {
+        let yaml_docs: Vec<Yaml> = match YamlLoader::load_from_str(definitions) {
+            Ok(doc) => doc,
+            Err(_) => return Err(CompletionDefinitions::syntax_error(String::from("Completion definitions are not a valid YAML"))),
+        };
+        let mut commands: HashMap<String, CommandDefinition> = HashMap::new();
+        //An empty document has no definitions
+        let yaml_doc: &Yaml = match yaml_docs.first() {
+            Some(doc) => doc,
+            None => return Ok(CompletionDefinitions { commands }),
+        };
+        let map = match yaml_doc.as_hash() {
+            Some(map) => map,
+            None => return Err(CompletionDefinitions::syntax_error(String::from("Completion definitions are not a map"))),
+        };
+        for (name, definition) in map.iter() {
+            let name: String = match name.as_str() {
+                Some(name) => String::from(name),
+                None => return Err(CompletionDefinitions::syntax_error(String::from("Command name is not a string"))),
+            };
+            let subcommands: Vec<String> = CompletionDefinitions::parse_list(definition, &name, "subcommands")?;
+            let flags: Vec<String> = CompletionDefinitions::parse_list(definition, &name, "flags")?;
+            commands.insert(
+                name,
+                CommandDefinition {
+                    subcommands,
+                    flags,
+                },
+            );
+        }
+        Ok(CompletionDefinitions { commands })
+    }
+
+    /// ### merge
+    ///
+    /// Merge other definitions into these ones; subcommands and flags of the same command are joined
+    pub fn merge(&mut self, other: CompletionDefinitions) {
+        for (name, definition) in other.commands.into_iter() {
+            let entry: &mut CommandDefinition = self.commands.entry(name).or_insert(CommandDefinition {
+                subcommands: Vec::new(),
+                flags: Vec::new(),
+            });
+            for subcommand in definition.subcommands.into_iter() {
+                if !entry.subcommands.contains(&subcommand) {
+                    entry.subcommands.push(subcommand);
+                }
+            }
+            for flag in definition.flags.into_iter() {
+                if !entry.flags.contains(&flag) {
+                    entry.flags.push(flag);
+                }
+            }
+        }
+    }
+
+    /// ### get
+    ///
+    /// Get the definition of a command
+    pub fn get(&self, command: &str) -> Option<&CommandDefinition> {
+        self.commands.get(command)
+    }
+
+    /// ### parse_list
+    ///
+    /// Parse an optional list of strings of a command definition
+    fn parse_list(definition: &Yaml, name: &str, key: &str) -> Result<Vec<String>, ConfigError> {
+        let list: &Yaml = &definition[key];
+        if list.is_badvalue() || list.is_null() {
+            return Ok(Vec::new());
+        }
+        let list: &Vec<Yaml> = match list.as_vec() {
+            Some(list) => list,
+            None => return Err(CompletionDefinitions::syntax_error(format!("'{}.{}' is not an array", name, key))),
+        };
+        let mut items: Vec<String> = Vec::with_capacity(list.len());
+        for item in list.iter() {
+            match item {
+                Yaml::String(s) => items.push(s.clone()),
+                Yaml::Integer(i) => items.push(i.to_string()), //e.g. `- -4` is parsed as number
+                _ => return Err(CompletionDefinitions::syntax_error(format!("'{}.{}' item is not a string", name, key))),
+            }
+        }
+        Ok(items)
+    }
+
+    fn syntax_error(message: String) -> ConfigError {
+        ConfigError {
+            code: ConfigErrorCode::YamlSyntaxError,
+            message,
+        }
+    }
+}
+
+impl CommandDefinition {
+    /// ### short_flags
+    ///
+    /// Returns the letters of the single-letter flags (e.g. `-x`), which can be grouped together
+    pub fn short_flags(&self) -> Vec<char> {
+        self.flags
+            .iter()
+            .filter_map(|flag| {
+                let letters: Vec<char> = flag.chars().collect();
+                match letters.len() == 2 && letters[0] == '-' && letters[1] != '-' {
+                    true => Some(letters[1]),
+                    false => None,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::io::Write;
+
+    const FIXTURE: &str = "git:\n  subcommands:\n    - checkout\n    - cherry-pick\n    - commit\n  flags:\n    - --version\ntar:\n  flags:\n    - -x\n    - -v\n    - -f\n    - --extract\n";
+
+    #[test]
+    fn test_completion_definitions_parse() {
+        let definitions: CompletionDefinitions = CompletionDefinitions::parse_str(FIXTURE).ok().unwrap();
+        let git: &CommandDefinition = definitions.get("git").unwrap();
+        assert_eq!(git.subcommands, vec![String::from("checkout"), String::from("cherry-pick"), String::from("commit")]);
+        assert_eq!(git.flags, vec![String::from("--version")]);
+        let tar: &CommandDefinition = definitions.get("tar").unwrap();
+        assert_eq!(tar.subcommands.len(), 0);
+        assert_eq!(tar.short_flags(), vec!['x', 'v', 'f']);
+        assert!(definitions.get("ls").is_none());
+        //Empty document
+        assert!(CompletionDefinitions::parse_str("").ok().unwrap().get("git").is_none());
+    }
+
+    #[test]
+    fn test_completion_definitions_parse_bad() {
+        assert_eq!(CompletionDefinitions::parse_str("git: [").err().unwrap().code, ConfigErrorCode::YamlSyntaxError);
+        assert!(CompletionDefinitions::parse_str("- git").is_err());
+        assert!(CompletionDefinitions::parse_str("git:\n  flags: -x\n").is_err());
+        assert!(CompletionDefinitions::parse_str("git:\n  flags:\n    - [a]\n").is_err());
+    }
+
+    #[test]
+    fn test_completion_definitions_defaults() {
+        let (definitions, err): (CompletionDefinitions, Option<ConfigError>) = CompletionDefinitions::load(None);
+        assert!(err.is_none());
+        for command in ["git", "tar", "ssh", "docker"].iter() {
+            assert!(definitions.get(command).is_some());
+        }
+        assert!(definitions.get("ssh").unwrap().short_flags().contains(&'4'));
+    }
+
+    #[test]
+    fn test_completion_definitions_merge() {
+        let mut user_file: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        write!(user_file, "git:\n  subcommands:\n    - checkout\n    - lfs\npyc:\n  flags:\n    - --lang\n").unwrap();
+        let (definitions, err): (CompletionDefinitions, Option<ConfigError>) = CompletionDefinitions::load(Some(user_file.path()));
+        assert!(err.is_none());
+        let git: &CommandDefinition = definitions.get("git").unwrap();
+        assert!(git.subcommands.contains(&String::from("lfs")));
+        assert!(git.subcommands.contains(&String::from("cherry-pick")));
+        assert_eq!(git.subcommands.iter().filter(|s| *s == "checkout").count(), 1);
+        assert_eq!(definitions.get("pyc").unwrap().flags, vec![String::from("--lang")]);
+        //Bad user file
+        let mut user_file: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        write!(user_file, "git: [").unwrap();
+        let (definitions, err): (CompletionDefinitions, Option<ConfigError>) = CompletionDefinitions::load(Some(user_file.path()));
+        assert!(err.is_some());
+        assert!(definitions.get("git").is_some());
+        //Missing user file
+        let (_, err): (CompletionDefinitions, Option<ConfigError>) = CompletionDefinitions::load(Some(Path::new("/tmp/nosuchdir/completions.yml")));
+        assert!(err.is_none());
+    }
+}
@@ -0,0 +1,159 @@
+//! ## Index
+//!
+//! `index` provides the index of the executables found in the `PATH` directories, used to complete command names
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::is_executable;
+
+use crate::utils::buffer;
+
+#[cfg(test)]
+thread_local! {
+    /// Number of indexes built by the current thread
+    pub(crate) static INDEXES_BUILT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// ### CommandIndex
+///
+/// CommandIndex contains the sorted names of the executables in the directories of a `PATH`.
+/// The modification time of each directory is kept to tell when the index is no longer up to date
+pub struct CommandIndex {
+    path: Option<OsString>,
+    dirs: Vec<(PathBuf, Option<SystemTime>)>,
+    commands: Vec<String>,
+}
+
+impl CommandIndex {
+    /// ### build
+    ///
+    /// Build the index scanning the directories in `path`
+    pub fn build(path: Option<OsString>) -> CommandIndex {
+        #[cfg(test)]
+        INDEXES_BUILT.with(|count| count.set(count.get() + 1));
+        let mut dirs: Vec<(PathBuf, Option<SystemTime>)> = Vec::new();
+        let mut commands: Vec<String> = Vec::new();
+        if let Some(path) = path.as_ref() {
+            for dir in env::split_paths(path) {
+                let mtime: Option<SystemTime> = CommandIndex::mtime(&dir);
+                if let Ok(entries) = fs::read_dir(dir.as_path()) {
+                    for entry in entries.flatten() {
+                        if is_executable(entry.path().as_path()) {
+                            commands.push(buffer::os_str_to_string(&entry.file_name()));
+                        }
+                    }
+                }
+                dirs.push((dir, mtime));
+            }
+        }
+        commands.sort();
+        commands.dedup();
+        CommandIndex {
+            path,
+            dirs,
+            commands,
+        }
+    }
+
+    /// ### is_stale
+    ///
+    /// Returns whether the index has to be built again, since `path` has changed
+    /// or an executable has been added to or removed from one of its directories
+    pub fn is_stale(&self, path: &Option<OsString>) -> bool {
+        self.path != *path || self.dirs.iter().any(|(dir, mtime)| CommandIndex::mtime(dir) != *mtime)
+    }
+
+    /// ### starting_with
+    ///
+    /// Returns the commands whose name starts with `prefix`
+    pub fn starting_with(&self, prefix: &str) -> Vec<String> {
+        let first: usize = self.commands.partition_point(|c| c.as_str() < prefix);
+        self.commands[first..].iter().take_while(|c| c.starts_with(prefix)).cloned().collect()
+    }
+
+    /// ### contains
+    ///
+    /// Returns whether `command` is in the index
+    pub fn contains(&self, command: &str) -> bool {
+        self.commands.binary_search_by(|c| c.as_str().cmp(command)).is_ok()
+    }
+
+    /// ### commands
+    ///
+    /// Returns the names of the executables, sorted
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    /// ### mtime
+    ///
+    /// Get the modification time of a directory
+    fn mtime(dir: &Path) -> Option<SystemTime> {
+        fs::metadata(dir).and_then(|metadata| metadata.modified()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs::File;
+    use std::os::unix::fs::PermissionsExt;
+    use std::thread::sleep;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_completion_index() {
+        let tmpdir: TempDir = TempDir::new().unwrap();
+        for (file, mode) in [("mkdir", 0o755), ("mknod", 0o644), ("nano", 0o755), ("make", 0o700)].iter() {
+            let path: PathBuf = tmpdir.path().join(file);
+            File::create(path.as_path()).unwrap();
+            fs::set_permissions(path.as_path(), fs::Permissions::from_mode(*mode)).unwrap();
+        }
+        let path: Option<OsString> = Some(OsString::from(tmpdir.path().as_os_str()));
+        let index: CommandIndex = CommandIndex::build(path.clone());
+        assert_eq!(index.starting_with("mk"), vec![String::from("mkdir")]);
+        assert_eq!(index.starting_with("ma"), vec![String::from("make")]);
+        assert_eq!(index.starting_with(""), vec![String::from("make"), String::from("mkdir"), String::from("nano")]);
+        assert!(index.starting_with("x").is_empty());
+        assert!(!index.is_stale(&path));
+        //PATH changed
+        assert!(index.is_stale(&None));
+        //Executable added
+        sleep(Duration::from_millis(10));
+        File::create(tmpdir.path().join("mkfs")).unwrap();
+        fs::set_permissions(tmpdir.path().join("mkfs"), fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(index.is_stale(&path));
+        let index: CommandIndex = CommandIndex::build(path.clone());
+        assert_eq!(index.starting_with("mk"), vec![String::from("mkdir"), String::from("mkfs")]);
+        //No PATH
+        assert!(CommandIndex::build(None).starting_with("").is_empty());
+    }
+}
@@ -0,0 +1,509 @@
+//! ## Completion
+//!
+//! `completion` provides the tab completion of the token the user is typing
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+pub mod definitions;
+pub mod index;
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::translator::ioprocessor::IOProcessor;
+use crate::utils::buffer;
+use definitions::{CommandDefinition, CompletionDefinitions};
+use index::CommandIndex;
+
+/// ### Completion
+///
+/// Completion contains the candidates for the token which is being completed.
+/// `start` and `end` are the char indexes of the token in the line
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct Completion {
+    pub start: usize,
+    pub end: usize,
+    pub candidates: Vec<String>,
+}
+
+/// Returns the names of the aliases starting with a prefix
+type AliasSearch<'a> = &'a dyn Fn(&str) -> Vec<String>;
+
+/// ### Completer
+///
+/// Completer completes the first token of the line with aliases and executables in `PATH`,
+/// the arguments of well-known commands with their subcommands and flags
+/// and any other token with files and directories
+pub struct Completer<'a> {
+    processor: &'a IOProcessor,
+    aliases: Vec<String>,
+    alias_search: Option<AliasSearch<'a>>,
+    wrkdir: PathBuf,
+    path: Option<OsString>,
+    definitions: Option<&'a CompletionDefinitions>,
+    index: Option<&'a CommandIndex>,
+}
+
+impl<'a> Completer<'a> {
+    /// ### new
+    ///
+    /// Instantiates a new Completer; executables are searched in the directories of the `PATH` environment variable
+    pub fn new(processor: &'a IOProcessor, aliases: Vec<String>, wrkdir: PathBuf) -> Completer<'a> {
+        Completer {
+            processor,
+            aliases,
+            alias_search: None,
+            wrkdir,
+            path: env::var_os("PATH"),
+            definitions: None,
+            index: None,
+        }
+    }
+
+    /// ### with_definitions
+    ///
+    /// Set the definitions used to complete the subcommands and the flags of well-known commands
+    pub fn with_definitions(mut self, definitions: &'a CompletionDefinitions) -> Completer<'a> {
+        self.definitions = Some(definitions);
+        self
+    }
+
+    /// ### with_alias_search
+    ///
+    /// Set the function which returns the aliases starting with a prefix (e.g. `Config::find_aliases`);
+    /// they're completed along with the aliases passed to `new`
+    pub fn with_alias_search(mut self, search: AliasSearch<'a>) -> Completer<'a> {
+        self.alias_search = Some(search);
+        self
+    }
+
+    /// ### with_index
+    ///
+    /// Set the index of the executables; if not set, the `PATH` directories are scanned at each completion
+    pub fn with_index(mut self, index: &'a CommandIndex) -> Completer<'a> {
+        self.index = Some(index);
+        self
+    }
+
+    /// ### with_path
+    ///
+    /// Set the directories (as in `PATH`) where the executables are searched
+    #[cfg(test)]
+    pub fn with_path(mut self, path: Option<OsString>) -> Completer<'a> {
+        self.path = path;
+        self
+    }
+
+    /// ### complete
+    ///
+    /// Complete the token which ends at `cursor`. The token is transliterated to latin before matching;
+    /// candidates are sorted and are written in latin, except for subcommands and flags typed in cyrillic
+    pub fn complete(&self, line: &[char], cursor: usize) -> Completion {
+        let cursor: usize = std::cmp::min(cursor, line.len());
+        let start: usize = line[..cursor].iter().rposition(|c| c.is_whitespace()).map(|p| p + 1).unwrap_or(0);
+        let raw_token: String = line[start..cursor].iter().collect();
+        let token: String = self.processor.text_to_latin(&raw_token);
+        let words: Vec<String> = line[..start].iter().collect::<String>().split_whitespace().map(String::from).collect();
+        let mut candidates: Vec<String> = match words.first() {
+            None if !token.contains('/') => self.complete_command(raw_token.as_str(), token.as_str()),
+            None => self.complete_path(token.as_str()),
+            Some(command) => {
+                let command: String = self.processor.text_to_latin(command);
+                match self.complete_definition(command.as_str(), words.len(), raw_token.as_str(), token.as_str()) {
+                    Some(candidates) => candidates,
+                    None => self.complete_path(token.as_str()),
+                }
+            }
+        };
+        candidates.sort();
+        candidates.dedup();
+        Completion {
+            start,
+            end: cursor,
+            candidates,
+        }
+    }
+
+    /// ### complete_command
+    ///
+    /// Complete a command name with aliases (matched both as typed and transliterated) and executables
+    fn complete_command(&self, raw_token: &str, token: &str) -> Vec<String> {
+        let mut candidates: Vec<String> = self
+            .aliases
+            .iter()
+            .filter(|a| a.starts_with(raw_token) || a.starts_with(token))
+            .cloned()
+            .collect();
+        if let Some(search) = self.alias_search {
+            candidates.extend(search(raw_token));
+            candidates.extend(search(token));
+        }
+        match self.index {
+            Some(index) => candidates.extend(index.starting_with(token)),
+            None => candidates.extend(CommandIndex::build(self.path.clone()).starting_with(token)),
+        }
+        candidates
+    }
+
+    /// ### complete_definition
+    ///
+    /// Complete the argument at position `argc` of `command` with the subcommands and the flags in its definition.
+    /// Returns None if the command has no definition or nothing matches.
+    /// If the token has been typed in cyrillic, candidates are written in cyrillic too
+    fn complete_definition(&self, command: &str, argc: usize, raw_token: &str, token: &str) -> Option<Vec<String>> {
+        let definition: &CommandDefinition = self.definitions?.get(command)?;
+        let mut candidates: Vec<String> = Vec::new();
+        if token.starts_with('-') {
+            candidates.extend(definition.flags.iter().filter(|f| self.matches(f, raw_token, token)).cloned());
+            //Group single-letter flags (e.g. `-xv` + `f`)
+            let short_flags: Vec<char> = definition.short_flags();
+            let letters: Vec<char> = token.chars().skip(1).collect();
+            if !letters.is_empty() && letters.iter().all(|l| short_flags.contains(l)) {
+                candidates.extend(
+                    short_flags
+                        .iter()
+                        .filter(|f| !letters.contains(f))
+                        .map(|f| format!("{}{}", token, f)),
+                );
+            }
+        } else if argc == 1 {
+            candidates.extend(definition.subcommands.iter().filter(|s| self.matches(s, raw_token, token)).cloned());
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+        match raw_token.chars().any(is_cyrillic) {
+            true => Some(candidates.iter().map(|c| self.to_cyrillic(c)).collect()),
+            false => Some(candidates),
+        }
+    }
+
+    /// ### matches
+    ///
+    /// Returns whether a latin candidate matches the token, either transliterated or as typed
+    fn matches(&self, candidate: &str, raw_token: &str, token: &str) -> bool {
+        candidate.starts_with(token) || (raw_token.chars().any(is_cyrillic) && self.to_cyrillic(candidate).starts_with(raw_token))
+    }
+
+    /// ### to_cyrillic
+    ///
+    /// Converts a latin candidate to cyrillic. Since transliteration is not always reversible,
+    /// the candidate is kept in latin if the cyrillic text wouldn't be transliterated back to it
+    fn to_cyrillic(&self, candidate: &str) -> String {
+        let cyrillic: String = self.processor.text_to_cyrillic(&String::from(candidate));
+        match self.processor.text_to_latin(&cyrillic) == candidate {
+            true => cyrillic,
+            false => String::from(candidate),
+        }
+    }
+
+    /// ### complete_path
+    ///
+    /// Complete a path with the files in its directory; directories end with '/'
+    fn complete_path(&self, token: &str) -> Vec<String> {
+        //Split token into directory and file name
+        let (dir_part, file_part): (&str, &str) = match token.rfind('/') {
+            Some(pos) => (&token[..pos + 1], &token[pos + 1..]),
+            None => ("", token),
+        };
+        let dir: PathBuf = match dir_part {
+            "" => self.wrkdir.clone(),
+            d if d.starts_with("~/") => match dirs::home_dir() {
+                Some(home) => home.join(&d[2..]),
+                None => return Vec::new(),
+            },
+            d => self.wrkdir.join(d), //NOTE: if d is absolute, join returns d
+        };
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                //Bytes which are not valid UTF-8 are kept, so that the exact name is written to the shell
+                let name: String = buffer::os_str_to_string(&entry.file_name());
+                //Hidden files are completed only if the user typed the dot
+                if !name.starts_with(file_part) || (name.starts_with('.') && !file_part.starts_with('.')) {
+                    return None;
+                }
+                let is_dir: bool = entry.path().is_dir();
+                Some(format!("{}{}{}", dir_part, name, if is_dir { "/" } else { "" }))
+            })
+            .collect()
+    }
+}
+
+impl Completion {
+    /// ### common_prefix
+    ///
+    /// Returns the longest prefix shared by all the candidates
+    pub fn common_prefix(&self) -> String {
+        let first: &String = match self.candidates.first() {
+            Some(first) => first,
+            None => return String::new(),
+        };
+        let mut prefix: Vec<char> = first.chars().collect();
+        for candidate in self.candidates.iter().skip(1) {
+            let len: usize = prefix.iter().zip(candidate.chars()).take_while(|(a, b)| **a == *b).count();
+            prefix.truncate(len);
+        }
+        prefix.into_iter().collect()
+    }
+
+    /// ### replacement
+    ///
+    /// Returns the text to replace the token with: the only candidate (followed by a space, unless it's a directory)
+    /// or the common prefix of the candidates
+    pub fn replacement(&self) -> Option<String> {
+        match self.candidates.len() {
+            0 => None,
+            1 => {
+                let candidate: &String = &self.candidates[0];
+                match candidate.ends_with('/') {
+                    true => Some(candidate.clone()),
+                    false => Some(format!("{} ", candidate)),
+                }
+            }
+            _ => Some(self.common_prefix()),
+        }
+    }
+
+    /// ### display_names
+    ///
+    /// Returns the candidates as they are listed to the user (paths are shown without their directory).
+    /// Bytes which are not valid UTF-8 are shown as replacement characters
+    pub fn display_names(&self) -> Vec<String> {
+        self.candidates
+            .iter()
+            .map(|c| {
+                let trimmed: &str = c.trim_end_matches('/');
+                match trimmed.rfind('/') {
+                    Some(pos) => buffer::printable(&c[pos + 1..]),
+                    None => buffer::printable(c),
+                }
+            })
+            .collect()
+    }
+}
+
+/// ### is_cyrillic
+///
+/// Returns whether the character is a cyrillic letter
+fn is_cyrillic(c: char) -> bool {
+    ('\u{0400}'..='\u{04FF}').contains(&c)
+}
+
+/// ### is_executable
+///
+/// Returns whether the path is an executable file
+pub(crate) fn is_executable(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::translator::lang::Language;
+    use crate::translator::new_translator;
+    use std::ffi::OsStr;
+    use std::fs::File;
+    use std::os::unix::ffi::OsStrExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_completion_files() {
+        let tmpdir: TempDir = make_fixture();
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let completer: Completer = Completer::new(&iop, Vec::new(), tmpdir.path().to_path_buf()).with_path(None);
+        //Single match
+        let completion: Completion = complete(&completer, "cat RE");
+        assert_eq!(completion.candidates, vec![String::from("README.md")]);
+        assert_eq!((completion.start, completion.end), (4, 6));
+        assert_eq!(completion.replacement().unwrap(), String::from("README.md "));
+        //Multiple matches
+        let completion: Completion = complete(&completer, "cat ma");
+        assert_eq!(completion.candidates, vec![String::from("main.rs"), String::from("makefile"), String::from("manual/")]);
+        assert_eq!(completion.replacement().unwrap(), String::from("ma"));
+        assert_eq!(completion.display_names(), vec![String::from("main.rs"), String::from("makefile"), String::from("manual/")]);
+        //Directory
+        let completion: Completion = complete(&completer, "ls man");
+        assert_eq!(completion.replacement().unwrap(), String::from("manual/"));
+        //Inside a directory
+        let completion: Completion = complete(&completer, "cat manual/");
+        assert_eq!(completion.candidates, vec![String::from("manual/intro.txt")]);
+        assert_eq!(completion.display_names(), vec![String::from("intro.txt")]);
+        //Hidden files
+        assert_eq!(complete(&completer, "cat ").candidates.len(), 5);
+        assert_eq!(complete(&completer, "cat .").candidates, vec![String::from(".hidden")]);
+        //No match
+        assert!(complete(&completer, "cat foo").replacement().is_none());
+        //Absolute path
+        let line: String = format!("cat {}/RE", tmpdir.path().display());
+        assert_eq!(complete(&completer, line.as_str()).candidates, vec![format!("{}/README.md", tmpdir.path().display())]);
+    }
+
+    #[test]
+    fn test_completion_cyrillic() {
+        let tmpdir: TempDir = make_fixture();
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let completer: Completer = Completer::new(&iop, Vec::new(), tmpdir.path().to_path_buf()).with_path(None);
+        //Token typed in cyrillic is completed in latin
+        let completion: Completion = complete(&completer, "кат маи");
+        assert_eq!(completion.candidates, vec![String::from("main.rs")]);
+        assert_eq!(completion.start, 4);
+        let completion: Completion = complete(&completer, "кат мануал/ин");
+        assert_eq!(completion.candidates, vec![String::from("manual/intro.txt")]);
+    }
+
+    #[test]
+    fn test_completion_commands() {
+        let tmpdir: TempDir = make_fixture();
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let aliases: Vec<String> = vec![String::from("чд"), String::from("mkcd")];
+        let completer: Completer = Completer::new(&iop, aliases, tmpdir.path().to_path_buf())
+            .with_path(Some(OsString::from(tmpdir.path().join("bin").as_os_str())));
+        //Executables and aliases
+        assert_eq!(complete(&completer, "mk").candidates, vec![String::from("mkcd"), String::from("mkdir")]);
+        assert_eq!(complete(&completer, "  mk").candidates, vec![String::from("mkcd"), String::from("mkdir")]);
+        assert_eq!(complete(&completer, "нан").candidates, vec![String::from("nano")]);
+        //Files which are not executable are not completed
+        assert!(complete(&completer, "mkn").candidates.is_empty());
+        //Cyrillic alias
+        assert_eq!(complete(&completer, "ч").candidates, vec![String::from("чд")]);
+        //Path to executable
+        assert_eq!(complete(&completer, "./ma").candidates, vec![String::from("./main.rs"), String::from("./makefile"), String::from("./manual/")]);
+        //Cursor in the middle of the line
+        let line: Vec<char> = "mk foo".chars().collect();
+        assert_eq!(completer.complete(&line, 2).candidates, vec![String::from("mkcd"), String::from("mkdir")]);
+        //Aliases found by prefix
+        let search = |prefix: &str| -> Vec<String> {
+            vec![String::from("mkproj"), String::from("гс")].into_iter().filter(|alias| alias.starts_with(prefix)).collect()
+        };
+        let completer: Completer = completer.with_alias_search(&search);
+        assert_eq!(
+            complete(&completer, "mk").candidates,
+            vec![String::from("mkcd"), String::from("mkdir"), String::from("mkproj")]
+        );
+        assert_eq!(complete(&completer, "г").candidates, vec![String::from("гс")]);
+    }
+
+    #[test]
+    fn test_completion_definitions() {
+        let tmpdir: TempDir = make_fixture();
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let definitions: CompletionDefinitions = CompletionDefinitions::parse_str(
+            "git:\n  subcommands:\n    - checkout\n    - cherry-pick\n    - commit\n  flags:\n    - --version\ntar:\n  flags:\n    - -x\n    - -v\n    - -f\n    - --extract\n",
+        )
+        .ok()
+        .unwrap();
+        let completer: Completer = Completer::new(&iop, Vec::new(), tmpdir.path().to_path_buf())
+            .with_path(None)
+            .with_definitions(&definitions);
+        //Subcommands; command typed in cyrillic
+        let completion: Completion = complete(&completer, "гит che");
+        assert_eq!(completion.candidates, vec![String::from("checkout"), String::from("cherry-pick")]);
+        assert_eq!(completion.replacement().unwrap(), String::from("che"));
+        assert_eq!(complete(&completer, "git com").replacement().unwrap(), String::from("commit "));
+        //Subcommand typed in cyrillic is completed in cyrillic
+        assert_eq!(complete(&completer, "гит комм").candidates, vec![String::from("коммит")]);
+        //Transliteration of `checkout` is not reversible: keep it in latin
+        assert_eq!(complete(&completer, "гит че").candidates, vec![String::from("checkout"), String::from("черры-пикк")]);
+        //Subcommands are completed only as first argument
+        assert_eq!(complete(&completer, "git commit ma").candidates.len(), 3);
+        //Flags
+        assert_eq!(complete(&completer, "git --v").candidates, vec![String::from("--version")]);
+        assert_eq!(complete(&completer, "git commit --v").candidates, vec![String::from("--version")]);
+        assert_eq!(complete(&completer, "tar --").candidates, vec![String::from("--extract")]);
+        let completion: Completion = complete(&completer, "tar -x");
+        assert_eq!(completion.candidates, vec![String::from("-x"), String::from("-xf"), String::from("-xv")]);
+        assert_eq!(complete(&completer, "tar -xv").candidates, vec![String::from("-xvf")]);
+        assert_eq!(complete(&completer, "тар -ксв").candidates, vec![String::from("-ксвф")]);
+        //Nothing matches: complete files
+        assert_eq!(complete(&completer, "git RE").candidates, vec![String::from("README.md")]);
+        assert_eq!(complete(&completer, "ls ma").candidates.len(), 3);
+    }
+
+    #[test]
+    fn test_completion_non_utf8_names() {
+        let tmpdir: TempDir = TempDir::new().unwrap();
+        //"Привет" in KOI8-R
+        let name: &OsStr = OsStr::from_bytes(b"\xf0\xd2\xc9\xd7\xc5\xd4.txt");
+        File::create(tmpdir.path().join(name)).unwrap();
+        File::create(tmpdir.path().join("report.txt")).unwrap();
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let completer: Completer = Completer::new(&iop, Vec::new(), tmpdir.path().to_path_buf()).with_path(None);
+        //Undecodable names are listed with replacement characters
+        let completion: Completion = complete(&completer, "cat ");
+        assert_eq!(completion.candidates.len(), 2);
+        assert_eq!(completion.display_names(), vec![String::from("report.txt"), String::from("\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}.txt")]);
+        //The candidate keeps the original bytes
+        let token: String = buffer::os_str_to_string(OsStr::from_bytes(b"\xf0\xd2"));
+        let completion: Completion = complete(&completer, format!("cat {}", token).as_str());
+        assert_eq!(completion.candidates.len(), 1);
+        assert_eq!(buffer::string_to_bytes(completion.replacement().unwrap().as_str()), b"\xf0\xd2\xc9\xd7\xc5\xd4.txt ".to_vec());
+    }
+
+    #[test]
+    fn test_completion_common_prefix() {
+        let completion: Completion = Completion {
+            start: 0,
+            end: 0,
+            candidates: vec![String::from("checkout"), String::from("cherry-pick"), String::from("check")],
+        };
+        assert_eq!(completion.common_prefix(), String::from("che"));
+        let completion: Completion = Completion {
+            start: 0,
+            end: 0,
+            candidates: Vec::new(),
+        };
+        assert_eq!(completion.common_prefix(), String::new());
+    }
+
+    fn complete(completer: &Completer, line: &str) -> Completion {
+        let line: Vec<char> = line.chars().collect();
+        completer.complete(&line, line.len())
+    }
+
+    /// ### make_fixture
+    ///
+    /// Make a temp directory with known files
+    fn make_fixture() -> TempDir {
+        let tmpdir: TempDir = TempDir::new().unwrap();
+        for file in ["README.md", "main.rs", "makefile", ".hidden", "manual/intro.txt", "bin/mkdir", "bin/mknod", "bin/nano"].iter() {
+            let path: PathBuf = tmpdir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(path.as_path()).unwrap();
+        }
+        fs::set_permissions(tmpdir.path().join("bin/mkdir"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(tmpdir.path().join("bin/nano"), fs::Permissions::from_mode(0o755)).unwrap();
+        tmpdir
+    }
+}
@@ -0,0 +1,266 @@
+//! ## Dirstack
+//!
+//! `dirstack` provides the directory stack and the previous working directory used by `cd`, `pushd`, `popd` and `dirs`
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate dirs;
+
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::utils::buffer;
+
+/// ### DirError
+///
+/// DirError describes why a directory builtin failed
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub enum DirError {
+    NoSuchDirectory(String),
+    NotADirectory(String),
+    HomeNotSet,
+    OldPwdNotSet,
+    EmptyStack,
+    NoOtherDirectory,
+}
+
+/// ### DirStack
+///
+/// DirStack contains the directories pushed with `pushd` (the last one is the top of the stack)
+/// and the previous working directory (OLDPWD)
+#[derive(Clone, Default, std::fmt::Debug)]
+pub struct DirStack {
+    stack: Vec<PathBuf>,
+    oldpwd: Option<PathBuf>,
+}
+
+impl fmt::Display for DirError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DirError::NoSuchDirectory(dir) => write!(f, "{}: No such file or directory", dir),
+            DirError::NotADirectory(dir) => write!(f, "{}: Not a directory", dir),
+            DirError::HomeNotSet => write!(f, "HOME not set"),
+            DirError::OldPwdNotSet => write!(f, "OLDPWD not set"),
+            DirError::EmptyStack => write!(f, "directory stack empty"),
+            DirError::NoOtherDirectory => write!(f, "no other directory"),
+        }
+    }
+}
+
+impl DirStack {
+    /// ### new
+    ///
+    /// Instantiates a new empty DirStack
+    pub fn new() -> DirStack {
+        DirStack {
+            stack: Vec::new(),
+            oldpwd: None,
+        }
+    }
+
+    /// ### cd
+    ///
+    /// Resolve the directory to change to from the working directory: the home directory if `dir` is None,
+    /// the previous working directory if `dir` is `-`
+    pub fn cd(&mut self, dir: Option<&str>, wrkdir: &Path, to_latin: &dyn Fn(&str) -> String) -> Result<PathBuf, DirError> {
+        let target: PathBuf = match dir {
+            None => dirs::home_dir().ok_or(DirError::HomeNotSet)?,
+            Some("-") => self.oldpwd.clone().ok_or(DirError::OldPwdNotSet)?,
+            Some(dir) => resolve_dir(dir, wrkdir, to_latin)?,
+        };
+        self.oldpwd = Some(wrkdir.to_path_buf());
+        Ok(target)
+    }
+
+    /// ### pushd
+    ///
+    /// Push the working directory onto the stack and resolve the directory to change to.
+    /// Without `dir`, the working directory is exchanged with the top of the stack
+    pub fn pushd(&mut self, dir: Option<&str>, wrkdir: &Path, to_latin: &dyn Fn(&str) -> String) -> Result<PathBuf, DirError> {
+        let target: PathBuf = match dir {
+            None => self.stack.pop().ok_or(DirError::NoOtherDirectory)?,
+            Some(dir) => resolve_dir(dir, wrkdir, to_latin)?,
+        };
+        self.stack.push(wrkdir.to_path_buf());
+        self.oldpwd = Some(wrkdir.to_path_buf());
+        Ok(target)
+    }
+
+    /// ### popd
+    ///
+    /// Remove the top of the stack, which is the directory to change to
+    pub fn popd(&mut self, wrkdir: &Path) -> Result<PathBuf, DirError> {
+        let target: PathBuf = self.stack.pop().ok_or(DirError::EmptyStack)?;
+        self.oldpwd = Some(wrkdir.to_path_buf());
+        Ok(target)
+    }
+
+    /// ### oldpwd
+    ///
+    /// Returns the previous working directory, if any
+    pub fn oldpwd(&self) -> Option<&Path> {
+        self.oldpwd.as_deref()
+    }
+
+    /// ### dirs
+    ///
+    /// Returns the directory stack as printed by `dirs`: the working directory first, then the stack from its top
+    pub fn dirs(&self, wrkdir: &Path) -> String {
+        let mut dirs: Vec<String> = vec![abbreviate_home(wrkdir)];
+        dirs.extend(self.stack.iter().rev().map(|dir| abbreviate_home(dir.as_path())));
+        dirs.join(" ")
+    }
+}
+
+/// ### resolve_dir
+///
+/// Resolve a directory argument relative to the working directory, expanding `~` to the home directory.
+/// The directory is looked up as it has been typed first, then transliterated to latin
+pub fn resolve_dir(dir: &str, wrkdir: &Path, to_latin: &dyn Fn(&str) -> String) -> Result<PathBuf, DirError> {
+    let literal: PathBuf = expand_home(dir, wrkdir)?;
+    if literal.is_dir() {
+        return Ok(literal);
+    }
+    let translated: PathBuf = expand_home(to_latin(dir).as_str(), wrkdir)?;
+    if translated.is_dir() {
+        return Ok(translated);
+    }
+    match literal.exists() || translated.exists() {
+        true => Err(DirError::NotADirectory(String::from(dir))),
+        false => Err(DirError::NoSuchDirectory(String::from(dir))),
+    }
+}
+
+/// ### expand_home
+///
+/// Expand a leading `~` to the home directory and join relative paths to the working directory.
+/// Raw bytes of file names which are not valid UTF-8 are restored (see `buffer::os_str_to_string`)
+fn expand_home(dir: &str, wrkdir: &Path) -> Result<PathBuf, DirError> {
+    if dir == "~" || dir.starts_with("~/") {
+        let home: PathBuf = dirs::home_dir().ok_or(DirError::HomeNotSet)?;
+        return Ok(normalize(home.join(buffer::string_to_os_string(dir[1..].trim_start_matches('/'))).as_path()));
+    }
+    Ok(normalize(wrkdir.join(buffer::string_to_os_string(dir)).as_path()))
+}
+
+/// ### normalize
+///
+/// Remove `.` and `..` components from path, without resolving symbolic links (as `cd` does)
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized: PathBuf = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// ### abbreviate_home
+///
+/// Replace the home directory at the beginning of path with `~`
+fn abbreviate_home(path: &Path) -> String {
+    match dirs::home_dir() {
+        Some(home) if path == home => String::from("~"),
+        Some(home) if path.starts_with(&home) => format!("~/{}", path.strip_prefix(&home).unwrap().display()),
+        _ => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn test_dirstack_sequence() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let root: PathBuf = tmpdir.path().to_path_buf();
+        fs::create_dir(root.join("alpha")).unwrap();
+        fs::create_dir(root.join("документы")).unwrap();
+        fs::create_dir(root.join("beta")).unwrap();
+        fs::write(root.join("file"), "").unwrap();
+        let to_latin = |dir: &str| dir.replace("бета", "beta");
+        let mut stack: DirStack = DirStack::new();
+        let mut cwd: PathBuf = root.clone();
+        //cd - without OLDPWD
+        assert_eq!(stack.cd(Some("-"), &cwd, &to_latin).err().unwrap(), DirError::OldPwdNotSet);
+        //cd
+        cwd = stack.cd(Some("alpha"), &cwd, &to_latin).unwrap();
+        assert_eq!(cwd, root.join("alpha"));
+        assert_eq!(stack.oldpwd(), Some(root.as_path()));
+        //Cyrillic directory which exists is used as it is
+        cwd = stack.cd(Some("../документы"), &cwd, &to_latin).unwrap();
+        assert_eq!(cwd, root.join("документы"));
+        //cd - goes back and forth
+        cwd = stack.cd(Some("-"), &cwd, &to_latin).unwrap();
+        assert_eq!(cwd, root.join("alpha"));
+        cwd = stack.cd(Some("-"), &cwd, &to_latin).unwrap();
+        assert_eq!(cwd, root.join("документы"));
+        //pushd with a transliterated directory
+        cwd = root.clone();
+        cwd = stack.pushd(Some("бета"), &cwd, &to_latin).unwrap();
+        assert_eq!(cwd, root.join("beta"));
+        cwd = stack.pushd(Some(root.join("alpha").to_str().unwrap()), &cwd, &to_latin).unwrap();
+        assert_eq!(cwd, root.join("alpha"));
+        assert_eq!(stack.dirs(&cwd), format!("{} {} {}", root.join("alpha").display(), root.join("beta").display(), root.display()));
+        //pushd without arguments exchanges the top two directories
+        cwd = stack.pushd(None, &cwd, &to_latin).unwrap();
+        assert_eq!(cwd, root.join("beta"));
+        assert_eq!(stack.dirs(&cwd), format!("{} {} {}", root.join("beta").display(), root.join("alpha").display(), root.display()));
+        //popd
+        cwd = stack.popd(&cwd).unwrap();
+        assert_eq!(cwd, root.join("alpha"));
+        cwd = stack.popd(&cwd).unwrap();
+        assert_eq!(cwd, root);
+        assert_eq!(stack.popd(&cwd).err().unwrap(), DirError::EmptyStack);
+        assert_eq!(stack.pushd(None, &cwd, &to_latin).err().unwrap(), DirError::NoOtherDirectory);
+        //Errors
+        assert_eq!(stack.cd(Some("gamma"), &cwd, &to_latin).err().unwrap(), DirError::NoSuchDirectory(String::from("gamma")));
+        assert_eq!(stack.cd(Some("file"), &cwd, &to_latin).err().unwrap(), DirError::NotADirectory(String::from("file")));
+        assert_eq!(stack.pushd(Some("gamma"), &cwd, &to_latin).err().unwrap(), DirError::NoSuchDirectory(String::from("gamma")));
+        //A failed pushd doesn't change the stack
+        assert_eq!(stack.dirs(&cwd), root.display().to_string());
+        assert_eq!(DirError::EmptyStack.to_string(), String::from("directory stack empty"));
+    }
+
+    #[test]
+    fn test_dirstack_home() {
+        let home: PathBuf = dirs::home_dir().unwrap();
+        let to_latin = |dir: &str| String::from(dir);
+        let mut stack: DirStack = DirStack::new();
+        assert_eq!(stack.cd(None, Path::new("/"), &to_latin).unwrap(), home);
+        assert_eq!(stack.cd(Some("~"), Path::new("/"), &to_latin).unwrap(), home);
+        assert_eq!(abbreviate_home(home.as_path()), String::from("~"));
+        assert_eq!(abbreviate_home(home.join("src").as_path()), String::from("~/src"));
+        assert_eq!(expand_home("~/src", Path::new("/")).unwrap(), home.join("src"));
+        assert_eq!(expand_home("src", Path::new("/tmp")).unwrap(), PathBuf::from("/tmp/src"));
+        assert_eq!(expand_home("../src/./lib", Path::new("/tmp/a")).unwrap(), PathBuf::from("/tmp/src/lib"));
+        assert_eq!(normalize(Path::new("/../..")), PathBuf::from("/"));
+    }
+}
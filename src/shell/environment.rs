@@ -0,0 +1,205 @@
+//! ## Environment
+//!
+//! `environment` provides the builtins which change the environment of pyc and of the commands it runs (`export`, `unset`, `env`)
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use std::env;
+use std::fmt;
+use std::path::Path;
+
+use super::expansion::is_name;
+
+/// ### EnvError
+///
+/// EnvError describes why an environment builtin failed
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub enum EnvError {
+    InvalidIdentifier(String),
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EnvError::InvalidIdentifier(name) => write!(f, "`{}': not a valid identifier", name),
+        }
+    }
+}
+
+/// ### split_words
+///
+/// Split the arguments of a builtin into words, removing quotes and backslashes.
+/// Returns None if the arguments contain something which only the shell can handle (operators, expansions, substitutions)
+pub fn split_words(input: &str) -> Option<Vec<String>> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut backslash: bool = false;
+    for c in input.chars() {
+        if backslash {
+            backslash = false;
+            word.get_or_insert_with(String::new).push(c);
+            continue;
+        }
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (Some(_), '$') | (Some(_), '`') => return None,
+            (Some(_), '\\') => backslash = true,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, '\\') => backslash = true,
+            (None, c) if c.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            (None, ';') | (None, '&') | (None, '|') | (None, '<') | (None, '>') | (None, '(') | (None, ')') | (None, '$') | (None, '`') | (None, '#') => return None,
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() || backslash {
+        return None;
+    }
+    if let Some(word) = word {
+        words.push(word);
+    }
+    Some(words)
+}
+
+/// ### export
+///
+/// Export variables to pyc's environment, so that the commands it runs inherit them; each argument is `NAME=value` or `NAME`.
+/// Names are converted with `to_latin`, values are kept as they are. Returns the command which exports them to the shell
+pub fn export(args: &[String], to_latin: &dyn Fn(&str) -> String) -> Result<String, EnvError> {
+    let mut assignments: Vec<(String, Option<String>)> = Vec::with_capacity(args.len());
+    for arg in args.iter() {
+        let (name, value): (&str, Option<&str>) = match arg.find('=') {
+            Some(pos) => (&arg[..pos], Some(&arg[pos + 1..])),
+            None => (arg.as_str(), None),
+        };
+        let name: String = to_latin(name);
+        if !is_name(name.as_str()) {
+            return Err(EnvError::InvalidIdentifier(name));
+        }
+        assignments.push((name, value.map(String::from)));
+    }
+    let mut command: String = String::from("export");
+    for (name, value) in assignments.into_iter() {
+        match value {
+            Some(value) => {
+                command.push_str(format!(" {}={}", name, quote(value.as_str())).as_str());
+                env::set_var(name, value);
+            }
+            None => command.push_str(format!(" {}", name).as_str()),
+        }
+    }
+    Ok(command)
+}
+
+/// ### unset
+///
+/// Remove variables from pyc's environment. Names are converted with `to_latin`.
+/// Returns the command which removes them from the shell
+pub fn unset(args: &[String], to_latin: &dyn Fn(&str) -> String) -> Result<String, EnvError> {
+    let names: Vec<String> = args.iter().map(|arg| to_latin(arg.as_str())).collect();
+    if let Some(name) = names.iter().find(|name| !is_name(name.as_str())) {
+        return Err(EnvError::InvalidIdentifier(name.clone()));
+    }
+    for name in names.iter() {
+        env::remove_var(name);
+    }
+    Ok(format!("unset {}", names.join(" ")))
+}
+
+/// ### list_exports
+///
+/// List the variables of pyc's environment as `export NAME='value'`, sorted by name
+pub fn list_exports() -> Vec<String> {
+    let mut vars: Vec<(String, String)> = env::vars().collect();
+    vars.sort();
+    vars.into_iter()
+        .map(|(name, value)| format!("export {}={}", name, quote(value.as_str())))
+        .collect()
+}
+
+/// ### list_env
+///
+/// List the variables of the environment of the commands as `NAME=value`, sorted by name; `PWD` is the working directory of the shell
+pub fn list_env(wrkdir: &Path) -> Vec<String> {
+    let mut vars: Vec<(String, String)> = env::vars().filter(|(name, _)| name != "PWD").collect();
+    vars.push((String::from("PWD"), wrkdir.display().to_string()));
+    vars.sort();
+    vars.into_iter().map(|(name, value)| format!("{}={}", name, value)).collect()
+}
+
+/// ### quote
+///
+/// Single quote a value for the shell
+pub(crate) fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_environment_split_words() {
+        assert_eq!(split_words("A=1 B=\"два три\" C='$x' D=a\\ b").unwrap(), vec![
+            String::from("A=1"), String::from("B=два три"), String::from("C=$x"), String::from("D=a b")
+        ]);
+        assert_eq!(split_words("  A=''  ").unwrap(), vec![String::from("A=")]);
+        assert_eq!(split_words("").unwrap().len(), 0);
+        //Left to the shell
+        assert!(split_words("A=1; ls").is_none());
+        assert!(split_words("A=1 && ls").is_none());
+        assert!(split_words("A=$HOME").is_none());
+        assert!(split_words("A=\"$(pwd)\"").is_none());
+        assert!(split_words("A=\"unterminated").is_none());
+    }
+
+    #[test]
+    fn test_environment_export_unset() {
+        let to_latin = |name: &str| name.replace("ИМЯ", "NAME");
+        let command: String = export(&[String::from("PYC_ENV_ИМЯ=значение с пробелом"), String::from("PYC_ENV_OTHER")], &to_latin).unwrap();
+        assert_eq!(command, String::from("export PYC_ENV_NAME='значение с пробелом' PYC_ENV_OTHER"));
+        assert_eq!(env::var("PYC_ENV_NAME").unwrap(), String::from("значение с пробелом"));
+        assert!(env::var("PYC_ENV_OTHER").is_err());
+        assert!(list_exports().contains(&String::from("export PYC_ENV_NAME='значение с пробелом'")));
+        assert!(list_env(Path::new("/tmp")).contains(&String::from("PYC_ENV_NAME=значение с пробелом")));
+        assert!(list_env(Path::new("/tmp")).contains(&String::from("PWD=/tmp")));
+        //Quotes in value
+        assert_eq!(export(&[String::from("PYC_ENV_QUOTE=it's")], &to_latin).unwrap(), String::from("export PYC_ENV_QUOTE='it'\\''s'"));
+        //Unset
+        assert_eq!(unset(&[String::from("PYC_ENV_ИМЯ"), String::from("PYC_ENV_QUOTE")], &to_latin).unwrap(), String::from("unset PYC_ENV_NAME PYC_ENV_QUOTE"));
+        assert!(env::var("PYC_ENV_NAME").is_err());
+        //Bad names
+        assert_eq!(export(&[String::from("1A=b")], &to_latin).err().unwrap(), EnvError::InvalidIdentifier(String::from("1A")));
+        assert!(unset(&[String::from("A-B")], &to_latin).is_err());
+        assert_eq!(EnvError::InvalidIdentifier(String::from("1A")).to_string(), String::from("`1A': not a valid identifier"));
+    }
+}
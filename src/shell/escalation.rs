@@ -0,0 +1,232 @@
+//! ## Escalation
+//!
+//! `escalation` decides when an interrupted command, or a command which runs out of time, has to be terminated and then killed
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use crate::config::SigintEscalation;
+
+use std::time::{Duration, Instant};
+
+/// ### InterruptAction
+///
+/// InterruptAction is the signal to send to the running command when Ctrl+C is pressed
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum InterruptAction {
+    Interrupt, //SIGINT
+    Terminate, //SIGTERM
+}
+
+/// ### Escalation
+///
+/// Escalation keeps track of the interrupts sent to the running command: once it has been interrupted `presses` times
+/// within `window`, it's terminated; if it's still running `kill_after` later, it has to be killed
+pub struct Escalation {
+    presses: usize,
+    window: Duration,
+    kill_after: Duration,
+    interrupts: Vec<Instant>,
+    terminated: Option<Instant>, //When the command has been terminated
+}
+
+/// ### TimeoutAction
+///
+/// TimeoutAction is the signal to send to the command which has run out of time
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum TimeoutAction {
+    Terminate, //SIGTERM
+    Kill,      //SIGKILL
+}
+
+/// ### Timeout
+///
+/// Timeout terminates the command which is still running once `limit` has elapsed since it started;
+/// if it's still running `kill_after` later, it has to be killed
+pub struct Timeout {
+    deadline: Instant,
+    kill_after: Duration,
+    terminated: Option<Instant>, //When the command has been terminated
+    killed: bool,
+}
+
+impl Escalation {
+    /// ### new
+    ///
+    /// Instantiate a new Escalation with the provided configuration
+    pub fn new(config: &SigintEscalation) -> Escalation {
+        Escalation {
+            presses: config.presses,
+            window: Duration::from_millis(config.window_ms as u64),
+            kill_after: Duration::from_millis(config.kill_after_ms as u64),
+            interrupts: Vec::new(),
+            terminated: None,
+        }
+    }
+
+    /// ### interrupt
+    ///
+    /// Record an interrupt sent at `now` and returns the signal to send to the command
+    pub fn interrupt(&mut self, now: Instant) -> InterruptAction {
+        if self.presses == 0 || self.terminated.is_some() {
+            return InterruptAction::Interrupt;
+        }
+        let window: Duration = self.window;
+        self.interrupts.retain(|sent| now.duration_since(*sent) <= window);
+        self.interrupts.push(now);
+        match self.interrupts.len() >= self.presses {
+            true => {
+                self.interrupts.clear();
+                self.terminated = Some(now);
+                InterruptAction::Terminate
+            }
+            false => InterruptAction::Interrupt,
+        }
+    }
+
+    /// ### kill_due
+    ///
+    /// Returns whether the terminated command has to be killed at `now`; it's returned once
+    pub fn kill_due(&mut self, now: Instant) -> bool {
+        match self.terminated {
+            Some(terminated) if now.duration_since(terminated) >= self.kill_after => {
+                self.terminated = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// ### reset
+    ///
+    /// Forget the interrupts sent; called once the command has terminated
+    pub fn reset(&mut self) {
+        self.interrupts.clear();
+        self.terminated = None;
+    }
+}
+
+impl Timeout {
+    /// ### new
+    ///
+    /// Instantiate a new Timeout for a command started at `started`
+    pub fn new(started: Instant, limit: Duration, kill_after: Duration) -> Timeout {
+        Timeout {
+            deadline: started + limit,
+            kill_after,
+            terminated: None,
+            killed: false,
+        }
+    }
+
+    /// ### poll
+    ///
+    /// Returns the signal to send to the command at `now`, if any; each action is returned once
+    pub fn poll(&mut self, now: Instant) -> Option<TimeoutAction> {
+        match self.terminated {
+            None if now >= self.deadline => {
+                self.terminated = Some(now);
+                Some(TimeoutAction::Terminate)
+            }
+            Some(terminated) if !self.killed && now.duration_since(terminated) >= self.kill_after => {
+                self.killed = true;
+                Some(TimeoutAction::Kill)
+            }
+            _ => None,
+        }
+    }
+
+    /// ### expired
+    ///
+    /// Returns whether the command has run out of time
+    pub fn expired(&self) -> bool {
+        self.terminated.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn new_escalation(presses: usize) -> Escalation {
+        Escalation::new(&SigintEscalation {
+            presses,
+            window_ms: 2000,
+            kill_after_ms: 3000,
+        })
+    }
+
+    #[test]
+    fn test_escalation_interrupt() {
+        let mut escalation: Escalation = new_escalation(3);
+        let start: Instant = Instant::now();
+        assert_eq!(escalation.interrupt(start), InterruptAction::Interrupt);
+        assert_eq!(escalation.interrupt(start + Duration::from_millis(500)), InterruptAction::Interrupt);
+        assert_eq!(escalation.interrupt(start + Duration::from_millis(1000)), InterruptAction::Terminate);
+        //Then it's killed after the timeout
+        assert!(!escalation.kill_due(start + Duration::from_millis(3000)));
+        assert_eq!(escalation.interrupt(start + Duration::from_millis(3500)), InterruptAction::Interrupt);
+        assert!(escalation.kill_due(start + Duration::from_millis(4000)));
+        assert!(!escalation.kill_due(start + Duration::from_millis(5000)));
+    }
+
+    #[test]
+    fn test_escalation_window() {
+        let mut escalation: Escalation = new_escalation(3);
+        let start: Instant = Instant::now();
+        //Interrupts older than the window don't count
+        assert_eq!(escalation.interrupt(start), InterruptAction::Interrupt);
+        assert_eq!(escalation.interrupt(start + Duration::from_millis(1500)), InterruptAction::Interrupt);
+        assert_eq!(escalation.interrupt(start + Duration::from_millis(2500)), InterruptAction::Interrupt);
+        assert_eq!(escalation.interrupt(start + Duration::from_millis(3000)), InterruptAction::Terminate);
+        //Reset
+        escalation.reset();
+        assert!(!escalation.kill_due(start + Duration::from_millis(10000)));
+        assert_eq!(escalation.interrupt(start + Duration::from_millis(10000)), InterruptAction::Interrupt);
+    }
+
+    #[test]
+    fn test_escalation_disabled() {
+        let mut escalation: Escalation = new_escalation(0);
+        let start: Instant = Instant::now();
+        for _ in 0..5 {
+            assert_eq!(escalation.interrupt(start), InterruptAction::Interrupt);
+        }
+        assert!(!escalation.kill_due(start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_escalation_timeout() {
+        let start: Instant = Instant::now();
+        let mut timeout: Timeout = Timeout::new(start, Duration::from_secs(1), Duration::from_millis(500));
+        assert_eq!(timeout.poll(start + Duration::from_millis(999)), None);
+        assert!(!timeout.expired());
+        //Terminated once the limit has elapsed
+        assert_eq!(timeout.poll(start + Duration::from_millis(1000)), Some(TimeoutAction::Terminate));
+        assert!(timeout.expired());
+        assert_eq!(timeout.poll(start + Duration::from_millis(1200)), None);
+        //Then killed after the grace period
+        assert_eq!(timeout.poll(start + Duration::from_millis(1700)), Some(TimeoutAction::Kill));
+        assert_eq!(timeout.poll(start + Duration::from_secs(60)), None);
+        assert!(timeout.expired());
+    }
+}
@@ -0,0 +1,477 @@
+//! ## Expansion
+//!
+//! `expansion` provides the expansion of environment variables and special parameters in the commands typed by the user
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate regex;
+
+use regex::Regex;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::utils::buffer;
+
+/// ### ExpansionContext
+///
+/// ExpansionContext contains the values of the special parameters and how unset variables are handled
+pub struct ExpansionContext {
+    pub pid: u32,            //Value of `$$`
+    pub exit_status: u8,     //Value of `$?`
+    pub error_on_unset: bool, //Unset variables are an error instead of an empty string
+}
+
+/// ### ExpansionError
+///
+/// ExpansionError describes why a command couldn't be expanded
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub enum ExpansionError {
+    UnsetVariable(String),
+}
+
+impl fmt::Display for ExpansionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpansionError::UnsetVariable(name) => write!(f, "{}: unbound variable", name),
+        }
+    }
+}
+
+/// ### expand_variables
+///
+/// Expand `$VAR`, `${VAR}`, `$$` and `$?` in a command, as the shell would do, looking variables up with `lookup`.
+/// Nothing is expanded between single quotes or after a backslash; everything else (e.g. `$1`, `$(...)`, `${VAR:-x}`)
+/// is left to the shell, as are the variables assigned by the command itself (e.g. `for f in *; do echo $f; done`).
+/// The values are quoted, so that the shell doesn't interpret them again
+pub fn expand_variables(input: &str, ctx: &ExpansionContext, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, ExpansionError> {
+    let assigned: Vec<String> = assigned_names(input);
+    let chars: Vec<char> = input.chars().collect();
+    let mut output: String = String::with_capacity(input.len());
+    let mut quote: Option<char> = None;
+    let mut backslash: bool = false;
+    let mut i: usize = 0;
+    while i < chars.len() {
+        let c: char = chars[i];
+        i += 1;
+        if backslash {
+            backslash = false;
+            output.push(c);
+            continue;
+        }
+        match c {
+            '\\' if quote != Some('\'') => backslash = true,
+            '\'' | '"' if quote.is_none() => quote = Some(c),
+            '\'' | '"' if quote == Some(c) => quote = None,
+            '$' if quote != Some('\'') => {
+                let in_quotes: bool = quote.is_some();
+                let (name, end): (String, usize) = match chars.get(i) {
+                    Some('$') => {
+                        output.push_str(ctx.pid.to_string().as_str());
+                        i += 1;
+                        continue;
+                    }
+                    Some('?') => {
+                        output.push_str(ctx.exit_status.to_string().as_str());
+                        i += 1;
+                        continue;
+                    }
+                    Some('{') => match chars[i..].iter().position(|c| *c == '}') {
+                        Some(len) => (chars[i + 1..i + len].iter().collect(), i + len + 1),
+                        None => (String::new(), i),
+                    },
+                    _ => {
+                        let len: usize = chars[i..].iter().take_while(|c| c.is_ascii_alphanumeric() || **c == '_').count();
+                        (chars[i..i + len].iter().collect(), i + len)
+                    }
+                };
+                if !is_name(name.as_str()) || assigned.contains(&name) {
+                    output.push(c);
+                    continue;
+                }
+                match lookup(name.as_str()) {
+                    Some(value) => output.push_str(quote_value(value.as_str(), in_quotes).as_str()),
+                    None if ctx.error_on_unset => return Err(ExpansionError::UnsetVariable(name)),
+                    None => {}
+                }
+                i = end;
+                continue;
+            }
+            _ => {}
+        }
+        output.push(c);
+    }
+    Ok(output)
+}
+
+/// ### is_name
+///
+/// Returns whether the string is a valid variable name (`[A-Za-z_][A-Za-z0-9_]*`)
+pub(crate) fn is_name(name: &str) -> bool {
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+/// ### assigned_names
+///
+/// Returns the names of the variables which are assigned by the command itself (`NAME=`, `for NAME in`, `read NAME`)
+fn assigned_names(input: &str) -> Vec<String> {
+    lazy_static! {
+        static ref ASSIGNMENT: Regex = Regex::new(r"(?:^|[\s;&|(])([A-Za-z_][A-Za-z0-9_]*)=").unwrap();
+        static ref FOR_LOOP: Regex = Regex::new(r"\bfor\s+([A-Za-z_][A-Za-z0-9_]*)\b").unwrap();
+        static ref READ: Regex = Regex::new(r"\bread((?:\s+[A-Za-z_\-][A-Za-z0-9_]*)+)").unwrap();
+    }
+    let mut names: Vec<String> = Vec::new();
+    for groups in ASSIGNMENT.captures_iter(input).chain(FOR_LOOP.captures_iter(input)) {
+        names.push(String::from(&groups[1]));
+    }
+    for groups in READ.captures_iter(input) {
+        names.extend(groups[1].split_whitespace().filter(|arg| !arg.starts_with('-')).map(String::from));
+    }
+    names
+}
+
+/// ### quote_value
+///
+/// Quote the value of a variable for the shell. Between double quotes, the characters which are special there are escaped;
+/// otherwise the value is split into words (as the shell does) and each of them is single quoted
+fn quote_value(value: &str, in_quotes: bool) -> String {
+    match in_quotes {
+        true => value
+            .chars()
+            .fold(String::with_capacity(value.len()), |mut quoted, c| {
+                if c == '\\' || c == '"' || c == '$' || c == '`' {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+                quoted
+            }),
+        false => value
+            .split_whitespace()
+            .map(|word| format!("'{}'", word.replace('\'', "'\\''")))
+            .collect::<Vec<String>>()
+            .join(" "),
+    }
+}
+
+/// ### expand_globs
+///
+/// Expand the unquoted words which contain `*`, `?` or `[...]` into the sorted list of the paths they match,
+/// relative to `wrkdir`. A pattern which matches nothing is kept as it is, or removed if `nullglob` is true.
+/// Words which contain quotes, backslashes, expansions or which are assignments are left to the shell
+pub fn expand_globs(input: &str, wrkdir: &Path, nullglob: bool) -> String {
+    if input.contains("[[") {
+        return String::from(input);
+    }
+    let mut output: String = String::with_capacity(input.len());
+    let mut word: String = String::new();
+    let mut literal: bool = false;
+    let mut quote: Option<char> = None;
+    let mut backslash: bool = false;
+    for c in input.chars() {
+        if backslash {
+            backslash = false;
+            word.push(c);
+            continue;
+        }
+        match quote {
+            Some(q) => {
+                word.push(c);
+                if c == q {
+                    quote = None;
+                } else if c == '\\' && q == '"' {
+                    backslash = true;
+                }
+            }
+            None => match c {
+                '\\' => {
+                    word.push(c);
+                    backslash = true;
+                    literal = true;
+                }
+                '\'' | '"' => {
+                    word.push(c);
+                    quote = Some(c);
+                    literal = true;
+                }
+                c if c.is_whitespace() || ";&|<>()".contains(c) => {
+                    output.push_str(expand_word(word.as_str(), literal, wrkdir, nullglob).as_str());
+                    output.push(c);
+                    word.clear();
+                    literal = false;
+                }
+                c => word.push(c),
+            },
+        }
+    }
+    output.push_str(expand_word(word.as_str(), literal, wrkdir, nullglob).as_str());
+    output
+}
+
+/// ### expand_word
+///
+/// Expand a single word of the command, if it is a glob pattern
+fn expand_word(word: &str, literal: bool, wrkdir: &Path, nullglob: bool) -> String {
+    if literal || !is_pattern(word) || word.contains(&['$', '`', '{', '}'][..]) {
+        return String::from(word);
+    }
+    if let Some(pos) = word.find('=') {
+        if is_name(&word[..pos]) {
+            return String::from(word);
+        }
+    }
+    let matches: Vec<String> = glob(word, wrkdir);
+    match (matches.is_empty(), nullglob) {
+        (true, true) => String::new(),
+        (true, false) => String::from(word),
+        (false, _) => matches
+            .iter()
+            .map(|path| format!("'{}'", path.replace('\'', "'\\''")))
+            .collect::<Vec<String>>()
+            .join(" "),
+    }
+}
+
+/// ### is_pattern
+///
+/// Returns whether the word contains a glob metacharacter
+fn is_pattern(word: &str) -> bool {
+    word.contains('*') || word.contains('?') || word.find('[').map(|pos| word[pos + 1..].contains(']')).unwrap_or(false)
+}
+
+/// ### glob
+///
+/// Returns the sorted paths matching the pattern; relative patterns are resolved from `wrkdir`, but matches are relative too
+fn glob(pattern: &str, wrkdir: &Path) -> Vec<String> {
+    //Candidates: the path as it is written and the path on the filesystem
+    let mut candidates: Vec<(String, PathBuf)> = match pattern.starts_with('/') {
+        true => vec![(String::from("/"), PathBuf::from("/"))],
+        false => vec![(String::new(), wrkdir.to_path_buf())],
+    };
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    for (i, component) in components.iter().enumerate() {
+        let last: bool = i + 1 == components.len() && !pattern.ends_with('/');
+        let mut next: Vec<(String, PathBuf)> = Vec::new();
+        for (written, path) in candidates.into_iter() {
+            let prefix: String = match written.is_empty() || written.ends_with('/') {
+                true => written.clone(),
+                false => format!("{}/", written),
+            };
+            if !is_pattern(component) {
+                next.push((format!("{}{}", prefix, component), path.join(buffer::string_to_os_string(component))));
+                continue;
+            }
+            let entries: fs::ReadDir = match fs::read_dir(path.as_path()) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let name: String = buffer::os_str_to_string(&entry.file_name());
+                //Hidden files are matched only if the pattern starts with '.'
+                if name.starts_with('.') && !component.starts_with('.') {
+                    continue;
+                }
+                if !last && !entry.path().is_dir() {
+                    continue;
+                }
+                if fnmatch(component.chars().collect::<Vec<char>>().as_slice(), name.chars().collect::<Vec<char>>().as_slice()) {
+                    next.push((format!("{}{}", prefix, name), entry.path()));
+                }
+            }
+        }
+        candidates = next;
+    }
+    let mut matches: Vec<String> = candidates
+        .into_iter()
+        .filter(|(_, path)| path.symlink_metadata().is_ok())
+        .map(|(written, _)| written)
+        .collect();
+    if pattern.ends_with('/') {
+        matches = matches.into_iter().map(|path| format!("{}/", path)).collect();
+    }
+    matches.sort();
+    matches
+}
+
+/// ### fnmatch
+///
+/// Returns whether name matches the pattern (`*`, `?`, `[abc]`, `[a-z]`, `[!a]`)
+fn fnmatch(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|i| fnmatch(&pattern[1..], &name[i..])),
+        Some('?') => !name.is_empty() && fnmatch(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let c: char = match name.first() {
+                Some(c) => *c,
+                None => return false,
+            };
+            let negated: bool = pattern.get(1) == Some(&'!') || pattern.get(1) == Some(&'^');
+            let start: usize = if negated { 2 } else { 1 };
+            //The first character of the set can be ']'
+            let end: usize = match pattern.iter().skip(start + 1).position(|c| *c == ']') {
+                Some(pos) => start + 1 + pos,
+                None => return c == '[' && fnmatch(&pattern[1..], &name[1..]),
+            };
+            let set: &[char] = &pattern[start..end];
+            let mut matched: bool = false;
+            let mut i: usize = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    matched |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    matched |= set[i] == c;
+                    i += 1;
+                }
+            }
+            matched != negated && fnmatch(&pattern[end + 1..], &name[1..])
+        }
+        Some(p) => name.first() == Some(p) && fnmatch(&pattern[1..], &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_expansion_variables() {
+        let ctx: ExpansionContext = new_context(false);
+        assert_eq!(expand(&ctx, "echo $HOME").unwrap(), String::from("echo '/home/omar'"));
+        assert_eq!(expand(&ctx, "echo ${HOME}/src").unwrap(), String::from("echo '/home/omar'/src"));
+        assert_eq!(expand(&ctx, "echo $HOME_DIR $USER_").unwrap(), String::from("echo  "));
+        assert_eq!(expand(&ctx, "ls $DIR").unwrap(), String::from("ls 'my' 'docs'"));
+        //Nothing to expand
+        assert_eq!(expand(&ctx, "echo $ $1 $(pwd) ${HOME:-/} $@ 5$").unwrap(), String::from("echo $ $1 $(pwd) ${HOME:-/} $@ 5$"));
+        assert_eq!(expand(&ctx, "echo ${HOME").unwrap(), String::from("echo ${HOME"));
+    }
+
+    #[test]
+    fn test_expansion_quotes() {
+        let ctx: ExpansionContext = new_context(false);
+        //Single quotes
+        assert_eq!(expand(&ctx, "echo '$HOME'").unwrap(), String::from("echo '$HOME'"));
+        assert_eq!(expand(&ctx, "echo \"it's $HOME\"").unwrap(), String::from("echo \"it's /home/omar\""));
+        //Double quotes
+        assert_eq!(expand(&ctx, "echo \"$DIR\"").unwrap(), String::from("echo \"my docs\""));
+        assert_eq!(expand(&ctx, "echo \"${EVIL}\"").unwrap(), String::from("echo \"\\$(rm -rf \\\"x\\\")\""));
+        //Values are not interpreted again by the shell
+        assert_eq!(expand(&ctx, "echo $EVIL").unwrap(), String::from("echo '$(rm' '-rf' '\"x\")'"));
+        assert_eq!(expand(&ctx, "echo $QUOTE").unwrap(), String::from("echo 'it'\\''s'"));
+        //Backslash
+        assert_eq!(expand(&ctx, "echo \\$HOME \"\\$HOME\"").unwrap(), String::from("echo \\$HOME \"\\$HOME\""));
+        assert_eq!(expand(&ctx, "echo '\\' $HOME").unwrap(), String::from("echo '\\' '/home/omar'"));
+    }
+
+    #[test]
+    fn test_expansion_unset() {
+        let ctx: ExpansionContext = new_context(true);
+        assert_eq!(expand(&ctx, "echo $HOME").unwrap(), String::from("echo '/home/omar'"));
+        assert_eq!(expand(&ctx, "echo $NOPE").err().unwrap(), ExpansionError::UnsetVariable(String::from("NOPE")));
+        assert_eq!(expand(&ctx, "echo \"${NOPE}\"").err().unwrap(), ExpansionError::UnsetVariable(String::from("NOPE")));
+        assert_eq!(expand(&ctx, "echo '$NOPE'").unwrap(), String::from("echo '$NOPE'"));
+        assert_eq!(ExpansionError::UnsetVariable(String::from("NOPE")).to_string(), String::from("NOPE: unbound variable"));
+    }
+
+    #[test]
+    fn test_expansion_special_parameters() {
+        let ctx: ExpansionContext = new_context(false);
+        assert_eq!(expand(&ctx, "echo $$ $?").unwrap(), String::from("echo 4096 2"));
+        assert_eq!(expand(&ctx, "echo \"$$:$?\"").unwrap(), String::from("echo \"4096:2\""));
+        assert_eq!(expand(&ctx, "echo '$$'").unwrap(), String::from("echo '$$'"));
+    }
+
+    #[test]
+    fn test_expansion_assigned_by_command() {
+        let ctx: ExpansionContext = new_context(true);
+        assert_eq!(expand(&ctx, "for f in *; do echo $f; done").unwrap(), String::from("for f in *; do echo $f; done"));
+        assert_eq!(expand(&ctx, "NOPE=1; echo ${NOPE} $HOME").unwrap(), String::from("NOPE=1; echo ${NOPE} '/home/omar'"));
+        assert_eq!(expand(&ctx, "read -r a b; echo $a $b").unwrap(), String::from("read -r a b; echo $a $b"));
+        assert_eq!(assigned_names("export A=1 && (B=2 c)"), vec![String::from("A"), String::from("B")]);
+    }
+
+    #[test]
+    fn test_expansion_fnmatch() {
+        let matches = |pattern: &str, name: &str| fnmatch(pattern.chars().collect::<Vec<char>>().as_slice(), name.chars().collect::<Vec<char>>().as_slice());
+        assert!(matches("*.txt", "a.txt"));
+        assert!(matches("*.txt", ".txt"));
+        assert!(!matches("*.txt", "a.txt.bak"));
+        assert!(matches("?.txt", "б.txt"));
+        assert!(!matches("?.txt", "ab.txt"));
+        assert!(matches("[ab]*", "beta"));
+        assert!(matches("[a-c]1", "b1"));
+        assert!(!matches("[!a-c]1", "b1"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("a[", "a["));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn test_expansion_globs() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        for file in &["b.txt", "a.txt", "c.md", ".hidden.txt", "отчёт.txt", "it's.log", "src/main.rs", "src/lib.rs", "src/README"] {
+            let path: PathBuf = tmpdir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let wrkdir: &Path = tmpdir.path();
+        assert_eq!(expand_globs("ls *.txt", wrkdir, false), String::from("ls 'a.txt' 'b.txt' 'отчёт.txt'"));
+        assert_eq!(expand_globs("ls ?.txt -l", wrkdir, false), String::from("ls 'a.txt' 'b.txt' -l"));
+        assert_eq!(expand_globs("ls [ac].*", wrkdir, false), String::from("ls 'a.txt' 'c.md'"));
+        assert_eq!(expand_globs("ls .*.txt", wrkdir, false), String::from("ls '.hidden.txt'"));
+        assert_eq!(expand_globs("cat src/*.rs|wc", wrkdir, false), String::from("cat 'src/lib.rs' 'src/main.rs'|wc"));
+        assert_eq!(expand_globs("ls */", wrkdir, false), String::from("ls 'src/'"));
+        assert_eq!(expand_globs("ls */README", wrkdir, false), String::from("ls 'src/README'"));
+        assert_eq!(expand_globs("ls *.log", wrkdir, false), String::from("ls 'it'\\''s.log'"));
+        let absolute: String = format!("ls {}/*.md", wrkdir.display());
+        assert_eq!(expand_globs(absolute.as_str(), Path::new("/"), false), format!("ls '{}/c.md'", wrkdir.display()));
+        //No matches
+        assert_eq!(expand_globs("ls *.pdf", wrkdir, false), String::from("ls *.pdf"));
+        assert_eq!(expand_globs("ls *.pdf *.md", wrkdir, true), String::from("ls  'c.md'"));
+        //Quoted patterns and other words are left as they are
+        assert_eq!(expand_globs("ls '*.txt' \"*.md\" \\*.txt", wrkdir, false), String::from("ls '*.txt' \"*.md\" \\*.txt"));
+        assert_eq!(expand_globs("echo $(ls) ${x}* A=*.txt [ -f a.txt ] [[ a == *.txt ]]", wrkdir, false), String::from("echo $(ls) ${x}* A=*.txt [ -f a.txt ] [[ a == *.txt ]]"));
+        assert_eq!(expand_globs("[ -f a.txt ]", wrkdir, false), String::from("[ -f a.txt ]"));
+    }
+
+    fn new_context(error_on_unset: bool) -> ExpansionContext {
+        ExpansionContext {
+            pid: 4096,
+            exit_status: 2,
+            error_on_unset,
+        }
+    }
+
+    fn expand(ctx: &ExpansionContext, input: &str) -> Result<String, ExpansionError> {
+        expand_variables(input, ctx, &|name: &str| match name {
+            "HOME" => Some(String::from("/home/omar")),
+            "DIR" => Some(String::from("my docs")),
+            "EVIL" => Some(String::from("$(rm -rf \"x\")")),
+            "QUOTE" => Some(String::from("it's")),
+            _ => None,
+        })
+    }
+}
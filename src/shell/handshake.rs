@@ -0,0 +1,183 @@
+//! ## Handshake
+//!
+//! `handshake` provides the diagnostics reported when the shell doesn't answer the first command at startup,
+//! which usually happens because its rc files print output, clear the screen or replace the prompt command
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+//Amount of lines shown from the beginning and from the end of the shell output
+const EXCERPT_LINES: usize = 5;
+
+/// ### HandshakeFailure
+///
+/// HandshakeFailure describes why the handshake with the shell failed
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum HandshakeFailure {
+    Timeout(Duration),
+    Terminated,
+}
+
+/// ### HandshakeError
+///
+/// HandshakeError contains the reason why the handshake failed and everything the shell emitted before failing
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct HandshakeError {
+    pub failure: HandshakeFailure,
+    pub output: String,
+}
+
+impl HandshakeError {
+    /// ### new
+    ///
+    /// Instantiates a new HandshakeError
+    pub fn new(failure: HandshakeFailure, output: String) -> HandshakeError {
+        HandshakeError {
+            failure,
+            output,
+        }
+    }
+
+    /// ### hints
+    ///
+    /// Returns the hints about what may have broken the handshake, based on the shell output
+    pub fn hints(&self) -> Vec<&'static str> {
+        let mut hints: Vec<&'static str> = Vec::new();
+        if !self.output.trim().is_empty() {
+            hints.push("your rc file printed output before the marker");
+        }
+        if self.output.contains("\x1b[2J") || self.output.contains("\x1bc") {
+            hints.push("your rc file clears the screen");
+        }
+        //Terminal title sequences are usually printed by PROMPT_COMMAND
+        if env::var_os("PROMPT_COMMAND").is_some() || self.output.contains("\x1b]") {
+            hints.push("PROMPT_COMMAND is overriding the marker");
+        }
+        hints.push("set `shell.norc: true` to start the shell without its rc files");
+        hints
+    }
+
+    /// ### diagnostic
+    ///
+    /// Returns the report of the failure on several lines: the reason, the first and last lines of the shell output and the hints
+    pub fn diagnostic(&self) -> String {
+        let mut report: Vec<String> = vec![self.to_string()];
+        let lines: Vec<String> = self.output.lines().map(printable).filter(|l| !l.trim().is_empty()).collect();
+        match lines.len() {
+            0 => report.push(String::from("The shell didn't print anything")),
+            len => {
+                report.push(String::from("Output of the shell before the marker:"));
+                match len > EXCERPT_LINES * 2 {
+                    true => {
+                        report.extend(lines.iter().take(EXCERPT_LINES).map(|l| format!("  | {}", l)));
+                        report.push(format!("  | ... ({} more lines)", len - EXCERPT_LINES * 2));
+                        report.extend(lines.iter().skip(len - EXCERPT_LINES).map(|l| format!("  | {}", l)));
+                    }
+                    false => report.extend(lines.iter().map(|l| format!("  | {}", l))),
+                }
+            }
+        }
+        report.extend(self.hints().iter().map(|h| format!("Hint: {}", h)));
+        report.join("\n")
+    }
+}
+
+impl fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.failure {
+            HandshakeFailure::Timeout(timeout) => write!(f, "The shell didn't answer the handshake within {}ms", timeout.as_millis()),
+            HandshakeFailure::Terminated => write!(f, "The shell terminated before answering the handshake"),
+        }
+    }
+}
+
+/// ### norc_args
+///
+/// Returns the arguments which make the provided shell start without reading its rc files.
+/// Shells which are not known don't get any argument
+pub fn norc_args(exec: &str) -> Vec<String> {
+    let name: &str = Path::new(exec).file_name().and_then(|n| n.to_str()).unwrap_or(exec);
+    let args: &[&str] = match name {
+        "bash" => &["--norc", "--noprofile"],
+        "zsh" => &["--no-rcs"],
+        "fish" => &["--no-config"],
+        _ => &[],
+    };
+    args.iter().map(|a| String::from(*a)).collect()
+}
+
+/// ### printable
+///
+/// Make the control characters of a line visible, so that printing the diagnostic doesn't clear the screen
+fn printable(line: &str) -> String {
+    line.chars()
+        .map(|c| match c {
+            '\t' => String::from("\t"),
+            '\x1b' => String::from("^["),
+            c if (c as u32) < 0x20 || c == '\x7f' => format!("^{}", ((c as u8) ^ 0x40) as char),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_handshake_norc_args() {
+        assert_eq!(norc_args("bash"), vec![String::from("--norc"), String::from("--noprofile")]);
+        assert_eq!(norc_args("/usr/bin/zsh"), vec![String::from("--no-rcs")]);
+        assert_eq!(norc_args("fish"), vec![String::from("--no-config")]);
+        assert!(norc_args("sh").is_empty());
+    }
+
+    #[test]
+    fn test_handshake_diagnostic() {
+        //Banner and clear screen
+        let output: String = (1..=12).map(|i| format!("banner {}\n", i)).collect::<String>() + "\x1b[H\x1b[2Jready\n";
+        let error: HandshakeError = HandshakeError::new(HandshakeFailure::Timeout(Duration::from_millis(500)), output);
+        assert_eq!(error.to_string(), String::from("The shell didn't answer the handshake within 500ms"));
+        let hints: Vec<&str> = error.hints();
+        assert!(hints.contains(&"your rc file printed output before the marker"));
+        assert!(hints.contains(&"your rc file clears the screen"));
+        let diagnostic: String = error.diagnostic();
+        assert!(diagnostic.contains("  | banner 1\n"));
+        assert!(diagnostic.contains("  | banner 5\n  | ... (3 more lines)\n  | banner 9\n"));
+        assert!(diagnostic.contains("  | ^[[H^[[2Jready\n"));
+        assert!(!diagnostic.contains('\x1b'));
+        assert!(diagnostic.ends_with("Hint: set `shell.norc: true` to start the shell without its rc files"));
+        //Nothing printed
+        let error: HandshakeError = HandshakeError::new(HandshakeFailure::Terminated, String::new());
+        assert_eq!(error.to_string(), String::from("The shell terminated before answering the handshake"));
+        assert!(error.diagnostic().contains("The shell didn't print anything"));
+        assert!(!error.hints().contains(&"your rc file printed output before the marker"));
+        //Title set by PROMPT_COMMAND
+        let error: HandshakeError = HandshakeError::new(HandshakeFailure::Terminated, String::from("\x1b]0;user@host\x07"));
+        assert!(error.hints().contains(&"PROMPT_COMMAND is overriding the marker"));
+    }
+}
@@ -26,17 +26,19 @@
 use std::collections::VecDeque;
 
 pub struct ShellHistory {
-    history: VecDeque<String>
+    history: VecDeque<String>,
+    size: usize
 }
 
 impl ShellHistory {
 
     /// ### new
     /// 
-    /// Instantiate a new ShellHistory
-    pub fn new() -> ShellHistory {
+    /// Instantiate a new ShellHistory, which will keep at most `size` entries
+    pub fn new(size: usize) -> ShellHistory {
         ShellHistory {
-            history: VecDeque::with_capacity(2048)
+            history: VecDeque::with_capacity(size),
+            size
         }
     }
 
@@ -76,10 +78,17 @@ impl ShellHistory {
         self.history.len()
     }
 
+    /// ### size
+    ///
+    /// Returns the maximum amount of entries of the history
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
     /// ### load
     /// 
     /// Load history
-    /// NOTE: the maximum history size will still be the size provided at constructor
+    /// NOTE: the maximum history size will still be the size provided at constructor; the oldest entries are discarded
     pub fn load(&mut self, lines: Vec<String>) {
         //Clear current history
         self.clear();
@@ -108,9 +117,12 @@ impl ShellHistory {
                 return
             }
         }
-        //Check if history overflows the size
-        let size: usize = (self.history.capacity() + 1) / 2;
-        if self.history.len() + 1 > size {
+        //History is disabled
+        if self.size == 0 {
+            return;
+        }
+        //Check if history overflows the size; remove oldest entries
+        while self.history.len() + 1 > self.size {
             self.history.pop_back();
         }
         self.history.push_front(line);
@@ -127,8 +139,8 @@ mod tests {
 
     #[test]
     fn test_shell_history() {
-        let mut history: ShellHistory = ShellHistory::new();
-        assert_eq!(history.history.capacity(), (2048 * 2 - 1)); //2048 * 2 - 1
+        let mut history: ShellHistory = ShellHistory::new(2048);
+        assert_eq!(history.size, 2048);
         //Load history
         history.load(vec![String::from("ls"), String::from("cd /tmp/")]);
         assert_eq!(history.len(), 2);
@@ -174,4 +186,21 @@ mod tests {
         assert_eq!(*dump.get(1).unwrap(), String::from("cd /tmp/"));
     }
 
+    #[test]
+    fn test_shell_history_truncate() {
+        let mut history: ShellHistory = ShellHistory::new(3);
+        //Load more lines than size: oldest are discarded
+        history.load(vec![String::from("ls"), String::from("pwd"), String::from("чд /tmp/"), String::from("лс -л")]);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.dump(), vec![String::from("pwd"), String::from("чд /tmp/"), String::from("лс -л")]);
+        //Push
+        history.push(String::from("экхо привет"));
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.dump(), vec![String::from("чд /tmp/"), String::from("лс -л"), String::from("экхо привет")]);
+        //History disabled
+        let mut history: ShellHistory = ShellHistory::new(0);
+        history.push(String::from("ls"));
+        assert_eq!(history.len(), 0);
+    }
+
 }
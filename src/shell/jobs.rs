@@ -0,0 +1,631 @@
+//! ## Jobs
+//!
+//! `jobs` provides the table of the commands which have been started in background
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::proc::{ExecOptions, ResourceUsage, ShellError, ShellProc, ShellProcState};
+
+use nix::sys::signal::Signal;
+use std::time::{Duration, Instant};
+
+/// ### JobState
+///
+/// JobState describes whether a job is still running, has been suspended or has terminated with the provided exit code
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done(u8),
+}
+
+/// ### JobError
+///
+/// JobError describes why a job couldn't be started or found
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum JobError {
+    NoCurrentJob,
+    NoSuchJob,
+    AlreadyInBackground,
+    CouldNotStart(ShellError),
+    CouldNotSignal(ShellError),
+}
+
+/// ### CommandTiming
+///
+/// CommandTiming is the time taken by a command run with the `time` prefix
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub struct CommandTiming {
+    pub real: Duration,
+    pub user: Duration,
+    pub sys: Duration,
+}
+
+/// ### JobsSummary
+///
+/// JobsSummary is the amount of jobs running in background and of the suspended ones, as shown by the prompt
+#[derive(Copy, Clone, Default, PartialEq, std::fmt::Debug)]
+pub struct JobsSummary {
+    pub running: usize,
+    pub stopped: usize,
+}
+
+/// ### Job
+///
+/// Job is a command started in background
+pub struct Job {
+    pub id: usize,
+    pub command: String,
+    pub pid: i32,
+    pub state: JobState,
+    started: Instant,
+    timed: bool, //Whether the timing has to be reported once the job terminates
+    process: ShellProc,
+}
+
+/// ### JobTable
+///
+/// JobTable contains the jobs started in background. A job can be brought to foreground;
+/// while it's in foreground, the shell input and output is redirected to it. The job in foreground can be suspended
+/// and then resumed in foreground or in background
+pub struct JobTable {
+    jobs: Vec<Job>,
+    foreground: Option<usize>,      //Id of the job in foreground
+    waiting: bool,                  //Whether the shell is waiting for all the jobs to terminate
+    timing: Option<CommandTiming>,  //Timing of the last timed job, not reported yet
+}
+
+impl JobTable {
+    /// ### new
+    ///
+    /// Instantiate a new empty JobTable
+    pub fn new() -> JobTable {
+        JobTable {
+            jobs: Vec::new(),
+            foreground: None,
+            waiting: false,
+            timing: None,
+        }
+    }
+
+    /// ### spawn
+    ///
+    /// Run command with the provided shell applying the exec options (working directory and environment) and register it as a new job.
+    /// Returns the id of the new job
+    pub fn spawn(&mut self, shell: &str, command: &str, options: &ExecOptions) -> Result<usize, JobError> {
+        let argv: Vec<String> = vec![String::from(shell), String::from("-c"), String::from(command)];
+        match ShellProc::start_job(argv, options) {
+            Ok(process) => Ok(self.insert(String::from(command), process)),
+            Err(err) => Err(JobError::CouldNotStart(err)),
+        }
+    }
+
+    /// ### insert
+    ///
+    /// Register a process as a new job. Returns the id of the new job
+    pub fn insert(&mut self, command: String, process: ShellProc) -> usize {
+        let id: usize = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        self.jobs.push(Job {
+            id,
+            command,
+            pid: process.pid,
+            state: JobState::Running,
+            started: Instant::now(),
+            timed: false,
+            process,
+        });
+        id
+    }
+
+    /// ### get
+    ///
+    /// Get job by id
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    /// ### list
+    ///
+    /// Returns the jobs in the table, the most recent one last
+    pub fn list(&self) -> &[Job] {
+        self.jobs.as_slice()
+    }
+
+    /// ### summary
+    ///
+    /// Returns the amount of jobs running in background and of the suspended ones; the job in foreground and the terminated jobs are not counted
+    pub fn summary(&self) -> JobsSummary {
+        let mut summary: JobsSummary = JobsSummary::default();
+        for job in self.jobs.iter().filter(|job| Some(job.id) != self.foreground) {
+            match job.state {
+                JobState::Running => summary.running += 1,
+                JobState::Stopped => summary.stopped += 1,
+                JobState::Done(_) => {}
+            }
+        }
+        summary
+    }
+
+    /// ### current
+    ///
+    /// Returns the id of the current job (the most recent one)
+    pub fn current(&self) -> Option<usize> {
+        self.jobs.last().map(|job| job.id)
+    }
+
+    /// ### update
+    ///
+    /// Check whether running (or suspended) jobs have terminated.
+    /// If the job in foreground has terminated, it is removed from the table and its exit code and execution time are returned
+    pub fn update(&mut self) -> Option<(u8, Duration)> {
+        for job in self.jobs.iter_mut() {
+            if !matches!(job.state, JobState::Done(_)) && job.process.update_state() == ShellProcState::Terminated {
+                job.state = JobState::Done(job.process.cleanup().unwrap_or(255));
+            }
+        }
+        let foreground: usize = self.foreground?;
+        match self.jobs.iter().position(|job| job.id == foreground) {
+            Some(index) => match self.jobs[index].state {
+                JobState::Done(rc) => {
+                    let job: Job = self.jobs.remove(index);
+                    self.foreground = None;
+                    let elapsed: Duration = job.started.elapsed();
+                    if job.timed {
+                        let usage: ResourceUsage = job.process.rusage.unwrap_or_default();
+                        self.timing = Some(CommandTiming {
+                            real: elapsed,
+                            user: usage.user,
+                            sys: usage.sys,
+                        });
+                    }
+                    Some((rc, elapsed))
+                }
+                _ => None,
+            },
+            None => {
+                self.foreground = None;
+                None
+            }
+        }
+    }
+
+    /// ### set_timed
+    ///
+    /// Report the timing of the provided job once it terminates in foreground
+    pub fn set_timed(&mut self, id: usize) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.timed = true;
+        }
+    }
+
+    /// ### take_timing
+    ///
+    /// Returns the timing of the last timed job which has terminated, if it hasn't been reported yet
+    pub fn take_timing(&mut self) -> Option<CommandTiming> {
+        self.timing.take()
+    }
+
+    /// ### reap
+    ///
+    /// Remove the terminated jobs from the table and return them
+    pub fn reap(&mut self) -> Vec<Job> {
+        let mut finished: Vec<Job> = Vec::new();
+        let mut index: usize = 0;
+        while index < self.jobs.len() {
+            match self.jobs[index].state {
+                JobState::Done(_) => finished.push(self.jobs.remove(index)),
+                _ => index += 1,
+            }
+        }
+        finished
+    }
+
+    /// ### foreground
+    ///
+    /// Bring the provided job (or the current one if None) to foreground; a suspended job is resumed. Returns the id of the job
+    pub fn foreground(&mut self, id: Option<usize>) -> Result<usize, JobError> {
+        let job: &mut Job = self.find_alive(id)?;
+        if job.state == JobState::Stopped {
+            let _ = job.process.resume();
+            job.state = JobState::Running;
+        }
+        let id: usize = job.id;
+        self.foreground = Some(id);
+        Ok(id)
+    }
+
+    /// ### background
+    ///
+    /// Resume the provided suspended job (or the current one if None) in background. Returns the id of the job
+    pub fn background(&mut self, id: Option<usize>) -> Result<usize, JobError> {
+        let job: &mut Job = self.find_alive(id)?;
+        if job.state == JobState::Running {
+            return Err(JobError::AlreadyInBackground);
+        }
+        let _ = job.process.resume();
+        job.state = JobState::Running;
+        Ok(job.id)
+    }
+
+    /// ### signal
+    ///
+    /// Send a signal to the provided job. A suspended job is resumed, so that it can handle the signal
+    pub fn signal(&mut self, id: usize, signal: Signal) -> Result<(), JobError> {
+        let job: &mut Job = self.find_alive(Some(id))?;
+        job.process.raise(signal).map_err(JobError::CouldNotSignal)?;
+        if job.state == JobState::Stopped && !matches!(signal, Signal::SIGSTOP | Signal::SIGTSTP) {
+            let _ = job.process.resume();
+            job.state = JobState::Running;
+        }
+        Ok(())
+    }
+
+    /// ### suspend
+    ///
+    /// Suspend the job in foreground; the shell prompt is given back. Returns the id of the job, if a job was in foreground
+    pub fn suspend(&mut self) -> Option<usize> {
+        let foreground: usize = self.foreground?;
+        let job: &mut Job = self.jobs.iter_mut().find(|job| job.id == foreground)?;
+        job.process.suspend().ok()?;
+        job.state = JobState::Stopped;
+        self.foreground = None;
+        Some(foreground)
+    }
+
+    /// ### find_alive
+    ///
+    /// Find the provided job (or the current one if None), if it hasn't terminated
+    fn find_alive(&mut self, id: Option<usize>) -> Result<&mut Job, JobError> {
+        let id: usize = match id {
+            Some(id) => id,
+            None => self.current().ok_or(JobError::NoCurrentJob)?,
+        };
+        match self.jobs.iter_mut().find(|job| job.id == id) {
+            Some(job) if !matches!(job.state, JobState::Done(_)) => Ok(job),
+            _ => Err(JobError::NoSuchJob),
+        }
+    }
+
+    /// ### foreground_process
+    ///
+    /// Returns the process of the job in foreground
+    pub fn foreground_process(&mut self) -> Option<&mut ShellProc> {
+        let foreground: usize = self.foreground?;
+        self.jobs.iter_mut().find(|job| job.id == foreground).map(|job| &mut job.process)
+    }
+
+    /// ### wait
+    ///
+    /// Set whether the shell is waiting for all the running jobs to terminate
+    pub fn wait(&mut self, waiting: bool) {
+        self.waiting = waiting;
+    }
+
+    /// ### is_blocking
+    ///
+    /// Returns whether the shell prompt is blocked by a job in foreground or by `wait`
+    pub fn is_blocking(&mut self) -> bool {
+        if self.waiting && !self.jobs.iter().any(|job| job.state == JobState::Running) {
+            self.waiting = false;
+        }
+        self.foreground.is_some() || self.waiting
+    }
+
+    /// ### read_background
+    ///
+    /// Read the output of the jobs in background, without waiting for it
+    pub fn read_background(&mut self) -> (Option<String>, Option<String>) {
+        let mut stdout: Option<String> = None;
+        let mut stderr: Option<String> = None;
+        let foreground: Option<usize> = self.foreground;
+        for job in self.jobs.iter_mut().filter(|job| Some(job.id) != foreground) {
+            if let Ok((out, err)) = job.process.read_timeout(1) {
+                append_output(&mut stdout, out);
+                append_output(&mut stderr, err);
+            }
+        }
+        (stdout, stderr)
+    }
+}
+
+impl Default for JobTable {
+    fn default() -> Self {
+        JobTable::new()
+    }
+}
+
+impl std::fmt::Display for JobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JobError::NoCurrentJob => write!(f, "no current job"),
+            JobError::NoSuchJob => write!(f, "no such job"),
+            JobError::AlreadyInBackground => write!(f, "job already in background"),
+            JobError::CouldNotStart(err) => write!(f, "could not start job: {}", err),
+            JobError::CouldNotSignal(err) => write!(f, "could not signal job: {}", err),
+        }
+    }
+}
+
+impl Job {
+    /// ### state_str
+    ///
+    /// Returns the state of the job as shown by `jobs`
+    pub fn state_str(&self) -> String {
+        match self.state {
+            JobState::Running => String::from("Running"),
+            JobState::Stopped => String::from("Stopped"),
+            JobState::Done(0) => String::from("Done"),
+            JobState::Done(rc) => format!("Exit {}", rc),
+        }
+    }
+
+    /// ### describe
+    ///
+    /// Returns the line which describes the job in `jobs`; the current job is marked with `+`
+    pub fn describe(&self, current: bool) -> String {
+        let marker: &str = if current { "+" } else { " " };
+        let suffix: &str = if self.state == JobState::Running { " &" } else { "" };
+        format!("[{}]{} {:<24}{}{}", self.id, marker, self.state_str(), self.command, suffix)
+    }
+}
+
+impl CommandTiming {
+    /// ### report
+    ///
+    /// Returns the timing report, formatted as bash does
+    pub fn report(&self) -> String {
+        format!("\nreal\t{}\nuser\t{}\nsys\t{}\n", format_duration(self.real), format_duration(self.user), format_duration(self.sys))
+    }
+}
+
+/// ### format_duration
+///
+/// Format a duration as minutes and seconds with milliseconds (e.g. `0m1.250s`)
+fn format_duration(duration: Duration) -> String {
+    let millis: u128 = duration.as_millis();
+    format!("{}m{}.{:03}s", millis / 60000, (millis % 60000) / 1000, millis % 1000)
+}
+
+/// ### append_output
+///
+/// Append the output read from a job to the output read so far
+pub fn append_output(output: &mut Option<String>, data: Option<String>) {
+    if let Some(data) = data {
+        match output {
+            Some(output) => output.push_str(data.as_str()),
+            None => *output = Some(data),
+        }
+    }
+}
+
+//@! Tests
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::path::PathBuf;
+    use std::thread::sleep;
+
+    fn exec_options(wrkdir: &str) -> ExecOptions {
+        ExecOptions {
+            wrkdir: Some(PathBuf::from(wrkdir)),
+            ..ExecOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_jobs_spawn() {
+        let mut table: JobTable = JobTable::new();
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let options: ExecOptions = exec_options(tmpdir.path().to_str().unwrap());
+        let id: usize = table.spawn("sh", "pwd; sleep 0.2", &options).unwrap();
+        assert_eq!(id, 1);
+        let job: &Job = table.get(1).unwrap();
+        assert_eq!(job.command, String::from("pwd; sleep 0.2"));
+        assert_eq!(job.state, JobState::Running);
+        assert!(job.pid > 0);
+        //Job runs in the provided directory
+        sleep(Duration::from_millis(100));
+        let (stdout, _) = table.read_background();
+        assert_eq!(stdout.unwrap().trim(), tmpdir.path().canonicalize().unwrap().to_string_lossy());
+        assert_eq!(table.spawn("sh", "exit 0", &options).unwrap(), 2);
+        assert_eq!(table.current(), Some(2));
+    }
+
+    #[test]
+    fn test_jobs_state_transitions() {
+        let mut table: JobTable = JobTable::new();
+        let options: ExecOptions = exec_options("/tmp");
+        table.spawn("sh", "sleep 0.3", &options).unwrap();
+        table.spawn("sh", "exit 3", &options).unwrap();
+        sleep(Duration::from_millis(150));
+        assert!(table.update().is_none());
+        assert_eq!(table.get(1).unwrap().state, JobState::Running);
+        assert_eq!(table.get(2).unwrap().state, JobState::Done(3));
+        assert_eq!(table.get(2).unwrap().state_str(), String::from("Exit 3"));
+        //Terminated jobs can't be brought to foreground
+        assert_eq!(table.foreground(Some(2)).err().unwrap(), JobError::NoSuchJob);
+        assert_eq!(table.foreground(Some(4)).err().unwrap(), JobError::NoSuchJob);
+        //Foreground
+        assert!(!table.is_blocking());
+        assert_eq!(table.foreground(Some(1)).unwrap(), 1);
+        assert!(table.is_blocking());
+        assert!(table.foreground_process().is_some());
+        sleep(Duration::from_millis(300));
+        //Job in foreground is removed once it has terminated
+        let (rc, elapsed) = table.update().unwrap();
+        assert_eq!(rc, 0);
+        assert!(elapsed >= Duration::from_millis(300));
+        assert!(table.get(1).is_none());
+        assert!(!table.is_blocking());
+        assert!(table.foreground_process().is_none());
+        //Current job
+        assert_eq!(table.foreground(None).err().unwrap(), JobError::NoSuchJob);
+    }
+
+    #[test]
+    fn test_jobs_timing() {
+        let mut table: JobTable = JobTable::new();
+        let options: ExecOptions = exec_options("/tmp");
+        table.spawn("sh", "sleep 0.2", &options).unwrap();
+        table.set_timed(1);
+        //Timing is reported only for jobs terminated in foreground
+        assert_eq!(table.foreground(Some(1)).unwrap(), 1);
+        assert!(table.take_timing().is_none());
+        sleep(Duration::from_millis(300));
+        assert!(table.update().is_some());
+        let timing: CommandTiming = table.take_timing().unwrap();
+        assert!(timing.real >= Duration::from_millis(200));
+        assert!(timing.real < Duration::from_secs(2));
+        assert!(timing.user + timing.sys <= timing.real);
+        //Timing is reported once
+        assert!(table.take_timing().is_none());
+        //Report
+        let timing: CommandTiming = CommandTiming {
+            real: Duration::from_millis(61250),
+            user: Duration::from_millis(3),
+            sys: Duration::from_millis(0),
+        };
+        assert_eq!(timing.report(), String::from("\nreal\t1m1.250s\nuser\t0m0.003s\nsys\t0m0.000s\n"));
+    }
+
+    #[test]
+    fn test_jobs_suspend() {
+        let mut table: JobTable = JobTable::new();
+        let options: ExecOptions = exec_options("/tmp");
+        table.spawn("sh", "sleep 0.4", &options).unwrap();
+        //Nothing to suspend
+        assert!(table.suspend().is_none());
+        assert_eq!(table.foreground(None).unwrap(), 1);
+        assert_eq!(table.suspend(), Some(1));
+        assert!(!table.is_blocking());
+        let job: &Job = table.get(1).unwrap();
+        assert_eq!(job.state, JobState::Stopped);
+        assert_eq!(job.describe(true), String::from("[1]+ Stopped                 sleep 0.4"));
+        //Stopped job doesn't terminate
+        sleep(Duration::from_millis(500));
+        assert!(table.update().is_none());
+        assert_eq!(table.get(1).unwrap().state, JobState::Stopped);
+        //Resume in background
+        assert_eq!(table.background(None).unwrap(), 1);
+        assert_eq!(table.get(1).unwrap().describe(false), String::from("[1]  Running                 sleep 0.4 &"));
+        assert_eq!(table.background(Some(1)).err().unwrap(), JobError::AlreadyInBackground);
+        assert_eq!(table.background(Some(2)).err().unwrap(), JobError::NoSuchJob);
+        //Suspend again and resume in foreground
+        table.foreground(Some(1)).unwrap();
+        assert_eq!(table.suspend(), Some(1));
+        assert_eq!(table.foreground(Some(1)).unwrap(), 1);
+        assert_eq!(table.get(1).unwrap().state, JobState::Running);
+        sleep(Duration::from_millis(500));
+        assert_eq!(table.update().unwrap().0, 0);
+    }
+
+    #[test]
+    fn test_jobs_signal() {
+        let mut table: JobTable = JobTable::new();
+        let options: ExecOptions = exec_options("/tmp");
+        table.spawn("sh", "sleep 5", &options).unwrap();
+        table.spawn("sh", "sleep 5", &options).unwrap();
+        assert!(table.signal(1, Signal::SIGTERM).is_ok());
+        //A suspended job is resumed to handle the signal
+        table.foreground(Some(2)).unwrap();
+        assert_eq!(table.suspend(), Some(2));
+        assert!(table.signal(2, Signal::SIGTERM).is_ok());
+        assert_eq!(table.get(2).unwrap().state, JobState::Running);
+        sleep(Duration::from_millis(200));
+        table.update();
+        assert_eq!(table.get(1).unwrap().state, JobState::Done(143));
+        assert_eq!(table.get(2).unwrap().state, JobState::Done(143));
+        //Terminated jobs can't be signaled
+        assert_eq!(table.signal(1, Signal::SIGKILL).err().unwrap(), JobError::NoSuchJob);
+        assert_eq!(table.signal(3, Signal::SIGKILL).err().unwrap(), JobError::NoSuchJob);
+    }
+
+    #[test]
+    fn test_jobs_summary() {
+        let mut table: JobTable = JobTable::new();
+        let options: ExecOptions = exec_options("/tmp");
+        assert_eq!(table.summary(), JobsSummary::default());
+        table.spawn("sh", "sleep 5", &options).unwrap();
+        table.spawn("sh", "sleep 5", &options).unwrap();
+        table.spawn("sh", "exit 0", &options).unwrap();
+        assert_eq!(table.summary(), JobsSummary { running: 3, stopped: 0 });
+        //Suspended job
+        table.foreground(Some(2)).unwrap();
+        assert_eq!(table.suspend(), Some(2));
+        //Terminated jobs are not counted
+        sleep(Duration::from_millis(200));
+        table.update();
+        assert_eq!(table.summary(), JobsSummary { running: 1, stopped: 1 });
+        //Job in foreground is not counted
+        table.foreground(Some(1)).unwrap();
+        assert_eq!(table.summary(), JobsSummary { running: 0, stopped: 1 });
+        assert!(table.signal(1, Signal::SIGKILL).is_ok());
+        assert!(table.signal(2, Signal::SIGKILL).is_ok());
+    }
+
+    #[test]
+    fn test_jobs_fmt_error() {
+        assert_eq!(JobError::NoCurrentJob.to_string(), String::from("no current job"));
+        assert_eq!(JobError::NoSuchJob.to_string(), String::from("no such job"));
+        assert_eq!(JobError::AlreadyInBackground.to_string(), String::from("job already in background"));
+        assert_eq!(
+            JobError::CouldNotStart(ShellError::CouldNotStartProcess).to_string(),
+            String::from("could not start job: Could not start process")
+        );
+        assert_eq!(
+            JobError::CouldNotSignal(ShellError::CouldNotKill).to_string(),
+            String::from("could not signal job: Could not send signal to shell process")
+        );
+    }
+
+    #[test]
+    fn test_jobs_reap() {
+        let mut table: JobTable = JobTable::new();
+        assert_eq!(table.foreground(None).err().unwrap(), JobError::NoCurrentJob);
+        let options: ExecOptions = exec_options("/tmp");
+        table.spawn("sh", "exit 0", &options).unwrap();
+        table.spawn("sh", "sleep 0.3", &options).unwrap();
+        table.spawn("sh", "exit 1", &options).unwrap();
+        sleep(Duration::from_millis(150));
+        table.update();
+        let finished: Vec<Job> = table.reap();
+        assert_eq!(finished.len(), 2);
+        assert_eq!(finished[0].id, 1);
+        assert_eq!(finished[0].state_str(), String::from("Done"));
+        assert_eq!(finished[1].id, 3);
+        assert_eq!(finished[1].state_str(), String::from("Exit 1"));
+        assert_eq!(table.list().len(), 1);
+        assert!(table.reap().is_empty());
+        //Ids are assigned after the highest one in the table
+        assert_eq!(table.spawn("sh", "exit 0", &options).unwrap(), 3);
+        //Wait
+        table.wait(true);
+        assert!(table.is_blocking());
+        sleep(Duration::from_millis(300));
+        table.update();
+        assert!(!table.is_blocking());
+        assert_eq!(table.reap().len(), 2);
+    }
+}
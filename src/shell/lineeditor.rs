@@ -0,0 +1,810 @@
+//! ## LineEditor
+//!
+//! `LineEditor` provides the state of the line being typed by the user and the navigation through the history
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::history::ShellHistory;
+use crate::translator::ioprocessor::{is_complete, CompletionState};
+use crate::utils::console::{self, InputEvent};
+
+//Marker inserted in place of the newlines of pasted text
+pub const PASTE_NEWLINE: char = '⏎';
+
+/// ## LineEditor
+///
+/// LineEditor contains the line buffer, the cursor position and the history navigation state.
+/// When `history_index` is 0 the user is editing its own line; when it's N, the buffer contains the N-th newest history entry
+/// and `stash` contains the line the user was typing before starting to browse the history
+pub struct LineEditor {
+    pub(crate) buffer: Vec<char>,
+    pub(crate) cursor: usize,
+    pub(crate) history_index: usize,
+    stash: Vec<char>,
+}
+
+impl LineEditor {
+    /// ### new
+    ///
+    /// Instantiate a new empty LineEditor
+    pub fn new() -> LineEditor {
+        LineEditor {
+            buffer: Vec::with_capacity(2048),
+            cursor: 0,
+            history_index: 0,
+            stash: Vec::new(),
+        }
+    }
+
+    /// ### get_line
+    ///
+    /// Returns the current line as a string
+    pub fn get_line(&self) -> String {
+        self.buffer.iter().collect()
+    }
+
+    /// ### set_line
+    ///
+    /// Replace the current line; the cursor is moved to the end of the line
+    pub fn set_line(&mut self, line: &str) {
+        self.buffer = line.chars().collect();
+        self.cursor = self.buffer.len();
+    }
+
+    /// ### clear
+    ///
+    /// Clear buffer and reset cursor to 0
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.cursor = 0;
+    }
+
+    /// ### reset_history
+    ///
+    /// Stop browsing the history; the next history navigation will start from the newest entry
+    pub fn reset_history(&mut self) {
+        self.history_index = 0;
+        self.stash.clear();
+    }
+
+    /// ### insert
+    ///
+    /// Insert text at the cursor position
+    pub fn insert(&mut self, text: &str) {
+        for ch in text.chars() {
+            self.buffer.insert(self.cursor, ch);
+            self.cursor += 1;
+        }
+    }
+
+    /// ### paste
+    ///
+    /// Insert pasted text at the cursor position. Newlines are inserted as `PASTE_NEWLINE` markers, so they are visible
+    /// and nothing is executed before enter is pressed; tabs become spaces and the other control characters are discarded
+    pub fn paste(&mut self, text: &str) {
+        let text: String = text
+            .replace("\r\n", "\n")
+            .chars()
+            .filter_map(|ch| match ch {
+                '\n' | '\r' => Some(PASTE_NEWLINE),
+                '\t' => Some(' '),
+                ch if ch.is_control() => None,
+                ch => Some(ch),
+            })
+            .collect();
+        self.insert(text.as_str());
+    }
+
+    /// ### replace_range
+    ///
+    /// Replace the characters in `start..end` with `text`; the cursor is moved after the inserted text
+    pub fn replace_range(&mut self, start: usize, end: usize, text: &str) {
+        let end: usize = std::cmp::min(end, self.buffer.len());
+        let start: usize = std::cmp::min(start, end);
+        let text: Vec<char> = text.chars().collect();
+        self.cursor = start + text.len();
+        self.buffer.splice(start..end, text);
+    }
+
+    /// ### backspace
+    ///
+    /// Remove the character before the cursor. Returns whether a character has been removed
+    pub fn backspace(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.cursor -= 1;
+        if self.buffer.len() > self.cursor {
+            self.buffer.remove(self.cursor);
+        }
+        true
+    }
+
+    /// ### delete
+    ///
+    /// Remove the character under the cursor. Returns whether a character has been removed
+    pub fn delete(&mut self) -> bool {
+        if self.cursor >= self.buffer.len() {
+            return false;
+        }
+        self.buffer.remove(self.cursor);
+        true
+    }
+
+    /// ### kill_to_end
+    ///
+    /// Remove all the characters from the cursor to the end of the line. Returns whether a character has been removed
+    pub fn kill_to_end(&mut self) -> bool {
+        if self.cursor >= self.buffer.len() {
+            return false;
+        }
+        self.buffer.truncate(self.cursor);
+        true
+    }
+
+    /// ### move_left
+    ///
+    /// Move cursor to left. Returns whether the cursor has moved
+    pub fn move_left(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.cursor -= 1;
+        true
+    }
+
+    /// ### move_right
+    ///
+    /// Move cursor to right. Returns whether the cursor has moved
+    pub fn move_right(&mut self) -> bool {
+        if self.cursor >= self.buffer.len() {
+            return false;
+        }
+        self.cursor += 1;
+        true
+    }
+
+    /// ### move_home
+    ///
+    /// Move cursor to the beginning of the line. Returns whether the cursor has moved
+    pub fn move_home(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.cursor = 0;
+        true
+    }
+
+    /// ### move_end
+    ///
+    /// Move cursor to the end of the line. Returns whether the cursor has moved
+    pub fn move_end(&mut self) -> bool {
+        if self.cursor == self.buffer.len() {
+            return false;
+        }
+        self.cursor = self.buffer.len();
+        true
+    }
+
+    /// ### cursor_column
+    ///
+    /// Returns the terminal column of the cursor, relative to the beginning of the line
+    pub fn cursor_column(&self) -> usize {
+        self.buffer.iter().take(self.cursor).map(|c| console::char_width(*c)).sum()
+    }
+
+    /// ### history_previous
+    ///
+    /// Replace the line with the previous (older) history entry.
+    /// The line typed by the user is saved the first time; at the oldest entry nothing happens.
+    /// Returns whether the line has changed
+    pub fn history_previous(&mut self, history: &ShellHistory) -> bool {
+        let entry: String = match history.at(self.history_index) {
+            Some(entry) => entry,
+            None => return false, //Already at the oldest entry
+        };
+        if self.history_index == 0 {
+            self.stash = self.buffer.clone();
+        }
+        self.history_index += 1;
+        self.set_line(entry.as_str());
+        true
+    }
+
+    /// ### history_next
+    ///
+    /// Replace the line with the next (newer) history entry.
+    /// Past the newest entry, the line the user was typing is restored.
+    /// Returns whether the line has changed
+    pub fn history_next(&mut self, history: &ShellHistory) -> bool {
+        match self.history_index {
+            0 => false,
+            1 => {
+                self.history_index = 0;
+                self.buffer = std::mem::take(&mut self.stash);
+                self.cursor = self.buffer.len();
+                true
+            }
+            _ => {
+                self.history_index -= 1;
+                match history.at(self.history_index - 1) {
+                    Some(entry) => self.set_line(entry.as_str()),
+                    None => self.clear(),
+                }
+                true
+            }
+        }
+    }
+
+    /// ### handle_input_event
+    ///
+    /// Apply an editing or history input event to the line.
+    /// Returns whether the event has been handled
+    pub fn handle_input_event(&mut self, ev: &InputEvent, history: &ShellHistory) -> bool {
+        match ev {
+            InputEvent::ArrowUp => self.history_previous(history),
+            InputEvent::ArrowDown => self.history_next(history),
+            InputEvent::ArrowLeft => self.move_left(),
+            InputEvent::ArrowRight => self.move_right(),
+            InputEvent::Backspace => self.backspace(),
+            InputEvent::Delete => self.delete(),
+            InputEvent::Home | InputEvent::Ctrl(1) => self.move_home(),
+            InputEvent::End | InputEvent::Ctrl(5) => self.move_end(),
+            InputEvent::Ctrl(11) => self.kill_to_end(),
+            InputEvent::Key(k) => {
+                self.insert(k.as_str());
+                true
+            }
+            InputEvent::Paste(text) => {
+                self.paste(text.as_str());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        LineEditor::new()
+    }
+}
+
+/// ## SearchAction
+///
+/// SearchAction is what the line editor has to do after an input event has been handled by the reverse search
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum SearchAction {
+    Update,  //Query or candidate changed; the search line must be rendered again
+    Execute, //Candidate accepted and placed in the line; it must be executed
+    Cancel,  //Search aborted; the original line has been restored
+    Leave,   //Candidate accepted and placed in the line; the event must be handled by the line editor
+}
+
+/// ## ReverseSearch
+///
+/// ReverseSearch is the incremental search through the history started with Ctrl+R.
+/// `candidate` is the index (0 is the newest entry) and the text of the entry matching the query;
+/// matching is case insensitive, both for latin and cyrillic characters.
+/// `original` is the line the user was typing before starting the search, restored if the search is cancelled
+pub struct ReverseSearch {
+    query: String,
+    candidate: Option<(usize, String)>,
+    failed: bool,
+    original: Vec<char>,
+}
+
+impl ReverseSearch {
+    /// ### new
+    ///
+    /// Start a new reverse search; the query is the line being typed
+    pub fn new(editor: &LineEditor, history: &ShellHistory) -> ReverseSearch {
+        let mut search: ReverseSearch = ReverseSearch {
+            query: editor.get_line(),
+            candidate: None,
+            failed: false,
+            original: editor.buffer.clone(),
+        };
+        search.search(0, false, history);
+        search
+    }
+
+    /// ### query
+    ///
+    /// Returns the text being searched
+    pub fn query(&self) -> &str {
+        self.query.as_str()
+    }
+
+    /// ### candidate
+    ///
+    /// Returns the history entry matching the query, if any
+    pub fn candidate(&self) -> Option<&str> {
+        self.candidate.as_ref().map(|(_, entry)| entry.as_str())
+    }
+
+    /// ### label
+    ///
+    /// Returns the label of the search line; it tells whether the last search has failed
+    pub fn label(&self) -> &'static str {
+        match self.failed {
+            true => "(failed reverse-i-search)",
+            false => "(reverse-i-search)",
+        }
+    }
+
+    /// ### push
+    ///
+    /// Append text to the query; the current candidate is kept if it still matches
+    pub fn push(&mut self, text: &str, history: &ShellHistory) {
+        self.query.push_str(text);
+        let start: usize = self.candidate.as_ref().map(|(index, _)| *index).unwrap_or(0);
+        self.search(start, false, history);
+    }
+
+    /// ### pop
+    ///
+    /// Remove the last character of the query and search again from the newest entry
+    pub fn pop(&mut self, history: &ShellHistory) {
+        self.query.pop();
+        self.candidate = None;
+        self.search(0, false, history);
+    }
+
+    /// ### next
+    ///
+    /// Move to the next older entry matching the query; entries equal to the current candidate are skipped
+    pub fn next(&mut self, history: &ShellHistory) {
+        let start: usize = match &self.candidate {
+            Some((index, _)) => index + 1,
+            None => 0,
+        };
+        self.search(start, true, history);
+    }
+
+    /// ### accept
+    ///
+    /// Put the candidate in the line; if there's no candidate the original line is kept
+    pub fn accept(&self, editor: &mut LineEditor) {
+        match &self.candidate {
+            Some((_, entry)) => editor.set_line(entry.as_str()),
+            None => self.cancel(editor),
+        }
+        editor.reset_history();
+    }
+
+    /// ### cancel
+    ///
+    /// Restore the line the user was typing before starting the search
+    pub fn cancel(&self, editor: &mut LineEditor) {
+        editor.buffer = self.original.clone();
+        editor.cursor = editor.buffer.len();
+    }
+
+    /// ### handle_input_event
+    ///
+    /// Apply an input event to the search: typed text and backspace change the query, Ctrl+R moves to the next match,
+    /// enter accepts the candidate to be executed, while Esc and Ctrl+G cancel the search.
+    /// The search is over unless `SearchAction::Update` is returned
+    pub fn handle_input_event(&mut self, ev: &InputEvent, editor: &mut LineEditor, history: &ShellHistory) -> SearchAction {
+        match ev {
+            InputEvent::Key(k) => self.push(k.as_str(), history),
+            InputEvent::Backspace => self.pop(history),
+            InputEvent::Ctrl(18) => self.next(history),
+            InputEvent::Enter | InputEvent::CarriageReturn => {
+                self.accept(editor);
+                return SearchAction::Execute;
+            }
+            InputEvent::Escape | InputEvent::Ctrl(7) => {
+                self.cancel(editor);
+                return SearchAction::Cancel;
+            }
+            _ => {
+                self.accept(editor);
+                return SearchAction::Leave;
+            }
+        }
+        SearchAction::Update
+    }
+
+    /// ### search
+    ///
+    /// Look for the query in the history, starting from the entry at `start`; if `skip_current` is true,
+    /// the entries equal to the current candidate are skipped.
+    /// If no entry matches, the current candidate is kept and the search is marked as failed
+    fn search(&mut self, start: usize, skip_current: bool, history: &ShellHistory) {
+        if self.query.is_empty() {
+            self.failed = false;
+            return;
+        }
+        let query: String = self.query.to_lowercase();
+        let current: Option<String> = self.candidate.as_ref().map(|(_, entry)| entry.clone());
+        let mut index: usize = start;
+        while let Some(entry) = history.at(index) {
+            let duplicate: bool = skip_current && Some(&entry) == current.as_ref();
+            if !duplicate && entry.to_lowercase().contains(query.as_str()) {
+                self.candidate = Some((index, entry));
+                self.failed = false;
+                return;
+            }
+            index += 1;
+        }
+        self.failed = true;
+    }
+}
+
+/// ## MultilineInput
+///
+/// MultilineInput accumulates the lines of a command which continues on the next line, because it has an unclosed quote
+/// or it ends with a backslash. A backslash followed by the newline is removed, as the shell does,
+/// while the lines of a quoted string are joined with `PASTE_NEWLINE`, so the command is run as pasted text
+#[derive(Default)]
+pub struct MultilineInput {
+    pending: Option<String>,
+}
+
+impl MultilineInput {
+    /// ### new
+    ///
+    /// Instantiate a new empty MultilineInput
+    pub fn new() -> MultilineInput {
+        MultilineInput { pending: None }
+    }
+
+    /// ### is_pending
+    ///
+    /// Returns whether the command continues on the next line
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// ### clear
+    ///
+    /// Discard the lines entered so far
+    pub fn clear(&mut self) {
+        self.pending = None;
+    }
+
+    /// ### push
+    ///
+    /// Push an entered line. Returns the whole command once it's complete;
+    /// None is returned while it continues on the next line
+    pub fn push(&mut self, line: &str) -> Option<String> {
+        let expression: String = match self.pending.take() {
+            Some(mut pending) => {
+                match is_complete(pending.as_str()) {
+                    CompletionState::TrailingBackslash => {
+                        pending.pop();
+                    }
+                    _ => pending.push(PASTE_NEWLINE),
+                }
+                pending.push_str(line);
+                pending
+            }
+            None => String::from(line),
+        };
+        match is_complete(expression.as_str()) {
+            CompletionState::Complete => Some(expression),
+            _ => {
+                self.pending = Some(expression);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::utils::console::decode_input;
+
+    #[test]
+    fn test_lineeditor_new() {
+        let editor: LineEditor = LineEditor::new();
+        assert_eq!(editor.buffer.len(), 0);
+        assert_eq!(editor.cursor, 0);
+        assert_eq!(editor.history_index, 0);
+        assert_eq!(editor.stash.len(), 0);
+        assert_eq!(editor.get_line(), String::new());
+    }
+
+    #[test]
+    fn test_lineeditor_edit() {
+        let mut editor: LineEditor = LineEditor::new();
+        let history: ShellHistory = ShellHistory::new(16);
+        feed(&mut editor, &history, "лс -л".as_bytes());
+        assert_eq!(editor.get_line(), String::from("лс -л"));
+        assert_eq!(editor.cursor, 5);
+        //Move left and insert
+        feed(&mut editor, &history, b"\x1b[D\x1b[Da");
+        assert_eq!(editor.get_line(), String::from("лс a-л"));
+        assert_eq!(editor.cursor, 4);
+        //Backspace
+        feed(&mut editor, &history, b"\x7f\x7f");
+        assert_eq!(editor.get_line(), String::from("лс-л"));
+        assert_eq!(editor.cursor, 2);
+        //Move out of bounds
+        feed(&mut editor, &history, b"\x1b[C\x1b[C\x1b[C\x1b[C");
+        assert_eq!(editor.cursor, 4);
+        feed(&mut editor, &history, b"\x1b[D\x1b[D\x1b[D\x1b[D\x1b[D\x7f");
+        assert_eq!(editor.cursor, 0);
+        assert_eq!(editor.get_line(), String::from("лс-л"));
+    }
+
+    #[test]
+    fn test_lineeditor_replace_range() {
+        let mut editor: LineEditor = LineEditor::new();
+        editor.set_line("кат маи foo");
+        editor.replace_range(4, 7, "main.rs ");
+        assert_eq!(editor.get_line(), String::from("кат main.rs  foo"));
+        assert_eq!(editor.cursor, 12);
+        //Out of bounds
+        editor.replace_range(17, 20, "bar");
+        assert_eq!(editor.get_line(), String::from("кат main.rs  foobar"));
+        assert_eq!(editor.cursor, 19);
+    }
+
+    #[test]
+    fn test_lineeditor_inline_editing() {
+        let mut editor: LineEditor = LineEditor::new();
+        let history: ShellHistory = ShellHistory::new(16);
+        feed(&mut editor, &history, "экхо привет".as_bytes());
+        //Fix typo at the beginning: Home, Delete, insert
+        feed(&mut editor, &history, b"\x1b[H\x1b[3~");
+        assert_eq!(editor.get_line(), String::from("кхо привет"));
+        assert_eq!(editor.cursor, 0);
+        assert_eq!(editor.cursor_column(), 0);
+        feed(&mut editor, &history, "э".as_bytes());
+        assert_eq!(editor.get_line(), String::from("экхо привет"));
+        assert_eq!(editor.cursor, 1);
+        //End (and Ctrl+E), Delete at the end does nothing
+        feed(&mut editor, &history, b"\x1b[F\x1b[3~\x05");
+        assert_eq!(editor.get_line(), String::from("экхо привет"));
+        assert_eq!(editor.cursor, 11);
+        assert_eq!(editor.cursor_column(), 11);
+        //Ctrl+A, move right, backspace and delete relative to cursor
+        feed(&mut editor, &history, b"\x01\x1b[C\x1b[C\x7f\x1b[3~");
+        assert_eq!(editor.get_line(), String::from("эо привет"));
+        assert_eq!(editor.cursor, 1);
+        //VT Home and End
+        feed(&mut editor, &history, b"\x1b[4~");
+        assert_eq!(editor.cursor, 9);
+        feed(&mut editor, &history, b"\x1b[1~");
+        assert_eq!(editor.cursor, 0);
+        //Kill to end
+        feed(&mut editor, &history, b"\x1b[C\x1b[C\x0b");
+        assert_eq!(editor.get_line(), String::from("эо"));
+        assert_eq!(editor.cursor, 2);
+    }
+
+    #[test]
+    fn test_lineeditor_cursor_column() {
+        let mut editor: LineEditor = LineEditor::new();
+        editor.set_line("ls 日本 лс");
+        assert_eq!(editor.cursor, 8);
+        assert_eq!(editor.cursor_column(), 10);
+        editor.move_left();
+        editor.move_left();
+        editor.move_left();
+        assert_eq!(editor.cursor_column(), 7);
+        editor.move_left();
+        assert_eq!(editor.cursor_column(), 5);
+        //Cursor beyond the buffer
+        editor.cursor = 32;
+        assert_eq!(editor.cursor_column(), 10);
+    }
+
+    #[test]
+    fn test_lineeditor_history() {
+        let mut editor: LineEditor = LineEditor::new();
+        let mut history: ShellHistory = ShellHistory::new(16);
+        history.push(String::from("pwd"));
+        history.push(String::from("лс -л"));
+        //Type something, then go up
+        feed(&mut editor, &history, "экхо".as_bytes());
+        feed(&mut editor, &history, b"\x1b[A");
+        assert_eq!(editor.get_line(), String::from("лс -л"));
+        assert_eq!(editor.cursor, 5);
+        assert_eq!(editor.history_index, 1);
+        feed(&mut editor, &history, b"\x1b[A");
+        assert_eq!(editor.get_line(), String::from("pwd"));
+        assert_eq!(editor.history_index, 2);
+        //Up at the oldest entry stays put
+        feed(&mut editor, &history, b"\x1b[A\x1b[A");
+        assert_eq!(editor.get_line(), String::from("pwd"));
+        assert_eq!(editor.history_index, 2);
+        //Down
+        feed(&mut editor, &history, b"\x1b[B");
+        assert_eq!(editor.get_line(), String::from("лс -л"));
+        assert_eq!(editor.history_index, 1);
+        //Down past the newest restores the typed line
+        feed(&mut editor, &history, b"\x1b[B");
+        assert_eq!(editor.get_line(), String::from("экхо"));
+        assert_eq!(editor.cursor, 4);
+        assert_eq!(editor.history_index, 0);
+        //Further downs change nothing
+        feed(&mut editor, &history, b"\x1b[B\x1b[B");
+        assert_eq!(editor.get_line(), String::from("экхо"));
+        assert_eq!(editor.history_index, 0);
+        //Edit an history entry, then go back to the typed line
+        feed(&mut editor, &history, b"\x1b[A\x7f\x7f\x1b[B");
+        assert_eq!(editor.get_line(), String::from("экхо"));
+        //Reset history
+        feed(&mut editor, &history, b"\x1b[A");
+        editor.reset_history();
+        editor.clear();
+        assert_eq!(editor.history_index, 0);
+        feed(&mut editor, &history, b"\x1b[B");
+        assert_eq!(editor.get_line(), String::new());
+    }
+
+    #[test]
+    fn test_lineeditor_empty_history() {
+        let mut editor: LineEditor = LineEditor::new();
+        let history: ShellHistory = ShellHistory::new(16);
+        feed(&mut editor, &history, b"ls\x1b[A\x1b[B");
+        assert_eq!(editor.get_line(), String::from("ls"));
+        assert_eq!(editor.history_index, 0);
+    }
+
+    #[test]
+    fn test_lineeditor_paste() {
+        let mut editor: LineEditor = LineEditor::new();
+        let history: ShellHistory = ShellHistory::new(16);
+        feed(&mut editor, &history, "экхо ".as_bytes());
+        feed(&mut editor, &history, "\x1b[200~a\tb\r\nлс -л\n\x03\x1b[Aпривет\r\x1b[201~".as_bytes());
+        assert_eq!(editor.get_line(), String::from("экхо a b⏎лс -л⏎[Aпривет⏎"));
+        assert_eq!(editor.cursor, editor.buffer.len());
+    }
+
+    #[test]
+    fn test_lineeditor_multiline_input() {
+        let mut input: MultilineInput = MultilineInput::new();
+        assert!(!input.is_pending());
+        //Complete line
+        assert_eq!(input.push("лс -л"), Some(String::from("лс -л")));
+        assert!(!input.is_pending());
+        //Unclosed quote
+        assert_eq!(input.push("экхо \"привет"), None);
+        assert!(input.is_pending());
+        assert_eq!(input.push(""), None);
+        assert_eq!(input.push("мир\" | кат"), Some(String::from("экхо \"привет⏎⏎мир\" | кат")));
+        assert!(!input.is_pending());
+        //Trailing backslash
+        assert_eq!(input.push("экхо привет \\"), None);
+        assert_eq!(input.push("'мир \\"), None);
+        assert_eq!(input.push("!' \\"), None);
+        assert_eq!(input.push(""), Some(String::from("экхо привет 'мир \\⏎!' ")));
+        //Escaped backslash
+        assert_eq!(input.push("экхо \\\\"), Some(String::from("экхо \\\\")));
+        //Clear
+        assert_eq!(input.push("экхо 'привет"), None);
+        input.clear();
+        assert!(!input.is_pending());
+        assert_eq!(input.push("лс"), Some(String::from("лс")));
+    }
+
+    #[test]
+    fn test_lineeditor_reverse_search() {
+        let mut history: ShellHistory = ShellHistory::new(16);
+        history.push(String::from("экхо мир"));
+        history.push(String::from("экхо Привет"));
+        history.push(String::from("лс -л /tmp"));
+        history.push(String::from("GIT status"));
+        history.push(String::from("экхо мир"));
+        history.push(String::from("git log"));
+        //Empty query: no candidate
+        let mut editor: LineEditor = LineEditor::new();
+        let mut search: ReverseSearch = ReverseSearch::new(&editor, &history);
+        assert_eq!(search.query(), "");
+        assert_eq!(search.candidate(), None);
+        assert_eq!(search.label(), "(reverse-i-search)");
+        //Type (case insensitive, cyrillic)
+        assert_eq!(feed_search(&mut search, &mut editor, &history, "ЭК".as_bytes()), SearchAction::Update);
+        assert_eq!(search.query(), "ЭК");
+        assert_eq!(search.candidate(), Some("экхо мир"));
+        //Ctrl+R moves to the next older match
+        feed_search(&mut search, &mut editor, &history, b"\x12");
+        assert_eq!(search.candidate(), Some("экхо Привет"));
+        feed_search(&mut search, &mut editor, &history, b"\x12");
+        assert_eq!(search.candidate(), Some("экхо мир"));
+        //No more matches: the candidate is kept, the search fails
+        feed_search(&mut search, &mut editor, &history, b"\x12");
+        assert_eq!(search.candidate(), Some("экхо мир"));
+        assert_eq!(search.label(), "(failed reverse-i-search)");
+        //Backspace searches again from the newest entry
+        feed_search(&mut search, &mut editor, &history, b"\x7f");
+        assert_eq!(search.query(), "Э");
+        assert_eq!(search.candidate(), Some("экхо мир"));
+        assert_eq!(search.label(), "(reverse-i-search)");
+        //Typing moves to an older entry only if the candidate doesn't match anymore
+        feed_search(&mut search, &mut editor, &history, "кхо ".as_bytes());
+        assert_eq!(search.candidate(), Some("экхо мир"));
+        feed_search(&mut search, &mut editor, &history, "п".as_bytes());
+        assert_eq!(search.candidate(), Some("экхо Привет"));
+        feed_search(&mut search, &mut editor, &history, "x".as_bytes());
+        assert_eq!(search.candidate(), Some("экхо Привет"));
+        assert_eq!(search.label(), "(failed reverse-i-search)");
+        //Enter accepts and executes
+        assert_eq!(feed_search(&mut search, &mut editor, &history, b"\n"), SearchAction::Execute);
+        assert_eq!(editor.get_line(), String::from("экхо Привет"));
+        assert_eq!(editor.cursor, 11);
+        //Entries equal to the candidate are skipped
+        let mut editor: LineEditor = LineEditor::new();
+        editor.set_line("МИР");
+        let mut search: ReverseSearch = ReverseSearch::new(&editor, &history);
+        assert_eq!(search.candidate(), Some("экхо мир"));
+        feed_search(&mut search, &mut editor, &history, b"\x12");
+        assert_eq!(search.candidate(), Some("экхо мир"));
+        assert_eq!(search.label(), "(failed reverse-i-search)");
+        //Latin, case insensitive; the query is the line being typed
+        let mut editor: LineEditor = LineEditor::new();
+        editor.set_line("Git");
+        let mut search: ReverseSearch = ReverseSearch::new(&editor, &history);
+        assert_eq!(search.candidate(), Some("git log"));
+        feed_search(&mut search, &mut editor, &history, b"\x12");
+        assert_eq!(search.candidate(), Some("GIT status"));
+        //Esc cancels and restores the original line
+        assert_eq!(feed_search(&mut search, &mut editor, &history, b"\x1b"), SearchAction::Cancel);
+        assert_eq!(editor.get_line(), String::from("Git"));
+        assert_eq!(editor.cursor, 3);
+        //Ctrl+G cancels too
+        let mut search: ReverseSearch = ReverseSearch::new(&editor, &history);
+        feed_search(&mut search, &mut editor, &history, b" l");
+        assert_eq!(search.candidate(), Some("git log"));
+        assert_eq!(feed_search(&mut search, &mut editor, &history, b"\x07"), SearchAction::Cancel);
+        assert_eq!(editor.get_line(), String::from("Git"));
+        //Other keys accept the candidate and are left to the editor
+        let mut search: ReverseSearch = ReverseSearch::new(&editor, &history);
+        feed_search(&mut search, &mut editor, &history, b"\x12");
+        assert_eq!(feed_search(&mut search, &mut editor, &history, b"\x1b[D"), SearchAction::Leave);
+        assert_eq!(editor.get_line(), String::from("GIT status"));
+        //Accepting without a candidate keeps the original line
+        let mut editor: LineEditor = LineEditor::new();
+        editor.set_line("kubectl");
+        let mut search: ReverseSearch = ReverseSearch::new(&editor, &history);
+        assert_eq!(search.candidate(), None);
+        assert_eq!(feed_search(&mut search, &mut editor, &history, b"\r"), SearchAction::Execute);
+        assert_eq!(editor.get_line(), String::from("kubectl"));
+    }
+
+    /// ### feed_search
+    ///
+    /// Feed reverse search with bytes as they would be read from stdin; returns the action of the last event
+    fn feed_search(search: &mut ReverseSearch, editor: &mut LineEditor, history: &ShellHistory, bytes: &[u8]) -> SearchAction {
+        let mut action: SearchAction = SearchAction::Update;
+        for ev in decode_input(bytes).iter() {
+            action = search.handle_input_event(ev, editor, history);
+        }
+        action
+    }
+
+    /// ### feed
+    ///
+    /// Feed editor with bytes as they would be read from stdin
+    fn feed(editor: &mut LineEditor, history: &ShellHistory, bytes: &[u8]) {
+        for ev in decode_input(bytes).iter() {
+            editor.handle_input_event(ev, history);
+        }
+    }
+}
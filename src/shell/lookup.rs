@@ -0,0 +1,193 @@
+//! ## Lookup
+//!
+//! `lookup` selects the shell wrapped by Pyc and checks that it can be executed
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::completion::is_executable;
+
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Shell used when no other shell can be determined
+pub const FALLBACK_SHELL: &str = "/bin/sh";
+
+//Shells which can be detected as the one to wrap; they must be able to run the handshake (fish can't)
+const KNOWN_SHELLS: [&str; 6] = ["sh", "bash", "zsh", "dash", "ksh", "mksh"];
+
+/// ### LookupError
+///
+/// LookupError describes why the selected shell can't be executed
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub enum LookupError {
+    NotFound(String),
+    NotExecutable(String),
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LookupError::NotFound(exec) => write!(f, "{}: shell not found", exec),
+            LookupError::NotExecutable(exec) => write!(f, "{}: shell is not executable", exec),
+        }
+    }
+}
+
+/// ### select
+///
+/// Select the shell to wrap: the one given on the command line comes first, then the one in configuration,
+/// the shell Pyc has been started from, `SHELL` and at last `/bin/sh`.
+/// The detected shells (the parent and `SHELL`) are used only if they're known shells and they're not Pyc itself (`self_exe`),
+/// otherwise Pyc would wrap itself when it is the login shell
+pub fn select(cli: Option<String>, config: Option<String>, parent: Option<String>, env_shell: Option<String>, self_exe: Option<PathBuf>) -> String {
+    let detected = |shell: &String| is_known_shell(shell.as_str()) && !is_self(shell.as_str(), self_exe.as_deref());
+    cli.or(config)
+        .or_else(|| parent.filter(detected))
+        .or_else(|| env_shell.filter(detected))
+        .unwrap_or_else(|| String::from(FALLBACK_SHELL))
+}
+
+/// ### parent_shell
+///
+/// Returns the executable of the process Pyc has been started from
+pub fn parent_shell() -> Option<String> {
+    fs::read_link(format!("/proc/{}/exe", nix::unistd::getppid()))
+        .ok()
+        .map(|exe| exe.to_string_lossy().to_string())
+}
+
+/// ### is_known_shell
+///
+/// Returns whether the file name of the shell is one of the shells which can be detected
+fn is_known_shell(shell: &str) -> bool {
+    match Path::new(shell).file_name() {
+        Some(name) => KNOWN_SHELLS.contains(&name.to_string_lossy().as_ref()),
+        None => false,
+    }
+}
+
+/// ### is_self
+///
+/// Returns whether the shell resolves to the executable of Pyc
+fn is_self(shell: &str, self_exe: Option<&Path>) -> bool {
+    let self_exe: PathBuf = match self_exe.map(fs::canonicalize) {
+        Some(Ok(exe)) => exe,
+        _ => return false,
+    };
+    let shell: PathBuf = match check(shell, std::env::var_os("PATH")) {
+        Ok(shell) => shell,
+        Err(_) => return false,
+    };
+    fs::canonicalize(shell).map(|shell| shell == self_exe).unwrap_or(false)
+}
+
+/// ### check
+///
+/// Check that the shell exists and is executable; a shell without `/` is looked up in `path` (the value of `PATH`).
+/// Returns the path of the shell
+pub fn check(exec: &str, path: Option<OsString>) -> Result<PathBuf, LookupError> {
+    let candidates: Vec<PathBuf> = match exec.contains('/') {
+        true => vec![PathBuf::from(exec)],
+        false => match path {
+            Some(path) => std::env::split_paths(&path).map(|dir| dir.join(exec)).collect(),
+            None => Vec::new(),
+        },
+    };
+    if let Some(shell) = candidates.iter().find(|candidate| is_executable(candidate.as_path())) {
+        return Ok(shell.clone());
+    }
+    match candidates.iter().any(|candidate| Path::exists(candidate.as_path())) {
+        true => Err(LookupError::NotExecutable(String::from(exec))),
+        false => Err(LookupError::NotFound(String::from(exec))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_lookup_select() {
+        let select_with = |cli: Option<&str>, config: Option<&str>, parent: Option<&str>, env_shell: Option<&str>| {
+            select(cli.map(String::from), config.map(String::from), parent.map(String::from), env_shell.map(String::from), None)
+        };
+        assert_eq!(select_with(Some("zsh"), Some("fish"), Some("/bin/bash"), Some("/bin/dash")), String::from("zsh"));
+        assert_eq!(select_with(None, Some("fish"), Some("/bin/bash"), Some("/bin/dash")), String::from("fish"));
+        assert_eq!(select_with(None, None, Some("/bin/bash"), Some("/bin/dash")), String::from("/bin/bash"));
+        assert_eq!(select_with(None, None, None, Some("/bin/dash")), String::from("/bin/dash"));
+        assert_eq!(select_with(None, None, None, Some("")), String::from("/bin/sh"));
+        assert_eq!(select_with(None, None, None, None), String::from("/bin/sh"));
+        //Fish can't run the handshake: it's never detected
+        assert_eq!(select_with(None, None, Some("/usr/bin/fish"), Some("/usr/bin/fish")), String::from("/bin/sh"));
+        assert_eq!(select_with(None, None, Some("/usr/bin/fish"), Some("/bin/zsh")), String::from("/bin/zsh"));
+        //Unknown parents (e.g. sshd) are skipped
+        assert_eq!(select_with(None, None, Some("/usr/sbin/sshd"), Some("/bin/bash")), String::from("/bin/bash"));
+    }
+
+    #[test]
+    fn test_lookup_select_self() {
+        //Pyc is the login shell: SHELL points at it
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let pyc: PathBuf = tmpdir.path().join("pyc");
+        std::fs::write(&pyc, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&pyc, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let env_shell: String = String::from(pyc.to_str().unwrap());
+        assert_eq!(select(None, None, None, Some(env_shell), Some(pyc.clone())), String::from("/bin/sh"));
+        //Pyc installed as a known shell name
+        let link: PathBuf = tmpdir.path().join("bash");
+        std::os::unix::fs::symlink(&pyc, &link).unwrap();
+        let link: String = String::from(link.to_str().unwrap());
+        assert_eq!(select(None, None, Some(link.clone()), Some(link.clone()), Some(pyc.clone())), String::from("/bin/sh"));
+        assert_eq!(select(None, None, Some(String::from("/bin/sh")), Some(link.clone()), Some(pyc.clone())), String::from("/bin/sh"));
+        //Explicit shells are always used
+        assert_eq!(select(Some(link.clone()), None, None, None, Some(pyc.clone())), link);
+        //A different executable is fine
+        assert_eq!(select(None, None, None, Some(link.clone()), Some(PathBuf::from("/bin/sh"))), link);
+    }
+
+    #[test]
+    fn test_lookup_check() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let shell: PathBuf = tmpdir.path().join("fakesh");
+        std::fs::write(&shell, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&shell, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let path: Option<OsString> = Some(OsString::from(format!("/nonexisting:{}", tmpdir.path().display())));
+        //Not executable
+        assert_eq!(check(shell.to_str().unwrap(), None).err().unwrap(), LookupError::NotExecutable(String::from(shell.to_str().unwrap())));
+        assert_eq!(check("fakesh", path.clone()).err().unwrap(), LookupError::NotExecutable(String::from("fakesh")));
+        //Executable, by path and looked up in PATH
+        std::fs::set_permissions(&shell, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(check(shell.to_str().unwrap(), None).unwrap(), shell);
+        assert_eq!(check("fakesh", path.clone()).unwrap(), shell);
+        //Not found
+        assert_eq!(check("nosuchsh", path).err().unwrap(), LookupError::NotFound(String::from("nosuchsh")));
+        assert_eq!(check("fakesh", None).err().unwrap(), LookupError::NotFound(String::from("fakesh")));
+        assert_eq!(check("/nonexisting/sh", None).err().unwrap(), LookupError::NotFound(String::from("/nonexisting/sh")));
+        assert_eq!(LookupError::NotFound(String::from("zsh")).to_string(), String::from("zsh: shell not found"));
+        assert_eq!(LookupError::NotExecutable(String::from("zsh")).to_string(), String::from("zsh: shell is not executable"));
+    }
+}
@@ -23,23 +23,47 @@
 *
 */
 
+pub mod alias;
+pub mod completion;
+pub mod builtins;
+pub mod dirstack;
+pub mod environment;
+pub mod escalation;
+pub mod expansion;
+pub mod handshake;
 pub mod history;
+pub mod jobs;
+pub mod lineeditor;
+pub mod lookup;
 pub mod proc;
+pub mod terminal;
 pub mod prompt;
+pub mod session;
+pub mod suggestion;
 pub mod unixsignal;
 
 extern crate nix;
 extern crate whoami;
 
+use dirstack::DirStack;
+use escalation::{Escalation, InterruptAction};
+use handshake::{HandshakeError, HandshakeFailure};
 use history::ShellHistory;
-use proc::{ShellError, ShellProc, ShellProcState};
-use prompt::ShellPrompt;
+use jobs::{CommandTiming, Job, JobError, JobTable, JobsSummary};
+use proc::{ExecOptions, ShellError, ShellProc, ShellProcState};
+use prompt::{PromptCache, ShellPrompt, StatusReport};
+use session::ShellSession;
 
-use crate::config::PromptConfig;
+use crate::config::{PromptConfig, SigintEscalation};
 use crate::translator::ioprocessor::IOProcessor;
+use crate::translator::lang::Language;
+use crate::utils::buffer;
+use crate::utils::logger;
+use crate::utils::terminal::{self as term, TerminalSize};
 
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::time::{Duration};
+use std::time::{Duration, Instant};
 
 /// ### ShellState
 /// 
@@ -58,9 +82,16 @@ pub enum ShellState {
 /// Shell represents the current user shell configuration
 pub struct Shell {
     pub history: ShellHistory,
+    pub dirstack: DirStack,
+    pub aliases: HashMap<String, String>, // Aliases defined at runtime with `alias`
+    exec: String,
     process: ShellProc,
-    prompt: ShellPrompt,
-    props: ShellProps,
+    jobs: JobTable,
+    prompt: Option<ShellPrompt>, // Built when the prompt is printed for the first time
+    prompt_config: PromptConfig,
+    session: ShellSession,
+    pty: bool, // Whether processes run under a pseudo-terminal
+    escalation: Escalation, // Interrupts sent to the running command
     state: ShellState
 }
 
@@ -72,7 +103,10 @@ pub(crate) struct ShellProps {
     pub hostname: String,
     pub elapsed_time: Duration,
     pub exit_status: u8,
-    pub wrkdir: PathBuf
+    pub wrkdir: PathBuf,
+    pub uid: u32, //Effective user id
+    pub degraded: Vec<String>,
+    pub jobs: JobsSummary //Jobs in background, updated before each prompt
 }
 
 impl Shell {
@@ -80,27 +114,48 @@ impl Shell {
     ///  
     /// Start a new shell instance and instantiates a new Shell struct
     pub fn start(exec: String, args: Vec<String>, prompt_config: &PromptConfig) -> Result<Shell, ShellError> {
+        Shell::start_with(exec, args, prompt_config, false)
+    }
+
+    /// ### start_with
+    ///
+    /// Start a new shell instance; if `pty` is true, the shell and the jobs started by pyc run under a pseudo-terminal
+    pub fn start_with(exec: String, args: Vec<String>, prompt_config: &PromptConfig, pty: bool) -> Result<Shell, ShellError> {
         //Start shell
         let mut argv: Vec<String> = Vec::with_capacity(1 + args.len());
-        let shell_prompt: ShellPrompt = ShellPrompt::new(prompt_config);
         argv.push(exec.clone());
         for arg in args.iter() {
             argv.push(arg.clone());
         }
-        let shell_process: ShellProc = match ShellProc::start(argv) {
+        //The shell gets the size of the terminal, since its commands can't query it through the pipes
+        let options: ExecOptions = ExecOptions {
+            wrkdir: None,
+            env: terminal_env(),
+            clear_env: false,
+            pty,
+        };
+        let shell_process: ShellProc = match ShellProc::start_with(argv.clone(), &options) {
             Ok(p) => p,
-            Err(err) => return Err(err),
+            Err(err) => {
+                logger::error("shell", format!("could not start {}: {}", argv.join(" "), err).as_str());
+                return Err(err);
+            }
         };
-        //Get process username
-        let user: String = whoami::username();
-        //Get hostname
-        let hostname: String = Shell::get_hostname();
-        let wrkdir: PathBuf = shell_process.wrkdir.clone();
+        logger::info("shell", format!("started {} (pid {}, pty: {})", argv.join(" "), shell_process.pid, pty).as_str());
+        //Username and hostname are looked up once
+        let session: ShellSession = ShellSession::new(shell_process.wrkdir.clone());
         Ok(Shell {
+            exec,
             process: shell_process,
-            prompt: shell_prompt,
-            props: ShellProps::new(hostname, user, wrkdir),
-            history: ShellHistory::new(),
+            jobs: JobTable::new(),
+            prompt: None,
+            prompt_config: prompt_config.clone(),
+            session,
+            history: ShellHistory::new(prompt_config.history_size),
+            dirstack: DirStack::new(),
+            aliases: HashMap::new(),
+            pty,
+            escalation: Escalation::new(&SigintEscalation::default()),
             state: ShellState::Shell
         })
     }
@@ -113,43 +168,244 @@ impl Shell {
             let _ = self.process.kill();
         }
         self.history.clear();
-        self.process.cleanup()
+        let rc: Result<u8, ShellError> = self.process.cleanup();
+        if let Ok(rc) = rc {
+            logger::info("shell", format!("shell exited with {}", rc).as_str());
+        }
+        rc
+    }
+
+    /// ### handshake
+    ///
+    /// Wait for the shell to answer its first command, within the timeout.
+    /// Returns what the shell printed before answering (e.g. the banners printed by its rc files);
+    /// if the shell doesn't answer, the error contains everything it has emitted
+    pub fn handshake(&mut self, timeout: Duration) -> Result<String, HandshakeError> {
+        let mut output: String = String::new();
+        let started: Instant = Instant::now();
+        let mut state: ShellState = match self.write(String::from(":\n")) {
+            Ok(_) => ShellState::SubprocessRunning,
+            Err(_) => ShellState::Terminated,
+        };
+        loop {
+            match state {
+                ShellState::Shell => break Ok(output),
+                ShellState::Terminated => {
+                    //Read everything the shell has written before terminating
+                    while self.read_handshake_output(&mut output) {}
+                    logger::warn("shell", "shell terminated before answering the handshake");
+                    break Err(HandshakeError::new(HandshakeFailure::Terminated, output));
+                }
+                _ => {
+                    self.read_handshake_output(&mut output);
+                }
+            }
+            if started.elapsed() >= timeout {
+                logger::warn("shell", format!("shell didn't answer the handshake within {}ms", timeout.as_millis()).as_str());
+                break Err(HandshakeError::new(HandshakeFailure::Timeout(timeout), output));
+            }
+            state = self.get_state();
+        }
+    }
+
+    /// ### read_handshake_output
+    ///
+    /// Append what the shell has written to the output of the handshake; returns whether something has been read
+    fn read_handshake_output(&mut self, output: &mut String) -> bool {
+        match self.read() {
+            Ok((None, None)) | Err(_) => false,
+            Ok((stdout, stderr)) => {
+                output.push_str(stdout.unwrap_or_default().as_str());
+                output.push_str(stderr.unwrap_or_default().as_str());
+                true
+            }
+        }
     }
 
     /// ### read
     ///
     /// Mirrors ShellProc read
     pub fn read(&mut self) -> Result<(Option<String>, Option<String>), ShellError> {
-        self.process.read()
+        //If a job is in foreground, its output is read instead of the shell's one
+        let (mut stdout, mut stderr) = match self.jobs.foreground_process() {
+            Some(process) => process.read()?,
+            None => self.process.read()?,
+        };
+        let (job_stdout, job_stderr) = self.jobs.read_background();
+        jobs::append_output(&mut stdout, job_stdout);
+        jobs::append_output(&mut stderr, job_stderr);
+        Ok((stdout, stderr))
     }
 
     /// ### write
     ///
-    /// Mirrors ShellProc write
+    /// Mirrors ShellProc write; the raw bytes of file names which are not valid UTF-8 are written as they were.
+    /// If the job in foreground has exited `ProcessTerminated` is returned, while `ShellTerminated` is returned if the shell has
     pub fn write(&mut self, input: String) -> Result<(), ShellError> {
-        self.process.write(input)
+        let input: Vec<u8> = buffer::string_to_bytes(input.as_str());
+        match self.jobs.foreground_process() {
+            Some(process) => process.write_bytes(input),
+            None => match self.process.write_bytes(input) {
+                Err(ShellError::ProcessTerminated) => Err(ShellError::ShellTerminated),
+                result => result,
+            },
+        }
     }
 
     /// ### raise
     ///
-    /// Send a signal to shell process
-    #[allow(dead_code)]
+    /// Send a signal to shell process.
+    /// SIGINT and SIGQUIT are sent to the processes of the running command (e.g. to every stage of a pipeline), so that the shell keeps running;
+    /// if no command is running, SIGINT is sent to the shell, while SIGQUIT is discarded
     pub fn raise(&mut self, sig: unixsignal::UnixSignal) -> Result<(), ShellError> {
+        logger::debug("shell", format!("raising {:?}", sig).as_str());
+        if let Some(process) = self.jobs.foreground_process() {
+            return process.raise(sig.to_nix_signal());
+        }
+        //Interrupt stops waiting for jobs
+        if sig == unixsignal::UnixSignal::Sigint && self.jobs.is_blocking() {
+            self.jobs.wait(false);
+            self.process.exit_status = 130;
+            return Ok(());
+        }
+        if sig == unixsignal::UnixSignal::Sigint && self.process.raise_children(sig.to_nix_signal()) > 0 {
+            return Ok(());
+        }
+        if sig == unixsignal::UnixSignal::Sigquit {
+            self.process.raise_children(sig.to_nix_signal());
+            return Ok(());
+        }
         self.process.raise(sig.to_nix_signal())
     }
 
+    /// ### interrupt
+    ///
+    /// Interrupt the running command (Ctrl+C). If it keeps running after being interrupted the configured amount of times
+    /// within the window, it's terminated (SIGTERM) instead, and killed once the timeout has elapsed (see `poll_escalation`).
+    /// Returns the signal which has been sent
+    pub fn interrupt(&mut self) -> Result<InterruptAction, ShellError> {
+        let action: InterruptAction = match self.get_state() {
+            ShellState::SubprocessRunning => self.escalation.interrupt(Instant::now()),
+            _ => InterruptAction::Interrupt,
+        };
+        match action {
+            InterruptAction::Interrupt => self.raise(unixsignal::UnixSignal::Sigint)?,
+            InterruptAction::Terminate => {
+                logger::warn("shell", "command is still running after being interrupted: sending SIGTERM");
+                self.signal_command(nix::sys::signal::Signal::SIGTERM)?;
+            }
+        }
+        Ok(action)
+    }
+
+    /// ### poll_escalation
+    ///
+    /// Kill the command which has been terminated by `interrupt`, if it's still running once the timeout has elapsed.
+    /// Returns whether it has been killed
+    pub fn poll_escalation(&mut self) -> bool {
+        if !self.escalation.kill_due(Instant::now()) || self.get_state() != ShellState::SubprocessRunning {
+            return false;
+        }
+        logger::warn("shell", "command is still running after SIGTERM: sending SIGKILL");
+        self.signal_command(nix::sys::signal::Signal::SIGKILL).is_ok()
+    }
+
+    /// ### abort
+    ///
+    /// Send a signal to the running command and to the shell (e.g. when the command has run out of time)
+    pub fn abort(&mut self, sig: unixsignal::UnixSignal) -> Result<(), ShellError> {
+        let _ = self.signal_command(sig.to_nix_signal());
+        self.process.raise(sig.to_nix_signal())
+    }
+
+    /// ### set_sigint_escalation
+    ///
+    /// Set when an interrupted command is terminated and killed
+    pub fn set_sigint_escalation(&mut self, config: &SigintEscalation) {
+        self.escalation = Escalation::new(config);
+    }
+
+    /// ### signal_command
+    ///
+    /// Send a signal to the running command: to the job in foreground or to the processes started by the shell, never to the shell itself
+    fn signal_command(&mut self, signal: nix::sys::signal::Signal) -> Result<(), ShellError> {
+        match self.jobs.foreground_process() {
+            Some(process) => process.raise(signal),
+            None => {
+                self.process.raise_children(signal);
+                Ok(())
+            }
+        }
+    }
+
+    /// ### signal_job
+    ///
+    /// Send a signal to a job (`kill %N`)
+    pub fn signal_job(&mut self, id: usize, sig: unixsignal::UnixSignal) -> Result<(), JobError> {
+        self.jobs.update();
+        self.jobs.signal(id, sig.to_nix_signal())
+    }
+
+    /// ### signal_process
+    ///
+    /// Send a signal to a process (`kill PID`)
+    pub fn signal_process(&self, pid: i32, sig: unixsignal::UnixSignal) -> nix::Result<()> {
+        nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid), sig.to_nix_signal())
+    }
+
+    /// ### suspend
+    ///
+    /// Suspend the job in foreground and give the prompt back. Returns the id and the command of the suspended job;
+    /// None if no job is in foreground (commands run by the shell itself can't be suspended)
+    pub fn suspend(&mut self) -> Option<(usize, String)> {
+        let id: usize = self.jobs.suspend()?;
+        self.process.exit_status = 148;
+        Some((id, self.jobs.get(id)?.command.clone()))
+    }
+
+    /// ### resize
+    ///
+    /// Notify the running command that the terminal has been resized: under a pseudo-terminal its size is updated,
+    /// which makes the kernel send SIGWINCH; otherwise SIGWINCH is forwarded to its processes
+    pub fn resize(&mut self) {
+        let sig: unixsignal::UnixSignal = unixsignal::UnixSignal::Sigwinch;
+        let size: TerminalSize = term::size();
+        match self.jobs.foreground_process() {
+            Some(process) => {
+                if !process.set_window_size(size.columns, size.rows) {
+                    let _ = process.raise(sig.to_nix_signal());
+                }
+            }
+            None => {
+                if !self.process.set_window_size(size.columns, size.rows) {
+                    self.process.raise_children(sig.to_nix_signal());
+                }
+            }
+        }
+    }
+
     /// ### get_state
     ///
     /// Returns the current Shell state
     pub fn get_state(&mut self) -> ShellState {
         let proc_state: ShellProcState = self.process.update_state();
+        //Job in foreground has terminated: its exit status becomes the one of the last command
+        if let Some((rc, elapsed)) = self.jobs.update() {
+            self.process.exit_status = rc;
+            self.process.exec_time = elapsed;
+        }
         match self.state {
             _ => {
                 self.state = match proc_state {
+                    ShellProcState::Idle if self.jobs.is_blocking() => ShellState::SubprocessRunning,
                     ShellProcState::Idle => ShellState::Shell,
                     ShellProcState::SubprocessRunning => ShellState::SubprocessRunning,
                     _ => ShellState::Terminated
                 };
+                //The interrupts sent to the command are forgotten once it has terminated
+                if self.state != ShellState::SubprocessRunning {
+                    self.escalation.reset();
+                }
                 self.state
             }
         }
@@ -157,31 +413,201 @@ impl Shell {
 
     /// ### refresh_env
     /// 
-    /// Refresh Shell Environment information
+    /// Refresh Shell Environment information.
+    /// Username and hostname don't change during a session: they're looked up once, when the shell is started
     pub fn refresh_env(&mut self) {
-        self.props.username = whoami::username();
-        self.props.hostname = Shell::get_hostname();
-        self.props.wrkdir = self.process.wrkdir.clone();
-        self.props.exit_status = self.process.exit_status;
-        self.props.elapsed_time = self.process.exec_time;
+        self.session.record(self.process.exit_status, self.process.exec_time, self.process.wrkdir.clone());
+    }
+
+    /// ### spawn_job
+    /// 
+    /// Run a command in background. Returns the id and the pid of the new job
+    pub fn spawn_job(&mut self, command: &str) -> Result<(usize, i32), JobError> {
+        let id: usize = self.jobs.spawn(self.exec.as_str(), command, &self.exec_options())?;
+        //Unwrap is safe, job has just been inserted
+        Ok((id, self.jobs.get(id).unwrap().pid))
+    }
+
+    /// ### exec_options
+    ///
+    /// Returns the exec options for the processes started by pyc besides the shell:
+    /// they run in the working directory of the shell with PWD and OLDPWD updated accordingly and get the current terminal size
+    fn exec_options(&self) -> ExecOptions {
+        let wrkdir: PathBuf = self.process.wrkdir.clone();
+        let mut env: Vec<(String, String)> = vec![(String::from("PWD"), buffer::os_str_to_string(wrkdir.as_os_str()))];
+        if let Some(oldpwd) = self.dirstack.oldpwd() {
+            env.push((String::from("OLDPWD"), buffer::os_str_to_string(oldpwd.as_os_str())));
+        }
+        env.extend(terminal_env());
+        ExecOptions {
+            wrkdir: Some(wrkdir),
+            env,
+            clear_env: false,
+            pty: self.pty,
+        }
+    }
+
+    /// ### jobs
+    /// 
+    /// Returns the jobs started in background
+    pub fn jobs(&mut self) -> &[Job] {
+        self.jobs.update();
+        self.jobs.list()
+    }
+
+    /// ### foreground_job
+    /// 
+    /// Bring a job (or the current one if None) to foreground; input and output are redirected to it until it terminates.
+    /// Returns the command of the job
+    pub fn foreground_job(&mut self, id: Option<usize>) -> Result<String, JobError> {
+        self.jobs.update();
+        let id: usize = self.jobs.foreground(id)?;
+        self.process.exit_status = 0;
+        Ok(self.jobs.get(id).unwrap().command.clone())
+    }
+
+    /// ### time_job
+    /// 
+    /// Run a command in foreground reporting its timing once it terminates (see `take_timing`).
+    /// Input and output are redirected to it until it terminates
+    pub fn time_job(&mut self, command: &str) -> Result<(), JobError> {
+        let (id, _) = self.spawn_job(command)?;
+        self.jobs.set_timed(id);
+        self.jobs.foreground(Some(id))?;
+        self.process.exit_status = 0;
+        Ok(())
+    }
+
+    /// ### take_timing
+    /// 
+    /// Returns the timing of the last command run with `time_job`, once it has terminated; the timing is returned once
+    pub fn take_timing(&mut self) -> Option<CommandTiming> {
+        self.jobs.take_timing()
+    }
+
+    /// ### set_exec_time
+    /// 
+    /// Set the execution time of the last command; used for the commands handled by pyc which don't reach the shell
+    pub fn set_exec_time(&mut self, elapsed: Duration) {
+        self.process.exec_time = elapsed;
+        self.session.set_elapsed_time(elapsed);
+    }
+
+    /// ### background_job
+    /// 
+    /// Resume a suspended job (or the current one if None) in background. Returns the id and the command of the job
+    pub fn background_job(&mut self, id: Option<usize>) -> Result<(usize, String), JobError> {
+        self.jobs.update();
+        let id: usize = self.jobs.background(id)?;
+        self.process.exit_status = 0;
+        Ok((id, self.jobs.get(id).unwrap().command.clone()))
+    }
+
+    /// ### wait_jobs
+    /// 
+    /// Wait for all the jobs to terminate before returning to the prompt
+    pub fn wait_jobs(&mut self) {
+        self.jobs.update();
+        self.jobs.wait(true);
+        self.process.exit_status = 0;
+    }
+
+    /// ### get_wrkdir
+    /// 
+    /// Returns the current working directory of the shell
+    pub fn get_wrkdir(&self) -> PathBuf {
+        self.process.wrkdir.clone()
+    }
+
+    /// ### get_exit_status
+    /// 
+    /// Returns the exit status of the last command executed by the shell
+    pub fn get_exit_status(&self) -> u8 {
+        self.process.exit_status
+    }
+
+    /// ### set_degraded
+    /// 
+    /// Set the features which have been disabled at startup (shown by `${DEGRADED}`)
+    pub fn set_degraded(&mut self, features: Vec<String>) {
+        self.session.set_degraded(features);
     }
 
     /// ### pprompt
     /// 
     /// Print prompt line
     pub fn get_promptline(&mut self, processor: &IOProcessor) -> String {
-        self.prompt.get_line(&self.props, processor)
+        //Notify jobs which have terminated before the prompt
+        let mut notifications: String = String::new();
+        for job in self.jobs.reap() {
+            notifications.push_str(format!("[{}] {:<24}{}\n", job.id, job.state_str(), job.command).as_str());
+        }
+        self.session.set_jobs(self.jobs.summary());
+        let prompt_config: &PromptConfig = &self.prompt_config;
+        let prompt: &mut ShellPrompt = self.prompt.get_or_insert_with(|| ShellPrompt::new(prompt_config));
+        notifications + prompt.get_line(self.session.props(), processor).as_str()
     }
 
-    /// ### get_hostname
+    /// ### redraw_promptline
     /// 
-    /// Get hostname without domain
-    fn get_hostname() -> String {
-        let full_hostname: String = whoami::hostname();
-        let tokens: Vec<&str> = full_hostname.split(".").collect();
-        String::from(*tokens.get(0).unwrap())
+    /// Get prompt line with the values of the async prompt modules resolved after it has been printed
+    pub fn redraw_promptline(&mut self, processor: &IOProcessor) -> String {
+        let prompt_config: &PromptConfig = &self.prompt_config;
+        let prompt: &mut ShellPrompt = self.prompt.get_or_insert_with(|| ShellPrompt::new(prompt_config));
+        prompt.redraw_line(self.session.props(), processor)
     }
 
+    /// ### reload_prompt
+    ///
+    /// Replace the prompt configuration; if the prompt has already been built, its options are replaced in place
+    pub fn reload_prompt(&mut self, prompt_config: &PromptConfig) {
+        self.prompt_config = prompt_config.clone();
+        if let Some(prompt) = self.prompt.as_mut() {
+            prompt.reconfigure(prompt_config);
+        }
+    }
+
+    /// ### poll_prompt
+    /// 
+    /// Collect the values of the async prompt modules. Returns whether the prompt has to be redrawn
+    pub fn poll_prompt(&mut self) -> bool {
+        match self.prompt.as_mut() {
+            Some(prompt) => prompt.poll_modules(),
+            None => false,
+        }
+    }
+
+}
+
+/// ### terminal_env
+///
+/// Returns the environment variables which tell the size of the terminal (COLUMNS and LINES)
+fn terminal_env() -> Vec<(String, String)> {
+    let size: TerminalSize = term::size();
+    vec![(String::from("COLUMNS"), size.columns.to_string()), (String::from("LINES"), size.rows.to_string())]
+}
+
+/// ### render_prompt
+///
+/// Render the configured prompt line outside of a Shell (e.g. for the prompt of another shell):
+/// the exit status, the duration of the last command and the working directory are provided, while user and host are taken from the environment.
+/// Since the prompt is never redrawn, async modules are waited for until they're resolved or time out
+pub fn render_prompt(prompt_config: &PromptConfig, exit_status: u8, elapsed_time: Duration, wrkdir: PathBuf, processor: &IOProcessor) -> String {
+    let mut session: ShellSession = ShellSession::new(wrkdir.clone());
+    session.record(exit_status, elapsed_time, wrkdir);
+    let mut prompt_config: PromptConfig = prompt_config.clone();
+    prompt_config.module_timeout = u32::MAX as usize;
+    ShellPrompt::new(&prompt_config).get_line(session.props(), processor)
+}
+
+/// ### status
+///
+/// Resolve the status values (git, virtual environment, ...) of the working directory for external status bars.
+/// The repository discovery is cached in the provided cache
+pub fn status(prompt_config: &PromptConfig, exit_status: u8, wrkdir: PathBuf, language: Language, cache: &mut PromptCache) -> StatusReport {
+    let mut session: ShellSession = ShellSession::new(wrkdir.clone());
+    session.record(exit_status, Duration::from_secs(0), wrkdir);
+    prompt::resolve_status(session.props(), language, prompt_config.git_commit_ref, cache)
 }
 
 //@! Shell Props
@@ -195,8 +621,11 @@ impl ShellProps {
             hostname: hostname,
             username: username,
             wrkdir: wrkdir,
+            uid: nix::unistd::geteuid().as_raw(),
             elapsed_time: Duration::from_secs(0),
-            exit_status: 0
+            exit_status: 0,
+            degraded: Vec::new(),
+            jobs: JobsSummary::default()
         }
     }
 }
@@ -207,6 +636,10 @@ impl ShellProps {
 mod tests {
 
     use super::*;
+    use crate::translator::lang::Language;
+    use crate::translator::new_translator;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
     use std::thread::sleep;
     use std::time::{Duration, Instant};
 
@@ -219,6 +652,9 @@ mod tests {
         assert_eq!(shell_props.wrkdir, PathBuf::from("/tmp/"));
         assert_eq!(shell_props.elapsed_time.as_millis(), 0);
         assert_eq!(shell_props.exit_status, 0);
+        assert_eq!(shell_props.uid, nix::unistd::geteuid().as_raw());
+        assert_eq!(shell_props.degraded.len(), 0);
+        assert_eq!(shell_props.jobs, JobsSummary::default());
     }
 
     #[test]
@@ -237,20 +673,66 @@ mod tests {
         // Verify env state
         assert_eq!(shell_env.state, ShellState::Shell);
         //Get username etc
-        println!("Username: {}", shell_env.props.username);
-        println!("Hostname: {}", shell_env.props.hostname);
-        println!("Working directory: {}", shell_env.props.wrkdir.display());
-        assert!(shell_env.props.username.len() > 0);
-        assert!(shell_env.props.hostname.len() > 0);
-        assert!(format!("{}", shell_env.props.wrkdir.display()).len() > 0);
+        println!("Username: {}", shell_env.session.props().username);
+        println!("Hostname: {}", shell_env.session.props().hostname);
+        println!("Working directory: {}", shell_env.session.props().wrkdir.display());
+        assert!(!shell_env.session.props().username.is_empty());
+        assert!(!shell_env.session.props().hostname.is_empty());
+        assert!(!format!("{}", shell_env.session.props().wrkdir.display()).is_empty());
         //Refresh environment
+        let hostname: String = shell_env.session.props().hostname.clone();
+        shell_env.session = ShellSession::with_identity(hostname.clone(), String::from("cached"), shell_env.get_wrkdir());
         shell_env.refresh_env();
+        assert_eq!(shell_env.session.props().username, String::from("cached"));
+        assert_eq!(shell_env.session.props().hostname, hostname);
         //Terminate shell
-        assert_eq!(shell_env.stop().unwrap(), 9);
+        assert_eq!(shell_env.stop().unwrap(), 137);
         sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
         assert_eq!(shell_env.get_state(), ShellState::Terminated);
     }
 
+    #[test]
+    fn test_shell_lazy_prompt() {
+        let built = || prompt::PROMPTS_BUILT.with(|count| count.get());
+        let processor: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let mut shell_env: Shell = Shell::start(String::from("sh"), vec![], &PromptConfig::default()).ok().unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Run a command as in oneshot mode: prompt is never built
+        assert!(shell_env.write(String::from("echo foo; exit $?\n")).is_ok());
+        sleep(Duration::from_millis(500));
+        assert!(shell_env.read().is_ok());
+        assert!(!shell_env.poll_prompt());
+        assert!(shell_env.prompt.is_none());
+        assert_eq!(built(), 0);
+        //Prompt is built when it is printed for the first time
+        assert!(!shell_env.get_promptline(&processor).is_empty());
+        assert!(!shell_env.redraw_promptline(&processor).is_empty());
+        assert!(shell_env.prompt.is_some());
+        assert_eq!(built(), 1);
+        assert!(shell_env.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_shell_start_pty() {
+        let mut shell_env: Shell = Shell::start_with(String::from("sh"), vec![], &PromptConfig::default(), true).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Commands are not echoed back and get the pseudo-terminal
+        assert!(shell_env.write(String::from("tty\n")).is_ok());
+        sleep(Duration::from_millis(500));
+        let (stdout, _) = shell_env.read().unwrap();
+        assert!(stdout.unwrap().starts_with("/dev/pts/"));
+        assert_eq!(shell_env.get_state(), ShellState::Shell);
+        //Jobs run under a pseudo-terminal too
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let output: PathBuf = tmpdir.path().join("tty");
+        shell_env.spawn_job(format!("tty > {}", output.display()).as_str()).unwrap();
+        sleep(Duration::from_millis(500));
+        assert!(std::fs::read_to_string(output.as_path()).unwrap().starts_with("/dev/pts/"));
+        assert!(shell_env.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
     #[test]
     fn test_shell_start_failed() {
         //Use fictional shell
@@ -262,6 +744,55 @@ mod tests {
         assert_eq!(shell_env.get_state(), ShellState::Terminated);
     }
 
+    #[test]
+    fn test_shell_handshake() {
+        //Shell which prints a banner before starting
+        let argv: Vec<String> = vec![String::from("-c"), String::from("echo 'Welcome!'; exec sh")];
+        let mut shell_env: Shell = Shell::start(String::from("sh"), argv, &PromptConfig::default()).unwrap();
+        assert_eq!(shell_env.handshake(Duration::from_secs(5)).unwrap(), String::from("Welcome!\n"));
+        assert_eq!(shell_env.get_state(), ShellState::Shell);
+        assert!(shell_env.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_shell_handshake_failed() {
+        //Shell which prints a banner, clears the screen and exits
+        let argv: Vec<String> = vec![String::from("-c"), String::from("echo 'Welcome!'; printf '\\033[H\\033[2J'; exit 1")];
+        let mut shell_env: Shell = Shell::start(String::from("sh"), argv, &PromptConfig::default()).unwrap();
+        let err: HandshakeError = shell_env.handshake(Duration::from_secs(5)).err().unwrap();
+        assert_eq!(err.failure, HandshakeFailure::Terminated);
+        let diagnostic: String = err.diagnostic();
+        assert!(diagnostic.contains("  | Welcome!\n"));
+        assert!(diagnostic.contains("Hint: your rc file printed output before the marker\n"));
+        assert!(diagnostic.contains("Hint: your rc file clears the screen\n"));
+        assert!(shell_env.stop().is_ok());
+        //Shell which never reads its input
+        let argv: Vec<String> = vec![String::from("-c"), String::from("echo 'Loading...'; sleep 10")];
+        let mut shell_env: Shell = Shell::start(String::from("sh"), argv, &PromptConfig::default()).unwrap();
+        let err: HandshakeError = shell_env.handshake(Duration::from_millis(500)).err().unwrap();
+        assert_eq!(err.failure, HandshakeFailure::Timeout(Duration::from_millis(500)));
+        assert_eq!(err.output, String::from("Loading...\n"));
+        assert!(shell_env.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_shell_render_prompt() {
+        let mut prompt_config: PromptConfig = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${USER}@${HOSTNAME}:${WRKDIR} ${RC} ${CMD_TIME}$");
+        prompt_config.break_enabled = true;
+        let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        //Render the prompt of an interactive shell
+        let mut shell_env: Shell = Shell::start(String::from("sh"), vec![], &prompt_config).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        shell_env.session.record(2, Duration::from_millis(3400), PathBuf::from("/tmp"));
+        let expected: String = shell_env.get_promptline(&iop);
+        assert_eq!(render_prompt(&prompt_config, 2, Duration::from_millis(3400), PathBuf::from("/tmp"), &iop), expected);
+        assert!(shell_env.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
     #[test]
     fn test_shell_exec() {
         //Use universal accepted shell
@@ -315,7 +846,7 @@ mod tests {
         //Shell should have terminated
         sleep(Duration::from_millis(500));
         assert_eq!(shell_env.get_state(), ShellState::Terminated);
-        assert_eq!(shell_env.stop().unwrap(), 9);
+        assert_eq!(shell_env.stop().unwrap(), 137);
     }
 
     #[test]
@@ -354,11 +885,190 @@ mod tests {
         //Verify shell has terminated
         assert_eq!(shell_env.get_state(), ShellState::Terminated);
         //Verify exitcode to be 0
-        assert_eq!(shell_env.stop().unwrap(), 2);
+        assert_eq!(shell_env.stop().unwrap(), 130);
+    }
+
+    #[test]
+    fn test_shell_suspend() {
+        let mut shell_env: Shell = Shell::start(String::from("sh"), vec![], &PromptConfig::default()).ok().unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Nothing to suspend; SIGQUIT doesn't terminate the shell
+        assert!(shell_env.suspend().is_none());
+        assert!(shell_env.raise(unixsignal::UnixSignal::Sigquit).is_ok());
+        sleep(Duration::from_millis(100));
+        assert_eq!(shell_env.get_state(), ShellState::Shell);
+        //Suspend job in foreground
+        let (id, _) = shell_env.spawn_job("sleep 5").unwrap();
+        assert_eq!(shell_env.foreground_job(Some(id)).unwrap(), String::from("sleep 5"));
+        assert_eq!(shell_env.get_state(), ShellState::SubprocessRunning);
+        assert_eq!(shell_env.suspend(), Some((id, String::from("sleep 5"))));
+        assert_eq!(shell_env.get_exit_status(), 148);
+        assert_eq!(shell_env.get_state(), ShellState::Shell);
+        //Resume in background
+        assert_eq!(shell_env.background_job(None).unwrap(), (id, String::from("sleep 5")));
+        assert_eq!(shell_env.background_job(None).err().unwrap(), JobError::AlreadyInBackground);
+        //SIGQUIT terminates the job in foreground
+        shell_env.foreground_job(None).unwrap();
+        assert!(shell_env.raise(unixsignal::UnixSignal::Sigquit).is_ok());
+        sleep(Duration::from_millis(200));
+        assert_eq!(shell_env.get_state(), ShellState::Shell);
+        assert!(shell_env.jobs().is_empty());
+        assert!(shell_env.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_shell_write_terminated_job() {
+        let mut shell_env: Shell = Shell::start(String::from("sh"), vec![], &PromptConfig::default()).ok().unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        shell_env.spawn_job("true").unwrap();
+        shell_env.foreground_job(None).unwrap();
+        sleep(Duration::from_millis(200));
+        //Input for the job which has exited is rejected; the shell keeps running
+        assert_eq!(shell_env.write(String::from("hello\n")).err().unwrap(), ShellError::ProcessTerminated);
+        assert_eq!(shell_env.get_state(), ShellState::Shell);
+        assert!(shell_env.write(String::from("echo foo\n")).is_ok());
+        assert!(shell_env.stop().is_ok());
+        assert_eq!(shell_env.write(String::from("echo foo\n")).err().unwrap(), ShellError::ShellTerminated);
+    }
+
+    #[test]
+    fn test_shell_time_job() {
+        let mut shell_env: Shell = Shell::start(String::from("sh"), vec![], &PromptConfig::default()).ok().unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(shell_env.time_job("sleep 0.2").is_ok());
+        assert_eq!(shell_env.get_state(), ShellState::SubprocessRunning);
+        let t_start: Instant = Instant::now();
+        while shell_env.get_state() != ShellState::Shell && t_start.elapsed() < Duration::from_secs(3) {
+            sleep(Duration::from_millis(50));
+        }
+        let timing: CommandTiming = shell_env.take_timing().unwrap();
+        assert!(timing.real >= Duration::from_millis(200));
+        assert!(timing.user + timing.sys <= timing.real);
+        //Timing becomes the execution time of the last command
+        shell_env.refresh_env();
+        assert_eq!(shell_env.session.props().elapsed_time, timing.real);
+        assert!(shell_env.take_timing().is_none());
+        //Execution time of commands handled by pyc
+        shell_env.set_exec_time(Duration::from_millis(20));
+        assert_eq!(shell_env.session.props().elapsed_time, Duration::from_millis(20));
+        assert!(shell_env.stop().is_ok());
+    }
+
+    #[test]
+    fn test_shell_interrupt_escalation() {
+        let mut shell_env: Shell = Shell::start(String::from("sh"), vec![], &PromptConfig::default()).ok().unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        shell_env.set_sigint_escalation(&SigintEscalation { presses: 3, window_ms: 2000, kill_after_ms: 300 });
+        let wait_prompt = |shell_env: &mut Shell| {
+            let t_start: Instant = Instant::now();
+            while shell_env.get_state() != ShellState::Shell && t_start.elapsed() < Duration::from_secs(3) {
+                sleep(Duration::from_millis(50));
+            }
+            shell_env.get_state()
+        };
+        //The command ignores SIGINT: it's terminated at the third interrupt
+        shell_env.spawn_job("trap '' INT; sleep 10").unwrap();
+        shell_env.foreground_job(None).unwrap();
+        sleep(Duration::from_millis(100));
+        assert_eq!(shell_env.interrupt().unwrap(), InterruptAction::Interrupt);
+        assert_eq!(shell_env.interrupt().unwrap(), InterruptAction::Interrupt);
+        sleep(Duration::from_millis(200));
+        assert_eq!(shell_env.get_state(), ShellState::SubprocessRunning);
+        assert_eq!(shell_env.interrupt().unwrap(), InterruptAction::Terminate);
+        assert_eq!(wait_prompt(&mut shell_env), ShellState::Shell);
+        assert!(!shell_env.poll_escalation());
+        //The command ignores SIGTERM too: it's killed after the timeout
+        shell_env.spawn_job("trap '' INT TERM; sleep 10").unwrap();
+        shell_env.foreground_job(None).unwrap();
+        sleep(Duration::from_millis(100));
+        for _ in 0..2 {
+            assert_eq!(shell_env.interrupt().unwrap(), InterruptAction::Interrupt);
+        }
+        assert_eq!(shell_env.interrupt().unwrap(), InterruptAction::Terminate);
+        assert!(!shell_env.poll_escalation());
+        sleep(Duration::from_millis(200));
+        assert_eq!(shell_env.get_state(), ShellState::SubprocessRunning);
+        sleep(Duration::from_millis(200));
+        assert!(shell_env.poll_escalation());
+        assert_eq!(wait_prompt(&mut shell_env), ShellState::Shell);
+        assert_eq!(shell_env.get_exit_status(), 137);
+        assert!(shell_env.stop().is_ok());
     }
 
     #[test]
-    fn test_shell_hostname() {
-        assert_ne!(Shell::get_hostname(), String::from(""));
+    fn test_shell_signal_job() {
+        let mut shell_env: Shell = Shell::start(String::from("sh"), vec![], &PromptConfig::default()).ok().unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        let (id, pid) = shell_env.spawn_job("sleep 10").unwrap();
+        assert!(shell_env.signal_job(id, unixsignal::UnixSignal::Sigkill).is_ok());
+        sleep(Duration::from_millis(200));
+        assert!(shell_env.signal_job(id, unixsignal::UnixSignal::Sigkill).is_err());
+        assert!(shell_env.signal_process(pid, unixsignal::UnixSignal::Sigterm).is_err());
+        //Process
+        let (_, pid) = shell_env.spawn_job("sleep 10").unwrap();
+        assert!(shell_env.signal_process(pid, unixsignal::UnixSignal::Sigterm).is_ok());
+        sleep(Duration::from_millis(200));
+        assert_eq!(shell_env.jobs().last().unwrap().state, jobs::JobState::Done(143));
+        assert!(shell_env.stop().is_ok());
+    }
+
+    #[test]
+    fn test_shell_pipeline() {
+        let mut shell_env: Shell = Shell::start(String::from("sh"), vec![], &PromptConfig::default()).ok().unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //Output of the last stage
+        assert_eq!(run_command(&mut shell_env, "echo foo | grep foo\n"), (String::from("foo\n"), 0));
+        //Exit status is the exit status of the last stage
+        assert_eq!(run_command(&mut shell_env, "echo foo | grep bar\n"), (String::new(), 1));
+        assert_eq!(run_command(&mut shell_env, "false | echo bar\n"), (String::from("bar\n"), 0));
+        //Quoted pipes don't split the command
+        assert_eq!(run_command(&mut shell_env, "echo 'a|b' | grep \"a|b\"\n"), (String::from("a|b\n"), 0));
+        //SIGINT stops every stage, but not the shell
+        assert!(shell_env.write(String::from("sleep 5 | cat\n")).is_ok());
+        sleep(Duration::from_millis(500));
+        assert_eq!(shell_env.get_state(), ShellState::SubprocessRunning);
+        assert!(shell_env.raise(unixsignal::UnixSignal::Sigint).is_ok());
+        assert_eq!(run_command(&mut shell_env, ""), (String::new(), 130));
+        assert_eq!(shell_env.get_state(), ShellState::Shell);
+        assert!(shell_env.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    #[test]
+    fn test_shell_write_raw_bytes() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        //"Привет" in KOI8-R
+        let name: &OsStr = OsStr::from_bytes(b"\xf0\xd2\xc9\xd7\xc5\xd4");
+        let mut shell_env: Shell = Shell::start(String::from("sh"), vec![], &PromptConfig::default()).ok().unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        //The command receives the exact bytes of the file name
+        let path: String = buffer::os_str_to_string(tmpdir.path().join(name).as_os_str());
+        assert_eq!(run_command(&mut shell_env, format!("touch '{}'\n", path).as_str()).1, 0);
+        assert!(tmpdir.path().join(name).exists());
+        let (stdout, _) = run_command(&mut shell_env, format!("printf '%s' '{}' | od -An -tx1\n", buffer::os_str_to_string(name)).as_str());
+        assert_eq!(stdout.split_whitespace().collect::<Vec<&str>>(), vec!["f0", "d2", "c9", "d7", "c5", "d4"]);
+        assert!(shell_env.stop().is_ok());
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+    }
+
+    /// ### run_command
+    ///
+    /// Write a command to the shell and collect its stdout until the shell is idle; returns the output and the exit status
+    fn run_command(shell_env: &mut Shell, command: &str) -> (String, u8) {
+        if !command.is_empty() {
+            assert!(shell_env.write(String::from(command)).is_ok());
+        }
+        let mut output: String = String::new();
+        let t_start: Instant = Instant::now();
+        loop {
+            if let Ok((Some(stdout), _)) = shell_env.read() {
+                output.push_str(stdout.as_str());
+            }
+            if shell_env.get_state() == ShellState::Shell || t_start.elapsed() > Duration::from_secs(3) {
+                break;
+            }
+        }
+        (output, shell_env.get_exit_status())
     }
 }
@@ -33,6 +33,10 @@ use std::time::{Duration, Instant};
 
 use pipe::Pipe;
 
+use crate::shell::environment::quote;
+use crate::translator::LocalizedError;
+use crate::utils::buffer;
+
 //Proc has a thread which runs the subprocess of the shell and 3 pipes (stdout, stdin, stderr). It must provides the function to write and to read
 
 /// ### ShellProcState
@@ -55,10 +59,32 @@ pub enum ShellError {
     IoTimeout,
     ShellRunning,
     ShellTerminated,
+    ProcessTerminated,
     CouldNotKill,
     PipeError(nix::errno::Errno)
 }
 
+/// ### ExecOptions
+///
+/// ExecOptions contains the working directory and the environment variables a command is executed with,
+/// instead of the ones of the shell session
+#[derive(Clone, Default, PartialEq, std::fmt::Debug)]
+pub struct ExecOptions {
+    pub wrkdir: Option<PathBuf>,            //Working directory override
+    pub env: Vec<(String, String)>,         //Environment variables to set
+    pub clear_env: bool,                    //Don't inherit the environment (only honored by processes started with `start_with`)
+    pub pty: bool,                          //Run under a pseudo-terminal (only honored by processes started with `start_with`)
+}
+
+/// ### ResourceUsage
+///
+/// ResourceUsage is the CPU time used by a terminated process and by the children it has waited for
+#[derive(Copy, Clone, Default, PartialEq, std::fmt::Debug)]
+pub struct ResourceUsage {
+    pub user: Duration,
+    pub sys: Duration,
+}
+
 /// ### ShellProc
 /// 
 /// Shell Proc represents an instance of the shell process wrapper
@@ -69,11 +95,14 @@ pub struct ShellProc {
     pub pid: i32,                           //Shell pid
     pub wrkdir: PathBuf,                    //Working directory
     pub exec_time: Duration,                //Execution time of the last command
+    pub rusage: Option<ResourceUsage>,      //CPU time used by the process; None until it has terminated
     //Private
-    rc: u8,                                 //Return code of the shell process
+    rc: Option<u8>,                         //Return code of the shell process; None until it has terminated
     uuid: String,                           //UUID used for handshake with the shell
     start_time: Instant,                    //Instant when the last command was started
-    stdout_cache: Option<String>,           //Used to prevent buffer fragmentation
+    stdout_cache: Option<String>,           //Beginning of the metadata split between two reads
+    stdout_carry: Vec<u8>,                  //Incomplete UTF-8 sequence at the end of the last stdout read
+    stderr_carry: Vec<u8>,                  //Incomplete UTF-8 sequence at the end of the last stderr read
     echo_command: String,                   //Echo command
     //Pipes
     stdin_pipe: Pipe,
@@ -83,16 +112,54 @@ pub struct ShellProc {
 
 impl std::fmt::Display for ShellError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let code_str: String = match self {
-            ShellError::CouldNotStartProcess => String::from("Could not start process"),
-            ShellError::InvalidData => String::from("Invalid data from process"),
-            ShellError::IoTimeout => String::from("I/O timeout"),
-            ShellError::ShellTerminated => String::from("Shell has terminated"),
-            ShellError::ShellRunning => String::from("Tried to clean shell up while still running"),
-            ShellError::CouldNotKill => String::from("Could not send signal to shell process"),
-            ShellError::PipeError(errno) => format!("Pipe error: {}", errno),
+        match self {
+            ShellError::CouldNotStartProcess => write!(f, "Could not start process"),
+            ShellError::InvalidData => write!(f, "Invalid data from process"),
+            ShellError::IoTimeout => write!(f, "I/O timeout"),
+            ShellError::ShellTerminated => write!(f, "Shell has terminated"),
+            ShellError::ProcessTerminated => write!(f, "Process has terminated"),
+            ShellError::ShellRunning => write!(f, "Tried to clean shell up while still running"),
+            ShellError::CouldNotKill => write!(f, "Could not send signal to shell process"),
+            ShellError::PipeError(errno) => write!(f, "Pipe error: {}", errno),
+        }
+    }
+}
+
+impl std::error::Error for ShellError {}
+
+impl LocalizedError for ShellError {}
+
+impl ExecOptions {
+    /// ### is_empty
+    ///
+    /// Returns whether the options don't change anything; running under a pseudo-terminal is not taken into account
+    pub fn is_empty(&self) -> bool {
+        self.wrkdir.is_none() && self.env.is_empty() && !self.clear_env
+    }
+
+    /// ### wrap
+    ///
+    /// Wrap a command line in a subshell which applies the options before running it;
+    /// since the subshell exits with the command, the working directory and the environment of the session are left unchanged.
+    /// If the working directory can't be entered, the command is not run.
+    /// `clear_env` is ignored, since the command is run by the shell of the session
+    pub fn wrap(&self, command: &str) -> String {
+        if self.wrkdir.is_none() && self.env.is_empty() {
+            return String::from(command);
+        }
+        let mut steps: Vec<String> = Vec::with_capacity(self.env.len() + 2);
+        if let Some(wrkdir) = self.wrkdir.as_ref() {
+            steps.push(format!("cd {}", quote(buffer::os_str_to_string(wrkdir.as_os_str()).as_str())));
+        }
+        for (name, value) in self.env.iter() {
+            steps.push(format!("export {}={}", name, quote(value.as_str())));
+        }
+        steps.push(String::from(command.trim_end_matches('\n')));
+        let newline: &str = match command.ends_with('\n') {
+            true => "\n",
+            false => "",
         };
-        write!(f, "{}", code_str)
+        format!("({}){}", steps.join(" && "), newline)
     }
 }
 
@@ -102,6 +169,26 @@ impl std::fmt::Display for ShellError {
 mod tests {
 
     use super::*;
+    use crate::translator::lang::Language;
+    use crate::translator::{new_translator, Translator};
+
+    #[test]
+    fn test_proc_exec_options_wrap() {
+        let options: ExecOptions = ExecOptions::default();
+        assert!(options.is_empty());
+        assert_eq!(options.wrap("ls -l\n"), String::from("ls -l\n"));
+        let options: ExecOptions = ExecOptions {
+            wrkdir: Some(PathBuf::from("/home/omar/l'infra")),
+            env: vec![(String::from("ANSIBLE_FORCE_COLOR"), String::from("1")), (String::from("MSG"), String::from("привет мир"))],
+            clear_env: false,
+            pty: false,
+        };
+        assert!(!options.is_empty());
+        assert_eq!(
+            options.wrap("ansible-playbook site.yml | tee log\n"),
+            String::from("(cd '/home/omar/l'\\''infra' && export ANSIBLE_FORCE_COLOR='1' && export MSG='привет мир' && ansible-playbook site.yml | tee log)\n")
+        );
+    }
 
     #[test]
     fn test_proc_fmt_shell_error() {
@@ -109,9 +196,27 @@ mod tests {
         assert_eq!(format!("{}", ShellError::InvalidData), String::from("Invalid data from process"));
         assert_eq!(format!("{}", ShellError::IoTimeout), String::from("I/O timeout"));
         assert_eq!(format!("{}", ShellError::ShellTerminated), String::from("Shell has terminated"));
+        assert_eq!(format!("{}", ShellError::ProcessTerminated), String::from("Process has terminated"));
         assert_eq!(format!("{}", ShellError::ShellRunning), String::from("Tried to clean shell up while still running"));
         assert_eq!(format!("{}", ShellError::CouldNotKill), String::from("Could not send signal to shell process"));
         assert_eq!(format!("{}", ShellError::PipeError(nix::errno::Errno::EACCES)), format!("Pipe error: {}", nix::errno::Errno::EACCES));
     }
 
+    #[test]
+    fn test_proc_localized_shell_error() {
+        let translator: Box<dyn Translator> = new_translator(Language::Russian);
+        let localized = |err: ShellError| err.localized(translator.as_ref());
+        assert_eq!(localized(ShellError::CouldNotStartProcess), String::from("Коулд нот старт прокесс"));
+        assert_eq!(localized(ShellError::InvalidData), String::from("Инвалид дата фром прокесс"));
+        assert_eq!(localized(ShellError::IoTimeout), String::from("И/О тимеоут"));
+        assert_eq!(localized(ShellError::ShellTerminated), String::from("Шелл хас терминатед"));
+        assert_eq!(localized(ShellError::ProcessTerminated), String::from("Прокесс хас терминатед"));
+        assert_eq!(localized(ShellError::ShellRunning), String::from("Триед то клеан шелл уп ухиле стилл руннинг"));
+        assert_eq!(localized(ShellError::CouldNotKill), String::from("Коулд нот сенд сигнал то шелл прокесс"));
+        assert!(localized(ShellError::PipeError(nix::errno::Errno::EACCES)).starts_with("Пипе еррор: ЕАККЕС"));
+        //Can be boxed
+        let err: Box<dyn std::error::Error> = Box::new(ShellError::IoTimeout);
+        assert_eq!(err.to_string(), String::from("I/O timeout"));
+    }
+
 }
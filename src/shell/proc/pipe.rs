@@ -29,12 +29,17 @@ use super::{ShellError};
 
 use std::path::PathBuf;
 use std::os::unix::io::RawFd;
+use std::thread;
 use std::time::{Instant, Duration};
 
 //UNIX
 use nix::unistd;
 
-#[derive(Clone, std::fmt::Debug)]
+/// ### Pipe
+///
+/// Pipe is a FIFO (or a descriptor wrapped with `from_fd`) read and written with poll.
+/// The FIFO is deleted once the Pipe is dropped; the descriptor is closed by `close`
+#[derive(std::fmt::Debug)]
 pub(crate) struct Pipe {
     pub path: PathBuf, //Pipe path
     pub fd: RawFd
@@ -44,17 +49,15 @@ impl Pipe {
 
     /// ### open
     /// 
-    /// Open and creates a new pipe. Returns pipe on suceess or shell error
+    /// Open and creates a new pipe. Returns pipe on suceess or shell error.
+    /// The descriptor is non blocking: `read` and `write` wait for the pipe with poll, so they can respect their timeout
     pub fn open(path: &PathBuf) -> Result<Pipe, ShellError> {
         //Mkfifo - Not necessary with O_CREAT
         if let Err(err) = unistd::mkfifo(path.as_path(), nix::sys::stat::Mode::S_IRWXU | nix::sys::stat::Mode::S_IRWXG | nix::sys::stat::Mode::S_IRWXO) {
-            match err {
-                nix::Error::Sys(errno) => return Err(ShellError::PipeError(errno)),
-                _ => return Err(ShellError::PipeError(nix::errno::Errno::UnknownErrno))
-            }
+            return Err(pipe_error(err))
         }
         //Open fifo
-        match nix::fcntl::open(path.as_path(), nix::fcntl::OFlag::O_RDWR, nix::sys::stat::Mode::S_IRWXU | nix::sys::stat::Mode::S_IRWXG | nix::sys::stat::Mode::S_IRWXO) {
+        match nix::fcntl::open(path.as_path(), nix::fcntl::OFlag::O_RDWR | nix::fcntl::OFlag::O_NONBLOCK, nix::sys::stat::Mode::S_IRWXU | nix::sys::stat::Mode::S_IRWXG | nix::sys::stat::Mode::S_IRWXO) {
             Ok(fd) => {
                 Ok(Pipe {
                     path: path.clone(),
@@ -62,26 +65,40 @@ impl Pipe {
                 })
             },
             Err(err) => {
-                match err {
-                    nix::Error::Sys(errno) => Err(ShellError::PipeError(errno)),
-                    _ => Err(ShellError::PipeError(nix::errno::Errno::UnknownErrno))
-                }
+                let _ = unistd::unlink(path.as_path());
+                Err(pipe_error(err))
             }
         }
     }
 
+    /// ### from_fd
+    /// 
+    /// Wrap a descriptor which is not a FIFO (e.g. the master of a pseudo-terminal); there's no file to delete on close
+    pub fn from_fd(fd: RawFd) -> Pipe {
+        Pipe {
+            path: PathBuf::new(),
+            fd
+        }
+    }
+
+    /// ### open_end
+    /// 
+    /// Open another descriptor for the pipe, with the provided access mode (e.g. O_WRONLY for the writer end)
+    pub fn open_end(&self, mode: nix::fcntl::OFlag) -> Result<RawFd, ShellError> {
+        nix::fcntl::open(self.path.as_path(), mode, nix::sys::stat::Mode::empty()).map_err(pipe_error)
+    }
+
     /// ### close
     /// 
     /// Close and delete pipe
     pub fn close(&self) -> Result<(), ShellError> {
         if let Err(err) = unistd::close(self.fd) {
-            match err {
-                nix::Error::Sys(errno) => return Err(ShellError::PipeError(errno)),
-                _ => return Err(ShellError::PipeError(nix::errno::Errno::UnknownErrno))
-            }
+            return Err(pipe_error(err))
         };
         //Unlink pipe
-        let _ = unistd::unlink(self.path.as_path());
+        if !self.path.as_os_str().is_empty() {
+            let _ = unistd::unlink(self.path.as_path());
+        }
         Ok(())
     }
 
@@ -90,18 +107,35 @@ impl Pipe {
     /// Read from pipe
     /// If read_all parameter is False, then the function returns after reading 8192 or less
     /// otherwise, if set to True, reads until there's something available to be read
+    #[cfg(test)]
     pub fn read(&self, timeout: u64, read_all: bool) -> Result<Option<String>, ShellError> {
+        match self.read_bytes(timeout, read_all) {
+            Ok(None) => Ok(None),
+            Ok(Some(data)) => match String::from_utf8(data) {
+                Ok(s) => Ok(Some(s)),
+                Err(_) => Err(ShellError::InvalidData)
+            },
+            Err(err) => Err(err)
+        }
+    }
+
+    /// ### read_bytes
+    /// 
+    /// Read raw bytes from pipe. Same as `read`, but data is not converted to string,
+    /// so a multi-byte character may be split between two reads
+    pub fn read_bytes(&self, timeout: u64, read_all: bool) -> Result<Option<Vec<u8>>, ShellError> {
         //Create poll fd wrapper
         let mut poll_fds: [nix::poll::PollFd; 1] = [nix::poll::PollFd::new(self.fd, nix::poll::PollFlags::POLLIN | nix::poll::PollFlags::POLLRDBAND | nix::poll::PollFlags::POLLHUP)];
         //Prepare out buffer
-        let mut data_out: String = String::new();
+        let mut data_out: Vec<u8> = Vec::new();
         let mut data_size: usize = 0;
         //Prepare times
+        let poll_timeout: i32 = std::cmp::min(timeout, 50) as i32;
         let timeout: Duration = Duration::from_millis(timeout);
         let time: Instant = Instant::now();
         while time.elapsed() < timeout {
             //Poll pipe
-            match nix::poll::poll(&mut poll_fds, 50) {
+            match nix::poll::poll(&mut poll_fds, poll_timeout) {
                 Ok(ret) => {
                     if ret > 0 && poll_fds[0].revents().is_some() { //Fifo is available to be read
                         let event: nix::poll::PollFlags = poll_fds[0].revents().unwrap();
@@ -111,13 +145,8 @@ impl Pipe {
                             match unistd::read(self.fd, &mut buffer) {
                                 Ok(bytes_read) => {
                                     data_size += bytes_read;
-                                    //Push bytes converted to string to data out
-                                    data_out.push_str(match std::str::from_utf8(&buffer[0..bytes_read]) {
-                                        Ok(s) => s,
-                                        Err(_) => {
-                                            return Err(ShellError::InvalidData)
-                                        }
-                                    });
+                                    //Push bytes to data out
+                                    data_out.extend_from_slice(&buffer[0..bytes_read]);
                                     if ! read_all {
                                         break;
                                     }
@@ -133,10 +162,18 @@ impl Pipe {
                                                         break; //All data has been read
                                                     }
                                                 },
+                                                nix::errno::Errno::EIO => { //Pseudo-terminal whose slave has been closed; same as HUP
+                                                    if data_size == 0 {
+                                                        thread::sleep(Duration::from_millis(poll_timeout as u64));
+                                                        continue;
+                                                    } else {
+                                                        break;
+                                                    }
+                                                },
                                                 _ => return Err(ShellError::PipeError(errno)) //Error
                                             }
                                         },
-                                        _ => return Err(ShellError::PipeError(nix::errno::Errno::UnknownErrno))
+                                        err => return Err(pipe_error(err))
                                     }
                                 }
                             };
@@ -173,7 +210,7 @@ impl Pipe {
                                 _ => return Err(ShellError::PipeError(errno)) //Error
                             }
                         },
-                        _ => return Err(ShellError::PipeError(nix::errno::Errno::UnknownErrno))
+                        err => return Err(pipe_error(err))
                     }
                 }
             }
@@ -188,14 +225,22 @@ impl Pipe {
     /// ### write
     /// 
     /// Write data out to pipe
+    #[cfg(test)]
     pub fn write(&self, data: String, timeout: u64) -> Result<(), ShellError> {
+        self.write_bytes(data.as_bytes(), timeout)
+    }
+
+    /// ### write_bytes
+    /// 
+    /// Write bytes out to pipe; data is not required to be valid UTF-8.
+    /// If the pipe has no reader anymore (EPIPE), `ProcessTerminated` is returned;
+    /// if no byte can be written for `timeout` milliseconds (the pipe is full), `IoTimeout` is returned
+    pub fn write_bytes(&self, data_out: &[u8], timeout: u64) -> Result<(), ShellError> {
         //Create poll fd wrapper
         let mut poll_fds: [nix::poll::PollFd; 1] = [nix::poll::PollFd::new(self.fd, nix::poll::PollFlags::POLLOUT)];
-        //Prepare times
+        //Prepare times; the timeout restarts each time some bytes are written
         let timeout: Duration = Duration::from_millis(timeout);
-        let time: Instant = Instant::now();
-        //Prepare data out
-        let data_out = data.as_bytes();
+        let mut time: Instant = Instant::now();
         let total_bytes_amount: usize = data_out.len();
         //Write bytes
         let mut bytes_written: usize = 0;
@@ -214,25 +259,22 @@ impl Pipe {
                             match unistd::write(self.fd, &data_out[bytes_written..(bytes_written + bytes_out)]) {
                                 Ok(bytes) => {
                                     bytes_written += bytes; //Increment bytes written of bytes
+                                    time = Instant::now();
                                 },
                                 Err(err) => {
                                     match err {
-                                        nix::Error::Sys(errno) => return Err(ShellError::PipeError(errno)),
-                                        _ => return Err(ShellError::PipeError(nix::errno::Errno::UnknownErrno))
+                                        nix::Error::Sys(nix::errno::Errno::EPIPE) => return Err(ShellError::ProcessTerminated),
+                                        nix::Error::Sys(nix::errno::Errno::EAGAIN) => {}, //Pipe is full; wait for the reader
+                                        err => return Err(pipe_error(err))
                                     }
                                 }
                             }
                         }
                     }
                 },
-                Err(err) => {
-                    match err {
-                        nix::Error::Sys(errno) => return Err(ShellError::PipeError(errno)),
-                        _ => return Err(ShellError::PipeError(nix::errno::Errno::UnknownErrno))
-                    }
-                }
+                Err(err) => return Err(pipe_error(err))
             };
-            if bytes_written == 0 && time.elapsed() >= timeout {
+            if bytes_written < total_bytes_amount && time.elapsed() >= timeout {
                 //Return Io Timeout
                 return Err(ShellError::IoTimeout);
             }
@@ -242,6 +284,25 @@ impl Pipe {
 
 }
 
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        //Delete the FIFO; descriptors opened on it are still valid
+        if !self.path.as_os_str().is_empty() {
+            let _ = unistd::unlink(self.path.as_path());
+        }
+    }
+}
+
+/// ### pipe_error
+/// 
+/// Convert a nix error into a PipeError with its errno
+fn pipe_error(err: nix::Error) -> ShellError {
+    match err {
+        nix::Error::Sys(errno) => ShellError::PipeError(errno),
+        _ => ShellError::PipeError(nix::errno::Errno::UnknownErrno)
+    }
+}
+
 //@! Test module
 
 #[cfg(test)]
@@ -249,6 +310,7 @@ mod tests {
 
     use super::*;
 
+    use std::sync::Arc;
     use std::thread;
     use std::time::Duration;
 
@@ -264,6 +326,21 @@ mod tests {
         assert!(pipe.close().is_ok());
     }
 
+    #[test]
+    fn test_pipe_open_end() {
+        let tmpdir: tempfile::TempDir = create_tmp_dir();
+        let pipe_path: PathBuf = tmpdir.path().join("test.fifo");
+        let pipe: Pipe = Pipe::open(&pipe_path).unwrap();
+        //Open writer end and write through it
+        let writer: RawFd = pipe.open_end(nix::fcntl::OFlag::O_WRONLY).unwrap();
+        assert!(unistd::write(writer, b"HELLO\n").is_ok());
+        assert_eq!(pipe.read(500, false).unwrap().unwrap(), String::from("HELLO\n"));
+        assert!(unistd::close(writer).is_ok());
+        assert!(pipe.close().is_ok());
+        //Open end of a closed pipe
+        assert!(pipe.open_end(nix::fcntl::OFlag::O_WRONLY).is_err());
+    }
+
     #[test]
     fn test_pipe_io() {
         let tmpdir: tempfile::TempDir = create_tmp_dir();
@@ -271,8 +348,8 @@ mod tests {
         //Open Pipe
         let pipe: Result<Pipe, ShellError> = Pipe::open(&pipe_path);
         assert!(pipe.is_ok(), format!("Pipe ({}) should be OK, but is {:?}", pipe_path.display(), pipe));
-        let pipe: Pipe = pipe.unwrap();
-        let pipe_thread: Pipe = pipe.clone();
+        let pipe: Arc<Pipe> = Arc::new(pipe.unwrap());
+        let pipe_thread: Arc<Pipe> = Arc::clone(&pipe);
         //Start thread
         let join_hnd: thread::JoinHandle<()> = thread::spawn(move || {
             let input: String = pipe_thread.read(1000, true).unwrap().unwrap();
@@ -302,8 +379,8 @@ mod tests {
         //Open Pipe
         let pipe: Result<Pipe, ShellError> = Pipe::open(&pipe_path);
         assert!(pipe.is_ok(), format!("Pipe ({}) should be OK, but is {:?}", pipe_path.display(), pipe));
-        let pipe: Pipe = pipe.unwrap();
-        let pipe_thread: Pipe = pipe.clone();
+        let pipe: Arc<Pipe> = Arc::new(pipe.unwrap());
+        let pipe_thread: Arc<Pipe> = Arc::clone(&pipe);
         //Start thread
         let join_hnd: thread::JoinHandle<()> = thread::spawn(move || {
             let mut data: String = String::with_capacity(10240);
@@ -357,6 +434,46 @@ mod tests {
         assert!(pipe.close().is_ok());
     }
 
+    #[test]
+    fn test_pipe_write_timeout() {
+        let tmpdir: tempfile::TempDir = create_tmp_dir();
+        let pipe: Pipe = Pipe::open(&tmpdir.path().join("stdin.fifo")).unwrap();
+        //Nobody reads the pipe: once it's full, the write times out instead of blocking
+        let data: Vec<u8> = vec![b'c'; 1024 * 1024];
+        let time: Instant = Instant::now();
+        assert_eq!(pipe.write_bytes(&data, 200).err().unwrap(), ShellError::IoTimeout);
+        assert!(time.elapsed() < Duration::from_secs(2));
+        //What has been written can still be read
+        assert!(pipe.read(200, false).unwrap().is_some());
+        assert!(pipe.close().is_ok());
+    }
+
+    #[test]
+    fn test_pipe_drop() {
+        let tmpdir: tempfile::TempDir = create_tmp_dir();
+        let pipe_path: PathBuf = tmpdir.path().join("stdout.fifo");
+        let pipe: Pipe = Pipe::open(&pipe_path).unwrap();
+        let fd: RawFd = pipe.fd;
+        assert!(pipe_path.exists());
+        //The FIFO is deleted once the pipe is dropped
+        drop(pipe);
+        assert!(!pipe_path.exists());
+        assert!(unistd::close(fd).is_ok());
+        //Opening a FIFO which already exists fails
+        let pipe: Pipe = Pipe::open(&pipe_path).unwrap();
+        assert_eq!(Pipe::open(&pipe_path).err().unwrap(), ShellError::PipeError(nix::errno::Errno::EEXIST));
+        assert!(pipe.close().is_ok());
+    }
+
+    #[test]
+    fn test_pipe_write_no_reader() {
+        let (reader, writer) = unistd::pipe().unwrap();
+        assert!(unistd::close(reader).is_ok());
+        let pipe: Pipe = Pipe::from_fd(writer);
+        assert_eq!(pipe.write(String::from("HELLO\n"), 1000).err().unwrap(), ShellError::ProcessTerminated);
+        assert!(pipe.close().is_ok());
+    }
+
     fn create_tmp_dir() -> tempfile::TempDir {
         tempfile::TempDir::new().unwrap()
     }
@@ -27,49 +27,69 @@ extern crate nix;
 extern crate tempfile;
 extern crate uuid;
 
-use super::{ShellError, ShellProc, ShellProcState};
+use super::{ExecOptions, ResourceUsage, ShellError, ShellProc, ShellProcState};
 use super::pipe::Pipe;
+use crate::utils::terminal::{self, TerminalSize};
 
 use std::ffi::{CStr, CString};
+use std::fs;
 use std::os::unix::io::RawFd;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
+use nix::sys::termios;
 use uuid::Uuid;
 
 impl ShellProc {
 
     /// ### start
     /// 
-    /// Start a process
+    /// Start a process in the working directory and with the environment of pyc
+    #[cfg(test)]
     pub fn start(argv: Vec<String>) -> Result<ShellProc, ShellError> {
+        ShellProc::start_with(argv, &ExecOptions::default())
+    }
+
+    /// ### start_with
+    /// 
+    /// Start a process applying the exec options: the working directory override, the environment variables to set,
+    /// whether the environment inherited from pyc must be cleared first and whether it runs under a pseudo-terminal.
+    /// Under a pseudo-terminal stderr is kept on a pipe, so that pyc can still tell it apart from stdout
+    /// (and a shell doesn't turn interactive, which requires both stdin and stderr to be terminals)
+    pub fn start_with(argv: Vec<String>, options: &ExecOptions) -> Result<ShellProc, ShellError> {
         if argv.len() == 0 {
             return Err(ShellError::CouldNotStartProcess)
         }
         //Generate UUID - NOTE: UUID is used to notice process that shell subprocess has terminated
         let uuid: String = Uuid::new_v4().to_hyphenated().to_string();
-        //Create pipes
+        //Create pipes; under a pseudo-terminal stdin and stdout are the master, while stderr is still a pipe
         let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
-        let stdin_pipe: Pipe = match Pipe::open(&tmpdir.path().join("stdin.fifo")) {
-            Ok(p) => p,
-            Err(err) => return Err(err)
-        };
         let stderr_pipe: Pipe = match Pipe::open(&tmpdir.path().join("stderr.fifo")) {
             Ok(p) => p,
             Err(err) => return Err(err)
         };
-        let stdout_pipe: Pipe = match Pipe::open(&tmpdir.path().join("stdout.fifo")) {
-            Ok(p) => p,
-            Err(err) => return Err(err)
+        let (stdin_pipe, stdout_pipe, child_stdin, child_stdout): (Pipe, Pipe, RawFd, RawFd) = match options.pty {
+            true => ShellProc::open_pty()?,
+            false => ShellProc::open_pipes(tmpdir.path())?
         };
+        let child_stderr: RawFd = stderr_pipe.open_end(nix::fcntl::OFlag::O_WRONLY)?;
         //Fork process
         match unsafe {nix::unistd::fork()} {
             Ok(nix::unistd::ForkResult::Parent { child, .. }) => {
+                //Put the child in its own process group from the parent too, so signals can be sent to the group right away (fails harmlessly if the child did it first)
+                if !options.pty {
+                    let _ = nix::unistd::setpgid(child, child);
+                }
+                //Child ends are not used by the parent
+                for fd in [child_stdin, child_stderr, child_stdout].iter() {
+                    let _ = nix::unistd::close(*fd);
+                }
                 //Prepare echo command
                 //FIXME: handle fish $status
                 let echo_command: String = format!("echo \"\x02$?;`pwd`;{}\x03\"\n", uuid);
-                let wrkdir: PathBuf = match std::env::current_dir() {
-                    Err(_) => PathBuf::from("/"),
-                    Ok(path) => PathBuf::from(path.as_path())
+                let wrkdir: PathBuf = match (options.wrkdir.as_ref(), std::env::current_dir()) {
+                    (Some(path), _) => path.clone(),
+                    (None, Err(_)) => PathBuf::from("/"),
+                    (None, Ok(path)) => PathBuf::from(path.as_path())
                 };
                 //Return Shell Proc
                 Ok(ShellProc {
@@ -77,10 +97,13 @@ impl ShellProc {
                     uuid: uuid,
                     exit_status: 0,
                     exec_time: Duration::from_millis(0),
+                    rusage: None,
                     wrkdir: wrkdir,
                     pid: child.as_raw(),
-                    rc: 255,
+                    rc: None,
                     stdout_cache: None,
+                    stdout_carry: Vec::new(),
+                    stderr_carry: Vec::new(),
                     start_time: Instant::now(),
                     echo_command: echo_command,
                     stdin_pipe: stdin_pipe,
@@ -89,7 +112,11 @@ impl ShellProc {
                 })
             },
             Ok(nix::unistd::ForkResult::Child) => {
-                std::process::exit(ShellProc::run(argv, stdin_pipe.fd, stderr_pipe.fd, stdout_pipe.fd));
+                //Parent ends are not used by the child
+                for fd in [stdin_pipe.fd, stderr_pipe.fd, stdout_pipe.fd].iter() {
+                    let _ = nix::unistd::close(*fd);
+                }
+                std::process::exit(ShellProc::run(argv, options, child_stdin, child_stderr, child_stdout));
             },
             Err(_) => {
                 return Err(ShellError::CouldNotStartProcess)
@@ -97,6 +124,77 @@ impl ShellProc {
         }
     }
 
+    /// ### open_pipes
+    /// 
+    /// Create the stdin and stdout FIFOs in `dir`. Returns the pipes and the child ends (stdin, stdout)
+    fn open_pipes(dir: &Path) -> Result<(Pipe, Pipe, RawFd, RawFd), ShellError> {
+        let stdin_pipe: Pipe = Pipe::open(&dir.join("stdin.fifo"))?;
+        let stdout_pipe: Pipe = Pipe::open(&dir.join("stdout.fifo"))?;
+        //Open the child ends of the pipes; the child mustn't be a reader of its own output,
+        //otherwise it would never get SIGPIPE once pyc stops reading from it
+        let child_stdin: RawFd = stdin_pipe.open_end(nix::fcntl::OFlag::O_RDONLY)?;
+        let child_stdout: RawFd = stdout_pipe.open_end(nix::fcntl::OFlag::O_WRONLY)?;
+        Ok((stdin_pipe, stdout_pipe, child_stdin, child_stdout))
+    }
+
+    /// ### open_pty
+    /// 
+    /// Open a pseudo-terminal with the size of the terminal of pyc. Returns the master (once for stdin and once for stdout)
+    /// and the slave (once for stdin and once for stdout).
+    /// Echo, signal characters and output post-processing are disabled on the slave: pyc echoes and handles control keys by itself
+    /// and output must keep its newlines as they are
+    fn open_pty() -> Result<(Pipe, Pipe, RawFd, RawFd), ShellError> {
+        let size: TerminalSize = terminal::size();
+        let winsize: nix::pty::Winsize = nix::pty::Winsize {
+            ws_row: size.rows as u16,
+            ws_col: size.columns as u16,
+            ws_xpixel: 0,
+            ws_ypixel: 0
+        };
+        let pty: nix::pty::OpenptyResult = match nix::pty::openpty(Some(&winsize), None) {
+            Ok(pty) => pty,
+            Err(nix::Error::Sys(errno)) => return Err(ShellError::PipeError(errno)),
+            Err(_) => return Err(ShellError::PipeError(nix::errno::Errno::UnknownErrno))
+        };
+        let setup = || -> nix::Result<(RawFd, RawFd)> {
+            let mut attrs: termios::Termios = termios::tcgetattr(pty.slave)?;
+            attrs.local_flags.remove(termios::LocalFlags::ECHO | termios::LocalFlags::ISIG);
+            attrs.input_flags.remove(termios::InputFlags::IXON);
+            attrs.output_flags.remove(termios::OutputFlags::OPOST);
+            termios::tcsetattr(pty.slave, termios::SetArg::TCSANOW, &attrs)?;
+            Ok((nix::unistd::dup(pty.master)?, nix::unistd::dup(pty.slave)?))
+        };
+        match setup() {
+            Ok((master, slave)) => Ok((Pipe::from_fd(master), Pipe::from_fd(pty.master), slave, pty.slave)),
+            Err(err) => {
+                let _ = nix::unistd::close(pty.master);
+                let _ = nix::unistd::close(pty.slave);
+                match err {
+                    nix::Error::Sys(errno) => Err(ShellError::PipeError(errno)),
+                    _ => Err(ShellError::PipeError(nix::errno::Errno::UnknownErrno))
+                }
+            }
+        }
+    }
+
+    /// ### start_job
+    /// 
+    /// Start a process which runs a single command (e.g. `sh -c "sleep 10"`).
+    /// Since there's no shell handshake, the process is running until it terminates and data written to it is sent as it is
+    pub fn start_job(argv: Vec<String>, options: &ExecOptions) -> Result<ShellProc, ShellError> {
+        let mut process: ShellProc = ShellProc::start_with(argv, options)?;
+        process.set_state_running();
+        Ok(process)
+    }
+
+    /// ### set_window_size
+    /// 
+    /// Set the size of the pseudo-terminal the process runs under; its foreground process group gets SIGWINCH.
+    /// Returns false if the process doesn't run under a pseudo-terminal
+    pub fn set_window_size(&self, columns: usize, rows: usize) -> bool {
+        nix::unistd::isatty(self.stdout_pipe.fd).unwrap_or(false) && terminal::set_window_size(self.stdout_pipe.fd, columns, rows).is_ok()
+    }
+
     /// ### cleanup
     /// 
     /// cleanup shell once exited. Returns the shell exit code
@@ -108,19 +206,52 @@ impl ShellProc {
         let _ = self.stdin_pipe.close();
         let _ = self.stdout_pipe.close();
         let _ = self.stderr_pipe.close();
-        Ok(self.rc)
+        self.rc.ok_or(ShellError::ShellRunning)
     }
 
     /// ### raise
     /// 
-    /// Send signal to shell
+    /// Send signal to the process group led by the shell (the shell and the commands it runs without job control, e.g. every stage of a pipeline).
+    /// If the group can't be signaled, the signal is sent to the shell only
     pub fn raise(&self, signal: nix::sys::signal::Signal) -> Result<(), ShellError> {
-        match nix::sys::signal::kill(nix::unistd::Pid::from_raw(self.pid), signal) {
+        let pid: nix::unistd::Pid = nix::unistd::Pid::from_raw(self.pid);
+        if nix::sys::signal::killpg(pid, signal).is_ok() {
+            return Ok(())
+        }
+        match nix::sys::signal::kill(pid, signal) {
             Ok(_) => Ok(()),
             Err(_) => Err(ShellError::CouldNotKill)
         }
     }
 
+    /// ### raise_children
+    /// 
+    /// Send signal to the processes started by the shell (e.g. every stage of a pipeline) and to their children.
+    /// The shell itself doesn't receive the signal. Returns the amount of processes which have been signaled
+    pub fn raise_children(&self, signal: nix::sys::signal::Signal) -> usize {
+        let children: Vec<i32> = ShellProc::descendants(self.pid);
+        for pid in children.iter() {
+            let _ = nix::sys::signal::kill(nix::unistd::Pid::from_raw(*pid), signal);
+        }
+        children.len()
+    }
+
+    /// ### suspend
+    /// 
+    /// Stop the process and the processes it started sending SIGTSTP; they can be resumed with `resume`
+    pub fn suspend(&self) -> Result<(), ShellError> {
+        self.raise_children(nix::sys::signal::Signal::SIGTSTP);
+        self.raise(nix::sys::signal::Signal::SIGTSTP)
+    }
+
+    /// ### resume
+    /// 
+    /// Continue the process and the processes it started, after they've been suspended, sending SIGCONT
+    pub fn resume(&self) -> Result<(), ShellError> {
+        self.raise_children(nix::sys::signal::Signal::SIGCONT);
+        self.raise(nix::sys::signal::Signal::SIGCONT)
+    }
+
     /// ### kill
     /// 
     /// Kill shell sending SIGKILL
@@ -132,53 +263,136 @@ impl ShellProc {
     /// 
     /// Read from child pipes
     pub fn read(&mut self) -> Result<(Option<String>, Option<String>), ShellError> {
+        self.read_timeout(50)
+    }
+
+    /// ### read_timeout
+    /// 
+    /// Read from child pipes, waiting at most `timeout` milliseconds for each pipe
+    pub fn read_timeout(&mut self, timeout: u64) -> Result<(Option<String>, Option<String>), ShellError> {
         /* NOTE: doesn't make sense; read must be possible even if shell has terminated
         if self.update_state() == ShellProcState::Terminated {
             return Err(ShellError::ShellTerminated)
         }*/
-        let stdout: Option<String> = match self.stdout_pipe.read(50, false) {
-            Ok(stdout) => self.parse_stdout(stdout),
+        let stdout: Option<String> = match self.stdout_pipe.read_bytes(timeout, false) {
+            Ok(stdout) => match ShellProc::decode_utf8(&mut self.stdout_carry, stdout) {
+                Ok(stdout) => self.parse_stdout(stdout),
+                Err(err) => return Err(err)
+            },
             Err(err) => return Err(err)
         };
-        let stderr: Option<String> = match self.stderr_pipe.read(50, false) {
-            Ok(stderr) => match stderr {
-                None => None,
-                Some(stderr) => Some(stderr)
-            },
+        let stderr: Option<String> = match self.stderr_pipe.read_bytes(timeout, false) {
+            Ok(stderr) => ShellProc::decode_utf8(&mut self.stderr_carry, stderr)?,
             Err(err) => return Err(err)
         };
         Ok((stdout, stderr))
     }
 
+    /// ### descendants
+    /// 
+    /// Get the pids of the children of `pid` and of their children, reading the parent pid of each process from `/proc`.
+    /// Returns an empty vector where `/proc` is not available
+    fn descendants(pid: i32) -> Vec<i32> {
+        let entries = match fs::read_dir("/proc") {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        //Associate each process to its parent
+        let mut processes: Vec<(i32, i32)> = Vec::new();
+        for entry in entries.flatten() {
+            let child: i32 = match entry.file_name().to_string_lossy().parse::<i32>() {
+                Ok(child) => child,
+                Err(_) => continue,
+            };
+            //Parent pid is the second field after the command name, which is between parentheses and can contain spaces
+            let stat: String = match fs::read_to_string(entry.path().join("stat")) {
+                Ok(stat) => stat,
+                Err(_) => continue,
+            };
+            let ppid: Option<i32> = stat.rfind(')').and_then(|pos| stat[pos + 1..].split_whitespace().nth(1)).and_then(|ppid| ppid.parse::<i32>().ok());
+            if let Some(ppid) = ppid {
+                processes.push((child, ppid));
+            }
+        }
+        let mut descendants: Vec<i32> = Vec::new();
+        let mut parents: Vec<i32> = vec![pid];
+        while let Some(parent) = parents.pop() {
+            for (child, _) in processes.iter().filter(|(_, ppid)| *ppid == parent) {
+                descendants.push(*child);
+                parents.push(*child);
+            }
+        }
+        descendants
+    }
+
+    /// ### decode_utf8
+    /// 
+    /// Convert bytes read from a pipe to string, prepending the bytes carried over from the previous read.
+    /// If data ends with an incomplete multi-byte character, its bytes are kept in carry for the next read
+    fn decode_utf8(carry: &mut Vec<u8>, data: Option<Vec<u8>>) -> Result<Option<String>, ShellError> {
+        let mut bytes: Vec<u8> = match data {
+            None => return Ok(None),
+            Some(data) => {
+                let mut bytes: Vec<u8> = Vec::with_capacity(carry.len() + data.len());
+                bytes.append(carry);
+                bytes.extend(data);
+                bytes
+            }
+        };
+        if let Err(err) = std::str::from_utf8(&bytes) {
+            match err.error_len() {
+                None => *carry = bytes.split_off(err.valid_up_to()), //Incomplete sequence at the end of data
+                Some(_) => return Err(ShellError::InvalidData)
+            }
+        }
+        match bytes.len() {
+            0 => Ok(None),
+            _ => Ok(Some(String::from_utf8(bytes).unwrap()))
+        }
+    }
+
     /// ### write
     /// 
     /// Write to child process stdin
-    pub fn write(&mut self, mut data: String) -> Result<(), ShellError> {
+    #[cfg(test)]
+    pub fn write(&mut self, data: String) -> Result<(), ShellError> {
+        self.write_bytes(data.into_bytes())
+    }
+
+    /// ### write_bytes
+    /// 
+    /// Write bytes to child process stdin; data is not required to be valid UTF-8 (e.g. file names in legacy encodings).
+    /// If the process has terminated (even while writing), `ProcessTerminated` is returned
+    pub fn write_bytes(&mut self, mut data: Vec<u8>) -> Result<(), ShellError> {
         if self.update_state() == ShellProcState::Terminated {
-            return Err(ShellError::ShellTerminated)
+            return Err(ShellError::ProcessTerminated)
         }
         //Add echo command to data if shell state is Idle
         if self.state == ShellProcState::Idle {
             //Replace data newline with ';'
-            while data.ends_with('\n') {
+            while data.ends_with(b"\n") {
                 data.pop();
             }
             //Append semicolon to data
-            if ! data.ends_with(';') {
-                data.push(';');
+            if ! data.ends_with(b";") {
+                data.push(b';');
             }
             //Append echo command to data
-            data.push_str(self.echo_command.as_str());
+            data.extend_from_slice(self.echo_command.as_bytes());
             //Set state to running
             self.set_state_running();
         }
-        self.stdin_pipe.write(data, 5000)
+        match self.stdin_pipe.write_bytes(&data, 5000) {
+            //The process may have exited while data was being written
+            Err(_) if self.update_state() == ShellProcState::Terminated => Err(ShellError::ProcessTerminated),
+            result => result,
+        }
     }
 
     /// ### run
     /// 
     /// Run method for thread
-    fn run(argv: Vec<String>, stdin: RawFd, stderr: RawFd, stdout: RawFd) -> i32 {
+    fn run(argv: Vec<String>, options: &ExecOptions, stdin: RawFd, stderr: RawFd, stdout: RawFd) -> i32 {
         //Set child process stdout/stdin/stderr
         if let Err(_) = nix::unistd::dup2(stdin, 0) {
             return 255
@@ -189,6 +403,37 @@ impl ShellProc {
         if let Err(_) = nix::unistd::dup2(stderr, 2) {
             return 255
         }
+        for fd in [stdin, stdout, stderr].iter() {
+            let _ = nix::unistd::close(*fd);
+        }
+        //Under a pseudo-terminal, the process leads a new session whose controlling terminal is the slave (its stdin)
+        if options.pty {
+            if nix::unistd::setsid().is_err() {
+                return 255
+            }
+            if unsafe { nix::libc::ioctl(0, nix::libc::TIOCSCTTY, 0) } != 0 {
+                return 255
+            }
+        } else if nix::unistd::setpgid(nix::unistd::Pid::from_raw(0), nix::unistd::Pid::from_raw(0)).is_err() {
+            //Otherwise the process leads a new process group, so that signals generated by pyc's terminal don't reach it
+            return 255
+        }
+        //Restore SIGPIPE default disposition (Rust ignores it and ignored signals are inherited through exec)
+        let _ = unsafe { nix::sys::signal::signal(nix::sys::signal::Signal::SIGPIPE, nix::sys::signal::SigHandler::SigDfl) };
+        //Apply exec options
+        if let Some(wrkdir) = options.wrkdir.as_ref() {
+            if nix::unistd::chdir(wrkdir.as_path()).is_err() {
+                return 255
+            }
+        }
+        if options.clear_env {
+            for (name, _) in std::env::vars_os() {
+                std::env::remove_var(name);
+            }
+        }
+        for (name, value) in options.env.iter() {
+            std::env::set_var(name, value);
+        }
         //Prepare arguments
         let mut c_argv: Vec<CString> = Vec::with_capacity(argv.len());
         for arg in argv.iter() {
@@ -210,90 +455,113 @@ impl ShellProc {
     /// Update shell running state checking if the other thread has terminated
     pub fn update_state(&mut self) -> ShellProcState {
         //Wait pid (NO HANG)
-        match nix::sys::wait::waitpid(nix::unistd::Pid::from_raw(self.pid), Some(nix::sys::wait::WaitPidFlag::WNOHANG)) {
-            Err(_) => {}, //Could not get information
-            Ok(status) => match status {
-                nix::sys::wait::WaitStatus::Exited(_, rc) => {
-                    self.state = ShellProcState::Terminated;
-                    self.rc = rc as u8;
-                },
-                nix::sys::wait::WaitStatus::Signaled(_, signal, _) => {
-                    self.state = ShellProcState::Terminated;
-                    self.rc = signal as u8;
-                },
-                _ => {}, //Still running
+        match ShellProc::wait(self.pid) {
+            None => {}, //Could not get information
+            Some((status, rusage)) => if let Some(rc) = ShellProc::exit_code(status) {
+                self.state = ShellProcState::Terminated;
+                self.rc = Some(rc);
+                self.rusage = Some(rusage);
             }
         };
         self.state
     }
 
+    /// ### wait
+    ///
+    /// Wait (without hanging) for the process to change state, like `waitpid`, collecting the resources it has used with `wait4`.
+    /// Returns None if the state couldn't be collected
+    fn wait(pid: i32) -> Option<(nix::sys::wait::WaitStatus, ResourceUsage)> {
+        let mut status: i32 = 0;
+        let mut rusage: nix::libc::rusage = unsafe { std::mem::zeroed() };
+        let res: nix::libc::pid_t = unsafe { nix::libc::wait4(pid, &mut status, nix::libc::WNOHANG, &mut rusage) };
+        if res == 0 {
+            return Some((nix::sys::wait::WaitStatus::StillAlive, ResourceUsage::default()));
+        }
+        if res < 0 {
+            return None;
+        }
+        let to_duration = |time: nix::libc::timeval| Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64);
+        let usage: ResourceUsage = ResourceUsage {
+            user: to_duration(rusage.ru_utime),
+            sys: to_duration(rusage.ru_stime),
+        };
+        nix::sys::wait::WaitStatus::from_raw(nix::unistd::Pid::from_raw(res), status).ok().map(|status| (status, usage))
+    }
+
+    /// ### exit_code
+    /// 
+    /// Get the return code of a terminated process from its wait status, following the shell convention:
+    /// the exit code is masked to 8 bits and a process killed by signal N returns 128 + N.
+    /// Returns None if the process hasn't terminated
+    fn exit_code(status: nix::sys::wait::WaitStatus) -> Option<u8> {
+        match status {
+            nix::sys::wait::WaitStatus::Exited(_, rc) => Some((rc & 0xff) as u8),
+            nix::sys::wait::WaitStatus::Signaled(_, signal, _) => Some(128 + signal as u8),
+            _ => None //Still running
+        }
+    }
+
     /// ### parse_stdout
     /// 
-    /// Parse stdout received from shell process
+    /// Parse stdout received from shell process.
+    /// The metadata echoed after each command (\x02${?};${PWD};${UUID}\x03\n) are stripped from the output;
+    /// if they arrive split between two reads, their beginning is kept in the cache and not returned
     fn parse_stdout(&mut self, stdout: Option<String>) -> Option<String> {
-        match stdout {
-            None => None,
-            Some(stdout) => {
-                //Treat stdout
-                let termination_string: String = format!("{}\x03\n", self.uuid);
-                //Check if ends with this (\x02${?};${PWD};${UUID}\x03\n)
-                //Create check string (cache + stdout)
-                let check_string: String = match &self.stdout_cache {
-                    None => stdout.clone(),
-                    Some(cache) => {
-                        let mut s: String = String::with_capacity(stdout.len() + cache.len());
-                        s.push_str(cache.as_str());
-                        s.push_str(stdout.as_str());
-                        s
-                    }
-                };
-                //Check if string ends with termination string
-                if check_string.ends_with(termination_string.as_str()) {
-                    //It's the end of shell execution, split string in output and METADATA
-                    //Let's find the index of \x02
-                    let mut stx_index: usize = check_string.len();
-                    for c in check_string.chars().rev() {
-                        if c == '\x02' {
-                            break;
-                        }
-                        stx_index -= 1; //Decrement STX index
-                    }
-                    let metadata: String = String::from(&check_string[stx_index..check_string.len() - 2]);
-                    //Get stdout
-                    let stx_index_stdout: usize = stx_index - match &self.stdout_cache {
-                        Some(s) => s.len(),
-                        None => 0
-                    };
-                    let stdout: String = String::from(&stdout[..stx_index_stdout - 1]);
-                    //get metadata
-                    self.set_state_idle(metadata);
-                    //Clear cache
-                    self.stdout_cache = None;
-                    match stdout.len() {
-                        0 => None,
-                        _ => Some(stdout)
-                    }
-                } else {
-                    //Not a termination
-                    //Push stdout to cache
-                    self.stdout_cache = Some(stdout.clone());
-                    //Return stdout
-                    Some(stdout)
+        let stdout: String = stdout?;
+        //Create check string (cache + stdout)
+        let check_string: String = match self.stdout_cache.take() {
+            None => stdout,
+            Some(mut cache) => {
+                cache.push_str(stdout.as_str());
+                cache
+            }
+        };
+        //Check if ends with termination string
+        let termination_string: String = format!("{}\x03\n", self.uuid);
+        let output: String = if check_string.ends_with(termination_string.as_str()) {
+            //It's the end of shell execution, split string in output and METADATA
+            //Let's find the index of \x02 (byte index, since output may contain multibyte characters)
+            let stx_index: usize = check_string.rfind('\x02').unwrap_or(0);
+            let metadata: String = String::from(&check_string[stx_index + 1..check_string.len() - 2]);
+            //get metadata
+            self.set_state_idle(metadata);
+            String::from(&check_string[..stx_index])
+        } else {
+            //Not a termination; keep what could be the beginning of the metadata
+            match check_string.rfind('\x02') {
+                Some(stx_index) if ShellProc::is_metadata_prefix(&check_string[stx_index..]) => {
+                    self.stdout_cache = Some(String::from(&check_string[stx_index..]));
+                    String::from(&check_string[..stx_index])
                 }
+                _ => check_string,
             }
+        };
+        match output.len() {
+            0 => None,
+            _ => Some(output)
         }
     }
 
+    /// ### is_metadata_prefix
+    ///
+    /// Returns whether the provided string (starting with \x02) may be the beginning of the metadata echoed by the shell
+    fn is_metadata_prefix(data: &str) -> bool {
+        let metadata: &str = data.trim_start_matches('\x02');
+        //Metadata start with the exit status and don't contain newlines before their end
+        let exit_status: &str = metadata.split(';').next().unwrap_or("");
+        !metadata.contains('\n') && !metadata.contains('\x03') && exit_status.chars().all(|ch| ch.is_ascii_digit())
+    }
+
     /// ### set_state_idle
     /// 
     /// Parse metadata string and set state back to idle
     fn set_state_idle(&mut self, metadata: String) {
-        for (index, token) in metadata.split(";").enumerate() {
-            match index {
-                0 => self.exit_status = token.parse::<u8>().unwrap_or(255),
-                1 => self.wrkdir = PathBuf::from(token),
-                _ => continue
-            }
+        //Working directory may contain ';': it's everything between exit status and uuid
+        let metadata: &str = metadata.rsplitn(2, ';').last().unwrap_or("");
+        let mut tokens = metadata.splitn(2, ';');
+        self.exit_status = tokens.next().unwrap_or("").parse::<u8>().unwrap_or(255);
+        if let Some(wrkdir) = tokens.next() {
+            self.wrkdir = PathBuf::from(wrkdir);
         }
         self.exec_time = self.start_time.elapsed();
         self.state = ShellProcState::Idle;
@@ -317,6 +585,50 @@ impl Drop for ShellProc {
     }
 }
 
+/// ### capture_output
+/// 
+/// Run a command in `wrkdir`, without a shell, and return its stdout.
+/// The command is killed if it doesn't terminate within `timeout`; a failure exit status is an error too
+#[cfg(any(test, feature = "git-lite"))]
+pub fn capture_output(argv: &[&str], wrkdir: &Path, timeout: Duration) -> Result<String, ShellError> {
+    use std::io::Read;
+    use std::process::{Child, Command, Stdio};
+    use std::thread;
+
+    let (program, args): (&&str, &[&str]) = match argv.split_first() {
+        Some(argv) => argv,
+        None => return Err(ShellError::CouldNotStartProcess),
+    };
+    let mut child: Child = match Command::new(program).args(args).current_dir(wrkdir).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn() {
+        Ok(child) => child,
+        Err(_) => return Err(ShellError::CouldNotStartProcess),
+    };
+    //Read stdout on another thread, so that the child never blocks on a full pipe
+    let mut stdout = child.stdout.take().unwrap();
+    let reader = thread::spawn(move || {
+        let mut output: Vec<u8> = Vec::new();
+        let _ = stdout.read_to_end(&mut output);
+        output
+    });
+    let deadline: Instant = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => break,
+            Ok(Some(_)) => return Err(ShellError::InvalidData),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(5)),
+            Ok(None) | Err(_) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(ShellError::IoTimeout);
+            }
+        }
+    }
+    match reader.join() {
+        Ok(output) => String::from_utf8(output).map_err(|_| ShellError::InvalidData),
+        Err(_) => Err(ShellError::InvalidData),
+    }
+}
+
 //@! Test module
 
 #[cfg(test)]
@@ -338,7 +650,7 @@ mod tests {
         assert_ne!(shell_proc.pid, 0);
         assert_ne!(shell_proc.wrkdir.len(), 0);
         assert_eq!(shell_proc.exec_time, Duration::from_millis(0));
-        assert_eq!(shell_proc.rc, 255);
+        assert_eq!(shell_proc.rc, None);
         assert_ne!(shell_proc.uuid.len(), 0);
         assert!(shell_proc.stdout_cache.is_none());
         assert_eq!(shell_proc.echo_command, format!("echo \"\x02$?;`pwd`;{}\x03\"\n", shell_proc.uuid));
@@ -349,9 +661,9 @@ mod tests {
         assert!(shell_proc.kill().is_ok());
         sleep(Duration::from_millis(500));
         assert_eq!(shell_proc.update_state(), ShellProcState::Terminated);
-        //Rc should be set to 9
+        //Rc should be set to 128 + 9
         assert_eq!(shell_proc.state, ShellProcState::Terminated);
-        assert_eq!(shell_proc.rc, 9);
+        assert_eq!(shell_proc.rc, Some(137));
         //Cleanup
         assert!(shell_proc.cleanup().is_ok());
     }
@@ -363,7 +675,7 @@ mod tests {
         //Shell should have died
         sleep(Duration::from_millis(1000));
         assert_eq!(shell_proc.update_state(), ShellProcState::Terminated);
-        assert_eq!(shell_proc.rc, 255);
+        assert_eq!(shell_proc.rc, Some(255));
     }
 
     #[test]
@@ -377,7 +689,65 @@ mod tests {
         assert!(shell_proc.raise(nix::sys::signal::Signal::SIGINT).is_ok());
         sleep(Duration::from_millis(500));
         assert_eq!(shell_proc.update_state(), ShellProcState::Terminated);
-        assert_eq!(shell_proc.rc, 2);
+        assert_eq!(shell_proc.rc, Some(130));
+    }
+
+    #[test]
+    fn test_process_raise_children() {
+        let mut shell_proc: ShellProc = ShellProc::start(vec![String::from("sh")]).unwrap();
+        sleep(Duration::from_millis(500));
+        assert_eq!(shell_proc.update_state(), ShellProcState::Idle);
+        //No process running
+        assert_eq!(shell_proc.raise_children(nix::sys::signal::Signal::SIGINT), 0);
+        //Interrupt a pipeline: both stages are signaled, while the shell keeps running
+        assert!(shell_proc.write(String::from("sleep 5 | cat\n")).is_ok());
+        sleep(Duration::from_millis(500));
+        assert_eq!(shell_proc.raise_children(nix::sys::signal::Signal::SIGINT), 2);
+        let t_start: Instant = Instant::now();
+        while shell_proc.update_state() != ShellProcState::Idle && t_start.elapsed() < Duration::from_secs(3) {
+            let _ = shell_proc.read();
+        }
+        assert_eq!(shell_proc.state, ShellProcState::Idle);
+        assert_eq!(shell_proc.exit_status, 130);
+        assert!(shell_proc.kill().is_ok());
+    }
+
+    #[test]
+    fn test_process_group() {
+        let mut shell_proc: ShellProc = ShellProc::start(vec![String::from("sh"), String::from("-c"), String::from("sleep 5 | cat")]).unwrap();
+        sleep(Duration::from_millis(500));
+        //The process leads its own group
+        assert_eq!(nix::unistd::getpgid(Some(nix::unistd::Pid::from_raw(shell_proc.pid))).unwrap().as_raw(), shell_proc.pid);
+        assert_ne!(nix::unistd::getpgrp().as_raw(), shell_proc.pid);
+        let stages: Vec<i32> = ShellProc::descendants(shell_proc.pid);
+        assert_eq!(stages.len(), 2);
+        //Interrupt the group: the shell and both stages of the pipeline die
+        assert!(shell_proc.raise(nix::sys::signal::Signal::SIGINT).is_ok());
+        let t_start: Instant = Instant::now();
+        while shell_proc.update_state() != ShellProcState::Terminated && t_start.elapsed() < Duration::from_secs(3) {
+            sleep(Duration::from_millis(50));
+        }
+        assert_eq!(shell_proc.state, ShellProcState::Terminated);
+        let alive = |pid: &i32| match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat.rfind(')').and_then(|pos| stat[pos + 1..].split_whitespace().next()).map(|state| state != "Z").unwrap_or(false),
+            Err(_) => false
+        };
+        let t_start: Instant = Instant::now();
+        while stages.iter().any(alive) && t_start.elapsed() < Duration::from_secs(3) {
+            sleep(Duration::from_millis(50));
+        }
+        assert!(!stages.iter().any(alive));
+    }
+
+    #[test]
+    fn test_process_exit_code() {
+        let pid: nix::unistd::Pid = nix::unistd::Pid::from_raw(1);
+        assert_eq!(ShellProc::exit_code(nix::sys::wait::WaitStatus::Exited(pid, 0)), Some(0));
+        assert_eq!(ShellProc::exit_code(nix::sys::wait::WaitStatus::Exited(pid, 300)), Some(44));
+        assert_eq!(ShellProc::exit_code(nix::sys::wait::WaitStatus::Signaled(pid, nix::sys::signal::Signal::SIGINT, false)), Some(130));
+        assert_eq!(ShellProc::exit_code(nix::sys::wait::WaitStatus::Signaled(pid, nix::sys::signal::Signal::SIGKILL, true)), Some(137));
+        assert_eq!(ShellProc::exit_code(nix::sys::wait::WaitStatus::Stopped(pid, nix::sys::signal::Signal::SIGTSTP)), None);
+        assert_eq!(ShellProc::exit_code(nix::sys::wait::WaitStatus::StillAlive), None);
     }
 
     #[test]
@@ -414,7 +784,7 @@ mod tests {
         shell_proc.state = ShellProcState::SubprocessRunning;
         assert_eq!(shell_proc.parse_stdout(Some(String::from("HELLO\n"))).unwrap(), String::from("HELLO\n"));
         assert_eq!(shell_proc.state, ShellProcState::SubprocessRunning); //State unchanged
-        assert_eq!(*shell_proc.stdout_cache.as_ref().unwrap(), String::from("HELLO\n"));
+        assert!(shell_proc.stdout_cache.is_none());
         //Parse stdout with everything
         shell_proc.state = ShellProcState::SubprocessRunning;
         assert_eq!(shell_proc.parse_stdout(Some(format!("HELLO\n\x022;/tmp;{}\x03\n", shell_proc.uuid))).unwrap(), String::from("HELLO\n"));
@@ -422,6 +792,81 @@ mod tests {
         assert_eq!(shell_proc.wrkdir, PathBuf::from("/tmp"));
         assert_eq!(shell_proc.state, ShellProcState::Idle);
         assert!(shell_proc.stdout_cache.is_none());
+        //Output and working directory with multibyte characters
+        shell_proc.state = ShellProcState::SubprocessRunning;
+        assert_eq!(shell_proc.parse_stdout(Some(format!("привет\n\x020;/tmp/документы;{}\x03\n", shell_proc.uuid))).unwrap(), String::from("привет\n"));
+        assert_eq!(shell_proc.exit_status, 0);
+        assert_eq!(shell_proc.wrkdir, PathBuf::from("/tmp/документы"));
+        //Metadata split between reads are not returned
+        shell_proc.state = ShellProcState::SubprocessRunning;
+        assert_eq!(shell_proc.parse_stdout(Some(String::from("HELLO\n\x021;/tmp/a;b"))).unwrap(), String::from("HELLO\n"));
+        assert_eq!(shell_proc.state, ShellProcState::SubprocessRunning);
+        assert!(shell_proc.parse_stdout(Some(format!(";{}", &shell_proc.uuid[..8]))).is_none());
+        assert!(shell_proc.parse_stdout(Some(format!("{}\x03\n", &shell_proc.uuid[8..]))).is_none());
+        assert_eq!(shell_proc.exit_status, 1);
+        assert_eq!(shell_proc.wrkdir, PathBuf::from("/tmp/a;b"));
+        assert_eq!(shell_proc.state, ShellProcState::Idle);
+        assert!(shell_proc.stdout_cache.is_none());
+        //Output which only looks like metadata is returned
+        shell_proc.state = ShellProcState::SubprocessRunning;
+        assert_eq!(shell_proc.parse_stdout(Some(String::from("\x02abc"))).unwrap(), String::from("\x02abc"));
+        assert_eq!(shell_proc.parse_stdout(Some(String::from("\x020;/tmp\n"))).unwrap(), String::from("\x020;/tmp\n"));
+        assert!(shell_proc.stdout_cache.is_none());
+        //Kill
+        assert!(shell_proc.kill().is_ok());
+    }
+
+    #[test]
+    fn test_process_decode_utf8() {
+        let mut carry: Vec<u8> = Vec::new();
+        //No data
+        assert!(ShellProc::decode_utf8(&mut carry, None).unwrap().is_none());
+        //Complete string
+        assert_eq!(ShellProc::decode_utf8(&mut carry, Some("привет".as_bytes().to_vec())).unwrap().unwrap(), String::from("привет"));
+        assert_eq!(carry.len(), 0);
+        //Split character ('т' is 0xD1 0x82)
+        let data: &[u8] = "привет".as_bytes();
+        assert_eq!(ShellProc::decode_utf8(&mut carry, Some(data[..data.len() - 1].to_vec())).unwrap().unwrap(), String::from("приве"));
+        assert_eq!(carry, vec![0xd1]);
+        //Only the incomplete byte
+        let mut only_carry: Vec<u8> = Vec::new();
+        assert!(ShellProc::decode_utf8(&mut only_carry, Some(vec![0xd1])).unwrap().is_none());
+        assert_eq!(only_carry, vec![0xd1]);
+        //Next read completes the character
+        assert_eq!(ShellProc::decode_utf8(&mut carry, Some(vec![0x82, 0x0a])).unwrap().unwrap(), String::from("т\n"));
+        assert_eq!(carry.len(), 0);
+        //Invalid data
+        assert_eq!(ShellProc::decode_utf8(&mut carry, Some(vec![0xff, 0x41])).err().unwrap(), ShellError::InvalidData);
+    }
+
+    #[test]
+    fn test_process_read_split_utf8() {
+        //Prepare a file with more than 8192 bytes of cyrillic text
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let file_path: PathBuf = tmpdir.path().join("cyrillic.txt");
+        let mut content: String = String::new();
+        //Odd offset, so that a character is split at the buffer boundary
+        content.push('a');
+        for _ in 0..1024 {
+            content.push_str("привет мир\n");
+        }
+        std::fs::write(&file_path, content.as_bytes()).unwrap();
+        let mut shell_proc: ShellProc = ShellProc::start(vec![String::from("sh")]).unwrap();
+        sleep(Duration::from_millis(500)); //DON'T REMOVE THIS SLEEP
+        assert!(shell_proc.write(format!("cat {}\n", file_path.display())).is_ok());
+        //Read until command has terminated
+        let mut output: String = String::new();
+        let t_start: Instant = Instant::now();
+        while t_start.elapsed() < Duration::from_secs(5) {
+            let (stdout, _) = shell_proc.read().unwrap();
+            if let Some(stdout) = stdout {
+                output.push_str(stdout.as_str());
+            }
+            if shell_proc.update_state() == ShellProcState::Idle {
+                break;
+            }
+        }
+        assert_eq!(output.as_bytes(), content.as_bytes());
         //Kill
         assert!(shell_proc.kill().is_ok());
     }
@@ -449,15 +894,176 @@ mod tests {
         assert_eq!(shell_proc.exit_status, 0);
         //Verify execution time
         assert_ne!(shell_proc.exec_time.as_nanos(), 0);
+        //Failing command
+        assert!(shell_proc.write(String::from("echo foo; ls /this/does/not/exist\n")).is_ok());
+        sleep(Duration::from_millis(100));
+        let (stdout, _) = shell_proc.read().unwrap();
+        assert_eq!(stdout.unwrap(), String::from("foo\n"));
+        assert_eq!(shell_proc.update_state(), ShellProcState::Idle);
+        assert_ne!(shell_proc.exit_status, 0);
+        assert_eq!(shell_proc.wrkdir, PathBuf::from("/tmp"));
+        //Relative cd
+        assert!(shell_proc.write(String::from("cd ..\n")).is_ok());
+        sleep(Duration::from_millis(100));
+        assert!(shell_proc.read().unwrap().0.is_none());
+        assert_eq!(shell_proc.update_state(), ShellProcState::Idle);
+        assert_eq!(shell_proc.exit_status, 0);
+        assert_eq!(shell_proc.wrkdir, PathBuf::from("/"));
         //Stop process
         assert!(shell_proc.kill().is_ok());
         sleep(Duration::from_millis(500));
         assert_eq!(shell_proc.update_state(), ShellProcState::Terminated);
-        //Rc should be set to 9
+        //Rc should be set to 128 + 9
         assert_eq!(shell_proc.state, ShellProcState::Terminated);
-        assert_eq!(shell_proc.rc, 9);
+        assert_eq!(shell_proc.rc, Some(137));
         //Cleanup
         assert!(shell_proc.cleanup().is_ok());
     }
 
+    #[test]
+    fn test_process_start_job() {
+        let mut job: ShellProc = ShellProc::start_job(vec![String::from("sh"), String::from("-c"), String::from("read line; echo \"$line\"; exit 2")], &ExecOptions::default()).unwrap();
+        assert_eq!(job.state, ShellProcState::SubprocessRunning);
+        //Data is written as it is
+        assert!(job.write(String::from("hello\n")).is_ok());
+        sleep(Duration::from_millis(200));
+        let (stdout, _) = job.read().unwrap();
+        assert_eq!(stdout.unwrap(), String::from("hello\n"));
+        assert_eq!(job.update_state(), ShellProcState::Terminated);
+        assert_eq!(job.cleanup().unwrap(), 2);
+        //Short timeout
+        let mut job: ShellProc = ShellProc::start_job(vec![String::from("sleep"), String::from("1")], &ExecOptions::default()).unwrap();
+        assert_eq!(job.read_timeout(1).unwrap(), (None, None));
+        assert!(job.kill().is_ok());
+    }
+
+    #[test]
+    fn test_process_write_terminated() {
+        let mut job: ShellProc = ShellProc::start_job(vec![String::from("true")], &ExecOptions::default()).unwrap();
+        sleep(Duration::from_millis(200));
+        //Writing to a process which has exited doesn't panic nor raise SIGPIPE
+        assert_eq!(job.write(String::from("hello\n")).err().unwrap(), ShellError::ProcessTerminated);
+        assert_eq!(job.state, ShellProcState::Terminated);
+        assert_eq!(job.cleanup().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_process_suspend_resume() {
+        let mut job: ShellProc = ShellProc::start_job(vec![String::from("cat")], &ExecOptions::default()).unwrap();
+        assert!(job.write(String::from("first\n")).is_ok());
+        sleep(Duration::from_millis(200));
+        assert_eq!(job.read().unwrap().0.unwrap(), String::from("first\n"));
+        //Suspended: input is not echoed
+        assert!(job.suspend().is_ok());
+        sleep(Duration::from_millis(200));
+        let stat: String = std::fs::read_to_string(format!("/proc/{}/stat", job.pid)).unwrap();
+        assert_eq!(stat[stat.rfind(')').unwrap() + 2..].chars().next(), Some('T'));
+        assert!(job.write(String::from("second\n")).is_ok());
+        assert_eq!(job.read_timeout(200).unwrap().0, None);
+        assert_ne!(job.update_state(), ShellProcState::Terminated);
+        //Resumed: the input written meanwhile is echoed, then it keeps echoing
+        assert!(job.resume().is_ok());
+        sleep(Duration::from_millis(200));
+        assert_eq!(job.read().unwrap().0.unwrap(), String::from("second\n"));
+        assert!(job.write(String::from("third\n")).is_ok());
+        sleep(Duration::from_millis(200));
+        assert_eq!(job.read().unwrap().0.unwrap(), String::from("third\n"));
+        assert!(job.kill().is_ok());
+    }
+
+    #[test]
+    fn test_process_start_with() {
+        //Working directory override
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let options: ExecOptions = ExecOptions {
+            wrkdir: Some(tmpdir.path().to_path_buf()),
+            ..ExecOptions::default()
+        };
+        let mut job: ShellProc = ShellProc::start_job(vec![String::from("pwd")], &options).unwrap();
+        assert_eq!(job.wrkdir, tmpdir.path().to_path_buf());
+        sleep(Duration::from_millis(200));
+        let (stdout, _) = job.read().unwrap();
+        assert_eq!(stdout.unwrap().trim(), tmpdir.path().canonicalize().unwrap().to_str().unwrap());
+        assert_eq!(job.update_state(), ShellProcState::Terminated);
+        assert_eq!(job.cleanup().unwrap(), 0);
+        //Extra environment variables
+        let options: ExecOptions = ExecOptions {
+            wrkdir: None,
+            env: vec![(String::from("PYC_EXEC_TEST"), String::from("привет")), (String::from("OLDPWD"), String::from("/tmp"))],
+            clear_env: false,
+            pty: false,
+        };
+        let mut job: ShellProc = ShellProc::start_job(vec![String::from("printenv"), String::from("PYC_EXEC_TEST"), String::from("OLDPWD"), String::from("HOME")], &options).unwrap();
+        sleep(Duration::from_millis(200));
+        let (stdout, _) = job.read().unwrap();
+        assert_eq!(stdout.unwrap(), format!("привет\n/tmp\n{}\n", std::env::var("HOME").unwrap()));
+        assert_eq!(job.update_state(), ShellProcState::Terminated);
+        assert_eq!(job.cleanup().unwrap(), 0);
+        //Cleared environment
+        let options: ExecOptions = ExecOptions {
+            wrkdir: None,
+            env: vec![(String::from("PYC_EXEC_TEST"), String::from("1"))],
+            clear_env: true,
+            pty: false,
+        };
+        let mut job: ShellProc = ShellProc::start_job(vec![String::from("printenv")], &options).unwrap();
+        sleep(Duration::from_millis(200));
+        let (stdout, _) = job.read().unwrap();
+        assert_eq!(stdout.unwrap(), String::from("PYC_EXEC_TEST=1\n"));
+        assert_eq!(job.cleanup().unwrap(), 0);
+        //Working directory which doesn't exist
+        let options: ExecOptions = ExecOptions {
+            wrkdir: Some(PathBuf::from("/nonexistent/dir")),
+            ..ExecOptions::default()
+        };
+        let mut job: ShellProc = ShellProc::start_job(vec![String::from("pwd")], &options).unwrap();
+        sleep(Duration::from_millis(200));
+        assert_eq!(job.update_state(), ShellProcState::Terminated);
+        assert_eq!(job.cleanup().unwrap(), 255);
+    }
+
+    #[test]
+    fn test_process_start_pty() {
+        let options: ExecOptions = ExecOptions {
+            pty: true,
+            ..ExecOptions::default()
+        };
+        let mut job: ShellProc = ShellProc::start_job(vec![String::from("sh"), String::from("-c"), String::from("tty; stty size; [ -t 2 ] || echo pipe >&2")], &options).unwrap();
+        assert!(job.set_window_size(132, 43));
+        sleep(Duration::from_millis(200));
+        let (stdout, stderr) = job.read().unwrap();
+        let stdout: String = stdout.unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert!(lines[0].starts_with("/dev/pts/"));
+        assert_eq!(lines[1], "43 132");
+        //stderr is not the pseudo-terminal
+        assert_eq!(stderr.unwrap(), String::from("pipe\n"));
+        assert_eq!(job.update_state(), ShellProcState::Terminated);
+        //Slave has been closed: nothing else to read
+        assert_eq!(job.read().unwrap(), (None, None));
+        assert_eq!(job.cleanup().unwrap(), 0);
+        //Without pseudo-terminal
+        let mut job: ShellProc = ShellProc::start_job(vec![String::from("tty")], &ExecOptions::default()).unwrap();
+        assert!(!job.set_window_size(132, 43));
+        sleep(Duration::from_millis(200));
+        let (stdout, _) = job.read().unwrap();
+        assert_eq!(stdout.unwrap(), String::from("not a tty\n"));
+        assert_eq!(job.cleanup().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_process_capture_output() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        assert_eq!(capture_output(&["pwd"], tmpdir.path(), Duration::from_secs(5)).unwrap().trim(), tmpdir.path().to_str().unwrap());
+        //Failure exit status
+        assert_eq!(capture_output(&["sh", "-c", "echo foo; exit 1"], tmpdir.path(), Duration::from_secs(5)).err().unwrap(), ShellError::InvalidData);
+        //Timeout
+        let started: Instant = Instant::now();
+        assert_eq!(capture_output(&["sleep", "5"], tmpdir.path(), Duration::from_millis(100)).err().unwrap(), ShellError::IoTimeout);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        //Command which doesn't exist
+        assert_eq!(capture_output(&["pyc-does-not-exist"], tmpdir.path(), Duration::from_secs(5)).err().unwrap(), ShellError::CouldNotStartProcess);
+        assert_eq!(capture_output(&[], tmpdir.path(), Duration::from_secs(5)).err().unwrap(), ShellError::CouldNotStartProcess);
+    }
+
 }
@@ -23,12 +23,46 @@
 *
 */
 
-extern crate git2;
+use super::modules::git::{HeadFingerprint, Repository};
 
-use git2::Repository;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 pub struct PromptCache {
-    git_cache: Option<Repository>,
+    git_heads: HashMap<PathBuf, GitHead>, //Branch and commit of the repository of each working directory
+    repositories: HashMap<PathBuf, DiscoveredRepository>, //Root of the repository of each working directory (None if there's no repository)
+    repository: Option<OpenRepository>, //Repository of the last working directory
+    hits: usize,   //Times the repository has been taken from the cache
+    misses: usize, //Times the repository has been discovered
+}
+
+/// ## GitHead
+///
+/// GitHead contains the branch and the commit of a repository and the state of HEAD they've been looked up with
+struct GitHead {
+    fingerprint: HeadFingerprint,
+    branch: Option<String>,
+    commit: Option<String>,
+}
+
+/// ## DiscoveredRepository
+///
+/// DiscoveredRepository contains the result of the repository discovery of a working directory and when it was made
+struct DiscoveredRepository {
+    root: Option<PathBuf>,
+    discovered: u64, //Seconds since the epoch
+}
+
+/// ## OpenRepository
+///
+/// OpenRepository contains the repository discovered for a working directory (None if there's no repository) and when it was discovered
+struct OpenRepository {
+    wrkdir: PathBuf,
+    repository: Option<Repository>,
+    discovered: Instant,
 }
 
 impl PromptCache {
@@ -36,32 +70,140 @@ impl PromptCache {
     ///
     /// Instantiate a new Prompt cache object
     pub fn new() -> PromptCache {
-        PromptCache { git_cache: None }
+        PromptCache {
+            git_heads: HashMap::new(),
+            repositories: HashMap::new(),
+            repository: None,
+            hits: 0,
+            misses: 0,
+        }
     }
 
-    /// ### invalidate
+    /// ### load
     ///
-    /// Invalidate cache
-    pub fn invalidate(&mut self) {
-        self.git_cache = None
+    /// Load the discovered repositories from the cache file; a cache file which can't be read is treated as empty.
+    /// Each line of the file contains the time of the discovery, the working directory and the root of its repository, separated by tabs
+    pub fn load(path: &Path) -> PromptCache {
+        let mut cache: PromptCache = PromptCache::new();
+        let content: String = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => return cache,
+        };
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if let [discovered, wrkdir, root] = fields.as_slice() {
+                if let Ok(discovered) = discovered.parse::<u64>() {
+                    let root: Option<PathBuf> = match root.is_empty() {
+                        true => None,
+                        false => Some(PathBuf::from(root)),
+                    };
+                    cache.repositories.insert(PathBuf::from(wrkdir), DiscoveredRepository { root, discovered });
+                }
+            }
+        }
+        cache
     }
 
-    /// ### cache_git
+    /// ### save
     ///
-    /// Cache git repository
-    pub fn cache_git(&mut self, git_repo: Repository) {
-        self.git_cache = Some(git_repo);
+    /// Write the discovered repositories to the cache file. Paths which can't be written on a line are not saved
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let writable = |path: &Path| path.to_str().map(|p| !p.contains('\t') && !p.contains('\n')).unwrap_or(false);
+        let mut lines: Vec<String> = self
+            .repositories
+            .iter()
+            .filter(|(wrkdir, entry)| writable(wrkdir.as_path()) && entry.root.as_ref().map(|root| writable(root.as_path())).unwrap_or(true))
+            .map(|(wrkdir, entry)| {
+                let root: String = entry.root.as_ref().map(|root| root.display().to_string()).unwrap_or_default();
+                format!("{}\t{}\t{}\n", entry.discovered, wrkdir.display(), root)
+            })
+            .collect();
+        lines.sort();
+        fs::write(path, lines.concat())
     }
 
-    /// ### get_git
+    /// ### get_repository_root
     ///
-    /// Get git repository
-    pub fn get_cached_git(&self) -> Option<&Repository> {
-        match self.git_cache.as_ref() {
-            Some(g) => Some(&g),
-            None => None,
+    /// Returns the root of the repository discovered for the working directory, if the discovery is not older than `ttl`.
+    /// The outer option is None if the discovery has to be made again
+    pub fn get_repository_root(&self, wrkdir: &Path, ttl: Duration) -> Option<Option<PathBuf>> {
+        let entry: &DiscoveredRepository = self.repositories.get(wrkdir)?;
+        match now().saturating_sub(entry.discovered) < ttl.as_secs() {
+            true => Some(entry.root.clone()),
+            false => None,
         }
     }
+
+    /// ### cache_repository_root
+    ///
+    /// Cache the root of the repository discovered for the working directory (None if there's no repository)
+    pub fn cache_repository_root(&mut self, wrkdir: &Path, root: Option<PathBuf>) {
+        self.repositories.insert(wrkdir.to_path_buf(), DiscoveredRepository { root, discovered: now() });
+    }
+
+    /// ### get_repository
+    ///
+    /// Returns the repository of the working directory. The repository is kept open while the working directory doesn't change;
+    /// it's discovered again with `discover` when the working directory changes or when the discovery is older than `ttl`
+    pub fn get_repository(&mut self, wrkdir: &Path, ttl: Duration, discover: &dyn Fn(&Path) -> Option<Repository>) -> Option<&Repository> {
+        match self.repository.as_ref() {
+            Some(entry) if entry.wrkdir == wrkdir && entry.discovered.elapsed() < ttl => self.hits += 1,
+            _ => {
+                self.misses += 1;
+                self.repository = Some(OpenRepository {
+                    wrkdir: wrkdir.to_path_buf(),
+                    repository: discover(wrkdir),
+                    discovered: Instant::now(),
+                });
+            }
+        }
+        self.repository.as_ref().and_then(|entry| entry.repository.as_ref())
+    }
+
+    /// ### hits
+    ///
+    /// Returns how many times the repository has been taken from the cache
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// ### misses
+    ///
+    /// Returns how many times the repository has been discovered
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// ### get_git_head
+    ///
+    /// Returns branch and commit cached for the working directory, if HEAD hasn't changed since they've been cached
+    pub fn get_git_head(&self, wrkdir: &Path, fingerprint: &HeadFingerprint) -> Option<(Option<String>, Option<String>)> {
+        match self.git_heads.get(wrkdir) {
+            Some(head) if head.fingerprint == *fingerprint => Some((head.branch.clone(), head.commit.clone())),
+            _ => None,
+        }
+    }
+
+    /// ### cache_git_head
+    ///
+    /// Cache branch and commit of the repository of the working directory, with the state of HEAD they've been looked up with
+    pub fn cache_git_head(&mut self, wrkdir: &Path, fingerprint: HeadFingerprint, head: (Option<String>, Option<String>)) {
+        self.git_heads.insert(
+            wrkdir.to_path_buf(),
+            GitHead {
+                fingerprint,
+                branch: head.0,
+                commit: head.1,
+            },
+        );
+    }
+}
+
+/// ### now
+///
+/// Returns the seconds elapsed since the epoch
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
 }
 
 #[cfg(test)]
@@ -69,19 +211,67 @@ mod tests {
 
     use super::*;
 
+    use super::super::modules::git::{find_repository, head_fingerprint};
+
+    #[test]
+    fn test_prompt_cache_git_heads() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(tmpdir.path().join(".git")).unwrap();
+        std::fs::write(tmpdir.path().join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        let fingerprint: HeadFingerprint = head_fingerprint(tmpdir.path()).unwrap();
+        let mut cache: PromptCache = PromptCache::new();
+        assert!(cache.get_git_head(tmpdir.path(), &fingerprint).is_none());
+        cache.cache_git_head(tmpdir.path(), fingerprint.clone(), (Some(String::from("main")), None));
+        assert_eq!(cache.get_git_head(tmpdir.path(), &fingerprint), Some((Some(String::from("main")), None)));
+        //Entries are kept by working directory
+        assert!(cache.get_git_head(Path::new("/tmp"), &fingerprint).is_none());
+        //HEAD has changed
+        std::fs::write(tmpdir.path().join(".git/HEAD"), "ref: refs/heads/feature\n").unwrap();
+        assert!(cache.get_git_head(tmpdir.path(), &head_fingerprint(tmpdir.path()).unwrap()).is_none());
+    }
+
     #[test]
-    fn test_prompt_cache() {
-        //Create temp directory
+    fn test_prompt_cache_open_repository() {
         let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
-        let git_repo: Repository = Repository::init(tmpdir.path()).unwrap();
+        let discover = |wrkdir: &Path| find_repository(wrkdir);
         let mut cache: PromptCache = PromptCache::new();
-        //Cache repository
-        cache.cache_git(git_repo);
-        //Verify git cache is Some
-        assert!(cache.get_cached_git().is_some());
-        //Invalidate cache
-        cache.invalidate();
-        //Verify git is None
-        assert!(cache.get_cached_git().is_none());
+        let ttl: Duration = Duration::from_secs(30);
+        //No repository
+        assert!(cache.get_repository(Path::new("/"), ttl, &discover).is_none());
+        assert!(cache.get_repository(Path::new("/"), ttl, &discover).is_none());
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+        //The working directory changed
+        assert!(cache.get_repository(Path::new("."), ttl, &discover).is_some());
+        assert!(cache.get_repository(Path::new("."), ttl, &discover).is_some());
+        assert_eq!((cache.hits(), cache.misses()), (2, 2));
+        //Expired entry
+        assert!(cache.get_repository(Path::new("."), Duration::from_secs(0), &discover).is_some());
+        assert_eq!((cache.hits(), cache.misses()), (2, 3));
+        assert!(cache.get_repository(tmpdir.path(), ttl, &discover).is_none());
+        assert_eq!((cache.hits(), cache.misses()), (2, 4));
+    }
+
+    #[test]
+    fn test_prompt_cache_repositories() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let cache_file: PathBuf = tmpdir.path().join("cache");
+        //Missing cache file
+        let mut cache: PromptCache = PromptCache::load(cache_file.as_path());
+        let ttl: Duration = Duration::from_secs(30);
+        assert!(cache.get_repository_root(Path::new("/home/omar/src/pyc"), ttl).is_none());
+        cache.cache_repository_root(Path::new("/home/omar/src/pyc"), Some(PathBuf::from("/home/omar/src/pyc")));
+        cache.cache_repository_root(Path::new("/tmp"), None);
+        cache.cache_repository_root(Path::new("/tmp/a\tb"), None);
+        assert!(cache.save(cache_file.as_path()).is_ok());
+        //Load saved cache
+        let cache: PromptCache = PromptCache::load(cache_file.as_path());
+        assert_eq!(cache.get_repository_root(Path::new("/home/omar/src/pyc"), ttl), Some(Some(PathBuf::from("/home/omar/src/pyc"))));
+        assert_eq!(cache.get_repository_root(Path::new("/tmp"), ttl), Some(None));
+        assert!(cache.get_repository_root(Path::new("/tmp/a\tb"), ttl).is_none());
+        //Expired entries
+        assert!(cache.get_repository_root(Path::new("/tmp"), Duration::from_secs(0)).is_none());
+        //Corrupted lines are ignored
+        std::fs::write(cache_file.as_path(), "garbage\nnot-a-number\t/tmp\t\n").unwrap();
+        assert!(PromptCache::load(cache_file.as_path()).get_repository_root(Path::new("/tmp"), ttl).is_none());
     }
 }
@@ -0,0 +1,162 @@
+//! ## Macros
+//!
+//! `macros` expands the user-defined prompt keys (`prompt.macros`) to their definition before the keys are resolved
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::modules;
+use super::{PROMPT_KEYS, PROMPT_KEY_REGEX, PROMPT_TOKEN_REGEX};
+
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+//Maximum amount of macros which can be nested in each other
+pub(crate) const MACRO_MAX_DEPTH: usize = 8;
+
+/// ### is_builtin_key
+///
+/// Returns whether the provided name (without `${` and `}`) is a key resolved by Pyc, which can't be redefined by a macro
+pub(crate) fn is_builtin_key(name: &str) -> bool {
+    let key: String = format!("${{{}}}", name);
+    PROMPT_KEYS.iter().any(|(builtin, _)| *builtin == key.as_str()) || key.starts_with(modules::colors::PROMPT_PALETTE_PREFIX)
+        || modules::colors::is_parameterized_key(key.as_str())
+}
+
+/// ### recursive_macros
+///
+/// Returns the names of the macros which (directly or not) reference themselves or which are nested
+/// more than `MACRO_MAX_DEPTH` times. These macros render empty
+pub(crate) fn recursive_macros(macros: &HashMap<String, String>) -> Vec<String> {
+    let mut names: Vec<String> = macros
+        .keys()
+        .filter(|name| is_recursive(name.as_str(), macros, &mut Vec::new()))
+        .cloned()
+        .collect();
+    names.sort();
+    names
+}
+
+/// ### expand_macros
+///
+/// Replace the macros in the prompt line with their definition, expanding the macros they contain too.
+/// Keys which are not macros are kept, so that they can be resolved later
+pub(crate) fn expand_macros(prompt_line: &str, macros: &HashMap<String, String>) -> String {
+    if macros.is_empty() {
+        return String::from(prompt_line);
+    }
+    let recursive: Vec<String> = recursive_macros(macros);
+    expand(prompt_line, macros, &recursive)
+}
+
+/// ### expand
+///
+/// Expand the macros of `text`; recursive macros are replaced with an empty string. Escaped keys (`$${NAME}`) are kept
+fn expand(text: &str, macros: &HashMap<String, String>, recursive: &[String]) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(PROMPT_TOKEN_REGEX).unwrap();
+    }
+    RE.replace_all(text, |caps: &Captures| match caps.get(1).and_then(|name| macros.get(name.as_str())) {
+        None => String::from(&caps[0]),
+        Some(_) if recursive.iter().any(|name| name == &caps[1]) => String::new(),
+        Some(definition) => expand(definition, macros, recursive),
+    })
+    .to_string()
+}
+
+/// ### is_recursive
+///
+/// Returns whether expanding the first macro of the `stack` ends up in itself or goes deeper than `MACRO_MAX_DEPTH`.
+/// Cycles which don't contain the first macro are reported for the macros in the cycle only
+fn is_recursive(name: &str, macros: &HashMap<String, String>, stack: &mut Vec<String>) -> bool {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(PROMPT_KEY_REGEX).unwrap();
+    }
+    if stack.first().map(|first| first == name).unwrap_or(false) || stack.len() >= MACRO_MAX_DEPTH {
+        return true;
+    }
+    if stack.iter().any(|n| n == name) {
+        return false;
+    }
+    let definition: &String = match macros.get(name) {
+        Some(definition) => definition,
+        None => return false,
+    };
+    stack.push(String::from(name));
+    let recursive: bool = RE
+        .captures_iter(definition)
+        .any(|caps| macros.contains_key(&caps[1]) && is_recursive(&caps[1], macros, stack));
+    stack.pop();
+    recursive
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn macros(definitions: &[(&str, &str)]) -> HashMap<String, String> {
+        definitions.iter().map(|(name, definition)| (String::from(*name), String::from(*definition))).collect()
+    }
+
+    #[test]
+    fn test_prompt_macros_expand() {
+        let defs: HashMap<String, String> = macros(&[("GITLINE", "${KMAG}${GIT_BRANCH}${KRST}@${GIT_COMMIT}")]);
+        assert_eq!(
+            expand_macros("${USER} ${GITLINE} $", &defs),
+            String::from("${USER} ${KMAG}${GIT_BRANCH}${KRST}@${GIT_COMMIT} $")
+        );
+        //Unknown keys are kept
+        assert_eq!(expand_macros("${FOO}", &defs), String::from("${FOO}"));
+        assert_eq!(expand_macros("${GITLINE}", &HashMap::new()), String::from("${GITLINE}"));
+    }
+
+    #[test]
+    fn test_prompt_macros_nested() {
+        let defs: HashMap<String, String> = macros(&[("WHO", "${USER}@${HOSTNAME}"), ("WHERE", "${WHO}:${WRKDIR}"), ("LINE", "[${WHERE}] ${WHO}")]);
+        assert_eq!(expand_macros("${LINE}$", &defs), String::from("[${USER}@${HOSTNAME}:${WRKDIR}] ${USER}@${HOSTNAME}$"));
+        assert!(recursive_macros(&defs).is_empty());
+        //Too deep
+        let deep: Vec<(String, String)> = (0..=MACRO_MAX_DEPTH).map(|i| (format!("M{}", i), format!("${{M{}}}", i + 1))).collect();
+        let deep: HashMap<String, String> = deep.into_iter().collect();
+        assert!(recursive_macros(&deep).contains(&String::from("M0")));
+        assert_eq!(expand_macros("a${M0}b", &deep), String::from("ab"));
+    }
+
+    #[test]
+    fn test_prompt_macros_cycles() {
+        let defs: HashMap<String, String> = macros(&[("A", "a${B}"), ("B", "b${A}"), ("SELF", "${SELF}!"), ("OK", "${USER}"), ("USES", "${A}${OK}")]);
+        assert_eq!(recursive_macros(&defs), vec![String::from("A"), String::from("B"), String::from("SELF")]);
+        //Recursive macros render empty
+        assert_eq!(expand_macros("${A}|${SELF}|${OK}|${USES}", &defs), String::from("||${USER}|${USER}"));
+    }
+
+    #[test]
+    fn test_prompt_macros_builtin_keys() {
+        assert!(is_builtin_key("USER"));
+        assert!(is_builtin_key("GIT_BRANCH"));
+        assert!(is_builtin_key("KRST"));
+        assert!(is_builtin_key("KSELECT"));
+        assert!(is_builtin_key("VENV"));
+        assert!(is_builtin_key("C:primary"));
+        assert!(!is_builtin_key("GITLINE"));
+    }
+}
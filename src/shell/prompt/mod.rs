@@ -26,36 +26,116 @@
 extern crate regex;
 
 mod cache;
+mod macros;
 mod modules;
+mod registry;
+mod status;
 
+use super::jobs::JobsSummary;
 use super::ShellProps;
 use crate::config::PromptConfig;
+use crate::translator::ansi::{self, Segment};
 use crate::translator::ioprocessor::IOProcessor;
-use cache::PromptCache;
+use crate::translator::messages;
+use crate::utils::{console, terminal};
+pub(crate) use cache::PromptCache;
+pub(crate) use modules::time::LocalTime;
+use modules::wrkdir::WrkdirOptions;
 use modules::*;
+use registry::ModuleRegistry;
+
+pub(crate) use macros::{expand_macros, is_builtin_key, recursive_macros, MACRO_MAX_DEPTH};
+pub(crate) use modules::colors::{palette_references, Palette, PromptColor};
+pub(crate) use status::{resolve_status, StatusReport, DEFAULT_TEMPLATE};
 
 use regex::Regex;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
 const PROMPT_KEY_REGEX: &str = r"\$\{(.*?)\}";
+//Keys and escaped `${` (`$${`), which is not part of a key
+const PROMPT_TOKEN_REGEX: &str = r"\$\$\{|\$\{(.*?)\}";
+const ANSI_ESCAPE_REGEX: &str = "\x1b\\[[0-9;]*[A-Za-z]";
 //Prompt standard keys
 const PROMPT_USER: &str = "${USER}";
 const PROMPT_HOSTNAME: &str = "${HOSTNAME}";
 const PROMPT_WRKDIR: &str = "${WRKDIR}";
 const PROMPT_CMDTIME: &str = "${CMD_TIME}";
 const PROMPT_RC: &str = "${RC}";
+const PROMPT_RC_CODE: &str = "${RC_CODE}";
+const PROMPT_CHAR: &str = "${PROMPT_CHAR}";
+const PROMPT_DEGRADED: &str = "${DEGRADED}";
+const PROMPT_JOBS: &str = "${JOBS}";
+const PROMPT_NEWLINE: &str = "${NEWLINE}";
+
+/// Keys resolved by Pyc, with their description; they can't be redefined by a macro and are listed by `help`.
+/// Colors of the palette (`${C:name}`) are resolved too
+pub(crate) const PROMPT_KEYS: [(&str, &str); 35] = [
+    (PROMPT_USER, "Username"),
+    (PROMPT_HOSTNAME, "Hostname"),
+    (PROMPT_WRKDIR, "Current directory"),
+    (modules::language::PROMPT_LANG, "Language configured for Pyc"),
+    (PROMPT_CMDTIME, "Execution time of the last command"),
+    (PROMPT_RC, "Result of the last command"),
+    (PROMPT_RC_CODE, "Exit code of the last command"),
+    (PROMPT_CHAR, "`#` for root, `$` for the other users"),
+    (PROMPT_DEGRADED, "Features disabled at startup"),
+    (PROMPT_JOBS, "Amount of jobs in background"),
+    (PROMPT_NEWLINE, "Line break"),
+    (modules::time::PROMPT_TIME, "Current local time"),
+    (modules::time::PROMPT_DATE, "Current local date"),
+    (modules::venv::PROMPT_VENV, "Active Python virtualenv"),
+    (modules::battery::PROMPT_BATTERY, "Charge of the battery"),
+    (modules::git::PROMPT_GIT_BRANCH, "Current git branch"),
+    (modules::git::PROMPT_GIT_COMMIT, "Current git commit"),
+    (modules::git::PROMPT_GIT_STATUS, "Modified and staged files"),
+    (modules::colors::PROMPT_KYEL, "Yellow"),
+    (modules::colors::PROMPT_KRED, "Red"),
+    (modules::colors::PROMPT_KBLU, "Blue"),
+    (modules::colors::PROMPT_KMAG, "Magenta"),
+    (modules::colors::PROMPT_KGRN, "Green"),
+    (modules::colors::PROMPT_KWHT, "White"),
+    (modules::colors::PROMPT_KBLK, "Black"),
+    (modules::colors::PROMPT_KGRY, "Gray"),
+    (modules::colors::PROMPT_KBOLD, "Bold text"),
+    (modules::colors::PROMPT_KBLINK, "Blinking text"),
+    (modules::colors::PROMPT_KSELECT, "Selected text"),
+    (modules::colors::PROMPT_KRST, "Reset colors"),
+    ("${K256:n}", "Color `n` (0-255) of the 256 colors table"),
+    ("${KRGB:rrggbb}", "Truecolor with the provided hex value"),
+    ("${B256:n}", "Background color `n` (0-255) of the 256 colors table"),
+    ("${BRGB:rrggbb}", "Truecolor background with the provided hex value"),
+    ("${C:name}", "Style of `name` in the palette"),
+];
+
+#[cfg(test)]
+thread_local! {
+    /// Number of prompts instantiated by the current thread
+    pub(crate) static PROMPTS_BUILT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
 
 /// ## ShellPrompt
 ///
 /// ShellPrompt is the struct which contains the current shell prompt configuration
 pub struct ShellPrompt {
     prompt_line: String,
+    prompt_line_right: String, //Segment aligned to the right edge of the terminal
     translate: bool,
+    keep_trailing_space: bool, //Whether the whitespace at the end of the line is kept
     break_opt: Option<BreakOptions>,
     duration_opt: Option<DurationOptions>,
     rc_opt: Option<RcOptions>,
-    git_opt: Option<GitOptions>,
-    cache: PromptCache,
+    prompt_char_opt: Option<PromptCharOptions>,
+    jobs_opt: Option<JobsOptions>,
+    wrkdir_opt: WrkdirOptions,
+    palette: Palette,
+    registry: ModuleRegistry,
+    refresh_modules: bool, //Whether async modules have to be resolved again while rendering
+    time_format: String,
+    date_format: String,
+    clock: fn() -> LocalTime, //Returns the current time (can be replaced in tests)
+    columns: fn() -> usize,   //Returns the width of the terminal (can be replaced in tests)
 }
 
 /// ## ShellPrompt
@@ -70,6 +150,24 @@ struct BreakOptions {
 /// DurationOptions is the struct which contains the current duration configuration
 struct DurationOptions {
     pub minimum: Duration,
+    pub format: Option<String>,
+    pub prefix: Option<String>,
+}
+
+/// ## PromptCharOptions
+///
+/// PromptCharOptions contains the symbols rendered by `PROMPT_CHAR`, depending on the effective user
+struct PromptCharOptions {
+    pub user: String,
+    pub root: String,
+}
+
+/// ## JobsOptions
+///
+/// JobsOptions contains the symbols rendered by `JOBS` before the amount of jobs, depending on whether a job is suspended
+struct JobsOptions {
+    pub running: String,
+    pub stopped: String,
 }
 
 /// ## RcOptions
@@ -78,16 +176,7 @@ struct DurationOptions {
 struct RcOptions {
     pub ok: String,
     pub err: String,
-}
-
-/// ## GitOptions
-///
-/// GitOptions is the struct which contains the current git module configuration
-struct GitOptions {
-    pub branch: String,
-    pub commit_ref_len: usize,
-    pub commit_ref_prepend: Option<String>,
-    pub commit_ref_append: Option<String>
+    pub code_always: bool,
 }
 
 impl ShellPrompt {
@@ -95,81 +184,206 @@ impl ShellPrompt {
     ///
     /// Instantiate a new ShellPrompt with the provided parameters
     pub(super) fn new(prompt_opt: &PromptConfig) -> ShellPrompt {
+        #[cfg(test)]
+        PROMPTS_BUILT.with(|count| count.set(count.get() + 1));
+        //Macros are expanded before anything else, so the keys they contain enable the options too
+        let prompt_line: String = expand_macros(&prompt_opt.prompt_line, &prompt_opt.prompt_macros);
+        let prompt_line_right: String = expand_macros(&prompt_opt.prompt_line_right, &prompt_opt.prompt_macros);
+        //Keys of both lines enable the options
+        let keys: String = format!("{} {}", prompt_line, prompt_line_right);
         let break_opt: Option<BreakOptions> = match prompt_opt.break_enabled {
             true => Some(BreakOptions::new(&prompt_opt.break_str)),
             false => None,
         };
         let duration_opt: Option<DurationOptions> =
-            match DurationOptions::should_enable(&prompt_opt.prompt_line) {
-                true => Some(DurationOptions::new(prompt_opt.min_duration)),
+            match DurationOptions::should_enable(&keys) {
+                true => Some(DurationOptions::new(prompt_opt.min_duration, &prompt_opt.duration_format, &prompt_opt.duration_prefix)),
                 false => None,
             };
-        let rc_opt: Option<RcOptions> = match RcOptions::should_enable(&prompt_opt.prompt_line) {
-            true => Some(RcOptions::new(&prompt_opt.rc_ok, &prompt_opt.rc_err)),
+        let palette: Palette = Palette::new(&prompt_opt.palette);
+        let rc_opt: Option<RcOptions> = match RcOptions::should_enable(&keys) {
+            true => Some(RcOptions::new(
+                &colors::expand_colors(&palette.expand(&expand_macros(&prompt_opt.rc_ok, &prompt_opt.prompt_macros))),
+                &colors::expand_colors(&palette.expand(&expand_macros(&prompt_opt.rc_err, &prompt_opt.prompt_macros))),
+                prompt_opt.rc_code_always,
+            )),
             false => None,
         };
-        let git_opt: Option<GitOptions> = match GitOptions::should_enable(&prompt_opt.prompt_line) {
-            true => Some(GitOptions::new(
-                &prompt_opt.git_branch,
-                prompt_opt.git_commit_ref,
-                &prompt_opt.git_commit_prepend,
-                &prompt_opt.git_commit_append
+        let prompt_char_opt: Option<PromptCharOptions> = match PromptCharOptions::should_enable(&keys) {
+            true => Some(PromptCharOptions::new(
+                &colors::expand_colors(&palette.expand(&expand_macros(&prompt_opt.prompt_char_user, &prompt_opt.prompt_macros))),
+                &colors::expand_colors(&palette.expand(&expand_macros(&prompt_opt.prompt_char_root, &prompt_opt.prompt_macros))),
+            )),
+            false => None,
+        };
+        let jobs_opt: Option<JobsOptions> = match JobsOptions::should_enable(&keys) {
+            true => Some(JobsOptions::new(
+                &colors::expand_colors(&palette.expand(&expand_macros(&prompt_opt.jobs_running, &prompt_opt.prompt_macros))),
+                &colors::expand_colors(&palette.expand(&expand_macros(&prompt_opt.jobs_stopped, &prompt_opt.prompt_macros))),
             )),
             false => None,
         };
+        let mut registry: ModuleRegistry = ModuleRegistry::new();
+        registry.set_wait(Duration::from_millis(prompt_opt.module_timeout as u64));
+        if git::GitModule::should_enable(&keys) {
+            registry.register(Arc::new(git::GitModule::new(
+                &prompt_opt.git_branch,
+                prompt_opt.git_commit_ref,
+                &prompt_opt.git_commit_prepend,
+                &prompt_opt.git_commit_append,
+                &prompt_opt.git_dirty,
+                &prompt_opt.git_staged,
+                Duration::from_secs(prompt_opt.git_cache_ttl as u64),
+            )));
+        }
+        if venv::VenvModule::should_enable(&keys) {
+            registry.register(Arc::new(venv::VenvModule::new(&prompt_opt.venv_prefix)));
+        }
+        if battery::BatteryModule::should_enable(&keys) {
+            registry.register(Arc::new(battery::BatteryModule::new(
+                &prompt_opt.battery_charging,
+                &prompt_opt.battery_discharging,
+                prompt_opt.battery_low_threshold,
+            )));
+        }
         ShellPrompt {
-            prompt_line: prompt_opt.prompt_line.clone(),
+            prompt_line,
+            prompt_line_right,
             translate: prompt_opt.translate,
+            keep_trailing_space: prompt_opt.keep_trailing_space,
             break_opt: break_opt,
             duration_opt: duration_opt,
             rc_opt: rc_opt,
-            git_opt: git_opt,
-            cache: PromptCache::new(),
+            prompt_char_opt,
+            jobs_opt,
+            wrkdir_opt: WrkdirOptions::new(prompt_opt.wrkdir_max_depth, prompt_opt.wrkdir_truncate_repo),
+            palette,
+            registry,
+            refresh_modules: true,
+            time_format: prompt_opt.time_format.clone(),
+            date_format: prompt_opt.date_format.clone(),
+            clock: LocalTime::now,
+            columns: || terminal::size().columns,
         }
     }
 
+    /// ### reconfigure
+    ///
+    /// Replace the options derived from the prompt configuration (e.g. after the configuration has been reloaded).
+    /// The new options are built before replacing the current ones, so the prompt is never rendered with a partial configuration;
+    /// the clock and the terminal width are kept
+    pub(super) fn reconfigure(&mut self, prompt_opt: &PromptConfig) {
+        let mut prompt: ShellPrompt = ShellPrompt::new(prompt_opt);
+        prompt.clock = self.clock;
+        prompt.columns = self.columns;
+        *self = prompt;
+    }
+
     /// ### get_line
     ///
     /// get prompt line with resolved values
     pub(super) fn get_line(&mut self, shell_props: &ShellProps, processor: &IOProcessor) -> String {
         let mut prompt_line: String = self.process_prompt(shell_props, processor);
+        let mut right: String = self.process_prompt_right(shell_props, processor);
         //Translate prompt if necessary
         if self.translate {
             prompt_line = processor.text_to_cyrillic(&prompt_line);
+            right = processor.text_to_cyrillic(&right);
         }
         //Write prompt
+        align_right(prompt_line, right.as_str(), (self.columns)())
+    }
+
+    /// ### redraw_line
+    ///
+    /// get prompt line with the values of the async modules resolved after the last `get_line`.
+    /// Async modules are not resolved again
+    pub(super) fn redraw_line(&mut self, shell_props: &ShellProps, processor: &IOProcessor) -> String {
+        self.refresh_modules = false;
+        let prompt_line: String = self.get_line(shell_props, processor);
+        self.refresh_modules = true;
         prompt_line
     }
 
+    /// ### poll_modules
+    ///
+    /// Collect the values of the async modules. Returns whether the prompt has to be redrawn
+    pub(super) fn poll_modules(&mut self) -> bool {
+        self.registry.poll()
+    }
+
+    /// ### register_module
+    ///
+    /// Register a prompt module, which will resolve the keys it handles
+    #[cfg(test)]
+    pub(crate) fn register_module(&mut self, module: Arc<dyn PromptModule>) {
+        self.registry.register(module);
+    }
+
     /// ### process_prompt
     ///
     /// Process prompt keys and resolve prompt line
     /// Returns the processed prompt line
-    /// This function is optimized to try to cache the previous values
+    /// Async modules are waited for at most `module_timeout` in total
     fn process_prompt(&mut self, shell_props: &ShellProps, processor: &IOProcessor) -> String {
-        let mut prompt_line: String = self.prompt_line.clone();
-        //Iterate over keys through regex ```\${(.*?)}```
-        lazy_static! {
-            static ref RE: Regex = Regex::new(PROMPT_KEY_REGEX).unwrap();
+        if self.refresh_modules {
+            self.registry.begin();
         }
-        for regex_match in RE.captures_iter(prompt_line.clone().as_str()) {
-            let mtch: String = String::from(&regex_match[0]);
-            let replace_with: String = self.resolve_key(shell_props, processor, &mtch);
-            prompt_line = prompt_line.replace(mtch.as_str(), replace_with.as_str());
-        }
-        //Trim prompt line
-        prompt_line = String::from(prompt_line.trim());
+        let mut prompt_line: String = self.resolve_line(self.prompt_line.clone(), shell_props, processor);
         //If break, break line
         if let Some(brkopt) = &self.break_opt {
             prompt_line += "\n";
             prompt_line += brkopt.break_with.trim();
         }
-        //Invalidate cache
-        self.cache.invalidate();
         //Return prompt line
         prompt_line
     }
 
+    /// ### process_prompt_right
+    ///
+    /// Process the keys of the right segment; it must be called after `process_prompt`, which starts waiting for async modules
+    fn process_prompt_right(&mut self, shell_props: &ShellProps, processor: &IOProcessor) -> String {
+        match self.prompt_line_right.is_empty() {
+            true => String::new(),
+            false => self.resolve_line(self.prompt_line_right.clone(), shell_props, processor),
+        }
+    }
+
+    /// ### resolve_line
+    ///
+    /// Replace the keys in the line with their values and trim it; `$${` is written as a literal `${`.
+    /// The whitespace at the end of the line is kept if `keep_trailing_space` is set
+    fn resolve_line(&mut self, line: String, shell_props: &ShellProps, processor: &IOProcessor) -> String {
+        //Iterate over keys through regex ```\$\${|\${(.*?)}```
+        lazy_static! {
+            static ref RE: Regex = Regex::new(PROMPT_TOKEN_REGEX).unwrap();
+        }
+        //Each key is resolved once, even if it occurs more times
+        let mut values: HashMap<String, String> = HashMap::new();
+        let mut resolved: String = String::with_capacity(line.len());
+        let mut last: usize = 0;
+        for regex_match in RE.captures_iter(line.as_str()) {
+            let mtch: regex::Match = regex_match.get(0).unwrap();
+            resolved.push_str(&line[last..mtch.start()]);
+            last = mtch.end();
+            if regex_match.get(1).is_none() {
+                resolved.push_str("${");
+                continue;
+            }
+            let key: String = String::from(mtch.as_str());
+            if !values.contains_key(&key) {
+                let value: String = self.resolve_key(shell_props, processor, &key);
+                values.insert(key.clone(), value);
+            }
+            resolved.push_str(values[&key].as_str());
+        }
+        resolved.push_str(&line[last..]);
+        match self.keep_trailing_space {
+            true => String::from(resolved.trim_start()),
+            false => String::from(resolved.trim()),
+        }
+    }
+
     /// ### resolve_key
     ///
     /// Replace the provided key with the resolved value
@@ -183,10 +397,21 @@ impl ShellPrompt {
             PROMPT_CMDTIME => {
                 match &self.duration_opt {
                     Some(opt) => {
-                        if shell_props.elapsed_time.as_millis() >= opt.minimum.as_millis() {
-                            let millis: u128 = shell_props.elapsed_time.as_millis();
-                            let secs: f64 = (millis as f64 / 1000 as f64) as f64;
-                            String::from(format!("took {:.1}s", secs))
+                        if shell_props.elapsed_time >= opt.minimum {
+                            //Use the format of the language, unless a custom format is configured
+                            match &opt.format {
+                                Some(format) => {
+                                    let millis: u128 = shell_props.elapsed_time.as_millis();
+                                    format
+                                        .replace("{secs}", messages::format_decimal(processor.language, shell_props.elapsed_time.as_secs_f64(), 1).as_str())
+                                        .replace("{millis}", millis.to_string().as_str())
+                                }
+                                None => format!(
+                                    "{}{}",
+                                    opt.prefix.as_deref().unwrap_or_else(|| messages::duration_prefix(processor.language)),
+                                    messages::format_duration(processor.language, shell_props.elapsed_time)
+                                ),
+                            }
                         } else {
                             String::from("")
                         }
@@ -194,63 +419,22 @@ impl ShellPrompt {
                     None => String::from(""),
                 }
             }
-            modules::git::PROMPT_GIT_BRANCH => {
-                if self.git_opt.is_none() {
-                    return String::from("");
-                }
-                //If repository is not cached, find repository
-                if self.cache.get_cached_git().is_none() {
-                    let repo_opt = git::find_repository(&shell_props.wrkdir);
-                    match repo_opt {
-                        Some(repo) => self.cache.cache_git(repo),
-                        None => return String::from(""),
-                    };
-                }
-                //Get branch (unwrap without fear; can't be None here)
-                let branch: String = match git::get_branch(self.cache.get_cached_git().unwrap()) {
-                    Some(branch) => branch,
-                    None => return String::from(""),
-                };
-                //Format branch
-                String::from(format!(
-                    "{}{}",
-                    self.git_opt.as_ref().unwrap().branch.clone(),
-                    branch
-                ))
-            }
-            modules::git::PROMPT_GIT_COMMIT => {
-                if self.git_opt.is_none() {
-                    return String::from("");
-                }
-                //If repository is not cached, find repository
-                if self.cache.get_cached_git().is_none() {
-                    let repo_opt = git::find_repository(&shell_props.wrkdir);
-                    match repo_opt {
-                        Some(repo) => self.cache.cache_git(repo),
-                        None => return String::from(""),
-                    };
-                }
-                //Get commit (unwrap without fear; can't be None here)
-                match git::get_commit(
-                    self.cache.get_cached_git().unwrap(),
-                    self.git_opt.as_ref().unwrap().commit_ref_len,
-                ) {
-                    Some(commit) => {
-                        // Format commit
-                        let commit_prepend: String = match &self.git_opt.as_ref().unwrap().commit_ref_prepend {
-                            Some(s) => s.clone(),
-                            None => String::from("")
-                        };
-                        let commit_append: String = match &self.git_opt.as_ref().unwrap().commit_ref_append {
-                            Some(s) => s.clone(),
-                            None => String::from("")
-                        };
-                        format!("{}{}{}", commit_prepend, commit, commit_append)
-                    },
-                    None => String::from(""),
-                }
-            }
+            modules::time::PROMPT_DATE => (self.clock)().format(self.date_format.as_str(), processor.language),
+            modules::time::PROMPT_TIME => (self.clock)().format(self.time_format.as_str(), processor.language),
+            PROMPT_CHAR => match &self.prompt_char_opt {
+                Some(opt) => match shell_props.uid {
+                    0 => opt.root.clone(),
+                    _ => opt.user.clone(),
+                },
+                None => String::from(""),
+            },
+            PROMPT_DEGRADED => shell_props.degraded.join(","),
             PROMPT_HOSTNAME => shell_props.hostname.clone(),
+            PROMPT_JOBS => match &self.jobs_opt {
+                Some(opt) => opt.resolve(shell_props.jobs),
+                None => String::from(""),
+            },
+            PROMPT_NEWLINE => String::from("\n"),
             modules::colors::PROMPT_KBLINK | modules::colors::PROMPT_KBLK | modules::colors::PROMPT_KBLU | modules::colors::PROMPT_KBOLD | modules::colors::PROMPT_KCYN | modules::colors::PROMPT_KGRN | modules::colors::PROMPT_KGRY | modules::colors::PROMPT_KMAG | modules::colors::PROMPT_KRED | modules::colors::PROMPT_KRST | modules::colors::PROMPT_KSELECT | modules::colors::PROMPT_KWHT | modules::colors::PROMPT_KYEL => colors::PromptColor::from_key(key.as_str()).to_string(),
             modules::language::PROMPT_LANG => language::language_to_str(processor.language),
             PROMPT_RC => match &self.rc_opt {
@@ -260,9 +444,25 @@ impl ShellPrompt {
                 },
                 None => String::from(""),
             },
+            PROMPT_RC_CODE => match &self.rc_opt {
+                Some(opt) if opt.code_always || shell_props.exit_status != 0 => shell_props.exit_status.to_string(),
+                _ => String::from(""),
+            },
+            key if colors::is_parameterized_key(key) => colors::parameterized_color(key),
+            key if key.starts_with(modules::colors::PROMPT_PALETTE_PREFIX) => {
+                self.palette.resolve(&key[modules::colors::PROMPT_PALETTE_PREFIX.len()..key.len() - 1])
+            }
             PROMPT_USER => shell_props.username.clone(),
-            PROMPT_WRKDIR => shell_props.wrkdir.as_path().display().to_string(),
-            _ => key.clone(), //Keep unresolved keys
+            PROMPT_WRKDIR => self.wrkdir_opt.resolve(shell_props.wrkdir.as_path()),
+            _ => {
+                let ctx: ModuleContext = ModuleContext {
+                    wrkdir: shell_props.wrkdir.clone(),
+                };
+                match self.registry.resolve(key.as_str(), &ctx, self.refresh_modules) {
+                    Some(value) => value,
+                    None => key.clone(), //Keep unresolved keys
+                }
+            }
         }
     }
 }
@@ -289,53 +489,145 @@ impl DurationOptions {
     /// ### new
     ///
     /// Instantiate a new DurationOptions with the provided parameters
-    pub fn new(min_duration: usize) -> DurationOptions {
+    pub fn new(min_duration: usize, format: &Option<String>, prefix: &Option<String>) -> DurationOptions {
         DurationOptions {
             minimum: Duration::from_millis(min_duration as u64),
+            format: format.clone(),
+            prefix: prefix.clone(),
         }
     }
 }
 
-impl RcOptions {
+impl PromptCharOptions {
     /// ### should_enable
     ///
-    /// helper which says if rc module should be enabled
-    pub fn should_enable(prompt_line: &String) -> bool {
-        prompt_line.contains(PROMPT_RC)
+    /// helper which says if the prompt char module should be enabled
+    pub fn should_enable(prompt_line: &str) -> bool {
+        prompt_line.contains(PROMPT_CHAR)
     }
 
     /// ### new
     ///
-    /// Instantiate a new RcOptions with the provided parameters
-    pub fn new(ok_str: &String, err_str: &String) -> RcOptions {
-        RcOptions {
-            ok: ok_str.clone(),
-            err: err_str.clone(),
+    /// Instantiate a new PromptCharOptions with the provided parameters
+    pub fn new(user: &str, root: &str) -> PromptCharOptions {
+        PromptCharOptions {
+            user: user.to_string(),
+            root: root.to_string(),
+        }
+    }
+}
+
+impl JobsOptions {
+    /// ### should_enable
+    ///
+    /// helper which says if the jobs module should be enabled
+    pub fn should_enable(prompt_line: &str) -> bool {
+        prompt_line.contains(PROMPT_JOBS)
+    }
+
+    /// ### new
+    ///
+    /// Instantiate a new JobsOptions with the provided parameters
+    pub fn new(running: &str, stopped: &str) -> JobsOptions {
+        JobsOptions {
+            running: running.to_string(),
+            stopped: stopped.to_string(),
+        }
+    }
+
+    /// ### resolve
+    ///
+    /// Returns the symbol followed by the amount of jobs (e.g. `⚙[2]`); the stopped symbol is used if at least one job is suspended.
+    /// Nothing is rendered if there are no jobs
+    pub fn resolve(&self, jobs: JobsSummary) -> String {
+        let symbol: &String = match jobs.stopped {
+            0 => &self.running,
+            _ => &self.stopped,
+        };
+        match jobs.running + jobs.stopped {
+            0 => String::from(""),
+            count => format!("{}[{}]", symbol, count),
         }
     }
 }
 
-impl GitOptions {
+impl RcOptions {
     /// ### should_enable
     ///
-    /// helper which says if git module should be enabled
+    /// helper which says if rc module should be enabled
     pub fn should_enable(prompt_line: &String) -> bool {
-        prompt_line.contains(modules::git::PROMPT_GIT_BRANCH) || prompt_line.contains(modules::git::PROMPT_GIT_COMMIT)
+        prompt_line.contains(PROMPT_RC) || prompt_line.contains(PROMPT_RC_CODE)
     }
 
     /// ### new
     ///
-    /// Instantiate a new GitOptions with the provided parameters
-    pub fn new(branch: &String, commit: usize, commit_prepend: &Option<String>, commit_append: &Option<String>) -> GitOptions {
-        GitOptions {
-            branch: branch.clone(),
-            commit_ref_len: commit,
-            commit_ref_prepend: commit_prepend.clone(),
-            commit_ref_append: commit_append.clone()
+    /// Instantiate a new RcOptions with the provided parameters
+    pub fn new(ok_str: &str, err_str: &str, code_always: bool) -> RcOptions {
+        RcOptions {
+            ok: ok_str.to_string(),
+            err: err_str.to_string(),
+            code_always,
+        }
+    }
+}
+
+/// ### align_right
+///
+/// Add the right segment to the prompt line, padded so that it ends at the last column of the terminal.
+/// Escape sequences don't count toward the width. If the prompt has more lines, the segment is placed at the end of the line
+/// before the last one; otherwise it's printed first and the cursor goes back to the beginning of the line before the prompt.
+/// The segment is dropped if it doesn't fit next to the prompt
+fn align_right(prompt_line: String, right: &str, columns: usize) -> String {
+    if right.is_empty() {
+        return prompt_line;
+    }
+    let right_width: usize = visible_width(right);
+    match prompt_line.rfind('\n') {
+        Some(last) => {
+            let (head, tail) = prompt_line.split_at(last);
+            let width: usize = visible_width(&head[head.rfind('\n').map(|i| i + 1).unwrap_or(0)..]);
+            match width + right_width < columns {
+                true => format!("{}{}{}{}", head, " ".repeat(columns - width - right_width), right, tail),
+                false => prompt_line,
+            }
         }
+        None => match visible_width(prompt_line.as_str()) + right_width < columns {
+            true => format!("{}{}\r{}", " ".repeat(columns - right_width), right, prompt_line),
+            false => prompt_line,
+        },
     }
 }
 
+/// ### visible_width
+///
+/// Returns the amount of columns used by the text on the terminal, without escape sequences
+fn visible_width(text: &str) -> usize {
+    ansi::segments(text)
+        .iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => console::str_width(text),
+            Segment::Escape(_) => 0,
+        })
+        .sum()
+}
+
+/// ### readline_escapes
+///
+/// Prepare a prompt line to be set as bash's `PS1`: escape sequences are wrapped in `\[ \]`, so that readline doesn't count them
+/// as printed characters, backslashes are escaped and the line break is written as `\n`.
+/// A right segment printed before the prompt (see `align_right`) is wrapped as a whole, since the prompt is printed over it
+pub fn readline_escapes(prompt_line: &str) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(ANSI_ESCAPE_REGEX).unwrap();
+    }
+    let (right, prompt_line): (String, &str) = match prompt_line.rfind('\r') {
+        Some(cr) => (format!("\\[{}\\]", prompt_line[..=cr].replace('\\', "\\\\")), &prompt_line[cr + 1..]),
+        None => (String::new(), prompt_line),
+    };
+    let prompt_line: String = prompt_line.replace('\\', "\\\\").replace('\n', "\\n");
+    right + RE.replace_all(prompt_line.as_str(), "\\[$0\\]").as_ref()
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -344,12 +636,69 @@ mod tests {
     use crate::translator::ioprocessor::IOProcessor;
     use crate::translator::new_translator;
     use crate::translator::lang::Language;
-    use colors::PromptColor;
+    use registry::tests::{slow_module, FastModule, SlowModule};
+    use std::sync::atomic::Ordering;
 
-    use git2::Repository;
+    use modules::git::Repository;
     use std::path::PathBuf;
     use std::time::Duration;
 
+    #[test]
+    fn test_prompt_readline_escapes() {
+        assert_eq!(readline_escapes("\x1b[1;32mroot\x1b[0m$"), String::from("\\[\x1b[1;32m\\]root\\[\x1b[0m\\]$"));
+        assert_eq!(readline_escapes("C:\\\n>"), String::from("C:\\\\\\n>"));
+        assert_eq!(readline_escapes("plain"), String::from("plain"));
+        //Right segment printed before the prompt
+        assert_eq!(readline_escapes("  \x1b[31m12\x1b[0m\ruser$"), String::from("\\[  \x1b[31m12\x1b[0m\r\\]user$"));
+    }
+
+    #[test]
+    fn test_prompt_right() {
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${USER}");
+        prompt_config.prompt_line_right = String::from("${TIME}");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        prompt.clock = fixed_time;
+        prompt.columns = || 20;
+        let iop: IOProcessor = get_ioprocessor();
+        let shellenv: ShellProps = get_shellenv();
+        //Right segment is printed first, then the prompt from the beginning of the line
+        assert_eq!(prompt.get_line(&shellenv, &iop), format!("{}09:04:03\ruser", " ".repeat(12)));
+        //Colors don't count toward the width
+        prompt_config.prompt_line_right = String::from("${KRED}${TIME}${KRST}");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        prompt.clock = fixed_time;
+        prompt.columns = || 20;
+        assert_eq!(
+            prompt.get_line(&shellenv, &iop),
+            format!("{}{}09:04:03{}\ruser", " ".repeat(12), PromptColor::Red.to_string(), PromptColor::Reset.to_string())
+        );
+        //Wide characters take two columns
+        prompt_config.prompt_line_right = String::from("漢 ${TIME}");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        prompt.clock = fixed_time;
+        prompt.columns = || 20;
+        assert_eq!(prompt.get_line(&shellenv, &iop), format!("{}漢 09:04:03\ruser", " ".repeat(9)));
+        //With break, the segment ends the line before the input
+        prompt_config.prompt_line_right = String::from("${TIME}");
+        prompt_config.break_enabled = true;
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        prompt.clock = fixed_time;
+        prompt.columns = || 20;
+        assert_eq!(prompt.get_line(&shellenv, &iop), format!("user{}09:04:03\n❯", " ".repeat(8)));
+        //Translated prompt is measured after the translation
+        prompt_config.prompt_line_right = String::from("${RC_CODE}${TIME}");
+        prompt_config.rc_code_always = true;
+        prompt_config.translate = true;
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        prompt.clock = fixed_time;
+        prompt.columns = || 20;
+        assert_eq!(prompt.get_line(&shellenv, &iop), format!("усер{}009:04:03\n❯", " ".repeat(7)));
+        //Segment doesn't fit
+        prompt.columns = || 12;
+        assert_eq!(prompt.get_line(&shellenv, &iop), String::from("усер\n❯"));
+    }
+
     #[test]
     fn test_prompt_simple() {
         let prompt_config_default = PromptConfig::default();
@@ -375,6 +724,50 @@ mod tests {
         println!("\n");
     }
 
+    #[test]
+    fn test_prompt_macros() {
+        let mut prompt_config: PromptConfig = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${WHO} ${RESULT}$");
+        prompt_config.prompt_macros.insert(String::from("WHO"), String::from("${USER}@${HOSTNAME}"));
+        prompt_config.prompt_macros.insert(String::from("RESULT"), String::from("${RC}${LOOP}"));
+        prompt_config.prompt_macros.insert(String::from("LOOP"), String::from("${RESULT}"));
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let iop: IOProcessor = get_ioprocessor();
+        let shellenv: ShellProps = get_shellenv();
+        //The keys in macros are resolved (and enable their options); recursive macros render empty
+        assert!(prompt.rc_opt.is_none());
+        assert_eq!(
+            prompt.process_prompt(&shellenv, &iop),
+            format!("{}@{} $", shellenv.username, shellenv.hostname)
+        );
+        prompt_config.prompt_macros.remove("LOOP");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        assert!(prompt.rc_opt.is_some());
+        assert_eq!(
+            prompt.process_prompt(&shellenv, &iop),
+            format!("{}@{} ✔${{LOOP}}$", shellenv.username, shellenv.hostname)
+        );
+    }
+
+    #[test]
+    fn test_prompt_reconfigure() {
+        let mut prompt_config: PromptConfig = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${USER}$");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let iop: IOProcessor = get_ioprocessor();
+        let shellenv: ShellProps = get_shellenv();
+        assert_eq!(prompt.get_line(&shellenv, &iop), format!("{}$", shellenv.username));
+        //Apply the reloaded configuration
+        let mut reloaded: PromptConfig = PromptConfig::default();
+        reloaded.prompt_line = String::from("${KRED}${HOSTNAME}${KRST}>");
+        reloaded.translate = true;
+        prompt.reconfigure(&reloaded);
+        assert_eq!(
+            prompt.get_line(&shellenv, &iop),
+            iop.text_to_cyrillic(&format!("{}{}{}>", PromptColor::Red.to_string(), shellenv.hostname, PromptColor::Reset.to_string()))
+        );
+    }
+
     #[test]
     fn test_prompt_colors() {
         let mut prompt_config_default = PromptConfig::default();
@@ -407,6 +800,13 @@ mod tests {
             PromptColor::Reset.to_string()
         ));
         assert_eq!(prompt_line, expected_prompt_line);
+        //256 colors and truecolor; invalid parameters are rendered empty
+        prompt_config_default.prompt_line = String::from("${K256:196}A${BRGB:ff8800}B${KRGB:nope}C${KRST}");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config_default);
+        assert_eq!(
+            prompt.process_prompt(&shellenv, &iop),
+            format!("\x1b[38;5;196mA\x1b[48;2;255;136;0mBC{}", PromptColor::Reset.to_string())
+        );
         //Terminate shell at the end of a test
         //terminate_shell(&mut shellenv);
         println!("\n");
@@ -431,7 +831,7 @@ mod tests {
         //Get prompt line
         let prompt_line: String = prompt.process_prompt(&shellenv, &iop);
         let expected_prompt_line = String::from(format!(
-            "{} ~ {}{}{} on {}{}{} in {}{}{} {}took 5.1s{}\n❯",
+            "{} ~ {}{}{} on {}{}{} in {}{}{} {}5,1с{}\n❯",
             language::language_to_str(Language::Russian),
             PromptColor::Yellow.to_string(),
             shellenv.username.clone(),
@@ -463,6 +863,7 @@ mod tests {
         //Update prompt line
         prompt_config.prompt_line =
             String::from("${USER}@${HOSTNAME}:${WRKDIR} ${GIT_BRANCH} ${GIT_COMMIT}");
+        prompt_config.module_timeout = 5000;
         let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
         let iop: IOProcessor = get_ioprocessor();
         let mut shellenv: ShellProps = get_shellenv();
@@ -516,12 +917,42 @@ mod tests {
         println!("\n");
     }
 
+    #[test]
+    #[cfg(not(feature = "git-lite"))]
+    fn test_prompt_git_status() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let repo: Repository = git::tests::make_repository(&tmpdir);
+        let branch: String = git::get_branch(&repo).unwrap();
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${USER} ${GIT_BRANCH} ${GIT_STATUS}");
+        prompt_config.module_timeout = 5000;
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let iop: IOProcessor = get_ioprocessor();
+        let mut shellenv: ShellProps = get_shellenv();
+        shellenv.wrkdir = PathBuf::from(tmpdir.path());
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), format!("user on {} ✚2 ●1", branch));
+        //Custom symbols
+        prompt_config.git_dirty = String::from("*");
+        prompt_config.git_staged = String::from("+");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), format!("user on {} *2 +1", branch));
+        //Clean repository
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        Repository::init(tmpdir.path()).unwrap();
+        shellenv.wrkdir = PathBuf::from(tmpdir.path());
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user"));
+        //Not in a repository
+        shellenv.wrkdir = PathBuf::from("/");
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user"));
+    }
+
     #[test]
     fn test_prompt_git_not_in_repo() {
         let mut prompt_config_default = PromptConfig::default();
         //Update prompt line
         prompt_config_default.prompt_line =
             String::from("${USER}@${HOSTNAME}:${WRKDIR} ${GIT_BRANCH} ${GIT_COMMIT}");
+        prompt_config_default.module_timeout = 5000;
         let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config_default);
         let iop: IOProcessor = get_ioprocessor();
         let mut shellenv: ShellProps = get_shellenv();
@@ -546,6 +977,145 @@ mod tests {
         println!("\n");
     }
 
+    #[test]
+    fn test_prompt_time() {
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("[${TIME}] ${DATE} ${USER}");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        prompt.clock = fixed_time;
+        let iop: IOProcessor = get_ioprocessor();
+        let shellenv: ShellProps = get_shellenv();
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("[09:04:03] 2021-01-31 user"));
+        //Custom formats; unsupported directives are kept
+        prompt_config.time_format = String::from("%I:%M %p");
+        prompt_config.date_format = String::from("%d.%m.%y %Q");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        prompt.clock = fixed_time;
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("[09:04 AM] 31.01.21 %Q user"));
+    }
+
+    #[test]
+    fn test_prompt_time_languages() {
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${TIME} ${DATE}");
+        prompt_config.time_format = String::from("%a %H:%M");
+        prompt_config.date_format = String::from("%d %B %Y");
+        prompt_config.translate = true;
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        prompt.clock = fixed_time;
+        let shellenv: ShellProps = get_shellenv();
+        //Russian; localized names are not converted again
+        let iop: IOProcessor = get_ioprocessor();
+        assert_eq!(prompt.get_line(&shellenv, &iop), String::from("вс 09:04 31 январь 2021"));
+        //English
+        let iop: IOProcessor = IOProcessor::new(Language::Nil, new_translator(Language::Nil));
+        assert_eq!(prompt.get_line(&shellenv, &iop), String::from("Sun 09:04 31 January 2021"));
+    }
+
+    #[test]
+    fn test_prompt_duration_languages() {
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${CMD_TIME}");
+        prompt_config.translate = true;
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let mut shellenv: ShellProps = get_shellenv();
+        shellenv.elapsed_time = Duration::from_millis(5140);
+        //Russian; decimal comma
+        let iop: IOProcessor = get_ioprocessor();
+        assert_eq!(prompt.get_line(&shellenv, &iop), String::from("5,1с"));
+        //English
+        let iop_en: IOProcessor = IOProcessor::new(Language::Nil, new_translator(Language::Nil));
+        assert_eq!(prompt.get_line(&shellenv, &iop_en), String::from("took 5.1s"));
+        //Custom format
+        prompt_config.duration_format = Some(String::from("⏱ {secs} ({millis}ms)"));
+        prompt_config.translate = false;
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        assert_eq!(prompt.get_line(&shellenv, &iop), String::from("⏱ 5,1 (5140ms)"));
+        assert_eq!(prompt.get_line(&shellenv, &iop_en), String::from("⏱ 5.1 (5140ms)"));
+        //Below minimum duration
+        shellenv.elapsed_time = Duration::from_millis(1000);
+        assert_eq!(prompt.get_line(&shellenv, &iop), String::from(""));
+        //Custom prefix; minutes and hours
+        prompt_config.duration_format = None;
+        prompt_config.duration_prefix = Some(String::from("⏱ "));
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        shellenv.elapsed_time = Duration::from_millis(127400);
+        assert_eq!(prompt.get_line(&shellenv, &iop_en), String::from("⏱ 2m 7s"));
+        assert_eq!(prompt.get_line(&shellenv, &iop), String::from("⏱ 2мин 7с"));
+        shellenv.elapsed_time = Duration::from_secs(3780);
+        assert_eq!(prompt.get_line(&shellenv, &iop_en), String::from("⏱ 1h 3m"));
+        //Minimum duration is inclusive
+        shellenv.elapsed_time = Duration::from_millis(2000);
+        assert_eq!(prompt.get_line(&shellenv, &iop_en), String::from("⏱ 2.0s"));
+    }
+
+    #[test]
+    fn test_prompt_venv() {
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${USER} ${VENV}");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let iop: IOProcessor = get_ioprocessor();
+        let shellenv: ShellProps = get_shellenv();
+        std::env::set_var("VIRTUAL_ENV", "/home/user/project/.venv");
+        std::env::set_var("CONDA_DEFAULT_ENV", "base");
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user 🐍.venv"));
+        //Conda fallback
+        std::env::remove_var("VIRTUAL_ENV");
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user 🐍base"));
+        //Custom prefix
+        prompt_config.venv_prefix = String::from("py:");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user py:base"));
+        //No virtual environment
+        std::env::remove_var("CONDA_DEFAULT_ENV");
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user"));
+        //Module is not enabled if the key is not used
+        let ctx: ModuleContext = ModuleContext {
+            wrkdir: shellenv.wrkdir.clone(),
+        };
+        assert!(ShellPrompt::new(&PromptConfig::default()).registry.resolve(modules::venv::PROMPT_VENV, &ctx, true).is_none());
+    }
+
+    #[test]
+    fn test_prompt_async_modules() {
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${USER} ${SLOW} ${FAST}");
+        prompt_config.module_timeout = 0;
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let module: Arc<SlowModule> = slow_module(100, 2000);
+        prompt.register_module(module.clone());
+        prompt.register_module(Arc::new(FastModule));
+        let iop: IOProcessor = get_ioprocessor();
+        let shellenv: ShellProps = get_shellenv();
+        //Prompt is rendered immediately with the placeholder, fast module is resolved inline
+        assert_eq!(prompt.get_line(&shellenv, &iop), String::from("user … fast:/home/user/"));
+        assert!(registry::tests::wait_poll(|| prompt.poll_modules()));
+        assert_eq!(prompt.redraw_line(&shellenv, &iop), String::from("user slow fast:/home/user/"));
+        //Redraw doesn't resolve async modules again
+        assert_eq!(module.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_prompt_module_timeout() {
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${USER} ${SLOW}");
+        prompt_config.module_timeout = 20;
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        prompt.register_module(slow_module(300, 2000));
+        let iop: IOProcessor = get_ioprocessor();
+        let shellenv: ShellProps = get_shellenv();
+        //Module takes longer than the timeout: placeholder is rendered
+        assert_eq!(prompt.get_line(&shellenv, &iop), String::from("user …"));
+        assert!(registry::tests::wait_poll(|| prompt.poll_modules()));
+        //Module takes longer than the timeout again: previous value is rendered
+        assert_eq!(prompt.get_line(&shellenv, &iop), String::from("user slow"));
+        //Module answers within the timeout
+        prompt_config.module_timeout = 2000;
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        prompt.register_module(slow_module(50, 2000));
+        assert_eq!(prompt.get_line(&shellenv, &iop), String::from("user slow"));
+    }
+
     #[test]
     fn test_prompt_rc_ok() {
         let mut prompt_config_default = PromptConfig::default();
@@ -575,6 +1145,158 @@ mod tests {
         println!("\n");
     }
 
+    #[test]
+    fn test_prompt_char() {
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${USER} ${PROMPT_CHAR}");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let iop: IOProcessor = get_ioprocessor();
+        let mut shellenv: ShellProps = get_shellenv();
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user $"));
+        shellenv.uid = 0;
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user #"));
+        //Configured symbols, with colors
+        prompt_config.prompt_char_user = String::from("${C:success}❯${KRST}");
+        prompt_config.prompt_char_root = String::from("${KRED}#${KRST}");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user \x1b[31m#\x1b[0m"));
+        shellenv.uid = 1000;
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user \x1b[32m❯\x1b[0m"));
+    }
+
+    #[test]
+    fn test_prompt_jobs() {
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${USER} ${JOBS}$");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let iop: IOProcessor = get_ioprocessor();
+        let mut shellenv: ShellProps = get_shellenv();
+        //No jobs
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user $"));
+        //Running jobs only
+        shellenv.jobs = JobsSummary { running: 2, stopped: 0 };
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user ⚙[2]$"));
+        //Stopped jobs only
+        shellenv.jobs = JobsSummary { running: 0, stopped: 1 };
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user ⏸[1]$"));
+        //Both: all jobs are counted, with the stopped symbol
+        shellenv.jobs = JobsSummary { running: 2, stopped: 1 };
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user ⏸[3]$"));
+        //Configured symbols, with colors
+        prompt_config.jobs_running = String::new();
+        prompt_config.jobs_stopped = String::from("${C:error}✋");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user \x1b[31m✋[3]$"));
+        shellenv.jobs = JobsSummary { running: 2, stopped: 0 };
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user [2]$"));
+        //Module disabled if the key is not used
+        prompt_config.prompt_line = String::from("${USER}");
+        assert!(ShellPrompt::new(&prompt_config).jobs_opt.is_none());
+    }
+
+    #[test]
+    fn test_prompt_rc_code() {
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${RC} ${RC_CODE} ${USER}");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let iop: IOProcessor = get_ioprocessor();
+        let mut shellenv: ShellProps = get_shellenv();
+        //Exit code is shown only if not 0
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("✔  user"));
+        shellenv.exit_status = 1;
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("✖ 1 user"));
+        shellenv.exit_status = 255;
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("✖ 255 user"));
+        //Always show exit code
+        prompt_config.rc_code_always = true;
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        shellenv.exit_status = 0;
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("✔ 0 user"));
+        shellenv.exit_status = 1;
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("✖ 1 user"));
+        shellenv.exit_status = 255;
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("✖ 255 user"));
+        //Without RC
+        prompt_config.prompt_line = String::from("${USER} [${RC_CODE}]");
+        prompt_config.rc_code_always = false;
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user [255]"));
+        shellenv.exit_status = 0;
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user []"));
+    }
+
+    #[test]
+    fn test_prompt_palette() {
+        let mut prompt_config = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${C:primary}${USER}${KRST} ${RC} ${C:unknown}x");
+        prompt_config.rc_ok = String::from("${C:success}✔${KRST}");
+        prompt_config.rc_err = String::from("${C:error}✖${KRST}");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let iop: IOProcessor = get_ioprocessor();
+        let mut shellenv: ShellProps = get_shellenv();
+        //Default palette
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("\x1b[33muser\x1b[0m \x1b[32m✔\x1b[0m x"));
+        shellenv.exit_status = 1;
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("\x1b[33muser\x1b[0m \x1b[31m✖\x1b[0m x"));
+        //Switching palette reskins the prompt
+        prompt_config.palette.insert(String::from("primary"), String::from("214"));
+        prompt_config.palette.insert(String::from("error"), String::from("#ff0000"));
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        assert_eq!(
+            prompt.process_prompt(&shellenv, &iop),
+            String::from("\x1b[38;5;214muser\x1b[0m \x1b[38;2;255;0;0m✖\x1b[0m x")
+        );
+    }
+
+    #[test]
+    fn test_prompt_degraded() {
+        let mut prompt_config_default = PromptConfig::default();
+        //Update prompt line
+        prompt_config_default.prompt_line = String::from("${DEGRADED} ${USER}");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config_default);
+        let iop: IOProcessor = get_ioprocessor();
+        let mut shellenv: ShellProps = get_shellenv();
+        //Not degraded
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("user"));
+        //Degraded
+        shellenv.degraded = vec![String::from("history"), String::from("trace")];
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), String::from("history,trace user"));
+    }
+
+    #[test]
+    fn test_prompt_newline() {
+        let mut prompt_config: PromptConfig = PromptConfig::default();
+        prompt_config.prompt_line = String::from("${USER}@${HOSTNAME}${NEWLINE}${WRKDIR}${NEWLINE}$ ");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let iop: IOProcessor = get_ioprocessor();
+        let shellenv: ShellProps = get_shellenv();
+        //The trailing space is trimmed by default
+        assert_eq!(
+            prompt.process_prompt(&shellenv, &iop),
+            format!("{}@{}\n{}\n$", shellenv.username, shellenv.hostname, shellenv.wrkdir.display())
+        );
+        //Keep it
+        prompt_config.keep_trailing_space = true;
+        prompt_config.prompt_line = String::from("  ${USER}${NEWLINE}❯ ");
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        assert_eq!(prompt.process_prompt(&shellenv, &iop), format!("{}\n❯ ", shellenv.username));
+    }
+
+    #[test]
+    fn test_prompt_escape() {
+        let mut prompt_config: PromptConfig = PromptConfig::default();
+        prompt_config.prompt_line = String::from("$${USER}=${USER} $${FOOBAR} $$${USER} $${ $");
+        prompt_config.prompt_macros.insert(String::from("FOOBAR"), String::from("${HOSTNAME}"));
+        let mut prompt: ShellPrompt = ShellPrompt::new(&prompt_config);
+        let iop: IOProcessor = get_ioprocessor();
+        let shellenv: ShellProps = get_shellenv();
+        //Escaped keys and macros are written as they are
+        assert_eq!(
+            prompt.process_prompt(&shellenv, &iop),
+            format!("${{USER}}={} ${{FOOBAR}} $${{USER}} ${{ $", shellenv.username)
+        );
+    }
+
     #[test]
     fn test_prompt_rc_error() {
         let mut prompt_config_default = PromptConfig::default();
@@ -636,6 +1358,19 @@ mod tests {
         println!("\n");
     }
 
+    fn fixed_time() -> LocalTime {
+        LocalTime {
+            year: 2021,
+            month: 1,
+            day: 31,
+            hour: 9,
+            minute: 4,
+            second: 3,
+            weekday: 0,
+            yearday: 31,
+        }
+    }
+
     fn get_ioprocessor() -> IOProcessor {
         IOProcessor::new(Language::Russian, new_translator(Language::Russian))
     }
@@ -646,7 +1381,10 @@ mod tests {
             username: String::from("user"),
             elapsed_time: Duration::from_secs(0),
             exit_status: 0,
-            wrkdir: PathBuf::from("/home/user/")
+            wrkdir: PathBuf::from("/home/user/"),
+            uid: 1000,
+            degraded: Vec::new(),
+            jobs: JobsSummary::default()
         }
     }
 }
@@ -0,0 +1,224 @@
+//! ## Battery
+//!
+//! `battery` is the module which provides the charge of the battery
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::colors::PromptColor;
+use super::{ModuleContext, PromptModule};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+//Keys
+pub(crate) const PROMPT_BATTERY: &str = "${BATTERY}";
+
+//Directory where Linux exposes the power supplies
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+/// ### BatteryState
+///
+/// BatteryState describes the charge of the battery
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub struct BatteryState {
+    pub capacity: u8, //Percentage
+    pub charging: bool,
+}
+
+/// ## BatterySource
+///
+/// BatterySource provides the state of the battery; it returns None if there is no battery
+pub trait BatterySource: Send + Sync {
+    fn read(&self) -> Option<BatteryState>;
+}
+
+/// ## SysfsBattery
+///
+/// SysfsBattery reads the state of the first battery (`BAT*`) from the power supplies exposed by sysfs
+pub struct SysfsBattery {
+    power_supply_dir: PathBuf,
+}
+
+impl SysfsBattery {
+    /// ### new
+    ///
+    /// Instantiate a new SysfsBattery which reads the power supplies from `power_supply_dir`
+    pub fn new(power_supply_dir: &Path) -> SysfsBattery {
+        SysfsBattery {
+            power_supply_dir: power_supply_dir.to_path_buf(),
+        }
+    }
+}
+
+impl BatterySource for SysfsBattery {
+    fn read(&self) -> Option<BatteryState> {
+        let mut batteries: Vec<PathBuf> = fs::read_dir(&self.power_supply_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("BAT"))
+            .map(|entry| entry.path())
+            .collect();
+        batteries.sort();
+        let battery: &PathBuf = batteries.first()?;
+        let capacity: u8 = fs::read_to_string(battery.join("capacity")).ok()?.trim().parse::<u8>().ok()?;
+        //Status is one of "Charging", "Discharging", "Not charging", "Full" and "Unknown"
+        let charging: bool = match fs::read_to_string(battery.join("status")) {
+            Ok(status) => status.trim() == "Charging",
+            Err(_) => false,
+        };
+        Some(BatteryState {
+            capacity: capacity.min(100),
+            charging,
+        })
+    }
+}
+
+/// ## BatteryModule
+///
+/// BatteryModule renders the charge of the battery after the symbol of its status; it renders empty if there is no battery
+pub struct BatteryModule {
+    source: Box<dyn BatterySource>,
+    charging: String,
+    discharging: String,
+    low_threshold: u8,
+}
+
+impl BatteryModule {
+    /// ### should_enable
+    ///
+    /// helper which says if battery module should be enabled
+    pub fn should_enable(prompt_line: &str) -> bool {
+        prompt_line.contains(PROMPT_BATTERY)
+    }
+
+    /// ### new
+    ///
+    /// Instantiate a new BatteryModule which reads the battery from sysfs
+    pub fn new(charging: &str, discharging: &str, low_threshold: u8) -> BatteryModule {
+        BatteryModule::with_source(Box::new(SysfsBattery::new(Path::new(POWER_SUPPLY_DIR))), charging, discharging, low_threshold)
+    }
+
+    /// ### with_source
+    ///
+    /// Instantiate a new BatteryModule which reads the battery from the provided source
+    pub fn with_source(source: Box<dyn BatterySource>, charging: &str, discharging: &str, low_threshold: u8) -> BatteryModule {
+        BatteryModule {
+            source,
+            charging: charging.to_string(),
+            discharging: discharging.to_string(),
+            low_threshold,
+        }
+    }
+}
+
+impl PromptModule for BatteryModule {
+    fn handles(&self, key: &str) -> bool {
+        key == PROMPT_BATTERY
+    }
+
+    fn resolve(&self, _key: &str, _ctx: &ModuleContext) -> String {
+        let state: BatteryState = match self.source.read() {
+            Some(state) => state,
+            None => return String::from(""),
+        };
+        let symbol: &String = match state.charging {
+            true => &self.charging,
+            false => &self.discharging,
+        };
+        let charge: String = format!("{} {}%", symbol, state.capacity);
+        match state.capacity <= self.low_threshold {
+            true => format!("{}{}{}", PromptColor::Red.to_string(), charge, PromptColor::Reset.to_string()),
+            false => charge,
+        }
+    }
+}
+
+//@! Tests
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    struct FixedBattery {
+        state: Option<BatteryState>,
+    }
+
+    impl BatterySource for FixedBattery {
+        fn read(&self) -> Option<BatteryState> {
+            self.state
+        }
+    }
+
+    fn resolve_with(state: Option<BatteryState>) -> String {
+        let module: BatteryModule = BatteryModule::with_source(
+            Box::new(FixedBattery { state }),
+            &String::from("⚡"),
+            &String::from("🔋"),
+            20,
+        );
+        module.resolve(PROMPT_BATTERY, &ModuleContext { wrkdir: PathBuf::from("/") })
+    }
+
+    #[test]
+    fn test_prompt_battery_module() {
+        let red: String = PromptColor::Red.to_string();
+        let reset: String = PromptColor::Reset.to_string();
+        assert_eq!(resolve_with(Some(BatteryState { capacity: 85, charging: false })), String::from("🔋 85%"));
+        assert_eq!(resolve_with(Some(BatteryState { capacity: 42, charging: true })), String::from("⚡ 42%"));
+        assert_eq!(resolve_with(Some(BatteryState { capacity: 100, charging: false })), String::from("🔋 100%"));
+        //Low battery is red, charging or not
+        assert_eq!(resolve_with(Some(BatteryState { capacity: 20, charging: false })), format!("{}🔋 20%{}", red, reset));
+        assert_eq!(resolve_with(Some(BatteryState { capacity: 5, charging: true })), format!("{}⚡ 5%{}", red, reset));
+        assert_eq!(resolve_with(Some(BatteryState { capacity: 21, charging: false })), String::from("🔋 21%"));
+        //No battery
+        assert_eq!(resolve_with(None), String::from(""));
+        assert!(BatteryModule::should_enable(&String::from("${USER} ${BATTERY}")));
+        assert!(!BatteryModule::should_enable(&String::from("${USER}")));
+    }
+
+    #[test]
+    fn test_prompt_battery_sysfs() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let source: SysfsBattery = SysfsBattery::new(tmpdir.path());
+        //Only the AC adapter
+        fs::create_dir(tmpdir.path().join("AC")).unwrap();
+        fs::write(tmpdir.path().join("AC").join("online"), "1\n").unwrap();
+        assert_eq!(source.read(), None);
+        //The first battery is read
+        for (name, capacity, status) in [("BAT1", "30\n", "Discharging\n"), ("BAT0", "85\n", "Charging\n")].iter() {
+            fs::create_dir(tmpdir.path().join(name)).unwrap();
+            fs::write(tmpdir.path().join(name).join("capacity"), capacity).unwrap();
+            fs::write(tmpdir.path().join(name).join("status"), status).unwrap();
+        }
+        assert_eq!(source.read(), Some(BatteryState { capacity: 85, charging: true }));
+        fs::write(tmpdir.path().join("BAT0").join("status"), "Full\n").unwrap();
+        assert_eq!(source.read(), Some(BatteryState { capacity: 85, charging: false }));
+        //Without status the battery is discharging; an invalid capacity is no battery
+        fs::remove_file(tmpdir.path().join("BAT0").join("status")).unwrap();
+        assert_eq!(source.read(), Some(BatteryState { capacity: 85, charging: false }));
+        fs::write(tmpdir.path().join("BAT0").join("capacity"), "n/a\n").unwrap();
+        assert_eq!(source.read(), None);
+        //No power supplies at all
+        assert_eq!(SysfsBattery::new(&tmpdir.path().join("nonexisting")).read(), None);
+    }
+}
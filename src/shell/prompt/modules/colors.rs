@@ -23,6 +23,8 @@
 *
 */
 
+use std::collections::HashMap;
+
 //Keys
 pub(crate) const PROMPT_KRED: &str = "${KRED}";
 pub(crate) const PROMPT_KYEL: &str = "${KYEL}";
@@ -37,6 +39,11 @@ pub(crate) const PROMPT_KBOLD: &str = "${KBOLD}";
 pub(crate) const PROMPT_KBLINK: &str = "${KBLINK}";
 pub(crate) const PROMPT_KSELECT: &str = "${KSELECT}";
 pub(crate) const PROMPT_KRST: &str = "${KRST}";
+pub(crate) const PROMPT_PALETTE_PREFIX: &str = "${C:";
+pub(crate) const PROMPT_K256_PREFIX: &str = "${K256:";
+pub(crate) const PROMPT_KRGB_PREFIX: &str = "${KRGB:";
+pub(crate) const PROMPT_B256_PREFIX: &str = "${B256:";
+pub(crate) const PROMPT_BRGB_PREFIX: &str = "${BRGB:";
 
 //Colors
 const KRED: &str = "\x1b[31m";
@@ -68,6 +75,16 @@ pub enum PromptColor {
     Blink,
     Select,
     Reset,
+    Ansi256(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// ### Palette
+///
+/// Palette maps semantic color names (e.g. `primary`, `error`) to the escape sequences of their style
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct Palette {
+    styles: HashMap<String, String>,
 }
 
 impl ToString for PromptColor {
@@ -86,6 +103,8 @@ impl ToString for PromptColor {
             PromptColor::Blink => String::from(KBLINK),
             PromptColor::Select => String::from(KSELECT),
             PromptColor::Reset => String::from(KRST),
+            PromptColor::Ansi256(index) => format!("\x1b[38;5;{}m", index),
+            PromptColor::Rgb(r, g, b) => format!("\x1b[38;2;{};{};{}m", r, g, b),
         }
     }
 }
@@ -109,6 +128,186 @@ impl PromptColor {
             _ => PromptColor::Reset,
         }
     }
+
+    /// ### from_name
+    ///
+    /// Parse a color name (e.g. `red`), an index of the 256 colors table or an hex color (`#rrggbb`).
+    /// Used by the palette and by the error color of the output
+    pub fn from_name(spec: &str) -> Option<PromptColor> {
+        match spec.to_lowercase().as_str() {
+            "red" => Some(PromptColor::Red),
+            "yellow" => Some(PromptColor::Yellow),
+            "green" => Some(PromptColor::Green),
+            "blue" => Some(PromptColor::Blue),
+            "cyan" => Some(PromptColor::Cyan),
+            "magenta" => Some(PromptColor::Magenta),
+            "black" => Some(PromptColor::Black),
+            "gray" | "grey" => Some(PromptColor::Gray),
+            "white" => Some(PromptColor::White),
+            "bold" => Some(PromptColor::Bold),
+            "blink" => Some(PromptColor::Blink),
+            "select" => Some(PromptColor::Select),
+            spec if spec.starts_with('#') && spec.len() == 7 && spec.is_ascii() => {
+                let channel = |i: usize| u8::from_str_radix(&spec[i..i + 2], 16).ok();
+                match (channel(1), channel(3), channel(5)) {
+                    (Some(r), Some(g), Some(b)) => Some(PromptColor::Rgb(r, g, b)),
+                    _ => None,
+                }
+            }
+            spec => spec.parse::<u8>().ok().map(PromptColor::Ansi256),
+        }
+    }
+
+    /// ### to_background
+    ///
+    /// Returns the escape sequence which sets the color as background; text attributes are returned as they are
+    pub fn to_background(self) -> String {
+        match self {
+            PromptColor::Red => String::from("\x1b[41m"),
+            PromptColor::Green => String::from("\x1b[42m"),
+            PromptColor::Yellow => String::from("\x1b[43m"),
+            PromptColor::Blue => String::from("\x1b[44m"),
+            PromptColor::Cyan => String::from("\x1b[46m"),
+            PromptColor::Magenta => String::from("\x1b[45m"),
+            PromptColor::Black => String::from("\x1b[40m"),
+            PromptColor::Gray => String::from("\x1b[100m"),
+            PromptColor::White => String::from("\x1b[47m"),
+            PromptColor::Ansi256(index) => format!("\x1b[48;5;{}m", index),
+            PromptColor::Rgb(r, g, b) => format!("\x1b[48;2;{};{};{}m", r, g, b),
+            attribute => attribute.to_string(),
+        }
+    }
+}
+
+impl Palette {
+    /// ### new
+    ///
+    /// Instantiates a new Palette from the configured styles. Styles which can't be parsed are ignored
+    pub fn new(palette: &HashMap<String, String>) -> Palette {
+        Palette {
+            styles: palette
+                .iter()
+                .filter_map(|(name, spec)| Palette::parse_style(spec).map(|style| (name.clone(), style)))
+                .collect(),
+        }
+    }
+
+    /// ### parse_style
+    ///
+    /// Parse a style made up of whitespace separated colors (e.g. `bold #ff8700`) to its escape sequence
+    pub fn parse_style(spec: &str) -> Option<String> {
+        let mut style: String = String::new();
+        for color in spec.split_whitespace() {
+            style.push_str(PromptColor::from_name(color)?.to_string().as_str());
+        }
+        match style.is_empty() {
+            true => None,
+            false => Some(style),
+        }
+    }
+
+    /// ### resolve
+    ///
+    /// Returns the escape sequence of the style with the provided name; unknown names are rendered empty
+    pub fn resolve(&self, name: &str) -> String {
+        self.styles.get(name).cloned().unwrap_or_default()
+    }
+
+    /// ### expand
+    ///
+    /// Replace the palette keys (`${C:name}`) in text with their style
+    pub fn expand(&self, text: &str) -> String {
+        let mut expanded: String = String::from(text);
+        for name in palette_references(text) {
+            expanded = expanded.replace(format!("{}{}}}", PROMPT_PALETTE_PREFIX, name).as_str(), self.resolve(name.as_str()).as_str());
+        }
+        expanded
+    }
+}
+
+/// ### expand_colors
+///
+/// Replace the color keys (e.g. `${KRED}`) in text with their escape sequence
+pub fn expand_colors(text: &str) -> String {
+    let mut expanded: String = String::from(text);
+    for key in [
+        PROMPT_KRED, PROMPT_KYEL, PROMPT_KGRN, PROMPT_KBLU, PROMPT_KCYN, PROMPT_KMAG, PROMPT_KBLK, PROMPT_KGRY, PROMPT_KWHT,
+        PROMPT_KBOLD, PROMPT_KBLINK, PROMPT_KSELECT, PROMPT_KRST,
+    ]
+    .iter()
+    {
+        expanded = expanded.replace(key, PromptColor::from_key(key).to_string().as_str());
+    }
+    for key in parameterized_keys(text) {
+        expanded = expanded.replace(key.as_str(), parameterized_color(key.as_str()).as_str());
+    }
+    expanded
+}
+
+/// ### is_parameterized_key
+///
+/// Returns whether key is a color key with a parameter (`${K256:n}`, `${KRGB:rrggbb}`, `${B256:n}` or `${BRGB:rrggbb}`)
+pub fn is_parameterized_key(key: &str) -> bool {
+    [PROMPT_K256_PREFIX, PROMPT_KRGB_PREFIX, PROMPT_B256_PREFIX, PROMPT_BRGB_PREFIX]
+        .iter()
+        .any(|prefix| key.starts_with(prefix))
+        && key.ends_with('}')
+}
+
+/// ### parameterized_color
+///
+/// Returns the escape sequence of a color key with a parameter; `K` keys set the foreground, `B` keys the background.
+/// Keys with an invalid parameter (e.g. `${K256:300}`) are rendered empty
+pub fn parameterized_color(key: &str) -> String {
+    //All the prefixes have the same length
+    let param: &str = &key[PROMPT_K256_PREFIX.len()..key.len() - 1];
+    let color: Option<PromptColor> = match &key[..PROMPT_K256_PREFIX.len()] {
+        PROMPT_K256_PREFIX | PROMPT_B256_PREFIX if param.chars().all(|ch| ch.is_ascii_digit()) => param.parse::<u8>().ok().map(PromptColor::Ansi256),
+        PROMPT_KRGB_PREFIX | PROMPT_BRGB_PREFIX => PromptColor::from_name(format!("#{}", param).as_str()),
+        _ => None,
+    };
+    match color {
+        Some(color) if key.starts_with(PROMPT_B256_PREFIX) || key.starts_with(PROMPT_BRGB_PREFIX) => color.to_background(),
+        Some(color) => color.to_string(),
+        None => String::new(),
+    }
+}
+
+/// ### parameterized_keys
+///
+/// Returns the color keys with a parameter found in text
+fn parameterized_keys(text: &str) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for (start, _) in text.match_indices("${") {
+        if let Some(end) = text[start..].find('}') {
+            let key: &str = &text[start..start + end + 1];
+            if is_parameterized_key(key) && !keys.iter().any(|k| k == key) {
+                keys.push(String::from(key));
+            }
+        }
+    }
+    keys
+}
+
+/// ### palette_references
+///
+/// Returns the names of the palette styles referenced in text through `${C:name}`
+pub fn palette_references(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest: &str = text;
+    while let Some(start) = rest.find(PROMPT_PALETTE_PREFIX) {
+        rest = &rest[start + PROMPT_PALETTE_PREFIX.len()..];
+        let end: usize = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        let name: String = String::from(&rest[..end]);
+        if !names.contains(&name) {
+            names.push(name);
+        }
+        rest = &rest[end + 1..];
+    }
+    names
 }
 
 #[cfg(test)]
@@ -116,6 +315,70 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_prompt_color_from_name() {
+        assert_eq!(PromptColor::from_name("Yellow"), Some(PromptColor::Yellow));
+        assert_eq!(PromptColor::from_name("grey"), Some(PromptColor::Gray));
+        assert_eq!(PromptColor::from_name("214"), Some(PromptColor::Ansi256(214)));
+        assert_eq!(PromptColor::from_name("#FF8700"), Some(PromptColor::Rgb(255, 135, 0)));
+        assert_eq!(PromptColor::from_name("256"), None);
+        assert_eq!(PromptColor::from_name("#ff87"), None);
+        assert_eq!(PromptColor::from_name("#gg8700"), None);
+        assert_eq!(PromptColor::from_name("purple"), None);
+        assert_eq!(PromptColor::from_name(""), None);
+        assert_eq!(PromptColor::from_name("MAGENTA").unwrap().to_string(), "\x1b[35m");
+        assert_eq!(PromptColor::from_name("red").unwrap().to_string(), "\x1b[31m");
+        assert_eq!(PromptColor::Ansi256(214).to_string(), "\x1b[38;5;214m");
+        assert_eq!(PromptColor::Rgb(255, 135, 0).to_string(), "\x1b[38;2;255;135;0m");
+    }
+
+    #[test]
+    fn test_prompt_color_palette() {
+        let mut styles: HashMap<String, String> = HashMap::new();
+        styles.insert(String::from("primary"), String::from("bold 214"));
+        styles.insert(String::from("error"), String::from("red"));
+        styles.insert(String::from("broken"), String::from("purple"));
+        let palette: Palette = Palette::new(&styles);
+        assert_eq!(palette.resolve("primary"), "\x1b[1m\x1b[38;5;214m");
+        assert_eq!(palette.resolve("error"), KRED);
+        assert_eq!(palette.resolve("broken"), "");
+        assert_eq!(palette.resolve("accent"), "");
+        assert_eq!(palette.expand("${C:error}✖${KRST} ${C:accent}x"), format!("{}✖${{KRST}} x", KRED));
+        assert_eq!(Palette::parse_style(""), None);
+        assert_eq!(expand_colors("${KBOLD}x${KRST}${KFOO}"), format!("{}x{}${{KFOO}}", KBOLD, KRST));
+        assert_eq!(
+            palette_references("${C:primary}${USER} ${C:muted}${C:primary} ${C:open"),
+            vec![String::from("primary"), String::from("muted")]
+        );
+    }
+
+    #[test]
+    fn test_prompt_color_parameterized() {
+        assert!(is_parameterized_key("${K256:196}"));
+        assert!(is_parameterized_key("${BRGB:ff8800}"));
+        assert!(!is_parameterized_key("${KRED}"));
+        assert!(!is_parameterized_key("${K256:196"));
+        assert_eq!(parameterized_color("${K256:196}"), "\x1b[38;5;196m");
+        assert_eq!(parameterized_color("${B256:0}"), "\x1b[48;5;0m");
+        assert_eq!(parameterized_color("${KRGB:ff8800}"), "\x1b[38;2;255;136;0m");
+        assert_eq!(parameterized_color("${BRGB:FF8800}"), "\x1b[48;2;255;136;0m");
+        //Malformed parameters are rendered empty
+        assert_eq!(parameterized_color("${K256:256}"), "");
+        assert_eq!(parameterized_color("${K256:+5}"), "");
+        assert_eq!(parameterized_color("${K256:red}"), "");
+        assert_eq!(parameterized_color("${K256:}"), "");
+        assert_eq!(parameterized_color("${KRGB:ff88}"), "");
+        assert_eq!(parameterized_color("${KRGB:#ff8800}"), "");
+        assert_eq!(parameterized_color("${BRGB:gg8800}"), "");
+        assert_eq!(parameterized_color("${KRGB:ффф}"), "");
+        assert_eq!(
+            expand_colors("${K256:196}✖${KRST} ${BRGB:zz}${K256:196}"),
+            format!("\x1b[38;5;196m✖{} \x1b[38;5;196m", KRST)
+        );
+        assert_eq!(PromptColor::Red.to_background(), "\x1b[41m");
+        assert_eq!(PromptColor::Bold.to_background(), KBOLD);
+    }
+
     #[test]
     fn test_prompt_color_from_str() {
         assert_eq!(PromptColor::from_key(PROMPT_KRED), PromptColor::Red);
@@ -1,6 +1,7 @@
 //! ## Git
 //!
-//! `Git` is the module which provides git repository information
+//! `Git` is the module which provides git repository information.
+//! Repositories are read through libgit2, or, with the `git-lite` feature, by reading the `.git` directory
 
 /*
 *
@@ -23,77 +24,245 @@
 *
 */
 
-extern crate git2;
+#[cfg(not(feature = "git-lite"))]
+mod libgit2;
+#[cfg(feature = "git-lite")]
+mod lite;
 
-use git2::Repository;
+#[cfg(not(feature = "git-lite"))]
+pub use libgit2::{find_repository, get_branch, get_commit, get_status, get_workdir, Repository};
+#[cfg(feature = "git-lite")]
+pub use lite::{find_repository, get_branch, get_commit, get_status, get_workdir, Repository};
+
+use super::super::cache::PromptCache;
+use super::{ModuleContext, PromptModule};
+
+use crate::utils::logger;
+
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
 
 //Keys
 pub(crate) const PROMPT_GIT_BRANCH: &str = "${GIT_BRANCH}";
 pub(crate) const PROMPT_GIT_COMMIT: &str = "${GIT_COMMIT}";
+pub(crate) const PROMPT_GIT_STATUS: &str = "${GIT_STATUS}";
 
-/// ### find_repository
+/// ## GitModule
 ///
-/// Find repository in the current path
-pub fn find_repository(wrkdir: &PathBuf) -> Option<Repository> {
-    let wrkdir_path: &Path = wrkdir.as_path();
-    //Find repository
-    match Repository::discover(wrkdir_path) {
-        Ok(repo) => Some(repo),
-        Err(_) => None,
-    }
+/// GitModule renders the branch, the commit and the status of the repository of the working directory.
+/// It is resolved on the worker pool, since looking into a large repository can take a while;
+/// the repository is kept open while the working directory doesn't change (for `cache_ttl` at most),
+/// while branch and commit are cached by working directory until HEAD changes
+pub struct GitModule {
+    branch: String,
+    commit_ref_len: usize,
+    commit_ref_prepend: String,
+    commit_ref_append: String,
+    dirty: String,
+    staged: String,
+    cache_ttl: Duration,
+    cache: Mutex<PromptCache>,
+    discover: fn(&Path) -> Option<Repository>, //Repository discovery (replaced in tests)
 }
 
-/// ### get_branch
+/// ### GitDirs
 ///
-/// Get current branch from provided repository
-pub fn get_branch(repository: &Repository) -> Option<String> {
-    let git_head = match repository.head() {
-        Ok(head) => head,
-        Err(_) => return None,
-    };
-    let shorthand = git_head.shorthand();
-    shorthand.map(std::string::ToString::to_string)
+/// GitDirs contains the directories of a repository found on the disk
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct GitDirs {
+    pub git_dir: PathBuf,    // Directory containing HEAD (`.git` or the directory of a worktree)
+    pub common_dir: PathBuf, // Directory containing refs and packed-refs
+    pub workdir: PathBuf,    // Working tree
 }
 
-/// ### get_commit
+/// ### HeadFingerprint
 ///
-/// Get current commit
-pub fn get_commit(repository: &Repository, hashlen: usize) -> Option<String> {
-    let git_head = match repository.head() {
-        Ok(head) => head,
-        Err(_) => return None,
-    };
-    let head_commit = match git_head.peel_to_commit() {
-        Ok(cmt_res) => cmt_res,
-        Err(_) => return None,
-    };
-    let commit_oid = head_commit.id();
-    Some(bytes_to_hexstr(commit_oid.as_bytes(), hashlen))
+/// HeadFingerprint identifies the state of HEAD: what it points to and the modification time of HEAD,
+/// of the reference it points to and of the packed references. It changes when switching branch or committing
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct HeadFingerprint {
+    git_dir: PathBuf,
+    head: String,
+    mtimes: Vec<Option<SystemTime>>,
 }
 
-/// ### bytes_to_hexstr
+/// ### GitStatusCounts
 ///
-/// Convert bytes to hex string representation
-fn bytes_to_hexstr(bytes: &[u8], len: usize) -> String {
-    bytes
-        .iter()
-        .map(|b| format!("{:02x}", b))
-        .collect::<Vec<String>>()
-        .join("")
-        .chars()
-        .take(len)
-        .collect()
+/// GitStatusCounts contains the amount of files modified in the working tree and staged in the index.
+/// A file which has been staged and then modified again is counted in both
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub struct GitStatusCounts {
+    pub modified: usize,
+    pub staged: usize,
+}
+
+impl GitModule {
+    /// ### should_enable
+    ///
+    /// helper which says if git module should be enabled
+    pub fn should_enable(prompt_line: &str) -> bool {
+        prompt_line.contains(PROMPT_GIT_BRANCH) || prompt_line.contains(PROMPT_GIT_COMMIT) || prompt_line.contains(PROMPT_GIT_STATUS)
+    }
+
+    /// ### new
+    ///
+    /// Instantiate a new GitModule with the provided parameters
+    pub fn new(branch: &str, commit: usize, commit_prepend: &Option<String>, commit_append: &Option<String>, dirty: &str, staged: &str, cache_ttl: Duration) -> GitModule {
+        GitModule {
+            branch: branch.to_string(),
+            commit_ref_len: commit,
+            commit_ref_prepend: commit_prepend.clone().unwrap_or_default(),
+            commit_ref_append: commit_append.clone().unwrap_or_default(),
+            dirty: dirty.to_string(),
+            staged: staged.to_string(),
+            cache_ttl,
+            cache: Mutex::new(PromptCache::new()),
+            discover: find_repository,
+        }
+    }
+
+    /// ### with_repository
+    ///
+    /// Call `f` with the repository of the working directory, taken from the cache if the working directory hasn't changed
+    fn with_repository<T>(&self, wrkdir: &Path, f: impl Fn(&Repository) -> Option<T>) -> Option<T> {
+        let discover = |wrkdir: &Path| (self.discover)(wrkdir);
+        match self.cache.lock() {
+            Ok(mut cache) => {
+                let misses: usize = cache.misses();
+                let result: Option<T> = cache.get_repository(wrkdir, self.cache_ttl, &discover).and_then(&f);
+                if cache.misses() > misses {
+                    logger::debug("git", format!("repository of {} discovered (cache hits: {}, misses: {})", wrkdir.display(), cache.hits(), cache.misses()).as_str());
+                }
+                result
+            }
+            Err(_) => discover(wrkdir).as_ref().and_then(f),
+        }
+    }
+
+    /// ### head
+    ///
+    /// Get branch and commit of the repository of the working directory; they're looked up again only if HEAD has changed
+    fn head(&self, wrkdir: &Path) -> (Option<String>, Option<String>) {
+        let fingerprint: Option<HeadFingerprint> = head_fingerprint(wrkdir);
+        if let (Some(fingerprint), Ok(cache)) = (fingerprint.as_ref(), self.cache.lock()) {
+            if let Some(head) = cache.get_git_head(wrkdir, fingerprint) {
+                return head;
+            }
+        }
+        let head: (Option<String>, Option<String>) = self
+            .with_repository(wrkdir, |repo| Some((get_branch(repo), get_commit(repo, self.commit_ref_len))))
+            .unwrap_or((None, None));
+        if let (Some(fingerprint), Ok(mut cache)) = (fingerprint, self.cache.lock()) {
+            cache.cache_git_head(wrkdir, fingerprint, head.clone());
+        }
+        head
+    }
+}
+
+impl PromptModule for GitModule {
+    fn handles(&self, key: &str) -> bool {
+        key == PROMPT_GIT_BRANCH || key == PROMPT_GIT_COMMIT || key == PROMPT_GIT_STATUS
+    }
+
+    fn resolve(&self, key: &str, ctx: &ModuleContext) -> String {
+        match key {
+            PROMPT_GIT_BRANCH => match self.head(&ctx.wrkdir).0 {
+                Some(branch) => format!("{}{}", self.branch, branch),
+                None => String::from(""),
+            },
+            PROMPT_GIT_COMMIT => match self.head(&ctx.wrkdir).1 {
+                Some(commit) => format!("{}{}{}", self.commit_ref_prepend, commit, self.commit_ref_append),
+                None => String::from(""),
+            },
+            _ => match self.with_repository(&ctx.wrkdir, get_status) {
+                Some(counts) => {
+                    let mut status: Vec<String> = Vec::with_capacity(2);
+                    if counts.modified > 0 {
+                        status.push(format!("{}{}", self.dirty, counts.modified));
+                    }
+                    if counts.staged > 0 {
+                        status.push(format!("{}{}", self.staged, counts.staged));
+                    }
+                    status.join(" ")
+                }
+                None => String::from(""),
+            },
+        }
+    }
+
+    fn is_async(&self) -> bool {
+        true
+    }
+}
+
+/// ### find_git_dirs
+///
+/// Find the git directory of the repository of the working directory, walking up to the root directory
+pub fn find_git_dirs(wrkdir: &Path) -> Option<GitDirs> {
+    let wrkdir: PathBuf = wrkdir.canonicalize().unwrap_or_else(|_| wrkdir.to_path_buf());
+    for dir in wrkdir.ancestors() {
+        let dotgit: PathBuf = dir.join(".git");
+        let git_dir: PathBuf = match dotgit.is_file() {
+            //Worktrees and submodules: `.git` is a file which refers to the git directory
+            true => match fs::read_to_string(dotgit.as_path()) {
+                Ok(content) => match content.trim().strip_prefix("gitdir:") {
+                    Some(path) => dir.join(path.trim()),
+                    None => continue,
+                },
+                Err(_) => continue,
+            },
+            false => dotgit,
+        };
+        if !git_dir.join("HEAD").is_file() {
+            continue;
+        }
+        let common_dir: PathBuf = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(path) => git_dir.join(path.trim()),
+            Err(_) => git_dir.clone(),
+        };
+        return Some(GitDirs {
+            git_dir,
+            common_dir,
+            workdir: dir.to_path_buf(),
+        });
+    }
+    None
+}
+
+/// ### head_fingerprint
+///
+/// Get the fingerprint of HEAD of the repository of the working directory; None if there's no repository
+pub fn head_fingerprint(wrkdir: &Path) -> Option<HeadFingerprint> {
+    let dirs: GitDirs = find_git_dirs(wrkdir)?;
+    let mtime = |path: PathBuf| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let head: String = fs::read_to_string(dirs.git_dir.join("HEAD")).ok()?;
+    let mut mtimes: Vec<Option<SystemTime>> = vec![mtime(dirs.git_dir.join("HEAD")), mtime(dirs.common_dir.join("packed-refs"))];
+    if let Some(name) = head.trim().strip_prefix("ref:") {
+        mtimes.push(mtime(dirs.common_dir.join(name.trim())));
+    }
+    Some(HeadFingerprint {
+        git_dir: dirs.git_dir,
+        head,
+        mtimes,
+    })
 }
 
 //@! Tests
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
 
     use super::*;
 
+    use std::path::PathBuf;
+    #[cfg(not(feature = "git-lite"))]
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     #[test]
+    #[cfg(not(feature = "git-lite"))]
     fn test_prompt_git_module_empty() {
         //Create temp directory
         let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
@@ -134,8 +303,97 @@ mod tests {
         assert_eq!(commit.unwrap().len(), 8);
     }
 
+    #[test]
+    #[cfg(not(feature = "git-lite"))]
+    fn test_prompt_git_module_status() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let repo: Repository = make_repository(&tmpdir);
+        assert_eq!(get_status(&repo).unwrap(), GitStatusCounts { modified: 2, staged: 1 });
+        //Clean repository
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let repo: Repository = Repository::init(tmpdir.path()).unwrap();
+        std::fs::write(tmpdir.path().join("untracked.txt"), "untracked\n").unwrap();
+        assert_eq!(get_status(&repo).unwrap(), GitStatusCounts { modified: 0, staged: 0 });
+    }
+
+    #[test]
+    #[cfg(not(feature = "git-lite"))]
+    fn test_prompt_git_module_head_changed() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let repo: Repository = make_repository(&tmpdir);
+        let module: GitModule = GitModule::new(&String::from("on "), 8, &None, &None, &String::from("*"), &String::from("+"), Duration::from_secs(30));
+        let ctx: ModuleContext = ModuleContext {
+            wrkdir: PathBuf::from(tmpdir.path()),
+        };
+        assert_eq!(module.resolve(PROMPT_GIT_BRANCH, &ctx), format!("on {}", get_branch(&repo).unwrap()));
+        assert_eq!(module.resolve(PROMPT_GIT_STATUS, &ctx), String::from("*2 +1"));
+        //Switching branch changes HEAD: branch is looked up again
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("feature", &commit, false).unwrap();
+        repo.set_head("refs/heads/feature").unwrap();
+        assert_eq!(module.resolve(PROMPT_GIT_BRANCH, &ctx), String::from("on feature"));
+        assert_eq!(module.resolve(PROMPT_GIT_COMMIT, &ctx), get_commit(&repo, 8).unwrap());
+    }
+
+    #[test]
+    fn test_prompt_git_module_discovery_cached() {
+        static DISCOVERIES: AtomicUsize = AtomicUsize::new(0);
+        fn counting_discovery(wrkdir: &Path) -> Option<Repository> {
+            DISCOVERIES.fetch_add(1, Ordering::SeqCst);
+            find_repository(wrkdir)
+        }
+        let mut module: GitModule = GitModule::new(&String::from("on "), 8, &None, &None, &String::from("*"), &String::from("+"), Duration::from_secs(30));
+        module.discover = counting_discovery;
+        let ctx: ModuleContext = ModuleContext {
+            wrkdir: PathBuf::from("./"),
+        };
+        //Two prompts in the same directory: the repository is discovered once
+        for _ in 0..2 {
+            assert!(!module.resolve(PROMPT_GIT_BRANCH, &ctx).is_empty());
+            assert!(!module.resolve(PROMPT_GIT_COMMIT, &ctx).is_empty());
+            let _ = module.resolve(PROMPT_GIT_STATUS, &ctx);
+        }
+        assert_eq!(DISCOVERIES.load(Ordering::SeqCst), 1);
+        assert_eq!(module.cache.lock().unwrap().misses(), 1);
+        assert!(module.cache.lock().unwrap().hits() >= 1);
+        //The working directory changed
+        let ctx: ModuleContext = ModuleContext {
+            wrkdir: PathBuf::from("/"),
+        };
+        assert!(module.resolve(PROMPT_GIT_STATUS, &ctx).is_empty());
+        assert_eq!(DISCOVERIES.load(Ordering::SeqCst), 2);
+    }
+
     #[test]
     fn test_prompt_git_repo_not_found() {
         assert!(find_repository(&PathBuf::from("/")).is_none());
     }
+
+    /// ### make_repository
+    ///
+    /// Make a repository with a commit, two files modified in the working tree and one staged file
+    #[cfg(not(feature = "git-lite"))]
+    pub(crate) fn make_repository(tmpdir: &tempfile::TempDir) -> Repository {
+        let repo: Repository = Repository::init(tmpdir.path()).unwrap();
+        for file in ["a.txt", "b.txt", "c.txt"].iter() {
+            std::fs::write(tmpdir.path().join(file), "first\n").unwrap();
+        }
+        //Commit files
+        {
+            let mut index = repo.index().unwrap();
+            index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature = git2::Signature::now("pyc", "pyc@localhost").unwrap();
+            repo.commit(Some("HEAD"), &signature, &signature, "first commit", &tree, &[]).unwrap();
+        }
+        //Stage c.txt, modify a.txt and b.txt
+        std::fs::write(tmpdir.path().join("c.txt"), "second\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("c.txt")).unwrap();
+        index.write().unwrap();
+        std::fs::write(tmpdir.path().join("a.txt"), "second\n").unwrap();
+        std::fs::remove_file(tmpdir.path().join("b.txt")).unwrap();
+        repo
+    }
 }
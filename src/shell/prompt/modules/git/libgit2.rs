@@ -0,0 +1,114 @@
+//! ## Libgit2
+//!
+//! `libgit2` is the git backend which reads repositories through libgit2
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate git2;
+
+use super::GitStatusCounts;
+
+pub use git2::Repository;
+use git2::{Status, StatusOptions};
+use std::path::{Path, PathBuf};
+
+/// ### find_repository
+///
+/// Find repository in the current path
+pub fn find_repository(wrkdir: &Path) -> Option<Repository> {
+    //Find repository
+    Repository::discover(wrkdir).ok()
+}
+
+/// ### get_workdir
+///
+/// Get the root of the working tree of the repository (the git directory for bare repositories)
+pub fn get_workdir(repository: &Repository) -> PathBuf {
+    repository.workdir().unwrap_or_else(|| repository.path()).to_path_buf()
+}
+
+/// ### get_branch
+///
+/// Get current branch from provided repository
+pub fn get_branch(repository: &Repository) -> Option<String> {
+    let git_head = match repository.head() {
+        Ok(head) => head,
+        Err(_) => return None,
+    };
+    let shorthand = git_head.shorthand();
+    shorthand.map(std::string::ToString::to_string)
+}
+
+/// ### get_commit
+///
+/// Get current commit
+pub fn get_commit(repository: &Repository, hashlen: usize) -> Option<String> {
+    let git_head = match repository.head() {
+        Ok(head) => head,
+        Err(_) => return None,
+    };
+    let head_commit = match git_head.peel_to_commit() {
+        Ok(cmt_res) => cmt_res,
+        Err(_) => return None,
+    };
+    let commit_oid = head_commit.id();
+    Some(bytes_to_hexstr(commit_oid.as_bytes(), hashlen))
+}
+
+/// ### get_status
+///
+/// Get the amount of modified and staged files; untracked and ignored files are not counted
+pub fn get_status(repository: &Repository) -> Option<GitStatusCounts> {
+    let mut options: StatusOptions = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let statuses = match repository.statuses(Some(&mut options)) {
+        Ok(statuses) => statuses,
+        Err(_) => return None,
+    };
+    let staged_mask: Status = Status::INDEX_NEW | Status::INDEX_MODIFIED | Status::INDEX_DELETED | Status::INDEX_RENAMED | Status::INDEX_TYPECHANGE;
+    let modified_mask: Status = Status::WT_MODIFIED | Status::WT_DELETED | Status::WT_RENAMED | Status::WT_TYPECHANGE;
+    let mut counts: GitStatusCounts = GitStatusCounts { modified: 0, staged: 0 };
+    for entry in statuses.iter() {
+        let status: Status = entry.status();
+        if status.intersects(staged_mask) {
+            counts.staged += 1;
+        }
+        if status.intersects(modified_mask) {
+            counts.modified += 1;
+        }
+    }
+    Some(counts)
+}
+
+/// ### bytes_to_hexstr
+///
+/// Convert bytes to hex string representation
+fn bytes_to_hexstr(bytes: &[u8], len: usize) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<String>>()
+        .join("")
+        .chars()
+        .take(len)
+        .collect()
+}
@@ -0,0 +1,308 @@
+//! ## Lite
+//!
+//! `lite` is the git backend which reads the `.git` directory by itself, for systems where libgit2 is not available.
+//! The working tree status is read through `git status`
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::{find_git_dirs, GitDirs, GitStatusCounts};
+use crate::shell::proc::process::capture_output;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+//Maximum amount of symbolic references followed while resolving a reference
+const MAX_SYMREF_DEPTH: usize = 5;
+//Maximum time `git status` can take
+const STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// ### Repository
+///
+/// Repository contains the paths of a repository found on the disk
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct Repository {
+    git_dir: PathBuf,    // Directory containing HEAD (`.git` or the directory of a worktree)
+    common_dir: PathBuf, // Directory containing refs and packed-refs
+    workdir: PathBuf,    // Working tree
+}
+
+/// ### Head
+///
+/// Head describes what HEAD points to
+enum Head {
+    Symbolic(String),
+    Detached(String),
+}
+
+/// ### find_repository
+///
+/// Find repository in the current path, walking up to the root directory
+pub fn find_repository(wrkdir: &Path) -> Option<Repository> {
+    let dirs: GitDirs = find_git_dirs(wrkdir)?;
+    Some(Repository {
+        git_dir: dirs.git_dir,
+        common_dir: dirs.common_dir,
+        workdir: dirs.workdir,
+    })
+}
+
+/// ### get_workdir
+///
+/// Get the root of the working tree of the repository
+pub fn get_workdir(repository: &Repository) -> PathBuf {
+    repository.workdir.clone()
+}
+
+/// ### get_branch
+///
+/// Get current branch from provided repository; `HEAD` if head is detached
+pub fn get_branch(repository: &Repository) -> Option<String> {
+    match read_head(repository)? {
+        Head::Symbolic(name) => {
+            //Branch without commits
+            resolve_ref(repository, name.as_str(), 0)?;
+            let shorthand: &str = ["refs/heads/", "refs/remotes/", "refs/tags/", "refs/"]
+                .iter()
+                .find_map(|prefix| name.strip_prefix(prefix))
+                .unwrap_or(name.as_str());
+            Some(String::from(shorthand))
+        }
+        Head::Detached(_) => Some(String::from("HEAD")),
+    }
+}
+
+/// ### get_commit
+///
+/// Get current commit
+pub fn get_commit(repository: &Repository, hashlen: usize) -> Option<String> {
+    let oid: String = match read_head(repository)? {
+        Head::Symbolic(name) => resolve_ref(repository, name.as_str(), 0)?,
+        Head::Detached(oid) => oid,
+    };
+    Some(oid.chars().take(hashlen).collect())
+}
+
+/// ### get_status
+///
+/// Get the amount of modified and staged files from `git status`; untracked and ignored files are not counted
+pub fn get_status(repository: &Repository) -> Option<GitStatusCounts> {
+    let output: String = capture_output(
+        &["git", "status", "--porcelain", "--untracked-files=no"],
+        repository.workdir.as_path(),
+        STATUS_TIMEOUT,
+    )
+    .ok()?;
+    Some(parse_porcelain(output.as_str()))
+}
+
+/// ### parse_porcelain
+///
+/// Count the files modified and staged in the output of `git status --porcelain`
+fn parse_porcelain(output: &str) -> GitStatusCounts {
+    let mut counts: GitStatusCounts = GitStatusCounts { modified: 0, staged: 0 };
+    for line in output.lines() {
+        let mut status = line.chars();
+        let (index, worktree): (char, char) = match (status.next(), status.next()) {
+            (Some(index), Some(worktree)) => (index, worktree),
+            _ => continue,
+        };
+        if "MADRCT".contains(index) {
+            counts.staged += 1;
+        }
+        if "MDRT".contains(worktree) {
+            counts.modified += 1;
+        }
+    }
+    counts
+}
+
+/// ### read_head
+///
+/// Read HEAD of repository
+fn read_head(repository: &Repository) -> Option<Head> {
+    let head: String = fs::read_to_string(repository.git_dir.join("HEAD")).ok()?;
+    let head: &str = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(name) => Some(Head::Symbolic(String::from(name.trim()))),
+        None if is_oid(head) => Some(Head::Detached(String::from(head))),
+        None => None,
+    }
+}
+
+/// ### resolve_ref
+///
+/// Resolve a reference to the object id it points to, looking for a loose reference first and then in packed-refs
+fn resolve_ref(repository: &Repository, name: &str, depth: usize) -> Option<String> {
+    if depth > MAX_SYMREF_DEPTH {
+        return None;
+    }
+    let loose: Option<String> = [repository.git_dir.as_path(), repository.common_dir.as_path()]
+        .iter()
+        .find_map(|dir: &&Path| fs::read_to_string(dir.join(name)).ok());
+    if let Some(content) = loose {
+        let content: &str = content.trim();
+        return match content.strip_prefix("ref:") {
+            Some(target) => resolve_ref(repository, target.trim(), depth + 1),
+            None if is_oid(content) => Some(String::from(content)),
+            None => None,
+        };
+    }
+    //Lines are `<oid> <name>`; comments start with '#' and peeled tags with '^'
+    let packed_refs: String = fs::read_to_string(repository.common_dir.join("packed-refs")).ok()?;
+    packed_refs
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .find_map(|line| {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some(oid), Some(refname)) if refname == name && is_oid(oid) => Some(String::from(oid)),
+                _ => None,
+            }
+        })
+}
+
+/// ### is_oid
+///
+/// Returns whether s is a SHA-1 or SHA-256 object id
+fn is_oid(s: &str) -> bool {
+    (s.len() == 40 || s.len() == 64) && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const OID: &str = "3f786850e387550fdab836ed7e6dc881de23001b";
+    const PACKED_OID: &str = "89e6c98d92887913cadf06b2adb97f26cde4849b";
+
+    #[test]
+    fn test_prompt_git_lite_attached() {
+        let tmpdir: tempfile::TempDir = make_fixture("ref: refs/heads/main\n");
+        write(&tmpdir, ".git/refs/heads/main", format!("{}\n", OID).as_str());
+        //Repository is found from a subdirectory
+        write(&tmpdir, "src/main.rs", "");
+        let repo: Repository = find_repository(&tmpdir.path().join("src")).unwrap();
+        assert_eq!(repo.workdir, tmpdir.path().canonicalize().unwrap());
+        assert_eq!(get_branch(&repo).unwrap(), String::from("main"));
+        assert_eq!(get_commit(&repo, 8).unwrap(), String::from("3f786850"));
+    }
+
+    #[test]
+    fn test_prompt_git_lite_detached() {
+        let tmpdir: tempfile::TempDir = make_fixture(format!("{}\n", OID).as_str());
+        let repo: Repository = find_repository(&PathBuf::from(tmpdir.path())).unwrap();
+        assert_eq!(get_branch(&repo).unwrap(), String::from("HEAD"));
+        assert_eq!(get_commit(&repo, 40).unwrap(), String::from(OID));
+    }
+
+    #[test]
+    fn test_prompt_git_lite_packed_refs() {
+        let tmpdir: tempfile::TempDir = make_fixture("ref: refs/heads/feature/packed\n");
+        write(
+            &tmpdir,
+            ".git/packed-refs",
+            format!("# pack-refs with: peeled fully-peeled sorted\n{} refs/heads/feature/packed\n{} refs/tags/v1.0\n^{}\n", PACKED_OID, OID, PACKED_OID).as_str(),
+        );
+        let repo: Repository = find_repository(&PathBuf::from(tmpdir.path())).unwrap();
+        assert_eq!(get_branch(&repo).unwrap(), String::from("feature/packed"));
+        assert_eq!(get_commit(&repo, 8).unwrap(), String::from("89e6c98d"));
+        //Loose refs win over packed refs
+        write(&tmpdir, ".git/refs/heads/feature/packed", OID);
+        assert_eq!(get_commit(&repo, 8).unwrap(), String::from("3f786850"));
+    }
+
+    #[test]
+    fn test_prompt_git_lite_unborn_branch() {
+        let tmpdir: tempfile::TempDir = make_fixture("ref: refs/heads/master\n");
+        let repo: Repository = find_repository(&PathBuf::from(tmpdir.path())).unwrap();
+        assert!(get_branch(&repo).is_none());
+        assert!(get_commit(&repo, 8).is_none());
+    }
+
+    #[test]
+    fn test_prompt_git_lite_worktree() {
+        //Main repository
+        let tmpdir: tempfile::TempDir = make_fixture("ref: refs/heads/master\n");
+        write(&tmpdir, ".git/refs/heads/master", OID);
+        write(&tmpdir, ".git/worktrees/wt/HEAD", "ref: refs/heads/topic\n");
+        write(&tmpdir, ".git/worktrees/wt/commondir", "../..\n");
+        write(&tmpdir, ".git/packed-refs", format!("{} refs/heads/topic\n", PACKED_OID).as_str());
+        //Worktree
+        write(&tmpdir, "wt/.git", "gitdir: ../.git/worktrees/wt\n");
+        let repo: Repository = find_repository(&tmpdir.path().join("wt")).unwrap();
+        assert_eq!(get_branch(&repo).unwrap(), String::from("topic"));
+        assert_eq!(get_commit(&repo, 8).unwrap(), String::from("89e6c98d"));
+    }
+
+    #[test]
+    fn test_prompt_git_lite_not_found() {
+        assert!(find_repository(&PathBuf::from("/")).is_none());
+        //Not a git directory
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        write(&tmpdir, ".git/config", "");
+        assert!(find_repository(&PathBuf::from(tmpdir.path())).is_none());
+    }
+
+    #[test]
+    fn test_prompt_git_lite_status() {
+        assert_eq!(
+            parse_porcelain("M  staged.txt\n M modified.txt\nMM both.txt\nD  removed.txt\n?? untracked.txt\nR  old.txt -> new.txt\n"),
+            GitStatusCounts { modified: 2, staged: 4 }
+        );
+        assert_eq!(parse_porcelain(""), GitStatusCounts { modified: 0, staged: 0 });
+        //Through git
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            let argv: Vec<&str> = std::iter::once("git").chain(args.iter().cloned()).collect();
+            capture_output(&argv, tmpdir.path(), Duration::from_secs(5)).unwrap();
+        };
+        git(&["init", "-q"]);
+        write(&tmpdir, "a.txt", "first\n");
+        write(&tmpdir, "b.txt", "first\n");
+        write(&tmpdir, "untracked.txt", "first\n");
+        git(&["add", "a.txt", "b.txt"]);
+        write(&tmpdir, "a.txt", "second\n");
+        let repo: Repository = find_repository(&PathBuf::from(tmpdir.path())).unwrap();
+        assert_eq!(get_status(&repo).unwrap(), GitStatusCounts { modified: 1, staged: 2 });
+    }
+
+    /// ### make_fixture
+    ///
+    /// Make a `.git` directory with the provided HEAD
+    fn make_fixture(head: &str) -> tempfile::TempDir {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        write(&tmpdir, ".git/HEAD", head);
+        fs::create_dir_all(tmpdir.path().join(".git/refs/heads")).unwrap();
+        tmpdir
+    }
+
+    /// ### write
+    ///
+    /// Write a file in the fixture, creating its parent directories
+    fn write(tmpdir: &tempfile::TempDir, path: &str, content: &str) {
+        let path: PathBuf = tmpdir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+}
@@ -55,6 +55,16 @@ pub fn language_to_str(language: Language) -> String {
             lang_str.chars().nth(2).unwrap_or(' '),
             PromptColor::Reset.to_string()
         )),
+        Language::Kazakh => format!(
+            "{}{}{}{}{}{}{}",
+            PromptColor::Yellow.to_string(),
+            lang_str.chars().next().unwrap_or(' '),
+            PromptColor::Cyan.to_string(),
+            lang_str.chars().nth(1).unwrap_or(' '),
+            PromptColor::Cyan.to_string(),
+            lang_str.chars().nth(2).unwrap_or(' '),
+            PromptColor::Reset.to_string()
+        ),
         Language::Russian => String::from(format!(
             "{}{}{}{}{}{}{}",
             PromptColor::White.to_string(),
@@ -111,6 +121,10 @@ mod tests {
         let expected_str = String::from("\x1b[37mб\x1b[32mл\x1b[31mг\x1b[0m");
         println!("{}", language_to_str(Language::Bulgarian));
         assert_eq!(language_to_str(Language::Bulgarian), expected_str);
+        // Kazakh
+        let expected_str = String::from("\x1b[33mк\x1b[36mа\x1b[36mз\x1b[0m");
+        println!("{}", language_to_str(Language::Kazakh));
+        assert_eq!(language_to_str(Language::Kazakh), expected_str);
         // Russian
         let expected_str = String::from("\x1b[37mр\x1b[34mу\x1b[31mс\x1b[0m");
         println!("{}", language_to_str(Language::Russian));
@@ -23,6 +23,60 @@
 *
 */
 
+pub(crate) mod battery;
 pub(crate) mod colors;
 pub(crate) mod git;
 pub(crate) mod language;
+pub(crate) mod time;
+pub(crate) mod venv;
+pub(crate) mod wrkdir;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// ### ModuleContext
+///
+/// ModuleContext contains the shell information a prompt module can use to resolve its value.
+/// It is sent to the worker threads, so it must own its data
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct ModuleContext {
+    pub wrkdir: PathBuf,
+}
+
+/// ## PromptModule
+///
+/// PromptModule is a prompt module which is resolved through the modules registry.
+/// Modules which can block (e.g. because they run a command or read the disk) should be marked as async:
+/// they're resolved on the worker pool, while the prompt shows their placeholder
+pub(crate) trait PromptModule: Send + Sync {
+    /// ### handles
+    ///
+    /// Returns whether the module resolves the provided key
+    fn handles(&self, key: &str) -> bool;
+
+    /// ### resolve
+    ///
+    /// Resolve the value of the key
+    fn resolve(&self, key: &str, ctx: &ModuleContext) -> String;
+
+    /// ### is_async
+    ///
+    /// Returns whether the module has to be resolved on the worker pool
+    fn is_async(&self) -> bool {
+        false
+    }
+
+    /// ### timeout
+    ///
+    /// Maximum time an async resolution can take; after that the module is rendered empty
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(2)
+    }
+
+    /// ### placeholder
+    ///
+    /// Value rendered while an async module is being resolved for the first time
+    fn placeholder(&self) -> String {
+        String::from("…")
+    }
+}
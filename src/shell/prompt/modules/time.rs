@@ -0,0 +1,186 @@
+//! ## Time
+//!
+//! `time` is the module which provides the current date and time
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use nix::libc;
+
+use crate::translator::lang::Language;
+use crate::translator::messages;
+
+//Keys
+pub(crate) const PROMPT_TIME: &str = "${TIME}";
+pub(crate) const PROMPT_DATE: &str = "${DATE}";
+
+/// ### LocalTime
+///
+/// LocalTime is a broken-down local time. `month` is in range 1-12, `weekday` is in range 0-6 (0 is Sunday)
+/// and `yearday` in range 1-366
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub struct LocalTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub weekday: u32,
+    pub yearday: u32,
+}
+
+impl LocalTime {
+    /// ### now
+    ///
+    /// Returns the current local time
+    pub fn now() -> LocalTime {
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        unsafe {
+            let now: libc::time_t = libc::time(std::ptr::null_mut());
+            libc::localtime_r(&now, &mut tm);
+        }
+        LocalTime {
+            year: tm.tm_year + 1900,
+            month: (tm.tm_mon + 1) as u32,
+            day: tm.tm_mday as u32,
+            hour: tm.tm_hour as u32,
+            minute: tm.tm_min as u32,
+            second: tm.tm_sec as u32,
+            weekday: tm.tm_wday as u32,
+            yearday: (tm.tm_yday + 1) as u32,
+        }
+    }
+
+    /// ### format
+    ///
+    /// Format time with a strftime-style format string, using the month and weekday names of the provided language.
+    /// Directives which are not supported are written as they are
+    pub fn format(&self, fmt: &str, language: Language) -> String {
+        let mut out: String = String::with_capacity(fmt.len() * 2);
+        let mut chars = fmt.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            let directive: char = match chars.next() {
+                Some(d) => d,
+                None => {
+                    out.push('%');
+                    break;
+                }
+            };
+            match directive {
+                'a' => out.push_str(messages::weekday_abbr(language, self.weekday)),
+                'A' => out.push_str(messages::weekday_name(language, self.weekday)),
+                'b' | 'h' => out.push_str(messages::month_abbr(language, self.month)),
+                'B' => out.push_str(messages::month_name(language, self.month)),
+                'd' => out.push_str(format!("{:02}", self.day).as_str()),
+                'e' => out.push_str(format!("{:>2}", self.day).as_str()),
+                'D' => out.push_str(self.format("%m/%d/%y", language).as_str()),
+                'F' => out.push_str(self.format("%Y-%m-%d", language).as_str()),
+                'H' => out.push_str(format!("{:02}", self.hour).as_str()),
+                'I' => out.push_str(format!("{:02}", (self.hour + 11) % 12 + 1).as_str()),
+                'j' => out.push_str(format!("{:03}", self.yearday).as_str()),
+                'm' => out.push_str(format!("{:02}", self.month).as_str()),
+                'M' => out.push_str(format!("{:02}", self.minute).as_str()),
+                'n' => out.push('\n'),
+                'p' => out.push_str(if self.hour < 12 { "AM" } else { "PM" }),
+                'R' => out.push_str(self.format("%H:%M", language).as_str()),
+                'S' => out.push_str(format!("{:02}", self.second).as_str()),
+                't' => out.push('\t'),
+                'T' => out.push_str(self.format("%H:%M:%S", language).as_str()),
+                'u' => out.push_str(format!("{}", (self.weekday + 6) % 7 + 1).as_str()),
+                'w' => out.push_str(format!("{}", self.weekday).as_str()),
+                'y' => out.push_str(format!("{:02}", self.year.rem_euclid(100)).as_str()),
+                'Y' => out.push_str(format!("{}", self.year).as_str()),
+                '%' => out.push('%'),
+                _ => {
+                    //Unsupported directive
+                    out.push('%');
+                    out.push(directive);
+                }
+            }
+        }
+        out
+    }
+}
+
+//@! Tests
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_prompt_time_format() {
+        let time: LocalTime = LocalTime {
+            year: 2021,
+            month: 3,
+            day: 7,
+            hour: 14,
+            minute: 5,
+            second: 9,
+            weekday: 0,
+            yearday: 66,
+        };
+        assert_eq!(time.format("%H:%M:%S", Language::Nil), String::from("14:05:09"));
+        assert_eq!(time.format("%d/%m/%Y", Language::Nil), String::from("07/03/2021"));
+        assert_eq!(time.format("%a %e %b %y", Language::Nil), String::from("Sun  7 Mar 21"));
+        assert_eq!(time.format("%A %B %j", Language::Nil), String::from("Sunday March 066"));
+        assert_eq!(time.format("%I:%M %p", Language::Nil), String::from("02:05 PM"));
+        assert_eq!(time.format("%F %T %R %D", Language::Nil), String::from("2021-03-07 14:05:09 14:05 03/07/21"));
+        assert_eq!(time.format("%u %w 100%%", Language::Nil), String::from("7 0 100%"));
+        //Unsupported directives
+        assert_eq!(time.format("%H %Q %", Language::Nil), String::from("14 %Q %"));
+        assert_eq!(time.format("время", Language::Nil), String::from("время"));
+    }
+
+    #[test]
+    fn test_prompt_time_format_languages() {
+        let time: LocalTime = LocalTime {
+            year: 2021,
+            month: 3,
+            day: 7,
+            hour: 14,
+            minute: 5,
+            second: 9,
+            weekday: 0,
+            yearday: 66,
+        };
+        assert_eq!(time.format("%a %e %b %y", Language::Russian), String::from("вс  7 мар 21"));
+        assert_eq!(time.format("%A, %d %B", Language::Russian), String::from("воскресенье, 07 март"));
+        assert_eq!(time.format("%A, %d %B", Language::Nil), String::from("Sunday, 07 March"));
+        assert_eq!(time.format("%F %T", Language::Ukrainian), String::from("2021-03-07 14:05:09"));
+    }
+
+    #[test]
+    fn test_prompt_time_now() {
+        let now: LocalTime = LocalTime::now();
+        assert!(now.year >= 2020);
+        assert!(now.month >= 1 && now.month <= 12);
+        assert!(now.day >= 1 && now.day <= 31);
+        assert!(now.hour < 24 && now.minute < 60 && now.second <= 60);
+        assert!(now.weekday < 7);
+    }
+}
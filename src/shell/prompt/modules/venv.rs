@@ -0,0 +1,112 @@
+//! ## Venv
+//!
+//! `venv` is the module which provides the name of the active Python virtual environment
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::{ModuleContext, PromptModule};
+
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+
+//Keys
+pub(crate) const PROMPT_VENV: &str = "${VENV}";
+
+/// ## VenvModule
+///
+/// VenvModule renders the name of the active virtual environment after its prefix
+pub struct VenvModule {
+    prefix: String,
+}
+
+impl VenvModule {
+    /// ### should_enable
+    ///
+    /// helper which says if virtual environment module should be enabled
+    pub fn should_enable(prompt_line: &str) -> bool {
+        prompt_line.contains(PROMPT_VENV)
+    }
+
+    /// ### new
+    ///
+    /// Instantiate a new VenvModule with the provided parameters
+    pub fn new(prefix: &str) -> VenvModule {
+        VenvModule {
+            prefix: prefix.to_string(),
+        }
+    }
+}
+
+impl PromptModule for VenvModule {
+    fn handles(&self, key: &str) -> bool {
+        key == PROMPT_VENV
+    }
+
+    fn resolve(&self, _key: &str, _ctx: &ModuleContext) -> String {
+        match get_venv() {
+            Some(name) => format!("{}{}", self.prefix, name),
+            None => String::from(""),
+        }
+    }
+}
+
+/// ### get_venv
+///
+/// Get the name of the active virtual environment from `VIRTUAL_ENV`, or from `CONDA_DEFAULT_ENV` as fallback
+pub fn get_venv() -> Option<String> {
+    venv_name(env::var_os("VIRTUAL_ENV"), env::var_os("CONDA_DEFAULT_ENV"))
+}
+
+/// ### venv_name
+///
+/// Returns the basename of the first virtual environment path which is set and not empty
+fn venv_name(virtual_env: Option<OsString>, conda_env: Option<OsString>) -> Option<String> {
+    virtual_env
+        .into_iter()
+        .chain(conda_env)
+        .filter(|path| !path.is_empty())
+        .filter_map(|path| Path::new(&path).file_name().map(|name| name.to_string_lossy().to_string()))
+        .next()
+}
+
+//@! Tests
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_prompt_venv_name() {
+        assert_eq!(
+            venv_name(Some(OsString::from("/home/user/project/.venv")), Some(OsString::from("base"))),
+            Some(String::from(".venv"))
+        );
+        assert_eq!(venv_name(None, Some(OsString::from("data-science"))), Some(String::from("data-science")));
+        assert_eq!(
+            venv_name(Some(OsString::from("")), Some(OsString::from("/opt/conda/envs/ml/"))),
+            Some(String::from("ml"))
+        );
+        assert_eq!(venv_name(None, None), None);
+    }
+}
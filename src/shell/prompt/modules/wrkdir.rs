@@ -0,0 +1,186 @@
+//! ## Wrkdir
+//!
+//! `wrkdir` is the module which shortens the working directory shown in the prompt
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+
+extern crate dirs;
+
+use super::super::cache::PromptCache;
+use super::super::status::DISCOVERY_TTL;
+use super::git::find_git_dirs;
+
+use std::path::{Component, Path, PathBuf};
+
+//Written in place of the components which have been removed
+const ELLIPSIS: &str = "…";
+
+/// ## WrkdirOptions
+///
+/// WrkdirOptions contains the options used to shorten the working directory
+pub struct WrkdirOptions {
+    max_depth: usize,     //Amount of components kept (0 keeps all of them)
+    truncate_repo: bool,  //Whether the path is relative to the root of the repository
+    home: Option<PathBuf>,
+    cache: PromptCache,   //Root of the repository of the working directories
+}
+
+impl WrkdirOptions {
+    /// ### new
+    ///
+    /// Instantiate a new WrkdirOptions with the provided parameters
+    pub fn new(max_depth: usize, truncate_repo: bool) -> WrkdirOptions {
+        WrkdirOptions {
+            max_depth,
+            truncate_repo,
+            home: dirs::home_dir(),
+            cache: PromptCache::new(),
+        }
+    }
+
+    /// ### resolve
+    ///
+    /// Get the working directory to show in the prompt
+    pub fn resolve(&mut self, wrkdir: &Path) -> String {
+        let root: Option<PathBuf> = match self.truncate_repo {
+            true => self.repository_root(wrkdir),
+            false => None,
+        };
+        format_wrkdir(wrkdir, self.home.as_deref(), root.as_deref(), self.max_depth)
+    }
+
+    /// ### repository_root
+    ///
+    /// Get the root of the repository of the working directory; the discovery is cached for `DISCOVERY_TTL`
+    fn repository_root(&mut self, wrkdir: &Path) -> Option<PathBuf> {
+        if let Some(root) = self.cache.get_repository_root(wrkdir, DISCOVERY_TTL) {
+            return root;
+        }
+        let root: Option<PathBuf> = find_git_dirs(wrkdir).map(|dirs| dirs.workdir);
+        self.cache.cache_repository_root(wrkdir, root.clone());
+        root
+    }
+}
+
+/// ### format_wrkdir
+///
+/// Format the working directory. Inside the repository whose root is `repo_root`, the path is relative to the root, prefixed by the name
+/// of the repository; otherwise the home directory is replaced by `~`.
+/// If `max_depth` is not 0, only the last `max_depth` components are kept after `…` (the name of the repository is always kept)
+pub fn format_wrkdir(wrkdir: &Path, home: Option<&Path>, repo_root: Option<&Path>, max_depth: usize) -> String {
+    if let Some(root) = repo_root {
+        let canonical: PathBuf = wrkdir.canonicalize().unwrap_or_else(|_| wrkdir.to_path_buf());
+        let relative: Option<&Path> = wrkdir.strip_prefix(root).or_else(|_| canonical.strip_prefix(root)).ok();
+        if let (Some(name), Some(relative)) = (root.file_name(), relative) {
+            let mut parts: Vec<String> = vec![name.to_string_lossy().to_string()];
+            parts.extend(truncate(components(relative), max_depth));
+            return parts.join("/");
+        }
+    }
+    if let Some(relative) = home.and_then(|home| wrkdir.strip_prefix(home).ok()) {
+        let parts: Vec<String> = components(relative);
+        return match max_depth > 0 && parts.len() > max_depth {
+            true => truncate(parts, max_depth).join("/"),
+            false => std::iter::once(String::from("~")).chain(parts).collect::<Vec<String>>().join("/"),
+        };
+    }
+    let parts: Vec<String> = components(wrkdir);
+    match wrkdir.is_absolute() && max_depth > 0 && parts.len() > max_depth {
+        true => truncate(parts, max_depth).join("/"),
+        false => wrkdir.display().to_string(),
+    }
+}
+
+/// ### components
+///
+/// Get the names of the directories of the path
+fn components(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// ### truncate
+///
+/// Keep the last `max_depth` components, after `…`; all of them are kept if `max_depth` is 0
+fn truncate(parts: Vec<String>, max_depth: usize) -> Vec<String> {
+    match max_depth > 0 && parts.len() > max_depth {
+        true => std::iter::once(String::from(ELLIPSIS)).chain(parts[parts.len() - max_depth..].iter().cloned()).collect(),
+        false => parts,
+    }
+}
+
+//@! Tests
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn test_prompt_wrkdir_home() {
+        let home: Option<&Path> = Some(Path::new("/home/omar"));
+        assert_eq!(format_wrkdir(Path::new("/home/omar/projects/pyc/src"), home, None, 0), String::from("~/projects/pyc/src"));
+        assert_eq!(format_wrkdir(Path::new("/home/omar"), home, None, 0), String::from("~"));
+        //Only whole components are replaced
+        assert_eq!(format_wrkdir(Path::new("/home/omarx/src"), home, None, 0), String::from("/home/omarx/src"));
+        assert_eq!(format_wrkdir(Path::new("/usr/local"), home, None, 0), String::from("/usr/local"));
+        assert_eq!(format_wrkdir(Path::new("./"), None, None, 0), String::from("./"));
+    }
+
+    #[test]
+    fn test_prompt_wrkdir_max_depth() {
+        let home: Option<&Path> = Some(Path::new("/home/omar"));
+        assert_eq!(format_wrkdir(Path::new("/home/omar/projects/pyc/src"), home, None, 2), String::from("…/pyc/src"));
+        assert_eq!(format_wrkdir(Path::new("/home/omar/projects/pyc"), home, None, 2), String::from("~/projects/pyc"));
+        assert_eq!(format_wrkdir(Path::new("/usr/local/share/doc"), home, None, 2), String::from("…/share/doc"));
+        assert_eq!(format_wrkdir(Path::new("/usr/local"), home, None, 2), String::from("/usr/local"));
+        assert_eq!(format_wrkdir(Path::new("/"), home, None, 1), String::from("/"));
+    }
+
+    #[test]
+    fn test_prompt_wrkdir_repository() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let root: PathBuf = tmpdir.path().join("pyc");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::create_dir_all(root.join("src/shell/prompt")).unwrap();
+        let mut options: WrkdirOptions = WrkdirOptions::new(0, true);
+        assert_eq!(options.resolve(root.join("src/shell/prompt").as_path()), String::from("pyc/src/shell/prompt"));
+        assert_eq!(options.resolve(root.as_path()), String::from("pyc"));
+        //Name of the repository is kept
+        let mut options: WrkdirOptions = WrkdirOptions::new(1, true);
+        assert_eq!(options.resolve(root.join("src/shell/prompt").as_path()), String::from("pyc/…/prompt"));
+        //Root of the repository is taken from the cache
+        fs::remove_dir_all(root.join(".git")).unwrap();
+        assert_eq!(options.resolve(root.join("src/shell/prompt").as_path()), String::from("pyc/…/prompt"));
+        //Outside of the repository
+        options.home = Some(tmpdir.path().to_path_buf());
+        assert_eq!(options.resolve(tmpdir.path().join("other").as_path()), String::from("~/other"));
+    }
+}
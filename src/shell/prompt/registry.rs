@@ -0,0 +1,456 @@
+//! ## Registry
+//!
+//! `registry` provides the registry of the prompt modules and the worker pool which resolves the async ones
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::modules::{ModuleContext, PromptModule};
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const WORKERS: usize = 2;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// ## ModuleRegistry
+///
+/// ModuleRegistry contains the registered prompt modules.
+/// Synchronous modules are resolved inline, while async modules are resolved on a shared worker pool;
+/// their results are cached by key and working directory
+pub struct ModuleRegistry {
+    modules: Vec<Arc<dyn PromptModule>>,
+    cache: HashMap<(String, PathBuf), CacheEntry>,
+    pool: Option<WorkerPool>, //Started when the first async module is resolved
+    results_tx: Sender<AsyncResult>,
+    results_rx: Receiver<AsyncResult>,
+    next_job: u64,
+    wait: Duration,            //How long the rendering of a prompt waits for async modules
+    deadline: Option<Instant>, //Until when the prompt being rendered waits for async modules
+    redraw: bool,              //A value has changed while waiting for another one
+}
+
+/// ## CacheEntry
+///
+/// CacheEntry contains the last value of an async module and the resolution in progress, if any
+struct CacheEntry {
+    value: Option<String>,
+    pending: Option<PendingJob>,
+}
+
+/// ## PendingJob
+///
+/// PendingJob describes a resolution which has been sent to the worker pool
+struct PendingJob {
+    id: u64,
+    started: Instant,
+    timeout: Duration,
+}
+
+/// ## AsyncResult
+///
+/// AsyncResult is the value of an async module sent back by a worker
+struct AsyncResult {
+    key: String,
+    wrkdir: PathBuf,
+    job_id: u64,
+    value: String,
+}
+
+/// ## WorkerPool
+///
+/// WorkerPool is a fixed set of threads which run the jobs sent to it; workers terminate when the pool is dropped
+struct WorkerPool {
+    jobs: Sender<Job>,
+}
+
+impl ModuleRegistry {
+    /// ### new
+    ///
+    /// Instantiate a new empty ModuleRegistry
+    pub fn new() -> ModuleRegistry {
+        let (results_tx, results_rx) = channel();
+        ModuleRegistry {
+            modules: Vec::new(),
+            cache: HashMap::new(),
+            pool: None,
+            results_tx,
+            results_rx,
+            next_job: 0,
+            wait: Duration::from_millis(0),
+            deadline: None,
+            redraw: false,
+        }
+    }
+
+    /// ### set_wait
+    ///
+    /// Set how long the rendering of a prompt waits for the async modules before falling back to their previous value
+    /// (or to their placeholder)
+    pub fn set_wait(&mut self, wait: Duration) {
+        self.wait = wait;
+    }
+
+    /// ### begin
+    ///
+    /// Start rendering a prompt: async modules resolved from now on are waited for until the wait time has elapsed
+    pub fn begin(&mut self) {
+        self.deadline = Some(Instant::now() + self.wait);
+    }
+
+    /// ### register
+    ///
+    /// Register a prompt module
+    pub(crate) fn register(&mut self, module: Arc<dyn PromptModule>) {
+        self.modules.push(module);
+    }
+
+    /// ### resolve
+    ///
+    /// Resolve the provided key. Returns None if no module handles the key.
+    /// Async modules return their cached value, or their placeholder if they haven't been resolved yet in this directory.
+    /// If `refresh` is true, a new resolution is started for async modules which have a cached value too
+    pub fn resolve(&mut self, key: &str, ctx: &ModuleContext, refresh: bool) -> Option<String> {
+        let module: Arc<dyn PromptModule> = self.modules.iter().find(|m| m.handles(key))?.clone();
+        if !module.is_async() {
+            return Some(module.resolve(key, ctx));
+        }
+        let (cached, pending): (Option<String>, bool) = match self.cache.get(&(String::from(key), ctx.wrkdir.clone())) {
+            Some(entry) => (entry.value.clone(), entry.pending.is_some()),
+            None => (None, false),
+        };
+        if !pending && (cached.is_none() || refresh) {
+            self.schedule(module.clone(), key, ctx);
+        }
+        let cached: Option<String> = match refresh {
+            true => self.wait_for(key, ctx).or(cached),
+            false => cached,
+        };
+        Some(cached.unwrap_or_else(|| module.placeholder()))
+    }
+
+    /// ### poll
+    ///
+    /// Collect the values resolved by the workers and expire the resolutions which timed out.
+    /// Returns whether a value rendered in the prompt has changed (i.e. the prompt has to be redrawn)
+    pub fn poll(&mut self) -> bool {
+        let mut changed: bool = std::mem::replace(&mut self.redraw, false);
+        while let Ok(result) = self.results_rx.try_recv() {
+            changed |= self.apply(result);
+        }
+        for entry in self.cache.values_mut() {
+            let expired: bool = match entry.pending.as_ref() {
+                Some(job) => job.started.elapsed() >= job.timeout,
+                None => false,
+            };
+            if expired {
+                entry.pending = None;
+                //Keep the previous value if any; otherwise replace the placeholder with nothing
+                if entry.value.is_none() {
+                    entry.value = Some(String::new());
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// ### wait_for
+    ///
+    /// Wait until the resolution of the key in progress has completed, has timed out or the deadline of the prompt has been reached.
+    /// Returns the value if it has been resolved in time
+    fn wait_for(&mut self, key: &str, ctx: &ModuleContext) -> Option<String> {
+        let entry_key: (String, PathBuf) = (String::from(key), ctx.wrkdir.clone());
+        loop {
+            let entry: &CacheEntry = self.cache.get(&entry_key)?;
+            let deadline: Instant = match entry.pending.as_ref() {
+                Some(job) => std::cmp::min(self.deadline?, job.started + job.timeout),
+                None => return entry.value.clone(),
+            };
+            let now: Instant = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            match self.results_rx.recv_timeout(deadline - now) {
+                //Values of other keys may have already been rendered with their previous value
+                Ok(result) => {
+                    let other: bool = result.key != entry_key.0 || result.wrkdir != entry_key.1;
+                    if self.apply(result) && other {
+                        self.redraw = true;
+                    }
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+
+    /// ### apply
+    ///
+    /// Store the value resolved by a worker; results of expired resolutions are discarded. Returns whether the value has changed
+    fn apply(&mut self, result: AsyncResult) -> bool {
+        let entry: &mut CacheEntry = match self.cache.get_mut(&(result.key, result.wrkdir)) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        if entry.pending.as_ref().map(|job| job.id) != Some(result.job_id) {
+            return false;
+        }
+        entry.pending = None;
+        match entry.value.as_ref() != Some(&result.value) {
+            true => {
+                entry.value = Some(result.value);
+                true
+            }
+            false => false,
+        }
+    }
+
+    /// ### schedule
+    ///
+    /// Send the resolution of an async module to the worker pool
+    fn schedule(&mut self, module: Arc<dyn PromptModule>, key: &str, ctx: &ModuleContext) {
+        if self.pool.is_none() {
+            self.pool = Some(WorkerPool::new(WORKERS));
+        }
+        self.next_job += 1;
+        let job_id: u64 = self.next_job;
+        let timeout: Duration = module.timeout();
+        let key: String = String::from(key);
+        let ctx: ModuleContext = ctx.clone();
+        let results_tx: Sender<AsyncResult> = self.results_tx.clone();
+        let entry: &mut CacheEntry = self.cache.entry((key.clone(), ctx.wrkdir.clone())).or_insert(CacheEntry {
+            value: None,
+            pending: None,
+        });
+        entry.pending = Some(PendingJob {
+            id: job_id,
+            started: Instant::now(),
+            timeout,
+        });
+        //Unwrap is safe, pool has been started before
+        self.pool.as_ref().unwrap().execute(Box::new(move || {
+            let value: String = module.resolve(key.as_str(), &ctx);
+            let _ = results_tx.send(AsyncResult {
+                key,
+                wrkdir: ctx.wrkdir,
+                job_id,
+                value,
+            });
+        }));
+    }
+}
+
+impl WorkerPool {
+    /// ### new
+    ///
+    /// Start a new WorkerPool with the provided amount of workers
+    fn new(size: usize) -> WorkerPool {
+        let (jobs_tx, jobs_rx) = channel::<Job>();
+        let jobs_rx: Arc<Mutex<Receiver<Job>>> = Arc::new(Mutex::new(jobs_rx));
+        for _ in 0..size {
+            let jobs_rx: Arc<Mutex<Receiver<Job>>> = Arc::clone(&jobs_rx);
+            thread::spawn(move || loop {
+                //Lock is released before running the job
+                let job = match jobs_rx.lock() {
+                    Ok(jobs_rx) => jobs_rx.recv(),
+                    Err(_) => break,
+                };
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break, //Pool has been dropped
+                }
+            });
+        }
+        WorkerPool { jobs: jobs_tx }
+    }
+
+    /// ### execute
+    ///
+    /// Run a job on the first available worker
+    fn execute(&self, job: Job) {
+        let _ = self.jobs.send(job);
+    }
+}
+
+//@! Tests
+
+#[cfg(test)]
+pub(crate) mod tests {
+
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread::sleep;
+
+    /// ### SlowModule
+    ///
+    /// Async module which takes `delay` to resolve to "slow"
+    pub(crate) struct SlowModule {
+        pub delay: Duration,
+        pub timeout: Duration,
+        pub calls: AtomicUsize,
+    }
+
+    impl PromptModule for SlowModule {
+        fn handles(&self, key: &str) -> bool {
+            key == "${SLOW}"
+        }
+
+        fn resolve(&self, _key: &str, _ctx: &ModuleContext) -> String {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            sleep(self.delay);
+            String::from("slow")
+        }
+
+        fn is_async(&self) -> bool {
+            true
+        }
+
+        fn timeout(&self) -> Duration {
+            self.timeout
+        }
+    }
+
+    /// ### FastModule
+    ///
+    /// Synchronous module which resolves to the name of the working directory
+    pub(crate) struct FastModule;
+
+    impl PromptModule for FastModule {
+        fn handles(&self, key: &str) -> bool {
+            key == "${FAST}"
+        }
+
+        fn resolve(&self, _key: &str, ctx: &ModuleContext) -> String {
+            format!("fast:{}", ctx.wrkdir.display())
+        }
+    }
+
+    pub(crate) fn slow_module(delay: u64, timeout: u64) -> Arc<SlowModule> {
+        Arc::new(SlowModule {
+            delay: Duration::from_millis(delay),
+            timeout: Duration::from_millis(timeout),
+            calls: AtomicUsize::new(0),
+        })
+    }
+
+    /// ### wait_poll
+    ///
+    /// Poll registry until a value changes or 2 seconds have elapsed
+    pub(crate) fn wait_poll<F: FnMut() -> bool>(mut poll: F) -> bool {
+        let started: Instant = Instant::now();
+        while started.elapsed() < Duration::from_secs(2) {
+            if poll() {
+                return true;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    fn context(wrkdir: &str) -> ModuleContext {
+        ModuleContext {
+            wrkdir: PathBuf::from(wrkdir),
+        }
+    }
+
+    #[test]
+    fn test_prompt_registry_async() {
+        let mut registry: ModuleRegistry = ModuleRegistry::new();
+        let module: Arc<SlowModule> = slow_module(100, 2000);
+        registry.register(module.clone());
+        //Unknown key
+        assert!(registry.resolve("${USER}", &context("/tmp"), true).is_none());
+        //Placeholder first
+        assert_eq!(registry.resolve("${SLOW}", &context("/tmp"), true).unwrap(), String::from("…"));
+        //Resolution in progress is not started again
+        assert_eq!(registry.resolve("${SLOW}", &context("/tmp"), true).unwrap(), String::from("…"));
+        assert!(!registry.poll());
+        //Then value
+        assert!(wait_poll(|| registry.poll()));
+        assert_eq!(registry.resolve("${SLOW}", &context("/tmp"), false).unwrap(), String::from("slow"));
+        assert_eq!(module.calls.load(Ordering::SeqCst), 1);
+        //Cached value is returned while refreshing; unchanged value doesn't require a redraw
+        assert_eq!(registry.resolve("${SLOW}", &context("/tmp"), true).unwrap(), String::from("slow"));
+        sleep(Duration::from_millis(300));
+        assert!(!registry.poll());
+        assert_eq!(module.calls.load(Ordering::SeqCst), 2);
+        //Values are cached by working directory
+        assert_eq!(registry.resolve("${SLOW}", &context("/home"), true).unwrap(), String::from("…"));
+        assert!(wait_poll(|| registry.poll()));
+        assert_eq!(registry.resolve("${SLOW}", &context("/home"), false).unwrap(), String::from("slow"));
+    }
+
+    #[test]
+    fn test_prompt_registry_sync() {
+        let mut registry: ModuleRegistry = ModuleRegistry::new();
+        registry.register(Arc::new(FastModule));
+        //Fast modules are never deferred
+        assert_eq!(registry.resolve("${FAST}", &context("/tmp"), true).unwrap(), String::from("fast:/tmp"));
+        assert_eq!(registry.resolve("${FAST}", &context("/home"), true).unwrap(), String::from("fast:/home"));
+        assert!(registry.pool.is_none());
+        assert!(registry.cache.is_empty());
+        assert!(!registry.poll());
+    }
+
+    #[test]
+    fn test_prompt_registry_wait() {
+        let mut registry: ModuleRegistry = ModuleRegistry::new();
+        let module: Arc<SlowModule> = slow_module(300, 2000);
+        registry.register(module.clone());
+        registry.register(Arc::new(FastModule));
+        registry.set_wait(Duration::from_millis(50));
+        //Module doesn't answer in time: placeholder is rendered
+        registry.begin();
+        assert_eq!(registry.resolve("${SLOW}", &context("/tmp"), true).unwrap(), String::from("…"));
+        assert!(wait_poll(|| registry.poll()));
+        //Module doesn't answer in time again: previous value is rendered
+        registry.begin();
+        assert_eq!(registry.resolve("${SLOW}", &context("/tmp"), true).unwrap(), String::from("slow"));
+        assert_eq!(module.calls.load(Ordering::SeqCst), 2);
+        //Module answers in time
+        sleep(Duration::from_millis(400));
+        registry.set_wait(Duration::from_millis(1000));
+        registry.begin();
+        assert_eq!(registry.resolve("${SLOW}", &context("/home"), true).unwrap(), String::from("slow"));
+        //Redraws never wait
+        registry.begin();
+        assert_eq!(registry.resolve("${SLOW}", &context("/srv"), false).unwrap(), String::from("…"));
+    }
+
+    #[test]
+    fn test_prompt_registry_timeout() {
+        let mut registry: ModuleRegistry = ModuleRegistry::new();
+        registry.register(slow_module(500, 50));
+        assert_eq!(registry.resolve("${SLOW}", &context("/tmp"), true).unwrap(), String::from("…"));
+        //Placeholder is removed once the module times out
+        assert!(wait_poll(|| registry.poll()));
+        assert_eq!(registry.resolve("${SLOW}", &context("/tmp"), false).unwrap(), String::from(""));
+        //Late result is discarded
+        sleep(Duration::from_millis(600));
+        assert!(!registry.poll());
+        assert_eq!(registry.resolve("${SLOW}", &context("/tmp"), false).unwrap(), String::from(""));
+    }
+}
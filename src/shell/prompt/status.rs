@@ -0,0 +1,174 @@
+//! ## Status
+//!
+//! `status` resolves the values the prompt is made of, without colors, so that they can be shown by external status bars (e.g. tmux)
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+
+use super::cache::PromptCache;
+use super::modules::{git, venv};
+use super::ShellProps;
+use crate::translator::lang::Language;
+use crate::translator::trace::json_string;
+
+use regex::Regex;
+use std::time::Duration;
+
+//Time after which the repository of a working directory is discovered again
+pub const DISCOVERY_TTL: Duration = Duration::from_secs(30);
+//Template used for the plain status line, when none is provided
+pub const DEFAULT_TEMPLATE: &str = "${GIT_BRANCH} ${GIT_COMMIT}";
+
+/// ## StatusReport
+///
+/// StatusReport contains the values of the status keys (without `${}`), in the order they're printed
+pub struct StatusReport {
+    pub values: Vec<(String, String)>,
+    pub cached_repository: bool, //Whether the repository has been taken from the cache, instead of being discovered
+}
+
+impl StatusReport {
+    /// ### get
+    ///
+    /// Returns the value of a key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// ### to_json
+    ///
+    /// Serialize the values to a JSON object on a single line
+    pub fn to_json(&self) -> String {
+        let fields: Vec<String> = self.values.iter().map(|(k, v)| format!("{}:{}", json_string(k), json_string(v))).collect();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// ### render
+    ///
+    /// Replace the `${KEY}` of the template with their values; unknown keys are kept
+    pub fn render(&self, template: &str) -> String {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(super::PROMPT_KEY_REGEX).unwrap();
+        }
+        RE.replace_all(template, |captures: &regex::Captures| match self.get(&captures[1]) {
+            Some(value) => String::from(value),
+            None => String::from(&captures[0]),
+        })
+        .trim()
+        .to_string()
+    }
+}
+
+/// ### resolve_status
+///
+/// Resolve the status values for the shell props. The repository of the working directory is taken from the cache if it has been
+/// discovered in the last `DISCOVERY_TTL`; otherwise it's discovered and cached
+pub(crate) fn resolve_status(props: &ShellProps, language: Language, commit_len: usize, cache: &mut PromptCache) -> StatusReport {
+    let (repository, cached): (Option<git::Repository>, bool) = match cache.get_repository_root(props.wrkdir.as_path(), DISCOVERY_TTL) {
+        Some(root) => (root.and_then(|root| git::find_repository(&root)), true),
+        None => {
+            let repository: Option<git::Repository> = git::find_repository(&props.wrkdir);
+            cache.cache_repository_root(props.wrkdir.as_path(), repository.as_ref().map(git::get_workdir));
+            (repository, false)
+        }
+    };
+    let (branch, commit, counts) = match repository.as_ref() {
+        Some(repository) => (git::get_branch(repository), git::get_commit(repository, commit_len), git::get_status(repository)),
+        None => (None, None, None),
+    };
+    let values: Vec<(&str, String)> = vec![
+        ("USER", props.username.clone()),
+        ("HOSTNAME", props.hostname.clone()),
+        ("WRKDIR", props.wrkdir.display().to_string()),
+        ("RC", props.exit_status.to_string()),
+        ("LANG", language.to_string()),
+        ("VENV", venv::get_venv().unwrap_or_default()),
+        ("GIT_BRANCH", branch.unwrap_or_default()),
+        ("GIT_COMMIT", commit.unwrap_or_default()),
+        ("GIT_MODIFIED", counts.map(|c| c.modified.to_string()).unwrap_or_default()),
+        ("GIT_STAGED", counts.map(|c| c.staged.to_string()).unwrap_or_default()),
+    ];
+    StatusReport {
+        values: values.into_iter().map(|(k, v)| (String::from(k), v)).collect(),
+        cached_repository: cached,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::path::PathBuf;
+
+    fn props(wrkdir: PathBuf) -> ShellProps {
+        let mut props: ShellProps = ShellProps::new(String::from("localhost"), String::from("omar"), wrkdir);
+        props.exit_status = 2;
+        props
+    }
+
+    #[test]
+    #[cfg(not(feature = "git-lite"))]
+    fn test_prompt_status_git() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let repo: git::Repository = git::tests::make_repository(&tmpdir);
+        let branch: String = git::get_branch(&repo).unwrap();
+        std::fs::create_dir(tmpdir.path().join("src")).unwrap();
+        let wrkdir: PathBuf = tmpdir.path().join("src");
+        let cache_file: PathBuf = tmpdir.path().join("prompt-cache");
+        //First invocation: the repository is discovered
+        let mut cache: PromptCache = PromptCache::load(cache_file.as_path());
+        let report: StatusReport = resolve_status(&props(wrkdir.clone()), Language::Russian, 8, &mut cache);
+        assert!(!report.cached_repository);
+        assert!(cache.save(cache_file.as_path()).is_ok());
+        assert_eq!(report.get("GIT_BRANCH"), Some(branch.as_str()));
+        assert_eq!(report.get("GIT_COMMIT").unwrap().len(), 8);
+        assert_eq!(report.get("GIT_MODIFIED"), Some("2"));
+        assert_eq!(report.get("GIT_STAGED"), Some("1"));
+        assert_eq!(report.get("RC"), Some("2"));
+        let json: String = report.to_json();
+        assert!(json.starts_with("{\"USER\":\"omar\",\"HOSTNAME\":\"localhost\","));
+        assert!(json.contains(format!("\"GIT_BRANCH\":\"{}\"", branch).as_str()));
+        assert!(json.ends_with("\"GIT_MODIFIED\":\"2\",\"GIT_STAGED\":\"1\"}"));
+        //Second invocation: the repository is taken from the cache file
+        let mut cache: PromptCache = PromptCache::load(cache_file.as_path());
+        let cached: StatusReport = resolve_status(&props(wrkdir.clone()), Language::Russian, 8, &mut cache);
+        assert!(cached.cached_repository);
+        assert_eq!(cached.values, report.values);
+        //Plain line
+        assert_eq!(cached.render("${GIT_BRANCH} +${GIT_STAGED} ~${GIT_MODIFIED} ${UNKNOWN}"), format!("{} +1 ~2 ${{UNKNOWN}}", branch));
+    }
+
+    #[test]
+    fn test_prompt_status_no_repository() {
+        let mut cache: PromptCache = PromptCache::new();
+        let report: StatusReport = resolve_status(&props(PathBuf::from("/")), Language::Russian, 8, &mut cache);
+        assert!(!report.cached_repository);
+        assert_eq!(report.get("GIT_BRANCH"), Some(""));
+        assert_eq!(report.get("WRKDIR"), Some("/"));
+        assert_eq!(report.render(DEFAULT_TEMPLATE), String::new());
+        //No repository is cached too
+        let report: StatusReport = resolve_status(&props(PathBuf::from("/")), Language::Russian, 8, &mut cache);
+        assert!(report.cached_repository);
+        assert_eq!(report.get("GIT_COMMIT"), Some(""));
+    }
+}
@@ -0,0 +1,156 @@
+//! ## Session
+//!
+//! `session` provides the state of the shell session shown by the prompt, which is kept up to date after every command
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::jobs::JobsSummary;
+use super::ShellProps;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// ### ShellSession
+///
+/// ShellSession owns the properties of the session shown by the prompt.
+/// Username and hostname don't change during a session: they're looked up once, when the session is created;
+/// the working directory, the exit status and the duration are recorded after every command
+pub struct ShellSession {
+    props: ShellProps,
+}
+
+impl ShellSession {
+    /// ### new
+    ///
+    /// Instantiates a new ShellSession in the provided working directory, looking up the username and the hostname
+    pub fn new(wrkdir: PathBuf) -> ShellSession {
+        ShellSession::with_identity(get_hostname(), whoami::username(), wrkdir)
+    }
+
+    /// ### with_identity
+    ///
+    /// Instantiates a new ShellSession with the provided hostname and username
+    pub fn with_identity(hostname: String, username: String, wrkdir: PathBuf) -> ShellSession {
+        ShellSession {
+            props: ShellProps::new(hostname, username, wrkdir),
+        }
+    }
+
+    /// ### record
+    ///
+    /// Record the outcome of the last command: its exit status, how long it took and the working directory it left
+    pub fn record(&mut self, exit_status: u8, elapsed_time: Duration, wrkdir: PathBuf) {
+        self.props.exit_status = exit_status;
+        self.props.elapsed_time = elapsed_time;
+        self.props.wrkdir = wrkdir;
+    }
+
+    /// ### set_elapsed_time
+    ///
+    /// Set the duration of the last command only (e.g. of a builtin, which doesn't change the exit status of the shell)
+    pub fn set_elapsed_time(&mut self, elapsed_time: Duration) {
+        self.props.elapsed_time = elapsed_time;
+    }
+
+    /// ### set_degraded
+    ///
+    /// Set the features which have been disabled at startup
+    pub fn set_degraded(&mut self, features: Vec<String>) {
+        self.props.degraded = features;
+    }
+
+    /// ### set_jobs
+    ///
+    /// Set the amount of jobs in background, as shown by the prompt
+    pub fn set_jobs(&mut self, jobs: JobsSummary) {
+        self.props.jobs = jobs;
+    }
+
+    /// ### props
+    ///
+    /// Returns the properties of the session, as rendered by the prompt
+    pub(crate) fn props(&self) -> &ShellProps {
+        &self.props
+    }
+}
+
+/// ### get_hostname
+///
+/// Get hostname without domain
+fn get_hostname() -> String {
+    let full_hostname: String = whoami::hostname();
+    let tokens: Vec<&str> = full_hostname.split(".").collect();
+    String::from(*tokens.first().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_session_new() {
+        let session: ShellSession = ShellSession::new(PathBuf::from("/tmp"));
+        assert!(!session.props().username.is_empty());
+        assert!(!session.props().hostname.is_empty());
+        assert!(!session.props().hostname.contains('.'));
+        assert_eq!(session.props().wrkdir, PathBuf::from("/tmp"));
+        assert_eq!(session.props().exit_status, 0);
+        assert_eq!(session.props().elapsed_time, Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_session_hostname() {
+        assert_ne!(get_hostname(), String::from(""));
+    }
+
+    #[test]
+    fn test_session_command_cycles() {
+        let mut session: ShellSession = ShellSession::with_identity(String::from("computer"), String::from("root"), PathBuf::from("/home/root"));
+        session.set_degraded(vec![String::from("history")]);
+        //`cd /tmp`
+        session.record(0, Duration::from_millis(3), PathBuf::from("/tmp"));
+        assert_eq!(session.props().wrkdir, PathBuf::from("/tmp"));
+        assert_eq!(session.props().exit_status, 0);
+        assert_eq!(session.props().elapsed_time, Duration::from_millis(3));
+        //`sleep 2; false`
+        session.record(1, Duration::from_millis(2004), PathBuf::from("/tmp"));
+        assert_eq!(session.props().wrkdir, PathBuf::from("/tmp"));
+        assert_eq!(session.props().exit_status, 1);
+        assert_eq!(session.props().elapsed_time, Duration::from_millis(2004));
+        //A builtin keeps the exit status
+        session.set_elapsed_time(Duration::from_millis(1));
+        assert_eq!(session.props().exit_status, 1);
+        assert_eq!(session.props().elapsed_time, Duration::from_millis(1));
+        //`cd -`
+        session.record(0, Duration::from_millis(2), PathBuf::from("/home/root"));
+        assert_eq!(session.props().wrkdir, PathBuf::from("/home/root"));
+        assert_eq!(session.props().exit_status, 0);
+        //Identity never changes
+        assert_eq!(session.props().username, String::from("root"));
+        assert_eq!(session.props().hostname, String::from("computer"));
+        assert_eq!(session.props().degraded, vec![String::from("history")]);
+        //Jobs
+        session.set_jobs(JobsSummary { running: 2, stopped: 1 });
+        assert_eq!(session.props().jobs, JobsSummary { running: 2, stopped: 1 });
+    }
+}
@@ -0,0 +1,211 @@
+//! ## Suggestion
+//!
+//! `suggestion` looks for the executables whose name is similar to a command which has not been found
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::completion::index::CommandIndex;
+
+use crate::translator::ioprocessor::IOProcessor;
+
+use std::ffi::OsString;
+
+/// Maximum amount of commands suggested
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// ### CommandSuggester
+///
+/// CommandSuggester suggests the executables in `PATH` similar to a command which has not been found.
+/// The index of the executables is built at the first suggestion and kept for the whole session;
+/// it is built again only when `PATH` or one of its directories changes, or when it is cleared
+pub struct CommandSuggester {
+    index: Option<CommandIndex>,
+}
+
+impl CommandSuggester {
+    /// ### new
+    ///
+    /// Instantiates a new CommandSuggester; the index is built at the first suggestion
+    pub fn new() -> CommandSuggester {
+        CommandSuggester { index: None }
+    }
+
+    /// ### clear
+    ///
+    /// Drop the index of the executables, which is built again at the next suggestion
+    pub fn clear(&mut self) {
+        self.index = None;
+    }
+
+    /// ### suggest
+    ///
+    /// Returns up to `MAX_SUGGESTIONS` executables in `path` whose name is close to `command`, the closest first.
+    /// The command is compared as it is and as it would look if it had been typed with the cyrillic letters
+    /// which look like the latin ones (e.g. `sat`, typed as `сат`, suggests `cat`)
+    pub fn suggest(&mut self, command: &str, path: Option<OsString>, processor: &IOProcessor) -> Vec<String> {
+        if command.is_empty() || command.contains('/') {
+            return Vec::new();
+        }
+        if self.index.as_ref().map(|index| index.is_stale(&path)).unwrap_or(true) {
+            self.index = Some(CommandIndex::build(path));
+        }
+        let index: &CommandIndex = self.index.as_ref().unwrap();
+        //The command exists: it has failed for another reason
+        if index.contains(command) {
+            return Vec::new();
+        }
+        let mut variants: Vec<String> = vec![String::from(command), processor.text_to_latin(&String::from(command))];
+        variants.push(lookalike_to_latin(&processor.text_to_cyrillic(&String::from(command)), processor));
+        variants.dedup();
+        let max_distance: usize = match command.chars().count() {
+            0..=4 => 1,
+            _ => 2,
+        };
+        let mut candidates: Vec<(usize, &String)> = index
+            .commands()
+            .iter()
+            .filter_map(|candidate| {
+                variants
+                    .iter()
+                    .map(|variant| edit_distance(variant.as_str(), candidate.as_str()))
+                    .min()
+                    .filter(|distance| *distance <= max_distance)
+                    .map(|distance| (distance, candidate))
+            })
+            .collect();
+        //Commands are sorted by name, the sort is stable
+        candidates.sort_by_key(|(distance, _)| *distance);
+        candidates.into_iter().take(MAX_SUGGESTIONS).map(|(_, candidate)| candidate.clone()).collect()
+    }
+}
+
+impl Default for CommandSuggester {
+    fn default() -> Self {
+        CommandSuggester::new()
+    }
+}
+
+/// ### edit_distance
+///
+/// Levenshtein distance between a and b
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous: usize = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current: usize = row[j + 1];
+            row[j + 1] = match ca == *cb {
+                true => previous,
+                false => 1 + std::cmp::min(previous, std::cmp::min(row[j], row[j + 1])),
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// ### lookalike_to_latin
+///
+/// Replace the cyrillic letters of text with the latin letters they look like;
+/// the other letters are transliterated
+fn lookalike_to_latin(text: &str, processor: &IOProcessor) -> String {
+    text.chars()
+        .map(|ch| match ch {
+            'а' => String::from("a"),
+            'в' => String::from("b"),
+            'е' | 'ё' => String::from("e"),
+            'і' => String::from("i"),
+            'ј' => String::from("j"),
+            'к' => String::from("k"),
+            'м' => String::from("m"),
+            'н' => String::from("h"),
+            'о' => String::from("o"),
+            'р' => String::from("p"),
+            'с' => String::from("c"),
+            'ѕ' => String::from("s"),
+            'т' => String::from("t"),
+            'у' => String::from("y"),
+            'х' => String::from("x"),
+            _ => processor.text_to_latin(&ch.to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::translator::lang::Language;
+    use crate::translator::new_translator;
+
+    use std::fs::{self, File};
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_suggestion_edit_distance() {
+        assert_eq!(edit_distance("gti", "git"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("пинг", "пинг"), 0);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("pyhton", "python"), 2);
+    }
+
+    #[test]
+    fn test_suggestion_suggest() {
+        let tmpdir: TempDir = TempDir::new().unwrap();
+        for (file, mode) in [("cat", 0o755), ("cut", 0o755), ("git", 0o755), ("gist", 0o755), ("python", 0o755), ("pythn", 0o644)].iter() {
+            let path: PathBuf = tmpdir.path().join(file);
+            File::create(path.as_path()).unwrap();
+            fs::set_permissions(path.as_path(), fs::Permissions::from_mode(*mode)).unwrap();
+        }
+        let path: Option<OsString> = Some(OsString::from(tmpdir.path().as_os_str()));
+        let processor: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+        let mut suggester: CommandSuggester = CommandSuggester::new();
+        assert_eq!(suggester.suggest("cta", path.clone(), &processor), Vec::<String>::new());
+        assert_eq!(suggester.suggest("gitt", path.clone(), &processor), vec![String::from("gist"), String::from("git")]);
+        assert_eq!(suggester.suggest("pyhton", path.clone(), &processor), vec![String::from("python")]);
+        assert_eq!(suggester.suggest("czt", path.clone(), &processor), vec![String::from("cat"), String::from("cut")]);
+        //Cyrillic lookalikes
+        assert_eq!(suggester.suggest("sat", path.clone(), &processor), vec![String::from("cat"), String::from("cut")]);
+        //Untranslated command
+        assert_eq!(suggester.suggest("гит", path.clone(), &processor), vec![String::from("git"), String::from("gist")]);
+        //Existing commands and paths
+        assert!(suggester.suggest("git", path.clone(), &processor).is_empty());
+        assert!(suggester.suggest("/usr/bin/gitt", path.clone(), &processor).is_empty());
+        assert!(suggester.suggest("", path.clone(), &processor).is_empty());
+        //The index is cached until PATH changes
+        File::create(tmpdir.path().join("gitk")).unwrap();
+        fs::set_permissions(tmpdir.path().join("gitk"), fs::Permissions::from_mode(0o755)).unwrap();
+        suggester.clear();
+        assert_eq!(
+            suggester.suggest("gitt", path.clone(), &processor),
+            vec![String::from("gist"), String::from("git"), String::from("gitk")]
+        );
+        assert!(suggester.suggest("gitt", None, &processor).is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+//! ## Terminal
+//!
+//! `Terminal` keeps the controlling terminal in raw mode while pyc is interactive
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+
+extern crate termios;
+
+use crate::utils::console;
+
+use std::os::unix::io::RawFd;
+
+const STDIN_FILENO: RawFd = 0;
+
+/// ### Terminal
+///
+/// Terminal puts the terminal in raw mode when it is instantiated and restores the original termios when it is dropped,
+/// even if pyc is panicking. Signals (Ctrl+C) and output processing are left enabled, so that the output is printed as before
+pub struct Terminal {
+    original: termios::Termios,
+}
+
+impl Terminal {
+    /// ### enter_raw
+    ///
+    /// Put the terminal in raw mode: input is not line-buffered, nor echoed by the terminal.
+    /// Returns None if stdin is not a terminal
+    pub fn enter_raw() -> Option<Terminal> {
+        if !nix::unistd::isatty(STDIN_FILENO).unwrap_or(false) {
+            return None;
+        }
+        let original: termios::Termios = termios::Termios::from_fd(STDIN_FILENO).ok()?;
+        let mut raw: termios::Termios = original;
+        raw.c_lflag &= !(termios::ICANON | termios::ECHO | termios::IEXTEN);
+        raw.c_cc[termios::VMIN] = 1;
+        raw.c_cc[termios::VTIME] = 0;
+        if termios::tcsetattr(STDIN_FILENO, termios::TCSANOW, &raw).is_err() {
+            return None;
+        }
+        console::set_raw_mode(true);
+        Some(Terminal { original })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = termios::tcsetattr(STDIN_FILENO, termios::TCSADRAIN, &self.original);
+        console::set_raw_mode(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_terminal_enter_raw_not_a_tty() {
+        //Tests don't run in a terminal
+        if !nix::unistd::isatty(STDIN_FILENO).unwrap_or(false) {
+            assert!(Terminal::enter_raw().is_none());
+            assert!(!console::raw_mode());
+        }
+    }
+}
@@ -109,6 +109,20 @@ impl UnixSignal {
         }
     }
 
+    /// ### from_name
+    /// 
+    /// Convert the name of a signal, with or without `SIG` (e.g. `KILL`, `sigterm`), or its number to a Unix Signal
+    pub fn from_name(name: &str) -> Option<UnixSignal> {
+        if let Ok(sig) = name.parse::<u8>() {
+            return UnixSignal::from_u8(sig);
+        }
+        let name: String = name.to_uppercase();
+        let name: &str = name.strip_prefix("SIG").unwrap_or(name.as_str());
+        (1..=31)
+            .filter_map(UnixSignal::from_u8)
+            .find(|sig| format!("{:?}", sig).to_uppercase() == format!("SIG{}", name))
+    }
+
     #[cfg(target_os = "macos")]
     #[cfg(not(tarpaulin_include))]
     /// ### to_nix_signal
@@ -196,6 +210,19 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_unixsignal_from_name() {
+        assert_eq!(UnixSignal::from_name("KILL"), Some(UnixSignal::Sigkill));
+        assert_eq!(UnixSignal::from_name("SIGKILL"), Some(UnixSignal::Sigkill));
+        assert_eq!(UnixSignal::from_name("term"), Some(UnixSignal::Sigterm));
+        assert_eq!(UnixSignal::from_name("SigUsr1"), Some(UnixSignal::Sigusr1));
+        assert_eq!(UnixSignal::from_name("9"), Some(UnixSignal::Sigkill));
+        assert_eq!(UnixSignal::from_name("15"), Some(UnixSignal::Sigterm));
+        assert_eq!(UnixSignal::from_name("64"), None);
+        assert_eq!(UnixSignal::from_name("SIG"), None);
+        assert_eq!(UnixSignal::from_name("DIE"), None);
+    }
+
     #[test]
     fn test_unixsignal_from_u8() {
         assert_eq!(UnixSignal::from_u8(1).unwrap(), UnixSignal::Sighup);
@@ -0,0 +1,194 @@
+//! ## Ansi
+//!
+//! `ansi` splits the program output into visible text and ANSI escape sequences, which must never be transliterated
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// ### Segment
+///
+/// Segment is a piece of text: either visible text or an escape sequence (possibly not terminated yet)
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum Segment<'a> {
+  Text(&'a str),
+  Escape(&'a str),
+}
+
+/// ### segments
+///
+/// Split text into visible text and escape sequences (CSI, OSC, SS3, DCS and the other two-byte sequences).
+/// An escape sequence which is not terminated by the end of the text is returned as an escape too
+pub fn segments(text: &str) -> Vec<Segment<'_>> {
+  let bytes: &[u8] = text.as_bytes();
+  let mut segments: Vec<Segment> = Vec::new();
+  let mut start: usize = 0;
+  while let Some(offset) = bytes[start..].iter().position(|b| *b == ESC) {
+    let esc: usize = start + offset;
+    if esc > start {
+      segments.push(Segment::Text(&text[start..esc]));
+    }
+    let end: usize = match escape_len(&bytes[esc..]) {
+      Some(len) => esc + len,
+      None => text.len(),
+    };
+    segments.push(Segment::Escape(&text[esc..end]));
+    start = end;
+  }
+  if start < text.len() {
+    segments.push(Segment::Text(&text[start..]));
+  }
+  segments
+}
+
+/// ### incomplete_tail
+///
+/// Returns the byte index where the escape sequence left unterminated at the end of text begins;
+/// the rest of the sequence is expected in the next chunk of output
+pub fn incomplete_tail(text: &str) -> Option<usize> {
+  let bytes: &[u8] = text.as_bytes();
+  let mut start: usize = 0;
+  while let Some(offset) = bytes[start..].iter().position(|b| *b == ESC) {
+    let esc: usize = start + offset;
+    match escape_len(&bytes[esc..]) {
+      Some(len) => start = esc + len,
+      None => return Some(esc),
+    }
+  }
+  None
+}
+
+/// ### escape_len
+///
+/// Returns the length of the escape sequence at the beginning of bytes (which start with ESC), or None if it's not terminated.
+/// Malformed sequences end before the first byte which can't be part of them.
+/// Sequences end on ASCII bytes only, so they never split a multi-byte character
+fn escape_len(bytes: &[u8]) -> Option<usize> {
+  match bytes.get(1) {
+    None => None,
+    Some(b'[') => {
+      //CSI: parameters and intermediates, then the final byte
+      for (i, b) in bytes.iter().enumerate().skip(2) {
+        match b {
+          0x40..=0x7e => return Some(i + 1),
+          0x20..=0x3f => continue,
+          _ => return Some(i),
+        }
+      }
+      None
+    }
+    Some(b']') | Some(b'P') | Some(b'X') | Some(b'^') | Some(b'_') => {
+      //OSC (terminated by BEL or ST), DCS, SOS, PM and APC (terminated by ST)
+      let mut i: usize = 2;
+      while i < bytes.len() {
+        match bytes[i] {
+          BEL if bytes[1] == b']' => return Some(i + 1),
+          ESC => {
+            return match bytes.get(i + 1) {
+              None => None,
+              Some(b'\\') => Some(i + 2),
+              Some(_) => Some(i), //Another sequence starts
+            }
+          }
+          _ => i += 1,
+        }
+      }
+      None
+    }
+    Some(b'O') => match bytes.get(2) {
+      //SS3: a single character follows
+      None => None,
+      Some(b) if *b < 0x80 => Some(3),
+      Some(_) => Some(2),
+    },
+    Some(_) => {
+      //Intermediates, then the final byte (e.g. `ESC ( B`)
+      for (i, b) in bytes.iter().enumerate().skip(1) {
+        match b {
+          0x20..=0x2f => continue,
+          0x30..=0x7e => return Some(i + 1),
+          _ => return Some(i),
+        }
+      }
+      None
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_ansi_segments() {
+    assert_eq!(segments("ls"), vec![Segment::Text("ls")]);
+    assert_eq!(segments(""), vec![]);
+    assert_eq!(
+      segments("\x1b[0m\x1b[01;34msrc\x1b[0m  Cargo.toml\n"),
+      vec![
+        Segment::Escape("\x1b[0m"),
+        Segment::Escape("\x1b[01;34m"),
+        Segment::Text("src"),
+        Segment::Escape("\x1b[0m"),
+        Segment::Text("  Cargo.toml\n"),
+      ]
+    );
+    //OSC terminated by BEL and by ST
+    assert_eq!(
+      segments("\x1b]0;user@host: ~/проект\x07prompt\x1b]2;title\x1b\\$"),
+      vec![
+        Segment::Escape("\x1b]0;user@host: ~/проект\x07"),
+        Segment::Text("prompt"),
+        Segment::Escape("\x1b]2;title\x1b\\"),
+        Segment::Text("$"),
+      ]
+    );
+    //SS3, charset selection and keypad mode
+    assert_eq!(segments("\x1bOAup\x1b(Bok\x1b="), vec![
+      Segment::Escape("\x1bOA"),
+      Segment::Text("up"),
+      Segment::Escape("\x1b(B"),
+      Segment::Text("ok"),
+      Segment::Escape("\x1b="),
+    ]);
+    //Unterminated sequences
+    assert_eq!(segments("red\x1b[3"), vec![Segment::Text("red"), Segment::Escape("\x1b[3")]);
+    assert_eq!(segments("\x1b]0;title"), vec![Segment::Escape("\x1b]0;title")]);
+    assert_eq!(segments("\x1b"), vec![Segment::Escape("\x1b")]);
+    //Malformed sequences don't swallow text
+    assert_eq!(segments("\x1b[3привет"), vec![Segment::Escape("\x1b[3"), Segment::Text("привет")]);
+    assert_eq!(segments("\x1bпривет"), vec![Segment::Escape("\x1b"), Segment::Text("привет")]);
+  }
+
+  #[test]
+  fn test_ansi_incomplete_tail() {
+    assert_eq!(incomplete_tail("plain text"), None);
+    assert_eq!(incomplete_tail("\x1b[31mred\x1b[0m"), None);
+    assert_eq!(incomplete_tail("\x1b[31mred\x1b[0"), Some(8));
+    assert_eq!(incomplete_tail("text\x1b"), Some(4));
+    assert_eq!(incomplete_tail("\x1b]0;title\x1b"), Some(0));
+    assert_eq!(incomplete_tail("\x1b]0;title\x07"), None);
+  }
+}
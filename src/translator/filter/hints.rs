@@ -0,0 +1,225 @@
+//! ## Hints
+//!
+//! `hints` provides the localized summary printed after a command which has failed writing to stderr
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use std::collections::HashMap;
+
+use crate::translator::lang::Language;
+use crate::translator::messages;
+
+const STDERR_CAPACITY: usize = 16384; //Only the beginning of stderr is searched for patterns
+
+/// ### BUILTIN_PATTERNS
+///
+/// Stderr patterns of well-known tools: the commands the pattern applies to and the pattern itself.
+/// The localized hint of each pattern is at the same index of `builtin_hints`
+const BUILTIN_PATTERNS: [(&[&str], &str); 8] = [
+  (&["grep", "find", "tar"], "No such file or directory"),
+  (&["grep", "find", "tar"], "Permission denied"),
+  (&["grep", "find", "tar"], "Is a directory"),
+  (&["grep"], "Unmatched"),
+  (&["find"], "paths must precede expression"),
+  (&["find"], "unknown predicate"),
+  (&["tar"], "does not look like a tar archive"),
+  (&["tar"], "Not found in archive"),
+];
+
+/// ### ErrorHints
+///
+/// ErrorHints collects the stderr of the running command; once the command has terminated with a non-zero status,
+/// it provides a one-line summary with the meaning of the exit status and, if a known pattern has been written to stderr,
+/// a localized hint. The stderr itself is never altered
+pub struct ErrorHints {
+  language: Language,
+  extra: HashMap<String, String>,
+  command: Option<String>,
+  stderr: String,
+}
+
+impl ErrorHints {
+  /// ### new
+  ///
+  /// Instantiates a new ErrorHints for the provided language.
+  /// `extra` patterns apply to any command and are searched before the builtin ones
+  pub fn new(language: Language, extra: &HashMap<String, String>) -> ErrorHints {
+    ErrorHints {
+      language,
+      extra: extra.clone(),
+      command: None,
+      stderr: String::new(),
+    }
+  }
+
+  /// ### start
+  ///
+  /// Reset the collected stderr; `command` is the name of the command which is going to run
+  pub fn start(&mut self, command: Option<String>) {
+    self.command = command;
+    self.stderr.clear();
+  }
+
+  /// ### feed
+  ///
+  /// Collect a chunk of stderr of the running command
+  pub fn feed(&mut self, err: &str) {
+    if self.stderr.len() < STDERR_CAPACITY {
+      self.stderr.push_str(err);
+    }
+  }
+
+  /// ### finish
+  ///
+  /// Report the exit status of the command; returns the summary to print if the command has failed writing to stderr
+  pub fn finish(&mut self, rc: u8) -> Option<String> {
+    let stderr: String = std::mem::take(&mut self.stderr);
+    if rc == 0 || stderr.trim().is_empty() {
+      return None;
+    }
+    let meaning: String = messages::exit_status_meaning(self.language, rc);
+    Some(match self.hint(stderr.as_str()) {
+      Some(hint) => format!("[{}] {}: {}", rc, meaning, hint),
+      None => format!("[{}] {}", rc, meaning),
+    })
+  }
+
+  /// ### hint
+  ///
+  /// Search stderr for a known pattern; among the configured patterns, the longest one which matches wins
+  fn hint(&self, stderr: &str) -> Option<String> {
+    let extra: Option<&String> = self
+      .extra
+      .iter()
+      .filter(|(pattern, _)| stderr.contains(pattern.as_str()))
+      .max_by_key(|(pattern, _)| pattern.len())
+      .map(|(_, hint)| hint);
+    if let Some(hint) = extra {
+      return Some(hint.clone());
+    }
+    let command: &str = self.command.as_ref()?.as_str();
+    let hints: [&str; 8] = builtin_hints(self.language)?;
+    BUILTIN_PATTERNS
+      .iter()
+      .position(|(commands, pattern)| commands.contains(&command) && stderr.contains(pattern))
+      .map(|i| String::from(hints[i]))
+  }
+}
+
+/// ### builtin_hints
+///
+/// Returns the hints for the builtin patterns in the provided language; there are no hints for latin
+fn builtin_hints(language: Language) -> Option<[&'static str; 8]> {
+  match language {
+    Language::Belarusian => Some([
+      "няма такога файла або каталога", "доступ забаронены", "гэта каталог", "няпарная дужка ў рэгулярным выразе",
+      "шляхі павінны стаяць перад выразам", "невядомы прэдыкат", "файл не з'яўляецца tar-архівам", "файл не знойдзены ў архіве",
+    ]),
+    Language::Bulgarian => Some([
+      "няма такъв файл или директория", "отказан достъп", "това е директория", "несъответстваща скоба в регулярния израз",
+      "пътищата трябва да са преди израза", "непознат предикат", "файлът не е tar архив", "файлът не е намерен в архива",
+    ]),
+    Language::Kazakh => Some([
+      "мұндай файл немесе каталог жоқ", "қол жеткізуге тыйым салынған", "бұл каталог", "тұрақты өрнектегі жұпсыз жақша",
+      "жолдар өрнектің алдында тұруы керек", "белгісіз предикат", "файл tar-архив емес", "файл архивтен табылмады",
+    ]),
+    Language::Russian => Some([
+      "нет такого файла или каталога", "отказано в доступе", "это каталог", "непарная скобка в регулярном выражении",
+      "пути должны стоять перед выражением", "неизвестный предикат", "файл не является tar-архивом", "файл не найден в архиве",
+    ]),
+    Language::Serbian => Some([
+      "нема такве датотеке или директоријума", "приступ одбијен", "ово је директоријум", "неупарена заграда у регуларном изразу",
+      "путање морају да стоје пре израза", "непознат предикат", "датотека није tar архива", "датотека није пронађена у архиви",
+    ]),
+    Language::Ukrainian => Some([
+      "немає такого файлу або каталогу", "відмовлено у доступі", "це каталог", "непарна дужка в регулярному виразі",
+      "шляхи мають стояти перед виразом", "невідомий предикат", "файл не є tar-архівом", "файл не знайдено в архіві",
+    ]),
+    Language::Nil => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_filter_hints_builtin() {
+    let mut hints: ErrorHints = ErrorHints::new(Language::Russian, &HashMap::new());
+    let stderr: &str = "grep: /tmp/missing.txt: No such file or directory\n";
+    hints.start(Some(String::from("grep")));
+    hints.feed(stderr);
+    assert_eq!(hints.finish(2), Some(String::from("[2] неверное использование: нет такого файла или каталога")));
+    //Stderr has been consumed
+    assert_eq!(hints.finish(2), None);
+    //Tool specific patterns
+    hints.start(Some(String::from("tar")));
+    hints.feed("tar: This does not look like a tar archive\n");
+    hints.feed("tar: Exiting with failure status due to previous errors\n");
+    assert_eq!(hints.finish(2), Some(String::from("[2] неверное использование: файл не является tar-архивом")));
+    hints.start(Some(String::from("grep")));
+    hints.feed("find: unknown predicate `-foo'\n");
+    assert_eq!(hints.finish(1), Some(String::from("[1] ошибка")));
+    //Unknown tool: only the exit status
+    hints.start(Some(String::from("cat")));
+    hints.feed("cat: /tmp/missing.txt: No such file or directory\n");
+    assert_eq!(hints.finish(1), Some(String::from("[1] ошибка")));
+  }
+
+  #[test]
+  fn test_filter_hints_no_summary() {
+    let mut hints: ErrorHints = ErrorHints::new(Language::Ukrainian, &HashMap::new());
+    //Successful command
+    hints.start(Some(String::from("find")));
+    hints.feed("find: '/root': Permission denied\n");
+    assert_eq!(hints.finish(0), None);
+    //Nothing written to stderr
+    hints.start(Some(String::from("grep")));
+    assert_eq!(hints.finish(1), None);
+    hints.start(None);
+    hints.feed("\n");
+    assert_eq!(hints.finish(1), None);
+    //Latin: no hints
+    let mut hints: ErrorHints = ErrorHints::new(Language::Nil, &HashMap::new());
+    hints.start(Some(String::from("grep")));
+    hints.feed("grep: foo: No such file or directory\n");
+    assert_eq!(hints.finish(2), Some(String::from("[2] invalid usage")));
+  }
+
+  #[test]
+  fn test_filter_hints_extra() {
+    let mut extra: HashMap<String, String> = HashMap::new();
+    extra.insert(String::from("Disk quota exceeded"), String::from("превышена дисковая квота"));
+    extra.insert(String::from("No such file"), String::from("файла нет"));
+    extra.insert(String::from("No such file or directory"), String::from("нет ни файла, ни каталога"));
+    let mut hints: ErrorHints = ErrorHints::new(Language::Russian, &extra);
+    //Configured patterns apply to any command
+    hints.start(Some(String::from("cp")));
+    hints.feed("cp: error writing 'foo': Disk quota exceeded\n");
+    assert_eq!(hints.finish(1), Some(String::from("[1] ошибка: превышена дисковая квота")));
+    //Configured patterns win over the builtin ones; the longest one wins
+    hints.start(Some(String::from("grep")));
+    hints.feed("grep: foo: No such file or directory\n");
+    assert_eq!(hints.finish(2), Some(String::from("[2] неверное использование: нет ни файла, ни каталога")));
+  }
+}
@@ -0,0 +1,38 @@
+//! ## Filter
+//!
+//! `filter` provides the filters which can be applied to the shell output before it gets translated
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+pub mod hints;
+pub mod units;
+
+/// ### OutputFilter
+///
+/// An OutputFilter rewrites the output of the shell before it is converted to cyrillic.
+/// Filters are applied only to the output, never to the user input
+pub trait OutputFilter {
+  /// ### filter
+  ///
+  /// Returns the filtered output
+  fn filter(&self, output: &str) -> String;
+}
@@ -0,0 +1,246 @@
+//! ## Units
+//!
+//! `units` provides an output filter which localizes unit suffixes (e.g. `GiB`) and date abbreviations (e.g. `Jan`, `Tue`)
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate regex;
+
+use regex::Regex;
+use std::collections::HashMap;
+
+use super::OutputFilter;
+use crate::translator::lang::Language;
+use crate::translator::messages;
+
+const NUMBER_WITH_UNIT_REGEX: &str = r"^([0-9]+(?:[.,][0-9]+)?)([A-Za-z]+)$";
+const NUMBER_REGEX: &str = r"^[0-9]+(?:[.,][0-9]+)?$";
+
+/// ### UnitsFilter
+///
+/// UnitsFilter replaces unit suffixes and date abbreviations with the ones used in the configured language.
+/// When the replacement is shorter than the original token, it is padded with spaces, so that columns stay aligned
+pub struct UnitsFilter {
+  words: HashMap<String, String>,
+  units: HashMap<String, String>,
+  extra: HashMap<String, String>,
+  number_with_unit_regex: Regex,
+  number_regex: Regex,
+}
+
+impl UnitsFilter {
+  /// ### new
+  ///
+  /// Instantiates a new UnitsFilter for the provided language.
+  /// `extra` entries extend (and override) the builtin dictionary; they're used both as words and as unit suffixes
+  pub fn new(language: Language, extra: &HashMap<String, String>) -> UnitsFilter {
+    UnitsFilter {
+      words: to_dictionary(builtin_words(language)),
+      units: to_dictionary(builtin_units(language)),
+      extra: extra.clone(),
+      number_with_unit_regex: Regex::new(NUMBER_WITH_UNIT_REGEX).unwrap(),
+      number_regex: Regex::new(NUMBER_REGEX).unwrap(),
+    }
+  }
+
+  /// ### localize_word
+  ///
+  /// Get the localized version of a date abbreviation
+  fn localize_word(&self, word: &str) -> Option<&String> {
+    self.extra.get(word).or_else(|| self.words.get(word))
+  }
+
+  /// ### localize_unit
+  ///
+  /// Get the localized version of a unit suffix
+  fn localize_unit(&self, unit: &str) -> Option<&String> {
+    self.extra.get(unit).or_else(|| self.units.get(unit))
+  }
+
+  /// ### localize_token
+  ///
+  /// Localize a single token (a sequence of non whitespace characters).
+  /// A standalone unit is localized only if it follows a number
+  fn localize_token(&self, token: &str, after_number: bool) -> Option<String> {
+    if let Some(word) = self.localize_word(token) {
+      return Some(word.clone());
+    }
+    if let Some(groups) = self.number_with_unit_regex.captures(token) {
+      return self
+        .localize_unit(&groups[2])
+        .map(|unit| format!("{}{}", &groups[1], unit));
+    }
+    match after_number {
+      true => self.localize_unit(token).cloned(),
+      false => None,
+    }
+  }
+}
+
+impl OutputFilter for UnitsFilter {
+  fn filter(&self, output: &str) -> String {
+    let mut filtered: String = String::with_capacity(output.len());
+    let mut token: String = String::new();
+    let mut after_number: bool = false;
+    //Push token to output, localized if possible
+    let flush = |token: &mut String, filtered: &mut String, after_number: &mut bool| {
+      if token.is_empty() {
+        return;
+      }
+      match self.localize_token(token.as_str(), *after_number) {
+        Some(localized) => {
+          let width: usize = token.chars().count();
+          filtered.push_str(format!("{:<width$}", localized, width = width).as_str());
+        }
+        None => filtered.push_str(token.as_str()),
+      }
+      *after_number = self.number_regex.is_match(token.as_str());
+      token.clear();
+    };
+    for c in output.chars() {
+      match c.is_whitespace() {
+        true => {
+          flush(&mut token, &mut filtered, &mut after_number);
+          if c == '\n' {
+            after_number = false;
+          }
+          filtered.push(c);
+        }
+        false => token.push(c),
+      }
+    }
+    flush(&mut token, &mut filtered, &mut after_number);
+    filtered
+  }
+}
+
+/// ### to_dictionary
+///
+/// Converts a list of pairs into a dictionary
+fn to_dictionary(pairs: Vec<(&str, &str)>) -> HashMap<String, String> {
+  pairs
+    .into_iter()
+    .map(|(k, v)| (String::from(k), String::from(v)))
+    .collect()
+}
+
+/// ### builtin_words
+///
+/// Returns the builtin month and weekday abbreviations for the provided language
+fn builtin_words(language: Language) -> Vec<(&'static str, &'static str)> {
+  if language == Language::Nil {
+    return Vec::new();
+  }
+  let mut words: Vec<(&str, &str)> = Vec::with_capacity(19);
+  for month in 1..=12 {
+    words.push((messages::month_abbr(Language::Nil, month), messages::month_abbr(language, month)));
+  }
+  for weekday in 0..7 {
+    words.push((messages::weekday_abbr(Language::Nil, weekday), messages::weekday_abbr(language, weekday)));
+  }
+  words
+}
+
+/// ### builtin_units
+///
+/// Returns the builtin unit suffixes for the provided language
+fn builtin_units(language: Language) -> Vec<(&'static str, &'static str)> {
+  match language {
+    Language::Belarusian | Language::Ukrainian => vec![
+      ("B", "Б"), ("K", "К"), ("M", "М"), ("G", "Г"), ("T", "Т"), ("P", "П"),
+      ("KB", "КБ"), ("MB", "МБ"), ("GB", "ГБ"), ("TB", "ТБ"), ("PB", "ПБ"),
+      ("KiB", "КіБ"), ("MiB", "МіБ"), ("GiB", "ГіБ"), ("TiB", "ТіБ"), ("PiB", "ПіБ"),
+    ],
+    Language::Bulgarian | Language::Kazakh | Language::Russian | Language::Serbian => vec![
+      ("B", "Б"), ("K", "К"), ("M", "М"), ("G", "Г"), ("T", "Т"), ("P", "П"),
+      ("KB", "КБ"), ("MB", "МБ"), ("GB", "ГБ"), ("TB", "ТБ"), ("PB", "ПБ"),
+      ("KiB", "КиБ"), ("MiB", "МиБ"), ("GiB", "ГиБ"), ("TiB", "ТиБ"), ("PiB", "ПиБ"),
+    ],
+    Language::Nil => Vec::new(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_filter_units_ls() {
+    let filter: UnitsFilter = UnitsFilter::new(Language::Russian, &HashMap::new());
+    let input: &str = "total 12\n-rw-r--r-- 1 user user 4096 Jan  3 10:21 Cargo.toml\ndrwxr-xr-x 2 user user 4096 Dec 24  2020 src\n";
+    let output: String = filter.filter(input);
+    assert_eq!(
+      output,
+      String::from("total 12\n-rw-r--r-- 1 user user 4096 янв  3 10:21 Cargo.toml\ndrwxr-xr-x 2 user user 4096 дек 24  2020 src\n")
+    );
+    //Alignment
+    for (a, b) in input.lines().zip(output.lines()) {
+      assert_eq!(a.chars().count(), b.chars().count());
+    }
+  }
+
+  #[test]
+  fn test_filter_units_df() {
+    let filter: UnitsFilter = UnitsFilter::new(Language::Russian, &HashMap::new());
+    let input: &str = "Filesystem      Size  Used Avail Use% Mounted on\n/dev/sda1        20G  3.2G   16G  17% /\ntmpfs           7.8G     0  7.8G   0% /dev/shm\n";
+    let output: String = filter.filter(input);
+    assert_eq!(
+      output,
+      String::from("Filesystem      Size  Used Avail Use% Mounted on\n/dev/sda1        20Г  3.2Г   16Г  17% /\ntmpfs           7.8Г     0  7.8Г   0% /dev/shm\n")
+    );
+    for (a, b) in input.lines().zip(output.lines()) {
+      assert_eq!(a.chars().count(), b.chars().count());
+    }
+  }
+
+  #[test]
+  fn test_filter_units_standalone() {
+    let filter: UnitsFilter = UnitsFilter::new(Language::Russian, &HashMap::new());
+    assert_eq!(filter.filter("3.2 GiB"), String::from("3.2 ГиБ"));
+    //Not after a number
+    assert_eq!(filter.filter("GiB B 5\nB"), String::from("GiB B 5\nB"));
+    //Weekdays are padded
+    assert_eq!(filter.filter("Tue Jan  5"), String::from("вт  янв  5"));
+    //Words inside other words are left unchanged
+    assert_eq!(filter.filter("Janet Tuesday"), String::from("Janet Tuesday"));
+  }
+
+  #[test]
+  fn test_filter_units_extra() {
+    let mut extra: HashMap<String, String> = HashMap::new();
+    extra.insert(String::from("Tue"), String::from("втр"));
+    extra.insert(String::from("Gi"), String::from("Ги"));
+    let filter: UnitsFilter = UnitsFilter::new(Language::Russian, &extra);
+    assert_eq!(filter.filter("Tue 4Gi"), String::from("втр 4Ги"));
+  }
+
+  #[test]
+  fn test_filter_units_languages() {
+    let filter: UnitsFilter = UnitsFilter::new(Language::Ukrainian, &HashMap::new());
+    assert_eq!(filter.filter("Sun 1GiB"), String::from("нд  1ГіБ"));
+    let filter: UnitsFilter = UnitsFilter::new(Language::Serbian, &HashMap::new());
+    assert_eq!(filter.filter("Jan Mon"), String::from("јан пон"));
+    let filter: UnitsFilter = UnitsFilter::new(Language::Nil, &HashMap::new());
+    assert_eq!(filter.filter("Jan 4G"), String::from("Jan 4G"));
+  }
+}
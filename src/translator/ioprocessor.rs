@@ -28,26 +28,84 @@ extern crate regex;
 use regex::Regex;
 use std::fmt;
 
+use super::ansi::{self, Segment};
+use super::filter::OutputFilter;
+use super::trace::{TokenDecision, TokenTrace};
 use super::Language;
-use super::Translator;
+use super::{localize_message, LocalizedError, TranslationError, TranslationMode, Translator};
+use crate::utils::logger::{self, LogLevel};
 
-const COLORS_ESCAPE_REGEX: &str = "\x1b\\[[0-9]{1,2}m";
+const STREAM_CARRY_LIMIT: usize = 4096; //Above this size, a line without newline is translated anyway
 
 pub struct IOProcessor {
   translator: Box<dyn Translator>,
   pub language: Language,
-  escape_colors_regex: Regex, //Escape regex as struct member to increase speed up to 500%
+  output_filters: Vec<Box<dyn OutputFilter>>,
+  preserve_redirect_targets: bool, //Don't translate the files the output is redirected to
+  preserve_output_args: Vec<String>, //Commands whose `-o`/`--output` argument is not translated
+  stream_carry: [String; 2],         //Output which hasn't been translated yet for each stream, waiting for the end of the line
+  literal_delimiters: Option<(String, String)>, //Delimiters of the blocks which are never translated to latin (removed from the expression)
+  no_translate_patterns: Vec<Regex>, //Output tokens matching one of these patterns are never translated to cyrillic
+  mode: TranslationMode,             //Whether characters with no mapping make expressions fail
 }
 
-/// ### ExpressionParserError
+/// ### OutputStream
 ///
-/// Parser Error represents an error while parsing an expression
+/// OutputStream identifies the shell output stream a chunk has been read from
 
 #[derive(Copy, Clone, PartialEq, fmt::Debug)]
+pub enum OutputStream {
+  Stdout,
+  Stderr,
+}
+
+/// ### ExpressionParserError
+///
+/// Parser Error represents an error while parsing an expression.
+/// Each error carries the position (in characters, starting from 0) of the character which caused it
+
+#[derive(Clone, PartialEq, fmt::Debug)]
 pub enum ExpressionParserError {
-  MissingToken,
+  UnclosedExpression(usize),        //'(' which is never closed
+  UnexpectedToken(usize, char),     //Token which doesn't close anything (e.g. ')')
+  UnterminatedQuote(usize, char),   //Quote (or literal block delimiter) which is never closed
+  TrailingBackslash(usize),         //Backslash at the end of the expression
+  UnmappedCharacters(usize, Vec<char>), //Characters with no transliteration, in strict mode (position of the first one)
+}
+
+impl ExpressionParserError {
+  /// ### position
+  ///
+  /// Returns the position, in characters, of the character which caused the error
+  pub fn position(&self) -> usize {
+    match self {
+      ExpressionParserError::UnclosedExpression(position) => *position,
+      ExpressionParserError::UnexpectedToken(position, _) => *position,
+      ExpressionParserError::UnterminatedQuote(position, _) => *position,
+      ExpressionParserError::TrailingBackslash(position) => *position,
+      ExpressionParserError::UnmappedCharacters(position, _) => *position,
+    }
+  }
+}
+
+impl fmt::Display for ExpressionParserError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ExpressionParserError::UnclosedExpression(position) => write!(f, "'(' at character {} is never closed", position + 1),
+      ExpressionParserError::UnexpectedToken(position, token) => write!(f, "unexpected '{}' at character {}", token, position + 1),
+      ExpressionParserError::UnterminatedQuote(position, quote) => write!(f, "unterminated quote {} at character {}", quote, position + 1),
+      ExpressionParserError::TrailingBackslash(position) => write!(f, "nothing to escape after '\\' at character {}", position + 1),
+      ExpressionParserError::UnmappedCharacters(position, chars) => {
+        write!(f, "{} (first one at character {})", TranslationError::UnmappedCharacters(chars.clone()), position + 1)
+      }
+    }
+  }
 }
 
+impl std::error::Error for ExpressionParserError {}
+
+impl LocalizedError for ExpressionParserError {}
+
 /// ### ExpressionParserStates
 ///
 /// Expression Parser states is a struct which represents the current state in converting an expressions into a text
@@ -55,12 +113,27 @@ pub enum ExpressionParserError {
 struct ExpressionParserStates {
   text: String,                                        //Current converted expression text
   expression_token: String,                            //Current expression token
-  escape_block: bool, //Check if we're inside an escaped block (hey, keep out for expressions though)
+  escape_block: Option<char>, //Quote which opened the escaped block we're inside (hey, keep out for expressions though)
+  literal: Option<String>,    //Content of the literal block we're inside
   backslash: bool,    //Check if backslash is active
   in_expression: bool, //Check is we're inside an expression
+  expression_start: usize, //Position of the '(' which opened the expression
+  block_start: usize,      //Position of the quote which opened the escaped block
+  literal_start: usize,    //Position of the delimiter which opened the literal block
+  backslash_start: usize,  //Position of the active backslash
   previous_state: Option<Box<ExpressionParserStates>>, //Reference to previous state
 }
 
+/// ### WordState
+///
+/// WordState keeps track of the words of the command being translated, to know which words must not be translated
+
+#[derive(Clone, Default)]
+struct WordState {
+  command: Option<String>, //Command of the current pipeline stage (in latin)
+  preserve_next: bool,     //The next word must not be translated
+}
+
 /// ### ExpressionConversion
 ///
 /// Expression Conversion indicates the type of conversion to perform on the expression
@@ -76,27 +149,98 @@ impl IOProcessor {
   ///
   /// Instantiates a new IOProcessor with the provided translator
   pub fn new(language: Language, translator: Box<dyn Translator>) -> IOProcessor {
-    let this_lang_regex: String =
-      String::from(translator.to_cyrillic(&String::from(COLORS_ESCAPE_REGEX)));
-    let re: Regex = Regex::new(this_lang_regex.as_str()).unwrap();
     IOProcessor {
       translator: translator,
       language: language,
-      escape_colors_regex: re,
+      output_filters: Vec::new(),
+      preserve_redirect_targets: false,
+      preserve_output_args: Vec::new(),
+      stream_carry: [String::new(), String::new()],
+      literal_delimiters: None,
+      no_translate_patterns: Vec::new(),
+      mode: TranslationMode::default(),
     }
   }
 
+  /// ### set_translation_mode
+  ///
+  /// Set whether characters with no mapping to latin pass through expressions (lossy) or make them fail (strict)
+  pub fn set_translation_mode(&mut self, mode: TranslationMode) {
+    self.mode = mode;
+  }
+
+  /// ### set_literal_delimiters
+  ///
+  /// Set the delimiters of the literal blocks: text between them is never translated to latin and the delimiters
+  /// are removed from the expression (e.g. `«пароль»` => `пароль`). Empty delimiters disable literal blocks
+  pub fn set_literal_delimiters(&mut self, open: &str, close: &str) {
+    self.literal_delimiters = match open.is_empty() || close.is_empty() {
+      true => None,
+      false => Some((String::from(open), String::from(close))),
+    };
+  }
+
+  /// ### set_preserve_rules
+  ///
+  /// Set which words are never translated to latin: the targets of output redirections (`>`, `>>`, `2>`, `| tee`)
+  /// and the argument of `-o`/`--output` for the provided commands
+  pub fn set_preserve_rules(&mut self, redirect_targets: bool, output_args: Vec<String>) {
+    self.preserve_redirect_targets = redirect_targets;
+    self.preserve_output_args = output_args;
+  }
+
+  /// ### set_no_translate_patterns
+  ///
+  /// Set the patterns of the output tokens (whitespace-delimited words) which are never translated to cyrillic
+  /// (e.g. URLs and paths). Invalid patterns are ignored
+  pub fn set_no_translate_patterns(&mut self, patterns: &[String]) {
+    self.no_translate_patterns = patterns.iter().filter_map(|p| Regex::new(p.as_str()).ok()).collect();
+  }
+
+  /// ### add_output_filter
+  ///
+  /// Add a filter to apply to the shell output before it gets converted to cyrillic
+  pub fn add_output_filter(&mut self, filter: Box<dyn OutputFilter>) {
+    self.output_filters.push(filter);
+  }
+
+  /// ### filter_output
+  ///
+  /// Apply the output filters to the provided shell output
+  pub fn filter_output(&self, output: &str) -> String {
+    self
+      .output_filters
+      .iter()
+      .fold(output.to_string(), |output, filter| filter.filter(output.as_str()))
+  }
+
   /// ### expression_to_latin
   ///
   /// Converts a cyrillic expression into a latin string ready to be performed as a shell process
   /// An expression must care of backslashes, escapes and inner expressions '(...)'
   pub fn expression_to_latin(&self, expression: &String) -> Result<String, ExpressionParserError> {
-    self.translate_expression(&expression, ExpressionConversion::ToLatin)
+    self.expression_to_latin_traced(expression).map(|(output, _)| output)
+  }
+
+  /// ### expression_to_latin_traced
+  ///
+  /// Same as `expression_to_latin`, but returns also the tokens the expression has been split into
+  /// and what has been done with each of them
+  pub fn expression_to_latin_traced(&self, expression: &str) -> Result<(String, Vec<TokenTrace>), ExpressionParserError> {
+    let mut tokens: Vec<TokenTrace> = Vec::new();
+    let output: String = self.translate_expression(expression, ExpressionConversion::ToLatin, &mut tokens)?;
+    if self.mode == TranslationMode::Strict {
+      self.check_unmapped(expression, &tokens)?;
+    }
+    if logger::enabled(LogLevel::Debug) {
+      logger::debug("translator", format!("'{}' translated to '{}'", expression.trim_end(), output.trim_end()).as_str());
+    }
+    Ok((output, tokens))
   }
 
   #[allow(dead_code)]
   pub fn expression_to_cyrillic(&self, expression: &String) -> Result<String, ExpressionParserError> {
-    self.translate_expression(expression, ExpressionConversion::ToCyrillic)
+    self.translate_expression(expression, ExpressionConversion::ToCyrillic, &mut Vec::new())
   }
 
   /// ### text_to_latin
@@ -108,108 +252,273 @@ impl IOProcessor {
 
   /// ### text_to_cyrillic
   ///
-  /// Converts a latin text into cyrillic using the provided translator
+  /// Converts a latin text into cyrillic using the provided translator.
+  /// ANSI escape sequences (colors, terminal titles...) are copied as they are: only the text between them is converted
   pub fn text_to_cyrillic(&self, text: &String) -> String {
-    self.escape_cyrillic(self.translator.to_cyrillic(text))
+    let mut cyrillic: String = String::with_capacity(text.len());
+    for segment in ansi::segments(text.as_str()) {
+      match segment {
+        Segment::Text(visible) => cyrillic.push_str(self.translator.to_cyrillic(&String::from(visible)).as_str()),
+        Segment::Escape(sequence) => cyrillic.push_str(sequence),
+      }
+    }
+    cyrillic
+  }
+
+  /// ### message_to_cyrillic
+  ///
+  /// Convert a message printed by pyc (e.g. an error) to cyrillic; the text between single quotes is kept as it is
+  pub fn message_to_cyrillic(&self, message: &str) -> String {
+    let mut cyrillic: String = String::with_capacity(message.len());
+    for segment in ansi::segments(message) {
+      match segment {
+        Segment::Text(visible) => cyrillic.push_str(localize_message(self.translator.as_ref(), visible).as_str()),
+        Segment::Escape(sequence) => cyrillic.push_str(sequence),
+      }
+    }
+    cyrillic
+  }
+
+  /// ### localize_error
+  ///
+  /// Render an error in cyrillic with the translator of the processor
+  pub fn localize_error(&self, err: &dyn LocalizedError) -> String {
+    err.localized(self.translator.as_ref())
+  }
+
+  /// ### push_output
+  ///
+  /// Filter and convert to cyrillic a chunk of the output read from the shell.
+  /// Only complete lines are converted: the trailing partial line is kept and converted together with the next chunks,
+  /// so that words split between two reads are translated as a whole. Call `flush_output` once the stream is idle
+  pub fn push_output(&mut self, stream: OutputStream, chunk: &str) -> String {
+    let mut carry: String = std::mem::take(&mut self.stream_carry[stream as usize]);
+    carry.push_str(chunk);
+    let split: usize = match carry.rfind('\n') {
+      Some(index) => index + 1,
+      None if carry.len() >= STREAM_CARRY_LIMIT => IOProcessor::escape_split(carry.as_str()),
+      None => 0,
+    };
+    let output: String = self.translate_output(&carry[..split]);
+    //Drain keeps the capacity of the carry buffer, which is reused by the next chunks
+    carry.drain(..split);
+    self.stream_carry[stream as usize] = carry;
+    output
+  }
+
+  /// ### flush_output
+  ///
+  /// Filter and convert to cyrillic the output of the stream which is still pending.
+  /// An escape sequence which is not terminated yet is kept, waiting for the rest of it
+  pub fn flush_output(&mut self, stream: OutputStream) -> String {
+    let mut carry: String = std::mem::take(&mut self.stream_carry[stream as usize]);
+    let split: usize = IOProcessor::escape_split(carry.as_str());
+    let output: String = self.translate_output(&carry[..split]);
+    carry.drain(..split);
+    self.stream_carry[stream as usize] = carry;
+    output
+  }
+
+  /// ### escape_split
+  ///
+  /// Returns where pending output can be split without breaking the escape sequence at its end;
+  /// a sequence longer than the carry limit is not waited for
+  fn escape_split(pending: &str) -> usize {
+    match ansi::incomplete_tail(pending) {
+      Some(index) if pending.len() - index < STREAM_CARRY_LIMIT => index,
+      _ => pending.len(),
+    }
+  }
+
+  /// ### translate_output
+  ///
+  /// Filter and convert to cyrillic a piece of output
+  fn translate_output(&self, output: &str) -> String {
+    match output.is_empty() {
+      true => String::new(),
+      false => self.output_to_cyrillic(self.filter_output(&String::from(output)).as_str()),
+    }
+  }
+
+  /// ### output_to_cyrillic
+  ///
+  /// Convert output to cyrillic as `text_to_cyrillic` does, except for the tokens matching the no-translate patterns,
+  /// which are copied as they are; the text around them is translated
+  fn output_to_cyrillic(&self, output: &str) -> String {
+    if self.no_translate_patterns.is_empty() {
+      return self.text_to_cyrillic(&String::from(output));
+    }
+    let mut cyrillic: String = String::with_capacity(output.len());
+    for segment in ansi::segments(output) {
+      let visible: &str = match segment {
+        Segment::Text(visible) => visible,
+        Segment::Escape(sequence) => {
+          cyrillic.push_str(sequence);
+          continue;
+        }
+      };
+      //Text which hasn't been copied yet starts at pending
+      let mut pending: usize = 0;
+      let mut token_start: Option<usize> = None;
+      for (index, ch) in visible.char_indices().chain(std::iter::once((visible.len(), ' '))) {
+        match (ch.is_whitespace(), token_start) {
+          (false, None) => token_start = Some(index),
+          (true, Some(start)) => {
+            let token: &str = &visible[start..index];
+            if self.no_translate_patterns.iter().any(|p| p.is_match(token)) {
+              cyrillic.push_str(self.translator.to_cyrillic(&String::from(&visible[pending..start])).as_str());
+              cyrillic.push_str(token);
+              pending = index;
+            }
+            token_start = None;
+          }
+          _ => {}
+        }
+      }
+      cyrillic.push_str(self.translator.to_cyrillic(&String::from(&visible[pending..])).as_str());
+    }
+    cyrillic
   }
 
   /// ### translate_expression
   ///
-  /// Converts an expression and translate unescaped texts using the desidered translate function
-  fn translate_expression(&self, expression: &String, conversion: ExpressionConversion) -> Result<String, ExpressionParserError> {
+  /// Converts an expression and translate unescaped texts using the desidered translate function.
+  /// Text between single quotes is never translated; text between double quotes is not translated,
+  /// but expressions inside it (`$(...)`) are. When converting to latin, the content of literal blocks
+  /// is never translated and their delimiters are removed.
+  fn translate_expression(&self, expression: &str, conversion: ExpressionConversion, tokens: &mut Vec<TokenTrace>) -> Result<String, ExpressionParserError> {
     //Instantiate a new Parser State
     let mut states: ExpressionParserStates = ExpressionParserStates::new(None);
+    //Words of the current command and of the commands enclosing the current expression
+    let mut words: WordState = WordState::default();
+    let mut words_stack: Vec<WordState> = Vec::new();
+    //Previous character (used to detect expressions inside double quotes)
+    let mut prev_char: Option<char> = None;
+    //Literal blocks are only removed when converting to latin
+    let literal_delimiters: Option<&(String, String)> = match conversion {
+      ExpressionConversion::ToLatin => self.literal_delimiters.as_ref(),
+      ExpressionConversion::ToCyrillic => None,
+    };
+    //Characters of the delimiter which have already been handled
+    let mut skip: usize = 0;
     //Iterate over input
-    for c in expression.chars() {
+    for (position, (index, c)) in expression.char_indices().enumerate() {
+      let last_char: Option<char> = prev_char;
+      prev_char = Some(c);
+      if skip > 0 {
+        skip -= 1;
+        continue;
+      }
+      //Inside single quotes everything is literal, until the quote is closed
+      if states.escape_block == Some('\'') {
+        states.expression_token.push(c);
+        if c == '\'' {
+          self.push_escaped_token(&mut states, &mut words, tokens);
+        }
+        continue;
+      }
+      //Literal blocks end only with the closing delimiter; they can start inside double quotes too
+      if let Some((open, close)) = literal_delimiters {
+        if states.literal.is_some() {
+          if expression[index..].starts_with(close.as_str()) {
+            skip = close.chars().count() - 1;
+            self.push_literal(&mut states, &mut words, tokens);
+          } else if let Some(literal) = states.literal.as_mut() {
+            literal.push(c);
+          }
+          continue;
+        }
+        if !states.backslash && expression[index..].starts_with(open.as_str()) {
+          skip = open.chars().count() - 1;
+          //Outside of quotes, the text before the block is translated
+          if states.escape_block.is_none() {
+            states.text.push_str(self.translate_token(&states.expression_token, &conversion, &mut words, tokens).as_str());
+            states.expression_token = String::new();
+          }
+          states.literal = Some(String::new());
+          states.literal_start = position;
+          continue;
+        }
+      }
       //If character is '(' an expression block starts (if backlsash is disabled)
-      if c == '(' && !states.backslash {
-        //Set escape to false
-        states.escape_block = false;
-        //Convert current expression to latin and push it to text
-        states.text.push_str(
-          match conversion {
-            ExpressionConversion::ToLatin => self.translator.to_latin(&states.expression_token),
-            ExpressionConversion::ToCyrillic => {
-              self.translator.to_cyrillic(&states.expression_token)
-            }
-          }.as_str(),
-        );
+      //Inside double quotes, only '$(' starts an expression
+      if c == '(' && !states.backslash && (states.escape_block.is_none() || last_char == Some('$') || last_char == Some('₽')) {
+        //Push current token to text; if we're inside an escaped block, it's not converted
+        match states.escape_block {
+          Some(_) => {
+            //The dollar sign must be converted though
+            let dollar: String = states.expression_token.pop().map(String::from).unwrap_or_default();
+            states.text.push_str(states.expression_token.as_str());
+            tokens.push(TokenTrace::new(states.expression_token.clone(), states.expression_token.clone(), TokenDecision::Escaped));
+            states.text.push_str(self.translate_token(&dollar, &conversion, &mut words, tokens).as_str());
+          }
+          None => states.text.push_str(self.translate_token(&states.expression_token, &conversion, &mut words, tokens).as_str()),
+        }
         //Expression token is reinitialized
         states.expression_token = String::new();
-        //@! Create new state
+        //@! Create new state; the expression is a new command
         states = ExpressionParserStates::new(Some(states));
+        words_stack.push(std::mem::take(&mut words));
         states.in_expression = true;
+        states.expression_start = position;
         //Push '(' to new expression
         states.expression_token.push(c);
         continue;
       }
-      //If character is ')' an expression ends (if backslash is disabled)
-      if c == ')' && !states.backslash {
+      //If character is ')' an expression ends (if backslash is disabled and it's not quoted)
+      if c == ')' && !states.backslash && states.escape_block.is_none() {
         states.in_expression = false;
         //Push ')' to current expression
         states.expression_token.push(c);
         //Convert current expression to latin and push it to text
-        states.text.push_str(
-          match conversion {
-            ExpressionConversion::ToLatin => {
-              self.translator.to_latin(&states.expression_token.clone())
-            }
-            ExpressionConversion::ToCyrillic => {
-              self.translator.to_cyrillic(&states.expression_token.clone())
-            }
-          }.as_str(),
-        );
+        states.text.push_str(self.translate_token(&states.expression_token, &conversion, &mut words, tokens).as_str());
         //Save text into a tmp variable
         let expression_output: String = states.text.clone();
         //If there are still active states, return error 'missing token'
-        if states.backslash || states.in_expression || states.escape_block {
+        if states.backslash || states.in_expression {
           //Check if expression has been completely closed
-          return Err(ExpressionParserError::MissingToken);
+          return Err(ExpressionParserError::UnexpectedToken(position, c));
         }
         //@! Restore previous state
         states = match states.previous_state {
           Some(_) => states.restore_previous_state(),
-          None => return Err(ExpressionParserError::MissingToken),
+          None => return Err(ExpressionParserError::UnexpectedToken(position, c)),
         };
         //Push converted expression to previous state's text
         states.text.push_str(expression_output.as_str());
+        words = words_stack.pop().unwrap_or_default();
         continue;
       } //@! End of expression closed
-        //Handle quotes
-        //Check if escape (and previous character is not backslash)
-      if c == '"' && !states.backslash {
-        if states.escape_block {
-          //Escape block ends, push current token to text WITHOUT CONVERTING IT
-          //Push quote to expression token
-          states.expression_token.push(c);
-          //Push expression token to text without converting it
-          states.text.push_str(states.expression_token.as_str());
-          //Reset expression token
-          states.expression_token = String::new();
-        } else {
-          //Escape block starts
-          //Convert and then Push current expression token to text
-          states.text.push_str(
-            match conversion {
-              ExpressionConversion::ToLatin => self.translator.to_latin(&states.expression_token),
-              ExpressionConversion::ToCyrillic => {
-                self.translator.to_cyrillic(&states.expression_token)
-              }
-            }.as_str(),
-          );
-          //Reset expression token
-          states.expression_token = String::new();
-          //Push quote to expression token
-          states.expression_token.push(c);
+      //Handle quotes
+      //Check if escape (and previous character is not backslash)
+      if (c == '"' || c == '\'') && !states.backslash {
+        match states.escape_block {
+          Some('"') if c == '"' => {
+            //Escape block ends, push current token to text WITHOUT CONVERTING IT
+            states.expression_token.push(c);
+            self.push_escaped_token(&mut states, &mut words, tokens);
+            continue;
+          }
+          Some(_) => {} //Other quote type inside escape block is just a character
+          None => {
+            //Escape block starts
+            //Convert and then Push current expression token to text
+            states.text.push_str(self.translate_token(&states.expression_token, &conversion, &mut words, tokens).as_str());
+            //Reset expression token
+            states.expression_token = String::new();
+            //Push quote to expression token
+            states.expression_token.push(c);
+            states.escape_block = Some(c);
+            states.block_start = position;
+            continue;
+          }
         }
-        //Invert escape block value
-        states.escape_block = !states.escape_block;
-        continue;
       }
-      //If backslash, enable backslash and push character
+      //If backslash, enable backslash and push character (an escaped backslash disables it)
       //NOTE: it's very important this statement is after every other
       if c == '\\' {
-        states.backslash = true;
+        states.backslash = !states.backslash;
+        states.backslash_start = position;
         states.expression_token.push(c);
         continue;
       } else {
@@ -218,41 +527,241 @@ impl IOProcessor {
       //Otheriwse, If it's just a character, Push it to the current expression
       states.expression_token.push(c);
     } //@! End of character iterator
-      //Push last expression token to text
-    states.text.push_str(
-      match conversion {
-        ExpressionConversion::ToLatin => self.translator.to_latin(&states.expression_token),
-        ExpressionConversion::ToCyrillic => self.translator.to_cyrillic(&states.expression_token),
-      }.as_str(),
-    );
+    //If a quote or a literal block has not been closed, return error 'unterminated quote'
+    if states.literal.is_some() {
+      let delimiter: char = literal_delimiters.and_then(|(open, _)| open.chars().next()).unwrap_or_default();
+      return Err(ExpressionParserError::UnterminatedQuote(states.literal_start, delimiter));
+    }
+    if let Some(quote) = states.escape_block {
+      return Err(ExpressionParserError::UnterminatedQuote(states.block_start, quote));
+    }
+    //Push last expression token to text
+    states.text.push_str(self.translate_token(&states.expression_token, &conversion, &mut words, tokens).as_str());
     //If there are still active states, return error 'missing token'
-    if states.backslash || states.in_expression || states.escape_block || states.previous_state.is_some() {
+    if states.backslash {
+      return Err(ExpressionParserError::TrailingBackslash(states.backslash_start));
+    }
+    if states.in_expression || states.previous_state.is_some() {
       //Check if expression has been completely closed
-      return Err(ExpressionParserError::MissingToken);
+      return Err(ExpressionParserError::UnclosedExpression(states.expression_start));
     }
     Ok(states.text)
   }
 
-  /// ### escape_cyrillic
+  /// ### check_unmapped
+  ///
+  /// Returns an error listing the characters of the translated tokens which have no mapping to latin, if any.
+  /// Quoted and preserved tokens are not translated, so they're not checked
+  fn check_unmapped(&self, expression: &str, tokens: &[TokenTrace]) -> Result<(), ExpressionParserError> {
+    let mut unmapped: Vec<char> = Vec::new();
+    for token in tokens.iter().filter(|t| matches!(t.decision, TokenDecision::Translated | TokenDecision::Unchanged)) {
+      if let Err(TranslationError::UnmappedCharacters(chars)) = self.translator.to_latin_with_mode(&token.token, self.mode) {
+        unmapped.extend(chars.into_iter().filter(|c| !unmapped.contains(c)).collect::<Vec<char>>());
+      }
+    }
+    match unmapped.first() {
+      None => Ok(()),
+      Some(first) => {
+        let position: usize = expression.chars().position(|c| c == *first).unwrap_or(0);
+        Err(ExpressionParserError::UnmappedCharacters(position, unmapped))
+      }
+    }
+  }
+
+  /// ### push_escaped_token
+  ///
+  /// Push current expression token to text without converting it and close the escaped block
+  fn push_escaped_token(&self, states: &mut ExpressionParserStates, words: &mut WordState, tokens: &mut Vec<TokenTrace>) {
+    //A quoted word satisfies a pending redirection
+    words.preserve_next = false;
+    states.text.push_str(states.expression_token.as_str());
+    tokens.push(TokenTrace::new(states.expression_token.clone(), states.expression_token.clone(), TokenDecision::Escaped));
+    states.expression_token = String::new();
+    states.escape_block = None;
+  }
+
+  /// ### push_literal
+  ///
+  /// Close the literal block, pushing its content without the delimiters: inside double quotes it's part of the quoted token,
+  /// otherwise it's pushed to text as it is
+  fn push_literal(&self, states: &mut ExpressionParserStates, words: &mut WordState, tokens: &mut Vec<TokenTrace>) {
+    let literal: String = states.literal.take().unwrap_or_default();
+    match states.escape_block {
+      Some(_) => states.expression_token.push_str(literal.as_str()),
+      None => {
+        //A literal word satisfies a pending redirection
+        words.preserve_next = false;
+        states.text.push_str(literal.as_str());
+        tokens.push(TokenTrace::new(literal.clone(), literal, TokenDecision::Override));
+      }
+    }
+  }
+
+  /// ### translate_token
+  ///
+  /// Translate a token of an expression using the desidered conversion and trace it into tokens.
+  /// When converting to latin, the words which must be preserved (see `preserved_part`) and the URLs typed in latin are kept as they are
+  fn translate_token(&self, token: &String, conversion: &ExpressionConversion, words: &mut WordState, tokens: &mut Vec<TokenTrace>) -> String {
+    if let ExpressionConversion::ToCyrillic = conversion {
+      let output: String = self.translator.to_cyrillic(token);
+      if !token.is_empty() {
+        tokens.push(TokenTrace::translated(token.clone(), output.clone()));
+      }
+      return output;
+    }
+    let mut output: String = String::with_capacity(token.len());
+    //Text which has to be translated
+    let mut pending: String = String::new();
+    let mut word: String = String::new();
+    for c in token.chars().chain(std::iter::once(' ')) {
+      if !c.is_whitespace() {
+        word.push(c);
+        continue;
+      }
+      if !word.is_empty() {
+        match self.preserved_part(word.as_str(), words) {
+          Some(pos) => {
+            pending.push_str(&word[..pos]);
+            self.flush_pending(&mut pending, &mut output, tokens);
+            output.push_str(&word[pos..]);
+            tokens.push(TokenTrace::new(String::from(&word[pos..]), String::from(&word[pos..]), TokenDecision::PreservedPath));
+          }
+          None if is_url(word.as_str()) => {
+            self.flush_pending(&mut pending, &mut output, tokens);
+            output.push_str(word.as_str());
+            tokens.push(TokenTrace::new(word.clone(), word.clone(), TokenDecision::PreservedUrl));
+          }
+          None => pending.push_str(word.as_str()),
+        }
+        word.clear();
+      }
+      pending.push(c);
+    }
+    pending.pop(); //Remove the space chained to the token
+    self.flush_pending(&mut pending, &mut output, tokens);
+    output
+  }
+
+  /// ### flush_pending
   ///
-  /// Apply different escapes to escape cyrillic texts
-  fn escape_cyrillic(&self, cyrillic_text: String) -> String {
-    self.escape_colors(cyrillic_text)
+  /// Translate the pending text to latin, push it to output and trace it
+  fn flush_pending(&self, pending: &mut String, output: &mut String, tokens: &mut Vec<TokenTrace>) {
+    if pending.is_empty() {
+      return;
+    }
+    let translated: String = self.translator.to_latin(pending);
+    output.push_str(translated.as_str());
+    tokens.push(TokenTrace::translated(pending.clone(), translated));
+    pending.clear();
   }
 
-  /// ### escape_colors
+  /// ### preserved_part
   ///
-  /// Since colors sequences have latin characters, the translator will translate these ascii characters too
-  /// this functions has been implemented to redefine color sequences
-  fn escape_colors(&self, cyrillic_text: String) -> String {
-    let mut res: String = cyrillic_text.clone();
-    for regex_match in self.escape_colors_regex.captures_iter(cyrillic_text.clone().as_str()) {
-      let mtch: String = String::from(&regex_match[0]);
-      let replace_with: String = self.text_to_latin(&mtch);
-      res = res.replace(mtch.as_str(), replace_with.as_str());
+  /// Returns the byte index where the part of the word which mustn't be translated starts, if any.
+  /// The target of an output redirection (`> file`, `2>file`), the files of `tee` and the argument of
+  /// `-o`/`--output` (for the configured commands) are preserved
+  fn preserved_part(&self, word: &str, words: &mut WordState) -> Option<usize> {
+    if words.preserve_next {
+      words.preserve_next = false;
+      return Some(0);
+    }
+    let latin: String = self.translator.to_latin(&String::from(word));
+    //A command separator starts a new command
+    if ["|", "||", "&&", ";", "&"].contains(&latin.as_str()) {
+      words.command = None;
+      return None;
+    }
+    if self.preserve_redirect_targets {
+      if let Some(pos) = redirection_target(word) {
+        return match pos == word.len() {
+          true => {
+            //Target is the next word
+            words.preserve_next = true;
+            None
+          }
+          false => Some(pos),
+        };
+      }
+    }
+    let command: &String = match words.command.as_ref() {
+      Some(command) => command,
+      None => {
+        words.command = Some(latin);
+        return None;
+      }
+    };
+    if self.preserve_redirect_targets && command == "tee" && !latin.starts_with('-') {
+      return Some(0);
+    }
+    if self.preserve_output_args.contains(command) {
+      if latin == "-o" || latin == "--output" {
+        words.preserve_next = true;
+      } else if latin.starts_with("--output=") {
+        return word.find('=').map(|pos| pos + 1);
+      }
+    }
+    None
+  }
+}
+
+/// ### CompletionState
+///
+/// Whether an expression can be run, or it continues on the next line
+
+#[derive(Copy, Clone, PartialEq, fmt::Debug)]
+pub enum CompletionState {
+  Complete,
+  UnclosedQuote(char), //Quote which is still open at the end of the expression
+  TrailingBackslash,   //The expression ends with a backslash which escapes the newline
+}
+
+/// ### is_complete
+///
+/// Check whether the quotes of the expression are balanced and it doesn't end with an escape, as the shell does:
+/// in single quotes a backslash is just a character, in double quotes and outside of quotes it escapes the next one
+pub fn is_complete(expression: &str) -> CompletionState {
+  let mut quote: Option<char> = None;
+  let mut backslash: bool = false;
+  for c in expression.chars() {
+    if backslash {
+      backslash = false;
+      continue;
     }
-    res
+    match (quote, c) {
+      (Some('\''), '\'') => quote = None,
+      (Some('\''), _) => {}
+      (_, '\\') => backslash = true,
+      (Some('"'), '"') => quote = None,
+      (None, '"') | (None, '\'') => quote = Some(c),
+      _ => {}
+    }
+  }
+  match (quote, backslash) {
+    (Some(quote), _) => CompletionState::UnclosedQuote(quote),
+    (None, true) => CompletionState::TrailingBackslash,
+    (None, false) => CompletionState::Complete,
+  }
+}
+
+/// ### redirection_target
+///
+/// If the word starts with an output redirection operator (`>`, `>>`, `>|`, `2>`, `&>`...),
+/// returns the byte index where the target of the redirection starts
+fn redirection_target(word: &str) -> Option<usize> {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(r"^(?:[0-9]*|&)>[>|]?").unwrap();
   }
+  RE.find(word).map(|m| m.end())
+}
+
+/// ### is_url
+///
+/// Returns whether the word is a URL typed in latin (e.g. `https://example.com/путь`)
+fn is_url(word: &str) -> bool {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*://\S").unwrap();
+  }
+  RE.is_match(word)
 }
 
 impl ExpressionParserStates {
@@ -260,9 +769,14 @@ impl ExpressionParserStates {
     ExpressionParserStates {
       text: String::new(),
       expression_token: String::new(),
-      escape_block: false,
+      escape_block: None,
+      literal: None,
       backslash: false,
       in_expression: false,
+      expression_start: 0,
+      block_start: 0,
+      literal_start: 0,
+      backslash_start: 0,
       previous_state: match previous_state {
         None => None,
         Some(prev_state) => Some(Box::new(prev_state)),
@@ -275,8 +789,13 @@ impl ExpressionParserStates {
       text: strref.text.clone(),                         //Text is restored
       expression_token: strref.expression_token.clone(), //Expression token is restored
       escape_block: strref.escape_block,
+      literal: strref.literal.clone(),
       backslash: strref.backslash,
       in_expression: strref.in_expression,
+      expression_start: strref.expression_start,
+      block_start: strref.block_start,
+      literal_start: strref.literal_start,
+      backslash_start: strref.backslash_start,
       previous_state: match &strref.previous_state {
         //Recursive clone
         None => None,
@@ -299,7 +818,9 @@ impl ExpressionParserStates {
 mod tests {
 
   use super::*;
+  use crate::translator::filter::units::UnitsFilter;
   use crate::translator::{new_translator, Language};
+  use std::collections::HashMap;
 
   #[test]
   fn to_cyrillic_simple() {
@@ -488,6 +1009,225 @@ mod tests {
     assert!(iop.expression_to_latin(&input).is_ok());
   }
 
+  #[test]
+  fn test_expression_error_localized() {
+    let translator: Box<dyn Translator> = new_translator(Language::Russian);
+    let localized = |err: ExpressionParserError| err.localized(translator.as_ref());
+    assert_eq!(localized(ExpressionParserError::UnclosedExpression(6)), String::from("'(' ат чарактер 7 ис невер клосед"));
+    assert_eq!(localized(ExpressionParserError::UnexpectedToken(11, ')')), String::from("унекспектед ')' ат чарактер 12"));
+    assert_eq!(localized(ExpressionParserError::UnterminatedQuote(9, '"')), String::from("унтерминатед кюуоте \" ат чарактер 10"));
+    assert_eq!(localized(ExpressionParserError::TrailingBackslash(12)), String::from("нотхинг то ескапе афтер '\\' ат чарактер 13"));
+    assert_eq!(
+      localized(ExpressionParserError::UnmappedCharacters(5, vec!['😀', 'ä'])),
+      String::from("но транслитератён фор '😀', 'ä' (фирст оне ат чарактер 6)")
+    );
+    //Can be boxed
+    let err: Box<dyn std::error::Error> = Box::new(ExpressionParserError::TrailingBackslash(0));
+    assert_eq!(err.to_string(), String::from("nothing to escape after '\\' at character 1"));
+  }
+
+  #[test]
+  fn test_ioprocessor_message_to_cyrillic() {
+    let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    assert_eq!(iop.message_to_cyrillic("Could not read 'file'"), String::from("Коулд нот реад 'file'"));
+    assert_eq!(iop.message_to_cyrillic("\x1b[31mInput error\x1b[0m"), String::from("\x1b[31mИнпут еррор\x1b[0m"));
+    assert_eq!(iop.localize_error(&ExpressionParserError::UnexpectedToken(0, ')')), String::from("унекспектед ')' ат чарактер 1"));
+  }
+
+  #[test]
+  fn test_expression_error_position() {
+    let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    //Unclosed expressions
+    let err: ExpressionParserError = iop.expression_to_latin(&String::from("экхо ₽(хостнамэ")).err().unwrap();
+    assert_eq!(err, ExpressionParserError::UnclosedExpression(6));
+    assert_eq!(err.position(), 6);
+    assert_eq!(err.to_string(), String::from("'(' at character 7 is never closed"));
+    assert_eq!(
+      iop.expression_to_latin(&String::from("экхо ₽(цат ₽(хостнамэ)")).err().unwrap(),
+      ExpressionParserError::UnclosedExpression(6)
+    );
+    //Unexpected tokens
+    let err: ExpressionParserError = iop.expression_to_latin(&String::from("экхо привет)")).err().unwrap();
+    assert_eq!(err, ExpressionParserError::UnexpectedToken(11, ')'));
+    assert_eq!(err.to_string(), String::from("unexpected ')' at character 12"));
+    //Unterminated quotes
+    let err: ExpressionParserError = iop.expression_to_latin(&String::from("экхо 'а' \"привет")).err().unwrap();
+    assert_eq!(err, ExpressionParserError::UnterminatedQuote(9, '"'));
+    assert_eq!(err.to_string(), String::from("unterminated quote \" at character 10"));
+    assert_eq!(
+      iop.expression_to_latin(&String::from("экхо ₽(цат 'файл)")).err().unwrap(),
+      ExpressionParserError::UnterminatedQuote(11, '\'')
+    );
+    iop.set_literal_delimiters("{{", "}}");
+    assert_eq!(
+      iop.expression_to_latin(&String::from("экхо \"{{привет\"")).err().unwrap(),
+      ExpressionParserError::UnterminatedQuote(6, '{')
+    );
+    //Trailing backslash
+    let err: ExpressionParserError = iop.expression_to_latin(&String::from("экхо привет \\")).err().unwrap();
+    assert_eq!(err, ExpressionParserError::TrailingBackslash(12));
+    assert_eq!(err.to_string(), String::from("nothing to escape after '\\' at character 13"));
+  }
+
+  #[test]
+  fn test_expression_translation_mode() {
+    let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    let input: String = String::from("тоуч фа😀йл'é' \"ö\" пé😀");
+    //Lossy: characters pass through
+    assert_eq!(iop.expression_to_latin(&input).unwrap(), String::from("touch fa😀jl'é' \"ö\" pé😀"));
+    //Strict: quoted text is not translated, so it's not checked
+    iop.set_translation_mode(TranslationMode::Strict);
+    let err: ExpressionParserError = iop.expression_to_latin(&input).err().unwrap();
+    assert_eq!(err, ExpressionParserError::UnmappedCharacters(7, vec!['😀', 'é']));
+    assert_eq!(err.position(), 7);
+    assert_eq!(err.to_string(), String::from("no transliteration for '😀', 'é' (first one at character 8)"));
+    assert!(iop.expression_to_latin_traced(&input).is_err());
+    assert_eq!(iop.expression_to_latin(&String::from("лс -л 'ö'")).unwrap(), String::from("ls -l 'ö'"));
+    iop.set_translation_mode(TranslationMode::Lossy);
+    assert!(iop.expression_to_latin(&input).is_ok());
+  }
+
+  #[test]
+  fn test_expression_to_latin_quotes() {
+    let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    //Single quotes
+    let input: String = String::from("экхо 'привет мир'");
+    assert_eq!(iop.expression_to_latin(&input).unwrap(), String::from("echo 'привет мир'"));
+    //Escaped quotes inside double quotes
+    let input: String = String::from("экхо \"он сказал \\\"привет\\\"\"");
+    assert_eq!(iop.expression_to_latin(&input).unwrap(), String::from("echo \"он сказал \\\"привет\\\"\""));
+    //Quotes of the other type inside quotes
+    let input: String = String::from("экхо \"он'с\" 'он\"с'");
+    assert_eq!(iop.expression_to_latin(&input).unwrap(), String::from("echo \"он'с\" 'он\"с'"));
+    //Backslash is literal inside single quotes
+    let input: String = String::from("экхо 'привет\\' мир");
+    assert_eq!(iop.expression_to_latin(&input).unwrap(), String::from("echo 'привет\\' mir"));
+    //Escaped backslash before quote
+    let input: String = String::from("экхо \\\\\"привет\"");
+    assert_eq!(iop.expression_to_latin(&input).unwrap(), String::from("echo \\\\\"привет\""));
+    //Parenthesis inside quotes
+    let input: String = String::from("экхо \"(привет)\" '₽(мир)'");
+    assert_eq!(iop.expression_to_latin(&input).unwrap(), String::from("echo \"(привет)\" '₽(мир)'"));
+    //Expression inside double quotes is translated; the quoted text after it is not
+    let input: String = String::from("экхо \"привет ₽(хостнамэ) мир\"");
+    assert_eq!(iop.expression_to_latin(&input).unwrap(), String::from("echo \"привет $(hostname) мир\""));
+    //Unterminated quotes
+    assert_eq!(iop.expression_to_latin(&String::from("экхо \"привет")).err().unwrap(), ExpressionParserError::UnterminatedQuote(5, '"'));
+    assert_eq!(iop.expression_to_latin(&String::from("экхо 'привет")).err().unwrap(), ExpressionParserError::UnterminatedQuote(5, '\''));
+    assert_eq!(iop.expression_to_latin(&String::from("экхо \"привет\\\"")).err().unwrap(), ExpressionParserError::UnterminatedQuote(5, '"'));
+    assert_eq!(iop.expression_to_latin(&String::from("экхо ₽(кат \"привет)")).err().unwrap(), ExpressionParserError::UnterminatedQuote(11, '"'));
+  }
+
+  #[test]
+  fn test_filter_output() {
+    let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    //No filters
+    assert_eq!(iop.filter_output(&String::from("Jan 4G")), String::from("Jan 4G"));
+    iop.add_output_filter(Box::new(UnitsFilter::new(Language::Russian, &HashMap::new())));
+    assert_eq!(iop.filter_output(&String::from("Jan 4G")), String::from("янв 4Г"));
+  }
+
+  #[test]
+  fn test_expression_to_latin_traced() {
+    let iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    let input: String = String::from("экхо \"привет\" ₽(хостнамэ) -n");
+    let (output, tokens) = iop.expression_to_latin_traced(&input).unwrap();
+    assert_eq!(output, String::from("echo \"привет\" $(hostname) -n"));
+    assert_eq!(
+      tokens,
+      vec![
+        TokenTrace::new(String::from("экхо "), String::from("echo "), TokenDecision::Translated),
+        TokenTrace::new(String::from("\"привет\""), String::from("\"привет\""), TokenDecision::Escaped),
+        TokenTrace::new(String::from(" ₽"), String::from(" $"), TokenDecision::Translated),
+        TokenTrace::new(String::from("(хостнамэ)"), String::from("(hostname)"), TokenDecision::Translated),
+        TokenTrace::new(String::from(" -n"), String::from(" -n"), TokenDecision::Unchanged),
+      ]
+    );
+    //URLs typed in latin and literal blocks
+    let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    iop.set_literal_delimiters("«", "»");
+    let (output, tokens) = iop.expression_to_latin_traced(&String::from("курл https://пример.рф/путь «ключ»")).unwrap();
+    assert_eq!(output, String::from("curl https://пример.рф/путь ключ"));
+    assert_eq!(
+      tokens,
+      vec![
+        TokenTrace::new(String::from("курл "), String::from("curl "), TokenDecision::Translated),
+        TokenTrace::new(String::from("https://пример.рф/путь"), String::from("https://пример.рф/путь"), TokenDecision::PreservedUrl),
+        TokenTrace::new(String::from(" "), String::from(" "), TokenDecision::Unchanged),
+        TokenTrace::new(String::from("ключ"), String::from("ключ"), TokenDecision::Override),
+      ]
+    );
+    //Error
+    assert!(iop.expression_to_latin_traced(&String::from("экхо \"привет")).is_err());
+  }
+
+  #[test]
+  fn test_literal_blocks() {
+    let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    //Disabled by default
+    assert_eq!(iop.expression_to_latin(&String::from("экхо «привет»")).unwrap(), String::from("echo «privet»"));
+    iop.set_literal_delimiters("«", "»");
+    //Escaped and unescaped segments in the same expression; delimiters are removed
+    assert_eq!(
+      iop.expression_to_latin(&String::from("экхо -н«Пароль123» «хост» тэст")).unwrap(),
+      String::from("echo -nПароль123 хост test")
+    );
+    //Composes with quotes: removed inside double quotes, literal inside single quotes
+    assert_eq!(iop.expression_to_latin(&String::from("экхо \"а «б» ц\" 'д «е»'")).unwrap(), String::from("echo \"а б ц\" 'д «е»'"));
+    //Quotes and parentheses inside a literal block are just characters
+    assert_eq!(iop.expression_to_latin(&String::from("экхо «it's (ок)» ₽(пwд)")).unwrap(), String::from("echo it's (ок) $(pwd)"));
+    //Escaped delimiter
+    assert_eq!(iop.expression_to_latin(&String::from("экхо \\«а")).unwrap(), String::from("echo \\«a"));
+    //Unterminated block
+    assert_eq!(iop.expression_to_latin(&String::from("экхо «привет")).err().unwrap(), ExpressionParserError::UnterminatedQuote(5, '«'));
+    //Output is not affected
+    assert_eq!(iop.expression_to_cyrillic(&String::from("echo «privet»")).unwrap(), String::from("ечо «привет»"));
+    //Multi-character delimiters which differ from the shell command substitution
+    iop.set_literal_delimiters("{{", "}}");
+    let (output, tokens) = iop.expression_to_latin_traced(&String::from("гит коммит -м {{фикс}}`дате`")).unwrap();
+    assert_eq!(output, String::from("git commit -m фикс`date`"));
+    assert!(tokens.contains(&TokenTrace::new(String::from("фикс"), String::from("фикс"), TokenDecision::Override)));
+    //Disabled
+    iop.set_literal_delimiters("", "");
+    assert_eq!(iop.expression_to_latin(&String::from("{{а}}")).unwrap(), String::from("{{a}}"));
+  }
+
+  #[test]
+  fn test_preserve_redirect_targets() {
+    let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    //Rules are disabled by default
+    assert_eq!(iop.expression_to_latin(&String::from("экхо привет > отчёт.ткст")).unwrap(), iop.text_to_latin(&String::from("экхо привет > отчёт.ткст")));
+    iop.set_preserve_rules(true, vec![String::from("curl")]);
+    //Redirections
+    assert_eq!(iop.expression_to_latin(&String::from("экхо привет > отчёт.ткст\n")).unwrap(), String::from("echo privet > отчёт.ткст\n"));
+    assert_eq!(iop.expression_to_latin(&String::from("экхо привет >> отчёт.ткст")).unwrap(), String::from("echo privet >> отчёт.ткст"));
+    assert_eq!(iop.expression_to_latin(&String::from("лс 2>ошибки.лог")).unwrap(), String::from("ls 2>ошибки.лог"));
+    assert_eq!(iop.expression_to_latin(&String::from("лс  >отчёт  -л")).unwrap(), String::from("ls  >отчёт  -l"));
+    assert_eq!(iop.expression_to_latin(&String::from("лс > \"отчёт\" -л")).unwrap(), String::from("ls > \"отчёт\" -l"));
+    //Tee
+    assert_eq!(iop.expression_to_latin(&String::from("лс | тее -а отчёт отчёт2")).unwrap(), String::from("ls | tee -a отчёт отчёт2"));
+    assert_eq!(iop.expression_to_latin(&String::from("тее отчёт | лс отчёт")).unwrap(), String::from("tee отчёт | ls otchyot"));
+    //Redirection inside an expression
+    assert_eq!(iop.expression_to_latin(&String::from("экхо ₽(лс > отчёт) > итог")).unwrap(), String::from("echo $(ls > отчёт) > итог"));
+    //Output argument
+    assert_eq!(iop.expression_to_latin(&String::from("курл -о страница.хтмл хттп://пример")).unwrap(), String::from("curl -o страница.хтмл http://primer"));
+    assert_eq!(iop.expression_to_latin(&String::from("курл --оутпут=страница хттп://пример")).unwrap(), String::from("curl --output=страница http://primer"));
+    assert_eq!(iop.expression_to_latin(&String::from("лс -о программа")).unwrap(), String::from("ls -o programma"));
+    //Trace
+    let (output, tokens) = iop.expression_to_latin_traced(&String::from("экхо а > отчёт\n")).unwrap();
+    assert_eq!(output, String::from("echo a > отчёт\n"));
+    assert_eq!(
+      tokens,
+      vec![
+        TokenTrace::new(String::from("экхо а > "), String::from("echo a > "), TokenDecision::Translated),
+        TokenTrace::new(String::from("отчёт"), String::from("отчёт"), TokenDecision::PreservedPath),
+        TokenTrace::new(String::from("\n"), String::from("\n"), TokenDecision::Unchanged),
+      ]
+    );
+    let (_, tokens) = iop.expression_to_latin_traced(&String::from("курл --оутпут=страница")).unwrap();
+    assert_eq!(tokens.last(), Some(&TokenTrace::new(String::from("страница"), String::from("страница"), TokenDecision::PreservedPath)));
+  }
+
   #[test]
   fn test_escapes() {
     let latin_text: String = String::from("\x1b[31mRED\x1b[0m");
@@ -496,4 +1236,100 @@ mod tests {
     assert_eq!(iop.language, Language::Russian);
     assert_eq!(iop.text_to_cyrillic(&latin_text), String::from("\x1b[31mРЕД\x1b[0m"));
   }
+
+  #[test]
+  fn test_ioprocessor_is_complete() {
+    assert_eq!(is_complete(""), CompletionState::Complete);
+    assert_eq!(is_complete("экхо \"привет\" 'мир'"), CompletionState::Complete);
+    assert_eq!(is_complete("экхо \"привет"), CompletionState::UnclosedQuote('"'));
+    assert_eq!(is_complete("экхо 'привет"), CompletionState::UnclosedQuote('\''));
+    //Quotes inside other quotes
+    assert_eq!(is_complete("экхо \"it's\""), CompletionState::Complete);
+    assert_eq!(is_complete("экхо '\"'"), CompletionState::Complete);
+    //Escapes
+    assert_eq!(is_complete("экхо \\\"привет"), CompletionState::Complete);
+    assert_eq!(is_complete("экхо \"\\\"\""), CompletionState::Complete);
+    assert_eq!(is_complete("экхо 'a\\'"), CompletionState::Complete);
+    assert_eq!(is_complete("экхо привет \\"), CompletionState::TrailingBackslash);
+    assert_eq!(is_complete("экхо привет \\\\"), CompletionState::Complete);
+    assert_eq!(is_complete("экхо \"привет \\"), CompletionState::UnclosedQuote('"'));
+    //Multiple lines
+    assert_eq!(is_complete("экхо 'привет\nмир'"), CompletionState::Complete);
+  }
+
+  #[test]
+  fn test_no_translate_patterns() {
+    let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    let patterns: Vec<String> = crate::config::DEFAULT_NO_TRANSLATE_PATTERNS.iter().map(|p| String::from(*p)).collect();
+    iop.set_no_translate_patterns(&patterns);
+    //URL in the middle of a sentence
+    assert_eq!(
+      iop.push_output(OutputStream::Stdout, "see https://example.com/path for details\n"),
+      String::from("сее https://example.com/path фор детаилс\n")
+    );
+    //Emails, paths and hex digests
+    assert_eq!(
+      iop.push_output(OutputStream::Stdout, "mail user@example.org at  '/tmp/docs': commit 3f2a9c1d\n"),
+      String::from("маил user@example.org ат  '/tmp/docs': коммит 3f2a9c1d\n")
+    );
+    //Colored url
+    assert_eq!(
+      iop.push_output(OutputStream::Stdout, "open \x1b[34mhttp://host\x1b[0m now\n"),
+      String::from("опен \x1b[34mhttp://host\x1b[0m ноу\n")
+    );
+    //Without patterns everything is translated; invalid patterns are ignored
+    iop.set_no_translate_patterns(&[String::from("(")]);
+    assert_eq!(iop.push_output(OutputStream::Stdout, "see /tmp\n"), String::from("сее /тмп\n"));
+  }
+
+  #[test]
+  fn test_push_output() {
+    let text: &str = "Hello world\nchat shop \x1b[31mred\x1b[0m\nSchastye\nyes";
+    for language in [Language::Russian, Language::Serbian].iter() {
+      let mut iop: IOProcessor = IOProcessor::new(*language, new_translator(*language));
+      let expected: String = iop.text_to_cyrillic(&String::from(text));
+      //Split text at every possible boundary
+      for (i, _) in text.char_indices().skip(1) {
+        let mut output: String = iop.push_output(OutputStream::Stdout, &text[..i]);
+        output.push_str(iop.push_output(OutputStream::Stdout, &text[i..]).as_str());
+        output.push_str(iop.flush_output(OutputStream::Stdout).as_str());
+        assert_eq!(output, expected);
+      }
+    }
+  }
+
+  #[test]
+  fn test_push_output_escapes() {
+    //Colored `ls` output and a terminal title: escape sequences are left untouched, wherever the chunks are split
+    let text: &str = "\x1b]0;user@host: ~/docs\x07\x1b[0m\x1b[01;34mdocs\x1b[0m  \x1b[01;32mrun.sh\x1b[0m  \x1bOAnotes\n";
+    let expected: &str = "\x1b]0;user@host: ~/docs\x07\x1b[0m\x1b[01;34mдокс\x1b[0m  \x1b[01;32mрун.ш\x1b[0m  \x1bOAнотес\n";
+    let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    assert_eq!(iop.text_to_cyrillic(&String::from(text)), String::from(expected));
+    for (i, _) in text.char_indices().skip(1) {
+      let mut output: String = iop.push_output(OutputStream::Stdout, &text[..i]);
+      output.push_str(iop.push_output(OutputStream::Stdout, &text[i..]).as_str());
+      assert_eq!(output.as_bytes(), expected.as_bytes());
+    }
+    //An unterminated sequence is kept until the rest of it arrives
+    assert_eq!(iop.push_output(OutputStream::Stdout, "red \x1b[3"), String::new());
+    assert_eq!(iop.flush_output(OutputStream::Stdout), String::from("ред "));
+    assert_eq!(iop.push_output(OutputStream::Stdout, "1mred"), String::new());
+    assert_eq!(iop.flush_output(OutputStream::Stdout), String::from("\x1b[31mред"));
+  }
+
+  #[test]
+  fn test_push_output_carry() {
+    let mut iop: IOProcessor = IOProcessor::new(Language::Russian, new_translator(Language::Russian));
+    //Partial lines are kept until the line is complete
+    assert_eq!(iop.push_output(OutputStream::Stdout, "privet mi"), String::new());
+    //Streams are independent
+    assert_eq!(iop.push_output(OutputStream::Stderr, "oshibka\nch"), String::from("ошибка\n"));
+    assert_eq!(iop.push_output(OutputStream::Stdout, "r\nda"), String::from("привет мир\n"));
+    assert_eq!(iop.flush_output(OutputStream::Stdout), String::from("да"));
+    assert_eq!(iop.flush_output(OutputStream::Stdout), String::new());
+    assert_eq!(iop.flush_output(OutputStream::Stderr), String::from("ч"));
+    //Long lines without newline are not kept forever
+    let line: String = "a".repeat(STREAM_CARRY_LIMIT);
+    assert_eq!(iop.push_output(OutputStream::Stdout, line.as_str()), "а".repeat(STREAM_CARRY_LIMIT));
+  }
 }
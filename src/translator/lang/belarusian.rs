@@ -33,6 +33,7 @@ impl Translator for Belarusian {
     /// Characters between '"' (quotes) are escaped, expressions inside escaped blocks are translitarated anyway
     /// Transliteration according to GOST 7.79-2000
     fn to_latin(&self, input: &String) -> String {
+      let chars: Vec<char> = input.chars().collect();
       let mut output = String::new();
       let mut skip_counter: usize = 0;
       for (i, c) in input.chars().enumerate() {
@@ -50,7 +51,7 @@ impl Translator for Belarusian {
           'б' => "b",
           'В' => {
             //If following character is 'ь', then is always W
-            match input.chars().nth(i + 1) {
+            match chars.get(i + 1).copied() {
               Some(ch) => {
                 match ch {
                   'ь' | 'Ь' => {
@@ -66,7 +67,7 @@ impl Translator for Belarusian {
           'в' =>
           //If following character is 'ь', then is always W
           {
-            match input.chars().nth(i + 1) {
+            match chars.get(i + 1).copied() {
               Some(ch) => {
                 match ch {
                   'ь' | 'Ь' => {
@@ -104,7 +105,7 @@ impl Translator for Belarusian {
             //If following character is 'ь', then is always C
             //If following character is 'y', then is always Q
             //If follwing character is 'с', then is always X
-            match input.chars().nth(i + 1) {
+            match chars.get(i + 1).copied() {
               Some(ch) => {
                 //Check following character
                 match ch {
@@ -113,7 +114,7 @@ impl Translator for Belarusian {
                     //Check previous character
                     match i {
                       0 => "K",
-                      _ => match input.chars().nth(i - 1) {
+                      _ => match chars.get(i - 1).copied() {
                         Some(ch) => match ch {
                           'К' | 'А' | 'І' | 'О' | 'к' | 'а' | 'і' | 'о' | ' ' => "K",
                           _ => "C",
@@ -145,7 +146,7 @@ impl Translator for Belarusian {
                 //Check previous character
                 match i {
                   0 => "K",
-                  _ => match input.chars().nth(i - 1) {
+                  _ => match chars.get(i - 1).copied() {
                     //Check previous character
                     Some(ch) => match ch {
                       'К' | 'А' | 'І' | 'О' | 'У' | 'к' | 'а' | 'і' | 'о' | 'у' | ' ' => {
@@ -162,7 +163,7 @@ impl Translator for Belarusian {
           'к' => {
             //K is very complex, sometimes it is C and sometimes is K
             //If following letter is in (E, I, Y), then is K
-            match input.chars().nth(i + 1) {
+            match chars.get(i + 1).copied() {
               Some(ch) => {
                 //Check following character
                 match ch {
@@ -170,7 +171,7 @@ impl Translator for Belarusian {
                   ' ' => {
                     match i {
                       0 => "k",
-                      _ => match input.chars().nth(i - 1) {
+                      _ => match chars.get(i - 1).copied() {
                         //Check previous character
                         Some(ch) => match ch {
                           'К' | 'А' | 'І' | 'О' | 'к' | 'а' | 'і' | 'о' | ' ' => "k",
@@ -203,7 +204,7 @@ impl Translator for Belarusian {
                 //Check previous character
                 match i {
                   0 => "k",
-                  _ => match input.chars().nth(i - 1) {
+                  _ => match chars.get(i - 1).copied() {
                     Some(ch) => match ch {
                       'К' | 'А' | 'І' | 'О' | 'У' | 'к' | 'а' | 'і' | 'о' | 'у' | ' ' => {
                         "k"
@@ -269,6 +270,7 @@ impl Translator for Belarusian {
     /// Converts a string which contains latin characters into a belarusian cyrillic string.
     /// Characters between quotes are escapes
     fn to_cyrillic(&self, input: &String) -> String {
+      let chars: Vec<char> = input.chars().collect();
       let mut output: String = String::new();
       let mut skip_cycles: usize = 0;
       for (i, c) in input.chars().enumerate() {
@@ -282,7 +284,7 @@ impl Translator for Belarusian {
           'a' => "а",
           'B' => "Б",
           'b' => "б",
-          'C' => match input.chars().nth(i + 1) {
+          'C' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               'h' | 'H' => {
                 skip_cycles += 1;
@@ -292,7 +294,7 @@ impl Translator for Belarusian {
             },
             None => "К",
           },
-          'c' => match input.chars().nth(i + 1) {
+          'c' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               'h' | 'H' => {
                 skip_cycles += 1;
@@ -308,14 +310,14 @@ impl Translator for Belarusian {
           'e' => "е",
           'F' => "Ф",
           'f' => "ф",
-          'G' => match input.chars().nth(i + 1) {
+          'G' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               'y' | 'Y' | 'e' | 'E' | 'i' | 'I' => "ДЖ",
               _ => "Г",
             },
             None => "Г",
           },
-          'g' => match input.chars().nth(i + 1) {
+          'g' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               'y' | 'Y' | 'e' | 'E' | 'i' | 'I' => "дж",
               _ => "г",
@@ -324,7 +326,7 @@ impl Translator for Belarusian {
           },
           'H' => "Х",
           'h' => "х",
-          'I' => match input.chars().nth(i + 1) {
+          'I' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               'u' | 'U' => {
                 skip_cycles += 1;
@@ -342,7 +344,7 @@ impl Translator for Belarusian {
             },
             None => "І",
           },
-          'i' => match input.chars().nth(i + 1) {
+          'i' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               'u' | 'U' => {
                 skip_cycles += 1;
@@ -378,7 +380,7 @@ impl Translator for Belarusian {
           'q' => "кю",
           'R' => "Р",
           'r' => "р",
-          'S' => match input.chars().nth(i + 1) {
+          'S' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               'h' | 'H' => {
                 skip_cycles += 1;
@@ -388,7 +390,7 @@ impl Translator for Belarusian {
             },
             None => "С",
           },
-          's' => match input.chars().nth(i + 1) {
+          's' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               'h' | 'H' => {
                 skip_cycles += 1;
@@ -398,7 +400,7 @@ impl Translator for Belarusian {
             },
             None => "с",
           },
-          'T' => match input.chars().nth(i + 1) {
+          'T' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               's' | 'S' => {
                 skip_cycles += 1;
@@ -408,7 +410,7 @@ impl Translator for Belarusian {
             },
             None => "Т",
           },
-          't' => match input.chars().nth(i + 1) {
+          't' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               's' | 'T' => {
                 skip_cycles += 1;
@@ -426,7 +428,7 @@ impl Translator for Belarusian {
           'w' => "ў",
           'X' => "КС",
           'x' => "кс",
-          'Y' => match input.chars().nth(i + 1) {
+          'Y' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               'e' | 'E' => {
                 skip_cycles += 1;
@@ -436,7 +438,7 @@ impl Translator for Belarusian {
             },
             None => "Ы",
           },
-          'y' => match input.chars().nth(i + 1) {
+          'y' => match chars.get(i + 1).copied() {
             Some(ch) => match ch {
               'e' | 'E' => {
                 skip_cycles += 1;
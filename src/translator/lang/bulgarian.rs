@@ -34,6 +34,7 @@ impl Translator for Bulgarian {
   /// Characters between '"' (quotes) are escaped, expressions inside escaped blocks are translitarated anyway
   /// Transliteration according to GOST 7.79-2000
   fn to_latin(&self, input: &String) -> String {
+    let chars: Vec<char> = input.chars().collect();
     let mut output = String::new();
     let mut skip_counter: usize = 0;
     for (i, c) in input.chars().enumerate() {
@@ -51,7 +52,7 @@ impl Translator for Bulgarian {
         'б' => "b",
         'В' => {
           //If following character is 'ь', then is always W
-          match input.chars().nth(i + 1) {
+          match chars.get(i + 1).copied() {
             Some(ch) => {
               match ch {
                 'ь' | 'Ь' => {
@@ -67,7 +68,7 @@ impl Translator for Bulgarian {
         'в' =>
         //If following character is 'ь', then is always W
         {
-          match input.chars().nth(i + 1) {
+          match chars.get(i + 1).copied() {
             Some(ch) => {
               match ch {
                 'ь' | 'Ь' => {
@@ -105,7 +106,7 @@ impl Translator for Bulgarian {
           //If following character is 'ь', then is always C
           //If following character is 'y', then is always Q
           //If follwing character is 'с', then is always X
-          match input.chars().nth(i + 1) {
+          match chars.get(i + 1).copied() {
             Some(ch) => {
               //Check following character
               match ch {
@@ -114,7 +115,7 @@ impl Translator for Bulgarian {
                   //Check previous character
                   match i {
                     0 => "K",
-                    _ => match input.chars().nth(i - 1) {
+                    _ => match chars.get(i - 1).copied() {
                       Some(ch) => match ch {
                         'К' | 'А' | 'И' | 'О' | 'к' | 'а' | 'и' | 'о' | ' ' => "K",
                         _ => "C",
@@ -146,7 +147,7 @@ impl Translator for Bulgarian {
               //Check previous character
               match i {
                 0 => "K",
-                _ => match input.chars().nth(i - 1) {
+                _ => match chars.get(i - 1).copied() {
                   //Check previous character
                   Some(ch) => match ch {
                     'К' | 'А' | 'И' | 'О' | 'У' | 'к' | 'а' | 'и' | 'о' | 'у' | ' ' => {
@@ -163,7 +164,7 @@ impl Translator for Bulgarian {
         'к' => {
           //K is very complex, sometimes it is C and sometimes is K
           //If following letter is in (E, I, Y), then is K
-          match input.chars().nth(i + 1) {
+          match chars.get(i + 1).copied() {
             Some(ch) => {
               //Check following character
               match ch {
@@ -171,7 +172,7 @@ impl Translator for Bulgarian {
                 ' ' => {
                   match i {
                     0 => "k",
-                    _ => match input.chars().nth(i - 1) {
+                    _ => match chars.get(i - 1).copied() {
                       //Check previous character
                       Some(ch) => match ch {
                         'К' | 'А' | 'И' | 'О' | 'к' | 'а' | 'и' | 'о' | ' ' => "k",
@@ -204,7 +205,7 @@ impl Translator for Bulgarian {
               //Check previous character
               match i {
                 0 => "k",
-                _ => match input.chars().nth(i - 1) {
+                _ => match chars.get(i - 1).copied() {
                   Some(ch) => match ch {
                     'К' | 'А' | 'И' | 'О' | 'У' | 'к' | 'а' | 'и' | 'о' | 'у' | ' ' => {
                       "k"
@@ -271,6 +272,7 @@ impl Translator for Bulgarian {
   /// Converts a string which contains latin characters into a bulgarian cyrillic string.
   /// Characters between quotes are escapes
   fn to_cyrillic(&self, input: &String) -> String {
+    let chars: Vec<char> = input.chars().collect();
     let mut output: String = String::new();
     let mut skip_cycles: usize = 0;
     for (i, c) in input.chars().enumerate() {
@@ -284,7 +286,7 @@ impl Translator for Bulgarian {
         'a' => "а",
         'B' => "Б",
         'b' => "б",
-        'C' => match input.chars().nth(i + 1) {
+        'C' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'h' | 'H' => {
               skip_cycles += 1;
@@ -294,7 +296,7 @@ impl Translator for Bulgarian {
           },
           None => "К",
         },
-        'c' => match input.chars().nth(i + 1) {
+        'c' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'h' | 'H' => {
               skip_cycles += 1;
@@ -310,14 +312,14 @@ impl Translator for Bulgarian {
         'e' => "е",
         'F' => "Ф",
         'f' => "ф",
-        'G' => match input.chars().nth(i + 1) {
+        'G' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'y' | 'Y' | 'e' | 'E' | 'i' | 'I' => "ДЖ",
             _ => "Г",
           },
           None => "Г",
         },
-        'g' => match input.chars().nth(i + 1) {
+        'g' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'y' | 'Y' | 'e' | 'E' | 'i' | 'I' => "дж",
             _ => "г",
@@ -326,7 +328,7 @@ impl Translator for Bulgarian {
         },
         'H' => "Х",
         'h' => "х",
-        'I' => match input.chars().nth(i + 1) {
+        'I' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'u' | 'U' => {
               skip_cycles += 1;
@@ -344,7 +346,7 @@ impl Translator for Bulgarian {
           },
           None => "И",
         },
-        'i' => match input.chars().nth(i + 1) {
+        'i' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'u' | 'U' => {
               skip_cycles += 1;
@@ -380,7 +382,7 @@ impl Translator for Bulgarian {
         'q' => "кю",
         'R' => "Р",
         'r' => "р",
-        'S' => match input.chars().nth(i + 1) {
+        'S' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'h' | 'H' => {
               skip_cycles += 1;
@@ -390,7 +392,7 @@ impl Translator for Bulgarian {
           },
           None => "С",
         },
-        's' => match input.chars().nth(i + 1) {
+        's' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'h' | 'H' => {
               skip_cycles += 1;
@@ -400,7 +402,7 @@ impl Translator for Bulgarian {
           },
           None => "с",
         },
-        'T' => match input.chars().nth(i + 1) {
+        'T' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             's' | 'S' => {
               skip_cycles += 1;
@@ -410,7 +412,7 @@ impl Translator for Bulgarian {
           },
           None => "Т",
         },
-        't' => match input.chars().nth(i + 1) {
+        't' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             's' | 'T' => {
               skip_cycles += 1;
@@ -428,7 +430,7 @@ impl Translator for Bulgarian {
         'w' => "у",
         'X' => "КС",
         'x' => "кс",
-        'Y' => match input.chars().nth(i + 1) {
+        'Y' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'e' | 'E' => {
               skip_cycles += 1;
@@ -438,7 +440,7 @@ impl Translator for Bulgarian {
           },
           None => "Ы",
         },
-        'y' => match input.chars().nth(i + 1) {
+        'y' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'e' | 'E' => {
               skip_cycles += 1;
@@ -0,0 +1,390 @@
+//! ### Kazakh
+//!
+//! `kazakh` language implementation of Translator trait
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::super::Translator;
+use super::Kazakh;
+
+impl Translator for Kazakh {
+    /// ### Kazakh translator    ///
+    /// Converts a string which contains kazakh cyrillic characters into a latin string.
+    /// The letters of the kazakh alphabet are transliterated according to the 2021 latin alphabet (e.g. 'қ' => 'q', 'ә' => 'ä'),
+    /// while the letters shared with russian have ascii transliterations (e.g. 'ш' => 'sh'), so that commands are typed as in russian.
+    /// The digraphs of uppercase letters are capitalized if the following letter is lowercase ('Шелл' => 'Shell')
+    fn to_latin(&self, input: &String) -> String {
+        let chars: Vec<char> = input.chars().collect();
+        let mut output: String = String::with_capacity(input.len());
+        let mut skip_counter: usize = 0;
+        for (i, c) in chars.iter().enumerate() {
+            if skip_counter > 0 {
+                //Skip cycles
+                skip_counter -= 1; //Decrement skip counter
+                continue;
+            }
+            let next: Option<char> = chars.get(i + 1).copied();
+            //Push transliterated character
+            let unchanged_str: String;
+            output.push_str(match c {
+                'А' => "A",
+                'а' => "a",
+                'Ә' => "Ä",
+                'ә' => "ä",
+                'Б' => "B",
+                'б' => "b",
+                'В' => match next {
+                    //If following character is 'В', then is always W
+                    Some('в') | Some('В') => {
+                        skip_counter += 1;
+                        "W"
+                    }
+                    _ => "V",
+                },
+                'в' => match next {
+                    Some('в') | Some('В') => {
+                        skip_counter += 1;
+                        "w"
+                    }
+                    _ => "v",
+                },
+                'Г' => "G",
+                'г' => "g",
+                'Ғ' => "Ğ",
+                'ғ' => "ğ",
+                'Д' => "D",
+                'д' => "d",
+                'Е' | 'Э' => "E",
+                'е' | 'э' => "e",
+                'Ё' => digraph(next, "IO", "Io"),
+                'ё' => "io",
+                'Ж' => "J",
+                'ж' => "j",
+                'З' => "Z",
+                'з' => "z",
+                'И' | 'Й' => "I",
+                'и' | 'й' => "i",
+                'К' => match next {
+                    //If following character is 'С', then is always X
+                    Some('с') | Some('С') => {
+                        skip_counter += 1;
+                        "X"
+                    }
+                    _ => "K",
+                },
+                'к' => match next {
+                    Some('с') | Some('С') => {
+                        skip_counter += 1;
+                        "x"
+                    }
+                    _ => "k",
+                },
+                'Қ' => "Q",
+                'қ' => "q",
+                'Л' => "L",
+                'л' => "l",
+                'М' => "M",
+                'м' => "m",
+                'Н' => "N",
+                'н' => "n",
+                'Ң' => "Ñ",
+                'ң' => "ñ",
+                'О' => "O",
+                'о' => "o",
+                'Ө' => "Ö",
+                'ө' => "ö",
+                'П' => "P",
+                'п' => "p",
+                'Р' => "R",
+                'р' => "r",
+                'С' => "S",
+                'с' => "s",
+                'Т' => "T",
+                'т' => "t",
+                'У' => "U",
+                'у' => "u",
+                'Ұ' => "Ū",
+                'ұ' => "ū",
+                'Ү' => "Ü",
+                'ү' => "ü",
+                'Ф' => "F",
+                'ф' => "f",
+                'Х' | 'Һ' => "H",
+                'х' | 'һ' => "h",
+                'Ц' => "C",
+                'ц' => "c",
+                'Ч' => digraph(next, "CH", "Ch"),
+                'ч' => "ch",
+                'Ш' => digraph(next, "SH", "Sh"),
+                'ш' => "sh",
+                'Щ' => digraph(next, "SHH", "Shh"),
+                'щ' => "shh",
+                'Ъ' | 'ъ' | 'Ь' | 'ь' => "",
+                'Ы' => "Y",
+                'ы' => "y",
+                'І' => "I",
+                'і' => "ı",
+                'Ю' => digraph(next, "IU", "Iu"),
+                'ю' => "iu",
+                'Я' => digraph(next, "IA", "Ia"),
+                'я' => "ia",
+                _ => {
+                    unchanged_str = c.to_string();
+                    unchanged_str.as_str()
+                }
+            });
+        }
+        output
+    }
+
+    /// Converts a string which contains latin characters into a kazakh cyrillic string.
+    /// The letters of the 2021 latin alphabet are converted back to the kazakh letters (e.g. 'ñ' => 'ң'),
+    /// the case of a digraph is the one of its first letter ('Sh' => 'Ш')
+    fn to_cyrillic(&self, input: &String) -> String {
+        let chars: Vec<char> = input.chars().collect();
+        let mut output: String = String::with_capacity(input.len());
+        let mut skip_cycles: usize = 0;
+        for (i, c) in chars.iter().enumerate() {
+            if skip_cycles > 0 {
+                skip_cycles -= 1;
+                continue;
+            }
+            let next: Option<char> = chars.get(i + 1).copied();
+            let unchanged_str: String;
+            output.push_str(match c {
+                'A' => "А",
+                'a' => "а",
+                'Ä' => "Ә",
+                'ä' => "ә",
+                'B' => "Б",
+                'b' => "б",
+                'C' => match next {
+                    Some('h') | Some('H') => {
+                        skip_cycles += 1;
+                        "Ч"
+                    }
+                    _ => "Ц",
+                },
+                'c' => match next {
+                    Some('h') | Some('H') => {
+                        skip_cycles += 1;
+                        "ч"
+                    }
+                    _ => "ц",
+                },
+                'D' => "Д",
+                'd' => "д",
+                'E' => "Е",
+                'e' => "е",
+                'F' => "Ф",
+                'f' => "ф",
+                'G' => "Г",
+                'g' => "г",
+                'Ğ' => "Ғ",
+                'ğ' => "ғ",
+                'H' => "Х",
+                'h' => "х",
+                'I' | 'İ' => match next {
+                    // 'IA', 'IU' and 'IO' are 'Я', 'Ю' and 'Ё'
+                    Some('a') | Some('A') => {
+                        skip_cycles += 1;
+                        "Я"
+                    }
+                    Some('u') | Some('U') => {
+                        skip_cycles += 1;
+                        "Ю"
+                    }
+                    Some('o') | Some('O') => {
+                        skip_cycles += 1;
+                        "Ё"
+                    }
+                    _ => "И",
+                },
+                'i' => match next {
+                    Some('a') | Some('A') => {
+                        skip_cycles += 1;
+                        "я"
+                    }
+                    Some('u') | Some('U') => {
+                        skip_cycles += 1;
+                        "ю"
+                    }
+                    Some('o') | Some('O') => {
+                        skip_cycles += 1;
+                        "ё"
+                    }
+                    _ => "и",
+                },
+                'ı' => "і",
+                'J' => "Ж",
+                'j' => "ж",
+                'K' => "К",
+                'k' => "к",
+                'L' => "Л",
+                'l' => "л",
+                'M' => "М",
+                'm' => "м",
+                'N' => "Н",
+                'n' => "н",
+                'Ñ' => "Ң",
+                'ñ' => "ң",
+                'O' => "О",
+                'o' => "о",
+                'Ö' => "Ө",
+                'ö' => "ө",
+                'P' => "П",
+                'p' => "п",
+                'Q' => "Қ",
+                'q' => "қ",
+                'R' => "Р",
+                'r' => "р",
+                'S' => match (next, chars.get(i + 2)) {
+                    // 'SHH' is 'Щ', 'SH' is 'Ш'
+                    (Some('h'), Some('h')) | (Some('H'), Some('H')) => {
+                        skip_cycles += 2;
+                        "Щ"
+                    }
+                    (Some('h'), _) | (Some('H'), _) => {
+                        skip_cycles += 1;
+                        "Ш"
+                    }
+                    _ => "С",
+                },
+                's' => match (next, chars.get(i + 2)) {
+                    (Some('h'), Some('h')) | (Some('H'), Some('H')) => {
+                        skip_cycles += 2;
+                        "щ"
+                    }
+                    (Some('h'), _) | (Some('H'), _) => {
+                        skip_cycles += 1;
+                        "ш"
+                    }
+                    _ => "с",
+                },
+                'T' => "Т",
+                't' => "т",
+                'U' => "У",
+                'u' => "у",
+                'Ū' => "Ұ",
+                'ū' => "ұ",
+                'Ü' => "Ү",
+                'ü' => "ү",
+                'V' => "В",
+                'v' => "в",
+                'W' => "ВВ",
+                'w' => "вв",
+                'X' => "КС",
+                'x' => "кс",
+                'Y' => "Ы",
+                'y' => "ы",
+                'Z' => "З",
+                'z' => "з",
+                _ => {
+                    unchanged_str = c.to_string();
+                    unchanged_str.as_str()
+                }
+            });
+        }
+        output
+    }
+}
+
+/// ### digraph
+///
+/// Returns the transliteration of an uppercase letter which becomes a digraph:
+/// capitalized if the following character is a lowercase letter, uppercase otherwise
+fn digraph(next: Option<char>, upper: &'static str, capitalized: &'static str) -> &'static str {
+    match next {
+        Some(ch) if ch.is_lowercase() => capitalized,
+        _ => upper,
+    }
+}
+
+//@! Tests
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::translator::{new_translator, Language};
+
+    #[test]
+    fn test_translator_lang_kazakh_to_latin() {
+        // Kazakh translator
+        let translator: Box<dyn Translator> = new_translator(Language::Kazakh);
+        // All characters
+        assert_eq!(
+            translator.to_latin(&String::from("АӘБВГҒДЕЁЖЗИЙКҚЛМНҢОӨПРСТУҰҮФХҺЦЧШЩЪЫІЬЭЮЯ")),
+            String::from("AÄBVGĞDEIOJZIIKQLMNÑOÖPRSTUŪÜFHHCCHSHSHHYIEIUIA")
+        );
+        assert_eq!(
+            translator.to_latin(&String::from("аәбвгғдеёжзийкқлмнңоөпрстуұүфхһцчшщъыіьэюя")),
+            String::from("aäbvgğdeiojziikqlmnñoöprstuūüfhhcchshshhyıeiuia")
+        );
+        // The letters of the kazakh alphabet
+        assert_eq!(translator.to_latin(&String::from("Қазақстан")), String::from("Qazaqstan"));
+        assert_eq!(translator.to_latin(&String::from("әғқңөұүһі")), String::from("äğqñöūühı"));
+        assert_eq!(translator.to_latin(&String::from("ӘҒҚҢӨҰҮҺІ")), String::from("ÄĞQÑÖŪÜHI"));
+        // Simple commands (lower)
+        assert_eq!(translator.to_latin(&String::from("лс -ла /тмп")), String::from("ls -la /tmp"));
+        assert_eq!(translator.to_latin(&String::from("цд тестс/")), String::from("cd tests/"));
+        assert_eq!(translator.to_latin(&String::from("ексец зш")), String::from("exec zsh"));
+        assert_eq!(translator.to_latin(&String::from("чмод +кс филе")), String::from("chmod +x file"));
+        assert_eq!(translator.to_latin(&String::from("гит статус")), String::from("git status"));
+        // Simple commands (upper)
+        assert_eq!(translator.to_latin(&String::from("ЛС -ЛА /ТМП")), String::from("LS -LA /TMP"));
+        assert_eq!(translator.to_latin(&String::from("ЦД ТЕСТС/")), String::from("CD TESTS/"));
+        // Digraphs
+        assert_eq!(translator.to_latin(&String::from("Шелл ШЕЛЛ Ш")), String::from("Shell SHELL SH"));
+        assert_eq!(translator.to_latin(&String::from("Чат ЧАТ Ярлық ЯРЛЫҚ Юрта")), String::from("Chat CHAT Iarlyq IARLYQ Iurta"));
+        assert_eq!(translator.to_latin(&String::from("вв ВВ")), String::from("w W"));
+    }
+
+    #[test]
+    fn test_translator_lang_kazakh_to_cyrillic() {
+        // Kazakh translator
+        let translator: Box<dyn Translator> = new_translator(Language::Kazakh);
+        // All characters
+        assert_eq!(
+            translator.to_cyrillic(&String::from("AÄBCCHDEFGĞHIİJKLMNÑOÖPQRSSHSHHTUŪÜVWXYZ")),
+            String::from("АӘБЦЧДЕФГҒХИИЖКЛМНҢОӨПҚРСШЩТУҰҮВВВКСЫЗ")
+        );
+        assert_eq!(
+            translator.to_cyrillic(&String::from("aäbcchdefgğhiıjklmnñoöpqrsshshhtuūüvwxyz")),
+            String::from("аәбцчдефгғхиіжклмнңоөпқрсшщтуұүвввксыз")
+        );
+        // Kazakh words
+        assert_eq!(translator.to_cyrillic(&String::from("Qazaqstan")), String::from("Қазақстан"));
+        assert_eq!(translator.to_cyrillic(&String::from("ıa iu io")), String::from("іа ю ё"));
+        // Commands
+        assert_eq!(translator.to_cyrillic(&String::from("ls -la /tmp")), String::from("лс -ла /тмп"));
+        assert_eq!(translator.to_cyrillic(&String::from("cat file")), String::from("цат филе"));
+        // Digraphs: the case is the one of the first letter
+        assert_eq!(translator.to_cyrillic(&String::from("shell Shell SHELL sHELL")), String::from("шелл Шелл ШЕЛЛ шЕЛЛ"));
+        assert_eq!(translator.to_cyrillic(&String::from("chat Chat CHAT")), String::from("чат Чат ЧАТ"));
+        assert_eq!(translator.to_cyrillic(&String::from("Iarlyq IARLYQ iurta")), String::from("Ярлық ЯРЛЫҚ юрта"));
+        // Round trip
+        for text in ["ls -la /tmp", "Shell", "SHELL", "Qazaqstan äğñöūü"].iter() {
+            assert_eq!(translator.to_latin(&translator.to_cyrillic(&String::from(*text))), String::from(*text));
+        }
+    }
+}
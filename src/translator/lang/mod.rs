@@ -23,6 +23,8 @@
 *
 */
 
+use super::TransliterationStandard;
+
 /// ### Language
 ///
 /// Cyrillic alphabet language
@@ -31,27 +33,36 @@
 pub enum Language {
   Belarusian,
   Bulgarian,
+  Kazakh,
   Russian,
   Serbian,
   Ukrainian,
   Nil
 }
 
-/// ## Languages
-///
-/// Languages are empty structs which must implement the Translator trait
-
+// /// ## Languages
+// ///
+// /// Languages are structs which must implement the Translator trait; they contain the translator options they use
 //NOTE: languages are listed here
 pub(crate) struct Belarusian {}
 pub(crate) struct Bulgarian {}
-pub(crate) struct Russian {}
+pub(crate) struct Kazakh {}
+pub(crate) struct Russian {
+  pub strict_boundaries: bool,
+  pub standard: TransliterationStandard,
+}
 pub(crate) struct Serbian {}
-pub(crate) struct Ukrainian {}
+pub(crate) struct Ukrainian {
+  pub strict_boundaries: bool,
+  pub standard: TransliterationStandard,
+}
 pub(crate) struct Nil {}
 mod belarusian;
 mod bulgarian;
+mod kazakh;
 mod russian;
 mod serbian;
+mod standard;
 mod ukrainian;
 mod nil;
 
@@ -60,6 +71,7 @@ impl ToString for Language {
         match self {
         Language::Belarusian => String::from("бел"),
         Language::Bulgarian => String::from("блг"),
+        Language::Kazakh => String::from("каз"),
         Language::Russian => String::from("рус"),
         Language::Serbian => String::from("срб"),
         Language::Ukrainian => String::from("укр"),
@@ -68,6 +80,106 @@ impl ToString for Language {
     }
 }
 
+/// ### CharClass
+///
+/// CharClass describes a character adjacent to the one being transliterated
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub(crate) enum CharClass {
+  Edge,            //Beginning or end of the input
+  Separator(char), //Character which separates words
+  Letter(char),
+}
+
+/// ### Neighbours
+///
+/// Neighbours contains the classes of the characters preceding and following the one being transliterated
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub(crate) struct Neighbours {
+  pub prev: CharClass,
+  pub next: CharClass,
+}
+
+impl CharClass {
+  /// ### of
+  ///
+  /// Classify a character. With strict boundaries every character which is not alphabetic separates words;
+  /// otherwise only spaces do
+  pub(crate) fn of(c: Option<char>, strict: bool) -> CharClass {
+    match c {
+      None => CharClass::Edge,
+      Some(' ') => CharClass::Separator(' '),
+      Some(c) if strict && !c.is_alphabetic() => CharClass::Separator(c),
+      Some(c) => CharClass::Letter(c),
+    }
+  }
+}
+
+impl Neighbours {
+  /// ### of
+  ///
+  /// Classify the characters around the character at index `i` of `input`
+  pub(crate) fn of(input: &[char], i: usize, strict: bool) -> Neighbours {
+    let prev: Option<char> = match i {
+      0 => None,
+      _ => input.get(i - 1).copied(),
+    };
+    Neighbours {
+      prev: CharClass::of(prev, strict),
+      next: CharClass::of(input.get(i + 1).copied(), strict),
+    }
+  }
+}
+
+/// ### LetterTable
+///
+/// LetterTable contains the transliterations of a contiguous range of characters, starting from `first`,
+/// so that a character is looked up by its distance from `first`
+pub(crate) struct LetterTable {
+  pub first: char,
+  pub letters: &'static [&'static str],
+}
+
+impl LetterTable {
+  /// ### get
+  ///
+  /// Returns the transliteration of the character, if it is in the range of the table
+  pub(crate) fn get(&self, c: char) -> Option<&'static str> {
+    (c as u32)
+      .checked_sub(self.first as u32)
+      .and_then(|index| self.letters.get(index as usize))
+      .copied()
+  }
+}
+
+/// ### hard_k_at_word_end
+///
+/// Returns whether 'к' at the end of a word is transliterated to 'k' rather than to 'c':
+/// it is if it is a word by itself or if it follows 'к', 'а', 'и', 'о' or 'у'.
+/// Without strict boundaries 'у' is considered only at the end of the input, as in the previous releases
+pub(crate) fn hard_k_at_word_end(neighbours: &Neighbours, strict: bool) -> bool {
+  match neighbours.prev {
+    CharClass::Edge | CharClass::Separator(_) => true,
+    CharClass::Letter(ch) => match ch {
+      'К' | 'А' | 'И' | 'О' | 'к' | 'а' | 'и' | 'о' => true,
+      'У' | 'у' => strict || neighbours.next == CharClass::Edge,
+      _ => false,
+    },
+  }
+}
+
+/// ### unmapped_chars
+///
+/// Returns the characters of the input which are neither ASCII nor mapped, each once, in order of appearance
+pub(crate) fn unmapped_chars(input: &str, mapped: &dyn Fn(char) -> bool) -> Vec<char> {
+  let mut unmapped: Vec<char> = Vec::new();
+  for c in input.chars() {
+    if !c.is_ascii() && !mapped(c) && !unmapped.contains(&c) {
+      unmapped.push(c);
+    }
+  }
+  unmapped
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -77,10 +189,51 @@ mod tests {
   fn test_translator_language_to_string() {
     assert_eq!(Language::Belarusian.to_string(), String::from("бел"));
     assert_eq!(Language::Bulgarian.to_string(), String::from("блг"));
+    assert_eq!(Language::Kazakh.to_string(), String::from("каз"));
     assert_eq!(Language::Russian.to_string(), String::from("рус"));
     assert_eq!(Language::Serbian.to_string(), String::from("срб"));
     assert_eq!(Language::Ukrainian.to_string(), String::from("укр"));
     assert_eq!(Language::Nil.to_string(), String::from("nil"));
   }
 
+  #[test]
+  fn test_translator_lang_char_class() {
+    assert_eq!(CharClass::of(None, true), CharClass::Edge);
+    assert_eq!(CharClass::of(Some(' '), false), CharClass::Separator(' '));
+    assert_eq!(CharClass::of(Some(','), true), CharClass::Separator(','));
+    assert_eq!(CharClass::of(Some(','), false), CharClass::Letter(','));
+    assert_eq!(CharClass::of(Some('к'), true), CharClass::Letter('к'));
+    assert_eq!(CharClass::of(Some('k'), true), CharClass::Letter('k'));
+    assert_eq!(CharClass::of(Some('/'), true), CharClass::Separator('/'));
+    assert_eq!(CharClass::of(Some('-'), true), CharClass::Separator('-'));
+    let chars = |s: &str| s.chars().collect::<Vec<char>>();
+    let neighbours: Neighbours = Neighbours::of(&chars("(как)"), 3, true);
+    assert_eq!(neighbours.prev, CharClass::Letter('а'));
+    assert_eq!(neighbours.next, CharClass::Separator(')'));
+    assert!(hard_k_at_word_end(&neighbours, true));
+    let neighbours: Neighbours = Neighbours::of(&chars("к"), 0, false);
+    assert_eq!(neighbours, Neighbours { prev: CharClass::Edge, next: CharClass::Edge });
+    //'у' only at the end of the input without strict boundaries
+    assert!(hard_k_at_word_end(&Neighbours::of(&chars("тук"), 2, false), false));
+    assert!(!hard_k_at_word_end(&Neighbours::of(&chars("тук "), 2, false), false));
+    assert!(hard_k_at_word_end(&Neighbours::of(&chars("тук "), 2, true), true));
+    assert!(!hard_k_at_word_end(&Neighbours::of(&chars("рэк"), 2, true), true));
+  }
+
+  #[test]
+  fn test_translator_lang_letter_table() {
+    let table: LetterTable = LetterTable { first: 'a', letters: &["а", "б", "ц"] };
+    assert_eq!(table.get('a'), Some("а"));
+    assert_eq!(table.get('c'), Some("ц"));
+    assert_eq!(table.get('d'), None);
+    assert_eq!(table.get('A'), None);
+  }
+
+  #[test]
+  fn test_translator_lang_unmapped_chars() {
+    let mapped = |c: char| c == 'а';
+    assert_eq!(unmapped_chars("cat а", &mapped), Vec::<char>::new());
+    assert_eq!(unmapped_chars("é😀аé", &mapped), vec!['é', '😀']);
+  }
+
 }
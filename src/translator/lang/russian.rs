@@ -25,32 +25,57 @@
 
 use super::Russian;
 use super::super::Translator;
+use super::standard::{russian_table, table_maps, table_to_cyrillic, table_to_latin};
+use super::{hard_k_at_word_end, unmapped_chars, CharClass, LetterTable, Neighbours};
+
+//Transliteration of the letters from 'А' to 'я'.
+//The letters which depend on the adjacent characters are transliterated by the translator
+const CYRILLIC_TO_LATIN: LetterTable = LetterTable {
+  first: 'А',
+  letters: &[
+    "A", "B", "V", "G", "D", "E", "J", "Z", "I", "J", "C", "L", "M", "N", "O", "P",
+    "R", "S", "T", "U", "F", "H", "Z", "CH", "SH", "SHH", "'", "Y", "`", "E", "YU", "YA",
+    "a", "b", "v", "g", "d", "e", "j", "z", "i", "j", "c", "l", "m", "n", "o", "p",
+    "r", "s", "t", "u", "f", "h", "z", "ch", "sh", "shh", "'", "y", "`", "e", "yu", "ya",
+  ],
+};
+
+//Transliteration of the characters from 'A' to 'z' (the characters between 'Z' and 'a' are kept).
+//The letters which depend on the following character are transliterated by the translator
+const LATIN_TO_CYRILLIC: LetterTable = LetterTable {
+  first: 'A',
+  letters: &[
+    "А", "Б", "К", "Д", "Е", "Ф", "Г", "Х", "И", "Ж", "К", "Л", "М", "Н", "О", "П",
+    "КЮ", "Р", "С", "Т", "У", "В", "У", "КС", "Ы", "З", "[", "\\", "]", "^", "_", "`",
+    "а", "б", "к", "д", "е", "ф", "г", "х", "и", "ж", "к", "л", "м", "н", "о", "п",
+    "кю", "р", "с", "т", "у", "в", "у", "кс", "ы", "з",
+  ],
+};
 
 impl Translator for Russian {
   /// ### Russian translator
 
   /// Converts a string which contains russian cyrillic characters into a latin string.
   /// Characters between '"' (quotes) are escaped, expressions inside escaped blocks are translitarated anyway
-  /// Transliteration according to GOST 7.79-2000
+  /// Transliteration according to GOST 7.79-2000, unless another standard has been chosen
   fn to_latin(&self, input: &String) -> String {
-    let mut output = String::new();
+    if let Some(table) = russian_table(self.standard) {
+      return table_to_latin(table, input);
+    }
+    let chars: Vec<char> = input.chars().collect();
+    let mut output: String = String::with_capacity(input.len());
     let mut skip_counter: usize = 0;
-    for (i, c) in input.chars().enumerate() {
+    for (i, c) in chars.iter().copied().enumerate() {
       if skip_counter > 0 {
         //Skip cycles
         skip_counter -= 1; //Decrement skip counter
         continue;
       }
       //Push transliterated character
-      let unchanged_str: String;
-      output.push_str(match c {
-        'А' => "A",
-        'а' => "a",
-        'Б' => "B",
-        'б' => "b",
+      let latin: &str = match c {
         'В' => {
           //If following character is 'ь', then is always W
-          match input.chars().nth(i + 1) {
+          match chars.get(i + 1).copied() {
             Some(ch) => {
               match ch {
                 'ь' | 'Ь' => {
@@ -66,7 +91,7 @@ impl Translator for Russian {
         'в' =>
         //If following character is 'ь', then is always W
         {
-          match input.chars().nth(i + 1) {
+          match chars.get(i + 1).copied() {
             Some(ch) => {
               match ch {
                 'ь' | 'Ь' => {
@@ -79,22 +104,8 @@ impl Translator for Russian {
             None => "v",
           }
         }
-        'Г' => "G",
-        'г' => "g",
-        'Д' => "D",
-        'д' => "d",
-        'Е' | 'Э' => "E",
-        'е' | 'э' => "e",
         'Ё' => "YO",
         'ё' => "yo",
-        'Ж' => "J",
-        'ж' => "j",
-        'З' => "Z",
-        'з' => "z",
-        'И' => "I",
-        'и' => "i",
-        'Й' => "J",
-        'й' => "j",
         'К' => {
           //K is very complex, sometimes it is C, sometimes is K or even Q or X
           //If following letter is in (E, I, Y), then is K
@@ -102,24 +113,13 @@ impl Translator for Russian {
           //If following character is 'ь', then is always C
           //If following character is 'y', then is always Q
           //If follwing character is 'с', then is always X
-          match input.chars().nth(i + 1) {
-            Some(ch) => {
+          //At the end of a word, it depends on the previous character
+          let neighbours: Neighbours = Neighbours::of(&chars, i, self.strict_boundaries);
+          match neighbours.next {
+            CharClass::Letter(ch) => {
               //Check following character
               match ch {
                 'Е' | 'Э' | 'И' | 'Й' | 'Ы' | 'е' | 'э' | 'и' | 'й' | 'ы' => "K",
-                ' ' => {
-                  //Check previous character
-                  match i {
-                    0 => "K",
-                    _ => match input.chars().nth(i - 1) {
-                      Some(ch) => match ch {
-                        'К' | 'А' | 'И' | 'О' | 'к' | 'а' | 'и' | 'о' | ' ' => "K",
-                        _ => "C",
-                      },
-                      None => "K",
-                    },
-                  }
-                }
                 'Ю' | 'ю' => {
                   skip_counter += 1;
                   "Q"
@@ -139,45 +139,22 @@ impl Translator for Russian {
                 _ => "C",
               }
             }
-            None => {
-              //Check previous character
-              match i {
-                0 => "K",
-                _ => match input.chars().nth(i - 1) {
-                  //Check previous character
-                  Some(ch) => match ch {
-                    'К' | 'А' | 'И' | 'О' | 'У' | 'к' | 'а' | 'и' | 'о' | 'у' | ' ' => {
-                      "K"
-                    }
-                    _ => "C",
-                  },
-                  None => "K",
-                },
-              }
-            }
+            //Check previous character
+            _ => match hard_k_at_word_end(&neighbours, self.strict_boundaries) {
+              true => "K",
+              false => "C",
+            },
           }
         }
         'к' => {
           //K is very complex, sometimes it is C and sometimes is K
           //If following letter is in (E, I, Y), then is K
-          match input.chars().nth(i + 1) {
-            Some(ch) => {
+          let neighbours: Neighbours = Neighbours::of(&chars, i, self.strict_boundaries);
+          match neighbours.next {
+            CharClass::Letter(ch) => {
               //Check following character
               match ch {
                 'Е' | 'Э' | 'И' | 'Й' | 'Ы' | 'е' | 'э' | 'и' | 'й' | 'ы' => "k",
-                ' ' => {
-                  match i {
-                    0 => "k",
-                    _ => match input.chars().nth(i - 1) {
-                      //Check previous character
-                      Some(ch) => match ch {
-                        'К' | 'А' | 'И' | 'О' | 'к' | 'а' | 'и' | 'о' | ' ' => "k",
-                        _ => "c",
-                      },
-                      None => "k",
-                    },
-                  }
-                }
                 'Ю' | 'ю' => {
                   skip_counter += 1;
                   "q"
@@ -197,70 +174,24 @@ impl Translator for Russian {
                 _ => "c",
               }
             }
-            None => {
-              //Check previous character
-              match i {
-                0 => "k",
-                _ => match input.chars().nth(i - 1) {
-                  Some(ch) => match ch {
-                    'К' | 'А' | 'И' | 'О' | 'У' | 'к' | 'а' | 'и' | 'о' | 'у' | ' ' => {
-                      "k"
-                    }
-                    _ => "c",
-                  },
-                  None => "k",
-                },
-              }
-            }
+            //Check previous character
+            _ => match hard_k_at_word_end(&neighbours, self.strict_boundaries) {
+              true => "k",
+              false => "c",
+            },
           }
         }
-        'Л' => "L",
-        'л' => "l",
-        'М' => "M",
-        'м' => "m",
-        'Н' => "N",
-        'н' => "n",
-        'О' => "O",
-        'о' => "o",
-        'П' => "P",
-        'п' => "p",
-        'Р' => "R",
-        'р' => "r",
-        'С' => "S",
-        'с' => "s",
-        'Т' => "T",
-        'т' => "t",
-        'У' => "U",
-        'у' => "u",
-        'Ф' => "F",
-        'ф' => "f",
-        'Х' => "H",
-        'х' => "h",
-        'Ч' => "CH",
-        'ч' => "ch",
-        'Ш' => "SH",
-        'ш' => "sh",
-        'Щ' => "SHH",
-        'щ' => "shh",
-        'Ъ' => "'",
-        'ъ' => "'",
-        'Ы' => "Y",
-        'ы' => "y",
-        'Ь' => "`",
-        'ь' => "`",
-        'Ю' => "YU",
-        'ю' => "yu",
-        'Я' => "YA",
-        'я' => "ya",
-        'Ц' => "Z",
-        'ц' => "z",
         '№' => "#",
         '₽' => "$",
-        _ => {
-          unchanged_str = c.to_string();
-          unchanged_str.as_str()
-        }
-      });
+        _ => match CYRILLIC_TO_LATIN.get(c) {
+          Some(latin) => latin,
+          None => {
+            output.push(c);
+            continue;
+          }
+        },
+      };
+      output.push_str(latin);
     }
     output
   }
@@ -268,20 +199,19 @@ impl Translator for Russian {
   /// Converts a string which contains latin characters into a russian cyrillic string.
   /// Characters between quotes are escapes
   fn to_cyrillic(&self, input: &String) -> String {
-    let mut output: String = String::new();
+    if let Some(table) = russian_table(self.standard) {
+      return table_to_cyrillic(table, input);
+    }
+    let chars: Vec<char> = input.chars().collect();
+    let mut output: String = String::with_capacity(input.len());
     let mut skip_cycles: usize = 0;
-    for (i, c) in input.chars().enumerate() {
+    for (i, c) in chars.iter().copied().enumerate() {
       if skip_cycles > 0 {
         skip_cycles -= 1;
         continue;
       }
-      let unchanged_str: String;
-      output.push_str(match c {
-        'A' => "А",
-        'a' => "а",
-        'B' => "Б",
-        'b' => "б",
-        'C' => match input.chars().nth(i + 1) {
+      let cyrillic: &str = match c {
+        'C' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'h' | 'H' => {
               skip_cycles += 1;
@@ -291,7 +221,7 @@ impl Translator for Russian {
           },
           None => "К",
         },
-        'c' => match input.chars().nth(i + 1) {
+        'c' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'h' | 'H' => {
               skip_cycles += 1;
@@ -301,29 +231,21 @@ impl Translator for Russian {
           },
           None => "к",
         },
-        'D' => "Д",
-        'd' => "д",
-        'E' => "Е",
-        'e' => "е",
-        'F' => "Ф",
-        'f' => "ф",
-        'G' => match input.chars().nth(i + 1) {
+        'G' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'y' | 'Y' | 'e' | 'E' | 'i' | 'I' => "ДЖ",
             _ => "Г",
           },
           None => "Г",
         },
-        'g' => match input.chars().nth(i + 1) {
+        'g' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'y' | 'Y' | 'e' | 'E' | 'i' | 'I' => "дж",
             _ => "г",
           },
           None => "г",
         },
-        'H' => "Х",
-        'h' => "х",
-        'I' => match input.chars().nth(i + 1) {
+        'I' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'u' | 'U' => {
               skip_cycles += 1;
@@ -341,7 +263,7 @@ impl Translator for Russian {
           },
           None => "И",
         },
-        'i' => match input.chars().nth(i + 1) {
+        'i' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'u' | 'U' => {
               skip_cycles += 1;
@@ -359,25 +281,7 @@ impl Translator for Russian {
           },
           None => "и",
         },
-        'J' => "Ж",
-        'j' => "ж",
-        'K' => "К",
-        'k' => "к",
-        'L' => "Л",
-        'l' => "л",
-        'M' => "М",
-        'm' => "м",
-        'N' => "Н",
-        'n' => "н",
-        'O' => "О",
-        'o' => "о",
-        'P' => "П",
-        'p' => "п",
-        'Q' => "КЮ",
-        'q' => "кю",
-        'R' => "Р",
-        'r' => "р",
-        'S' => match input.chars().nth(i + 1) {
+        'S' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'h' | 'H' => {
               skip_cycles += 1;
@@ -387,7 +291,7 @@ impl Translator for Russian {
           },
           None => "С",
         },
-        's' => match input.chars().nth(i + 1) {
+        's' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'h' | 'H' => {
               skip_cycles += 1;
@@ -397,7 +301,7 @@ impl Translator for Russian {
           },
           None => "с",
         },
-        'T' => match input.chars().nth(i + 1) {
+        'T' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             's' | 'S' => {
               skip_cycles += 1;
@@ -407,7 +311,7 @@ impl Translator for Russian {
           },
           None => "Т",
         },
-        't' => match input.chars().nth(i + 1) {
+        't' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             's' | 'T' => {
               skip_cycles += 1;
@@ -417,15 +321,7 @@ impl Translator for Russian {
           },
           None => "т",
         },
-        'U' => "У",
-        'u' => "у",
-        'V' => "В",
-        'v' => "в",
-        'W' => "У",
-        'w' => "у",
-        'X' => "КС",
-        'x' => "кс",
-        'Y' => match input.chars().nth(i + 1) {
+        'Y' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'e' | 'E' => {
               skip_cycles += 1;
@@ -435,7 +331,7 @@ impl Translator for Russian {
           },
           None => "Ы",
         },
-        'y' => match input.chars().nth(i + 1) {
+        'y' => match chars.get(i + 1).copied() {
           Some(ch) => match ch {
             'e' | 'E' => {
               skip_cycles += 1;
@@ -445,16 +341,27 @@ impl Translator for Russian {
           },
           None => "ы",
         },
-        'Z' => "З",
-        'z' => "з",
-        _ => {
-          unchanged_str = c.to_string();
-          unchanged_str.as_str()
-        }
-      });
+        _ => match LATIN_TO_CYRILLIC.get(c) {
+          Some(cyrillic) => cyrillic,
+          None => {
+            output.push(c);
+            continue;
+          }
+        },
+      };
+      output.push_str(cyrillic);
     }
     output
   }
+
+  /// Returns the characters which are not transliterated: anything which is neither ASCII nor a russian letter
+  /// (or a letter of the table of the standard in use)
+  fn unmapped(&self, input: &String) -> Vec<char> {
+    match russian_table(self.standard) {
+      Some(table) => unmapped_chars(input, &|c| table_maps(table, c)),
+      None => unmapped_chars(input, &|c| matches!(c, 'А'..='я' | 'Ё' | 'ё' | '№' | '₽')),
+    }
+  }
 }
 
 //@! Tests
@@ -463,7 +370,7 @@ impl Translator for Russian {
 mod tests {
 
   use super::*;
-  use crate::translator::{new_translator, Language};
+  use crate::translator::{new_translator, new_translator_with_options, Language, TranslatorOptions};
 
   #[test]
   fn test_translator_lang_russian_to_latin() {
@@ -593,6 +500,35 @@ mod tests {
     assert_eq!(output, "# $");
   }
 
+  #[test]
+  fn test_translator_lang_russian_word_boundaries() {
+    let translator: Box<dyn Translator> = new_translator(Language::Russian);
+    let legacy: Box<dyn Translator> = new_translator_with_options(Language::Russian, TranslatorOptions { strict_boundaries: false, ..TranslatorOptions::default() });
+    //Punctuation
+    assert_eq!(translator.to_latin(&String::from("(как)")), String::from("(cak)"));
+    assert_eq!(translator.to_latin(&String::from("как, так")), String::from("cak, tak"));
+    assert_eq!(translator.to_latin(&String::from("ЭКХО \"ОК\"")), String::from("ECHO \"OK\""));
+    assert_eq!(legacy.to_latin(&String::from("(как)")), String::from("(cac)"));
+    assert_eq!(legacy.to_latin(&String::from("как, так")), String::from("cac, tak"));
+    //Slashes
+    assert_eq!(translator.to_latin(&String::from("/рак/мак")), String::from("/rak/mak"));
+    assert_eq!(translator.to_latin(&String::from("/бук/")), String::from("/buk/"));
+    assert_eq!(translator.to_latin(&String::from("рэк/к")), String::from("rec/k"));
+    assert_eq!(legacy.to_latin(&String::from("/рак/мак")), String::from("/rac/mak"));
+    assert_eq!(legacy.to_latin(&String::from("/бук/")), String::from("/buc/"));
+    //Hyphens
+    assert_eq!(translator.to_latin(&String::from("ок-ок")), String::from("ok-ok"));
+    assert_eq!(translator.to_latin(&String::from("рэк-рэк")), String::from("rec-rec"));
+    assert_eq!(translator.to_latin(&String::from("-к")), String::from("-k"));
+    assert_eq!(legacy.to_latin(&String::from("ок-ок")), String::from("oc-ok"));
+    assert_eq!(legacy.to_latin(&String::from("-к")), String::from("-c"));
+    //Spaces are handled in the same way, except for 'к' after 'у' at the end of a word: it is 'k' in strict mode
+    assert_eq!(translator.to_latin(&String::from("рэк к к")), legacy.to_latin(&String::from("рэк к к")));
+    assert_eq!(translator.to_latin(&String::from("ок ок")), legacy.to_latin(&String::from("ок ок")));
+    assert_eq!(translator.to_latin(&String::from("тук к")), String::from("tuk k"));
+    assert_eq!(legacy.to_latin(&String::from("тук к")), String::from("tuc k"));
+  }
+
   #[test]
   fn test_translator_lang_russian_to_cyrillic() {
     let translator: Box<dyn Translator> = new_translator(Language::Russian);
@@ -33,6 +33,7 @@ impl Translator for Serbian {
     /// Characters between '"' (quotes) are escaped, expressions inside escaped blocks are translitarated anyway
     /// Transliteration according to GOST 7.79-2000
     fn to_latin(&self, input: &String) -> String {
+        let chars: Vec<char> = input.chars().collect();
         let mut output = String::new();
         let mut skip_counter: usize = 0;
         for (i, c) in input.chars().enumerate() {
@@ -50,7 +51,7 @@ impl Translator for Serbian {
                 'б' => "b",
                 'В' => {
                     //If following character is 'В', then is always W
-                    match input.chars().nth(i + 1) {
+                    match chars.get(i + 1).copied() {
                         Some(ch) => {
                             match ch {
                                 'в' | 'В' => {
@@ -66,7 +67,7 @@ impl Translator for Serbian {
                 'в' =>
                 //If following character is 'В', then is always W
                 {
-                    match input.chars().nth(i + 1) {
+                    match chars.get(i + 1).copied() {
                         Some(ch) => {
                             match ch {
                                 'в' | 'В' => {
@@ -94,7 +95,7 @@ impl Translator for Serbian {
                 'И' =>
                 //If following character is 'И', then is always Y
                 {
-                    match input.chars().nth(i + 1) {
+                    match chars.get(i + 1).copied() {
                         Some(ch) => {
                             match ch {
                                 'и' | 'И' => {
@@ -110,7 +111,7 @@ impl Translator for Serbian {
                 'и' =>
                 //If following character is 'И', then is always Y
                 {
-                    match input.chars().nth(i + 1) {
+                    match chars.get(i + 1).copied() {
                         Some(ch) => {
                             match ch {
                                 'и' | 'И' => {
@@ -126,7 +127,7 @@ impl Translator for Serbian {
                 'Ћ' => "C",
                 'ћ' => "c",
                 'К' => {
-                    match input.chars().nth(i + 1) {
+                    match chars.get(i + 1).copied() {
                         //If following character is 'С', then is always X
                         Some(ch) => {
                             match ch {
@@ -136,7 +137,7 @@ impl Translator for Serbian {
                                 }
                                 'и' | 'И' => {
                                     // If following characters are 'ИУ', then is always Q
-                                    match input.chars().nth(i + 2) {
+                                    match chars.get(i + 2).copied() {
                                         Some(ch) => {
                                             match ch {
                                                 'у' | 'У' => {
@@ -156,7 +157,7 @@ impl Translator for Serbian {
                     }
                 }
                 'к' => {
-                    match input.chars().nth(i + 1) {
+                    match chars.get(i + 1).copied() {
                         //If following character is 'С', then is always X
                         Some(ch) => {
                             match ch {
@@ -166,7 +167,7 @@ impl Translator for Serbian {
                                 }
                                 'и' | 'И' => {
                                     // If following characters are 'ИУ', then is always Q
-                                    match input.chars().nth(i + 2) {
+                                    match chars.get(i + 2).copied() {
                                         Some(ch) => {
                                             match ch {
                                                 'у' | 'У' => {
@@ -231,6 +232,7 @@ impl Translator for Serbian {
     /// Converts a string which contains latin characters into a serbian cyrillic string.
     /// Characters between quotes are escapes
     fn to_cyrillic(&self, input: &String) -> String {
+        let chars: Vec<char> = input.chars().collect();
         let mut output: String = String::new();
         let mut skip_cycles: usize = 0;
         for (i, c) in input.chars().enumerate() {
@@ -244,7 +246,7 @@ impl Translator for Serbian {
                 'a' => "а",
                 'B' => "Б",
                 'b' => "б",
-                'C' => match input.chars().nth(i + 1) {
+                'C' => match chars.get(i + 1).copied() {
                     Some(ch) => match ch {
                         'h' | 'H' => {
                             skip_cycles += 1;
@@ -254,7 +256,7 @@ impl Translator for Serbian {
                     },
                     None => "К",
                 },
-                'c' => match input.chars().nth(i + 1) {
+                'c' => match chars.get(i + 1).copied() {
                     Some(ch) => match ch {
                         'h' | 'H' => {
                             skip_cycles += 1;
@@ -264,7 +266,7 @@ impl Translator for Serbian {
                     },
                     None => "к",
                 },
-                'D' => match input.chars().nth(i + 1) {
+                'D' => match chars.get(i + 1).copied() {
                     // If 'J' follows => Ђ; if 'Z' follows => Џ
                     Some(ch) => match ch {
                         'J' | 'j' => {
@@ -279,7 +281,7 @@ impl Translator for Serbian {
                     },
                     None => "Д",
                 },
-                'd' => match input.chars().nth(i + 1) {
+                'd' => match chars.get(i + 1).copied() {
                     // If 'J' follows => Ђ; if 'Z' follows => Џ
                     Some(ch) => match ch {
                         'J' | 'j' => {
@@ -298,14 +300,14 @@ impl Translator for Serbian {
                 'e' => "е",
                 'F' => "Ф",
                 'f' => "ф",
-                'G' => match input.chars().nth(i + 1) {
+                'G' => match chars.get(i + 1).copied() {
                     Some(ch) => match ch {
                         'y' | 'Y' | 'e' | 'E' | 'i' | 'I' => "ДЖ",
                         _ => "Г",
                     },
                     None => "Г",
                 },
-                'g' => match input.chars().nth(i + 1) {
+                'g' => match chars.get(i + 1).copied() {
                     Some(ch) => match ch {
                         'y' | 'Y' | 'e' | 'E' | 'i' | 'I' => "дж",
                         _ => "г",
@@ -320,7 +322,7 @@ impl Translator for Serbian {
                 'j' => "ј",
                 'K' => "К",
                 'k' => "к",
-                'L' => match input.chars().nth(i + 1) {
+                'L' => match chars.get(i + 1).copied() {
                     // If 'J' follows => Љ
                     Some(ch) => match ch {
                         'J' | 'j' => {
@@ -331,7 +333,7 @@ impl Translator for Serbian {
                     },
                     None => "Л",
                 },
-                'l' => match input.chars().nth(i + 1) {
+                'l' => match chars.get(i + 1).copied() {
                     // If 'J' follows => Љ
                     Some(ch) => match ch {
                         'J' | 'j' => {
@@ -344,7 +346,7 @@ impl Translator for Serbian {
                 },
                 'M' => "М",
                 'm' => "м",
-                'N' => match input.chars().nth(i + 1) {
+                'N' => match chars.get(i + 1).copied() {
                     // If 'J' follows => Њ
                     Some(ch) => match ch {
                         'J' | 'j' => {
@@ -355,7 +357,7 @@ impl Translator for Serbian {
                     },
                     None => "Н",
                 },
-                'n' => match input.chars().nth(i + 1) {
+                'n' => match chars.get(i + 1).copied() {
                     // If 'J' follows => Њ
                     Some(ch) => match ch {
                         'J' | 'j' => {
@@ -374,7 +376,7 @@ impl Translator for Serbian {
                 'q' => "ку",
                 'R' => "Р",
                 'r' => "р",
-                'S' => match input.chars().nth(i + 1) {
+                'S' => match chars.get(i + 1).copied() {
                     Some(ch) => match ch {
                         'h' | 'H' => {
                             skip_cycles += 1;
@@ -384,7 +386,7 @@ impl Translator for Serbian {
                     },
                     None => "С",
                 },
-                's' => match input.chars().nth(i + 1) {
+                's' => match chars.get(i + 1).copied() {
                     Some(ch) => match ch {
                         'h' | 'H' => {
                             skip_cycles += 1;
@@ -394,7 +396,7 @@ impl Translator for Serbian {
                     },
                     None => "с",
                 },
-                'T' => match input.chars().nth(i + 1) {
+                'T' => match chars.get(i + 1).copied() {
                     Some(ch) => match ch {
                         's' | 'S' => {
                             skip_cycles += 1;
@@ -404,7 +406,7 @@ impl Translator for Serbian {
                     },
                     None => "Т",
                 },
-                't' => match input.chars().nth(i + 1) {
+                't' => match chars.get(i + 1).copied() {
                     Some(ch) => match ch {
                         's' | 'T' => {
                             skip_cycles += 1;
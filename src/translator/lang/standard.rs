@@ -0,0 +1,197 @@
+//! ### Standard
+//!
+//! `standard` implements the transliteration standards which can be chosen instead of the GOST 7.79 tables
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::super::TransliterationStandard;
+
+/// ### Table
+///
+/// Table associates the lowercase cyrillic letters to their latin transliteration.
+/// When transliterating to cyrillic, the longest latin sequence wins; on ties the first entry does
+pub(crate) type Table = &'static [(char, &'static str)];
+
+const RUSSIAN_BGN_PCGN: Table = &[
+  ('а', "a"), ('б', "b"), ('в', "v"), ('г', "g"), ('д', "d"), ('е', "e"), ('ё', "ë"), ('ж', "zh"),
+  ('з', "z"), ('и', "i"), ('й', "y"), ('к', "k"), ('л', "l"), ('м', "m"), ('н', "n"), ('о', "o"),
+  ('п', "p"), ('р', "r"), ('с', "s"), ('т', "t"), ('у', "u"), ('ф', "f"), ('х', "kh"), ('ц', "ts"),
+  ('ч', "ch"), ('ш', "sh"), ('щ', "shch"), ('ъ', "”"), ('ы', "y"), ('ь', "’"), ('э', "e"), ('ю', "yu"),
+  ('я', "ya"),
+];
+
+const RUSSIAN_SCIENTIFIC: Table = &[
+  ('а', "a"), ('б', "b"), ('в', "v"), ('г', "g"), ('д', "d"), ('е', "e"), ('ё', "ë"), ('ж', "ž"),
+  ('з', "z"), ('и', "i"), ('й', "j"), ('к', "k"), ('л', "l"), ('м', "m"), ('н', "n"), ('о', "o"),
+  ('п', "p"), ('р', "r"), ('с', "s"), ('т', "t"), ('у', "u"), ('ф', "f"), ('х', "x"), ('ц', "c"),
+  ('ч', "č"), ('ш', "š"), ('щ', "šč"), ('ъ', "ʺ"), ('ы', "y"), ('ь', "ʹ"), ('э', "è"), ('ю', "ju"),
+  ('я', "ja"),
+];
+
+const UKRAINIAN_BGN_PCGN: Table = &[
+  ('а', "a"), ('б', "b"), ('в', "v"), ('г', "h"), ('ґ', "g"), ('д', "d"), ('е', "e"), ('є', "ye"),
+  ('ж', "zh"), ('з', "z"), ('и', "y"), ('і', "i"), ('ї', "yi"), ('й', "y"), ('к', "k"), ('л', "l"),
+  ('м', "m"), ('н', "n"), ('о', "o"), ('п', "p"), ('р', "r"), ('с', "s"), ('т', "t"), ('у', "u"),
+  ('ф', "f"), ('х', "kh"), ('ц', "ts"), ('ч', "ch"), ('ш', "sh"), ('щ', "shch"), ('ь', "’"), ('ю', "yu"),
+  ('я', "ya"),
+];
+
+const UKRAINIAN_SCIENTIFIC: Table = &[
+  ('а', "a"), ('б', "b"), ('в', "v"), ('г', "h"), ('ґ', "g"), ('д', "d"), ('е', "e"), ('є', "je"),
+  ('ж', "ž"), ('з', "z"), ('и', "y"), ('і', "i"), ('ї', "ji"), ('й', "j"), ('к', "k"), ('л', "l"),
+  ('м', "m"), ('н', "n"), ('о', "o"), ('п', "p"), ('р', "r"), ('с', "s"), ('т', "t"), ('у', "u"),
+  ('ф', "f"), ('х', "x"), ('ц', "c"), ('ч', "č"), ('ш', "š"), ('щ', "šč"), ('ь', "ʹ"), ('ю', "ju"),
+  ('я', "ja"),
+];
+
+/// ### russian_table
+///
+/// Returns the russian table of the standard; GOST 7.79 has no table, since it's implemented by the Russian translator
+pub(crate) fn russian_table(standard: TransliterationStandard) -> Option<Table> {
+  match standard {
+    TransliterationStandard::Gost779 => None,
+    TransliterationStandard::BgnPcgn => Some(RUSSIAN_BGN_PCGN),
+    TransliterationStandard::Scientific => Some(RUSSIAN_SCIENTIFIC),
+  }
+}
+
+/// ### ukrainian_table
+///
+/// Returns the ukrainian table of the standard; GOST 7.79 has no table, since it's implemented by the Ukrainian translator
+pub(crate) fn ukrainian_table(standard: TransliterationStandard) -> Option<Table> {
+  match standard {
+    TransliterationStandard::Gost779 => None,
+    TransliterationStandard::BgnPcgn => Some(UKRAINIAN_BGN_PCGN),
+    TransliterationStandard::Scientific => Some(UKRAINIAN_SCIENTIFIC),
+  }
+}
+
+/// ### table_to_latin
+///
+/// Transliterate the cyrillic letters of the input with the table; other characters are kept.
+/// Uppercase letters followed by a lowercase letter are capitalized (e.g. "Щи" => "Shchi"), otherwise uppercased ("ЩИ" => "SHCHI")
+pub(crate) fn table_to_latin(table: Table, input: &str) -> String {
+  let chars: Vec<char> = input.chars().collect();
+  let mut output: String = String::with_capacity(input.len());
+  for (i, c) in chars.iter().enumerate() {
+    let lower: char = c.to_lowercase().next().unwrap_or(*c);
+    match table.iter().find(|(cyr, _)| *cyr == lower) {
+      None => output.push(*c),
+      Some((_, latin)) => match c.is_uppercase() {
+        false => output.push_str(latin),
+        true => match chars.get(i + 1) {
+          Some(next) if next.is_lowercase() => {
+            let mut latin = latin.chars();
+            if let Some(first) = latin.next() {
+              output.extend(first.to_uppercase());
+            }
+            output.push_str(latin.as_str());
+          }
+          _ => output.push_str(latin.to_uppercase().as_str()),
+        },
+      },
+    }
+  }
+  output
+}
+
+/// ### table_maps
+///
+/// Returns whether the table has a transliteration for the cyrillic letter (in any case)
+pub(crate) fn table_maps(table: Table, c: char) -> bool {
+  let lower: char = c.to_lowercase().next().unwrap_or(c);
+  table.iter().any(|(cyr, _)| *cyr == lower)
+}
+
+/// ### table_to_cyrillic
+///
+/// Transliterate the latin sequences of the table in the input to cyrillic; other characters are kept.
+/// The cyrillic letter is uppercase if the first character of the sequence is
+pub(crate) fn table_to_cyrillic(table: Table, input: &str) -> String {
+  let chars: Vec<char> = input.chars().collect();
+  let mut output: String = String::with_capacity(input.len());
+  let mut i: usize = 0;
+  while i < chars.len() {
+    let mut best: Option<(char, usize)> = None;
+    for (cyr, latin) in table.iter() {
+      let len: usize = latin.chars().count();
+      let matches: bool = i + len <= chars.len()
+        && latin.chars().zip(chars[i..].iter()).all(|(l, c)| c.to_lowercase().next() == Some(l));
+      if matches && best.map(|(_, best_len)| len > best_len).unwrap_or(true) {
+        best = Some((*cyr, len));
+      }
+    }
+    match best {
+      None => {
+        output.push(chars[i]);
+        i += 1;
+      }
+      Some((cyr, len)) => {
+        match chars[i].is_uppercase() {
+          true => output.extend(cyr.to_uppercase()),
+          false => output.push(cyr),
+        }
+        i += len;
+      }
+    }
+  }
+  output
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_translator_lang_standard_table_to_latin() {
+    let table: Table = russian_table(TransliterationStandard::BgnPcgn).unwrap();
+    assert_eq!(table_to_latin(table, "Щи и борщ"), String::from("Shchi i borshch"));
+    assert_eq!(table_to_latin(table, "ЩИ"), String::from("SHCHI"));
+    assert_eq!(table_to_latin(table, "Ж"), String::from("ZH"));
+    assert_eq!(table_to_latin(table, "ls -l ~/Документы"), String::from("ls -l ~/Dokumenty"));
+    assert!(russian_table(TransliterationStandard::Gost779).is_none());
+    assert!(ukrainian_table(TransliterationStandard::Gost779).is_none());
+  }
+
+  #[test]
+  fn test_translator_lang_standard_table_maps() {
+    let table: Table = ukrainian_table(TransliterationStandard::BgnPcgn).unwrap();
+    assert!(table_maps(table, 'ї'));
+    assert!(table_maps(table, 'Ґ'));
+    assert!(!table_maps(table, 'ы'));
+    assert!(!table_maps(table, 'a'));
+  }
+
+  #[test]
+  fn test_translator_lang_standard_table_to_cyrillic() {
+    let table: Table = russian_table(TransliterationStandard::BgnPcgn).unwrap();
+    //Longest sequence wins
+    assert_eq!(table_to_cyrillic(table, "Shchi i borshch"), String::from("Щи и борщ"));
+    assert_eq!(table_to_cyrillic(table, "SHCHI"), String::from("ЩИ"));
+    //Letters which are not in the table are kept
+    assert_eq!(table_to_cyrillic(table, "wq"), String::from("wq"));
+    let table: Table = russian_table(TransliterationStandard::Scientific).unwrap();
+    assert_eq!(table_to_cyrillic(table, "Ščuka jaščik"), String::from("Щука ящик"));
+    assert_eq!(table_to_cyrillic(table, ""), String::new());
+  }
+}
@@ -0,0 +1,325 @@
+//! ## Messages
+//!
+//! `messages` is the message catalog of Pyc: it provides the localized names and phrases rendered by the shell for each language
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use super::lang::Language;
+
+use std::time::Duration;
+
+const EN_MONTHS: [&str; 12] = [
+  "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December",
+];
+const EN_MONTHS_ABBR: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+const EN_WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+const EN_WEEKDAYS_ABBR: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// ### month_name
+///
+/// Returns the name of the month (1-12) in the provided language
+pub fn month_name(language: Language, month: u32) -> &'static str {
+  let months: [&str; 12] = match language {
+    Language::Belarusian => [
+      "студзень", "люты", "сакавік", "красавік", "травень", "чэрвень", "ліпень", "жнівень", "верасень", "кастрычнік", "лістапад", "снежань",
+    ],
+    Language::Bulgarian => [
+      "януари", "февруари", "март", "април", "май", "юни", "юли", "август", "септември", "октомври", "ноември", "декември",
+    ],
+    Language::Kazakh => [
+      "қаңтар", "ақпан", "наурыз", "сәуір", "мамыр", "маусым", "шілде", "тамыз", "қыркүйек", "қазан", "қараша", "желтоқсан",
+    ],
+    Language::Russian => [
+      "январь", "февраль", "март", "апрель", "май", "июнь", "июль", "август", "сентябрь", "октябрь", "ноябрь", "декабрь",
+    ],
+    Language::Serbian => [
+      "јануар", "фебруар", "март", "април", "мај", "јун", "јул", "август", "септембар", "октобар", "новембар", "децембар",
+    ],
+    Language::Ukrainian => [
+      "січень", "лютий", "березень", "квітень", "травень", "червень", "липень", "серпень", "вересень", "жовтень", "листопад", "грудень",
+    ],
+    Language::Nil => EN_MONTHS,
+  };
+  months[month_index(month)]
+}
+
+/// ### month_abbr
+///
+/// Returns the abbreviated name of the month (1-12) in the provided language
+pub fn month_abbr(language: Language, month: u32) -> &'static str {
+  let months: [&str; 12] = match language {
+    Language::Belarusian => ["сту", "лют", "сак", "кра", "тра", "чэр", "ліп", "жні", "вер", "кас", "ліс", "сне"],
+    Language::Bulgarian => ["яну", "фев", "мар", "апр", "май", "юни", "юли", "авг", "сеп", "окт", "ное", "дек"],
+    Language::Kazakh => ["қаң", "ақп", "нау", "сәу", "мам", "мау", "шіл", "там", "қыр", "қаз", "қар", "жел"],
+    Language::Russian => ["янв", "фев", "мар", "апр", "май", "июн", "июл", "авг", "сен", "окт", "ноя", "дек"],
+    Language::Serbian => ["јан", "феб", "мар", "апр", "мај", "јун", "јул", "авг", "сеп", "окт", "нов", "дец"],
+    Language::Ukrainian => ["січ", "лют", "бер", "кві", "тра", "чер", "лип", "сер", "вер", "жов", "лис", "гру"],
+    Language::Nil => EN_MONTHS_ABBR,
+  };
+  months[month_index(month)]
+}
+
+/// ### weekday_name
+///
+/// Returns the name of the weekday (0-6, 0 is Sunday) in the provided language
+pub fn weekday_name(language: Language, weekday: u32) -> &'static str {
+  let weekdays: [&str; 7] = match language {
+    Language::Belarusian => ["нядзеля", "панядзелак", "аўторак", "серада", "чацвер", "пятніца", "субота"],
+    Language::Bulgarian => ["неделя", "понеделник", "вторник", "сряда", "четвъртък", "петък", "събота"],
+    Language::Kazakh => ["жексенбі", "дүйсенбі", "сейсенбі", "сәрсенбі", "бейсенбі", "жұма", "сенбі"],
+    Language::Russian => ["воскресенье", "понедельник", "вторник", "среда", "четверг", "пятница", "суббота"],
+    Language::Serbian => ["недеља", "понедељак", "уторак", "среда", "четвртак", "петак", "субота"],
+    Language::Ukrainian => ["неділя", "понеділок", "вівторок", "середа", "четвер", "пʼятниця", "субота"],
+    Language::Nil => EN_WEEKDAYS,
+  };
+  weekdays[weekday as usize % 7]
+}
+
+/// ### weekday_abbr
+///
+/// Returns the abbreviated name of the weekday (0-6, 0 is Sunday) in the provided language
+pub fn weekday_abbr(language: Language, weekday: u32) -> &'static str {
+  let weekdays: [&str; 7] = match language {
+    Language::Belarusian => ["нд", "пн", "аў", "ср", "чц", "пт", "сб"],
+    Language::Bulgarian | Language::Ukrainian => ["нд", "пн", "вт", "ср", "чт", "пт", "сб"],
+    Language::Kazakh => ["жс", "дс", "сс", "ср", "бс", "жм", "сб"],
+    Language::Russian => ["вс", "пн", "вт", "ср", "чт", "пт", "сб"],
+    Language::Serbian => ["нед", "пон", "уто", "сре", "чет", "пет", "суб"],
+    Language::Nil => EN_WEEKDAYS_ABBR,
+  };
+  weekdays[weekday as usize % 7]
+}
+
+/// ### decimal_separator
+///
+/// Returns the character which separates the integer part from the fractional part of a number in the provided language
+pub fn decimal_separator(language: Language) -> char {
+  match language {
+    Language::Nil => '.',
+    _ => ',',
+  }
+}
+
+/// ### duration_prefix
+///
+/// Returns the phrase written before the duration of the last command
+pub fn duration_prefix(language: Language) -> &'static str {
+  match language {
+    Language::Nil => "took ",
+    _ => "",
+  }
+}
+
+/// ### format_duration
+///
+/// Format how long a command took: seconds with a decimal below a minute (`5.1s`), then minutes and seconds (`2m 7s`),
+/// then hours and minutes (`1h 3m`). The units are truncated, so a duration is never written as the next unit (e.g. 59.96s is `59.9s`)
+pub fn format_duration(language: Language, elapsed: Duration) -> String {
+  let (hours, minutes, seconds): (&str, &str, &str) = match language {
+    Language::Belarusian => ("гадз", "хв", "с"),
+    Language::Bulgarian | Language::Russian | Language::Serbian => ("ч", "мин", "с"),
+    Language::Kazakh => ("сағ", "мин", "с"),
+    Language::Ukrainian => ("год", "хв", "с"),
+    Language::Nil => ("h", "m", "s"),
+  };
+  let secs: u64 = elapsed.as_secs();
+  match secs {
+    0..=59 => {
+      let tenths: u128 = elapsed.as_millis() / 100;
+      format!("{}{}{}{}", tenths / 10, decimal_separator(language), tenths % 10, seconds)
+    }
+    60..=3599 => format!("{}{} {}{}", secs / 60, minutes, secs % 60, seconds),
+    _ => format!("{}{} {}{}", secs / 3600, hours, (secs % 3600) / 60, minutes),
+  }
+}
+
+/// ### exit_status_meaning
+///
+/// Returns what an exit status means, following the conventions of the shell:
+/// 2 is a misuse of the command, 126 and 127 mean the command couldn't be run and 128 + N means it was killed by signal N
+pub fn exit_status_meaning(language: Language, rc: u8) -> String {
+  //error, misuse, not executable, not found, interrupted, killed by signal
+  let meanings: [&str; 6] = match language {
+    Language::Belarusian => [
+      "памылка", "няправільнае выкарыстанне", "каманду немагчыма выканаць", "каманда не знойдзена", "перапынена", "завершана сігналам {}",
+    ],
+    Language::Bulgarian => [
+      "грешка", "неправилна употреба", "командата не може да бъде изпълнена", "командата не е намерена", "прекъснато", "прекратено със сигнал {}",
+    ],
+    Language::Kazakh => [
+      "қате", "дұрыс емес қолдану", "команданы орындау мүмкін емес", "команда табылмады", "үзілді", "{} сигналымен тоқтатылды",
+    ],
+    Language::Russian => [
+      "ошибка", "неверное использование", "команда не может быть выполнена", "команда не найдена", "прервано", "завершено сигналом {}",
+    ],
+    Language::Serbian => [
+      "грешка", "неисправна употреба", "команда не може да се изврши", "команда није пронађена", "прекинуто", "прекинуто сигналом {}",
+    ],
+    Language::Ukrainian => [
+      "помилка", "неправильне використання", "команду неможливо виконати", "команду не знайдено", "перервано", "завершено сигналом {}",
+    ],
+    Language::Nil => [
+      "error", "invalid usage", "command cannot be executed", "command not found", "interrupted", "terminated by signal {}",
+    ],
+  };
+  match rc {
+    2 => String::from(meanings[1]),
+    126 => String::from(meanings[2]),
+    127 => String::from(meanings[3]),
+    130 => String::from(meanings[4]),
+    129..=192 => meanings[5].replace("{}", (rc - 128).to_string().as_str()),
+    _ => String::from(meanings[0]),
+  }
+}
+
+/// ### usage_tips
+///
+/// Returns the usage tips shown by the startup banner in the provided language
+pub fn usage_tips(language: Language) -> [&'static str; 5] {
+  match language {
+    Language::Belarusian => [
+      "Тэкст у двукоссі не транслітаруецца",
+      "Ctrl+R шукае ў гісторыі",
+      "`reload` перачытвае канфігурацыю",
+      "`pyc doctor` правярае, што ўсё працуе",
+      "`pyc convert --to-latin <файл>` транслітаруе цэлыя файлы",
+    ],
+    Language::Bulgarian => [
+      "Текстът в кавички не се транслитерира",
+      "Ctrl+R търси в историята",
+      "`reload` зарежда отново конфигурацията",
+      "`pyc doctor` проверява дали всичко работи",
+      "`pyc convert --to-latin <файл>` транслитерира цели файлове",
+    ],
+    Language::Kazakh => [
+      "Тырнақшадағы мәтін транслитерацияланбайды",
+      "Ctrl+R тарихтан іздейді",
+      "`reload` конфигурацияны қайта оқиды",
+      "`pyc doctor` бәрі жұмыс істейтінін тексереді",
+      "`pyc convert --to-latin <файл>` бүкіл файлдарды транслитерациялайды",
+    ],
+    Language::Russian => [
+      "Текст в кавычках не транслитерируется",
+      "Ctrl+R ищет в истории",
+      "`reload` перечитывает конфигурацию",
+      "`pyc doctor` проверяет, что всё работает",
+      "`pyc convert --to-latin <файл>` транслитерирует целые файлы",
+    ],
+    Language::Serbian => [
+      "Текст под наводницима се не пресловљава",
+      "Ctrl+R претражује историју",
+      "`reload` поново учитава конфигурацију",
+      "`pyc doctor` проверава да ли све ради",
+      "`pyc convert --to-latin <датотека>` пресловљава целе датотеке",
+    ],
+    Language::Ukrainian => [
+      "Текст у лапках не транслітерується",
+      "Ctrl+R шукає в історії",
+      "`reload` перечитує конфігурацію",
+      "`pyc doctor` перевіряє, що все працює",
+      "`pyc convert --to-latin <файл>` транслітерує цілі файли",
+    ],
+    Language::Nil => [
+      "Text between quotes is not transliterated",
+      "Ctrl+R searches the history",
+      "`reload` reads the configuration again",
+      "`pyc doctor` checks that everything works",
+      "`pyc convert --to-latin <file>` transliterates whole files",
+    ],
+  }
+}
+
+/// ### format_decimal
+///
+/// Format a number with the provided amount of decimals, using the decimal separator of the language
+pub fn format_decimal(language: Language, value: f64, decimals: usize) -> String {
+  let number: String = format!("{:.*}", decimals, value);
+  match decimal_separator(language) {
+    '.' => number,
+    separator => number.replace('.', separator.to_string().as_str()),
+  }
+}
+
+/// ### month_index
+///
+/// Converts a month in range 1-12 to an array index
+fn month_index(month: u32) -> usize {
+  (month as usize + 11) % 12
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_messages_dates() {
+    assert_eq!(month_name(Language::Russian, 1), "январь");
+    assert_eq!(month_name(Language::Nil, 12), "December");
+    assert_eq!(month_abbr(Language::Serbian, 5), "мај");
+    assert_eq!(month_abbr(Language::Nil, 3), "Mar");
+    assert_eq!(weekday_name(Language::Ukrainian, 0), "неділя");
+    assert_eq!(weekday_name(Language::Nil, 6), "Saturday");
+    assert_eq!(weekday_abbr(Language::Russian, 1), "пн");
+    assert_eq!(weekday_abbr(Language::Nil, 0), "Sun");
+    assert_eq!(month_name(Language::Kazakh, 1), "қаңтар");
+    assert_eq!(weekday_name(Language::Kazakh, 5), "жұма");
+  }
+
+  #[test]
+  fn test_messages_numbers() {
+    assert_eq!(decimal_separator(Language::Nil), '.');
+    assert_eq!(decimal_separator(Language::Bulgarian), ',');
+    assert_eq!(format_decimal(Language::Russian, 5.1, 1), String::from("5,1"));
+    assert_eq!(format_decimal(Language::Nil, 5.14, 1), String::from("5.1"));
+    assert_eq!(format_decimal(Language::Nil, 12.0, 0), String::from("12"));
+    assert_eq!(duration_prefix(Language::Nil), "took ");
+    assert_eq!(duration_prefix(Language::Russian), "");
+  }
+
+  #[test]
+  fn test_messages_durations() {
+    assert_eq!(format_duration(Language::Nil, Duration::from_millis(5140)), String::from("5.1s"));
+    assert_eq!(format_duration(Language::Russian, Duration::from_millis(5140)), String::from("5,1с"));
+    assert_eq!(format_duration(Language::Nil, Duration::from_millis(59900)), String::from("59.9s"));
+    assert_eq!(format_duration(Language::Nil, Duration::from_millis(59999)), String::from("59.9s"));
+    assert_eq!(format_duration(Language::Nil, Duration::from_secs(60)), String::from("1m 0s"));
+    assert_eq!(format_duration(Language::Nil, Duration::from_millis(127400)), String::from("2m 7s"));
+    assert_eq!(format_duration(Language::Ukrainian, Duration::from_secs(187)), String::from("3хв 7с"));
+    assert_eq!(format_duration(Language::Nil, Duration::from_secs(3599)), String::from("59m 59s"));
+    assert_eq!(format_duration(Language::Nil, Duration::from_secs(3600)), String::from("1h 0m"));
+    assert_eq!(format_duration(Language::Nil, Duration::from_secs(3780)), String::from("1h 3m"));
+    assert_eq!(format_duration(Language::Russian, Duration::from_secs(3780)), String::from("1ч 3мин"));
+  }
+
+  #[test]
+  fn test_messages_exit_status() {
+    assert_eq!(exit_status_meaning(Language::Russian, 1), String::from("ошибка"));
+    assert_eq!(exit_status_meaning(Language::Russian, 2), String::from("неверное использование"));
+    assert_eq!(exit_status_meaning(Language::Ukrainian, 127), String::from("команду не знайдено"));
+    assert_eq!(exit_status_meaning(Language::Bulgarian, 126), String::from("командата не може да бъде изпълнена"));
+    assert_eq!(exit_status_meaning(Language::Serbian, 130), String::from("прекинуто"));
+    assert_eq!(exit_status_meaning(Language::Belarusian, 137), String::from("завершана сігналам 9"));
+    assert_eq!(exit_status_meaning(Language::Nil, 141), String::from("terminated by signal 13"));
+    assert_eq!(exit_status_meaning(Language::Nil, 255), String::from("error"));
+  }
+}
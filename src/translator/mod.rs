@@ -23,11 +23,18 @@
 *
 */
 
+pub mod ansi;
+pub mod filter;
 pub mod ioprocessor;
 pub mod lang;
+pub mod messages;
+pub mod stream;
+pub mod trace;
 
 use lang::Language;
 
+use std::fmt;
+
 /// ### Translator
 ///
 /// Struct used to convert form cyrillic script to latin script and viceversa
@@ -35,7 +42,7 @@ pub trait Translator {
   /// ### to_latin
   ///
   /// Converts a string which contains russian cyrillic characters into a latin string.
-  /// Characters between '"' (quotes) are escaped, expressions inside escaped blocks are translitarated anyway
+  /// Quotes are not handled here: escaped blocks are handled by the IOProcessor when translating expressions
   fn to_latin(&self, input: &String) -> String;
 
   /// ### to_cyrillic
@@ -43,37 +50,368 @@ pub trait Translator {
   /// Converts a string which contains latin characters into a russian cyrillic string.
   /// Characters between quotes are escapes
   fn to_cyrillic(&self, input: &String) -> String;
+
+  /// ### unmapped
+  ///
+  /// Returns the characters of the input which have no mapping to latin (e.g. emoji or stray diacritics), each once,
+  /// in order of appearance. Latin (ASCII) characters are kept as they are, so they're never reported.
+  /// Translators which don't define their mappings report none
+  fn unmapped(&self, _input: &String) -> Vec<char> {
+    Vec::new()
+  }
+
+  /// ### to_latin_with_mode
+  ///
+  /// Converts to latin as `to_latin` does. In lossy mode characters with no mapping pass through;
+  /// in strict mode they are an error which lists them
+  fn to_latin_with_mode(&self, input: &String, mode: TranslationMode) -> Result<String, TranslationError> {
+    if mode == TranslationMode::Strict {
+      let unmapped: Vec<char> = self.unmapped(input);
+      if !unmapped.is_empty() {
+        return Err(TranslationError::UnmappedCharacters(unmapped));
+      }
+    }
+    Ok(self.to_latin(input))
+  }
+}
+
+/// ### TranslationMode
+///
+/// TranslationMode tells what to do with the characters which have no mapping to latin
+#[derive(Copy, Clone, Default, PartialEq, std::fmt::Debug)]
+pub enum TranslationMode {
+  #[default]
+  Lossy,  //Characters pass through as they are
+  Strict, //Characters are an error
+}
+
+/// ### TranslationError
+///
+/// TranslationError describes why an input couldn't be converted in strict mode
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub enum TranslationError {
+  UnmappedCharacters(Vec<char>), //Characters with no mapping, each once, in order of appearance
+}
+
+impl fmt::Display for TranslationError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      TranslationError::UnmappedCharacters(chars) => {
+        let chars: Vec<String> = chars.iter().map(|c| format!("'{}'", c)).collect();
+        write!(f, "no transliteration for {}", chars.join(", "))
+      }
+    }
+  }
+}
+
+/// ### LocalizedError
+///
+/// LocalizedError is implemented by the errors which can be rendered in cyrillic when the output is translated
+pub trait LocalizedError: fmt::Display {
+  /// ### localized
+  ///
+  /// Returns the message of the error converted to cyrillic. The values it reports between single quotes
+  /// (names, paths, tokens) are kept as they are
+  fn localized(&self, translator: &dyn Translator) -> String {
+    localize_message(translator, self.to_string().as_str())
+  }
+}
+
+/// ### localize_message
+///
+/// Converts a message to cyrillic, except for the text between single quotes (e.g. `Could not read 'file.txt'`).
+/// A quote opens a block only at the beginning of a word and closes it only at the end of a word,
+/// so apostrophes (e.g. `can't`) and quotes which are never closed are converted as the rest of the message
+pub fn localize_message(translator: &dyn Translator, message: &str) -> String {
+  let chars: Vec<char> = message.chars().collect();
+  let mut output: String = String::with_capacity(message.len());
+  let mut text: String = String::new();
+  let mut i: usize = 0;
+  while i < chars.len() {
+    let opens: bool = chars[i] == '\'' && (i == 0 || !chars[i - 1].is_alphanumeric());
+    let close: Option<usize> = match opens {
+      true => (i + 1..chars.len()).find(|j| chars[*j] == '\'' && chars.get(j + 1).map(|c| !c.is_alphanumeric()).unwrap_or(true)),
+      false => None,
+    };
+    match close {
+      Some(close) => {
+        output.push_str(translator.to_cyrillic(&text).as_str());
+        text.clear();
+        output.extend(chars[i..=close].iter());
+        i = close + 1;
+      }
+      None => {
+        text.push(chars[i]);
+        i += 1;
+      }
+    }
+  }
+  output.push_str(translator.to_cyrillic(&text).as_str());
+  output
+}
+
+/// ### TransliterationStandard
+///
+/// TransliterationStandard is the standard used to transliterate russian and ukrainian.
+/// The other languages always use their GOST 7.79 tables
+#[derive(Copy, Clone, Default, PartialEq, std::fmt::Debug)]
+pub enum TransliterationStandard {
+  #[default]
+  Gost779,    //Pyc's tables, based on GOST 7.79-2000 (e.g. ж => j, х => h)
+  BgnPcgn,    //BGN/PCGN romanization (e.g. ж => zh, х => kh)
+  Scientific, //Scientific transliteration (e.g. ж => ž, х => x)
+}
+
+impl TransliterationStandard {
+  /// ### from_name
+  ///
+  /// Get the standard from its name in the configuration or in the command line options
+  pub fn from_name(name: &str) -> Option<TransliterationStandard> {
+    match name.to_lowercase().as_str() {
+      "gost" | "gost779" | "gost-7.79" => Some(TransliterationStandard::Gost779),
+      "bgn" | "bgn-pcgn" | "bgn/pcgn" => Some(TransliterationStandard::BgnPcgn),
+      "scientific" => Some(TransliterationStandard::Scientific),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for TransliterationStandard {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let standard_str: &str = match self {
+      TransliterationStandard::Gost779 => "gost",
+      TransliterationStandard::BgnPcgn => "bgn-pcgn",
+      TransliterationStandard::Scientific => "scientific",
+    };
+    write!(f, "{}", standard_str)
+  }
+}
+
+/// ### TranslatorOptions
+///
+/// TranslatorOptions contains the options which change how the translators transliterate
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub struct TranslatorOptions {
+  pub strict_boundaries: bool, //Every non-alphabetic character separates words (otherwise only spaces do)
+  pub standard: TransliterationStandard,
+}
+
+impl Default for TranslatorOptions {
+  fn default() -> TranslatorOptions {
+    TranslatorOptions {
+      strict_boundaries: true,
+      standard: TransliterationStandard::default(),
+    }
+  }
 }
 
 /// ### new_translator
 ///
 /// instantiates a new Translator with the provided language,
-/// associating the correct conversion functions
+/// associating the correct conversion functions. The default options (and GOST 7.79) are used
 pub fn new_translator(language: Language) -> Box<dyn Translator> {
+  new_translator_with_options(language, TranslatorOptions::default())
+}
+
+/// ### new_translator_with_options
+///
+/// instantiates a new Translator with the provided language and options (e.g. the transliteration standard)
+pub fn new_translator_with_options(language: Language, options: TranslatorOptions) -> Box<dyn Translator> {
   match language {
     Language::Belarusian => Box::new(lang::Belarusian {}),
     Language::Bulgarian => Box::new(lang::Bulgarian {}),
-    Language::Russian => Box::new(lang::Russian {}),
+    Language::Kazakh => Box::new(lang::Kazakh {}),
+    Language::Russian => Box::new(lang::Russian {
+      strict_boundaries: options.strict_boundaries,
+      standard: options.standard,
+    }),
     Language::Serbian => Box::new(lang::Serbian {}),
-    Language::Ukrainian => Box::new(lang::Ukrainian {}),
+    Language::Ukrainian => Box::new(lang::Ukrainian {
+      strict_boundaries: options.strict_boundaries,
+      standard: options.standard,
+    }),
     Language::Nil => Box::new(lang::Nil {})
   }
 }
 
+/// ### language_from_locale
+///
+/// Get the language of a POSIX locale (e.g. `uk_UA.UTF-8` or `sr_RS@latin`), looking at its language code only.
+/// Returns None if the locale is malformed or its language is not supported
+pub fn language_from_locale(locale: &str) -> Option<Language> {
+  //Drop the codeset and the modifier, then the territory
+  let name: &str = locale.split(&['.', '@'][..]).next().unwrap_or("");
+  let mut parts = name.splitn(2, '_');
+  let code: &str = parts.next().unwrap_or("");
+  if let Some(territory) = parts.next() {
+    if territory.len() != 2 || !territory.chars().all(|c| c.is_ascii_uppercase()) {
+      return None;
+    }
+  }
+  match code {
+    "be" => Some(Language::Belarusian),
+    "bg" => Some(Language::Bulgarian),
+    "kk" => Some(Language::Kazakh),
+    "ru" => Some(Language::Russian),
+    "sr" => Some(Language::Serbian),
+    "uk" => Some(Language::Ukrainian),
+    _ => None,
+  }
+}
+
 #[cfg(test)]
 mod tests {
 
   use super::*;
 
+  #[test]
+  fn test_translator_language_from_locale() {
+    assert_eq!(language_from_locale("be_BY.UTF-8"), Some(Language::Belarusian));
+    assert_eq!(language_from_locale("bg_BG.UTF-8"), Some(Language::Bulgarian));
+    assert_eq!(language_from_locale("kk_KZ.UTF-8"), Some(Language::Kazakh));
+    assert_eq!(language_from_locale("ru_RU.UTF-8"), Some(Language::Russian));
+    assert_eq!(language_from_locale("sr_RS.UTF-8"), Some(Language::Serbian));
+    assert_eq!(language_from_locale("uk_UA.UTF-8"), Some(Language::Ukrainian));
+    //Territory, codeset and modifier are optional
+    assert_eq!(language_from_locale("ru_RU"), Some(Language::Russian));
+    assert_eq!(language_from_locale("uk"), Some(Language::Ukrainian));
+    assert_eq!(language_from_locale("sr_RS@latin"), Some(Language::Serbian));
+    assert_eq!(language_from_locale("be_BY.UTF-8@latin"), Some(Language::Belarusian));
+    assert_eq!(language_from_locale("ru_UA.KOI8-U"), Some(Language::Russian));
+    //Unsupported or malformed
+    assert_eq!(language_from_locale("C"), None);
+    assert_eq!(language_from_locale("C.UTF-8"), None);
+    assert_eq!(language_from_locale("POSIX"), None);
+    assert_eq!(language_from_locale("en_US.UTF-8"), None);
+    assert_eq!(language_from_locale(""), None);
+    assert_eq!(language_from_locale("ru_"), None);
+    assert_eq!(language_from_locale("ru_russia"), None);
+    assert_eq!(language_from_locale("RU_RU"), None);
+    assert_eq!(language_from_locale("_RU"), None);
+  }
+
+  #[test]
+  fn test_translator_localize_message() {
+    let translator: Box<dyn Translator> = new_translator(Language::Russian);
+    assert_eq!(localize_message(translator.as_ref(), "Could not read 'file.txt'"), String::from("Коулд нот реад 'file.txt'"));
+    assert_eq!(localize_message(translator.as_ref(), "'a' and 'b'"), String::from("'a' анд 'b'"));
+    //Apostrophes and unterminated quotes are converted
+    assert_eq!(localize_message(translator.as_ref(), "can't"), String::from("кан'т"));
+    assert_eq!(localize_message(translator.as_ref(), "at ' end"), String::from("ат ' енд"));
+    assert_eq!(localize_message(translator.as_ref(), "'it's' ok"), String::from("'it's' ок"));
+    assert_eq!(localize_message(translator.as_ref(), ""), String::new());
+  }
+
   #[test]
   fn test_translator_new() {
     //Just don't panic
     let _ = new_translator(Language::Belarusian);
     let _ = new_translator(Language::Bulgarian);
+    let _ = new_translator(Language::Kazakh);
     let _ = new_translator(Language::Russian);
     let _ = new_translator(Language::Serbian);
     let _ = new_translator(Language::Ukrainian);
     let _ = new_translator(Language::Nil);
   }
 
+  #[test]
+  fn test_translator_strict_boundaries() {
+    let options: TranslatorOptions = TranslatorOptions {
+      strict_boundaries: false,
+      ..TranslatorOptions::default()
+    };
+    //Punctuation separates words only with strict boundaries
+    assert_eq!(new_translator(Language::Russian).to_latin(&String::from("(как)")), String::from("(cak)"));
+    assert_eq!(new_translator_with_options(Language::Russian, options).to_latin(&String::from("(как)")), String::from("(cac)"));
+    assert_eq!(new_translator(Language::Ukrainian).to_latin(&String::from("рак,")), String::from("rak,"));
+    assert_eq!(new_translator_with_options(Language::Ukrainian, options).to_latin(&String::from("рак,")), String::from("rac,"));
+    assert!(TranslatorOptions::default().strict_boundaries);
+  }
+
+  #[test]
+  fn test_translator_standard() {
+    assert_eq!(TransliterationStandard::default(), TransliterationStandard::Gost779);
+    assert_eq!(TransliterationStandard::from_name("gost"), Some(TransliterationStandard::Gost779));
+    assert_eq!(TransliterationStandard::from_name("BGN"), Some(TransliterationStandard::BgnPcgn));
+    assert_eq!(TransliterationStandard::from_name("bgn-pcgn"), Some(TransliterationStandard::BgnPcgn));
+    assert_eq!(TransliterationStandard::from_name("scientific"), Some(TransliterationStandard::Scientific));
+    assert_eq!(TransliterationStandard::from_name("iso9"), None);
+    assert_eq!(TransliterationStandard::BgnPcgn.to_string(), String::from("bgn-pcgn"));
+    let with_standard = |language: Language, standard: TransliterationStandard| -> Box<dyn Translator> {
+      new_translator_with_options(language, TranslatorOptions { standard, ..TranslatorOptions::default() })
+    };
+    //Same cyrillic input, one latin output for each standard
+    let input: String = String::from("Хрущёв ищет жильё");
+    assert_eq!(new_translator(Language::Russian).to_latin(&input), String::from("Hrushhyov ishhet jil`yo"));
+    assert_eq!(with_standard(Language::Russian, TransliterationStandard::Gost779).to_latin(&input), String::from("Hrushhyov ishhet jil`yo"));
+    assert_eq!(with_standard(Language::Russian, TransliterationStandard::BgnPcgn).to_latin(&input), String::from("Khrushchëv ishchet zhil’ë"));
+    assert_eq!(with_standard(Language::Russian, TransliterationStandard::Scientific).to_latin(&input), String::from("Xruščëv iščet žilʹë"));
+    //And viceversa
+    assert_eq!(with_standard(Language::Russian, TransliterationStandard::BgnPcgn).to_cyrillic(&String::from("Khrushchëv ishchet zhil’ë")), input);
+    assert_eq!(with_standard(Language::Russian, TransliterationStandard::Scientific).to_cyrillic(&String::from("Xruščëv iščet žilʹë")), input);
+    let input: String = String::from("Щастя Їжак Ґанок");
+    assert_eq!(with_standard(Language::Ukrainian, TransliterationStandard::BgnPcgn).to_latin(&input), String::from("Shchastya Yizhak Ganok"));
+    assert_eq!(with_standard(Language::Ukrainian, TransliterationStandard::Scientific).to_latin(&input), String::from("Ščastja Jižak Ganok"));
+    assert_eq!(with_standard(Language::Ukrainian, TransliterationStandard::BgnPcgn).to_cyrillic(&String::from("Shchastya Yizhak Ganok")), input);
+    assert_eq!(with_standard(Language::Ukrainian, TransliterationStandard::Scientific).to_cyrillic(&String::from("Ščastja Jižak Ganok")), input);
+    //Other languages ignore the standard
+    assert_eq!(with_standard(Language::Bulgarian, TransliterationStandard::BgnPcgn).to_latin(&String::from("жаба")), new_translator(Language::Bulgarian).to_latin(&String::from("жаба")));
+  }
+
+  #[test]
+  fn test_translator_mode() {
+    assert_eq!(TranslationMode::default(), TranslationMode::Lossy);
+    let bgn: TranslatorOptions = TranslatorOptions { standard: TransliterationStandard::BgnPcgn, ..TranslatorOptions::default() };
+    //Same input: lossy passes the characters through, strict lists them
+    let input: String = String::from("кат фа́йл😀.тхт № ₽");
+    let russian: Box<dyn Translator> = new_translator(Language::Russian);
+    assert_eq!(russian.to_latin_with_mode(&input, TranslationMode::Lossy), Ok(String::from("cat fa\u{301}jl😀.tht # $")));
+    assert_eq!(russian.to_latin_with_mode(&input, TranslationMode::Strict), Err(TranslationError::UnmappedCharacters(vec!['\u{301}', '😀'])));
+    assert_eq!(russian.to_latin_with_mode(&String::from("кат файл.тхт"), TranslationMode::Strict), Ok(String::from("cat fajl.tht")));
+    //The standard in use decides which characters are mapped
+    assert_eq!(new_translator_with_options(Language::Russian, bgn).unmapped(&input), vec!['\u{301}', '😀', '№', '₽']);
+    //Ukrainian has no 'ы' and 'э'
+    let input: String = String::from("рыба їжак э");
+    let ukrainian: Box<dyn Translator> = new_translator(Language::Ukrainian);
+    assert!(ukrainian.to_latin_with_mode(&input, TranslationMode::Lossy).is_ok());
+    assert_eq!(ukrainian.to_latin_with_mode(&input, TranslationMode::Strict), Err(TranslationError::UnmappedCharacters(vec!['ы', 'э'])));
+    assert_eq!(new_translator_with_options(Language::Ukrainian, bgn).unmapped(&input), vec!['ы', 'э']);
+    assert!(ukrainian.unmapped(&String::from("Ґанок, пʼять №1")).is_empty());
+    //Translators without strict mode never fail
+    assert!(new_translator(Language::Serbian).to_latin_with_mode(&String::from("😀"), TranslationMode::Strict).is_ok());
+    assert_eq!(
+      TranslationError::UnmappedCharacters(vec!['é', '😀']).to_string(),
+      String::from("no transliteration for 'é', '😀'")
+    );
+  }
+
+  #[test]
+  fn test_translator_large_input() {
+    //Lookahead and lookbehind must not depend on the length of the input: a few megabytes are translated in a moment
+    let cyrillic_line: String = String::from("кат РЕАДМЭ.мд | греп -и \"ксеон\" > /тмп/оут.тхт\n");
+    let latin_line: String = String::from("cat README.md | grep -i \"xeon\" > /tmp/out.txt\n");
+    let languages: [Language; 6] = [
+      Language::Belarusian,
+      Language::Bulgarian,
+      Language::Kazakh,
+      Language::Russian,
+      Language::Serbian,
+      Language::Ukrainian,
+    ];
+    for language in languages.iter() {
+      let translator: Box<dyn Translator> = new_translator(*language);
+      let input: String = cyrillic_line.repeat(50000);
+      assert!(input.len() > 2 * 1024 * 1024);
+      let t_start: std::time::Instant = std::time::Instant::now();
+      let output: String = translator.to_latin(&input);
+      assert!(t_start.elapsed() < std::time::Duration::from_secs(10), "{:?}: to_latin took {:?}", language, t_start.elapsed());
+      assert_eq!(output, translator.to_latin(&cyrillic_line).repeat(50000));
+      let input: String = latin_line.repeat(50000);
+      let t_start: std::time::Instant = std::time::Instant::now();
+      let output: String = translator.to_cyrillic(&input);
+      assert!(t_start.elapsed() < std::time::Duration::from_secs(10), "{:?}: to_cyrillic took {:?}", language, t_start.elapsed());
+      assert_eq!(output, translator.to_cyrillic(&latin_line).repeat(50000));
+    }
+  }
+
 }
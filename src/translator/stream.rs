@@ -0,0 +1,284 @@
+//! ## Stream
+//!
+//! `stream` provides the StreamTranslator, which transliterates arbitrarily large texts (e.g. files) in constant memory
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate tempfile;
+
+use std::fs::{self, File, Permissions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+use super::Translator;
+use crate::utils::file;
+
+const CHUNK_SIZE: usize = 4096; //Max size of the text translated at once (translators are quadratic on the input length)
+const BINARY_SAMPLE_SIZE: usize = 8192; //Bytes sniffed to tell whether the input is binary
+const BINARY_THRESHOLD: usize = 5; //Percentage of invalid UTF-8 bytes in the sample above which the input is binary
+
+/// ### Direction
+///
+/// Direction describes which alphabet the text is transliterated to
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum Direction {
+  ToLatin,
+  ToCyrillic,
+}
+
+/// ### ConvertResult
+///
+/// ConvertResult describes what has been done with a file
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum ConvertResult {
+  Converted,
+  SkippedBinary,
+}
+
+/// ### StreamTranslator
+///
+/// StreamTranslator reads a text line by line and writes its transliteration.
+/// Lines longer than `CHUNK_SIZE` are split at the last whitespace, so that words are never split
+pub struct StreamTranslator {
+  translator: Box<dyn Translator>,
+  direction: Direction,
+}
+
+impl StreamTranslator {
+  /// ### new
+  ///
+  /// Instantiates a new StreamTranslator
+  pub fn new(translator: Box<dyn Translator>, direction: Direction) -> StreamTranslator {
+    StreamTranslator {
+      translator,
+      direction,
+    }
+  }
+
+  /// ### translate
+  ///
+  /// Transliterate everything read from reader into writer.
+  /// Invalid UTF-8 sequences are replaced with U+FFFD
+  pub fn translate(&self, reader: &mut dyn BufRead, writer: &mut dyn Write) -> io::Result<()> {
+    let mut chunk: Vec<u8> = Vec::with_capacity(CHUNK_SIZE);
+    loop {
+      let limit: u64 = CHUNK_SIZE.saturating_sub(chunk.len()).max(1) as u64;
+      let read: usize = (&mut *reader).take(limit).read_until(b'\n', &mut chunk)?;
+      if chunk.is_empty() {
+        return writer.flush();
+      }
+      //Translate up to the end of the line, the end of the file, or the last whitespace of a long line
+      let end: usize = match read == 0 || chunk.ends_with(b"\n") {
+        true => chunk.len(),
+        false if chunk.len() < CHUNK_SIZE => continue,
+        false => split_point(chunk.as_slice()),
+      };
+      let text: String = String::from_utf8_lossy(&chunk[..end]).to_string();
+      let output: String = match self.direction {
+        Direction::ToLatin => self.translator.to_latin(&text),
+        Direction::ToCyrillic => self.translator.to_cyrillic(&text),
+      };
+      writer.write_all(output.as_bytes())?;
+      chunk.drain(..end);
+    }
+  }
+
+  /// ### convert_file
+  ///
+  /// Transliterate a file, writing the result to `out` or, if `out` is None, replacing the file.
+  /// The file is replaced atomically (the result is written to a temporary file, which is synced to disk and then renamed) and keeps its permissions.
+  /// Binary files are skipped
+  pub fn convert_file(&self, path: &Path, out: Option<&mut dyn Write>) -> io::Result<ConvertResult> {
+    let mut reader: BufReader<File> = BufReader::with_capacity(BINARY_SAMPLE_SIZE, File::open(path)?);
+    if is_binary(reader.fill_buf()?) {
+      return Ok(ConvertResult::SkippedBinary);
+    }
+    match out {
+      Some(out) => self.translate(&mut reader, out)?,
+      None => {
+        let permissions: Permissions = fs::metadata(path)?.permissions();
+        let dir: &Path = match path.parent() {
+          Some(dir) if dir != Path::new("") => dir,
+          _ => Path::new("."),
+        };
+        let tmp: NamedTempFile = NamedTempFile::new_in(dir)?;
+        {
+          let mut writer: BufWriter<&File> = BufWriter::new(tmp.as_file());
+          self.translate(&mut reader, &mut writer)?;
+        }
+        fs::set_permissions(tmp.path(), permissions)?;
+        file::persist(tmp, path)?;
+      }
+    }
+    Ok(ConvertResult::Converted)
+  }
+}
+
+/// ### is_binary
+///
+/// Returns whether the sample looks like binary data: more than `BINARY_THRESHOLD` percent of its first bytes are not valid UTF-8
+/// or are NUL (which never appears in text). A sequence truncated at the end of the sample is not counted
+pub fn is_binary(sample: &[u8]) -> bool {
+  let sample: &[u8] = &sample[..sample.len().min(BINARY_SAMPLE_SIZE)];
+  let mut invalid: usize = sample.iter().filter(|b| **b == 0).count();
+  let mut rest: &[u8] = sample;
+  while let Err(err) = std::str::from_utf8(rest) {
+    match err.error_len() {
+      Some(len) => {
+        invalid += len;
+        rest = &rest[err.valid_up_to() + len..];
+      }
+      None => break,
+    }
+  }
+  invalid * 100 > sample.len() * BINARY_THRESHOLD
+}
+
+/// ### split_point
+///
+/// Returns where a chunk which doesn't end with a newline can be split: after the last whitespace,
+/// or, if there is none, at the last character boundary
+fn split_point(chunk: &[u8]) -> usize {
+  if let Some(pos) = chunk.iter().rposition(|b| *b == b' ' || *b == b'\t') {
+    return pos + 1;
+  }
+  //Don't split a multi-byte character: find where the last one starts (continuation bytes are 10xxxxxx)
+  let mut start: usize = chunk.len();
+  while start > 0 && (chunk[start - 1] & 0xC0) == 0x80 {
+    start -= 1;
+  }
+  if start == 0 || chunk[start - 1] < 0xC0 {
+    return chunk.len();
+  }
+  let len: usize = match chunk[start - 1] {
+    b if b >= 0xF0 => 4,
+    b if b >= 0xE0 => 3,
+    _ => 2,
+  };
+  match chunk.len() - (start - 1) < len {
+    true => start - 1,
+    false => chunk.len(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use crate::translator::lang::Language;
+  use crate::translator::new_translator;
+
+  use std::io::Cursor;
+  use std::os::unix::fs::PermissionsExt;
+
+  #[test]
+  fn test_stream_translate() {
+    let stream: StreamTranslator = StreamTranslator::new(new_translator(Language::Russian), Direction::ToLatin);
+    let mut out: Vec<u8> = Vec::new();
+    assert!(stream.translate(&mut Cursor::new("привет мир\nкак дела\n\nпока"), &mut out).is_ok());
+    assert_eq!(String::from_utf8(out).unwrap(), String::from("privet mir\ncak dela\n\npoca"));
+    let stream: StreamTranslator = StreamTranslator::new(new_translator(Language::Russian), Direction::ToCyrillic);
+    let mut out: Vec<u8> = Vec::new();
+    assert!(stream.translate(&mut Cursor::new("privet mir\n"), &mut out).is_ok());
+    assert_eq!(String::from_utf8(out).unwrap(), String::from("привет мир\n"));
+  }
+
+  #[test]
+  fn test_stream_translate_long_lines() {
+    let stream: StreamTranslator = StreamTranslator::new(new_translator(Language::Russian), Direction::ToLatin);
+    //A line longer than a chunk is split between words
+    let line: String = "как ".repeat(CHUNK_SIZE / 3);
+    let mut out: Vec<u8> = Vec::new();
+    assert!(stream.translate(&mut Cursor::new(line.clone()), &mut out).is_ok());
+    assert_eq!(String::from_utf8(out).unwrap(), "cak ".repeat(CHUNK_SIZE / 3));
+    //A word longer than a chunk is split between characters
+    let word: String = "д".repeat(CHUNK_SIZE);
+    let mut out: Vec<u8> = Vec::new();
+    assert!(stream.translate(&mut Cursor::new(word), &mut out).is_ok());
+    assert_eq!(String::from_utf8(out).unwrap(), "d".repeat(CHUNK_SIZE));
+  }
+
+  #[test]
+  fn test_stream_split_point() {
+    assert_eq!(split_point(b"ab cd"), 3);
+    assert_eq!(split_point("ab\u{0434}".as_bytes()), 4);
+    assert_eq!(split_point(&"ab\u{0434}".as_bytes()[..3]), 2);
+    assert_eq!(split_point(b"abcd"), 4);
+  }
+
+  #[test]
+  fn test_stream_is_binary() {
+    assert!(!is_binary("привет\n".as_bytes()));
+    assert!(!is_binary(b""));
+    //Truncated character at the end of the sample
+    assert!(!is_binary(&"привет".as_bytes()[..5]));
+    //A few invalid bytes are tolerated
+    let mut text: Vec<u8> = "a".repeat(100).into_bytes();
+    text.push(0xFF);
+    assert!(!is_binary(text.as_slice()));
+    assert!(is_binary(&[0x7F, 0x45, 0x4C, 0x46, 0x02, 0x01, 0x01, 0x00, 0xFF, 0xFE, 0x90, 0x80]));
+    //NUL bytes
+    assert!(is_binary(&[0x7F, 0x45, 0x4C, 0x46, 0x02, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]));
+  }
+
+  #[test]
+  fn test_stream_convert_file_stdout() {
+    let stream: StreamTranslator = StreamTranslator::new(new_translator(Language::Russian), Direction::ToLatin);
+    let file: tempfile::NamedTempFile = write_sample_file("привет\nмир\n".as_bytes());
+    let mut out: Vec<u8> = Vec::new();
+    assert_eq!(stream.convert_file(file.path(), Some(&mut out)).unwrap(), ConvertResult::Converted);
+    assert_eq!(String::from_utf8(out).unwrap(), String::from("privet\nmir\n"));
+    //File is untouched
+    assert_eq!(fs::read_to_string(file.path()).unwrap(), String::from("привет\nмир\n"));
+    //No such file
+    assert!(stream.convert_file(Path::new("/tmp/pyc_no_such_file.txt"), Some(&mut Vec::new())).is_err());
+  }
+
+  #[test]
+  fn test_stream_convert_file_in_place() {
+    let stream: StreamTranslator = StreamTranslator::new(new_translator(Language::Russian), Direction::ToCyrillic);
+    let file: tempfile::NamedTempFile = write_sample_file(b"privet\nmir\n");
+    fs::set_permissions(file.path(), Permissions::from_mode(0o751)).unwrap();
+    assert_eq!(stream.convert_file(file.path(), None).unwrap(), ConvertResult::Converted);
+    assert_eq!(fs::read_to_string(file.path()).unwrap(), String::from("привет\nмир\n"));
+    assert_eq!(fs::metadata(file.path()).unwrap().permissions().mode() & 0o777, 0o751);
+  }
+
+  #[test]
+  fn test_stream_convert_file_binary() {
+    let stream: StreamTranslator = StreamTranslator::new(new_translator(Language::Russian), Direction::ToLatin);
+    let data: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+    let file: tempfile::NamedTempFile = write_sample_file(data.as_slice());
+    let mut out: Vec<u8> = Vec::new();
+    assert_eq!(stream.convert_file(file.path(), Some(&mut out)).unwrap(), ConvertResult::SkippedBinary);
+    assert!(out.is_empty());
+    assert_eq!(stream.convert_file(file.path(), None).unwrap(), ConvertResult::SkippedBinary);
+    assert_eq!(fs::read(file.path()).unwrap(), data);
+  }
+
+  fn write_sample_file(data: &[u8]) -> tempfile::NamedTempFile {
+    let mut tmpfile: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+    tmpfile.write_all(data).unwrap();
+    tmpfile
+  }
+}
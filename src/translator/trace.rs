@@ -0,0 +1,245 @@
+//! ## Trace
+//!
+//! `trace` provides the data structures which describe how an input has been translated before being dispatched to the shell
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use std::path::PathBuf;
+
+/// ### TokenDecision
+///
+/// TokenDecision describes what has been done with a token of the input
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum TokenDecision {
+  Translated,
+  Unchanged,
+  Escaped,
+  PreservedPath, //Redirection target or output argument, kept as typed
+  PreservedUrl,  //URL typed in latin, kept as typed
+  Override,      //Literal block, never translated
+  AliasExpanded,
+}
+
+/// ### DispatchMode
+///
+/// DispatchMode describes where the translated input has been sent to
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum DispatchMode {
+  Interactive,
+  Subprocess,
+  Oneshot,
+}
+
+/// ### TokenTrace
+///
+/// TokenTrace describes the translation of a single token of the input
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct TokenTrace {
+  pub token: String,
+  pub output: String,
+  pub decision: TokenDecision,
+}
+
+/// ### TranslationTrace
+///
+/// TranslationTrace records every step performed on an input, from the raw input to the argv sent to the shell
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct TranslationTrace {
+  pub input: String,
+  pub tokens: Vec<TokenTrace>,
+  pub argv: Vec<String>,
+  pub dispatch_mode: DispatchMode,
+}
+
+/// ### TraceOutput
+///
+/// TraceOutput describes where translation traces have to be written to
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub enum TraceOutput {
+  Stderr,
+  File(PathBuf),
+}
+
+impl TokenTrace {
+  /// ### new
+  ///
+  /// Instantiates a new TokenTrace
+  pub fn new(token: String, output: String, decision: TokenDecision) -> TokenTrace {
+    TokenTrace {
+      token,
+      output,
+      decision,
+    }
+  }
+
+  /// ### translated
+  ///
+  /// Instantiates a new TokenTrace for a token which went through the translator.
+  /// Decision is `Unchanged` if the translator didn't change the token
+  pub fn translated(token: String, output: String) -> TokenTrace {
+    let decision: TokenDecision = match token == output {
+      true => TokenDecision::Unchanged,
+      false => TokenDecision::Translated,
+    };
+    TokenTrace::new(token, output, decision)
+  }
+}
+
+impl TranslationTrace {
+  /// ### new
+  ///
+  /// Instantiates a new TranslationTrace for the provided input
+  pub fn new(input: String, dispatch_mode: DispatchMode) -> TranslationTrace {
+    TranslationTrace {
+      input,
+      tokens: Vec::new(),
+      argv: Vec::new(),
+      dispatch_mode,
+    }
+  }
+
+  /// ### set_output
+  ///
+  /// Set the final command which is going to be dispatched to the shell
+  pub fn set_output(&mut self, output: &str) {
+    self.argv = output.split_whitespace().map(String::from).collect();
+  }
+
+  /// ### to_json
+  ///
+  /// Serialize trace to a JSON object on a single line
+  pub fn to_json(&self) -> String {
+    let tokens: Vec<String> = self
+      .tokens
+      .iter()
+      .map(|t| {
+        format!(
+          "{{\"token\":{},\"output\":{},\"decision\":{}}}",
+          json_string(&t.token),
+          json_string(&t.output),
+          json_string(decision_to_str(t.decision))
+        )
+      })
+      .collect();
+    let argv: Vec<String> = self.argv.iter().map(|a| json_string(a)).collect();
+    format!(
+      "{{\"input\":{},\"tokens\":[{}],\"argv\":[{}],\"dispatch_mode\":{}}}",
+      json_string(&self.input),
+      tokens.join(","),
+      argv.join(","),
+      json_string(dispatch_mode_to_str(self.dispatch_mode))
+    )
+  }
+}
+
+/// ### decision_to_str
+///
+/// Converts a TokenDecision to its name in the trace
+fn decision_to_str(decision: TokenDecision) -> &'static str {
+  match decision {
+    TokenDecision::Translated => "translated",
+    TokenDecision::Unchanged => "unchanged",
+    TokenDecision::Escaped => "escaped",
+    TokenDecision::PreservedPath => "preserved_path",
+    TokenDecision::PreservedUrl => "preserved_url",
+    TokenDecision::Override => "override",
+    TokenDecision::AliasExpanded => "alias_expanded",
+  }
+}
+
+/// ### dispatch_mode_to_str
+///
+/// Converts a DispatchMode to its name in the trace
+fn dispatch_mode_to_str(mode: DispatchMode) -> &'static str {
+  match mode {
+    DispatchMode::Interactive => "interactive",
+    DispatchMode::Subprocess => "subprocess",
+    DispatchMode::Oneshot => "oneshot",
+  }
+}
+
+/// ### json_string
+///
+/// Quote and escape a string as a JSON string
+pub(crate) fn json_string(s: &str) -> String {
+  let mut out: String = String::with_capacity(s.len() + 2);
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => out.push_str(format!("\\u{:04x}", c as u32).as_str()),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_trace_token_translated() {
+    let token: TokenTrace = TokenTrace::translated(String::from("лс"), String::from("ls"));
+    assert_eq!(token.decision, TokenDecision::Translated);
+    let token: TokenTrace = TokenTrace::translated(String::from("ls"), String::from("ls"));
+    assert_eq!(token.decision, TokenDecision::Unchanged);
+  }
+
+  #[test]
+  fn test_trace_set_output() {
+    let mut trace: TranslationTrace = TranslationTrace::new(String::from("лс -л"), DispatchMode::Interactive);
+    trace.set_output("ls  -l\n");
+    assert_eq!(trace.argv, vec![String::from("ls"), String::from("-l")]);
+  }
+
+  #[test]
+  fn test_trace_to_json() {
+    let mut trace: TranslationTrace = TranslationTrace::new(String::from("экхо \"привет\"\n"), DispatchMode::Subprocess);
+    trace.tokens.push(TokenTrace::translated(String::from("экхо "), String::from("echo ")));
+    trace.tokens.push(TokenTrace::new(String::from("\"привет\""), String::from("\"привет\""), TokenDecision::Escaped));
+    trace.set_output("echo \"привет\"");
+    assert_eq!(
+      trace.to_json(),
+      String::from("{\"input\":\"экхо \\\"привет\\\"\\n\",\"tokens\":[{\"token\":\"экхо \",\"output\":\"echo \",\"decision\":\"translated\"},{\"token\":\"\\\"привет\\\"\",\"output\":\"\\\"привет\\\"\",\"decision\":\"escaped\"}],\"argv\":[\"echo\",\"\\\"привет\\\"\"],\"dispatch_mode\":\"subprocess\"}")
+    );
+  }
+
+  #[test]
+  fn test_trace_decision_to_str() {
+    assert_eq!(decision_to_str(TokenDecision::PreservedPath), "preserved_path");
+    assert_eq!(decision_to_str(TokenDecision::PreservedUrl), "preserved_url");
+    assert_eq!(decision_to_str(TokenDecision::Override), "override");
+    assert_eq!(decision_to_str(TokenDecision::AliasExpanded), "alias_expanded");
+  }
+
+  #[test]
+  fn test_trace_json_string() {
+    assert_eq!(json_string("a\tb\\c\x1b"), String::from("\"a\\tb\\\\c\\u001b\""));
+  }
+}
@@ -23,6 +23,13 @@
 *
 */
 
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+/// Bytes which are not valid UTF-8 (e.g. in KOI8-R file names) are stored in buffers as the characters
+/// of this private use range, so that they can be written back as they were
+const RAW_BYTE_BASE: u32 = 0x10FE00;
+
 /// ### chars_to_string
 /// 
 /// Converts a characters vector to string
@@ -30,6 +37,69 @@ pub fn chars_to_string(buff: &Vec<char>) -> String {
     buff.iter().collect()
 }
 
+/// ### os_str_to_string
+///
+/// Converts an OsStr to string; the bytes which are not valid UTF-8 are kept as raw byte characters
+pub fn os_str_to_string(s: &OsStr) -> String {
+    let mut out: String = String::with_capacity(s.len());
+    let mut bytes: &[u8] = s.as_bytes();
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(valid) => {
+                out.push_str(valid);
+                return out;
+            }
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                out.push_str(std::str::from_utf8(valid).unwrap());
+                let invalid_len: usize = err.error_len().unwrap_or(rest.len());
+                //NOTE: invalid bytes are never ASCII
+                out.extend(rest[..invalid_len].iter().filter_map(|b| std::char::from_u32(RAW_BYTE_BASE + *b as u32)));
+                bytes = &rest[invalid_len..];
+            }
+        }
+    }
+}
+
+/// ### string_to_bytes
+///
+/// Converts a string to bytes, restoring the raw byte characters to the original bytes
+pub fn string_to_bytes(s: &str) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::with_capacity(s.len());
+    let mut utf8: [u8; 4] = [0; 4];
+    for ch in s.chars() {
+        match raw_byte(ch) {
+            Some(byte) => out.push(byte),
+            None => out.extend_from_slice(ch.encode_utf8(&mut utf8).as_bytes()),
+        }
+    }
+    out
+}
+
+/// ### string_to_os_string
+///
+/// Converts a string to OsString, restoring the raw byte characters to the original bytes
+pub fn string_to_os_string(s: &str) -> OsString {
+    OsString::from_vec(string_to_bytes(s))
+}
+
+/// ### printable
+///
+/// Returns the string as it has to be shown to the user: raw byte characters are replaced with U+FFFD
+pub fn printable(s: &str) -> String {
+    s.chars().map(|ch| if raw_byte(ch).is_some() { '\u{FFFD}' } else { ch }).collect()
+}
+
+/// ### raw_byte
+///
+/// If the character is a raw byte character, returns its byte
+fn raw_byte(ch: char) -> Option<u8> {
+    match ch as u32 {
+        c if (RAW_BYTE_BASE + 0x80..=RAW_BYTE_BASE + 0xFF).contains(&c) => Some((c - RAW_BYTE_BASE) as u8),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -38,4 +108,25 @@ mod tests {
     fn test_utils_buffer_chars_to_string() {
         assert_eq!(chars_to_string(&vec!['a', 'b', 'c', 'л']), String::from("abcл"));
     }
+
+    #[test]
+    fn test_utils_buffer_raw_bytes() {
+        //"Привет" in KOI8-R
+        let name: &OsStr = OsStr::from_bytes(b"\xf0\xd2\xc9\xd7\xc5\xd4.txt");
+        let s: String = os_str_to_string(name);
+        assert_eq!(s.chars().count(), 10);
+        assert!(s.ends_with(".txt"));
+        assert_eq!(printable(s.as_str()), String::from("\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}.txt"));
+        assert_eq!(string_to_bytes(s.as_str()), name.as_bytes().to_vec());
+        assert_eq!(string_to_os_string(s.as_str()).as_os_str(), name);
+        //Valid UTF-8 is kept as it is
+        let s: String = os_str_to_string(OsStr::new("файл\u{FFFD}.txt"));
+        assert_eq!(s, String::from("файл\u{FFFD}.txt"));
+        assert_eq!(printable(s.as_str()), s);
+        assert_eq!(string_to_bytes(s.as_str()), s.as_bytes().to_vec());
+        //Mixed
+        let s: String = os_str_to_string(OsStr::from_bytes(b"a\xff\xd0\xb1\xc3"));
+        assert_eq!(printable(s.as_str()), String::from("a\u{FFFD}б\u{FFFD}"));
+        assert_eq!(string_to_bytes(s.as_str()), b"a\xff\xd0\xb1\xc3".to_vec());
+    }
 }
@@ -25,15 +25,31 @@
 extern crate nix;
 extern crate termios;
 
+#[cfg(test)]
+use std::cell::Cell;
 use std::io::{self, Read, Write};
 use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use super::recorder::Recorder;
 
 const STDIN_FILENO: RawFd = 0;
+const STDOUT_FILENO: RawFd = 1;
+const BRACKETED_PASTE_END: &[u8] = b"\x1b[201~";
+
+lazy_static! {
+    static ref RECORDER: Mutex<Option<Recorder>> = Mutex::new(None); //Everything written to the terminal is recorded here, if set
+    static ref ERROR_STYLE: Mutex<Option<String>> = Mutex::new(None); //Escape sequence used to print errors, if set
+    static ref ERROR_PREFIX: Mutex<String> = Mutex::new(String::new()); //Text prepended to each line of the errors
+}
+static INPUT_ECHO: AtomicBool = AtomicBool::new(true);
+static RAW_MODE: AtomicBool = AtomicBool::new(false); //Terminal is kept in raw mode, termios mustn't be changed on each read
 
 /// ## InputEvent
 /// 
 /// InputEvent enum represents an Input Event got from user on a read call
-#[derive(std::fmt::Debug, std::cmp::PartialEq)]
+#[derive(Clone, std::fmt::Debug, std::cmp::PartialEq)]
 pub enum InputEvent {
     Key(String),
     Ctrl(u8),
@@ -43,7 +59,12 @@ pub enum InputEvent {
     ArrowUp,
     ArrowLeft,
     ArrowRight,
-    ArrowDown
+    ArrowDown,
+    Home,
+    End,
+    Delete,
+    Escape,
+    Paste(String)
 }
 
 
@@ -55,14 +76,57 @@ pub fn backspace() {
     print(String::from("\x08 \x08"));
 }
 
+#[cfg(test)]
 pub fn move_cursor_right() {
     print(String::from("\x1b[1C"));
 }
 
+#[cfg(test)]
 pub fn move_cursor_left() {
     print(String::from("\x1b[1D"));
 }
 
+/// ### move_cursor
+/// 
+/// Move the cursor on the current line from column `from` to column `to`
+pub fn move_cursor(from: usize, to: usize) {
+    if to > from {
+        print(format!("\x1b[{}C", to - from));
+    } else if from > to {
+        print(format!("\x1b[{}D", from - to));
+    }
+}
+
+/// ### redraw
+/// 
+/// Redraw the input line. `prev_column` is the column of the cursor, relative to the beginning of the line, before the redraw;
+/// the rest of the terminal line is cleared and then the cursor is placed at `column`
+pub fn redraw(row: String, prev_column: usize, column: usize) {
+    let width: usize = str_width(row.as_str());
+    move_cursor(prev_column, 0);
+    print(format!("{}\x1b[K", row));
+    move_cursor(width, column);
+}
+
+/// ### str_width
+/// 
+/// Returns the amount of columns used by a string on the terminal.
+/// This is not the amount of bytes: each cyrillic character takes 2 bytes, but only one column
+pub fn str_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// ### char_width
+/// 
+/// Returns the amount of columns used by a character on the terminal
+pub fn char_width(ch: char) -> usize {
+    match ch as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0, //Combining and zero width characters
+        0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 | 0x1F300..=0x1F64F | 0x1F900..=0x1F9FF | 0x20000..=0x3FFFD => 2, //Wide characters
+        _ => 1
+    }
+}
+
 /// ### carriage_return
 /// 
 /// Return to the beginning of the line
@@ -70,11 +134,32 @@ pub fn carriage_return() {
     print(String::from("\r"));
 }
 
+/// ### clear_lines
+/// 
+/// Move the cursor to the beginning of the line `lines` rows above and clear everything from there
+pub fn clear_lines(lines: usize) {
+    match lines {
+        0 => print(String::from("\r\x1b[J")),
+        _ => print(format!("\r\x1b[{}A\x1b[J", lines)),
+    }
+}
+
 /// ### clear
 /// 
-/// Clear console
-pub fn clear() {
-    print(String::from("\x1b[H\x1b[2J"));
+/// Clear console; the scrollback is erased too if `scrollback` is true
+pub fn clear(scrollback: bool) {
+    print(String::from(clear_sequence(scrollback)));
+}
+
+/// ### clear_sequence
+/// 
+/// Returns the escape sequence which clears the console: the cursor goes home, then the screen is erased (ED2)
+/// and, if `scrollback` is true, the scrollback too (ED3)
+pub fn clear_sequence(scrollback: bool) -> &'static str {
+    match scrollback {
+        true => "\x1b[H\x1b[2J\x1b[3J",
+        false => "\x1b[H\x1b[2J",
+    }
 }
 
 /// ### read
@@ -84,6 +169,9 @@ pub fn read() -> Option<InputEvent> {
     let stdin_read = |buff: &mut [u8]| -> io::Result<()> {
         io::stdin().read_exact(buff)
     };
+    if raw_mode() {
+        return to_input_event(&input_ready, &stdin_read);
+    }
     prepare_termios();
     let ev: Option<InputEvent> = to_input_event(&input_ready, &stdin_read);
     reset_termios();
@@ -108,16 +196,44 @@ fn to_input_event(ready_fn: &dyn Fn() -> bool, read_fn: &dyn Fn(&mut [u8]) -> io
                 10 => InputEvent::Enter,
                 13 => InputEvent::CarriageReturn,
                 0..=26 => InputEvent::Ctrl(key), //CTRL key (exclude 8, 10, 13)
-                27 => { //Is Arrow Key
+                27 => { //Is Escape sequence (ESC [ x or ESC O x)
+                    if !ready_fn() { //Nothing follows ESC: the escape key has been pressed alone
+                        return Some(InputEvent::Escape)
+                    }
                     //Read twice
                     let _ = read_fn(&mut buf);
                     let _ = read_fn(&mut buf);
-                    let direction: char = *buf.get(0).unwrap_or(&0) as char;
-                    match direction {
+                    let code: char = *buf.first().unwrap_or(&0) as char;
+                    match code {
                         'A' => InputEvent::ArrowUp,
                         'B' => InputEvent::ArrowDown,
                         'C' => InputEvent::ArrowRight,
                         'D' => InputEvent::ArrowLeft,
+                        'H' => InputEvent::Home,
+                        'F' => InputEvent::End,
+                        '0'..='9' => { //VT sequence (e.g. ESC [ 3 ~); read until '~'
+                            let mut number: String = String::from(code);
+                            loop {
+                                if read_fn(&mut buf).is_err() {
+                                    return None
+                                }
+                                let ch: char = *buf.first().unwrap_or(&0) as char;
+                                if ch == '~' {
+                                    break
+                                }
+                                if !ch.is_ascii_digit() || number.len() >= 3 {
+                                    return None //Unknown event
+                                }
+                                number.push(ch);
+                            }
+                            match number.as_str() {
+                                "200" => InputEvent::Paste(read_paste(read_fn)),
+                                "1" | "7" => InputEvent::Home,
+                                "4" | "8" => InputEvent::End,
+                                "3" => InputEvent::Delete,
+                                _ => return None //Unknown event
+                            }
+                        },
                         _ => return None //Unknown event
                     }
                 },
@@ -159,9 +275,65 @@ fn to_input_event(ready_fn: &dyn Fn() -> bool, read_fn: &dyn Fn(&mut [u8]) -> io
     }
 }
 
+/// ### read_paste
+/// 
+/// Read the text pasted in bracketed paste mode, until the paste end sequence (ESC [ 2 0 1 ~)
+fn read_paste(read_fn: &dyn Fn(&mut [u8]) -> io::Result<()>) -> String {
+    let mut pasted: Vec<u8> = Vec::new();
+    let mut buf: Vec<u8> = vec![0u8; 1];
+    while read_fn(&mut buf).is_ok() {
+        pasted.push(buf[0]);
+        if pasted.ends_with(BRACKETED_PASTE_END) {
+            pasted.truncate(pasted.len() - BRACKETED_PASTE_END.len());
+            break
+        }
+    }
+    String::from_utf8_lossy(&pasted).to_string()
+}
+
+/// ### set_bracketed_paste
+/// 
+/// Enable or disable bracketed paste mode: when enabled, the terminal wraps pasted text between
+/// ESC [ 2 0 0 ~ and ESC [ 2 0 1 ~, so it is read as a single `InputEvent::Paste`
+pub fn set_bracketed_paste(enabled: bool) {
+    match enabled {
+        true => print(String::from("\x1b[?2004h")),
+        false => print(String::from("\x1b[?2004l"))
+    }
+}
+
+/// ### decode_input
+/// 
+/// Convert a sequence of bytes, as it would be read from stdin, into input events
+#[cfg(test)]
+pub fn decode_input(bytes: &[u8]) -> Vec<InputEvent> {
+    let position: Cell<usize> = Cell::new(0);
+    let ready_fn = || -> bool {
+        position.get() < bytes.len()
+    };
+    let read_fn = |buff: &mut [u8]| -> io::Result<()> {
+        match bytes.get(position.get()) {
+            Some(byte) => {
+                buff[0] = *byte;
+                position.set(position.get() + 1);
+                Ok(())
+            },
+            None => Err(io::Error::from(io::ErrorKind::UnexpectedEof))
+        }
+    };
+    let mut events: Vec<InputEvent> = Vec::new();
+    while ready_fn() {
+        if let Some(ev) = to_input_event(&ready_fn, &read_fn) {
+            events.push(ev);
+        }
+    }
+    events
+}
+
 /// ### rewrite
 /// 
 /// Rewrite current stdout line
+#[cfg(test)]
 pub fn rewrite(row: String, len: usize) {
     for _ in 0..len {
         backspace();
@@ -173,22 +345,129 @@ pub fn rewrite(row: String, len: usize) {
 /// 
 /// print on this line without newline
 pub fn print(row: String) {
-    print!("{}", row);
-    let _ = io::stdout().flush();
+    let _ = write(&mut io::stdout(), row.as_str());
 }
 
 /// ### println
 /// 
 /// Print line and go to new line
 pub fn println(row: String) {
-    println!("{}", row);
+    let _ = write(&mut io::stdout(), format!("{}\n", row).as_str());
+}
+
+/// ### write
+/// 
+/// Write data to the terminal (stdout or stderr) and flush it.
+/// Everything printed to the terminal must go through this function, so that it is recorded too
+pub fn write(writer: &mut dyn Write, data: &str) -> io::Result<()> {
+    writer.write_all(data.as_bytes())?;
+    writer.flush()?;
+    if let Ok(mut recorder) = RECORDER.lock() {
+        if let Some(recorder) = recorder.as_mut() {
+            let _ = recorder.record(data);
+        }
+    }
+    Ok(())
+}
+
+/// ### echo
+/// 
+/// Print the input typed by the user; nothing is printed (nor recorded) while input echo is disabled
+pub fn echo(row: String) {
+    if input_echo() {
+        print(row);
+    }
+}
+
+/// ### set_input_echo
+/// 
+/// Enable or disable the echo of the input typed by the user (e.g. while a password is being typed)
+pub fn set_input_echo(enabled: bool) {
+    INPUT_ECHO.store(enabled, Ordering::Relaxed);
+}
+
+/// ### input_echo
+/// 
+/// Returns whether the input typed by the user is echoed
+pub fn input_echo() -> bool {
+    INPUT_ECHO.load(Ordering::Relaxed)
+}
+
+/// ### set_raw_mode
+/// 
+/// Tell whether the terminal is kept in raw mode by the caller; while it is, reading input doesn't change the termios
+pub fn set_raw_mode(enabled: bool) {
+    RAW_MODE.store(enabled, Ordering::Relaxed);
+}
+
+/// ### raw_mode
+/// 
+/// Returns whether the terminal is kept in raw mode
+pub fn raw_mode() -> bool {
+    RAW_MODE.load(Ordering::Relaxed)
+}
+
+/// ### set_error_style
+/// 
+/// Set the escape sequence used to print pyc's errors (the `error` color of the palette)
+pub fn set_error_style(style: String) {
+    *ERROR_STYLE.lock().unwrap() = Some(style);
+}
+
+/// ### error_style
+/// 
+/// Returns the escape sequence used to print pyc's errors, if set
+pub fn error_style() -> Option<String> {
+    ERROR_STYLE.lock().unwrap().clone()
+}
+
+/// ### set_error_prefix
+/// 
+/// Set the text prepended to each line of the errors (`output.error_prefix`)
+pub fn set_error_prefix(prefix: String) {
+    *ERROR_PREFIX.lock().unwrap() = prefix;
+}
+
+/// ### error_prefix
+/// 
+/// Returns the text prepended to each line of the errors
+pub fn error_prefix() -> String {
+    ERROR_PREFIX.lock().unwrap().clone()
+}
+
+/// ### start_recording
+/// 
+/// Start recording everything written to the terminal with the provided recorder
+pub fn start_recording(recorder: Recorder) {
+    if let Ok(mut current) = RECORDER.lock() {
+        *current = Some(recorder);
+    }
+}
+
+/// ### stop_recording
+/// 
+/// Stop recording the terminal
+pub fn stop_recording() {
+    if let Ok(mut current) = RECORDER.lock() {
+        *current = None;
+    }
+}
+
+/// ### terminal_size
+/// 
+/// Returns the size of the terminal as (columns, rows), if stdout is a terminal
+pub fn terminal_size() -> Option<(usize, usize)> {
+    super::terminal::window_size(STDOUT_FILENO)
 }
 
 /// ### input_ready
 /// 
 /// Returns whether stdin is ready to be read
 fn input_ready() -> bool {
-    prepare_termios();
+    let raw: bool = raw_mode();
+    if !raw {
+        prepare_termios();
+    }
     let mut poll_fds: [nix::poll::PollFd; 1] = [nix::poll::PollFd::new(STDIN_FILENO, nix::poll::PollFlags::POLLIN | nix::poll::PollFlags::POLLRDBAND | nix::poll::PollFlags::POLLHUP)];
     let ready: bool = match nix::poll::poll(&mut poll_fds, 100) {
         Ok(ret) => {
@@ -205,15 +484,20 @@ fn input_ready() -> bool {
         },
         Err(_) => false
     };
-    reset_termios();
+    if !raw {
+        reset_termios();
+    }
     ready
 }
 
 /// ### prepare_termios
 /// 
-/// Prepare termios for console
+/// Prepare termios for console; nothing to do if stdin is not a terminal
 fn prepare_termios() {
-    let mut term = termios::Termios::from_fd(STDIN_FILENO).unwrap();
+    let mut term = match termios::Termios::from_fd(STDIN_FILENO) {
+        Ok(term) => term,
+        Err(_) => return,
+    };
     let _ = termios::tcgetattr(STDIN_FILENO, &mut term);
     term.c_lflag &= !termios::ICANON;
     term.c_lflag &= !termios::ECHO;
@@ -224,7 +508,10 @@ fn prepare_termios() {
 /// 
 /// Restore previous termios configuration
 fn reset_termios() {
-    let mut term = termios::Termios::from_fd(STDIN_FILENO).unwrap();
+    let mut term = match termios::Termios::from_fd(STDIN_FILENO) {
+        Ok(term) => term,
+        Err(_) => return,
+    };
     let _ = termios::tcgetattr(STDIN_FILENO, &mut term);
     term.c_lflag |= termios::ICANON;
     term.c_lflag &= termios::ECHO;
@@ -241,6 +528,10 @@ pub fn input_event_to_string(ev: InputEvent) -> String {
         InputEvent::ArrowRight => String::from("\x1b[C"),
         InputEvent::ArrowUp => String::from("\x1b[A"),
         InputEvent::Backspace => String::from("\x7F"),
+        InputEvent::Home => String::from("\x1b[H"),
+        InputEvent::End => String::from("\x1b[F"),
+        InputEvent::Delete => String::from("\x1b[3~"),
+        InputEvent::Escape => String::from("\x1b"),
         InputEvent::CarriageReturn => String::from("\x0D"),
         InputEvent::Ctrl(sig) => {
             let ch = sig as char;
@@ -249,7 +540,8 @@ pub fn input_event_to_string(ev: InputEvent) -> String {
             s
         },
         InputEvent::Enter => String::from("\x0A"),
-        InputEvent::Key(k) => String::from(k)
+        InputEvent::Key(k) => k,
+        InputEvent::Paste(text) => text
     }
 }
 
@@ -265,6 +557,10 @@ mod tests {
 
     #[test]
     fn test_utils_console_move_cursor() {
+        move_cursor(0, 4);
+        move_cursor(4, 1);
+        move_cursor(1, 1);
+        redraw(String::from("лс -л"), 3, 2);
         move_cursor_left();
         move_cursor_right();
         carriage_return();
@@ -272,7 +568,11 @@ mod tests {
 
     #[test]
     fn test_utils_console_clear() {
-        clear();
+        clear(false);
+        clear(true);
+        //ED2 erases the screen, ED3 the scrollback
+        assert_eq!(clear_sequence(false), "\x1b[H\x1b[2J");
+        assert_eq!(clear_sequence(true), "\x1b[H\x1b[2J\x1b[3J");
     }
 
     #[test]
@@ -282,6 +582,113 @@ mod tests {
         println(String::from("bar"));
     }
 
+    #[test]
+    fn test_utils_console_decode_input() {
+        assert_eq!(
+            decode_input("лс\x1b[A\x1b[B\x1b[C\x1b[D\x7f\x03\n".as_bytes()),
+            vec![
+                InputEvent::Key(String::from("л")),
+                InputEvent::Key(String::from("с")),
+                InputEvent::ArrowUp,
+                InputEvent::ArrowDown,
+                InputEvent::ArrowRight,
+                InputEvent::ArrowLeft,
+                InputEvent::Backspace,
+                InputEvent::Ctrl(3),
+                InputEvent::Enter
+            ]
+        );
+        //Truncated escape sequence
+        assert_eq!(decode_input(b"\x1b["), vec![]);
+        //Home, End and Delete
+        assert_eq!(
+            decode_input(b"\x1b[H\x1b[F\x1bOH\x1bOF\x1b[1~\x1b[4~\x1b[7~\x1b[8~\x1b[3~"),
+            vec![
+                InputEvent::Home,
+                InputEvent::End,
+                InputEvent::Home,
+                InputEvent::End,
+                InputEvent::Home,
+                InputEvent::End,
+                InputEvent::Home,
+                InputEvent::End,
+                InputEvent::Delete
+            ]
+        );
+        //Unknown VT sequences are discarded
+        assert_eq!(decode_input(b"\x1b[15~a\x1b[3"), vec![InputEvent::Key(String::from("a"))]);
+        //Bracketed paste is a single event, whatever it contains
+        assert_eq!(
+            decode_input("\x1b[200~лс -л\n\x03\x1b[Aпривет\r\n\x1b[201~\n".as_bytes()),
+            vec![InputEvent::Paste(String::from("лс -л\n\x03\x1b[Aпривет\r\n")), InputEvent::Enter]
+        );
+        //Paste without end sequence
+        assert_eq!(decode_input(b"\x1b[200~ls"), vec![InputEvent::Paste(String::from("ls"))]);
+        //Carriage return, tab, backspace and ctrl
+        assert_eq!(
+            decode_input(b"\r\t\x08\x12"),
+            vec![InputEvent::CarriageReturn, InputEvent::Ctrl(9), InputEvent::Backspace, InputEvent::Ctrl(18)]
+        );
+        //SS3 arrows
+        assert_eq!(
+            decode_input(b"\x1bOA\x1bOB\x1bOC\x1bOD"),
+            vec![InputEvent::ArrowUp, InputEvent::ArrowDown, InputEvent::ArrowRight, InputEvent::ArrowLeft]
+        );
+        //Unknown CSI sequences are discarded, a lone ESC is the escape key
+        assert_eq!(decode_input(b"\x1b[Zx"), vec![InputEvent::Key(String::from("x"))]);
+        assert_eq!(decode_input(b"a\x1b"), vec![InputEvent::Key(String::from("a")), InputEvent::Escape]);
+    }
+
+    #[test]
+    fn test_utils_console_recording() {
+        use super::super::recorder::Cast;
+        let tmpfile: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        start_recording(Recorder::create(tmpfile.path(), 80, 24).unwrap());
+        //Scripted session
+        print(String::from("user@host:~$ "));
+        echo(String::from("лс"));
+        println(String::new());
+        let _ = write(&mut io::stderr(), "ошибка\n");
+        print(String::from("Password: "));
+        set_input_echo(false);
+        echo(String::from("s3cr3t"));
+        assert!(!input_echo());
+        set_input_echo(true);
+        println(String::new());
+        echo(String::from("пвд"));
+        stop_recording();
+        print(String::from("not recorded"));
+        //Check cast
+        let cast: Cast = Cast::read(tmpfile.path()).unwrap();
+        assert_eq!((cast.width, cast.height), (80, 24));
+        let events: Vec<&str> = cast.events.iter().map(|e| e.data.as_str()).collect();
+        //Other tests may print while recording, so look only for the events of this session
+        let position = |data: &str| events.iter().position(|e| *e == data);
+        assert!(position("user@host:~$ ").unwrap() < position("лс").unwrap());
+        assert!(position("лс").unwrap() < position("ошибка\n").unwrap());
+        assert!(position("ошибка\n").unwrap() < position("Password: ").unwrap());
+        assert!(position("Password: ").unwrap() < position("пвд").unwrap());
+        //Input typed while echo is off is not recorded
+        assert!(!events.iter().any(|e| e.contains("s3cr3t")));
+        assert!(position("not recorded").is_none());
+    }
+
+    #[test]
+    fn test_utils_console_str_width() {
+        assert_eq!(str_width("ls -l"), 5);
+        assert_eq!(str_width("лс -л"), 5);
+        assert_eq!(str_width("日本"), 4);
+        assert_eq!(str_width("е\u{0301}"), 1);
+        assert_eq!(str_width(""), 0);
+    }
+
+    #[test]
+    fn test_utils_console_input_event_to_string() {
+        for ev in [InputEvent::Home, InputEvent::End, InputEvent::Delete, InputEvent::ArrowUp] {
+            assert_eq!(decode_input(input_event_to_string(ev.clone()).as_bytes()), vec![ev]);
+        }
+    }
+
     #[test]
     fn test_utils_console_input_ready() {
         assert_eq!(input_ready(), false);
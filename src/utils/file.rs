@@ -23,10 +23,52 @@
 *
 */
 
-use std::fs::File;
-use std::fs::OpenOptions;
+extern crate nix;
+extern crate tempfile;
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufRead, Write};
-use std::path::Path;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// ### LoadedLines
+///
+/// LoadedLines is the outcome of loading a state file: either its lines or, if the file was corrupt,
+/// the path it has been moved to and the reason why it's considered corrupt
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub enum LoadedLines {
+    Valid(Vec<String>),
+    Quarantined(PathBuf, &'static str),
+}
+
+/// ### FileLock
+///
+/// FileLock is an advisory lock on a state file, held until it is dropped.
+/// The lock is taken on a `.lock` file next to the state file, since the state file itself is replaced on each write
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    /// ### acquire
+    ///
+    /// Wait until the lock on the provided file can be acquired
+    pub fn acquire<P>(filename: P) -> io::Result<FileLock> where P: AsRef<Path> {
+        let file: File = OpenOptions::new().create(true).write(true).truncate(false).open(with_suffix(filename.as_ref(), ".lock"))?;
+        match nix::fcntl::flock(file.as_raw_fd(), nix::fcntl::FlockArg::LockExclusive) {
+            Ok(_) => Ok(FileLock { file }),
+            Err(err) => Err(io::Error::other(err.to_string())),
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = nix::fcntl::flock(self.file.as_raw_fd(), nix::fcntl::FlockArg::Unlock);
+    }
+}
 
 /// ### read_lines
 /// 
@@ -43,21 +85,105 @@ pub fn read_lines<P>(filename: P) -> io::Result<Vec<String>> where P: AsRef<Path
     Ok(lines)
 }
 
+/// ### load_lines
+///
+/// Read lines from a state file, checking that it has been written completely: a file which contains NUL bytes,
+/// isn't valid UTF-8 or whose last line is truncated is moved to `<file>.corrupt` instead of being loaded
+pub fn load_lines<P>(filename: P) -> io::Result<LoadedLines> where P: AsRef<Path> {
+    let filename: &Path = filename.as_ref();
+    let data: Vec<u8> = fs::read(filename)?;
+    let corruption: Option<&'static str> = if data.contains(&0) {
+        Some("contains NUL bytes")
+    } else if std::str::from_utf8(data.as_slice()).is_err() {
+        Some("is not valid UTF-8")
+    } else if !data.is_empty() && !data.ends_with(b"\n") {
+        Some("is truncated")
+    } else {
+        None
+    };
+    match corruption {
+        None => Ok(LoadedLines::Valid(String::from_utf8_lossy(data.as_slice()).lines().map(String::from).collect())),
+        Some(reason) => {
+            let quarantine: PathBuf = with_suffix(filename, ".corrupt");
+            fs::rename(filename, quarantine.as_path())?;
+            Ok(LoadedLines::Quarantined(quarantine, reason))
+        }
+    }
+}
+
 /// ### write_lines
 /// 
-/// Write lines to file
+/// Write lines to file; the file is replaced atomically while holding its lock
+#[cfg(test)]
 pub fn write_lines<P>(filename: P, lines: Vec<String>) -> io::Result<()> where P: AsRef<Path> {
-    match OpenOptions::new().create(true).write(true).append(false).truncate(true).open(filename) {
-        Ok(mut f) => {
-            for line in lines.iter() {
-                writeln!(f, "{}", line)?;
-            }
-            Ok(())
-        },
-        Err(err) => Err(err)
+    update_lines(filename, |_| lines)
+}
+
+/// ### update_lines
+///
+/// Read-modify-write cycle on a state file: while holding the lock of the file, its lines are passed to `update`
+/// and the lines it returns replace the file atomically. A file which doesn't exist or is corrupt has no lines
+pub fn update_lines<P, F>(filename: P, update: F) -> io::Result<()> where P: AsRef<Path>, F: FnOnce(Vec<String>) -> Vec<String> {
+    let filename: &Path = filename.as_ref();
+    let _lock: FileLock = FileLock::acquire(filename)?;
+    let current: Vec<String> = match load_lines(filename) {
+        Ok(LoadedLines::Valid(lines)) => lines,
+        Ok(LoadedLines::Quarantined(_, _)) => Vec::new(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err),
+    };
+    let mut data: String = String::new();
+    for line in update(current).iter() {
+        data.push_str(line.as_str());
+        data.push('\n');
+    }
+    safe_write(filename, data.as_bytes())
+}
+
+/// ### safe_write
+///
+/// Replace a file in a crash-safe way: data is written to a temporary file in the same directory,
+/// which is synced to disk and then renamed over the file. The permissions of the file are kept
+pub fn safe_write<P>(filename: P, data: &[u8]) -> io::Result<()> where P: AsRef<Path> {
+    let filename: &Path = filename.as_ref();
+    let mut tmp: NamedTempFile = NamedTempFile::new_in(parent_dir(filename))?;
+    tmp.write_all(data)?;
+    if let Ok(metadata) = fs::metadata(filename) {
+        fs::set_permissions(tmp.path(), metadata.permissions())?;
+    }
+    persist(tmp, filename)
+}
+
+/// ### persist
+///
+/// Sync a temporary file to disk and rename it to `filename`, then sync the directory, so that the rename survives a crash.
+/// The temporary file must be in the same directory of `filename`
+pub fn persist<P>(tmp: NamedTempFile, filename: P) -> io::Result<()> where P: AsRef<Path> {
+    let filename: &Path = filename.as_ref();
+    tmp.as_file().sync_all()?;
+    tmp.persist(filename).map_err(|err| err.error)?;
+    File::open(parent_dir(filename))?.sync_all()
+}
+
+/// ### parent_dir
+///
+/// Returns the directory which contains the file
+fn parent_dir(filename: &Path) -> &Path {
+    match filename.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
     }
 }
 
+/// ### with_suffix
+///
+/// Returns the path of the file with the suffix appended to its name
+fn with_suffix(filename: &Path, suffix: &str) -> PathBuf {
+    let mut name: OsString = filename.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,6 +224,82 @@ mod tests {
         assert!(write_lines(Path::new("/sample.file1231234.txt"), in_lines).is_err());
     }
 
+    #[test]
+    fn test_utils_file_safe_write() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let path: PathBuf = tmpdir.path().join("state");
+        assert!(safe_write(path.as_path(), b"first\n").is_ok());
+        fs::set_permissions(path.as_path(), fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(safe_write(path.as_path(), b"second\n").is_ok());
+        assert_eq!(fs::read_to_string(path.as_path()).unwrap(), String::from("second\n"));
+        //Permissions are kept and no temporary file is left behind
+        assert_eq!(fs::metadata(path.as_path()).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_dir(tmpdir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_utils_file_update_lines_concurrent() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let path: PathBuf = tmpdir.path().join("state");
+        //Concurrent writers append their lines: no update must get lost
+        let writers: Vec<std::thread::JoinHandle<()>> = (0..4)
+            .map(|writer| {
+                let path: PathBuf = path.clone();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        update_lines(path.as_path(), |mut lines| {
+                            lines.push(format!("{}-{}", writer, i));
+                            lines
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let lines: Vec<String> = read_lines(path.as_path()).unwrap();
+        assert_eq!(lines.len(), 100);
+        for writer in 0..4 {
+            let written: Vec<&String> = lines.iter().filter(|l| l.starts_with(format!("{}-", writer).as_str())).collect();
+            assert_eq!(written, (0..25).map(|i| format!("{}-{}", writer, i)).collect::<Vec<String>>().iter().collect::<Vec<&String>>());
+        }
+    }
+
+    #[test]
+    fn test_utils_file_load_lines() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let path: PathBuf = tmpdir.path().join("state");
+        //Missing file
+        assert_eq!(load_lines(path.as_path()).err().unwrap().kind(), io::ErrorKind::NotFound);
+        //Valid file
+        fs::write(path.as_path(), "row 1\nrow 2\n").unwrap();
+        assert_eq!(load_lines(path.as_path()).unwrap(), LoadedLines::Valid(vec![String::from("row 1"), String::from("row 2")]));
+        fs::write(path.as_path(), "").unwrap();
+        assert_eq!(load_lines(path.as_path()).unwrap(), LoadedLines::Valid(vec![]));
+        //Truncated file is quarantined, with its content
+        fs::write(path.as_path(), "row 1\nro").unwrap();
+        let quarantine: PathBuf = tmpdir.path().join("state.corrupt");
+        assert_eq!(load_lines(path.as_path()).unwrap(), LoadedLines::Quarantined(quarantine.clone(), "is truncated"));
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(quarantine.as_path()).unwrap(), String::from("row 1\nro"));
+        //NUL bytes (e.g. blocks which were allocated but never written)
+        fs::write(path.as_path(), b"row 1\n\0\0\0\n").unwrap();
+        assert_eq!(load_lines(path.as_path()).unwrap(), LoadedLines::Quarantined(quarantine.clone(), "contains NUL bytes"));
+        fs::write(path.as_path(), b"row \xff\n").unwrap();
+        assert_eq!(load_lines(path.as_path()).unwrap(), LoadedLines::Quarantined(quarantine.clone(), "is not valid UTF-8"));
+        //A corrupt file is replaced by the next update
+        fs::write(path.as_path(), "row 1\nro").unwrap();
+        assert!(update_lines(path.as_path(), |lines| {
+            assert!(lines.is_empty());
+            vec![String::from("row 3")]
+        })
+        .is_ok());
+        assert_eq!(read_lines(path.as_path()).unwrap(), vec![String::from("row 3")]);
+    }
+
     /// ### write_sample_file
     /// Write a sample file
     fn write_sample_file() -> tempfile::NamedTempFile {
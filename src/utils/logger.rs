@@ -0,0 +1,254 @@
+//! ## Logger
+//!
+//! `logger` writes the log entries of Pyc, with a timestamp, to the log file or to stderr, when enabled with `core.log_file` or `--debug`
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use crate::shell::prompt::LocalTime;
+use crate::translator::lang::Language;
+
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref LOGGER: Mutex<Option<Logger>> = Mutex::new(None); //Entries are written here, if set
+}
+
+/// ### LogLevel
+///
+/// LogLevel describes how important a log entry is; a logger writes the entries up to its level
+#[derive(Copy, Clone, PartialEq, PartialOrd, fmt::Debug)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    /// ### from_name
+    ///
+    /// Get the log level from its name in the configuration
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        match name {
+            "error" => Some(LogLevel::Error),
+            "warn" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level_str: &str = match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        };
+        write!(f, "{}", level_str)
+    }
+}
+
+/// ### Logger
+///
+/// Logger writes the entries up to its level to its output
+pub struct Logger {
+    level: LogLevel,
+    output: Box<dyn Write + Send>,
+}
+
+impl Logger {
+    /// ### new
+    ///
+    /// Instantiates a new Logger which writes to `output`
+    pub fn new(level: LogLevel, output: Box<dyn Write + Send>) -> Logger {
+        Logger {
+            level,
+            output,
+        }
+    }
+
+    /// ### open
+    ///
+    /// Instantiates a new Logger which appends to the provided file, or writes to stderr if None
+    pub fn open(level: LogLevel, log_file: Option<&Path>) -> io::Result<Logger> {
+        let output: Box<dyn Write + Send> = match log_file {
+            Some(log_file) => Box::new(OpenOptions::new().create(true).append(true).open(log_file)?),
+            None => Box::new(io::stderr()),
+        };
+        Ok(Logger::new(level, output))
+    }
+
+    /// ### enabled
+    ///
+    /// Returns whether entries of the provided level are written
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        level <= self.level
+    }
+
+    /// ### write
+    ///
+    /// Write an entry, if its level is enabled; `module` is the part of Pyc the entry comes from
+    pub fn write(&mut self, level: LogLevel, module: &str, message: &str) {
+        if self.enabled(level) {
+            let timestamp: String = LocalTime::now().format("%F %T", Language::Nil);
+            let _ = writeln!(self.output, "{} {:<5} [{}] {}", timestamp, level.to_string().to_uppercase(), module, message);
+        }
+    }
+}
+
+/// ### init
+///
+/// Start writing log entries to the provided logger
+pub fn init(logger: Logger) {
+    if let Ok(mut current) = LOGGER.lock() {
+        *current = Some(logger);
+    }
+}
+
+/// ### enabled
+///
+/// Returns whether entries of the provided level are written; used to avoid formatting entries which are discarded
+pub fn enabled(level: LogLevel) -> bool {
+    match LOGGER.lock() {
+        Ok(logger) => logger.as_ref().map(|logger| logger.enabled(level)).unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+/// ### log
+///
+/// Write an entry; does nothing if logging hasn't been started
+pub fn log(level: LogLevel, module: &str, message: &str) {
+    if let Ok(mut logger) = LOGGER.lock() {
+        if let Some(logger) = logger.as_mut() {
+            logger.write(level, module, message);
+        }
+    }
+}
+
+/// ### error
+///
+/// Write an error entry
+pub fn error(module: &str, message: &str) {
+    log(LogLevel::Error, module, message);
+}
+
+/// ### warn
+///
+/// Write a warning entry
+pub fn warn(module: &str, message: &str) {
+    log(LogLevel::Warn, module, message);
+}
+
+/// ### info
+///
+/// Write an info entry
+pub fn info(module: &str, message: &str) {
+    log(LogLevel::Info, module, message);
+}
+
+/// ### debug
+///
+/// Write a debug entry; command lines and their translation are logged only at this level
+pub fn debug(module: &str, message: &str) {
+    log(LogLevel::Debug, module, message);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::sync::Arc;
+
+    /// Output shared with the test, to read what the logger has written
+    #[derive(Clone)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedOutput {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap().lines().map(String::from).collect()
+        }
+    }
+
+    #[test]
+    fn test_logger_levels() {
+        assert_eq!(LogLevel::from_name("debug"), Some(LogLevel::Debug));
+        assert_eq!(LogLevel::from_name("warn"), Some(LogLevel::Warn));
+        assert!(LogLevel::from_name("verbose").is_none());
+        assert_eq!(LogLevel::Info.to_string(), String::from("info"));
+        assert!(LogLevel::Error < LogLevel::Debug);
+    }
+
+    #[test]
+    fn test_logger_filter() {
+        for (level, expected) in [(LogLevel::Error, 1), (LogLevel::Warn, 2), (LogLevel::Info, 3), (LogLevel::Debug, 4)].iter() {
+            let output: SharedOutput = SharedOutput(Arc::new(Mutex::new(Vec::new())));
+            let mut logger: Logger = Logger::new(*level, Box::new(output.clone()));
+            logger.write(LogLevel::Debug, "runtime", "dispatched 'ls -l'");
+            logger.write(LogLevel::Info, "shell", "started sh");
+            logger.write(LogLevel::Warn, "shell", "handshake failed");
+            logger.write(LogLevel::Error, "runtime", "could not start shell");
+            let lines: Vec<String> = output.lines();
+            assert_eq!(lines.len(), *expected);
+            //Entries have a timestamp, their level and their module
+            assert!(lines.last().unwrap().ends_with(" ERROR [runtime] could not start shell"));
+            assert_eq!(lines.last().unwrap().split(' ').next().unwrap().len(), 10);
+        }
+    }
+
+    #[test]
+    fn test_logger_file() {
+        let tmpdir: tempfile::TempDir = tempfile::TempDir::new().unwrap();
+        let log_file = tmpdir.path().join("pyc.log");
+        let mut logger: Logger = Logger::open(LogLevel::Info, Some(log_file.as_path())).unwrap();
+        logger.write(LogLevel::Info, "shell", "started sh");
+        logger.write(LogLevel::Debug, "shell", "discarded");
+        //File is appended to
+        let mut logger: Logger = Logger::open(LogLevel::Info, Some(log_file.as_path())).unwrap();
+        logger.write(LogLevel::Warn, "shell", "handshake failed");
+        let content: String = std::fs::read_to_string(log_file.as_path()).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.contains("INFO  [shell] started sh"));
+        assert!(content.contains("WARN  [shell] handshake failed"));
+        //Directory which doesn't exist
+        assert!(Logger::open(LogLevel::Info, Some(tmpdir.path().join("nope/pyc.log").as_path())).is_err());
+    }
+}
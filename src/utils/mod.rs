@@ -26,3 +26,7 @@
 pub mod buffer;
 pub mod console;
 pub mod file;
+pub mod logger;
+pub mod profile;
+pub mod recorder;
+pub mod terminal;
@@ -0,0 +1,138 @@
+//! ## Profile
+//!
+//! `profile` measures the time spent in each phase of the startup, when enabled with `--profile-startup`
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    static ref PROFILE: Mutex<Option<StartupProfile>> = Mutex::new(None); //Startup phases are recorded here, if set
+}
+
+/// ### StartupProfile
+///
+/// StartupProfile contains the duration of each startup phase, measured from the end of the previous one
+pub struct StartupProfile {
+    started: Instant,
+    last: Instant,
+    phases: Vec<(String, Duration)>,
+}
+
+impl StartupProfile {
+    /// ### new
+    ///
+    /// Instantiates a new StartupProfile; `started` is the instant the program has been started at
+    pub fn new(started: Instant) -> StartupProfile {
+        StartupProfile {
+            started,
+            last: started,
+            phases: Vec::new(),
+        }
+    }
+
+    /// ### mark
+    ///
+    /// Record the end of `phase` at `now`
+    pub fn mark(&mut self, phase: &str, now: Instant) {
+        self.phases.push((String::from(phase), now.duration_since(self.last)));
+        self.last = now;
+    }
+
+    /// ### report
+    ///
+    /// Format the duration of each phase and the total time, one per line
+    pub fn report(&self) -> String {
+        let mut report: String = String::new();
+        for (phase, duration) in self.phases.iter() {
+            report.push_str(format_phase(phase.as_str(), *duration).as_str());
+        }
+        report.push_str(format_phase("total", self.last.duration_since(self.started)).as_str());
+        report
+    }
+}
+
+/// ### start
+///
+/// Start profiling the startup; `started` is the instant the program has been started at
+pub fn start(started: Instant) {
+    if let Ok(mut profile) = PROFILE.lock() {
+        *profile = Some(StartupProfile::new(started));
+    }
+}
+
+/// ### mark
+///
+/// Record the end of a startup phase; does nothing if profiling hasn't been started
+pub fn mark(phase: &str) {
+    if let Ok(mut profile) = PROFILE.lock() {
+        if let Some(profile) = profile.as_mut() {
+            profile.mark(phase, Instant::now());
+        }
+    }
+}
+
+/// ### finish
+///
+/// Record the last startup phase, print the report to stderr and stop profiling
+pub fn finish(phase: &str) {
+    if let Ok(mut profile) = PROFILE.lock() {
+        if let Some(mut profile) = profile.take() {
+            profile.mark(phase, Instant::now());
+            eprint!("{}", profile.report());
+        }
+    }
+}
+
+/// ### format_phase
+///
+/// Format the duration of a phase in milliseconds
+fn format_phase(phase: &str, duration: Duration) -> String {
+    format!("startup: {:<14}{:>9.3}ms\n", phase, duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_profile_report() {
+        let started: Instant = Instant::now();
+        let mut profile: StartupProfile = StartupProfile::new(started);
+        profile.mark("config", started + Duration::from_micros(1500));
+        profile.mark("shell", started + Duration::from_millis(4));
+        assert_eq!(
+            profile.report(),
+            String::from("startup: config            1.500ms\nstartup: shell             2.500ms\nstartup: total             4.000ms\n")
+        );
+    }
+
+    #[test]
+    fn test_profile_disabled() {
+        //Profiling hasn't been started: nothing is recorded
+        mark("config");
+        finish("dispatch");
+        assert!(PROFILE.lock().unwrap().is_none());
+    }
+}
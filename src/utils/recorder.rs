@@ -0,0 +1,338 @@
+//! ## Recorder
+//!
+//! `recorder` provides the functions to record what is printed to the terminal into an asciicast (v2) file and to replay it
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate regex;
+
+use regex::Regex;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::translator::trace::json_string;
+
+const CAST_VERSION: u8 = 2;
+
+/// ### Recorder
+///
+/// Recorder writes each chunk of data printed to the terminal as an asciicast output event,
+/// with the time elapsed since the beginning of the recording
+pub struct Recorder {
+    writer: Box<dyn Write + Send>,
+    started: Instant,
+}
+
+/// ### CastEvent
+///
+/// CastEvent is an output event read from a cast file
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct CastEvent {
+    pub time: f64,
+    pub data: String,
+}
+
+/// ### Cast
+///
+/// Cast describes a recorded session
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct Cast {
+    pub width: usize,
+    pub height: usize,
+    pub events: Vec<CastEvent>,
+}
+
+impl Recorder {
+    /// ### new
+    ///
+    /// Instantiates a new Recorder which writes to `writer`; the cast header is written immediately
+    pub fn new(mut writer: Box<dyn Write + Send>, width: usize, height: usize) -> io::Result<Recorder> {
+        let timestamp: u64 = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(t) => t.as_secs(),
+            Err(_) => 0,
+        };
+        let env: Vec<String> = ["SHELL", "TERM"]
+            .iter()
+            .filter_map(|key| env::var(key).ok().map(|value| format!("{}: {}", json_string(key), json_string(value.as_str()))))
+            .collect();
+        writeln!(
+            writer,
+            "{{\"version\": {}, \"width\": {}, \"height\": {}, \"timestamp\": {}, \"env\": {{{}}}}}",
+            CAST_VERSION,
+            width,
+            height,
+            timestamp,
+            env.join(", ")
+        )?;
+        writer.flush()?;
+        Ok(Recorder {
+            writer,
+            started: Instant::now(),
+        })
+    }
+
+    /// ### create
+    ///
+    /// Create a new cast file at `path` and instantiates a Recorder which writes to it
+    pub fn create(path: &Path, width: usize, height: usize) -> io::Result<Recorder> {
+        let file: File = OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+        Recorder::new(Box::new(file), width, height)
+    }
+
+    /// ### record
+    ///
+    /// Write an output event with the provided data
+    pub fn record(&mut self, data: &str) -> io::Result<()> {
+        let elapsed: f64 = self.started.elapsed().as_secs_f64();
+        writeln!(self.writer, "[{:.6}, \"o\", {}]", elapsed, json_string(data))?;
+        self.writer.flush()
+    }
+}
+
+impl Cast {
+    /// ### read
+    ///
+    /// Read a cast file. Events which are not output events are ignored
+    pub fn read(path: &Path) -> io::Result<Cast> {
+        let reader: BufReader<File> = BufReader::new(File::open(path)?);
+        let mut lines = reader.lines();
+        let header: String = match lines.next() {
+            Some(line) => line?,
+            None => return Err(invalid_data("empty cast file")),
+        };
+        let mut cast: Cast = Cast::parse_header(header.as_str())?;
+        let event_regex: Regex = Regex::new(r#"^\[\s*([0-9]+(?:\.[0-9]+)?(?:[eE][-+]?[0-9]+)?)\s*,\s*"([a-z])"\s*,\s*(".*")\s*\]$"#).unwrap();
+        for (index, line) in lines.enumerate() {
+            let line: String = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let groups = match event_regex.captures(line.trim()) {
+                Some(groups) => groups,
+                None => return Err(invalid_data(format!("bad event at line {}", index + 2).as_str())),
+            };
+            if &groups[2] != "o" {
+                continue;
+            }
+            let data: String = match parse_json_string(&groups[3]) {
+                Some(data) => data,
+                None => return Err(invalid_data(format!("bad event data at line {}", index + 2).as_str())),
+            };
+            cast.events.push(CastEvent {
+                time: groups[1].parse::<f64>().unwrap_or(0.0),
+                data,
+            });
+        }
+        Ok(cast)
+    }
+
+    /// ### replay
+    ///
+    /// Write the events to `writer`, waiting between each of them as it happened during the recording.
+    /// `speed` is the playback speed multiplier
+    pub fn replay(&self, writer: &mut dyn Write, speed: f64) -> io::Result<()> {
+        let started: Instant = Instant::now();
+        for event in self.events.iter() {
+            let at: Duration = Duration::from_secs_f64(event.time / speed);
+            let elapsed: Duration = started.elapsed();
+            if at > elapsed {
+                sleep(at - elapsed);
+            }
+            writer.write_all(event.data.as_bytes())?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// ### parse_header
+    ///
+    /// Parse cast header line
+    fn parse_header(header: &str) -> io::Result<Cast> {
+        let version: Option<usize> = header_number(header, "version");
+        if version != Some(CAST_VERSION as usize) {
+            return Err(invalid_data("unsupported cast version"));
+        }
+        match (header_number(header, "width"), header_number(header, "height")) {
+            (Some(width), Some(height)) => Ok(Cast {
+                width,
+                height,
+                events: Vec::new(),
+            }),
+            _ => Err(invalid_data("bad cast header")),
+        }
+    }
+}
+
+/// ### header_number
+///
+/// Get a number from the header object
+fn header_number(header: &str, key: &str) -> Option<usize> {
+    let re: Regex = Regex::new(format!(r#""{}"\s*:\s*([0-9]+)"#, key).as_str()).unwrap();
+    re.captures(header).and_then(|groups| groups[1].parse::<usize>().ok())
+}
+
+/// ### parse_json_string
+///
+/// Parse a quoted and escaped JSON string
+fn parse_json_string(s: &str) -> Option<String> {
+    let mut chars = s.strip_prefix('"')?.strip_suffix('"')?.chars();
+    let mut out: String = String::with_capacity(s.len());
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '"' => out.push('"'),
+            '\\' => out.push('\\'),
+            '/' => out.push('/'),
+            'b' => out.push('\x08'),
+            'f' => out.push('\x0c'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            'u' => {
+                let mut code: u32 = parse_hex4(&mut chars)?;
+                //Surrogate pair
+                if (0xD800..0xDC00).contains(&code) {
+                    if chars.next()? != '\\' || chars.next()? != 'u' {
+                        return None;
+                    }
+                    let low: u32 = parse_hex4(&mut chars)?;
+                    code = 0x10000 + ((code - 0xD800) << 10) + (low.checked_sub(0xDC00)? & 0x3FF);
+                }
+                out.push(std::char::from_u32(code)?);
+            }
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+/// ### parse_hex4
+///
+/// Parse the 4 hex digits of a `\u` escape
+fn parse_hex4(chars: &mut std::str::Chars) -> Option<u32> {
+    let digits: String = chars.take(4).collect();
+    if digits.len() != 4 {
+        return None;
+    }
+    u32::from_str_radix(digits.as_str(), 16).ok()
+}
+
+/// ### invalid_data
+///
+/// Make an invalid data io error
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::io::Read;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_utils_recorder_record_and_read() {
+        let tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        let mut recorder: Recorder = Recorder::create(tmpfile.path(), 120, 40).unwrap();
+        recorder.record("user@host:~$ ").unwrap();
+        recorder.record("лс\n").unwrap();
+        recorder.record("\x1b[31mошибка\x1b[0m \"quoted\" \\ \t\r\n").unwrap();
+        drop(recorder);
+        //Check header
+        let mut content: String = String::new();
+        File::open(tmpfile.path()).unwrap().read_to_string(&mut content).unwrap();
+        let header: &str = content.lines().next().unwrap();
+        assert!(header.starts_with("{\"version\": 2, \"width\": 120, \"height\": 40, \"timestamp\": "));
+        assert_eq!(content.lines().count(), 4);
+        //Read cast
+        let cast: Cast = Cast::read(tmpfile.path()).unwrap();
+        assert_eq!(cast.width, 120);
+        assert_eq!(cast.height, 40);
+        assert_eq!(
+            cast.events.iter().map(|e| e.data.as_str()).collect::<Vec<&str>>(),
+            vec!["user@host:~$ ", "лс\n", "\x1b[31mошибка\x1b[0m \"quoted\" \\ \t\r\n"]
+        );
+        //Events are sorted by time
+        assert!(cast.events.windows(2).all(|w| w[0].time <= w[1].time));
+    }
+
+    #[test]
+    fn test_utils_recorder_read_cast() {
+        let mut tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        write!(
+            tmpfile,
+            "{{\"version\": 2, \"width\": 80, \"height\": 24}}\n[0.5, \"o\", \"a\\u00e9\\ud83d\\ude00\"]\n[0.7, \"i\", \"b\"]\n\n[1.0, \"o\", \"c\\/\"]\n"
+        )
+        .unwrap();
+        let cast: Cast = Cast::read(tmpfile.path()).unwrap();
+        assert_eq!(
+            cast.events,
+            vec![
+                CastEvent { time: 0.5, data: String::from("aé😀") },
+                CastEvent { time: 1.0, data: String::from("c/") },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_utils_recorder_read_bad_cast() {
+        let mut tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        assert!(Cast::read(tmpfile.path()).is_err()); //Empty
+        writeln!(tmpfile, "{{\"version\": 1, \"width\": 80, \"height\": 24}}").unwrap();
+        assert!(Cast::read(tmpfile.path()).is_err()); //Bad version
+        let mut tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        write!(tmpfile, "{{\"version\": 2, \"width\": 80, \"height\": 24}}\n[foo]\n").unwrap();
+        assert!(Cast::read(tmpfile.path()).is_err()); //Bad event
+        let mut tmpfile: NamedTempFile = NamedTempFile::new().unwrap();
+        write!(tmpfile, "{{\"version\": 2, \"width\": 80, \"height\": 24}}\n[0.1, \"o\", \"\\x\"]\n").unwrap();
+        assert!(Cast::read(tmpfile.path()).is_err()); //Bad escape
+        assert!(Cast::read(Path::new("/this/file/does/not/exist.cast")).is_err());
+    }
+
+    #[test]
+    fn test_utils_recorder_replay() {
+        let cast: Cast = Cast {
+            width: 80,
+            height: 24,
+            events: vec![
+                CastEvent { time: 0.0, data: String::from("$ ") },
+                CastEvent { time: 0.4, data: String::from("привет\n") },
+            ],
+        };
+        let mut output: Vec<u8> = Vec::new();
+        let t_start: Instant = Instant::now();
+        cast.replay(&mut output, 2.0).unwrap();
+        //Speed multiplier: 0.4 seconds at 2x take 0.2 seconds
+        assert!(t_start.elapsed() >= Duration::from_millis(200));
+        assert!(t_start.elapsed() < Duration::from_millis(400));
+        assert_eq!(String::from_utf8(output).unwrap(), String::from("$ привет\n"));
+    }
+}
@@ -0,0 +1,294 @@
+//! ## Terminal
+//!
+//! `terminal` provides the size of the terminal to the features which depend on it. The size is resolved
+//! through a chain (ioctl, `$COLUMNS`/`$LINES`, the configured `assume_width`, 80x24), so it is always known,
+//! and it is cached until the terminal is resized
+
+/*
+*
+*   Copyright (C) 2020 Christian Visintin - christian.visintin1997@gmail.com
+*
+* 	This file is part of "Pyc"
+*
+*   Pyc is free software: you can redistribute it and/or modify
+*   it under the terms of the GNU General Public License as published by
+*   the Free Software Foundation, either version 3 of the License, or
+*   (at your option) any later version.
+*
+*   Pyc is distributed in the hope that it will be useful,
+*   but WITHOUT ANY WARRANTY; without even the implied warranty of
+*   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+*   GNU General Public License for more details.
+*
+*   You should have received a copy of the GNU General Public License
+*   along with Pyc.  If not, see <http://www.gnu.org/licenses/>.
+*
+*/
+
+extern crate nix;
+
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet};
+use std::env;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use super::console;
+
+pub const DEFAULT_COLUMNS: usize = 80;
+pub const DEFAULT_ROWS: usize = 24;
+
+lazy_static! {
+    static ref CACHED_SIZE: Mutex<Option<TerminalSize>> = Mutex::new(None); //Last resolved size
+    static ref ASSUME_WIDTH: Mutex<Option<usize>> = Mutex::new(None); //`assume_width` set in configuration
+}
+static RESIZED: AtomicBool = AtomicBool::new(false); //Set by the SIGWINCH handler
+static FORWARD_RESIZE: AtomicBool = AtomicBool::new(false); //Set by the SIGWINCH handler; the new size must be sent to the running command
+
+/// ### SizeSource
+///
+/// SizeSource describes which step of the chain the terminal size has been resolved by
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub enum SizeSource {
+    Ioctl,
+    Environment,
+    Config,
+    Default,
+}
+
+/// ### TerminalSize
+///
+/// TerminalSize contains the amount of columns and rows of the terminal
+#[derive(Copy, Clone, PartialEq, std::fmt::Debug)]
+pub struct TerminalSize {
+    pub columns: usize,
+    pub rows: usize,
+    pub source: SizeSource,
+}
+
+/// ### TerminalInfo
+///
+/// TerminalInfo resolves the terminal size; the queries can be replaced in tests to simulate their failures
+pub struct TerminalInfo {
+    query: fn() -> Option<(usize, usize)>, //Returns the size reported by the terminal driver
+    var: fn(&str) -> Option<String>,       //Returns an environment variable
+    assume_width: Option<usize>,
+}
+
+impl TerminalInfo {
+    /// ### new
+    ///
+    /// Instantiates a TerminalInfo which queries the terminal of stdout and the environment
+    pub fn new(assume_width: Option<usize>) -> TerminalInfo {
+        TerminalInfo {
+            query: console::terminal_size,
+            var: |name| env::var(name).ok(),
+            assume_width,
+        }
+    }
+
+    /// ### resolve
+    ///
+    /// Resolve the terminal size: the size reported by the terminal, then `$COLUMNS` (and `$LINES`),
+    /// then `assume_width`, finally 80x24. Rows which can't be determined are 24
+    pub fn resolve(&self) -> TerminalSize {
+        if let Some((columns, rows)) = (self.query)() {
+            return TerminalSize {
+                columns,
+                rows,
+                source: SizeSource::Ioctl,
+            };
+        }
+        let rows: usize = self.dimension("LINES").unwrap_or(DEFAULT_ROWS);
+        if let Some(columns) = self.dimension("COLUMNS") {
+            return TerminalSize {
+                columns,
+                rows,
+                source: SizeSource::Environment,
+            };
+        }
+        match self.assume_width {
+            Some(columns) if columns > 0 => TerminalSize {
+                columns,
+                rows,
+                source: SizeSource::Config,
+            },
+            _ => TerminalSize {
+                columns: DEFAULT_COLUMNS,
+                rows,
+                source: SizeSource::Default,
+            },
+        }
+    }
+
+    /// ### dimension
+    ///
+    /// Get a positive number from the environment variable
+    fn dimension(&self, name: &str) -> Option<usize> {
+        match (self.var)(name).and_then(|value| value.trim().parse::<usize>().ok()) {
+            Some(value) if value > 0 => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// ### size
+///
+/// Returns the size of the terminal. The size is resolved once and then cached, until the terminal is resized
+/// (see `watch_resize`) or `assume_width` changes
+pub fn size() -> TerminalSize {
+    let mut cached = CACHED_SIZE.lock().unwrap_or_else(|err| err.into_inner());
+    if RESIZED.swap(false, Ordering::SeqCst) {
+        *cached = None;
+    }
+    if let Some(size) = *cached {
+        return size;
+    }
+    let assume_width: Option<usize> = *ASSUME_WIDTH.lock().unwrap_or_else(|err| err.into_inner());
+    let size: TerminalSize = TerminalInfo::new(assume_width).resolve();
+    *cached = Some(size);
+    size
+}
+
+/// ### set_assume_width
+///
+/// Set the width used when neither the terminal nor the environment report it
+pub fn set_assume_width(columns: Option<usize>) {
+    *ASSUME_WIDTH.lock().unwrap_or_else(|err| err.into_inner()) = columns;
+    invalidate();
+}
+
+/// ### invalidate
+///
+/// Resolve the size again the next time it's requested
+pub fn invalidate() {
+    *CACHED_SIZE.lock().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+/// ### watch_resize
+///
+/// Install the SIGWINCH handler, which makes the size to be resolved again when the terminal is resized
+pub fn watch_resize() {
+    let action: SigAction = SigAction::new(SigHandler::Handler(report_resize), SaFlags::SA_RESTART, SigSet::empty());
+    let _ = unsafe { signal::sigaction(signal::Signal::SIGWINCH, &action) };
+}
+
+/// ### take_resize
+///
+/// Returns whether the terminal has been resized since the last call
+pub fn take_resize() -> bool {
+    FORWARD_RESIZE.swap(false, Ordering::SeqCst)
+}
+
+/// ### window_size
+///
+/// Returns the window size of a terminal as (columns, rows), if the file descriptor is a terminal which reports it
+pub fn window_size(fd: RawFd) -> Option<(usize, usize)> {
+    let mut size: nix::libc::winsize = nix::libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    match unsafe { nix::libc::ioctl(fd, nix::libc::TIOCGWINSZ, &mut size) } {
+        0 if size.ws_col > 0 && size.ws_row > 0 => Some((size.ws_col as usize, size.ws_row as usize)),
+        _ => None,
+    }
+}
+
+/// ### set_window_size
+///
+/// Set the window size of a terminal; the processes of its foreground process group get SIGWINCH
+pub fn set_window_size(fd: RawFd, columns: usize, rows: usize) -> io::Result<()> {
+    let size: nix::libc::winsize = nix::libc::winsize {
+        ws_row: rows as u16,
+        ws_col: columns as u16,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    match unsafe { nix::libc::ioctl(fd, nix::libc::TIOCSWINSZ, &size) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// ### report_resize
+///
+/// SIGWINCH handler
+extern "C" fn report_resize(_: i32) {
+    RESIZED.store(true, Ordering::SeqCst);
+    FORWARD_RESIZE.store(true, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn terminal(query: fn() -> Option<(usize, usize)>, var: fn(&str) -> Option<String>, assume_width: Option<usize>) -> TerminalInfo {
+        TerminalInfo {
+            query,
+            var,
+            assume_width,
+        }
+    }
+
+    #[test]
+    fn test_utils_terminal_chain() {
+        //Terminal driver
+        let info: TerminalInfo = terminal(|| Some((120, 40)), |_| Some(String::from("100")), Some(90));
+        assert_eq!(info.resolve(), TerminalSize { columns: 120, rows: 40, source: SizeSource::Ioctl });
+        //Ioctl fails: environment
+        let info: TerminalInfo = terminal(
+            || None,
+            |name| match name {
+                "COLUMNS" => Some(String::from("100")),
+                "LINES" => Some(String::from("30")),
+                _ => None,
+            },
+            Some(90),
+        );
+        assert_eq!(info.resolve(), TerminalSize { columns: 100, rows: 30, source: SizeSource::Environment });
+        //Environment not set or invalid: configuration
+        let info: TerminalInfo = terminal(|| None, |_| Some(String::from("wide")), Some(90));
+        assert_eq!(info.resolve(), TerminalSize { columns: 90, rows: 24, source: SizeSource::Config });
+        let info: TerminalInfo = terminal(|| None, |_| Some(String::from("0")), Some(90));
+        assert_eq!(info.resolve().source, SizeSource::Config);
+        //Nothing works: default
+        let info: TerminalInfo = terminal(|| None, |_| None, None);
+        assert_eq!(info.resolve(), TerminalSize { columns: DEFAULT_COLUMNS, rows: DEFAULT_ROWS, source: SizeSource::Default });
+        let info: TerminalInfo = terminal(|| None, |_| None, Some(0));
+        assert_eq!(info.resolve().source, SizeSource::Default);
+    }
+
+    #[test]
+    fn test_utils_terminal_size_cached() {
+        //The size is always known, and it's cached until it's invalidated
+        let size: TerminalSize = size();
+        assert!(size.columns > 0 && size.rows > 0);
+        assert_eq!(super::size(), size);
+        report_resize(0);
+        assert!(super::size().columns > 0);
+        assert!(!RESIZED.load(Ordering::SeqCst));
+        //The resize is reported once
+        assert!(take_resize());
+        assert!(!take_resize());
+    }
+
+    #[test]
+    fn test_utils_terminal_window_size() {
+        let pty: nix::pty::OpenptyResult = nix::pty::openpty(None, None).unwrap();
+        assert!(set_window_size(pty.master, 132, 43).is_ok());
+        assert_eq!(window_size(pty.slave), Some((132, 43)));
+        assert_eq!(window_size(pty.master), Some((132, 43)));
+        assert!(set_window_size(pty.master, 80, 24).is_ok());
+        assert_eq!(window_size(pty.slave), Some((80, 24)));
+        //Not a terminal
+        let file: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        assert_eq!(window_size(std::os::unix::io::AsRawFd::as_raw_fd(file.as_file())), None);
+        assert!(set_window_size(std::os::unix::io::AsRawFd::as_raw_fd(file.as_file()), 80, 24).is_err());
+        let _ = nix::unistd::close(pty.master);
+        let _ = nix::unistd::close(pty.slave);
+    }
+}